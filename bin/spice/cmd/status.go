@@ -17,9 +17,12 @@ limitations under the License.
 package cmd
 
 import (
+	"fmt"
+
 	"github.com/spf13/cobra"
 	"github.com/spiceai/spiceai/bin/spice/pkg/api"
 	"github.com/spiceai/spiceai/bin/spice/pkg/context"
+	"github.com/spiceai/spiceai/bin/spice/pkg/util"
 )
 
 var statusCmd = &cobra.Command{
@@ -30,10 +33,17 @@ spice status
 `,
 	Run: func(cmd *cobra.Command, args []string) {
 		rtcontext := context.NewContext()
-		err := api.WriteDataTable(rtcontext, "/v1/status", api.Service{})
+		status, err := api.GetRuntimeStatus(rtcontext)
 		if err != nil {
-			cmd.PrintErrln(err.Error())
+			cmd.PrintErrln(fmt.Errorf("Error fetching runtime information: %w", err).Error())
+			return
+		}
+
+		var table []interface{}
+		for _, s := range status.Connections {
+			table = append(table, s)
 		}
+		util.WriteTable(table)
 	},
 }
 
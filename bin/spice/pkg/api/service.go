@@ -16,8 +16,20 @@ limitations under the License.
 
 package api
 
+import (
+	"github.com/spiceai/spiceai/bin/spice/pkg/context"
+)
+
 type Service struct {
 	Name     string `json:"name,omitempty" csv:"name" yaml:"name,omitempty"`
 	Endpoint string `json:"endpoint,omitempty" csv:"endpoint" yaml:"endpoint,omitempty"`
 	Status   string `json:"status,omitempty" csv:"status" yaml:"status,omitempty"`
 }
+
+type RuntimeStatus struct {
+	Connections []Service `json:"connections"`
+}
+
+func GetRuntimeStatus(rtcontext *context.RuntimeContext) (RuntimeStatus, error) {
+	return doRuntimeApiRequest[RuntimeStatus](rtcontext, GET, "/v1/status")
+}
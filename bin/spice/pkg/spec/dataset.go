@@ -35,6 +35,7 @@ type DatasetSpec struct {
 	Name         string            `json:"name,omitempty" csv:"name" yaml:"name,omitempty"`
 	Description  string            `json:"description,omitempty" csv:"description" yaml:"description,omitempty"`
 	Params       map[string]string `json:"params,omitempty" csv:"params" yaml:"params,omitempty"`
+	Secret       string            `json:"secret,omitempty" csv:"secret" yaml:"secret,omitempty"`
 	Acceleration *AccelerationSpec `json:"acceleration,omitempty" csv:"acceleration" yaml:"acceleration,omitempty"`
 }
 
@@ -21,20 +21,27 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use app::{App, AppBuilder};
 use clap::Parser;
 use flightrepl::ReplConfig;
 use futures::future::join_all;
 use futures::Future;
+use metrics_exporter_prometheus::PrometheusHandle;
 use runtime::config::Config as RuntimeConfig;
 
+use fleet::FleetExtensionFactory;
 use runtime::datasets_health_monitor::DatasetsHealthMonitor;
 use runtime::podswatcher::PodsWatcher;
+use runtime::validation::Diagnostic;
 use runtime::{extension::ExtensionFactory, Runtime};
 use snafu::prelude::*;
 use spice_cloud::SpiceExtensionFactory;
 
+/// How long the last metrics can take to be pushed on shutdown.
+const METRICS_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Unable to construct spice app: {source}"))]
@@ -43,6 +50,9 @@ pub enum Error {
     #[snafu(display("Unable to start Spice Runtime servers: {source}"))]
     UnableToStartServers { source: runtime::Error },
 
+    #[snafu(display("Unable to initialize audit log: {source}"))]
+    UnableToInitializeAuditLog { source: runtime::Error },
+
     #[snafu(display("Failed to load dataset: {source}"))]
     UnableToLoadDataset { source: runtime::Error },
 
@@ -73,10 +83,18 @@ pub struct Args {
     #[arg(long, value_name = "BIND_ADDRESS", help_heading = "Metrics")]
     pub metrics: Option<SocketAddr>,
 
+    /// Export traces to an OpenTelemetry collector over OTLP/gRPC, i.e. `http://localhost:4317`.
+    #[arg(long, value_name = "URL", help_heading = "Tracing")]
+    pub tracing_otlp_endpoint: Option<String>,
+
     /// Print the version and exit.
     #[arg(long)]
     pub version: bool,
 
+    /// Validate the spicepod in the current directory, print any problems found and exit.
+    #[arg(long)]
+    pub validate: bool,
+
     /// All runtime related arguments
     #[clap(flatten)]
     pub runtime: RuntimeConfig,
@@ -89,7 +107,19 @@ pub struct Args {
     pub repl_config: ReplConfig,
 }
 
-pub async fn run(args: Args) -> Result<()> {
+/// Validates the spicepod in the current directory without loading any of its components.
+pub async fn validate() -> Vec<Diagnostic> {
+    let current_dir = env::current_dir().unwrap_or(PathBuf::from("."));
+    let app = AppBuilder::build_from_filesystem_path(current_dir.clone()).ok();
+
+    let mut rt = Runtime::new(app, Arc::new(vec![])).await;
+    rt.with_pods_watcher(PodsWatcher::new(current_dir));
+    rt.load_secrets().await;
+
+    rt.validate().await
+}
+
+pub async fn run(args: Args, metrics_handle: Option<PrometheusHandle>) -> Result<()> {
     let current_dir = env::current_dir().unwrap_or(PathBuf::from("."));
     let pods_watcher = PodsWatcher::new(current_dir.clone());
     let app: Option<App> = match AppBuilder::build_from_filesystem_path(current_dir.clone())
@@ -113,6 +143,14 @@ pub async fn run(args: Args) -> Result<()> {
         }
     }
 
+    if cfg!(feature = "fleet") {
+        if let Some(app) = &app {
+            if let Some(manifest) = app.extensions.get("fleet") {
+                extension_factories.push(Box::new(FleetExtensionFactory::new(manifest.clone())));
+            }
+        }
+    }
+
     let mut rt: Runtime = Runtime::new(app, Arc::new(extension_factories)).await;
 
     // mutable reference
@@ -120,13 +158,62 @@ pub async fn run(args: Args) -> Result<()> {
 
     rt.with_datasets_health_monitor(DatasetsHealthMonitor::new(Arc::clone(&rt.datafusion().ctx)));
 
+    if let Some(metrics_handle) = metrics_handle {
+        rt.with_metrics_handle(metrics_handle);
+    }
+
     rt.start_datasets_health_monitor();
 
+    // Secrets are loaded before the servers start, as the TLS certificate can be stored in a secret.
+    rt.load_secrets().await;
+
+    // Report every problem with the spicepod up front, rather than as each component fails to load.
+    runtime::validation::log_diagnostics(&rt.validate().await);
+
+    // Auditing must be in place before the servers accept any requests.
+    rt.init_audit_log()
+        .await
+        .context(UnableToInitializeAuditLogSnafu)?;
+
+    if let Err(err) = rt.init_inference_log().await {
+        tracing::warn!("{err}");
+    }
+
+    // Without the configured key, masks are hashed with a random key, so they differ from other runtimes.
+    if let Err(err) = rt.init_mask_key().await {
+        tracing::warn!("{err}");
+    }
+
     let cloned_rt = rt.clone();
     let server_thread =
         tokio::spawn(async move { cloned_rt.start_servers(args.runtime, args.metrics).await });
 
-    rt.load_secrets().await;
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_secret_rotation().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_jobs().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_cluster().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_alerts().await });
+
+    let cloned_rt = rt.clone();
+    let otlp_metrics = tokio::spawn(async move { cloned_rt.start_otlp_metrics().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_expectation_checks().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_pii_detection().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_anomaly_detection().await });
+
+    let cloned_rt = rt.clone();
+    tokio::spawn(async move { cloned_rt.start_model_version_checks().await });
 
     rt.start_extensions().await;
 
@@ -152,8 +239,15 @@ pub async fn run(args: Args) -> Result<()> {
     ];
 
     if cfg!(feature = "models") {
-        let mut v: Vec<Pin<Box<dyn Future<Output = ()>>>> =
-            vec![Box::pin(rt.load_models()), Box::pin(rt.load_llms())];
+        let mut v: Vec<Pin<Box<dyn Future<Output = ()>>>> = vec![
+            Box::pin(rt.load_models()),
+            Box::pin(rt.load_llms()),
+            Box::pin(async {
+                if let Err(err) = rt.init_deployment_results().await {
+                    tracing::warn!("Creating internal deployment results table: {err}");
+                };
+            }),
+        ];
 
         futures.append(&mut v);
     }
@@ -165,10 +259,21 @@ pub async fn run(args: Args) -> Result<()> {
         },
     }
 
-    match server_thread.await {
+    let result = match server_thread.await {
         Ok(ok) => ok.context(UnableToStartServersSnafu),
         Err(_) => Err(Error::GenericError {
             reason: "Unable to start spiced".into(),
         }),
+    };
+
+    // The last metrics are pushed once the runtime has drained.
+    if runtime::shutdown::is_draining()
+        && tokio::time::timeout(METRICS_FLUSH_TIMEOUT, otlp_metrics)
+            .await
+            .is_err()
+    {
+        tracing::warn!("Unable to push the last metrics before shutting down");
     }
+
+    result
 }
@@ -14,17 +14,39 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::net::SocketAddr;
-
 use clap::Parser;
-use metrics_exporter_prometheus::PrometheusBuilder;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use opentelemetry::{trace::TraceError, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{self, Tracer},
+    Resource,
+};
 use tokio::runtime::Runtime;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
 
 fn main() {
     let args = spiced::Args::parse();
 
-    if let Err(err) = init_tracing() {
+    // Sets KRB5CCNAME while the process has a single thread, before the Tokio runtime starts its workers.
+    if let Err(err) = runtime::kerberos::init() {
+        eprintln!("Unable to initialize Kerberos: {err}");
+        std::process::exit(1);
+    }
+
+    let tokio_runtime = match Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("Unable to start Tokio runtime: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    // The OTLP exporter runs its batch span processor on the Tokio runtime.
+    let _runtime_guard = tokio_runtime.enter();
+
+    if let Err(err) = init_tracing(args.tracing_otlp_endpoint.as_deref()) {
         eprintln!("Unable to initialize tracing: {err}");
         std::process::exit(1);
     }
@@ -49,13 +71,18 @@ fn main() {
         return;
     }
 
-    let tokio_runtime = match Runtime::new() {
-        Ok(runtime) => runtime,
-        Err(err) => {
-            tracing::error!("Unable to start Tokio runtime: {err}");
-            std::process::exit(1);
+    if args.validate {
+        let diagnostics = tokio_runtime.block_on(spiced::validate());
+        if diagnostics.is_empty() {
+            println!("Spicepod is valid");
+            return;
         }
-    };
+
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+        std::process::exit(1);
+    }
 
     if args.repl {
         if let Err(e) = tokio_runtime.block_on(flightrepl::run(args.repl_config)) {
@@ -69,39 +96,70 @@ fn main() {
     if let Err(err) = tokio_runtime.block_on(start_runtime(args)) {
         tracing::error!("Spice Runtime error: {err}");
     }
+
+    // Flushes the spans that haven't been exported yet.
+    opentelemetry::global::shutdown_tracer_provider();
 }
 
 async fn start_runtime(args: spiced::Args) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(metrics_socket) = args.metrics {
-        init_metrics(metrics_socket)?;
-    }
+    // Metrics are recorded even without `--metrics`, as they can also be pushed with `runtime.otlp_metrics`.
+    let metrics_handle = Some(init_metrics()?);
 
-    spiced::run(args).await?;
+    spiced::run(args, metrics_handle).await?;
     Ok(())
 }
 
-fn init_tracing() -> Result<(), Box<dyn std::error::Error>> {
+fn init_tracing(otlp_endpoint: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     let filter = if let Ok(env_log) = std::env::var("SPICED_LOG") {
         EnvFilter::new(env_log)
     } else {
-        EnvFilter::new("spiced=INFO,runtime=INFO,secrets=INFO,sql_provider_datafusion=INFO,data_components=INFO,cache=INFO,extensions=INFO,spice_cloud=INFO")
+        EnvFilter::new("spiced=INFO,runtime=INFO,secrets=INFO,sql_provider_datafusion=INFO,data_components=INFO,cache=INFO,extensions=INFO,spice_cloud=INFO,fleet=INFO")
     };
 
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(filter)
-        .with_ansi(true)
-        .finish();
+    let otlp_layer = match otlp_endpoint {
+        Some(endpoint) => {
+            Some(tracing_opentelemetry::layer().with_tracer(init_otlp_tracer(endpoint)?))
+        }
+        None => None,
+    };
+
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_ansi(true))
+        .with(otlp_layer);
     tracing::subscriber::set_global_default(subscriber)?;
 
+    if let Some(endpoint) = otlp_endpoint {
+        tracing::info!("Exporting traces to {endpoint}");
+    }
+
     Ok(())
 }
 
-fn init_metrics(socket_addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-    let builder = PrometheusBuilder::new().with_http_listener(socket_addr);
+fn init_otlp_tracer(endpoint: &str) -> Result<Tracer, TraceError> {
+    // Continue the traces of callers that send a `traceparent` header.
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_resource(Resource::new(vec![KeyValue::new("service.name", "spiced")])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    Ok(tracer)
+}
 
-    // This needs to run inside a Tokio runtime.
-    builder.install()?;
-    tracing::info!("Metrics listening on {socket_addr}");
+/// Installs the Prometheus recorder. The runtime serves the recorded metrics on the `--metrics` address, and pushes
+/// them to the collector of `runtime.otlp_metrics`.
+fn init_metrics() -> Result<PrometheusHandle, Box<dyn std::error::Error>> {
+    let handle = PrometheusBuilder::new().install_recorder()?;
 
-    Ok(())
+    Ok(handle)
 }
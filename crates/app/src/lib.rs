@@ -16,21 +16,29 @@ limitations under the License.
 
 #![allow(clippy::missing_errors_doc)]
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
 use snafu::prelude::*;
 use spicepod::{
     component::{
+        alert::Alert,
         dataset::Dataset,
+        deployment::Deployment,
         embeddings::Embeddings,
         extension::Extension,
+        function::Function,
+        job::Job,
         llms::Llm,
         model::Model,
         runtime::{ResultsCache, Runtime},
         secrets::{Secrets, SpiceSecretStore},
+        test::Test,
         view::View,
     },
-    Spicepod,
+    reader, Spicepod,
 };
 
 #[derive(Debug, PartialEq)]
@@ -45,12 +53,22 @@ pub struct App {
 
     pub views: Vec<View>,
 
+    pub functions: Vec<Function>,
+
     pub models: Vec<Model>,
 
     pub embeddings: Vec<Embeddings>,
 
     pub llms: Vec<Llm>,
 
+    pub jobs: Vec<Job>,
+
+    pub alerts: Vec<Alert>,
+
+    pub tests: Vec<Test>,
+
+    pub deployments: Vec<Deployment>,
+
     pub spicepods: Vec<Spicepod>,
 
     pub runtime: Runtime,
@@ -63,8 +81,32 @@ pub enum Error {
         source: spicepod::Error,
         path: PathBuf,
     },
+
+    #[snafu(display("The namespace {namespace} is reserved or defined more than once"))]
+    DuplicateNamespace { namespace: String },
+
+    #[snafu(display(
+        "The namespaces {namespace} and {other} can't both be defined, as the secrets of {namespace} are read with the prefix {namespace}_, which also starts the secrets of {other}"
+    ))]
+    OverlappingNamespaces { namespace: String, other: String },
+
+    #[snafu(display(
+        "{name} in namespace {namespace} can't name a catalog, it is registered in the {namespace} catalog"
+    ))]
+    NamespacedComponentHasCatalog { namespace: String, name: String },
+
+    #[snafu(display(
+        "The spicepod of namespace {namespace} defines {components}, which can't be loaded into a namespace. Only datasets and views are loaded into namespaces"
+    ))]
+    UnsupportedNamespacedComponents {
+        namespace: String,
+        components: String,
+    },
 }
 
+/// The catalog of the datasets of the root spicepod and its dependencies, which namespaces can't use.
+const DEFAULT_CATALOG: &str = "spice";
+
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 pub struct AppBuilder {
@@ -73,9 +115,14 @@ pub struct AppBuilder {
     extensions: HashMap<String, Extension>,
     datasets: Vec<Dataset>,
     views: Vec<View>,
+    functions: Vec<Function>,
     models: Vec<Model>,
     llms: Vec<Llm>,
     embeddings: Vec<Embeddings>,
+    jobs: Vec<Job>,
+    alerts: Vec<Alert>,
+    tests: Vec<Test>,
+    deployments: Vec<Deployment>,
     spicepods: Vec<Spicepod>,
     runtime: Runtime,
 }
@@ -88,9 +135,14 @@ impl AppBuilder {
             extensions: HashMap::new(),
             datasets: vec![],
             views: vec![],
+            functions: vec![],
             models: vec![],
             llms: vec![],
             embeddings: vec![],
+            jobs: vec![],
+            alerts: vec![],
+            tests: vec![],
+            deployments: vec![],
             spicepods: vec![],
             runtime: Runtime::default(),
         }
@@ -102,9 +154,14 @@ impl AppBuilder {
         self.extensions.extend(spicepod.extensions.clone());
         self.datasets.extend(spicepod.datasets.clone());
         self.views.extend(spicepod.views.clone());
+        self.functions.extend(spicepod.functions.clone());
         self.models.extend(spicepod.models.clone());
         self.llms.extend(spicepod.llms.clone());
         self.embeddings.extend(spicepod.embeddings.clone());
+        self.jobs.extend(spicepod.jobs.clone());
+        self.alerts.extend(spicepod.alerts.clone());
+        self.tests.extend(spicepod.tests.clone());
+        self.deployments.extend(spicepod.deployments.clone());
         self.spicepods.push(spicepod);
         self
     }
@@ -117,7 +174,10 @@ impl AppBuilder {
 
     #[must_use]
     pub fn with_secret_store(mut self, secret: SpiceSecretStore) -> AppBuilder {
-        self.secrets = Secrets { store: secret };
+        self.secrets = Secrets {
+            store: secret,
+            ..Secrets::default()
+        };
         self
     }
 
@@ -133,6 +193,12 @@ impl AppBuilder {
         self
     }
 
+    #[must_use]
+    pub fn with_function(mut self, function: Function) -> AppBuilder {
+        self.functions.push(function);
+        self
+    }
+
     #[must_use]
     pub fn with_model(mut self, model: Model) -> AppBuilder {
         self.models.push(model);
@@ -151,6 +217,30 @@ impl AppBuilder {
         self
     }
 
+    #[must_use]
+    pub fn with_job(mut self, job: Job) -> AppBuilder {
+        self.jobs.push(job);
+        self
+    }
+
+    #[must_use]
+    pub fn with_alert(mut self, alert: Alert) -> AppBuilder {
+        self.alerts.push(alert);
+        self
+    }
+
+    #[must_use]
+    pub fn with_test(mut self, test: Test) -> AppBuilder {
+        self.tests.push(test);
+        self
+    }
+
+    #[must_use]
+    pub fn with_deployment(mut self, deployment: Deployment) -> AppBuilder {
+        self.deployments.push(deployment);
+        self
+    }
+
     #[must_use]
     pub fn with_results_cache(mut self, results_cache: ResultsCache) -> AppBuilder {
         self.runtime.results_cache = results_cache;
@@ -165,26 +255,44 @@ impl AppBuilder {
             extensions: self.extensions,
             datasets: self.datasets,
             views: self.views,
+            functions: self.functions,
             models: self.models,
             llms: self.llms,
             embeddings: self.embeddings,
+            jobs: self.jobs,
+            alerts: self.alerts,
+            tests: self.tests,
+            deployments: self.deployments,
             spicepods: self.spicepods,
             runtime: self.runtime,
         }
     }
 
     pub fn build_from_filesystem_path(path: impl Into<PathBuf>) -> Result<App> {
+        Self::build_from_filesystem(&reader::StdFileSystem, path)
+    }
+
+    /// Builds the app of the spicepod in `path`, reading it and the spicepods it references from `fs`.
+    pub fn build_from_filesystem<T>(
+        fs: &impl reader::ReadableYaml<T>,
+        path: impl Into<PathBuf>,
+    ) -> Result<App> {
         let path = path.into();
-        let spicepod_root =
-            Spicepod::load(&path).context(UnableToLoadSpicepodSnafu { path: path.clone() })?;
+        let spicepod_root = Spicepod::load_from(fs, &path)
+            .context(UnableToLoadSpicepodSnafu { path: path.clone() })?;
         let secrets = spicepod_root.secrets.clone();
         let runtime = spicepod_root.runtime.clone();
         let extensions = spicepod_root.extensions.clone();
         let mut datasets: Vec<Dataset> = vec![];
         let mut views: Vec<View> = vec![];
+        let mut functions: Vec<Function> = vec![];
         let mut models: Vec<Model> = vec![];
         let mut llms: Vec<Llm> = vec![];
         let mut embeddings: Vec<Embeddings> = vec![];
+        let mut jobs: Vec<Job> = vec![];
+        let mut alerts: Vec<Alert> = vec![];
+        let mut tests: Vec<Test> = vec![];
+        let mut deployments: Vec<Deployment> = vec![];
 
         for dataset in &spicepod_root.datasets {
             datasets.push(dataset.clone());
@@ -194,6 +302,10 @@ impl AppBuilder {
             views.push(view.clone());
         }
 
+        for function in &spicepod_root.functions {
+            functions.push(function.clone());
+        }
+
         for model in &spicepod_root.models {
             models.push(model.clone());
         }
@@ -206,13 +318,29 @@ impl AppBuilder {
             embeddings.push(embedding.clone());
         }
 
+        for job in &spicepod_root.jobs {
+            jobs.push(job.clone());
+        }
+
+        for alert in &spicepod_root.alerts {
+            alerts.push(alert.clone());
+        }
+
+        for test in &spicepod_root.tests {
+            tests.push(test.clone());
+        }
+
+        for deployment in &spicepod_root.deployments {
+            deployments.push(deployment.clone());
+        }
+
         let root_spicepod_name = spicepod_root.name.clone();
         let mut spicepods: Vec<Spicepod> = vec![];
 
         for dependency in &spicepod_root.dependencies {
             let dependency_path = path.join("spicepods").join(dependency);
             let dependent_spicepod =
-                Spicepod::load(&dependency_path).context(UnableToLoadSpicepodSnafu {
+                Spicepod::load_from(fs, &dependency_path).context(UnableToLoadSpicepodSnafu {
                     path: &dependency_path,
                 })?;
             for dataset in &dependent_spicepod.datasets {
@@ -221,6 +349,9 @@ impl AppBuilder {
             for view in &dependent_spicepod.views {
                 views.push(view.clone());
             }
+            for function in &dependent_spicepod.functions {
+                functions.push(function.clone());
+            }
             for model in &dependent_spicepod.models {
                 models.push(model.clone());
             }
@@ -230,9 +361,69 @@ impl AppBuilder {
             for embedding in &dependent_spicepod.embeddings {
                 embeddings.push(embedding.clone());
             }
+            for job in &dependent_spicepod.jobs {
+                jobs.push(job.clone());
+            }
+            for alert in &dependent_spicepod.alerts {
+                alerts.push(alert.clone());
+            }
+
+            for test in &dependent_spicepod.tests {
+                tests.push(test.clone());
+            }
+            for deployment in &dependent_spicepod.deployments {
+                deployments.push(deployment.clone());
+            }
             spicepods.push(dependent_spicepod);
         }
 
+        if let Some((namespace, other)) =
+            overlapping_namespaces(runtime.namespaces.iter().map(|n| n.name.as_str()))
+        {
+            return OverlappingNamespacesSnafu { namespace, other }.fail();
+        }
+
+        let mut namespaces = HashSet::from([DEFAULT_CATALOG.to_string()]);
+        for namespace in &runtime.namespaces {
+            ensure!(
+                namespaces.insert(namespace.name.clone()),
+                DuplicateNamespaceSnafu {
+                    namespace: namespace.name.clone()
+                }
+            );
+
+            let namespace_path = path.join(&namespace.path);
+            let namespace_spicepod =
+                Spicepod::load_from(fs, &namespace_path).context(UnableToLoadSpicepodSnafu {
+                    path: &namespace_path,
+                })?;
+            for dataset in &namespace_spicepod.datasets {
+                let mut dataset = dataset.clone();
+                dataset.name = namespaced_name(&namespace.name, &dataset.name)?;
+                dataset.namespace = Some(namespace.name.clone());
+                datasets.push(dataset);
+            }
+            let components = unsupported_namespaced_components(&namespace_spicepod);
+            ensure!(
+                components.is_empty(),
+                UnsupportedNamespacedComponentsSnafu {
+                    namespace: namespace.name.clone(),
+                    components: components.join(", "),
+                }
+            );
+            for view in &namespace_spicepod.views {
+                let mut view = view.clone();
+                view.name = namespaced_name(&namespace.name, &view.name)?;
+                view.namespace = Some(namespace.name.clone());
+                // The SQL files of a namespace are relative to its spicepod.
+                view.sql_ref = view
+                    .sql_ref
+                    .map(|sql_ref| namespace_path.join(sql_ref).to_string_lossy().to_string());
+                views.push(view);
+            }
+            spicepods.push(namespace_spicepod);
+        }
+
         spicepods.push(spicepod_root);
 
         Ok(App {
@@ -241,11 +432,94 @@ impl AppBuilder {
             extensions,
             datasets,
             views,
+            functions,
             models,
             embeddings,
             llms,
+            jobs,
+            alerts,
+            tests,
+            deployments,
             spicepods,
             runtime,
         })
     }
 }
+
+/// The components of a namespace's spicepod other than datasets and views, which aren't scoped to the namespace.
+fn unsupported_namespaced_components(spicepod: &Spicepod) -> Vec<&'static str> {
+    [
+        ("dependencies", spicepod.dependencies.is_empty()),
+        ("functions", spicepod.functions.is_empty()),
+        ("models", spicepod.models.is_empty()),
+        ("llms", spicepod.llms.is_empty()),
+        ("embeddings", spicepod.embeddings.is_empty()),
+        ("jobs", spicepod.jobs.is_empty()),
+        ("alerts", spicepod.alerts.is_empty()),
+        ("tests", spicepod.tests.is_empty()),
+        ("deployments", spicepod.deployments.is_empty()),
+    ]
+    .into_iter()
+    .filter(|(_, is_empty)| !is_empty)
+    .map(|(component, _)| component)
+    .collect()
+}
+
+/// Two namespaces whose secrets could be read by each other: the secret `a_pg` of `team` and the secret `pg` of
+/// `team_a` are both read as `team_a_pg`, as the secrets of a namespace are prefixed with its name and `_`.
+fn overlapping_namespaces<'a>(
+    namespaces: impl Iterator<Item = &'a str> + Clone,
+) -> Option<(String, String)> {
+    namespaces.clone().find_map(|namespace| {
+        let prefix = format!("{namespace}_");
+        namespaces
+            .clone()
+            .find(|other| other.starts_with(&prefix))
+            .map(|other| (namespace.to_string(), other.to_string()))
+    })
+}
+
+/// Qualifies the name of a dataset or view of a namespace with its catalog, i.e. `orders` as `team_a.public.orders`
+/// and `sales.orders` as `team_a.sales.orders`.
+fn namespaced_name(namespace: &str, name: &str) -> Result<String> {
+    match name.split('.').count() {
+        1 => Ok(format!("{namespace}.public.{name}")),
+        2 => Ok(format!("{namespace}.{name}")),
+        _ => NamespacedComponentHasCatalogSnafu {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+        .fail(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualifies_namespaced_names() {
+        assert_eq!(
+            namespaced_name("team_a", "orders").ok(),
+            Some("team_a.public.orders".to_string())
+        );
+        assert_eq!(
+            namespaced_name("team_a", "sales.orders").ok(),
+            Some("team_a.sales.orders".to_string())
+        );
+        assert!(namespaced_name("team_a", "spice.public.orders").is_err());
+    }
+
+    #[test]
+    fn rejects_overlapping_namespaces() {
+        assert_eq!(
+            overlapping_namespaces(["team_a", "team"].into_iter()),
+            Some(("team".to_string(), "team_a".to_string()))
+        );
+        assert_eq!(
+            overlapping_namespaces(["team_a", "team_b"].into_iter()),
+            None
+        );
+        assert_eq!(overlapping_namespaces(["teams", "team"].into_iter()), None);
+    }
+}
@@ -18,6 +18,7 @@ use std::convert;
 use std::sync::Arc;
 
 use crate::arrow::map_data_type_to_array_builder_optional;
+use crate::unsupported_type::UnsupportedTypeAction;
 use arrow::array::ArrayBuilder;
 use arrow::array::ArrayRef;
 use arrow::array::BooleanBuilder;
@@ -29,6 +30,7 @@ use arrow::array::Float64Builder;
 use arrow::array::Int16Builder;
 use arrow::array::Int32Builder;
 use arrow::array::Int64Builder;
+use arrow::array::IntervalMonthDayNanoBuilder;
 use arrow::array::ListBuilder;
 use arrow::array::RecordBatch;
 use arrow::array::RecordBatchOptions;
@@ -37,6 +39,8 @@ use arrow::array::TimestampMillisecondBuilder;
 use arrow::datatypes::DataType;
 use arrow::datatypes::Date32Type;
 use arrow::datatypes::Field;
+use arrow::datatypes::IntervalMonthDayNanoType;
+use arrow::datatypes::IntervalUnit;
 use arrow::datatypes::Schema;
 use arrow::datatypes::TimeUnit;
 use bigdecimal::num_bigint::BigInt;
@@ -92,6 +96,12 @@ pub enum Error {
 
     #[snafu(display("No column name for index: {index}"))]
     NoColumnNameForIndex { index: usize },
+
+    #[snafu(display("Unsupported data type {data_type} for column {column_name}"))]
+    UnsupportedDataType {
+        column_name: String,
+        data_type: String,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -142,17 +152,40 @@ macro_rules! handle_primitive_array_type {
     }};
 }
 
-/// Converts Postgres Columns to Arrow Data Types
+/// Converts Postgres Columns to Arrow Data Types. The columns of unsupported types are left out or read as strings
+/// as `unsupported_type_action` says, with a warning that lists them.
 ///
 /// # Errors
 ///
-/// Returns an error if the Postgres column type is not supported
-pub fn columns_to_schema(cols: &[Column]) -> Result<Arc<Schema>> {
+/// Returns an error if a Postgres column type is not supported and `unsupported_type_action` is `Error`.
+pub fn columns_to_schema(
+    cols: &[Column],
+    unsupported_type_action: UnsupportedTypeAction,
+) -> Result<Arc<Schema>> {
     let mut arrow_fields: Vec<Option<Field>> = Vec::new();
+    let mut unsupported_columns = Vec::new();
 
     for column in cols {
         let column_name = column.name();
         let column_type = column.type_();
+        if !is_supported_type(column_type) {
+            match unsupported_type_action {
+                UnsupportedTypeAction::Error => {
+                    return UnsupportedDataTypeSnafu {
+                        column_name,
+                        data_type: column_type.to_string(),
+                    }
+                    .fail();
+                }
+                UnsupportedTypeAction::Warn => {}
+                UnsupportedTypeAction::String => {
+                    arrow_fields.push(Some(Field::new(column_name, DataType::Utf8, true)));
+                }
+            }
+            unsupported_columns.push(format!("{column_name} ({column_type})"));
+            continue;
+        }
+
         let data_type = map_column_type_to_data_type(column_type);
         match &data_type {
             Some(data_type) => {
@@ -164,17 +197,31 @@ pub fn columns_to_schema(cols: &[Column]) -> Result<Arc<Schema>> {
 
     let arrow_fields = arrow_fields.into_iter().flatten().collect::<Vec<Field>>();
 
+    if !unsupported_columns.is_empty() {
+        let unsupported_columns = unsupported_columns.join(", ");
+        if unsupported_type_action == UnsupportedTypeAction::String {
+            tracing::warn!(
+                "Reading the columns of unsupported types as strings: {unsupported_columns}"
+            );
+        } else {
+            tracing::warn!("Skipping the columns of unsupported types: {unsupported_columns}");
+        }
+    }
+
     Ok(Arc::new(Schema::new(arrow_fields)))
 }
 
 /// Converts Postgres `Row`s to an Arrow `RecordBatch`. Assumes that all rows have the same schema and
-/// sets the schema based on the first row.
+/// sets the schema based on the first row. The columns of unsupported types are handled as in [`columns_to_schema`].
 ///
 /// # Errors
 ///
 /// Returns an error if there is a failure in converting the rows to a `RecordBatch`.
 #[allow(clippy::too_many_lines)]
-pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
+pub fn rows_to_arrow(
+    rows: &[Row],
+    unsupported_type_action: UnsupportedTypeAction,
+) -> Result<RecordBatch> {
     let mut arrow_fields: Vec<Option<Field>> = Vec::new();
     let mut arrow_columns_builders: Vec<Option<Box<dyn ArrayBuilder>>> = Vec::new();
     let mut postgres_types: Vec<Type> = Vec::new();
@@ -185,7 +232,21 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
         for column in row.columns() {
             let column_name = column.name();
             let column_type = column.type_();
-            let data_type = map_column_type_to_data_type(column_type);
+            let data_type = if is_supported_type(column_type) {
+                map_column_type_to_data_type(column_type)
+            } else {
+                match unsupported_type_action {
+                    UnsupportedTypeAction::Error => {
+                        return UnsupportedDataTypeSnafu {
+                            column_name,
+                            data_type: column_type.to_string(),
+                        }
+                        .fail();
+                    }
+                    UnsupportedTypeAction::Warn => None,
+                    UnsupportedTypeAction::String => Some(DataType::Utf8),
+                }
+            };
             match &data_type {
                 Some(data_type) => {
                     arrow_fields.push(Some(Field::new(column_name, data_type.clone(), true)));
@@ -209,6 +270,20 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                 return NoArrowFieldForIndexSnafu { index: i }.fail();
             };
 
+            if !is_supported_type(postgres_type) {
+                if unsupported_type_action == UnsupportedTypeAction::String {
+                    handle_primitive_type!(
+                        builder,
+                        postgres_type.clone(),
+                        StringBuilder,
+                        UnsupportedAsString,
+                        row,
+                        i
+                    );
+                }
+                continue;
+            }
+
             match *postgres_type {
                 Type::INT2 => {
                     handle_primitive_type!(builder, Type::INT2, Int16Builder, i16, row, i);
@@ -388,6 +463,32 @@ pub fn rows_to_arrow(rows: &[Row]) -> Result<RecordBatch> {
                         None => builder.append_null(),
                     }
                 }
+                Type::INTERVAL => {
+                    let Some(builder) = builder else {
+                        return NoBuilderForIndexSnafu { index: i }.fail();
+                    };
+                    let Some(builder) = builder
+                        .as_any_mut()
+                        .downcast_mut::<IntervalMonthDayNanoBuilder>()
+                    else {
+                        return FailedToDowncastBuilderSnafu {
+                            postgres_type: format!("{postgres_type}"),
+                        }
+                        .fail();
+                    };
+                    let v: Option<IntervalFromSql> =
+                        row.try_get(i).context(FailedToGetRowValueSnafu {
+                            pg_type: Type::INTERVAL,
+                        })?;
+                    match v {
+                        Some(v) => builder.append_value(IntervalMonthDayNanoType::make_value(
+                            v.months,
+                            v.days,
+                            v.microseconds.saturating_mul(1_000),
+                        )),
+                        None => builder.append_null(),
+                    }
+                }
                 Type::INT2_ARRAY => handle_primitive_array_type!(
                     Type::INT2_ARRAY,
                     builder,
@@ -478,6 +579,7 @@ fn map_column_type_to_data_type(column_type: &Type) -> Option<DataType> {
             Some(DataType::Timestamp(TimeUnit::Millisecond, None))
         }
         Type::DATE => Some(DataType::Date32),
+        Type::INTERVAL => Some(DataType::Interval(IntervalUnit::MonthDayNano)),
         Type::UUID => Some(DataType::FixedSizeBinary(16)),
         Type::INT2_ARRAY => Some(DataType::List(Arc::new(Field::new(
             "item",
@@ -514,7 +616,78 @@ fn map_column_type_to_data_type(column_type: &Type) -> Option<DataType> {
             DataType::Boolean,
             true,
         )))),
-        _ => unimplemented!("Unsupported column type {:?}", column_type),
+        _ => None,
+    }
+}
+
+fn is_supported_type(column_type: &Type) -> bool {
+    *column_type == Type::NUMERIC || map_column_type_to_data_type(column_type).is_some()
+}
+
+/// A Postgres `INTERVAL`, which is sent as microseconds, days and months.
+struct IntervalFromSql {
+    months: i32,
+    days: i32,
+    microseconds: i64,
+}
+
+impl<'a> FromSql<'a> for IntervalFromSql {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let (Some(microseconds), Some(days), Some(months)) = (
+            raw.get(0..8).and_then(|b| b.try_into().ok()),
+            raw.get(8..12).and_then(|b| b.try_into().ok()),
+            raw.get(12..16).and_then(|b| b.try_into().ok()),
+        ) else {
+            return Err(format!("Invalid interval of {} bytes", raw.len()).into());
+        };
+
+        Ok(IntervalFromSql {
+            months: i32::from_be_bytes(months),
+            days: i32::from_be_bytes(days),
+            microseconds: i64::from_be_bytes(microseconds),
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::INTERVAL)
+    }
+}
+
+/// The value of a column of an unsupported type as a string: textual values, like JSON or enum labels, as is and
+/// binary values hex encoded, like Postgres prints PostGIS geometries.
+struct UnsupportedAsString(String);
+
+impl AsRef<str> for UnsupportedAsString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> FromSql<'a> for UnsupportedAsString {
+    fn from_sql(
+        ty: &Type,
+        raw: &'a [u8],
+    ) -> std::prelude::v1::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        // The binary format of JSONB is a version byte followed by the JSON text.
+        let raw = match *ty {
+            Type::JSONB => raw.get(1..).unwrap_or_default(),
+            _ => raw,
+        };
+        let text = std::str::from_utf8(raw)
+            .ok()
+            .filter(|text| !text.chars().any(|c| c.is_control() && !c.is_whitespace()));
+
+        Ok(UnsupportedAsString(match text {
+            Some(text) => text.to_string(),
+            None => raw.iter().map(|byte| format!("{byte:02x}")).collect(),
+        }))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
     }
 }
 
@@ -637,4 +810,19 @@ mod tests {
             .expect("Failed to run FromSql");
         assert_eq!(negative_result.inner, negative);
     }
+
+    #[test]
+    fn test_unsupported_as_string_from_sql() {
+        let jsonb = UnsupportedAsString::from_sql(&Type::JSONB, b"\x01{\"a\": 1}")
+            .expect("Failed to run FromSql");
+        assert_eq!(jsonb.as_ref(), "{\"a\": 1}");
+
+        let binary = UnsupportedAsString::from_sql(&Type::BYTEA, &[0x01, 0x01, 0x00, 0xff])
+            .expect("Failed to run FromSql");
+        assert_eq!(binary.as_ref(), "010100ff");
+
+        assert!(!is_supported_type(&Type::JSONB));
+        assert!(is_supported_type(&Type::INTERVAL));
+        assert!(is_supported_type(&Type::NUMERIC));
+    }
 }
@@ -13,9 +13,12 @@ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 See the License for the specific language governing permissions and
 limitations under the License.
 */
+use std::{str::FromStr, sync::Arc};
+
 use arrow::{
-    array::{array, Array, RecordBatch},
-    datatypes::{DataType, SchemaRef, TimeUnit},
+    array::{array, Array, ArrayRef, RecordBatch},
+    compute::cast,
+    datatypes::{DataType, IntervalMonthDayNanoType, IntervalUnit, SchemaRef, TimeUnit},
 };
 
 use bigdecimal_0_3_0::BigDecimal;
@@ -165,11 +168,24 @@ impl InsertBuilder {
         insert_stmt: &mut InsertStatement,
         record_batch: &RecordBatch,
     ) -> Result<()> {
+        // Intervals are inserted from their months, days and nanoseconds, whatever their unit.
+        let columns = record_batch
+            .columns()
+            .iter()
+            .map(|column| match column.data_type() {
+                DataType::Interval(IntervalUnit::YearMonth | IntervalUnit::DayTime) => {
+                    cast(column, &DataType::Interval(IntervalUnit::MonthDayNano))
+                }
+                _ => Ok(Arc::clone(column)),
+            })
+            .collect::<std::result::Result<Vec<ArrayRef>, _>>()
+            .map_err(|e| Error::FailedToCreateInsertStatement {
+                source: Box::new(e),
+            })?;
+
         for row in 0..record_batch.num_rows() {
             let mut row_values: Vec<SimpleExpr> = vec![];
-            for col in 0..record_batch.num_columns() {
-                let column = record_batch.column(col);
-
+            for column in &columns {
                 match column.data_type() {
                     DataType::Int8 => push_value!(row_values, column, row, Int8Array),
                     DataType::Int16 => push_value!(row_values, column, row, Int16Array),
@@ -197,6 +213,46 @@ impl InsertBuilder {
                             );
                         }
                     }
+                    DataType::Decimal256(_, scale) => {
+                        let array = column.as_any().downcast_ref::<array::Decimal256Array>();
+                        if let Some(valid_array) = array {
+                            if valid_array.is_null(row) {
+                                row_values.push(Keyword::Null.into());
+                                continue;
+                            }
+                            let value =
+                                format!("{}e{}", valid_array.value(row), -i64::from(*scale));
+                            match BigDecimal::from_str(&value) {
+                                Ok(value) => row_values.push(value.into()),
+                                Err(e) => {
+                                    return Result::Err(Error::FailedToCreateInsertStatement {
+                                        source: Box::new(e),
+                                    })
+                                }
+                            }
+                        }
+                    }
+                    DataType::Interval(IntervalUnit::MonthDayNano) => {
+                        let array = column
+                            .as_any()
+                            .downcast_ref::<array::IntervalMonthDayNanoArray>();
+                        if let Some(valid_array) = array {
+                            if valid_array.is_null(row) {
+                                row_values.push(Keyword::Null.into());
+                                continue;
+                            }
+                            // Postgres and Arrow both parse this, to the microsecond like Postgres intervals.
+                            let (months, days, nanoseconds) =
+                                IntervalMonthDayNanoType::to_parts(valid_array.value(row));
+                            row_values.push(
+                                format!(
+                                    "{months} months {days} days {} microseconds",
+                                    nanoseconds / 1_000
+                                )
+                                .into(),
+                            );
+                        }
+                    }
                     DataType::Date32 => {
                         let array = column.as_any().downcast_ref::<array::Date32Array>();
                         if let Some(valid_array) = array {
@@ -588,7 +644,11 @@ fn map_data_type_to_column_type(data_type: &DataType) -> ColumnType {
         DataType::Utf8 | DataType::LargeUtf8 => ColumnType::Text,
         DataType::Boolean => ColumnType::Boolean,
         #[allow(clippy::cast_sign_loss)] // This is safe because scale will never be negative
-        DataType::Decimal128(p, s) => ColumnType::Decimal(Some((u32::from(*p), *s as u32))),
+        DataType::Decimal128(p, s) | DataType::Decimal256(p, s) => {
+            ColumnType::Decimal(Some((u32::from(*p), *s as u32)))
+        }
+        // Stored as text where there is no interval type, i.e. in SQLite.
+        DataType::Interval(_) => ColumnType::custom("interval"),
         DataType::Timestamp(_unit, _time_zone) => ColumnType::Timestamp,
         DataType::Date32 | DataType::Date64 => ColumnType::Date,
         DataType::Time64(_unit) | DataType::Time32(_unit) => ColumnType::Time,
@@ -665,6 +725,32 @@ mod tests {
         assert_eq!(sql, "INSERT INTO \"users\" (\"id\", \"name\", \"age\") VALUES (1, 'a', 10), (2, 'b', 20), (3, 'c', 30), (1, 'a', 10), (2, 'b', 20), (3, 'c', 30)");
     }
 
+    #[test]
+    fn test_table_with_decimal256_and_interval() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("amount", DataType::Decimal256(40, 2), false),
+            Field::new("wait", DataType::Interval(IntervalUnit::DayTime), false),
+        ]));
+        let sql = CreateTableBuilder::new(Arc::clone(&schema), "orders").build_postgres();
+        assert_eq!(sql, "CREATE TABLE IF NOT EXISTS \"orders\" ( \"amount\" decimal(40, 2) NOT NULL, \"wait\" interval NOT NULL )");
+
+        let amount_array =
+            array::Decimal256Array::from(vec![arrow::datatypes::i256::from_i128(12345)])
+                .with_precision_and_scale(40, 2)
+                .expect("Invalid precision and scale");
+        let wait_array = array::IntervalDayTimeArray::from(vec![
+            arrow::datatypes::IntervalDayTimeType::make_value(2, 1500),
+        ]);
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(amount_array), Arc::new(wait_array)])
+                .expect("Unable to build record batch");
+
+        let sql = InsertBuilder::new("orders", vec![batch])
+            .build_postgres()
+            .expect("Failed to build insert statement");
+        assert_eq!(sql, "INSERT INTO \"orders\" (\"amount\", \"wait\") VALUES (123.45, '0 months 2 days 1500000 microseconds')");
+    }
+
     #[test]
     fn test_table_creation_with_primary_keys() {
         let schema = Schema::new(vec![
@@ -0,0 +1,55 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{fmt::Display, str::FromStr};
+
+/// What to do with the columns of a source whose types have no Arrow type, i.e. PostGIS geometries or user defined
+/// types.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnsupportedTypeAction {
+    /// Fail to read the source.
+    #[default]
+    Error,
+    /// Leave the columns out, with a warning.
+    Warn,
+    /// Read the columns as strings, with a warning.
+    String,
+}
+
+impl FromStr for UnsupportedTypeAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "string" => Ok(Self::String),
+            _ => Err(format!(
+                "Unknown unsupported type action {s}, expected error, warn or string"
+            )),
+        }
+    }
+}
+
+impl Display for UnsupportedTypeAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warn => write!(f, "warn"),
+            Self::String => write!(f, "string"),
+        }
+    }
+}
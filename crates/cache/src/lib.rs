@@ -61,12 +61,24 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub struct QueryResult {
     pub data: SendableRecordBatchStream,
     pub from_cache: Option<bool>,
+    /// Caveats of the results, such as the stale accelerations they were read from.
+    pub warnings: Vec<String>,
 }
 
 impl QueryResult {
     #[must_use]
     pub fn new(data: SendableRecordBatchStream, from_cache: Option<bool>) -> Self {
-        QueryResult { data, from_cache }
+        QueryResult {
+            data,
+            from_cache,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
     }
 }
 
@@ -25,7 +25,7 @@ use std::fmt::{self, Debug};
 
 use std::sync::{Arc, Mutex};
 
-use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use arrow::{compute::concat_batches, datatypes::SchemaRef, record_batch::RecordBatch};
 use async_trait::async_trait;
 use datafusion::common::{Constraints, SchemaExt};
 use datafusion::datasource::{provider_as_source, TableProvider, TableType};
@@ -40,6 +40,7 @@ use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan};
 use futures::StreamExt;
 use tokio::sync::RwLock;
 
+use crate::compact::CompactionTableProvider;
 use crate::delete::{DeletionExec, DeletionSink, DeletionTableProvider};
 
 /// Type alias for partition data
@@ -285,6 +286,33 @@ impl DeletionTableProvider for MemTable {
     }
 }
 
+/// The number of rows of the batches partitions are merged into when the table is compacted.
+const COMPACTED_BATCH_SIZE: usize = 8192;
+
+#[async_trait]
+impl CompactionTableProvider for MemTable {
+    /// Merges the small batches appended to each partition, and the batches left sparse by deletions, into batches
+    /// of up to `COMPACTED_BATCH_SIZE` rows.
+    async fn compact(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for partition in &self.batches {
+            let mut batches = partition.write().await;
+            if batches.len() <= 1 {
+                continue;
+            }
+
+            let merged = concat_batches(&self.schema, batches.iter())?;
+            *batches = (0..merged.num_rows())
+                .step_by(COMPACTED_BATCH_SIZE)
+                .map(|offset| {
+                    merged.slice(offset, COMPACTED_BATCH_SIZE.min(merged.num_rows() - offset))
+                })
+                .collect();
+        }
+
+        Ok(())
+    }
+}
+
 struct MemDeletionSink {
     batches: Vec<PartitionData>,
     schema: SchemaRef,
@@ -362,7 +390,9 @@ mod tests {
         scalar::ScalarValue,
     };
 
-    use crate::{arrow::write::MemTable, delete::DeletionTableProvider};
+    use crate::{
+        arrow::write::MemTable, compact::CompactionTableProvider, delete::DeletionTableProvider,
+    };
 
     #[tokio::test]
     #[allow(clippy::unreadable_literal)]
@@ -414,4 +444,36 @@ mod tests {
         let expected = UInt64Array::from(vec![2]);
         assert_eq!(actual, &expected);
     }
+
+    #[tokio::test]
+    async fn test_compact() {
+        let schema = Arc::new(Schema::new(vec![arrow::datatypes::Field::new(
+            "name",
+            DataType::Utf8,
+            false,
+        )]));
+        let batches = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| {
+                RecordBatch::try_new(
+                    Arc::clone(&schema),
+                    vec![Arc::new(StringArray::from(vec![name]))],
+                )
+                .expect("data should be created")
+            })
+            .collect::<Vec<_>>();
+
+        let table = MemTable::try_new(schema, vec![batches]).expect("mem table should be created");
+        table
+            .compact()
+            .await
+            .expect("compaction should be successful");
+
+        let partition = table.batches[0].read().await;
+        assert_eq!(partition.len(), 1);
+        assert_eq!(
+            partition[0].column(0).as_ref(),
+            &StringArray::from(vec!["a", "b", "c"]) as &dyn arrow::array::Array
+        );
+    }
 }
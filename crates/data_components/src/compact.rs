@@ -0,0 +1,50 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use async_trait::async_trait;
+use datafusion::datasource::TableProvider;
+
+use crate::delete::DeletionTableProviderAdapter;
+
+/// A table whose engine can merge the small batches appended to it and reclaim the space of its deleted rows.
+#[async_trait]
+pub trait CompactionTableProvider: TableProvider {
+    async fn compact(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+#[must_use]
+pub fn get_compaction_provider(from: &dyn TableProvider) -> Option<&dyn CompactionTableProvider> {
+    let source = match from.as_any().downcast_ref::<DeletionTableProviderAdapter>() {
+        Some(adapter) => adapter.source().as_any(),
+        None => from.as_any(),
+    };
+
+    if let Some(p) = source.downcast_ref::<crate::arrow::write::MemTable>() {
+        return Some(p);
+    }
+
+    #[cfg(feature = "duckdb")]
+    if let Some(p) = source.downcast_ref::<crate::duckdb::write::DuckDBTableWriter>() {
+        return Some(p);
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(p) = source.downcast_ref::<crate::sqlite::write::SqliteTableWriter>() {
+        return Some(p);
+    }
+
+    None
+}
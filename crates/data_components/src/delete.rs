@@ -132,6 +132,11 @@ impl DeletionTableProviderAdapter {
     pub fn new(source: Arc<dyn DeletionTableProvider>) -> Self {
         Self { source }
     }
+
+    #[must_use]
+    pub fn source(&self) -> &Arc<dyn DeletionTableProvider> {
+        &self.source
+    }
 }
 
 #[allow(clippy::needless_pass_by_value)]
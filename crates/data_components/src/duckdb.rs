@@ -22,7 +22,11 @@ use crate::{
     },
     Read, ReadWrite,
 };
-use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use arrow::{
+    array::RecordBatch,
+    compute::cast,
+    datatypes::{DataType, Field, Schema, SchemaRef, DECIMAL128_MAX_PRECISION},
+};
 use async_trait::async_trait;
 use datafusion::{
     common::Constraints,
@@ -112,6 +116,9 @@ pub enum Error {
 
     #[snafu(display("Constraint Violation: {source}"))]
     ConstraintViolation { source: constraints::Error },
+
+    #[snafu(display("Unable to convert data to the types DuckDB stores it as: {source}"))]
+    UnableToConvertToStorageTypes { source: arrow::error::ArrowError },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -191,7 +198,7 @@ impl TableProviderFactory for DuckDBTableProviderFactory {
 
         let schema: SchemaRef = Arc::new(cmd.schema.as_ref().into());
 
-        let duckdb = TableCreator::new(name.clone(), Arc::clone(&schema), Arc::clone(&pool))
+        let duckdb = TableCreator::new(name.clone(), storage_schema(&schema), Arc::clone(&pool))
             .constraints(cmd.constraints.clone())
             .indexes(indexes)
             .create()
@@ -215,6 +222,54 @@ impl TableProviderFactory for DuckDBTableProviderFactory {
     }
 }
 
+/// The type DuckDB stores an Arrow type it has no equivalent of as: 256-bit decimals of up to 38 digits as 128-bit
+/// decimals, and longer ones as strings. Reads of the accelerated table cast them back.
+fn storage_type(data_type: &DataType) -> Option<DataType> {
+    match data_type {
+        DataType::Decimal256(precision, scale) if *precision <= DECIMAL128_MAX_PRECISION => {
+            Some(DataType::Decimal128(*precision, *scale))
+        }
+        DataType::Decimal256(..) => Some(DataType::Utf8),
+        _ => None,
+    }
+}
+
+fn storage_schema(schema: &SchemaRef) -> SchemaRef {
+    if !schema
+        .fields()
+        .iter()
+        .any(|field| storage_type(field.data_type()).is_some())
+    {
+        return Arc::clone(schema);
+    }
+
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| match storage_type(field.data_type()) {
+            Some(data_type) => Arc::new(Field::clone(field).with_data_type(data_type)),
+            None => Arc::clone(field),
+        })
+        .collect::<Vec<_>>();
+    Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+fn to_storage_batch(batch: &RecordBatch) -> Result<RecordBatch> {
+    let schema = storage_schema(&batch.schema());
+    if schema == batch.schema() {
+        return Ok(batch.clone());
+    }
+
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| cast(column, field.data_type()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .context(UnableToConvertToStorageTypesSnafu)?;
+    RecordBatch::try_new(schema, columns).context(UnableToConvertToStorageTypesSnafu)
+}
+
 fn to_datafusion_error(error: Error) -> DataFusionError {
     DataFusionError::External(Box::new(error))
 }
@@ -305,7 +360,8 @@ impl DuckDB {
             .appender(&self.table_name)
             .context(UnableToGetAppenderToDuckDBTableSnafu)?;
 
-        for batch in Self::split_batch(batch) {
+        let batch = to_storage_batch(batch)?;
+        for batch in Self::split_batch(&batch) {
             appender
                 .append_record_batch(batch.clone())
                 .context(UnableToInsertToDuckDBTableSnafu)?;
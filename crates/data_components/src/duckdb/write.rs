@@ -16,8 +16,10 @@ limitations under the License.
 
 use std::{any::Any, fmt, sync::Arc};
 
+use crate::compact::CompactionTableProvider;
 use crate::delete::{DeletionExec, DeletionSink, DeletionTableProvider};
 use crate::duckdb::DuckDB;
+use crate::sample::{SampleTableProvider, TableSample};
 use crate::util::constraints;
 use arrow::{array::RecordBatch, datatypes::SchemaRef};
 use async_trait::async_trait;
@@ -33,10 +35,10 @@ use datafusion::{
         DisplayAs, DisplayFormatType, ExecutionPlan,
     },
 };
-use duckdb::Transaction;
+use duckdb::{DuckdbConnectionManager, ToSql, Transaction};
 use futures::StreamExt;
 use snafu::prelude::*;
-use sql_provider_datafusion::expr::Engine;
+use sql_provider_datafusion::{expr::Engine, SqlTable};
 
 use super::to_datafusion_error;
 
@@ -99,6 +101,48 @@ impl TableProvider for DuckDBTableWriter {
     }
 }
 
+#[async_trait]
+impl SampleTableProvider for DuckDBTableWriter {
+    async fn scan_sample(
+        &self,
+        _state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+        sample: &TableSample,
+    ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+        let Some(read_provider) = self.read_provider.as_any().downcast_ref::<SqlTable<
+            r2d2::PooledConnection<DuckdbConnectionManager>,
+            &'static dyn ToSql,
+        >>() else {
+            return Err(DataFusionError::Internal(
+                "The DuckDB table can't be sampled".to_string(),
+            ));
+        };
+
+        let table_sample = match sample.seed {
+            Some(seed) => format!(
+                "TABLESAMPLE {} PERCENT ({}, {seed})",
+                sample.percent, sample.method
+            ),
+            None => format!("TABLESAMPLE {} PERCENT ({})", sample.percent, sample.method),
+        };
+        read_provider.scan_sample(projection, filters, limit, table_sample)
+    }
+}
+
+#[async_trait]
+impl CompactionTableProvider for DuckDBTableWriter {
+    /// Checkpoints the database, which merges the row groups of appends and reclaims the blocks of deleted rows.
+    async fn compact(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut db_conn = self.duckdb.connect().await?;
+        let duckdb_conn = DuckDB::duckdb_conn(&mut db_conn)?;
+        duckdb_conn.conn.execute_batch("CHECKPOINT")?;
+
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct DuckDBDataSink {
     duckdb: Arc<DuckDB>,
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use datafusion_federation::{FederatedTableProviderAdaptor, FederatedTableSource};
 use datafusion_federation_sql::{SQLExecutor, SQLFederationProvider, SQLTableSource};
+use db_connection_pool::query_comment::apply_query_comment;
 use std::sync::Arc;
 
 use datafusion::{
@@ -66,7 +67,7 @@ impl SQLExecutor for FlightSQLTable {
     ) -> DataFusionResult<SendableRecordBatchStream> {
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             schema,
-            query_to_stream(self.client.clone(), query),
+            query_to_stream(self.client.clone(), &apply_query_comment(query.to_string())),
         )))
     }
 
@@ -49,8 +49,10 @@ pub mod sqlite;
 #[cfg(feature = "snowflake")]
 pub mod snowflake;
 
+pub mod compact;
 pub mod delete;
 pub mod object;
+pub mod sample;
 pub mod util;
 
 #[async_trait]
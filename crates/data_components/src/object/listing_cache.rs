@@ -0,0 +1,99 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use object_store::ObjectMeta;
+use tokio::sync::Mutex;
+
+/// The objects of a listing, reused by the scans of an object store table until they are older than a TTL or
+/// invalidated, so repeated queries don't list large prefixes again.
+#[derive(Debug)]
+pub struct ListingCache {
+    ttl: Duration,
+    // Held while listing, so concurrent scans wait for one listing instead of each listing the store.
+    listing: Mutex<Option<(Instant, Arc<Vec<ObjectMeta>>)>>,
+}
+
+impl ListingCache {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            listing: Mutex::new(None),
+        }
+    }
+
+    /// Drops the cached listing, so the next scan lists the store.
+    pub async fn invalidate(&self) {
+        *self.listing.lock().await = None;
+    }
+
+    /// The cached objects if they were listed less than the TTL ago, otherwise the objects of `list`, which are
+    /// cached unless listing fails.
+    pub(crate) async fn get_or_list<F>(&self, list: F) -> object_store::Result<Arc<Vec<ObjectMeta>>>
+    where
+        F: Future<Output = object_store::Result<Vec<ObjectMeta>>>,
+    {
+        let mut listing = self.listing.lock().await;
+        if let Some((listed_at, objects)) = listing.as_ref() {
+            if listed_at.elapsed() < self.ttl {
+                return Ok(Arc::clone(objects));
+            }
+        }
+
+        let objects = Arc::new(list.await?);
+        *listing = Some((Instant::now(), Arc::clone(&objects)));
+        Ok(objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use object_store::path::Path;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reuses_listing_until_invalidated() {
+        let cache = ListingCache::new(Duration::from_secs(60));
+        let listings = AtomicUsize::new(0);
+        let list = || async {
+            listings.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![ObjectMeta {
+                location: Path::from("data/trips.csv"),
+                last_modified: "2024-06-01T00:00:00Z".parse().expect("timestamp"),
+                size: 10,
+                e_tag: None,
+                version: None,
+            }])
+        };
+
+        assert_eq!(cache.get_or_list(list()).await.expect("listing").len(), 1);
+        cache.get_or_list(list()).await.expect("listing");
+        assert_eq!(listings.load(Ordering::SeqCst), 1);
+
+        cache.invalidate().await;
+        cache.get_or_list(list()).await.expect("listing");
+        assert_eq!(listings.load(Ordering::SeqCst), 2);
+    }
+}
@@ -15,48 +15,97 @@ limitations under the License.
 */
 #![allow(clippy::module_name_repetitions)]
 
-use std::{any::Any, fmt, sync::Arc};
+use std::{
+    any::Any,
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    fmt,
+    ops::Range,
+    sync::Arc,
+    time::Duration,
+};
 
 use arrow::{
-    array::{ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array},
-    datatypes::{DataType, Field, Schema, SchemaRef},
+    array::{
+        ArrayRef, MapBuilder, RecordBatch, StringArray, StringBuilder, TimestampMillisecondArray,
+        UInt64Array,
+    },
+    datatypes::{DataType, Field, Fields, Schema, SchemaRef},
     error::ArrowError,
 };
 use async_stream::stream;
 use async_trait::async_trait;
 use datafusion::{
-    common::{project_schema, Constraint, Constraints},
+    common::{
+        project_schema,
+        tree_node::{Transformed, TreeNode},
+        Constraint, Constraints,
+    },
+    config::ConfigOptions,
     datasource::{TableProvider, TableType},
     error::{DataFusionError, Result as DataFusionResult},
     execution::{context::SessionState, SendableRecordBatchStream, TaskContext},
     logical_expr::{Expr, TableProviderFilterPushDown},
-    physical_expr::EquivalenceProperties,
+    physical_expr::{expressions::Column, EquivalenceProperties},
+    physical_optimizer::PhysicalOptimizerRule,
     physical_plan::{
-        stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionMode,
-        ExecutionPlan, Partitioning, PlanProperties,
+        sorts::sort::SortExec, stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType,
+        ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
     },
 };
 
 use futures::Stream;
-use futures::StreamExt;
-use object_store::{path::Path, ObjectMeta, ObjectStore};
+use futures::{StreamExt, TryStreamExt};
+use object_store::{path::Path, Attribute, GetOptions, ObjectMeta, ObjectStore};
 
-use super::ObjectStoreContext;
+use super::{listing_cache::ListingCache, ObjectStoreContext};
 use url::Url;
 
+/// The indices of the columns that are fetched with a HEAD request for each object, rather than listed.
+const HEAD_COLUMNS: Range<usize> = 5..9;
+
+/// The most HEAD requests in flight at once for a scan.
+const HEAD_CONCURRENCY: usize = 16;
+
+/// The most objects in each batch of a scan.
+const BATCH_SIZE: usize = 64;
+
+/// The metadata of an object that is only returned by a HEAD request.
+#[derive(Debug, Clone, Default)]
+struct ObjectDetails {
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    /// The storage class of the object, for stores that report it.
+    storage_class: Option<String>,
+    /// The other metadata set on the object, i.e. `cache-control` or `content-language`.
+    metadata: Vec<(String, String)>,
+}
+
 pub struct ObjectStoreMetadataTable {
     ctx: ObjectStoreContext,
 }
 
 impl ObjectStoreMetadataTable {
+    /// Creates the table of the metadata of the objects under `url`, with a column for each named capture group
+    /// of `filename_regex`. With a `listing_cache_ttl`, scans reuse the listing of the objects until it is older
+    /// than the TTL or the [`ListingCache`] is invalidated.
     pub fn try_new(
         store: Arc<dyn ObjectStore>,
         url: &Url,
         extension: Option<String>,
+        filename_regex: Option<&str>,
+        listing_cache_ttl: Option<Duration>,
     ) -> Result<Arc<Self>, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(Arc::new(Self {
-            ctx: ObjectStoreContext::try_new(store, url, extension)?,
-        }))
+        let mut ctx = ObjectStoreContext::try_new(store, url, extension, filename_regex)?;
+        ctx.check_capture_names(&Self::table_schema())?;
+        ctx.listing_cache = listing_cache_ttl.map(|ttl| Arc::new(ListingCache::new(ttl)));
+        Ok(Arc::new(Self { ctx }))
+    }
+
+    /// The cache of the listing of the objects, if the table has a listing cache TTL.
+    #[must_use]
+    pub fn listing_cache(&self) -> Option<&Arc<ListingCache>> {
+        self.ctx.listing_cache.as_ref()
     }
 
     #[must_use]
@@ -79,12 +128,34 @@ impl ObjectStoreMetadataTable {
             Field::new("size", DataType::UInt64, false),
             Field::new("e_tag", DataType::Utf8, true),
             Field::new("version", DataType::Utf8, true),
+            // Fetched with a HEAD request for each object, only when projected.
+            Field::new("content_type", DataType::Utf8, true),
+            Field::new("content_encoding", DataType::Utf8, true),
+            Field::new("storage_class", DataType::Utf8, true),
+            Field::new("metadata", Self::metadata_type(), true),
         ])
     }
 
-    /// Convert a list of [`ObjectMeta`] to a [`RecordBatch`]. Schema is defined in [`Self::table_schema`].
-    fn to_record_batch(meta_list: &[ObjectMeta]) -> Result<RecordBatch, ArrowError> {
-        let schema = Self::table_schema();
+    /// The `metadata` map of strings to strings, as built by [`MapBuilder`].
+    fn metadata_type() -> DataType {
+        let entries = Fields::from(vec![
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Utf8, true),
+        ]);
+        DataType::Map(
+            Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+            false,
+        )
+    }
+
+    /// Convert a list of [`ObjectMeta`], and the [`ObjectDetails`] of each object, to a [`RecordBatch`]. Schema is
+    /// defined in [`Self::table_schema`], followed by the capture groups of the filename regex.
+    fn to_record_batch(
+        ctx: &ObjectStoreContext,
+        meta_list: &[ObjectMeta],
+        details: &[ObjectDetails],
+    ) -> Result<RecordBatch, ArrowError> {
+        let schema = ctx.with_capture_fields(&Self::table_schema());
 
         let location_array = StringArray::from(
             meta_list
@@ -117,16 +188,48 @@ impl ObjectStoreMetadataTable {
                 .collect::<Vec<_>>(),
         );
 
-        let batch = RecordBatch::try_new(
-            Arc::new(schema),
-            vec![
-                Arc::new(location_array) as ArrayRef,
-                Arc::new(last_modified_array) as ArrayRef,
-                Arc::new(size_array) as ArrayRef,
-                Arc::new(e_tag_array) as ArrayRef,
-                Arc::new(version_array) as ArrayRef,
-            ],
-        )?;
+        let content_type_array = StringArray::from(
+            details
+                .iter()
+                .map(|details| details.content_type.clone())
+                .collect::<Vec<_>>(),
+        );
+        let content_encoding_array = StringArray::from(
+            details
+                .iter()
+                .map(|details| details.content_encoding.clone())
+                .collect::<Vec<_>>(),
+        );
+        let storage_class_array = StringArray::from(
+            details
+                .iter()
+                .map(|details| details.storage_class.clone())
+                .collect::<Vec<_>>(),
+        );
+        let mut metadata_builder =
+            MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+        for details in details {
+            for (key, value) in &details.metadata {
+                metadata_builder.keys().append_value(key);
+                metadata_builder.values().append_value(value);
+            }
+            metadata_builder.append(true)?;
+        }
+
+        let mut columns = vec![
+            Arc::new(location_array) as ArrayRef,
+            Arc::new(last_modified_array) as ArrayRef,
+            Arc::new(size_array) as ArrayRef,
+            Arc::new(e_tag_array) as ArrayRef,
+            Arc::new(version_array) as ArrayRef,
+            Arc::new(content_type_array) as ArrayRef,
+            Arc::new(content_encoding_array) as ArrayRef,
+            Arc::new(storage_class_array) as ArrayRef,
+            Arc::new(metadata_builder.finish()) as ArrayRef,
+        ];
+        columns.extend(ctx.capture_columns(meta_list));
+
+        let batch = RecordBatch::try_new(Arc::new(schema), columns)?;
 
         Ok(batch)
     }
@@ -141,7 +244,7 @@ impl TableProvider for ObjectStoreMetadataTable {
     /// Schema of [`ObjectStoreMetadataTable`] defined in relation to [`object_store::ObjectMeta`].
     /// Must match the order and types of the fields in [`to_record_batch`].
     fn schema(&self) -> SchemaRef {
-        Arc::new(Self::table_schema())
+        Arc::new(self.ctx.with_capture_fields(&Self::table_schema()))
     }
 
     fn constraints(&self) -> Option<&Constraints> {
@@ -167,6 +270,7 @@ impl TableProvider for ObjectStoreMetadataTable {
         let projected_schema = project_schema(&self.schema(), projection)?;
         Ok(Arc::new(ObjectStoreMetadataExec::new(
             projected_schema,
+            projection.cloned(),
             filters,
             limit,
             self.ctx.clone(),
@@ -186,8 +290,11 @@ impl TableProvider for ObjectStoreMetadataTable {
 
 pub struct ObjectStoreMetadataExec {
     projected_schema: SchemaRef,
-    _filters: Vec<Expr>,
+    projection: Option<Vec<usize>>,
+    filters: Vec<Expr>,
     limit: Option<usize>,
+    // Only the most recently modified objects, which are kept while listing.
+    latest: Option<usize>,
     properties: PlanProperties,
 
     ctx: ObjectStoreContext,
@@ -206,7 +313,11 @@ impl DisplayAs for ObjectStoreMetadataExec {
             "{} prefix={}",
             self.name(),
             self.ctx.prefix.clone().unwrap_or_default()
-        )
+        )?;
+        if let Some(latest) = self.latest {
+            write!(f, " latest={latest}")?;
+        }
+        Ok(())
     }
 }
 
@@ -245,7 +356,12 @@ impl ExecutionPlan for ObjectStoreMetadataExec {
     ) -> DataFusionResult<SendableRecordBatchStream> {
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
-            to_sendable_stream(self.ctx.clone(), self.limit), // TODO get prefix from filters
+            to_sendable_stream(
+                self.ctx.clone(),
+                self.projection.clone(),
+                self.limit,
+                self.latest,
+            ), // TODO get prefix from filters
         )))
     }
 }
@@ -253,14 +369,17 @@ impl ExecutionPlan for ObjectStoreMetadataExec {
 impl ObjectStoreMetadataExec {
     pub(crate) fn new(
         projected_schema: SchemaRef,
+        projection: Option<Vec<usize>>,
         filters: &[Expr],
         limit: Option<usize>,
         ctx: ObjectStoreContext,
     ) -> Self {
         Self {
             projected_schema: Arc::clone(&projected_schema),
-            _filters: filters.to_vec(),
+            projection,
+            filters: filters.to_vec(),
             limit,
+            latest: None,
             properties: PlanProperties::new(
                 EquivalenceProperties::new(projected_schema),
                 Partitioning::UnknownPartitioning(1),
@@ -269,41 +388,372 @@ impl ObjectStoreMetadataExec {
             ctx,
         }
     }
+
+    /// Only scans the `latest` most recently modified objects, keeping them in a bounded heap while listing instead
+    /// of fetching the details of every object.
+    #[must_use]
+    pub fn with_latest(&self, latest: usize) -> Self {
+        Self {
+            projected_schema: Arc::clone(&self.projected_schema),
+            projection: self.projection.clone(),
+            filters: self.filters.clone(),
+            limit: self.limit,
+            latest: Some(latest),
+            properties: self.properties.clone(),
+            ctx: self.ctx.clone(),
+        }
+    }
+}
+
+/// An object ordered by when it was last modified, then by its location.
+struct ByLastModified(ObjectMeta);
+
+impl Ord for ByLastModified {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.last_modified, &self.0.location).cmp(&(other.0.last_modified, &other.0.location))
+    }
+}
+
+impl PartialOrd for ByLastModified {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByLastModified {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByLastModified {}
+
+/// Pushes `ORDER BY last_modified DESC LIMIT n` down to [`ObjectStoreMetadataExec`], so only the `n` most recently
+/// modified objects are kept while listing. The sort is kept, to order those objects.
+#[derive(Debug, Default)]
+pub struct LatestObjectsPushdown {}
+
+impl LatestObjectsPushdown {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PhysicalOptimizerRule for LatestObjectsPushdown {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        plan.transform_down(|plan| {
+            let Some(sort) = plan.as_any().downcast_ref::<SortExec>() else {
+                return Ok(Transformed::no(plan));
+            };
+            let Some(exec) = sort
+                .input()
+                .as_any()
+                .downcast_ref::<ObjectStoreMetadataExec>()
+            else {
+                return Ok(Transformed::no(plan));
+            };
+            let (Some(fetch), [sort_expr]) = (sort.fetch(), sort.expr()) else {
+                return Ok(Transformed::no(plan));
+            };
+            let by_last_modified = sort_expr
+                .expr
+                .as_any()
+                .downcast_ref::<Column>()
+                .is_some_and(|column| column.name() == "last_modified");
+            if !by_last_modified || !sort_expr.options.descending || exec.latest.is_some() {
+                return Ok(Transformed::no(plan));
+            }
+
+            let input = Arc::new(exec.with_latest(fetch)) as Arc<dyn ExecutionPlan>;
+            let sort = SortExec::new(sort.expr().to_vec(), input)
+                .with_fetch(Some(fetch))
+                .with_preserve_partitioning(sort.preserve_partitioning());
+            Ok(Transformed::yes(Arc::new(sort) as Arc<dyn ExecutionPlan>))
+        })
+        .map(|transformed| transformed.data)
+    }
+
+    fn name(&self) -> &str {
+        "latest_objects_pushdown"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
 }
 
 fn to_sendable_stream(
     ctx: ObjectStoreContext,
+    projection: Option<Vec<usize>>,
     limit: Option<usize>,
+    latest: Option<usize>,
 ) -> impl Stream<Item = DataFusionResult<RecordBatch>> + 'static {
+    let fetch_details = projection.as_ref().map_or(true, |projection| {
+        projection.iter().any(|index| HEAD_COLUMNS.contains(index))
+    });
+
     stream! {
-        let mut object_stream = ctx.store.list(ctx.prefix.clone().map(Path::from).as_ref());
+        let mut object_stream = ctx.list();
+        let mut objects = Vec::with_capacity(BATCH_SIZE);
+        // The most recently modified objects so far, oldest first, when only the latest are scanned.
+        let mut heap: BinaryHeap<Reverse<ByLastModified>> = BinaryHeap::new();
         let mut count = 0;
 
         while let Some(item) = object_stream.next().await {
-            match item {
-                Ok(object_meta) => {
-
-                    if !ctx.filename_in_scan(&object_meta) {
+            let object_meta = match item {
+                Ok(object_meta) => object_meta,
+                Err(e) => {
+                    yield Err(DataFusionError::Execution(format!("{e}")));
                     continue;
-                    }
-                    match ObjectStoreMetadataTable::to_record_batch(&[object_meta]) {
-                        Ok(batch) => {
-                            let n = batch.num_rows();
-                            yield Ok(batch);
-                            count += n;
-                        },
-                        Err(e) => yield Err(DataFusionError::Execution(format!("{e}"))),
-                    }
-                },
-                Err(e) => yield Err(DataFusionError::Execution(format!("{e}"))),
+                }
+            };
+            if !ctx.filename_in_scan(&object_meta) {
+                continue;
             }
 
-            // Early exit on LIMIT clause
-            if let Some(limit) = limit {
-                if count >= limit {
-                    break;
+            // Every object is listed to find the latest, but only they are kept.
+            if let Some(latest) = latest {
+                heap.push(Reverse(ByLastModified(object_meta)));
+                if heap.len() > latest {
+                    heap.pop();
                 }
+                continue;
+            }
+
+            objects.push(object_meta);
+            count += 1;
+            // Early exit on LIMIT clause, before the next page of objects is listed.
+            if limit.is_some_and(|limit| count >= limit) {
+                break;
+            }
+            if objects.len() >= BATCH_SIZE {
+                let batch = std::mem::take(&mut objects);
+                yield to_projected_batch(&ctx, &batch, projection.as_deref(), fetch_details).await;
             }
         }
+        drop(object_stream);
+
+        if latest.is_some() {
+            // `into_sorted_vec` orders the reversed objects from the latest to the oldest.
+            objects = heap.into_sorted_vec().into_iter().map(|Reverse(ByLastModified(meta))| meta).collect();
+            if let Some(limit) = limit {
+                objects.truncate(limit);
+            }
+        }
+        for batch in objects.chunks(BATCH_SIZE) {
+            yield to_projected_batch(&ctx, batch, projection.as_deref(), fetch_details).await;
+        }
+    }
+}
+
+/// Converts listed objects to a projected [`RecordBatch`], fetching their [`ObjectDetails`] if they are projected.
+async fn to_projected_batch(
+    ctx: &ObjectStoreContext,
+    objects: &[ObjectMeta],
+    projection: Option<&[usize]>,
+    fetch_details: bool,
+) -> DataFusionResult<RecordBatch> {
+    let details = if fetch_details {
+        futures::stream::iter(
+            objects
+                .iter()
+                .map(|meta| head(ctx.store.as_ref(), &meta.location)),
+        )
+        .buffered(HEAD_CONCURRENCY)
+        .try_collect::<Vec<_>>()
+        .await?
+    } else {
+        vec![ObjectDetails::default(); objects.len()]
+    };
+
+    let batch = ObjectStoreMetadataTable::to_record_batch(ctx, objects, &details)
+        .map_err(|e| DataFusionError::Execution(format!("{e}")))?;
+    match projection {
+        Some(projection) => batch
+            .project(projection)
+            .map_err(|e| DataFusionError::Execution(format!("{e}"))),
+        None => Ok(batch),
+    }
+}
+
+/// Fetches the [`ObjectDetails`] of an object with a HEAD request.
+async fn head(store: &dyn ObjectStore, location: &Path) -> DataFusionResult<ObjectDetails> {
+    let options = GetOptions {
+        head: true,
+        ..GetOptions::default()
+    };
+    let result = store
+        .get_opts(location, options)
+        .await
+        .map_err(|e| DataFusionError::Execution(format!("{e}")))?;
+
+    let mut details = ObjectDetails::default();
+    for (attribute, value) in result.attributes.iter() {
+        let value: &str = value.as_ref();
+        let value = value.to_string();
+        match attribute {
+            Attribute::ContentType => details.content_type = Some(value),
+            Attribute::ContentEncoding => details.content_encoding = Some(value),
+            Attribute::CacheControl => details.metadata.push(("cache-control".to_string(), value)),
+            Attribute::ContentDisposition => {
+                details
+                    .metadata
+                    .push(("content-disposition".to_string(), value));
+            }
+            Attribute::ContentLanguage => {
+                details
+                    .metadata
+                    .push(("content-language".to_string(), value));
+            }
+            _ => {}
+        }
+    }
+    Ok(details)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+
+    use super::*;
+
+    #[test]
+    fn projects_details_and_captures() {
+        let ctx = ObjectStoreContext::try_new(
+            Arc::new(object_store::memory::InMemory::new()),
+            &Url::parse("file:///data/").expect("url"),
+            None,
+            Some(r"^(?<date>\d{4}-\d{2}-\d{2})_(?<name>\w+)\.parquet$"),
+        )
+        .expect("context");
+        let meta = ObjectMeta {
+            location: Path::from("data/2024-06-01_trips.parquet"),
+            last_modified: "2024-06-01T00:00:00Z".parse().expect("timestamp"),
+            size: 1024,
+            e_tag: None,
+            version: None,
+        };
+        let details = ObjectDetails {
+            content_type: Some("application/vnd.apache.parquet".to_string()),
+            metadata: vec![("cache-control".to_string(), "no-cache".to_string())],
+            ..ObjectDetails::default()
+        };
+
+        let batch = ObjectStoreMetadataTable::to_record_batch(&ctx, &[meta], &[details])
+            .expect("batch")
+            .project(&[0, 5, 8, 9, 10])
+            .expect("projected batch");
+
+        assert_eq!(batch.schema().field(1).name(), "content_type");
+        assert_eq!(
+            batch.column(2).data_type(),
+            &ObjectStoreMetadataTable::metadata_type()
+        );
+        assert_eq!(batch.schema().field(3).name(), "date");
+        assert_eq!(batch.column(3).as_string::<i32>().value(0), "2024-06-01");
+        assert_eq!(batch.column(4).as_string::<i32>().value(0), "trips");
+    }
+
+    #[tokio::test]
+    async fn pushes_down_latest_objects_with_ties() {
+        use arrow::compute::SortOptions;
+        use datafusion::physical_expr::PhysicalSortExpr;
+
+        // Three objects share each modified time, listed out of order across several batches.
+        let objects = (0..150)
+            .map(|i| (i * 7) % 150)
+            .map(|i| ObjectMeta {
+                location: Path::from(format!("data/object_{i:03}.csv")),
+                last_modified: format!("2024-06-01T00:{:02}:00Z", i / 3)
+                    .parse()
+                    .expect("timestamp"),
+                size: 10,
+                e_tag: None,
+                version: None,
+            })
+            .collect::<Vec<_>>();
+        let mut ctx = ObjectStoreContext::try_new(
+            Arc::new(object_store::memory::InMemory::new()),
+            &Url::parse("file:///data/").expect("url"),
+            None,
+            None,
+        )
+        .expect("context");
+        let listing_cache = Arc::new(ListingCache::new(Duration::from_secs(60)));
+        listing_cache
+            .get_or_list(async { Ok(objects) })
+            .await
+            .expect("listing");
+        ctx.listing_cache = Some(listing_cache);
+
+        let projection = vec![0, 1];
+        let schema = project_schema(
+            &Arc::new(ObjectStoreMetadataTable::table_schema()),
+            Some(&projection),
+        )
+        .expect("projected schema");
+        let exec = Arc::new(ObjectStoreMetadataExec::new(
+            schema,
+            Some(projection),
+            &[],
+            None,
+            ctx,
+        ));
+        let sort = Arc::new(
+            SortExec::new(
+                vec![PhysicalSortExpr {
+                    expr: Arc::new(Column::new("last_modified", 1)),
+                    options: SortOptions {
+                        descending: true,
+                        nulls_first: true,
+                    },
+                }],
+                exec,
+            )
+            .with_fetch(Some(5)),
+        );
+
+        let plan = LatestObjectsPushdown::new()
+            .optimize(sort, &ConfigOptions::default())
+            .expect("optimized plan");
+        let latest = plan.children()[0]
+            .as_any()
+            .downcast_ref::<ObjectStoreMetadataExec>()
+            .and_then(|exec| exec.latest);
+        assert_eq!(latest, Some(5));
+
+        let batches = datafusion::physical_plan::collect(plan, Arc::new(TaskContext::default()))
+            .await
+            .expect("results");
+        let mut locations = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_string::<i32>()
+                    .iter()
+                    .flatten()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // Ties on the modified time keep the greatest locations, so exactly 5 objects are kept. The sort isn't stable
+        // across ties, so only the objects kept are compared.
+        locations.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(
+            locations,
+            vec![
+                "data/object_149.csv",
+                "data/object_148.csv",
+                "data/object_147.csv",
+                "data/object_146.csv",
+                "data/object_145.csv",
+            ]
+        );
     }
 }
@@ -37,7 +37,7 @@ use datafusion::{
 };
 use futures::Stream;
 use futures::StreamExt;
-use object_store::{path::Path, GetResult, ObjectMeta, ObjectStore};
+use object_store::{GetResult, ObjectMeta, ObjectStore};
 use std::{any::Any, fmt, str::Utf8Error, sync::Arc};
 
 use super::ObjectStoreContext;
@@ -48,14 +48,17 @@ pub struct ObjectStoreTextTable {
 }
 
 impl ObjectStoreTextTable {
+    /// Creates the table of the content of the objects under `url`, with a column for each named capture group
+    /// of `filename_regex`.
     pub fn try_new(
         store: Arc<dyn ObjectStore>,
         url: &Url,
         extension: Option<String>,
+        filename_regex: Option<&str>,
     ) -> Result<Arc<Self>, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(Arc::new(Self {
-            ctx: ObjectStoreContext::try_new(store, url, extension)?,
-        }))
+        let ctx = ObjectStoreContext::try_new(store, url, extension, filename_regex)?;
+        ctx.check_capture_names(&Self::table_schema())?;
+        Ok(Arc::new(Self { ctx }))
     }
 
     fn table_schema() -> Schema {
@@ -65,12 +68,16 @@ impl ObjectStoreTextTable {
         ])
     }
 
-    fn to_record_batch(meta_list: &[ObjectMeta], raw: &[Bytes]) -> Result<RecordBatch, ArrowError> {
+    fn to_record_batch(
+        ctx: &ObjectStoreContext,
+        meta_list: &[ObjectMeta],
+        raw: &[Bytes],
+    ) -> Result<RecordBatch, ArrowError> {
         if meta_list.len() != raw.len() {
             return Err(ArrowError::ParseError("Length mismatch".to_string()));
         }
 
-        let schema = Self::table_schema();
+        let schema = ctx.with_capture_fields(&Self::table_schema());
 
         let location_array: ArrayRef = Arc::new(StringArray::from(
             meta_list
@@ -89,7 +96,9 @@ impl ObjectStoreTextTable {
                 .collect::<Vec<_>>(),
         ));
 
-        RecordBatch::try_new(Arc::new(schema), vec![location_array, content_array])
+        let mut columns = vec![location_array, content_array];
+        columns.extend(ctx.capture_columns(meta_list));
+        RecordBatch::try_new(Arc::new(schema), columns)
     }
 }
 
@@ -100,7 +109,7 @@ impl TableProvider for ObjectStoreTextTable {
     }
 
     fn schema(&self) -> SchemaRef {
-        Arc::new(Self::table_schema())
+        Arc::new(self.ctx.with_capture_fields(&Self::table_schema()))
     }
 
     fn constraints(&self) -> Option<&Constraints> {
@@ -121,6 +130,7 @@ impl TableProvider for ObjectStoreTextTable {
         let projected_schema = project_schema(&self.schema(), projection)?;
         Ok(Arc::new(ObjectStoreTextExec::new(
             projected_schema,
+            projection.cloned(),
             filters,
             limit,
             self.ctx.clone(),
@@ -140,6 +150,7 @@ impl TableProvider for ObjectStoreTextTable {
 
 pub struct ObjectStoreTextExec {
     projected_schema: SchemaRef,
+    projection: Option<Vec<usize>>,
     _filters: Vec<Expr>,
     limit: Option<usize>,
     properties: PlanProperties,
@@ -199,7 +210,7 @@ impl ExecutionPlan for ObjectStoreTextExec {
     ) -> DataFusionResult<SendableRecordBatchStream> {
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
-            to_sendable_stream(self.ctx.clone(), self.limit), // TODO get prefix from filters
+            to_sendable_stream(self.ctx.clone(), self.projection.clone(), self.limit), // TODO get prefix from filters
         )))
     }
 }
@@ -207,12 +218,14 @@ impl ExecutionPlan for ObjectStoreTextExec {
 impl ObjectStoreTextExec {
     pub(crate) fn new(
         projected_schema: SchemaRef,
+        projection: Option<Vec<usize>>,
         filters: &[Expr],
         limit: Option<usize>,
         ctx: ObjectStoreContext,
     ) -> Self {
         Self {
             projected_schema: Arc::clone(&projected_schema),
+            projection,
             _filters: filters.to_vec(),
             limit,
             properties: PlanProperties::new(
@@ -227,10 +240,11 @@ impl ObjectStoreTextExec {
 
 pub(crate) fn to_sendable_stream(
     ctx: ObjectStoreContext,
+    projection: Option<Vec<usize>>,
     limit: Option<usize>,
 ) -> impl Stream<Item = DataFusionResult<RecordBatch>> + 'static {
     stream! {
-        let mut object_stream = ctx.store.list(ctx.prefix.clone().map(Path::from).as_ref());
+        let mut object_stream = ctx.list();
         let mut count = 0;
 
         while let Some(item) = object_stream.next().await {
@@ -244,7 +258,12 @@ pub(crate) fn to_sendable_stream(
                     let result: GetResult = ctx.store.get(&object_meta.location).await?;
                     let bytz = result.bytes().await?;
 
-                    match ObjectStoreTextTable::to_record_batch(&[object_meta], &[bytz]) {
+                    let batch = ObjectStoreTextTable::to_record_batch(&ctx, &[object_meta], &[bytz])
+                        .and_then(|batch| match &projection {
+                            Some(projection) => batch.project(projection),
+                            None => Ok(batch),
+                        });
+                    match batch {
                         Ok(batch) => {
                             let n = batch.num_rows();
                             yield Ok(batch);
@@ -0,0 +1,80 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use datafusion::{
+    datasource::TableProvider, error::Result as DataFusionResult, execution::context::SessionState,
+    logical_expr::Expr, physical_plan::ExecutionPlan,
+};
+
+use crate::delete::DeletionTableProviderAdapter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleMethod {
+    /// Each row is kept with the sampling probability.
+    Bernoulli,
+    /// Each block of rows is kept with the sampling probability, which is faster but less random.
+    System,
+}
+
+impl fmt::Display for SampleMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleMethod::Bernoulli => write!(f, "bernoulli"),
+            SampleMethod::System => write!(f, "system"),
+        }
+    }
+}
+
+/// A sample of about `percent` percent of the rows of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSample {
+    pub method: SampleMethod,
+    pub percent: f64,
+    /// Makes the sample repeatable, for the same data.
+    pub seed: Option<u64>,
+}
+
+/// A table that can scan a sample of its rows in its engine, rather than reading every row.
+#[async_trait]
+pub trait SampleTableProvider: TableProvider {
+    async fn scan_sample(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+        sample: &TableSample,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>>;
+}
+
+#[must_use]
+pub fn get_sample_provider(from: &dyn TableProvider) -> Option<&dyn SampleTableProvider> {
+    #[allow(unused_variables)]
+    let source = match from.as_any().downcast_ref::<DeletionTableProviderAdapter>() {
+        Some(adapter) => adapter.source().as_any(),
+        None => from.as_any(),
+    };
+
+    #[cfg(feature = "duckdb")]
+    if let Some(p) = source.downcast_ref::<crate::duckdb::write::DuckDBTableWriter>() {
+        return Some(p);
+    }
+
+    None
+}
@@ -35,6 +35,7 @@ use snafu::prelude::*;
 use sql_provider_datafusion::expr::Engine;
 
 use crate::{
+    compact::CompactionTableProvider,
     delete::{DeletionExec, DeletionSink, DeletionTableProvider},
     util::constraints,
 };
@@ -206,6 +207,24 @@ impl DeletionTableProvider for SqliteTableWriter {
     }
 }
 
+#[async_trait]
+impl CompactionTableProvider for SqliteTableWriter {
+    /// Vacuums the database, which rebuilds it without the pages freed by deleted rows.
+    async fn compact(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut db_conn = self.sqlite.connect().await?;
+        let sqlite_conn = Sqlite::sqlite_conn(&mut db_conn)?;
+        sqlite_conn
+            .conn
+            .call(|conn| {
+                conn.execute_batch("VACUUM")?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
 struct SqliteDeletionSink {
     sqlite: Arc<Sqlite>,
     filters: Vec<Expr>,
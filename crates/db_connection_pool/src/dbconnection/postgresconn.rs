@@ -20,6 +20,7 @@ use std::error::Error;
 use arrow::datatypes::SchemaRef;
 use arrow_sql_gen::postgres::columns_to_schema;
 use arrow_sql_gen::postgres::rows_to_arrow;
+use arrow_sql_gen::unsupported_type::UnsupportedTypeAction;
 use bb8_postgres::tokio_postgres::types::ToSql;
 use bb8_postgres::PostgresConnectionManager;
 use datafusion::execution::SendableRecordBatchStream;
@@ -52,6 +53,15 @@ pub enum PostgresError {
 
 pub struct PostgresConnection {
     pub conn: bb8::PooledConnection<'static, PostgresConnectionManager<MakeTlsConnector>>,
+    unsupported_type_action: UnsupportedTypeAction,
+}
+
+impl PostgresConnection {
+    #[must_use]
+    pub fn with_unsupported_type_action(mut self, action: UnsupportedTypeAction) -> Self {
+        self.unsupported_type_action = action;
+        self
+    }
 }
 
 impl<'a>
@@ -90,7 +100,10 @@ impl<'a>
     fn new(
         conn: bb8::PooledConnection<'static, PostgresConnectionManager<MakeTlsConnector>>,
     ) -> Self {
-        PostgresConnection { conn }
+        PostgresConnection {
+            conn,
+            unsupported_type_action: UnsupportedTypeAction::default(),
+        }
     }
 
     async fn get_schema(
@@ -106,7 +119,7 @@ impl<'a>
             .await
         {
             Ok(statement) => {
-                return columns_to_schema(statement.columns())
+                return columns_to_schema(statement.columns(), self.unsupported_type_action)
                     .boxed()
                     .context(super::UnableToGetSchemaSnafu)
             }
@@ -137,7 +150,8 @@ impl<'a>
         params: &[&'a (dyn ToSql + Sync)],
     ) -> Result<SendableRecordBatchStream> {
         let rows = self.conn.query(sql, params).await.context(QuerySnafu)?;
-        let rec = rows_to_arrow(rows.as_slice()).context(ConversionSnafu)?;
+        let rec = rows_to_arrow(rows.as_slice(), self.unsupported_type_action)
+            .context(ConversionSnafu)?;
         let schema = rec.schema();
         let recs = vec![rec];
         Ok(Box::pin(MemoryStream::try_new(recs, schema, None)?))
@@ -0,0 +1,305 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Validates the connections of a pool before they are used, and stops connecting to a database that keeps failing.
+//!
+//! After `failure_threshold` consecutive failures the circuit of the pool opens, and connecting fails immediately
+//! for `reset_timeout`. The next connection is then a probe: the circuit closes if it succeeds, and opens again if
+//! it fails. A probe that never completes, e.g. because it was cancelled, is replaced after another `reset_timeout`.
+
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use snafu::prelude::*;
+
+use crate::{dbconnection::DbConnection, settings::PoolSettings, Result};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Not connecting to {pool} after {failures} consecutive failures, retrying in {}s",
+        retry_in.as_secs()
+    ))]
+    CircuitOpen {
+        pool: String,
+        failures: u32,
+        retry_in: Duration,
+    },
+
+    #[snafu(display("The validation query of {pool} failed: {source}"))]
+    ValidationFailed { pool: String, source: crate::Error },
+
+    #[snafu(display("Unable to validate the connections of {pool}"))]
+    UnableToValidate { pool: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    /// Calls fail fast until the reset timeout has passed.
+    Open,
+    /// A single call is probing whether the upstream has recovered.
+    HalfOpen,
+}
+
+/// Counts the consecutive failures of calls to an upstream, and rejects calls while it is failing.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_started_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    #[must_use]
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            reset_timeout,
+            state: Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_started_at: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        let state = self.lock();
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(_) if state.probe_started_at.is_some() => CircuitState::HalfOpen,
+            Some(opened_at) if opened_at.elapsed() >= self.reset_timeout => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    #[must_use]
+    pub fn consecutive_failures(&self) -> u32 {
+        self.lock().consecutive_failures
+    }
+
+    /// Allows a call, or returns how long until the circuit allows the next one. Once the reset timeout has passed,
+    /// a single call is allowed to probe the upstream, and `Ok(true)` is returned for it.
+    pub fn try_acquire(&self) -> Result<bool, Duration> {
+        let mut state = self.lock();
+        let Some(opened_at) = state.opened_at else {
+            return Ok(false);
+        };
+
+        let waiting_since = state.probe_started_at.unwrap_or(opened_at);
+        let elapsed = waiting_since.elapsed();
+        if elapsed < self.reset_timeout {
+            return Err(self.reset_timeout - elapsed);
+        }
+        state.probe_started_at = Some(Instant::now());
+        Ok(true)
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.lock();
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+        state.probe_started_at = None;
+    }
+
+    /// Records a failed call, returning `true` if it opened the circuit.
+    pub fn record_failure(&self) -> bool {
+        let mut state = self.lock();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        let was_open = state.opened_at.is_some();
+        if state.probe_started_at.is_some() || state.consecutive_failures >= self.failure_threshold
+        {
+            state.opened_at = Some(Instant::now());
+        }
+        state.probe_started_at = None;
+        !was_open && state.opened_at.is_some()
+    }
+}
+
+/// The health of the connections of a pool, shared by the pools of the SQL connectors.
+#[derive(Debug)]
+pub struct PoolHealth {
+    /// Identifies the pool in logs and metrics, i.e. `postgres://db.internal:5432/app`.
+    name: String,
+    validation_query: Option<String>,
+    breaker: CircuitBreaker,
+}
+
+impl PoolHealth {
+    #[must_use]
+    pub fn new(name: impl Into<String>, settings: &PoolSettings) -> Self {
+        Self {
+            name: name.into(),
+            validation_query: settings.validation_query.clone(),
+            breaker: CircuitBreaker::new(settings.failure_threshold, settings.reset_timeout),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    /// Gets a connection from the pool with `connect`, validating it with the validation query. Fails immediately
+    /// while the circuit of the pool is open.
+    pub async fn connect<T, P: 'static>(
+        &self,
+        connect: impl Future<Output = Result<Box<dyn DbConnection<T, P>>>>,
+    ) -> Result<Box<dyn DbConnection<T, P>>> {
+        let labels = [("pool", self.name.clone())];
+
+        if let Err(retry_in) = self.breaker.try_acquire() {
+            metrics::counter!("db_connection_pool_connections_rejected", &labels).increment(1);
+            return Err(CircuitOpenSnafu {
+                pool: self.name.clone(),
+                failures: self.breaker.consecutive_failures(),
+                retry_in,
+            }
+            .build()
+            .into());
+        }
+
+        let start = Instant::now();
+        let result = match connect.await {
+            Ok(conn) => self.validate(conn).await,
+            Err(e) => Err(e),
+        };
+        metrics::histogram!("db_connection_pool_acquire_duration_seconds", &labels)
+            .record(start.elapsed().as_secs_f64());
+
+        match &result {
+            Ok(_) => {
+                if self.breaker.state() != CircuitState::Closed {
+                    tracing::info!("Connections to {} have recovered", self.name);
+                }
+                self.breaker.record_success();
+                metrics::counter!("db_connection_pool_connections_acquired", &labels).increment(1);
+            }
+            Err(e) => {
+                metrics::counter!("db_connection_pool_connection_errors", &labels).increment(1);
+                if self.breaker.record_failure() {
+                    tracing::warn!(
+                        "Not connecting to {} for {}s after {} consecutive failures: {e}",
+                        self.name,
+                        self.breaker.reset_timeout.as_secs(),
+                        self.breaker.consecutive_failures()
+                    );
+                }
+            }
+        }
+        let circuit_open = if self.breaker.state() == CircuitState::Closed {
+            0.0
+        } else {
+            1.0
+        };
+        metrics::gauge!("db_connection_pool_circuit_open", &labels).set(circuit_open);
+
+        result
+    }
+
+    async fn validate<T, P: 'static>(
+        &self,
+        conn: Box<dyn DbConnection<T, P>>,
+    ) -> Result<Box<dyn DbConnection<T, P>>> {
+        let Some(validation_query) = &self.validation_query else {
+            return Ok(conn);
+        };
+
+        let validated = if let Some(sync_conn) = conn.as_sync() {
+            sync_conn.query_arrow(validation_query, &[]).map(|_| ())
+        } else if let Some(async_conn) = conn.as_async() {
+            async_conn
+                .query_arrow(validation_query, &[])
+                .await
+                .map(|_| ())
+        } else {
+            return Err(UnableToValidateSnafu {
+                pool: self.name.clone(),
+            }
+            .build()
+            .into());
+        };
+
+        validated.context(ValidationFailedSnafu {
+            pool: self.name.clone(),
+        })?;
+        Ok(conn)
+    }
+
+    /// Records the number of connections the pool has open, and how many are idle.
+    pub fn record_pool_state(&self, connections: u32, idle_connections: u32) {
+        let labels = [("pool", self.name.clone())];
+        metrics::gauge!("db_connection_pool_connections", &labels).set(f64::from(connections));
+        metrics::gauge!("db_connection_pool_idle_connections", &labels)
+            .set(f64::from(idle_connections));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_and_probes_the_circuit() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+        assert_eq!(breaker.try_acquire(), Ok(false));
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_err());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(breaker.try_acquire(), Ok(true));
+        // Only one probe is allowed at a time, until it times out.
+        assert!(breaker.try_acquire().is_err());
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(breaker.try_acquire(), Ok(true));
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.try_acquire().is_ok());
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.consecutive_failures(), 0);
+    }
+}
@@ -22,12 +22,15 @@ pub mod clickhousepool;
 pub mod dbconnection;
 #[cfg(feature = "duckdb")]
 pub mod duckdbpool;
+pub mod health;
 #[cfg(feature = "mysql")]
 pub mod mysqlpool;
 #[cfg(feature = "odbc")]
 pub mod odbcpool;
 #[cfg(feature = "postgres")]
 pub mod postgrespool;
+pub mod query_comment;
+pub mod settings;
 #[cfg(feature = "sqlite")]
 pub mod sqlitepool;
 
@@ -19,13 +19,15 @@ use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use async_trait::async_trait;
 use mysql_async::{
     prelude::{Queryable, ToValue},
-    Params, Row, SslOpts,
+    Params, PoolConstraints, PoolOpts, Row, SslOpts,
 };
 use secrets::{get_secret_or_param, Secret};
-use snafu::{ResultExt, Snafu};
+use snafu::{OptionExt, ResultExt, Snafu};
 
 use crate::{
     dbconnection::{mysqlconn::MySQLConnection, AsyncDbConnection, DbConnection},
+    health::PoolHealth,
+    settings::PoolSettings,
     JoinPushDown,
 };
 
@@ -44,11 +46,20 @@ pub enum Error {
 
     #[snafu(display("Invalid root cert path: {path}"))]
     InvalidRootCertPathError { path: String },
+
+    #[snafu(display("Invalid connection pool settings: {source}"))]
+    InvalidPoolSettings { source: crate::settings::Error },
+
+    #[snafu(display(
+        "Invalid connection pool settings: connection_pool_min_idle is larger than connection_pool_max_size"
+    ))]
+    InvalidPoolConstraints,
 }
 
 pub struct MySQLConnectionPool {
     pool: Arc<mysql_async::Pool>,
     join_push_down: JoinPushDown,
+    health: Arc<PoolHealth>,
 }
 
 impl MySQLConnectionPool {
@@ -120,9 +131,13 @@ impl MySQLConnectionPool {
 
         connection_string = connection_string.ssl_opts(ssl_opts);
 
+        let settings = PoolSettings::from_params(&params).context(InvalidPoolSettingsSnafu)?;
+        connection_string = connection_string.pool_opts(get_pool_opts(&settings)?);
+
         let opts = mysql_async::Opts::from(connection_string);
 
         let join_push_down = get_join_context(&opts);
+        let health = Arc::new(PoolHealth::new(get_pool_name(&opts), &settings));
 
         let pool = mysql_async::Pool::new(opts);
 
@@ -136,10 +151,40 @@ impl MySQLConnectionPool {
         Ok(Self {
             pool: Arc::new(pool),
             join_push_down,
+            health,
         })
     }
 }
 
+fn get_pool_opts(settings: &PoolSettings) -> Result<PoolOpts> {
+    let default_constraints = PoolConstraints::default();
+    let min = settings
+        .min_idle
+        .map_or(default_constraints.min(), |min_idle| min_idle as usize);
+    let max = settings
+        .max_size
+        .map_or(default_constraints.max().max(min), |max_size| {
+            max_size as usize
+        });
+    let constraints = PoolConstraints::new(min, max).context(InvalidPoolConstraintsSnafu)?;
+
+    let mut pool_opts = PoolOpts::default().with_constraints(constraints);
+    if let Some(idle_timeout) = settings.idle_timeout {
+        pool_opts = pool_opts.with_inactive_connection_ttl(idle_timeout);
+    }
+    Ok(pool_opts)
+}
+
+/// Identifies the pool in logs and metrics by its database, without its user.
+fn get_pool_name(opts: &mysql_async::Opts) -> String {
+    format!(
+        "mysql://{}:{}/{}",
+        opts.ip_or_hostname(),
+        opts.tcp_port(),
+        opts.db_name().unwrap_or_default()
+    )
+}
+
 fn get_join_context(opts: &mysql_async::Opts) -> JoinPushDown {
     let mut join_context = format!("host={},port={}", opts.ip_or_hostname(), opts.tcp_port());
     if let Some(db_name) = opts.db_name() {
@@ -181,8 +226,12 @@ impl DbConnectionPool<mysql_async::Conn, &'static (dyn ToValue + Sync)> for MySQ
         &self,
     ) -> Result<Box<dyn DbConnection<mysql_async::Conn, &'static (dyn ToValue + Sync)>>> {
         let pool = Arc::clone(&self.pool);
-        let conn = pool.get_conn().await.context(ConnectionPoolRunSnafu)?;
-        Ok(Box::new(MySQLConnection::new(conn)))
+        self.health
+            .connect(async {
+                let conn = pool.get_conn().await.context(ConnectionPoolRunSnafu)?;
+                Ok(Box::new(MySQLConnection::new(conn)) as Box<dyn DbConnection<_, _>>)
+            })
+            .await
     }
 
     fn join_push_down(&self) -> JoinPushDown {
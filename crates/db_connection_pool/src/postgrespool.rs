@@ -14,8 +14,9 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
+use arrow_sql_gen::unsupported_type::UnsupportedTypeAction;
 use async_trait::async_trait;
 use bb8::ErrorSink;
 use bb8_postgres::{
@@ -32,9 +33,14 @@ use tokio_postgres;
 use super::DbConnectionPool;
 use crate::{
     dbconnection::{postgresconn::PostgresConnection, AsyncDbConnection, DbConnection},
+    health::PoolHealth,
+    settings::PoolSettings,
     JoinPushDown,
 };
 
+/// bb8 closes connections idle for 10 minutes by default.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("ConnectionPoolError: {source}"))]
@@ -76,6 +82,9 @@ pub enum Error {
         "Authentication failed. Ensure that the username and password are correctly configured."
     ))]
     InvalidUsernameOrPassword { source: tokio_postgres::Error },
+
+    #[snafu(display("Invalid connection pool settings: {source}"))]
+    InvalidPoolSettings { source: crate::settings::Error },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -83,6 +92,8 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub struct PostgresConnectionPool {
     pool: Arc<bb8::Pool<PostgresConnectionManager<MakeTlsConnector>>>,
     join_push_down: JoinPushDown,
+    health: Arc<PoolHealth>,
+    unsupported_type_action: UnsupportedTypeAction,
 }
 
 impl PostgresConnectionPool {
@@ -177,15 +188,26 @@ impl PostgresConnectionPool {
         test_postgres_connection(connection_string.as_str(), connector.clone()).await?;
 
         let join_push_down = get_join_context(&config);
+        let settings = PoolSettings::from_params(&params).context(InvalidPoolSettingsSnafu)?;
+        let unsupported_type_action = match params.get("unsupported_type_action") {
+            Some(action) => action.parse().ok().context(InvalidParameterSnafu {
+                parameter_name: "unsupported_type_action".to_string(),
+            })?,
+            None => UnsupportedTypeAction::default(),
+        };
+        let health = Arc::new(PoolHealth::new(get_pool_name(&config), &settings));
 
         let manager = PostgresConnectionManager::new(config, connector);
         let error_sink = PostgresErrorSink::new();
 
-        let pool = bb8::Pool::builder()
+        let mut builder = bb8::Pool::builder()
             .error_sink(Box::new(error_sink))
-            .build(manager)
-            .await
-            .context(ConnectionPoolSnafu)?;
+            .min_idle(settings.min_idle)
+            .idle_timeout(settings.idle_timeout.or(Some(DEFAULT_IDLE_TIMEOUT)));
+        if let Some(max_size) = settings.max_size {
+            builder = builder.max_size(max_size);
+        }
+        let pool = builder.build(manager).await.context(ConnectionPoolSnafu)?;
 
         // Test the connection
         let conn = pool.get().await.context(ConnectionPoolRunSnafu)?;
@@ -196,6 +218,8 @@ impl PostgresConnectionPool {
         Ok(PostgresConnectionPool {
             pool: Arc::new(pool.clone()),
             join_push_down,
+            health,
+            unsupported_type_action,
         })
     }
 }
@@ -227,6 +251,21 @@ fn parse_connection_string(pg_connection_string: &str) -> (String, String, Optio
     (connection_string, ssl_mode, ssl_rootcert_path)
 }
 
+/// Identifies the pool in logs and metrics by its database, without its user.
+fn get_pool_name(config: &Config) -> String {
+    let host = config
+        .get_hosts()
+        .first()
+        .map(|host| match host {
+            Host::Tcp(host) => host.clone(),
+            Host::Unix(path) => path.display().to_string(),
+        })
+        .unwrap_or_default();
+    let port = config.get_ports().first().copied().unwrap_or(5432);
+    let dbname = config.get_dbname().unwrap_or_default();
+    format!("postgres://{host}:{port}/{dbname}")
+}
+
 fn get_join_context(config: &Config) -> JoinPushDown {
     let mut join_push_context_str = String::new();
     for host in config.get_hosts() {
@@ -352,8 +391,21 @@ impl
         >,
     > {
         let pool = Arc::clone(&self.pool);
-        let conn = pool.get_owned().await.context(ConnectionPoolRunSnafu)?;
-        Ok(Box::new(PostgresConnection::new(conn)))
+        let unsupported_type_action = self.unsupported_type_action;
+        let conn = self
+            .health
+            .connect(async {
+                let conn = pool.get_owned().await.context(ConnectionPoolRunSnafu)?;
+                let conn = PostgresConnection::new(conn)
+                    .with_unsupported_type_action(unsupported_type_action);
+                Ok(Box::new(conn) as Box<dyn DbConnection<_, _>>)
+            })
+            .await;
+
+        let state = self.pool.state();
+        self.health
+            .record_pool_state(state.connections, state.idle_connections);
+        conn
     }
 
     fn join_push_down(&self) -> JoinPushDown {
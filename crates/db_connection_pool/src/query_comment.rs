@@ -0,0 +1,67 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Appends a comment identifying the query to the SQL pushed down to sources, so the load on a source can be
+//! attributed to the queries that caused it, i.e. in `pg_stat_activity`. The comment follows the
+//! [sqlcommenter](https://google.github.io/sqlcommenter/) format, i.e. `SELECT 1 /*app='dashboard'*/`.
+//!
+//! The comment is set while the scans of a query are executed, which is when they build the SQL they send.
+
+use std::cell::RefCell;
+
+thread_local! {
+    static QUERY_COMMENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Restores the comment of the enclosing scope when dropped.
+struct Restore(Option<String>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        QUERY_COMMENT.with(|comment| *comment.borrow_mut() = previous);
+    }
+}
+
+/// Runs `f` with `comment` appended to the SQL that scans build while it runs.
+pub fn with_query_comment<R>(comment: Option<String>, f: impl FnOnce() -> R) -> R {
+    let _restore = Restore(QUERY_COMMENT.with(|current| current.replace(comment)));
+    f()
+}
+
+/// Appends the comment of the query being executed, if any, to `sql`.
+#[must_use]
+pub fn apply_query_comment(sql: String) -> String {
+    QUERY_COMMENT.with(|comment| match comment.borrow().as_deref() {
+        // `*/` would end the comment early, letting the comment inject SQL.
+        Some(comment) => format!("{sql} /*{}*/", comment.replace("*/", "* /")),
+        None => sql,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_comment_in_scope() {
+        let sql = with_query_comment(Some("team=growth */ DROP".to_string()), || {
+            apply_query_comment("SELECT 1".to_string())
+        });
+        assert_eq!(sql, "SELECT 1 /*team=growth * / DROP*/");
+        assert_eq!(apply_query_comment("SELECT 1".to_string()), "SELECT 1");
+    }
+}
@@ -0,0 +1,153 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The `connection_pool_*` params shared by the pools of the SQL connectors.
+
+use std::{collections::HashMap, time::Duration};
+
+use snafu::prelude::*;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_RESET_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid parameter {parameter_name}: {value}"))]
+    InvalidParameter {
+        parameter_name: String,
+        value: String,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSettings {
+    /// The number of idle connections the pool keeps open.
+    pub min_idle: Option<u32>,
+    /// The maximum number of connections the pool opens.
+    pub max_size: Option<u32>,
+    /// How long a connection can be idle before it is closed.
+    pub idle_timeout: Option<Duration>,
+    /// A query run on each connection before it is used, i.e. `SELECT 1`, to discard broken connections.
+    pub validation_query: Option<String>,
+    /// The number of consecutive failures to connect that open the circuit of the pool.
+    pub failure_threshold: u32,
+    /// How long connecting fails fast once the circuit is open, before a connection is tried again.
+    pub reset_timeout: Duration,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            min_idle: None,
+            max_size: None,
+            idle_timeout: None,
+            validation_query: None,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            reset_timeout: DEFAULT_RESET_TIMEOUT,
+        }
+    }
+}
+
+impl PoolSettings {
+    /// Reads the `connection_pool_min_idle`, `connection_pool_max_size`, `connection_pool_idle_timeout`,
+    /// `connection_pool_validation_query`, `connection_pool_failure_threshold` and `connection_pool_reset_timeout`
+    /// params.
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Self> {
+        let mut settings = Self {
+            min_idle: parse_param(params, "connection_pool_min_idle", |v| v.parse().ok())?,
+            max_size: parse_param(params, "connection_pool_max_size", |v| {
+                v.parse().ok().filter(|max_size| *max_size > 0)
+            })?,
+            idle_timeout: parse_param(params, "connection_pool_idle_timeout", |v| {
+                fundu::parse_duration(v).ok()
+            })?,
+            validation_query: params
+                .get("connection_pool_validation_query")
+                .filter(|query| !query.trim().is_empty())
+                .cloned(),
+            ..Self::default()
+        };
+
+        if let Some(failure_threshold) =
+            parse_param(params, "connection_pool_failure_threshold", |v| {
+                v.parse().ok().filter(|threshold| *threshold > 0)
+            })?
+        {
+            settings.failure_threshold = failure_threshold;
+        }
+        if let Some(reset_timeout) = parse_param(params, "connection_pool_reset_timeout", |v| {
+            fundu::parse_duration(v).ok()
+        })? {
+            settings.reset_timeout = reset_timeout;
+        }
+
+        Ok(settings)
+    }
+}
+
+fn parse_param<T>(
+    params: &HashMap<String, String>,
+    parameter_name: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Option<T>> {
+    params
+        .get(parameter_name)
+        .map(|value| {
+            parse(value.trim()).context(InvalidParameterSnafu {
+                parameter_name,
+                value: value.clone(),
+            })
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pool_settings() {
+        let params = HashMap::from([
+            ("connection_pool_max_size".to_string(), "20".to_string()),
+            ("connection_pool_idle_timeout".to_string(), "5m".to_string()),
+            (
+                "connection_pool_validation_query".to_string(),
+                "SELECT 1".to_string(),
+            ),
+            (
+                "connection_pool_failure_threshold".to_string(),
+                "3".to_string(),
+            ),
+        ]);
+        let settings = PoolSettings::from_params(&params).expect("valid settings");
+        assert_eq!(
+            settings,
+            PoolSettings {
+                min_idle: None,
+                max_size: Some(20),
+                idle_timeout: Some(Duration::from_secs(300)),
+                validation_query: Some("SELECT 1".to_string()),
+                failure_threshold: 3,
+                reset_timeout: DEFAULT_RESET_TIMEOUT,
+            }
+        );
+
+        let params = HashMap::from([("connection_pool_max_size".to_string(), "0".to_string())]);
+        assert!(PoolSettings::from_params(&params).is_err());
+    }
+}
@@ -0,0 +1,560 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Manages the runtime as a node of a fleet of edge deployments.
+//!
+//! The `fleet` extension registers the node with a control plane, sends it a heartbeat with the state of every
+//! component (including when each dataset was last refreshed), and optionally pulls the node's spicepod from it. A
+//! pulled spicepod replaces the local `spicepod.yaml`, which the runtime then reloads like any other change.
+//!
+//! The endpoint must use https unless `allow_insecure_endpoint` is `true`, as the node's API key and spicepod are sent
+//! over it. A pulled spicepod is only written once it passes the same validation as the spicepod the runtime reloads,
+//! and is refused if it removes or changes the `fleet` extension, which stays under the node's control.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use app::AppBuilder;
+use async_trait::async_trait;
+use reqwest::{header, StatusCode};
+use serde::Serialize;
+use snafu::prelude::*;
+
+use runtime::{
+    extension::{Extension, ExtensionFactory, ExtensionManifest, Result},
+    status::{self, ComponentStates},
+    validation, Runtime,
+};
+use spicepod::{
+    reader::{self, ReadablePath, StdFileSystem},
+    spec::SpicepodDefinition,
+};
+
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The fleet extension requires the endpoint param"))]
+    MissingEndpoint {},
+
+    #[snafu(display(
+        "The fleet endpoint {endpoint} must use https, as the node's API key and spicepod are sent over it. Set allow_insecure_endpoint to true to use it anyway"
+    ))]
+    InsecureEndpoint { endpoint: String },
+
+    #[snafu(display("The fleet extension requires the node_id param when HOSTNAME isn't set"))]
+    MissingNodeId {},
+
+    #[snafu(display("Invalid {param} value {value}: {source}"))]
+    InvalidDuration {
+        param: String,
+        value: String,
+        source: fundu::ParseError,
+    },
+
+    #[snafu(display("{param} must be greater than zero"))]
+    ZeroDuration { param: String },
+
+    #[snafu(display("Unable to get fleet secret {secret_name}: {source}"))]
+    UnableToGetFleetSecret {
+        secret_name: String,
+        source: Box<dyn std::error::Error + Sync + Send>,
+    },
+
+    #[snafu(display("Unable to reach the fleet control plane: {source}"))]
+    UnableToReachControlPlane { source: reqwest::Error },
+
+    #[snafu(display("The fleet control plane returned an invalid spicepod: {source}"))]
+    InvalidSpicepod { source: serde_yaml::Error },
+
+    #[snafu(display(
+        "The spicepod pulled from the fleet control plane has problems:\n{problems}"
+    ))]
+    RejectedSpicepod { problems: String },
+
+    #[snafu(display(
+        "The spicepod pulled from the fleet control plane removes or changes the fleet extension. Change it in the node's spicepod instead"
+    ))]
+    FleetExtensionChanged {},
+
+    #[snafu(display("Unable to write the pulled spicepod to {}: {source}", path.display()))]
+    UnableToWriteSpicepod {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// The extension's params.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FleetConfig {
+    /// `endpoint`: the URL of the control plane, which must use https unless `allow_insecure_endpoint` is `true`.
+    endpoint: String,
+    /// `node_id`: how the node is identified to the control plane, the `HOSTNAME` by default.
+    node_id: String,
+    /// `secret`: the secret whose `key` authenticates the node, if the control plane requires it.
+    secret_name: Option<String>,
+    /// `heartbeat_interval`: how often a heartbeat is sent.
+    heartbeat_interval: Duration,
+    /// `config_pull_interval`: how often the node's spicepod is pulled, or never if unset.
+    config_pull_interval: Option<Duration>,
+}
+
+impl FleetConfig {
+    fn try_from_params(
+        params: &HashMap<String, String>,
+        hostname: Option<String>,
+    ) -> std::result::Result<Self, Error> {
+        let endpoint = params
+            .get("endpoint")
+            .map(|endpoint| endpoint.trim_end_matches('/').to_string())
+            .context(MissingEndpointSnafu)?;
+        let allow_insecure_endpoint = params
+            .get("allow_insecure_endpoint")
+            .is_some_and(|allow| allow.eq_ignore_ascii_case("true"));
+        ensure!(
+            allow_insecure_endpoint
+                || endpoint
+                    .get(..8)
+                    .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://")),
+            InsecureEndpointSnafu { endpoint }
+        );
+
+        Ok(Self {
+            endpoint,
+            node_id: params
+                .get("node_id")
+                .cloned()
+                .or(hostname)
+                .context(MissingNodeIdSnafu)?,
+            secret_name: params.get("secret").cloned(),
+            heartbeat_interval: parse_duration_param(params, "heartbeat_interval")?
+                .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL),
+            config_pull_interval: parse_duration_param(params, "config_pull_interval")?,
+        })
+    }
+}
+
+fn parse_duration_param(
+    params: &HashMap<String, String>,
+    param: &str,
+) -> std::result::Result<Option<Duration>, Error> {
+    let Some(value) = params.get(param) else {
+        return Ok(None);
+    };
+
+    let duration = fundu::parse_duration(value).context(InvalidDurationSnafu { param, value })?;
+    ensure!(!duration.is_zero(), ZeroDurationSnafu { param });
+
+    Ok(Some(duration))
+}
+
+#[derive(Serialize)]
+struct Heartbeat<'a> {
+    node_id: &'a str,
+    version: &'static str,
+    app: Option<String>,
+    components: ComponentStates,
+}
+
+/// A client of the control plane for one node.
+#[derive(Clone)]
+struct ControlPlane {
+    config: FleetConfig,
+    /// The `fleet` extension of the node's spicepod, which pulled spicepods must keep as is.
+    manifest: ExtensionManifest,
+    client: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl ControlPlane {
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(
+            method,
+            format!(
+                "{}/v1/nodes/{}{path}",
+                self.config.endpoint, self.config.node_id
+            ),
+        );
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+
+    async fn send_heartbeat(&self, runtime: &Runtime) -> std::result::Result<(), Error> {
+        let app = runtime
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.name.clone());
+        let heartbeat = Heartbeat {
+            node_id: &self.config.node_id,
+            version: env!("CARGO_PKG_VERSION"),
+            app,
+            components: status::component_states(),
+        };
+
+        self.request(reqwest::Method::POST, "/heartbeat")
+            .json(&heartbeat)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(UnableToReachControlPlaneSnafu)?;
+
+        Ok(())
+    }
+
+    /// Pulls the node's spicepod, returning it with its `ETag` unless it's unchanged since `etag`, or the control
+    /// plane has none for the node.
+    async fn pull_spicepod(
+        &self,
+        etag: Option<&str>,
+    ) -> std::result::Result<Option<(String, Option<String>)>, Error> {
+        let mut request = self.request(reqwest::Method::GET, "/spicepod");
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context(UnableToReachControlPlaneSnafu)?;
+        if matches!(
+            response.status(),
+            StatusCode::NOT_MODIFIED | StatusCode::NOT_FOUND
+        ) {
+            return Ok(None);
+        }
+        let response = response
+            .error_for_status()
+            .context(UnableToReachControlPlaneSnafu)?;
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToString::to_string);
+        let spicepod = response
+            .text()
+            .await
+            .context(UnableToReachControlPlaneSnafu)?;
+
+        Ok(Some((spicepod, etag)))
+    }
+
+    async fn heartbeat(self, runtime: Runtime) {
+        let mut failing = false;
+        loop {
+            match self.send_heartbeat(&runtime).await {
+                Ok(()) if failing => {
+                    tracing::info!("Fleet heartbeats are being delivered again");
+                    failing = false;
+                }
+                Ok(()) => {}
+                Err(e) if !failing => {
+                    tracing::warn!("Unable to send fleet heartbeat: {e}");
+                    failing = true;
+                }
+                Err(e) => tracing::debug!("Unable to send fleet heartbeat: {e}"),
+            }
+
+            tokio::time::sleep(self.config.heartbeat_interval).await;
+        }
+    }
+
+    async fn pull_config(self, runtime: Runtime, spicepod_dir: PathBuf, interval: Duration) {
+        let mut etag: Option<String> = None;
+        loop {
+            match self.pull_spicepod(etag.as_deref()).await {
+                Ok(Some((spicepod, new_etag))) => {
+                    match apply_spicepod(&runtime, &self.manifest, &spicepod_dir, &spicepod).await {
+                        Ok(true) => {
+                            tracing::info!(
+                                "Applied the spicepod pulled from the fleet control plane"
+                            );
+                            etag = new_etag;
+                        }
+                        Ok(false) => etag = new_etag,
+                        Err(
+                            e @ (Error::InvalidSpicepod { .. }
+                            | Error::RejectedSpicepod { .. }
+                            | Error::FleetExtensionChanged { .. }),
+                        ) => {
+                            // Wait for the control plane to fix it rather than retrying the same spicepod.
+                            tracing::warn!("{e}");
+                            etag = new_etag;
+                        }
+                        Err(e) => tracing::warn!("{e}"),
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Unable to pull spicepod from the fleet control plane: {e}")
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// The spicepod directory with its spicepod replaced by a pulled one, so the pulled spicepod can be validated with the
+/// files it references before it's written.
+struct PulledSpicepodFileSystem<'a> {
+    spicepod_dir: &'a Path,
+    spicepod: &'a str,
+}
+
+impl<T> ReadablePath<T> for PulledSpicepodFileSystem<'_> {
+    fn open(&self, path: impl Into<PathBuf>) -> reader::Result<Box<dyn io::Read>> {
+        let path = path.into();
+        let is_spicepod = path.parent() == Some(self.spicepod_dir)
+            && path
+                .file_name()
+                .is_some_and(|name| name == "spicepod.yaml" || name == "spicepod.yml");
+        if is_spicepod {
+            return Ok(Box::new(io::Cursor::new(self.spicepod.as_bytes().to_vec())));
+        }
+        <StdFileSystem as ReadablePath<T>>::open(&StdFileSystem, path)
+    }
+}
+
+/// Replaces the spicepod in `spicepod_dir` with `spicepod` if it's valid, keeps the `fleet` extension `manifest` and
+/// is different, returning whether it was replaced.
+async fn apply_spicepod(
+    runtime: &Runtime,
+    manifest: &ExtensionManifest,
+    spicepod_dir: &Path,
+    spicepod: &str,
+) -> std::result::Result<bool, Error> {
+    serde_yaml::from_str::<SpicepodDefinition>(spicepod).context(InvalidSpicepodSnafu)?;
+
+    let path = ["spicepod.yaml", "spicepod.yml"]
+        .iter()
+        .map(|name| spicepod_dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| spicepod_dir.join("spicepod.yaml"));
+    if std::fs::read_to_string(&path).is_ok_and(|current| current == spicepod) {
+        return Ok(false);
+    }
+
+    let fs = PulledSpicepodFileSystem {
+        spicepod_dir,
+        spicepod,
+    };
+    let mut diagnostics = spicepod::validation::validate_from(&fs, spicepod_dir);
+    match AppBuilder::build_from_filesystem(&fs, spicepod_dir) {
+        Ok(app) => {
+            ensure!(
+                app.extensions.get("fleet") == Some(manifest),
+                FleetExtensionChangedSnafu
+            );
+            diagnostics.extend(
+                validation::validate_app(&app, &*runtime.secrets_provider.read().await).await,
+            );
+        }
+        Err(e) => diagnostics.push(validation::Diagnostic::new("", e.to_string())),
+    }
+    ensure!(
+        diagnostics.is_empty(),
+        RejectedSpicepodSnafu {
+            problems: diagnostics
+                .iter()
+                .map(|diagnostic| format!("  {diagnostic}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    );
+
+    // Write a temporary file first, so the runtime never reloads a partially written spicepod.
+    let tmp_path = path.with_extension("yaml.tmp");
+    std::fs::write(&tmp_path, spicepod)
+        .and_then(|()| std::fs::rename(&tmp_path, &path))
+        .context(UnableToWriteSpicepodSnafu { path })?;
+
+    Ok(true)
+}
+
+pub struct FleetExtension {
+    manifest: ExtensionManifest,
+    config: Option<FleetConfig>,
+}
+
+impl FleetExtension {
+    #[must_use]
+    pub fn new(manifest: ExtensionManifest) -> Self {
+        FleetExtension {
+            manifest,
+            config: None,
+        }
+    }
+
+    async fn get_api_key(
+        runtime: &Runtime,
+        secret_name: &str,
+    ) -> std::result::Result<Option<String>, Error> {
+        let secret = runtime
+            .secrets_provider
+            .read()
+            .await
+            .get_secret(secret_name)
+            .await
+            .context(UnableToGetFleetSecretSnafu { secret_name })?;
+
+        Ok(secret.and_then(|secret| secret.get("key").map(ToString::to_string)))
+    }
+}
+
+#[async_trait]
+impl Extension for FleetExtension {
+    fn name(&self) -> &'static str {
+        "fleet"
+    }
+
+    async fn initialize(&mut self, _runtime: &mut Runtime) -> Result<()> {
+        if !self.manifest.enabled {
+            return Ok(());
+        }
+
+        let config =
+            FleetConfig::try_from_params(&self.manifest.params, std::env::var("HOSTNAME").ok())
+                .map_err(|e| runtime::extension::Error::UnableToInitializeExtension {
+                    source: e.into(),
+                })?;
+        self.config = Some(config);
+
+        Ok(())
+    }
+
+    async fn on_start(&mut self, runtime: &Runtime) -> Result<()> {
+        let Some(config) = self.config.clone() else {
+            return Ok(());
+        };
+
+        let api_key = match &config.secret_name {
+            Some(secret_name) => Self::get_api_key(runtime, secret_name).await.map_err(|e| {
+                runtime::extension::Error::UnableToStartExtension { source: e.into() }
+            })?,
+            None => None,
+        };
+        let control_plane = ControlPlane {
+            config,
+            manifest: self.manifest.clone(),
+            client: reqwest::Client::new(),
+            api_key,
+        };
+
+        // The first heartbeat registers the node, so report it as failed to start if the control plane can't be
+        // reached, even though heartbeats keep being retried.
+        let registered = control_plane.send_heartbeat(runtime).await;
+        tracing::info!(
+            "Sending fleet heartbeats for node {} to {}",
+            control_plane.config.node_id,
+            control_plane.config.endpoint
+        );
+        tokio::spawn(control_plane.clone().heartbeat(runtime.clone()));
+
+        if let Some(interval) = control_plane.config.config_pull_interval {
+            match runtime.spicepod_path.clone() {
+                Some(spicepod_dir) => {
+                    tokio::spawn(control_plane.pull_config(
+                        runtime.clone(),
+                        spicepod_dir,
+                        interval,
+                    ));
+                }
+                None => tracing::warn!(
+                    "config_pull_interval is set, but the runtime isn't watching a spicepod to apply pulled changes to"
+                ),
+            }
+        }
+
+        registered
+            .map_err(|e| runtime::extension::Error::UnableToStartExtension { source: e.into() })
+    }
+}
+
+pub struct FleetExtensionFactory {
+    manifest: ExtensionManifest,
+}
+
+impl FleetExtensionFactory {
+    #[must_use]
+    pub fn new(manifest: ExtensionManifest) -> Self {
+        FleetExtensionFactory { manifest }
+    }
+}
+
+impl ExtensionFactory for FleetExtensionFactory {
+    fn create(&self) -> Box<dyn Extension> {
+        Box::new(FleetExtension::new(self.manifest.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn fleet_config_from_params() {
+        let config = FleetConfig::try_from_params(
+            &params(&[
+                ("endpoint", "https://fleet.example.com/"),
+                ("heartbeat_interval", "1m"),
+            ]),
+            Some("edge-1".to_string()),
+        )
+        .expect("valid params");
+        assert_eq!(config.endpoint, "https://fleet.example.com");
+        assert_eq!(config.node_id, "edge-1");
+        assert_eq!(config.heartbeat_interval, Duration::from_secs(60));
+        assert_eq!(config.config_pull_interval, None);
+
+        assert!(FleetConfig::try_from_params(&params(&[]), Some("edge-1".to_string())).is_err());
+        assert!(matches!(
+            FleetConfig::try_from_params(
+                &params(&[("endpoint", "http://fleet.example.com")]),
+                Some("edge-1".to_string())
+            ),
+            Err(Error::InsecureEndpoint { .. })
+        ));
+        assert!(FleetConfig::try_from_params(
+            &params(&[
+                ("endpoint", "http://localhost:8080"),
+                ("allow_insecure_endpoint", "true")
+            ]),
+            Some("edge-1".to_string())
+        )
+        .is_ok());
+        assert!(FleetConfig::try_from_params(
+            &params(&[("endpoint", "https://fleet.example.com")]),
+            None
+        )
+        .is_err());
+    }
+}
@@ -76,6 +76,8 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 #[derive(Debug, Clone)]
 pub struct FlightClient {
     token: Option<String>,
+    /// A token to authenticate with instead of a handshake, i.e. for a Spice runtime that validates bearer tokens.
+    bearer_token: Option<String>,
     flight_client: FlightServiceClient<Channel>,
     username: String,
     password: String,
@@ -103,6 +105,7 @@ impl FlightClient {
                 .max_encoding_message_size(100 * 1024 * 1024)
                 .max_decoding_message_size(100 * 1024 * 1024),
             token: None,
+            bearer_token: None,
             username: username.to_string(),
             password: password.to_string(),
             url: url.to_string(),
@@ -281,6 +284,11 @@ impl FlightClient {
     }
 
     async fn authenticate_basic_token(&mut self) -> Result<()> {
+        if let Some(bearer_token) = &self.bearer_token {
+            self.token = Some(bearer_token.clone());
+            return Ok(());
+        }
+
         let cmd = HandshakeRequest {
             protocol_version: 0,
             payload: Bytes::default(),
@@ -306,6 +314,13 @@ impl FlightClient {
         Ok(())
     }
 
+    /// Authenticates with `token` as a bearer token, instead of a handshake with the username and password.
+    #[must_use]
+    pub fn with_bearer_token(mut self, token: String) -> Self {
+        self.bearer_token = Some(token);
+        self
+    }
+
     pub fn url(&self) -> &str {
         &self.url
     }
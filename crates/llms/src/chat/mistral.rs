@@ -26,12 +26,17 @@ use mistralrs::{
 use mistralrs_core::{LocalModelPaths, ModelPaths, Pipeline};
 use snafu::ResultExt;
 use std::{path::Path, str::FromStr, sync::Arc};
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
 
 pub struct MistralLlama {
     pipeline: Arc<MistralRs>,
     tx: Sender<MistralRsponse>,
-    rx: Receiver<MistralRsponse>,
+    /// The responses of the pipeline, locked for the whole of a request so concurrent requests don't read each
+    /// other's responses.
+    rx: Mutex<Receiver<MistralRsponse>>,
 }
 
 impl MistralLlama {
@@ -225,7 +230,7 @@ impl MistralLlama {
             )
             .build(),
             tx,
-            rx,
+            rx: Mutex::new(rx),
         })
     }
 
@@ -250,7 +255,8 @@ impl MistralLlama {
 
 #[async_trait]
 impl Chat for MistralLlama {
-    async fn run(&mut self, prompt: String) -> Result<Option<String>> {
+    async fn run(&self, prompt: String) -> Result<Option<String>> {
+        let mut rx = self.rx.lock().await;
         let r = self.to_request(prompt);
         self.pipeline
             .get_sender()
@@ -258,7 +264,7 @@ impl Chat for MistralLlama {
             .await
             .boxed()
             .context(FailedToRunModelSnafu)?;
-        match self.rx.recv().await {
+        match rx.recv().await {
             Some(response) => match response {
                 MistralRsponse::CompletionDone(cr) => {
                     println!(
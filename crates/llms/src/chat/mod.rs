@@ -130,10 +130,10 @@ pub fn message_to_content(message: &ChatCompletionRequestMessage) -> String {
 
 #[async_trait]
 pub trait Chat: Sync + Send {
-    async fn run(&mut self, prompt: String) -> Result<Option<String>>;
+    async fn run(&self, prompt: String) -> Result<Option<String>>;
 
     async fn stream<'a>(
-        &mut self,
+        &self,
         prompt: String,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Option<String>>> + Send>>> {
         let resp = self.run(prompt).await;
@@ -142,7 +142,7 @@ pub trait Chat: Sync + Send {
 
     #[allow(deprecated)]
     async fn chat_stream(
-        &mut self,
+        &self,
         req: CreateChatCompletionRequest,
     ) -> Result<ChatCompletionResponseStream, OpenAIError> {
         let model_id = req.model.clone();
@@ -206,7 +206,7 @@ pub trait Chat: Sync + Send {
     /// implementation will be constructed based on the trait's [`run`] method.
     #[allow(deprecated)]
     async fn chat_request(
-        &mut self,
+        &self,
         req: CreateChatCompletionRequest,
     ) -> Result<CreateChatCompletionResponse, OpenAIError> {
         let model_id = req.model.clone();
@@ -0,0 +1,199 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+     https://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+#![allow(clippy::module_name_repetitions)]
+use std::path::{Path, PathBuf};
+
+use async_openai::types::EmbeddingInput;
+use async_trait::async_trait;
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use hf_hub::{api::sync::ApiBuilder, Repo, RepoType};
+use snafu::ResultExt;
+use tokenizers::{Tokenizer, TruncationParams};
+
+use super::{
+    normalize, Embed, Error, FailedToCreateEmbeddingSnafu, FailedToLoadModelSnafu,
+    FailedToPrepareInputSnafu, Pooling, Result,
+};
+
+/// The position embeddings of BERT models, when the config of the model doesn't list them.
+const DEFAULT_MAX_POSITION_EMBEDDINGS: usize = 512;
+
+/// A BERT based sentence-transformers style model, i.e. `all-MiniLM-L6-v2`, BGE or E5, run on the CPU.
+pub struct CandleEmbedding {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+    size: i32,
+    pooling: Pooling,
+    normalize: bool,
+}
+
+impl CandleEmbedding {
+    /// Downloads the model `model_id` at `revision` (`main` by default) from Hugging Face, unless it is cached.
+    pub fn from_hf(
+        model_id: &str,
+        revision: Option<&str>,
+        hf_token: Option<String>,
+        pooling: Pooling,
+        normalize: bool,
+    ) -> Result<Self> {
+        let api = ApiBuilder::new()
+            .with_token(hf_token)
+            .build()
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+        let repo = api.repo(Repo::with_revision(
+            model_id.to_string(),
+            RepoType::Model,
+            revision.unwrap_or("main").to_string(),
+        ));
+
+        let config = repo
+            .get("config.json")
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+        let tokenizer = repo
+            .get("tokenizer.json")
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+        // Older models only publish PyTorch weights.
+        let weights = match repo.get("model.safetensors") {
+            Ok(weights) => weights,
+            Err(_) => repo
+                .get("pytorch_model.bin")
+                .boxed()
+                .context(FailedToLoadModelSnafu)?,
+        };
+
+        Self::try_new(&weights, &config, &tokenizer, pooling, normalize)
+    }
+
+    pub fn try_new(
+        weights: &Path,
+        config: &Path,
+        tokenizer: &Path,
+        pooling: Pooling,
+        normalize: bool,
+    ) -> Result<Self> {
+        let config = std::fs::read_to_string(config)
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+        let sizes: serde_json::Value = serde_json::from_str(&config)
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+        let size = sizes
+            .get("hidden_size")
+            .and_then(serde_json::Value::as_i64)
+            .and_then(|size| i32::try_from(size).ok())
+            .ok_or_else(|| Error::FailedToLoadModel {
+                source: "The config of the model doesn't have a hidden_size".into(),
+            })?;
+        let max_length = sizes
+            .get("max_position_embeddings")
+            .and_then(serde_json::Value::as_u64)
+            .and_then(|length| usize::try_from(length).ok())
+            .unwrap_or(DEFAULT_MAX_POSITION_EMBEDDINGS);
+        let config: Config = serde_json::from_str(&config)
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+
+        let mut tokenizer = Tokenizer::from_file(tokenizer).context(FailedToLoadModelSnafu)?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length,
+                ..TruncationParams::default()
+            }))
+            .context(FailedToLoadModelSnafu)?;
+
+        let device = Device::Cpu;
+        let vb = if weights.extension().is_some_and(|ext| ext == "bin") {
+            VarBuilder::from_pth(weights, DTYPE, &device)
+        } else {
+            // SAFETY: the weights are memory mapped read-only, and aren't modified while the model is loaded.
+            unsafe {
+                VarBuilder::from_mmaped_safetensors(&[PathBuf::from(weights)], DTYPE, &device)
+            }
+        }
+        .boxed()
+        .context(FailedToLoadModelSnafu)?;
+        let model = BertModel::load(vb, &config)
+            .boxed()
+            .context(FailedToLoadModelSnafu)?;
+
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            size,
+            pooling,
+            normalize,
+        })
+    }
+
+    fn tokenize(&self, text: &str) -> Result<Vec<u32>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .context(FailedToPrepareInputSnafu)?;
+        Ok(encoding.get_ids().to_vec())
+    }
+
+    fn embed_tokens(&self, ids: &[u32]) -> Result<Vec<f32>> {
+        if ids.is_empty() {
+            return Err(Error::FailedToPrepareInput {
+                source: "Unable to embed an empty input".into(),
+            });
+        }
+
+        let tokens = (|| {
+            let input_ids = Tensor::new(ids, &self.device)?.unsqueeze(0)?;
+            let token_type_ids = input_ids.zeros_like()?;
+            self.model
+                .forward(&input_ids, &token_type_ids)?
+                .squeeze(0)?
+                .to_vec2::<f32>()
+        })()
+        .boxed()
+        .context(FailedToCreateEmbeddingSnafu)?;
+
+        let mut embedding = self.pooling.pool(&tokens);
+        if self.normalize {
+            normalize(&mut embedding);
+        }
+        Ok(embedding)
+    }
+}
+
+#[async_trait]
+impl Embed for CandleEmbedding {
+    async fn embed(&mut self, input: EmbeddingInput) -> Result<Vec<Vec<f32>>> {
+        // Each input is embedded on its own, so no input attends to the padding of a batch.
+        let inputs = match input {
+            EmbeddingInput::String(text) => vec![self.tokenize(&text)?],
+            EmbeddingInput::StringArray(texts) => texts
+                .iter()
+                .map(|text| self.tokenize(text))
+                .collect::<Result<_>>()?,
+            EmbeddingInput::IntegerArray(ids) => vec![ids],
+            EmbeddingInput::ArrayOfIntegerArray(ids) => ids,
+        };
+
+        inputs.iter().map(|ids| self.embed_tokens(ids)).collect()
+    }
+
+    fn size(&self) -> i32 {
+        self.size
+    }
+}
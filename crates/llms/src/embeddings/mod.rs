@@ -18,6 +18,10 @@ use async_openai::{
 };
 use async_trait::async_trait;
 use snafu::Snafu;
+use std::{fmt, path::Path, str::FromStr};
+
+#[cfg(feature = "candle")]
+pub mod candle;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -30,6 +34,17 @@ pub enum Error {
     FailedToCreateEmbedding {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[snafu(display("Failed to load embedding model: {source}"))]
+    FailedToLoadModel {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Local embedding model file, expected at {expected_path}, not found"))]
+    LocalModelNotFound { expected_path: String },
+
+    #[snafu(display("Unknown pooling strategy {pooling}, expected cls, mean or max"))]
+    UnknownPooling { pooling: String },
 }
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -75,3 +90,162 @@ pub trait Embed: Sync + Send {
         })
     }
 }
+
+/// How the embeddings of the tokens of an input are combined into the embedding of the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pooling {
+    /// The embedding of the first (`[CLS]`) token, as used by BGE models.
+    Cls,
+    /// The average of the token embeddings, as used by sentence-transformers and E5 models.
+    #[default]
+    Mean,
+    /// The maximum of each dimension over the token embeddings.
+    Max,
+}
+
+impl Pooling {
+    /// Combines the embeddings of the tokens of an input, which has at least one token.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn pool(self, tokens: &[Vec<f32>]) -> Vec<f32> {
+        let Some(first) = tokens.first() else {
+            return vec![];
+        };
+
+        match self {
+            Pooling::Cls => first.clone(),
+            Pooling::Mean => {
+                let mut pooled = vec![0.0; first.len()];
+                for token in tokens {
+                    for (p, v) in pooled.iter_mut().zip(token) {
+                        *p += v;
+                    }
+                }
+                let count = tokens.len() as f32;
+                pooled.iter_mut().for_each(|p| *p /= count);
+                pooled
+            }
+            Pooling::Max => {
+                let mut pooled = first.clone();
+                for token in &tokens[1..] {
+                    for (p, v) in pooled.iter_mut().zip(token) {
+                        *p = p.max(*v);
+                    }
+                }
+                pooled
+            }
+        }
+    }
+}
+
+impl FromStr for Pooling {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cls" => Ok(Pooling::Cls),
+            "mean" => Ok(Pooling::Mean),
+            "max" => Ok(Pooling::Max),
+            _ => Err(Error::UnknownPooling {
+                pooling: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Pooling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pooling::Cls => write!(f, "cls"),
+            Pooling::Mean => write!(f, "mean"),
+            Pooling::Max => write!(f, "max"),
+        }
+    }
+}
+
+/// Scales `embedding` to a length of 1, so the dot product of two embeddings is their cosine similarity.
+pub fn normalize(embedding: &mut [f32]) {
+    let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// Loads a sentence-transformers style model, i.e. `BAAI/bge-small-en-v1.5`, from Hugging Face to embed locally.
+#[allow(unused_variables)]
+pub fn create_hf_embedding(
+    model_id: &str,
+    revision: Option<&str>,
+    hf_token: Option<String>,
+    pooling: Pooling,
+    normalize: bool,
+) -> Result<Box<dyn Embed>> {
+    #[cfg(feature = "candle")]
+    {
+        candle::CandleEmbedding::from_hf(model_id, revision, hf_token, pooling, normalize)
+            .map(|x| Box::new(x) as Box<dyn Embed>)
+    }
+    #[cfg(not(feature = "candle"))]
+    {
+        Err(Error::FailedToLoadModel {
+            source: "No local embedding model feature enabled".into(),
+        })
+    }
+}
+
+/// Loads a sentence-transformers style model from its weights, `config.json` and `tokenizer.json` on disk.
+#[allow(unused_variables)]
+pub fn create_local_embedding(
+    weights_path: &str,
+    config_path: &str,
+    tokenizer_path: &str,
+    pooling: Pooling,
+    normalize: bool,
+) -> Result<Box<dyn Embed>> {
+    for path in [weights_path, config_path, tokenizer_path] {
+        if !Path::new(path).exists() {
+            return Err(Error::LocalModelNotFound {
+                expected_path: path.to_string(),
+            });
+        }
+    }
+
+    #[cfg(feature = "candle")]
+    {
+        candle::CandleEmbedding::try_new(
+            Path::new(weights_path),
+            Path::new(config_path),
+            Path::new(tokenizer_path),
+            pooling,
+            normalize,
+        )
+        .map(|x| Box::new(x) as Box<dyn Embed>)
+    }
+    #[cfg(not(feature = "candle"))]
+    {
+        Err(Error::FailedToLoadModel {
+            source: "No local embedding model feature enabled".into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pools_and_normalizes_token_embeddings() {
+        let tokens = vec![vec![3.0, 0.0], vec![1.0, 4.0]];
+
+        assert_eq!(Pooling::Cls.pool(&tokens), vec![3.0, 0.0]);
+        assert_eq!(Pooling::Mean.pool(&tokens), vec![2.0, 2.0]);
+        assert_eq!(Pooling::Max.pool(&tokens), vec![3.0, 4.0]);
+
+        let mut embedding = Pooling::Max.pool(&tokens);
+        normalize(&mut embedding);
+        assert_eq!(embedding, vec![0.6, 0.8]);
+
+        assert_eq!("CLS".parse::<Pooling>().ok(), Some(Pooling::Cls));
+        assert!("sum".parse::<Pooling>().is_err());
+    }
+}
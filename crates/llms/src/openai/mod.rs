@@ -83,7 +83,7 @@ impl Openai {
 
 #[async_trait]
 impl Chat for Openai {
-    async fn run(&mut self, prompt: String) -> ChatResult<Option<String>> {
+    async fn run(&self, prompt: String) -> ChatResult<Option<String>> {
         let req = CreateChatCompletionRequestArgs::default()
             .model(self.model.clone())
             .messages(vec![ChatCompletionRequestSystemMessageArgs::default()
@@ -110,7 +110,7 @@ impl Chat for Openai {
     }
 
     async fn stream<'a>(
-        &mut self,
+        &self,
         prompt: String,
     ) -> ChatResult<Pin<Box<dyn Stream<Item = ChatResult<Option<String>>> + Send>>> {
         let req = CreateChatCompletionRequestArgs::default()
@@ -145,7 +145,7 @@ impl Chat for Openai {
     }
 
     async fn chat_stream(
-        &mut self,
+        &self,
         req: CreateChatCompletionRequest,
     ) -> Result<ChatCompletionResponseStream, OpenAIError> {
         let mut inner_req = req.clone();
@@ -156,7 +156,7 @@ impl Chat for Openai {
     /// An OpenAI-compatible interface for the `v1/chat/completion` `Chat` trait. If not implemented, the default
     /// implementation will be constructed based on the trait's [`run`] method.
     async fn chat_request(
-        &mut self,
+        &self,
         req: CreateChatCompletionRequest,
     ) -> Result<CreateChatCompletionResponse, OpenAIError> {
         let mut inner_req = req.clone();
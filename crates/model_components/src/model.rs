@@ -24,11 +24,14 @@ use crate::{
 use arrow::record_batch::RecordBatch;
 use secrets::Secret;
 use snafu::prelude::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 pub struct Model {
     runnable: Box<dyn Runnable>,
     pub model: spicepod::component::model::Model,
+    /// The version of the model loaded, for models loaded from a registry.
+    pub version: Option<String>,
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -72,14 +75,24 @@ impl Model {
             return UnableToLoadRequiredSecretsSnafu {}.fail();
         };
 
-        let mut params = std::collections::HashMap::new();
-        params.insert("name".to_string(), model.name.to_string());
-        params.insert("path".to_string(), path(&model.from));
-        params.insert("from".to_string(), path(&model.from));
-        params.insert("files".to_string(), model.files.join(",").to_string());
-
         let model_source: Option<Box<dyn ModelSource>> = source.into();
         if let Some(model_source) = model_source {
+            let mut params = params(&model);
+            // Without the registry, a source falls back to the reference of `from`, i.e. a cached branch.
+            let version = match model_source
+                .version(secret.clone(), Arc::new(Some(params.clone())))
+                .await
+            {
+                Ok(version) => version,
+                Err(e) => {
+                    tracing::warn!("Unable to resolve the version of model {}: {e}", model.name);
+                    None
+                }
+            };
+            if let Some(version) = &version {
+                params.insert("version".to_string(), version.clone());
+            }
+
             let path = model_source
                 .pull(secret, Arc::new(Option::from(params)))
                 .await
@@ -92,6 +105,7 @@ impl Model {
                         Ok(Self {
                             runnable,
                             model: model.clone(),
+                            version,
                         })
                     }
                     Err(_) => Err(Error::UnableToLoadModel {
@@ -115,8 +129,42 @@ impl Model {
         }
     }
 
+    /// Returns the version of `model` its registry currently points to, or `None` if its source isn't a registry.
+    pub async fn registry_version(
+        model: &spicepod::component::model::Model,
+        secret: Secret,
+    ) -> Result<Option<String>> {
+        let Ok(source) = model.from.parse::<ModelSourceType>() else {
+            return Ok(None);
+        };
+        let model_source: Option<Box<dyn ModelSource>> = source.into();
+        let Some(model_source) = model_source else {
+            return Ok(None);
+        };
+
+        model_source
+            .version(secret, Arc::new(Some(params(model))))
+            .await
+            .context(UnableToLoadModelSnafu)
+    }
+
+    /// The number of rows a forecasting model reads from its dataset, if it is fixed by the model.
+    #[must_use]
+    pub fn lookback_size(&self) -> Option<usize> {
+        self.runnable.lookback_size()
+    }
+
     pub fn run(&self, data: Vec<RecordBatch>) -> Result<RecordBatch> {
         let result = self.runnable.run(data).context(UnableToRunModelSnafu {})?;
         Ok(result)
     }
 }
+
+fn params(model: &spicepod::component::model::Model) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    params.insert("name".to_string(), model.name.to_string());
+    params.insert("path".to_string(), path(&model.from));
+    params.insert("from".to_string(), path(&model.from));
+    params.insert("files".to_string(), model.files.join(",").to_string());
+    params
+}
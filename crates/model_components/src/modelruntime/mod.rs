@@ -48,6 +48,11 @@ pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub trait Runnable: Send + Sync {
     // Run inference with the input and loaded model
     fn run(&self, input: Vec<RecordBatch>) -> Result<RecordBatch, Error>;
+
+    /// The number of rows a forecasting model reads, for an input of the form `[1, lookback_size, num_variates]`.
+    fn lookback_size(&self) -> Option<usize> {
+        None
+    }
 }
 
 /// A `ModelRuntime` loads a model into it supported `ModelFormat`.
@@ -16,11 +16,15 @@ limitations under the License.
 
 use super::{ModelRuntime, Runnable};
 use crate::modelruntime::ModelFormat;
+use arrow::array::Array;
 use arrow::array::ArrayRef;
+use arrow::array::AsArray;
 use arrow::array::Float32Array;
 use arrow::array::Float64Array;
+use arrow::compute::cast;
 use arrow::datatypes::DataType;
 use arrow::datatypes::Field;
+use arrow::datatypes::Float32Type;
 use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
 use snafu::prelude::*;
@@ -71,6 +75,110 @@ impl Model {
 
         Ok([shape[0], shape[1], shape[2]])
     }
+
+    /// Returns the number of rows the model predicts at once, if it is fixed, and the number of features of each row,
+    /// for a tabular model with an input of the form `[batch_size, num_features]`.
+    fn tabular_input_shape(&self) -> Result<Option<(Option<usize>, usize)>> {
+        let fact = self.model.model().input_fact(0).context(TractSnafu)?;
+        if fact.shape.rank() != 2 {
+            return Ok(None);
+        }
+
+        let dim = |i: usize| {
+            fact.shape[i]
+                .as_i64()
+                .and_then(|dim| usize::try_from(dim).ok())
+        };
+        let Some(num_features) = dim(1) else {
+            return Err(Error::ShapeError {
+                source: ndarray::ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape),
+            });
+        };
+
+        Ok(Some((dim(0), num_features)))
+    }
+
+    /// Predicts a value for each row of `input`, from its numeric columns other than `ts`.
+    fn run_tabular(
+        &self,
+        input: &[RecordBatch],
+        batch_size: Option<usize>,
+        num_features: usize,
+    ) -> Result<RecordBatch> {
+        let return_schema = Arc::new(Schema::new(vec![Field::new("y", DataType::Float32, false)]));
+
+        let mut features: Vec<f32> = vec![];
+        for batch in input {
+            let columns = batch
+                .schema()
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .filter(|(field, _)| field.name() != "ts" && field.data_type().is_numeric())
+                .map(|(_, column)| cast(column, &DataType::Float32))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .context(ArrowSnafu)?;
+            if columns.len() != num_features {
+                return Err(Error::TractError {
+                    source: tract_core::anyhow::Error::msg(format!(
+                        "Number of features {} does not match expected ({num_features}) from model",
+                        columns.len(),
+                    )),
+                });
+            }
+
+            let columns = columns
+                .iter()
+                .map(|column| column.as_primitive::<Float32Type>())
+                .collect_vec();
+            for row in 0..batch.num_rows() {
+                features.extend(columns.iter().map(|column| {
+                    if column.is_null(row) {
+                        f32::NAN
+                    } else {
+                        column.value(row)
+                    }
+                }));
+            }
+        }
+
+        let num_rows = features.len() / num_features;
+        let batch_size = batch_size.unwrap_or(num_rows).max(1);
+        let mut predictions: Vec<f32> = Vec::with_capacity(num_rows);
+        for chunk in features.chunks(batch_size * num_features) {
+            let rows = chunk.len() / num_features;
+            // A model with a fixed batch size predicts a padded last batch.
+            let padded_rows = batch_size.max(rows);
+            let mut chunk = chunk.to_vec();
+            chunk.resize(padded_rows * num_features, 0.0);
+
+            let tensor: Tensor =
+                tract_ndarray::Array2::from_shape_vec((padded_rows, num_features), chunk)
+                    .context(ShapeSnafu)?
+                    .into_tensor();
+            let output = self.model.run(tvec!(tensor.into())).context(TractSnafu)?;
+            let output = output[0].cast_to::<f32>().context(TractSnafu)?;
+            let output = output.as_slice::<f32>().context(TractSnafu)?;
+            if output.is_empty() || output.len() % padded_rows != 0 {
+                return Err(Error::TractError {
+                    source: tract_core::anyhow::Error::msg(format!(
+                        "The model returned {} values for {padded_rows} rows",
+                        output.len()
+                    )),
+                });
+            }
+
+            // A model with several outputs per row, like class probabilities, predicts the first.
+            let outputs_per_row = output.len() / padded_rows;
+            predictions.extend(output.iter().step_by(outputs_per_row).take(rows));
+        }
+
+        RecordBatch::try_new(
+            return_schema,
+            vec![Arc::new(Float32Array::from(predictions))],
+        )
+        .context(ArrowSnafu)
+    }
 }
 
 impl ModelRuntime for Tract {
@@ -93,6 +201,10 @@ fn load_tract_model(path: &str) -> TractResult<Plan> {
 
 impl Runnable for Model {
     fn run(&self, input: Vec<RecordBatch>) -> std::result::Result<RecordBatch, super::Error> {
+        if let Some((batch_size, num_features)) = self.tabular_input_shape()? {
+            return Ok(self.run_tabular(&input, batch_size, num_features)?);
+        }
+
         {
             let this = &self;
             let reader: &[RecordBatch] = &input;
@@ -179,4 +291,10 @@ impl Runnable for Model {
             Ok(record_batch)
         }
     }
+
+    fn lookback_size(&self) -> Option<usize> {
+        self.try_get_input_shape()
+            .ok()
+            .map(|[_, lookback_size, _]| lookback_size)
+    }
 }
@@ -19,6 +19,7 @@ use super::ModelSource;
 use async_trait::async_trait;
 use regex::Regex;
 use secrets::Secret;
+use serde::Deserialize;
 use snafu::prelude::*;
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -73,26 +74,13 @@ impl ModelSource for Huggingface {
             .build());
         };
 
-        let Ok(re) = Regex::new(
-            r"\A(huggingface:)(huggingface\.co\/)?(?<org>[\w\-]+)\/(?<model>[\w\-]+)(:(?<revision>[\w\d\-\.]+))?\z",
-        ) else {
-            return Err(super::UnableToLoadConfigSnafu {
-                reason: "Invalid regex",
-            }
-            .build());
-        };
-        let Some(caps) = re.captures(remote_path.as_str()) else {
-            return Err(super::UnableToLoadConfigSnafu {
-                reason: format!("from is invalid for huggingface source: {remote_path}"),
-            }
-            .build());
-        };
-
-        let revision = match caps["revision"].to_owned() {
-            s if s.is_empty() => "main".to_string(),
-            s if s == "latest" => "main".to_string(),
-            _ => caps["revision"].to_string(),
-        };
+        let (org, model, revision) = parse(&remote_path)?;
+        // A revision resolved by `version`, so that a branch or tag is cached per commit.
+        let revision = params
+            .as_ref()
+            .as_ref()
+            .and_then(|p| p.get("version"))
+            .map_or(revision, ToString::to_string);
 
         let versioned_path = format!("{local_path}/{revision}");
 
@@ -106,26 +94,21 @@ impl ModelSource for Huggingface {
         for file in files {
             let file_name = format!("{p}/{file}");
 
+            if file.to_lowercase().ends_with(".onnx") {
+                onnx_file_name.clone_from(&file_name);
+            }
+
             if std::fs::metadata(file_name.clone()).is_ok() {
                 tracing::info!("File already exists: {}, skipping download", file_name);
 
                 continue;
             }
 
-            let download_url = format!(
-                "https://huggingface.co/{}/{}/resolve/{}/{}",
-                caps["org"].to_owned(),
-                caps["model"].to_owned(),
-                revision,
-                file,
-            );
+            let download_url =
+                format!("https://huggingface.co/{org}/{model}/resolve/{revision}/{file}");
 
             tracing::info!("Downloading model: {}", download_url);
 
-            if file.to_lowercase().ends_with(".onnx") {
-                onnx_file_name.clone_from(&file_name);
-            }
-
             let client = reqwest::Client::new();
             let response = client
                 .get(download_url)
@@ -149,4 +132,66 @@ impl ModelSource for Huggingface {
 
         Ok(onnx_file_name)
     }
+
+    /// Resolves the revision of the model, a branch, tag or commit, to its commit.
+    async fn version(
+        &self,
+        secret: Secret,
+        params: Arc<Option<HashMap<String, String>>>,
+    ) -> super::Result<Option<String>> {
+        let Some(remote_path) = params.as_ref().as_ref().and_then(|p| p.get("path")) else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: "From is required",
+            }
+            .build());
+        };
+        let (org, model, revision) = parse(remote_path)?;
+
+        let mut request = reqwest::Client::new().get(format!(
+            "https://huggingface.co/api/models/{org}/{model}/revision/{revision}"
+        ));
+        if let Some(token) = secret.get("token") {
+            request = request.bearer_auth(token);
+        }
+        let info: ModelInfo = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(super::UnableToFetchModelSnafu {})?
+            .json()
+            .await
+            .context(super::UnableToFetchModelSnafu {})?;
+
+        Ok(Some(info.sha))
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelInfo {
+    sha: String,
+}
+
+/// Returns the organization, model and revision of `huggingface:[huggingface.co/]<org>/<model>[:<revision>]`.
+fn parse(remote_path: &str) -> super::Result<(String, String, String)> {
+    let Ok(re) = Regex::new(
+        r"\A(huggingface:)(huggingface\.co\/)?(?<org>[\w\-]+)\/(?<model>[\w\-]+)(:(?<revision>[\w\d\-\.]+))?\z",
+    ) else {
+        return Err(super::UnableToLoadConfigSnafu {
+            reason: "Invalid regex",
+        }
+        .build());
+    };
+    let Some(caps) = re.captures(remote_path) else {
+        return Err(super::UnableToLoadConfigSnafu {
+            reason: format!("from is invalid for huggingface source: {remote_path}"),
+        }
+        .build());
+    };
+
+    let revision = match caps.name("revision").map(|r| r.as_str()) {
+        None | Some("" | "latest") => "main".to_string(),
+        Some(revision) => revision.to_string(),
+    };
+
+    Ok((caps["org"].to_string(), caps["model"].to_string(), revision))
 }
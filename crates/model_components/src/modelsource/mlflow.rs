@@ -0,0 +1,402 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use async_trait::async_trait;
+use secrets::Secret;
+use serde::Deserialize;
+use snafu::prelude::*;
+use std::collections::HashMap;
+use std::string::ToString;
+use std::sync::Arc;
+
+use super::ModelSource;
+
+/// Loads the ONNX artifact of a version of a model of an MLflow model registry, from
+/// `mlflow:models:/<name>/<version or stage>` or `mlflow:models:/<name>@<alias>`.
+///
+/// The tracking server is the `mlflow_tracking_uri` key of the secret or `MLFLOW_TRACKING_URI`, authenticated with
+/// `mlflow_tracking_token`, or `mlflow_tracking_username` and `mlflow_tracking_password`, or the environment
+/// variables of the same names. Each version is downloaded once, into `~/.spice/models/<name>/<version>`.
+pub struct MLflow {}
+
+/// The version of a registered model a URI refers to.
+#[derive(Debug, PartialEq)]
+enum Selector {
+    Version(String),
+    Stage(String),
+    Alias(String),
+}
+
+#[derive(Deserialize)]
+struct ModelVersion {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct LatestVersions {
+    #[serde(default)]
+    model_versions: Vec<ModelVersion>,
+}
+
+#[derive(Deserialize)]
+struct ModelVersionResponse {
+    model_version: ModelVersion,
+}
+
+#[derive(Deserialize)]
+struct DownloadUri {
+    artifact_uri: String,
+}
+
+#[derive(Deserialize)]
+struct Artifacts {
+    #[serde(default)]
+    files: Vec<Artifact>,
+}
+
+#[derive(Deserialize)]
+struct Artifact {
+    path: String,
+    #[serde(default)]
+    is_dir: bool,
+}
+
+/// Parses `mlflow:models:/<name>/<version or stage>` or `mlflow:models:/<name>@<alias>`.
+fn parse(from: &str) -> Option<(String, Selector)> {
+    let uri = from.strip_prefix("mlflow:")?.strip_prefix("models:/")?;
+    if let Some((name, alias)) = uri.split_once('@') {
+        return Some((name.to_string(), Selector::Alias(alias.to_string())));
+    }
+
+    let (name, reference) = uri.rsplit_once('/')?;
+    if name.is_empty() || reference.is_empty() {
+        return None;
+    }
+    let selector = if reference.chars().all(|c| c.is_ascii_digit()) {
+        Selector::Version(reference.to_string())
+    } else {
+        Selector::Stage(reference.to_string())
+    };
+    Some((name.to_string(), selector))
+}
+
+/// A client of the REST API of an MLflow tracking server.
+struct Registry {
+    client: reqwest::Client,
+    uri: String,
+    token: Option<String>,
+    basic_auth: Option<(String, String)>,
+}
+
+impl Registry {
+    fn new(secret: &Secret) -> super::Result<Self> {
+        let setting = |key: &str| {
+            secret
+                .get(key)
+                .map(ToString::to_string)
+                .or_else(|| std::env::var(key.to_ascii_uppercase()).ok())
+        };
+
+        let Some(uri) = setting("mlflow_tracking_uri") else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: "The MLflow tracking server is required, set mlflow_tracking_uri in the secret or MLFLOW_TRACKING_URI",
+            }
+            .build());
+        };
+        let basic_auth =
+            setting("mlflow_tracking_username").zip(setting("mlflow_tracking_password"));
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            uri: uri.trim_end_matches('/').to_string(),
+            token: setting("mlflow_tracking_token"),
+            basic_auth,
+        })
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(format!("{}/{path}", self.uri));
+        match (&self.token, &self.basic_auth) {
+            (Some(token), _) => request.bearer_auth(token),
+            (None, Some((username, password))) => request.basic_auth(username, Some(password)),
+            (None, None) => request,
+        }
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> super::Result<T> {
+        self.get(path)
+            .query(query)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(super::UnableToFetchModelSnafu)?
+            .json()
+            .await
+            .context(super::UnableToFetchModelSnafu)
+    }
+
+    /// Returns the version `selector` currently refers to.
+    async fn resolve(&self, name: &str, selector: &Selector) -> super::Result<String> {
+        match selector {
+            Selector::Version(version) => Ok(version.clone()),
+            Selector::Stage(stage) => {
+                let latest: LatestVersions = self
+                    .get_json(
+                        "api/2.0/mlflow/registered-models/get-latest-versions",
+                        &[("name", name), ("stages", stage.as_str())],
+                    )
+                    .await?;
+                match latest.model_versions.into_iter().next() {
+                    Some(model_version) => Ok(model_version.version),
+                    None => Err(super::UnableToLoadConfigSnafu {
+                        reason: format!("The MLflow model {name} has no version in stage {stage}"),
+                    }
+                    .build()),
+                }
+            }
+            Selector::Alias(alias) => {
+                let response: ModelVersionResponse = self
+                    .get_json(
+                        "api/2.0/mlflow/registered-models/alias",
+                        &[("name", name), ("alias", alias.as_str())],
+                    )
+                    .await?;
+                Ok(response.model_version.version)
+            }
+        }
+    }
+
+    /// Downloads the ONNX file of a version of a model into `local_path`, returning its path.
+    async fn download(
+        &self,
+        name: &str,
+        version: &str,
+        files: &[String],
+        local_path: &str,
+    ) -> super::Result<String> {
+        let download_uri: DownloadUri = self
+            .get_json(
+                "api/2.0/mlflow/model-versions/get-download-uri",
+                &[("name", name), ("version", version)],
+            )
+            .await?;
+        let Some(artifact_path) = proxied_artifact_path(&download_uri.artifact_uri) else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: format!(
+                    "Unable to download {}, only artifacts served by the MLflow tracking server are supported",
+                    download_uri.artifact_uri
+                ),
+            }
+            .build());
+        };
+
+        let files = if files.is_empty() {
+            let artifacts: Artifacts = self
+                .get_json(
+                    "api/2.0/mlflow-artifacts/artifacts",
+                    &[("path", artifact_path.as_str())],
+                )
+                .await?;
+            artifacts
+                .files
+                .into_iter()
+                .filter(|artifact| !artifact.is_dir)
+                .filter_map(|artifact| artifact.path.rsplit('/').next().map(ToString::to_string))
+                .filter(|file| file.to_lowercase().ends_with(".onnx"))
+                .collect()
+        } else {
+            files.to_vec()
+        };
+
+        let mut onnx_file_name = None;
+        for file in files {
+            let file_name = format!("{local_path}/{file}");
+            tracing::info!("Downloading model: {name} version {version}, {file}");
+            let bytes = self
+                .get(&format!(
+                    "api/2.0/mlflow-artifacts/artifacts/{artifact_path}/{file}"
+                ))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .context(super::UnableToFetchModelSnafu)?
+                .bytes()
+                .await
+                .context(super::UnableToFetchModelSnafu)?;
+            std::fs::write(&file_name, bytes).context(super::UnableToCreateModelPathSnafu)?;
+
+            if file.to_lowercase().ends_with(".onnx") {
+                onnx_file_name = Some(file_name);
+            }
+        }
+
+        onnx_file_name.ok_or_else(|| {
+            super::UnableToLoadConfigSnafu {
+                reason: format!(
+                    "Version {version} of the MLflow model {name} has no ONNX artifact"
+                ),
+            }
+            .build()
+        })
+    }
+}
+
+/// Returns the path of an artifact served by the tracking server, from `mlflow-artifacts:/<path>` or
+/// `mlflow-artifacts://<host>/<path>`.
+fn proxied_artifact_path(artifact_uri: &str) -> Option<String> {
+    let path = artifact_uri.strip_prefix("mlflow-artifacts:")?;
+    let path = match path.strip_prefix("//") {
+        Some(authority_and_path) => authority_and_path.split_once('/')?.1,
+        None => path,
+    };
+    Some(path.trim_matches('/').to_string())
+}
+
+fn parse_params(
+    params: &Arc<Option<HashMap<String, String>>>,
+) -> super::Result<(String, String, Selector)> {
+    let param = |key: &str| {
+        params
+            .as_ref()
+            .as_ref()
+            .and_then(|p| p.get(key))
+            .map(ToString::to_string)
+    };
+
+    let Some(name) = param("name") else {
+        return Err(super::UnableToLoadConfigSnafu {
+            reason: "Name is required",
+        }
+        .build());
+    };
+    let Some(from) = param("from") else {
+        return Err(super::UnableToLoadConfigSnafu {
+            reason: "From is required",
+        }
+        .build());
+    };
+    let Some((registered_name, selector)) = parse(&from) else {
+        return Err(super::UnableToLoadConfigSnafu {
+            reason: format!(
+                "from is invalid for mlflow source: {from}, expected mlflow:models:/<name>/<version or stage> or mlflow:models:/<name>@<alias>"
+            ),
+        }
+        .build());
+    };
+
+    Ok((name, registered_name, selector))
+}
+
+#[async_trait]
+impl ModelSource for MLflow {
+    async fn pull(
+        &self,
+        secret: Secret,
+        params: Arc<Option<HashMap<String, String>>>,
+    ) -> super::Result<String> {
+        let (name, registered_name, selector) = parse_params(&params)?;
+        let registry = Registry::new(&secret)?;
+
+        let version = match params.as_ref().as_ref().and_then(|p| p.get("version")) {
+            Some(version) => version.clone(),
+            None => registry.resolve(&registered_name, &selector).await?,
+        };
+
+        let local_path = format!("{}/{version}", super::ensure_model_path(name.as_str())?);
+        std::fs::create_dir_all(&local_path).context(super::UnableToCreateModelPathSnafu)?;
+        let cached = std::fs::read_dir(&local_path)
+            .context(super::UnableToCreateModelPathSnafu)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("onnx"))
+            });
+        if let Some(cached) = cached.and_then(|path| path.to_str().map(ToString::to_string)) {
+            tracing::info!("Using cached version {version} of model {name}: {cached}");
+            return Ok(cached);
+        }
+
+        let files: Vec<String> = params
+            .as_ref()
+            .as_ref()
+            .and_then(|p| p.get("files"))
+            .map(|files| {
+                files
+                    .split(',')
+                    .filter(|f| !f.is_empty())
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        registry
+            .download(&registered_name, &version, &files, &local_path)
+            .await
+    }
+
+    async fn version(
+        &self,
+        secret: Secret,
+        params: Arc<Option<HashMap<String, String>>>,
+    ) -> super::Result<Option<String>> {
+        let (_, registered_name, selector) = parse_params(&params)?;
+        let registry = Registry::new(&secret)?;
+        registry
+            .resolve(&registered_name, &selector)
+            .await
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_registry_uris() {
+        assert_eq!(
+            parse("mlflow:models:/churn/Production"),
+            Some((
+                "churn".to_string(),
+                Selector::Stage("Production".to_string())
+            ))
+        );
+        assert_eq!(
+            parse("mlflow:models:/team/churn/3"),
+            Some(("team/churn".to_string(), Selector::Version("3".to_string())))
+        );
+        assert_eq!(
+            parse("mlflow:models:/churn@champion"),
+            Some(("churn".to_string(), Selector::Alias("champion".to_string())))
+        );
+        assert_eq!(parse("mlflow:runs:/abc/model"), None);
+
+        assert_eq!(
+            proxied_artifact_path("mlflow-artifacts://mlflow:5000/1/abc/artifacts/model/")
+                .as_deref(),
+            Some("1/abc/artifacts/model")
+        );
+        assert_eq!(
+            proxied_artifact_path("mlflow-artifacts:/1/abc/artifacts/model").as_deref(),
+            Some("1/abc/artifacts/model")
+        );
+        assert_eq!(proxied_artifact_path("s3://bucket/1/abc"), None);
+    }
+}
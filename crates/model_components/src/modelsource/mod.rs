@@ -31,6 +31,10 @@ pub mod huggingface;
 #[cfg(feature = "full")]
 pub mod local;
 #[cfg(feature = "full")]
+pub mod mlflow;
+#[cfg(feature = "full")]
+pub mod s3;
+#[cfg(feature = "full")]
 pub mod spiceai;
 
 #[derive(Debug, Snafu)]
@@ -71,6 +75,9 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 /// Implementing `pull` is required, which will fetch the model from the source (either local or
 /// remote) and store it in the local directory. The local directory is returned for further
 /// processing by `ModelRuntime`.
+///
+/// Sources backed by a registry implement `version`, which resolves the version a reference like a stage or a
+/// branch currently points to, so that a newer version can be loaded when it changes.
 #[async_trait]
 pub trait ModelSource: Send + Sync {
     async fn pull(
@@ -78,12 +85,22 @@ pub trait ModelSource: Send + Sync {
         secret: Secret,
         params: Arc<Option<HashMap<String, String>>>,
     ) -> Result<String>;
+
+    async fn version(
+        &self,
+        _secret: Secret,
+        _params: Arc<Option<HashMap<String, String>>>,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ModelSourceType {
     Huggingface,
     Local,
+    MLflow,
+    S3,
     SpiceAI,
 }
 
@@ -92,6 +109,8 @@ impl fmt::Display for ModelSourceType {
         match self {
             ModelSourceType::Huggingface => write!(f, "huggingface"),
             ModelSourceType::Local => write!(f, "file"),
+            ModelSourceType::MLflow => write!(f, "mlflow"),
+            ModelSourceType::S3 => write!(f, "s3"),
             ModelSourceType::SpiceAI => write!(f, "spiceai"),
         }
     }
@@ -116,6 +135,8 @@ impl FromStr for ModelSourceType {
             s if s.starts_with("spiceai:") => Ok(ModelSourceType::SpiceAI),
             s if s.starts_with("huggingface:") => Ok(ModelSourceType::Huggingface),
             s if s.starts_with("file:/") => Ok(ModelSourceType::Local),
+            s if s.starts_with("s3://") => Ok(ModelSourceType::S3),
+            s if s.starts_with("mlflow:") => Ok(ModelSourceType::MLflow),
             _ => Err(ParseError {
                 message: "Unrecognized model source type prefix".to_string(),
             }),
@@ -154,6 +175,16 @@ impl From<ModelSourceType> for Option<Box<dyn ModelSource>> {
             return Some(Box::new(spiceai::SpiceAI {}));
         }
 
+        #[cfg(feature = "full")]
+        if source == ModelSourceType::S3 {
+            return Some(Box::new(s3::S3 {}));
+        }
+
+        #[cfg(feature = "full")]
+        if source == ModelSourceType::MLflow {
+            return Some(Box::new(mlflow::MLflow {}));
+        }
+
         #[cfg(feature = "full")]
         if source == ModelSourceType::Huggingface {
             return Some(Box::new(huggingface::Huggingface {}));
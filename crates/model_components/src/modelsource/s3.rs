@@ -0,0 +1,114 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use async_trait::async_trait;
+use object_store::{aws::AmazonS3Builder, path::Path, ObjectStore};
+use secrets::Secret;
+use snafu::prelude::*;
+use std::collections::HashMap;
+use std::string::ToString;
+use std::sync::Arc;
+
+use super::ModelSource;
+
+/// Downloads a model from `s3://bucket/path/model.onnx`, with the credentials of the `aws_access_key_id` and
+/// `aws_secret_access_key` keys of the secret, or those of the environment.
+pub struct S3 {}
+
+#[async_trait]
+impl ModelSource for S3 {
+    async fn pull(
+        &self,
+        secret: Secret,
+        params: Arc<Option<HashMap<String, String>>>,
+    ) -> super::Result<String> {
+        let param = |key: &str| {
+            params
+                .as_ref()
+                .as_ref()
+                .and_then(|p| p.get(key))
+                .map(ToString::to_string)
+        };
+
+        let Some(name) = param("name") else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: "Name is required",
+            }
+            .build());
+        };
+        let Some(from) = param("from") else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: "From is required",
+            }
+            .build());
+        };
+
+        let Some((bucket, key)) = from
+            .strip_prefix("s3://")
+            .and_then(|location| location.split_once('/'))
+        else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: format!("from is invalid for s3 source: {from}, expected s3://bucket/path"),
+            }
+            .build());
+        };
+        let Some(file_name) = key.rsplit('/').next().filter(|f| !f.is_empty()) else {
+            return Err(super::UnableToLoadConfigSnafu {
+                reason: format!("from is invalid for s3 source: {from}, expected a model file"),
+            }
+            .build());
+        };
+
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+        if let Some(region) = secret.get("aws_region") {
+            builder = builder.with_region(region);
+        }
+        match (
+            secret.get("aws_access_key_id"),
+            secret.get("aws_secret_access_key"),
+        ) {
+            (Some(key_id), Some(secret_key)) => {
+                builder = builder
+                    .with_access_key_id(key_id)
+                    .with_secret_access_key(secret_key);
+            }
+            _ if std::env::var("AWS_ACCESS_KEY_ID").is_err() => {
+                builder = builder.with_skip_signature(true);
+            }
+            _ => {}
+        }
+        let store = builder
+            .build()
+            .boxed()
+            .context(super::UnableToCreateModelSourceSnafu)?;
+
+        let local_path = format!("{}/{file_name}", super::ensure_model_path(name.as_str())?);
+        tracing::info!("Downloading model: {from}");
+        let bytes = store
+            .get(&Path::from(key))
+            .await
+            .boxed()
+            .context(super::UnableToCreateModelSourceSnafu)?
+            .bytes()
+            .await
+            .boxed()
+            .context(super::UnableToCreateModelSourceSnafu)?;
+        std::fs::write(&local_path, bytes).context(super::UnableToCreateModelPathSnafu)?;
+        tracing::info!("Downloaded: {local_path}");
+
+        Ok(local_path)
+    }
+}
@@ -14,22 +14,27 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use std::{any::Any, sync::Arc, time::Duration};
 
-use crate::component::dataset::acceleration::{RefreshMode, ZeroResultsAction};
+use crate::component::dataset::acceleration::{RefreshMode, UnavailableAction, ZeroResultsAction};
 use crate::component::dataset::TimeFormat;
 use crate::datafusion::SPICE_RUNTIME_SCHEMA;
-use arrow::array::UInt64Array;
+use arrow::array::{RecordBatch, UInt64Array};
 use arrow::datatypes::SchemaRef;
 use async_trait::async_trait;
 use cache::QueryResultsCacheProvider;
+use chrono::{DateTime, Utc};
+use data_components::compact::get_compaction_provider;
 use data_components::delete::get_deletion_provider;
-use datafusion::error::Result as DataFusionResult;
+use data_components::sample::{get_sample_provider, SampleTableProvider, TableSample};
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
 use datafusion::execution::context::SessionState;
 use datafusion::logical_expr::{Operator, TableProviderFilterPushDown};
 use datafusion::physical_plan::union::UnionExec;
-use datafusion::physical_plan::{collect, ExecutionPlan};
+use datafusion::physical_plan::{
+    collect, execute_stream, ExecutionPlan, SendableRecordBatchStream,
+};
 use datafusion::sql::TableReference;
 use datafusion::{
     datasource::{TableProvider, TableType},
@@ -37,20 +42,29 @@ use datafusion::{
     logical_expr::Expr,
 };
 use snafu::prelude::*;
+use spicepod::component::dataset::quality::QualityCheck;
 use tokio::task::JoinHandle;
-use tokio::time::interval;
+use uuid::Uuid;
 
-use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::sync::{mpsc, oneshot, watch, RwLock};
 
+use crate::cluster::{self, shard::Shards};
 use crate::dataconnector;
 use crate::datafusion::filter_converter::TimestampFilterConvert;
+use crate::dataupdate::{DataUpdate, UpdateType};
 use crate::execution_plan::fallback_on_zero_results::FallbackOnZeroResultsScanExec;
 use crate::execution_plan::schema_cast::SchemaCastScanExec;
 use crate::execution_plan::slice::SliceExec;
 use crate::execution_plan::tee::TeeExec;
 use crate::execution_plan::TableScanParams;
+use crate::status::{self, ComponentState, ComponentStatus};
 
+pub mod export;
+pub mod progress;
+mod quality;
 pub mod refresh;
+pub mod schedule;
+pub mod versions;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -70,6 +84,14 @@ pub enum Error {
     #[snafu(display("Manual refresh is not supported for `append` mode"))]
     ManualRefreshIsNotSupported {},
 
+    #[snafu(display(
+        "Scheduled refresh is not supported for `append` mode without a `time_column`"
+    ))]
+    ScheduledRefreshIsNotSupported {},
+
+    #[snafu(display("Refreshes are paused, resume them to refresh the dataset"))]
+    RefreshesPaused {},
+
     #[snafu(display("Failed to find latest timestamp in accelerated table"))]
     FailedToQueryLatestTimestamp {
         source: datafusion::error::DataFusionError,
@@ -77,6 +99,25 @@ pub enum Error {
 
     #[snafu(display("{reason}"))]
     FailedToFindLatestTimestamp { reason: String },
+
+    #[snafu(display("{source}"))]
+    SourceUnavailable {
+        source: crate::connector_health::Error,
+    },
+
+    #[snafu(display("The runtime is shutting down"))]
+    RuntimeShuttingDown,
+
+    #[snafu(display("{source}"))]
+    InvalidArtifact { source: export::Error },
+
+    #[snafu(display("{source}"))]
+    AccelerationQuotaExceeded { source: crate::quotas::Error },
+
+    #[snafu(display("Unable to load data into the acceleration: {source}"))]
+    UnableToLoadData {
+        source: datafusion::error::DataFusionError,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -90,10 +131,17 @@ pub struct AcceleratedTable {
     accelerator: Arc<dyn TableProvider>,
     federated: Arc<dyn TableProvider>,
     refresh_trigger: Option<mpsc::Sender<()>>,
+    /// When scheduled refreshes run, which can be changed while the table is loaded.
+    refresh_schedule: Option<watch::Sender<Option<schedule::RefreshSchedule>>>,
     handlers: Vec<JoinHandle<()>>,
     zero_results_action: ZeroResultsAction,
+    unavailable_action: UnavailableAction,
+    stale_after: Option<Duration>,
     refresh_params: Arc<RwLock<refresh::Refresh>>,
     refresher: Arc<refresh::Refresher>,
+    retained_versions: Option<versions::RetainedVersions>,
+    /// The shards of the dataset this member accelerates, when the other members of the cluster accelerate the rest.
+    shards: Option<Shards>,
 }
 
 fn validate_refresh_data_window(
@@ -121,9 +169,17 @@ pub struct Builder {
     federated: Arc<dyn TableProvider>,
     accelerator: Arc<dyn TableProvider>,
     refresh: refresh::Refresh,
+    refresh_cron: Option<schedule::CronSchedule>,
     retention: Option<Retention>,
+    compaction_interval: Option<Duration>,
     zero_results_action: ZeroResultsAction,
+    unavailable_action: UnavailableAction,
+    stale_after: Option<Duration>,
     cache_provider: Option<Arc<QueryResultsCacheProvider>>,
+    storage_file: Option<String>,
+    retained_versions: Option<versions::RetainedVersions>,
+    quality_checks: Vec<QualityCheck>,
+    shards: Option<Shards>,
 }
 
 impl Builder {
@@ -138,22 +194,54 @@ impl Builder {
             federated,
             accelerator,
             refresh,
+            refresh_cron: None,
             retention: None,
+            compaction_interval: None,
             zero_results_action: ZeroResultsAction::default(),
+            unavailable_action: UnavailableAction::default(),
+            stale_after: None,
             cache_provider: None,
+            storage_file: None,
+            retained_versions: None,
+            quality_checks: Vec::new(),
+            shards: None,
         }
     }
 
+    /// Schedules refreshes at the times of a cron expression, instead of every `check_interval` of the refresh.
+    pub fn refresh_cron(&mut self, refresh_cron: Option<schedule::CronSchedule>) -> &mut Self {
+        self.refresh_cron = refresh_cron;
+        self
+    }
+
     pub fn retention(&mut self, retention: Option<Retention>) -> &mut Self {
         self.retention = retention;
         self
     }
 
+    /// How often the accelerator merges the small batches of appends and reclaims the space of deleted rows.
+    pub fn compaction_interval(&mut self, compaction_interval: Option<Duration>) -> &mut Self {
+        self.compaction_interval = compaction_interval;
+        self
+    }
+
     pub fn zero_results_action(&mut self, zero_results_action: ZeroResultsAction) -> &mut Self {
         self.zero_results_action = zero_results_action;
         self
     }
 
+    /// What queries do while the acceleration hasn't loaded, failed its last refresh, or was last refreshed more
+    /// than `stale_after` ago.
+    pub fn unavailable_action(
+        &mut self,
+        unavailable_action: UnavailableAction,
+        stale_after: Option<Duration>,
+    ) -> &mut Self {
+        self.unavailable_action = unavailable_action;
+        self.stale_after = stale_after;
+        self
+    }
+
     pub fn cache_provider(
         &mut self,
         cache_provider: Option<Arc<QueryResultsCacheProvider>>,
@@ -162,10 +250,45 @@ impl Builder {
         self
     }
 
+    /// The file the accelerator stores the dataset in, used to report its size.
+    pub fn storage_file(&mut self, storage_file: Option<String>) -> &mut Self {
+        self.storage_file = storage_file;
+        self
+    }
+
+    /// Keeps the previous loads of the acceleration as versions queries can read.
+    pub fn retained_versions(
+        &mut self,
+        retained_versions: Option<versions::RetainedVersions>,
+    ) -> &mut Self {
+        self.retained_versions = retained_versions;
+        self
+    }
+
+    /// The checks the data of each refresh must pass before it is inserted into the accelerator.
+    pub fn quality_checks(&mut self, quality_checks: Vec<QualityCheck>) -> &mut Self {
+        self.quality_checks = quality_checks;
+        self
+    }
+
+    /// Scans the shards of the dataset accelerated by the other members of the cluster along with its own `shards`.
+    pub fn shards(&mut self, shards: Option<Shards>) -> &mut Self {
+        self.shards = shards;
+        self
+    }
+
     pub async fn build(self) -> (AcceleratedTable, oneshot::Receiver<()>) {
         let mut refresh_trigger = None;
+        let mut refresh_schedule = None;
         let mut scheduled_refreshes_handle: Option<JoinHandle<()>> = None;
         let (ready_sender, is_ready) = oneshot::channel::<()>();
+        let initial_schedule = self
+            .refresh_cron
+            .map(schedule::RefreshSchedule::Cron)
+            .or(self
+                .refresh
+                .check_interval
+                .map(schedule::RefreshSchedule::Interval));
 
         let acceleration_refresh_mode: refresh::AccelerationRefreshMode = match self.refresh.mode {
             RefreshMode::Append => {
@@ -173,23 +296,24 @@ impl Builder {
                     refresh::AccelerationRefreshMode::Append(None)
                 } else {
                     let (trigger, receiver) = mpsc::channel::<()>(1);
+                    let (schedule, schedule_receiver) = watch::channel(initial_schedule);
                     refresh_trigger = Some(trigger.clone());
-                    scheduled_refreshes_handle = AcceleratedTable::schedule_regular_refreshes(
-                        self.refresh.check_interval,
-                        trigger,
-                    )
-                    .await;
+                    refresh_schedule = Some(schedule);
+                    scheduled_refreshes_handle = Some(
+                        AcceleratedTable::schedule_regular_refreshes(schedule_receiver, trigger),
+                    );
                     refresh::AccelerationRefreshMode::Append(Some(receiver))
                 }
             }
             RefreshMode::Full => {
                 let (trigger, receiver) = mpsc::channel::<()>(1);
+                let (schedule, schedule_receiver) = watch::channel(initial_schedule);
                 refresh_trigger = Some(trigger.clone());
-                scheduled_refreshes_handle = AcceleratedTable::schedule_regular_refreshes(
-                    self.refresh.check_interval,
+                refresh_schedule = Some(schedule);
+                scheduled_refreshes_handle = Some(AcceleratedTable::schedule_regular_refreshes(
+                    schedule_receiver,
                     trigger,
-                )
-                .await;
+                ));
                 refresh::AccelerationRefreshMode::Full(receiver)
             }
         };
@@ -203,6 +327,9 @@ impl Builder {
             Arc::clone(&self.accelerator),
         );
         refresher.cache_provider(self.cache_provider.clone());
+        refresher.storage_file(self.storage_file.clone());
+        refresher.retained_versions(self.retained_versions.clone());
+        refresher.quality_checks(self.quality_checks.clone());
         let refresher = Arc::new(refresher);
 
         let refresher_tokio = Arc::clone(&refresher);
@@ -228,16 +355,30 @@ impl Builder {
             ));
             handlers.push(retention_check_handle);
         }
+
+        if let Some(compaction_interval) = self.compaction_interval {
+            let compaction_handle = tokio::spawn(AcceleratedTable::start_compaction(
+                self.dataset_name.clone(),
+                Arc::clone(&self.accelerator),
+                compaction_interval,
+            ));
+            handlers.push(compaction_handle);
+        }
         (
             AcceleratedTable {
                 dataset_name: self.dataset_name,
                 accelerator: self.accelerator,
                 federated: self.federated,
                 refresh_trigger,
+                refresh_schedule,
                 handlers,
                 zero_results_action: self.zero_results_action,
+                unavailable_action: self.unavailable_action,
+                stale_after: self.stale_after,
                 refresh_params,
                 refresher,
+                retained_versions: self.retained_versions,
+                shards: self.shards,
             },
             is_ready,
         )
@@ -259,20 +400,20 @@ impl AcceleratedTable {
         Arc::clone(&self.refresher)
     }
 
-    pub async fn trigger_refresh(&self) -> Result<()> {
-        match &self.refresh_trigger {
-            Some(refresh_trigger) => {
-                refresh_trigger
-                    .send(())
-                    .await
-                    .context(FailedToTriggerRefreshSnafu)?;
-            }
-            None => {
-                ManualRefreshIsNotSupportedSnafu.fail()?;
-            }
+    /// Triggers a refresh, returning the id of the job that reports the progress of the next refresh.
+    pub async fn trigger_refresh(&self) -> Result<Uuid> {
+        ensure!(!refresh::refreshes_paused(), RefreshesPausedSnafu);
+        let Some(refresh_trigger) = &self.refresh_trigger else {
+            return ManualRefreshIsNotSupportedSnafu.fail();
+        };
+
+        let job_id = progress::queue(&self.dataset_name);
+        if let Err(e) = refresh_trigger.send(()).await {
+            progress::cancel(&self.dataset_name, job_id, e.to_string());
+            return Err(e).context(FailedToTriggerRefreshSnafu);
         }
 
-        Ok(())
+        Ok(job_id)
     }
 
     #[must_use]
@@ -280,6 +421,95 @@ impl AcceleratedTable {
         Arc::clone(&self.federated)
     }
 
+    #[must_use]
+    pub(crate) fn get_accelerator(&self) -> Arc<dyn TableProvider> {
+        Arc::clone(&self.accelerator)
+    }
+
+    /// The shards of the dataset this member accelerates, if the members of the cluster split the dataset.
+    #[must_use]
+    pub(crate) fn shards(&self) -> Option<&Shards> {
+        self.shards.as_ref()
+    }
+
+    /// Why the accelerated data can't be relied on, if it hasn't loaded, failed its last refresh, or is stale.
+    #[must_use]
+    pub fn unavailable_reason(&self) -> Option<String> {
+        let state = status::dataset_state(&self.dataset_name)?;
+        unavailable_reason(&state, self.stale_after, Utc::now())
+    }
+
+    /// The warning to return with results read from the acceleration, if it is served while unavailable.
+    #[must_use]
+    pub fn stale_warning(&self) -> Option<String> {
+        if self.unavailable_action != UnavailableAction::ServeStale {
+            return None;
+        }
+        self.unavailable_reason()
+            .map(|reason| format!("The acceleration of {} {reason}", self.dataset_name))
+    }
+
+    /// The previous loads of the acceleration, if they are retained.
+    #[must_use]
+    pub fn retained_versions(&self) -> Option<&versions::RetainedVersions> {
+        self.retained_versions.as_ref()
+    }
+
+    /// Exports the accelerated data as a portable artifact, returning what to record about it and a stream of the data.
+    pub async fn export(&self) -> Result<(export::ArtifactMetadata, SendableRecordBatchStream)> {
+        let schema = self.accelerator.schema();
+        let metadata = export::ArtifactMetadata {
+            dataset: self.dataset_name.to_string(),
+            schema_version: export::schema_version(&schema),
+            watermark: self.refresher.watermark().await,
+            exported_at: Utc::now(),
+        };
+
+        let ctx = SessionContext::new();
+        let plan = self
+            .accelerator
+            .scan(&ctx.state(), None, &[], None)
+            .await
+            .context(UnableToScanTableProviderSnafu)?;
+        let data = execute_stream(plan, ctx.task_ctx()).context(UnableToScanTableProviderSnafu)?;
+        Ok((metadata, data))
+    }
+
+    /// Replaces the accelerated data with the data of an artifact exported from another runtime, returning the number
+    /// of rows loaded. The refresh schedule is unchanged.
+    pub async fn import(
+        &self,
+        metadata: &export::ArtifactMetadata,
+        data: Vec<RecordBatch>,
+    ) -> Result<usize> {
+        let schema = self.accelerator.schema();
+        metadata
+            .check_schema(&self.dataset_name.to_string(), &schema)
+            .context(InvalidArtifactSnafu)?;
+
+        // The batches of the artifact carry its metadata in their schema.
+        let data = data
+            .iter()
+            .map(|batch| RecordBatch::try_new(Arc::clone(&schema), batch.columns().to_vec()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(DataFusionError::from)
+            .context(UnableToLoadDataSnafu)?;
+
+        self.refresher
+            .load(DataUpdate {
+                schema,
+                data,
+                update_type: UpdateType::Overwrite,
+            })
+            .await
+    }
+
+    /// Whether the accelerator can scan a sample of the table, see [`SampleTableProvider`].
+    #[must_use]
+    pub fn supports_sampling(&self) -> bool {
+        get_sample_provider(self.accelerator.as_ref()).is_some()
+    }
+
     pub async fn update_refresh_sql(&self, refresh_sql: Option<String>) -> Result<()> {
         let dataset_name = &self.dataset_name;
 
@@ -295,29 +525,127 @@ impl AcceleratedTable {
         Ok(())
     }
 
-    async fn schedule_regular_refreshes(
-        refresh_check_interval: Option<Duration>,
+    /// Changes when scheduled refreshes run, taking effect from the next refresh. `None` stops scheduled refreshes,
+    /// leaving only manual ones.
+    pub async fn update_refresh_schedule(
+        &self,
+        schedule: Option<schedule::RefreshSchedule>,
+    ) -> Result<()> {
+        let Some(refresh_schedule) = &self.refresh_schedule else {
+            return ScheduledRefreshIsNotSupportedSnafu.fail();
+        };
+
+        let dataset_name = &self.dataset_name;
+        match &schedule {
+            Some(schedule) => {
+                tracing::info!(
+                    "[refresh] Updating refresh schedule for {dataset_name} to {schedule}"
+                );
+            }
+            None => tracing::info!("[refresh] Removing refresh schedule for {dataset_name}"),
+        }
+        self.refresh_params.write().await.check_interval = match &schedule {
+            Some(schedule::RefreshSchedule::Interval(interval)) => Some(*interval),
+            _ => None,
+        };
+        refresh_schedule.send_replace(schedule);
+        Ok(())
+    }
+
+    /// Triggers the initial refresh, then the refreshes of the schedule while it is set and refreshes aren't
+    /// paused. The schedule is read again after each refresh, and whenever it changes.
+    fn schedule_regular_refreshes(
+        mut schedule: watch::Receiver<Option<schedule::RefreshSchedule>>,
         refresh_trigger: mpsc::Sender<()>,
-    ) -> Option<JoinHandle<()>> {
-        if let Some(refresh_check_interval) = refresh_check_interval {
-            let mut interval_timer = interval(refresh_check_interval);
-            let trigger = refresh_trigger.clone();
-            let handle = tokio::spawn(async move {
-                loop {
-                    interval_timer.tick().await;
-                    // If sending fails, it means the receiver is dropped, and we should stop the task.
-                    if trigger.send(()).await.is_err() {
-                        break;
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            // If sending fails, it means the receiver is dropped, and we should stop the task.
+            if refresh_trigger.send(()).await.is_err() {
+                return;
+            }
+
+            loop {
+                let next_check = schedule
+                    .borrow_and_update()
+                    .as_ref()
+                    .map(|schedule| schedule.next_check(Utc::now()));
+                let check = async {
+                    match next_check {
+                        Some((wait, due)) => {
+                            tokio::time::sleep(wait).await;
+                            due
+                        }
+                        None => std::future::pending().await,
+                    }
+                };
+
+                tokio::select! {
+                    due = check => {
+                        if !due || refresh::refreshes_paused() {
+                            continue;
+                        }
+                        if refresh_trigger.send(()).await.is_err() {
+                            break;
+                        }
+                    }
+                    changed = schedule.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
                     }
                 }
-            });
+            }
+        })
+    }
 
-            return Some(handle);
-        } else if let Err(err) = refresh_trigger.send(()).await {
-            tracing::error!("Failed to trigger refresh: {err}");
+    async fn start_compaction(
+        dataset_name: TableReference,
+        accelerator: Arc<dyn TableProvider>,
+        compaction_interval: Duration,
+    ) {
+        if get_compaction_provider(accelerator.as_ref()).is_none() {
+            tracing::error!(
+                "[compaction] Accelerated table for {dataset_name} does not support compaction"
+            );
+            return;
         }
 
-        None
+        let mut interval_timer = tokio::time::interval(compaction_interval);
+        // The first tick completes immediately, when there is nothing to compact yet.
+        interval_timer.tick().await;
+
+        loop {
+            interval_timer.tick().await;
+
+            let Some(compaction_provider) = get_compaction_provider(accelerator.as_ref()) else {
+                return;
+            };
+
+            let start = Instant::now();
+            let result = compaction_provider.compact().await;
+            let duration = start.elapsed();
+
+            let status = match &result {
+                Ok(()) => {
+                    tracing::debug!(
+                        "[compaction] Compacted {dataset_name} in {}ms",
+                        duration.as_millis()
+                    );
+                    "ok"
+                }
+                Err(e) => {
+                    tracing::error!("[compaction] Error compacting {dataset_name}: {e}");
+                    "error"
+                }
+            };
+            let labels = [
+                ("dataset", dataset_name.to_string()),
+                ("status", status.to_string()),
+            ];
+            metrics::counter!("datasets_acceleration_compactions", &labels).increment(1);
+            metrics::histogram!("datasets_acceleration_compaction_duration_seconds", &labels)
+                .record(duration.as_secs_f64());
+        }
     }
 
     #[allow(clippy::cast_possible_wrap)]
@@ -426,6 +754,33 @@ impl AcceleratedTable {
     }
 }
 
+fn unavailable_reason(
+    state: &ComponentState,
+    stale_after: Option<Duration>,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let Some(last_refresh) = state.last_refresh else {
+        return Some(match (&state.status, &state.error) {
+            (ComponentStatus::Error, Some(error)) => format!("failed to load: {error}"),
+            _ => "hasn't loaded yet".to_string(),
+        });
+    };
+
+    if let (ComponentStatus::Error, Some(error)) = (&state.status, &state.error) {
+        return Some(format!("failed its last refresh: {error}"));
+    }
+
+    let stale_after = stale_after?;
+    let staleness = (now - last_refresh).to_std().ok()?;
+    (staleness > stale_after).then(|| {
+        format!(
+            "was last refreshed {}s ago, more than stale_after of {}s",
+            staleness.as_secs(),
+            stale_after.as_secs()
+        )
+    })
+}
+
 impl Drop for AcceleratedTable {
     fn drop(&mut self) {
         for handler in self.handlers.drain(..) {
@@ -462,11 +817,57 @@ impl TableProvider for AcceleratedTable {
         filters: &[Expr],
         limit: Option<usize>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        if let Some(reason) = self.unavailable_reason() {
+            match self.unavailable_action {
+                UnavailableAction::ServeStale => {}
+                UnavailableAction::UseSource => {
+                    tracing::debug!(
+                        "Querying the source of {}, as its acceleration {reason}",
+                        self.dataset_name
+                    );
+                    let input = self
+                        .federated
+                        .scan(state, projection, filters, limit)
+                        .await?;
+                    return Ok(Arc::new(SchemaCastScanExec::new(input, self.schema())));
+                }
+                UnavailableAction::Error => {
+                    return Err(DataFusionError::Execution(format!(
+                        "The acceleration of {} {reason}",
+                        self.dataset_name
+                    )));
+                }
+            }
+        }
+
         let input = self
             .accelerator
             .scan(state, projection, filters, limit)
             .await?;
 
+        if let Some(shards) = &self.shards {
+            // The accelerator only has the shards of this member, so the source can't stand in for it.
+            let local: Arc<dyn ExecutionPlan> =
+                Arc::new(SchemaCastScanExec::new(input, self.schema()));
+            let remote = cluster::exchange::remote_scans(
+                &self.dataset_name,
+                shards,
+                &self.schema(),
+                projection,
+                filters,
+                limit,
+            )?;
+            if remote.is_empty() {
+                return Ok(local);
+            }
+
+            let mut inputs = vec![local];
+            inputs.extend(remote.into_iter().map(|remote| {
+                Arc::new(SchemaCastScanExec::new(remote, self.schema())) as Arc<dyn ExecutionPlan>
+            }));
+            return Ok(Arc::new(UnionExec::new(inputs)));
+        }
+
         let plan: Arc<dyn ExecutionPlan> = match self.zero_results_action {
             ZeroResultsAction::ReturnEmpty => input,
             ZeroResultsAction::UseSource => Arc::new(FallbackOnZeroResultsScanExec::new(
@@ -512,6 +913,31 @@ impl TableProvider for AcceleratedTable {
     }
 }
 
+#[async_trait]
+impl SampleTableProvider for AcceleratedTable {
+    async fn scan_sample(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+        sample: &TableSample,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let Some(accelerator) = get_sample_provider(self.accelerator.as_ref()) else {
+            return Err(DataFusionError::NotImplemented(format!(
+                "The acceleration of {} can't be sampled",
+                self.dataset_name
+            )));
+        };
+
+        // An empty sample doesn't fall back to the source, as small samples of small tables can be empty.
+        let input = accelerator
+            .scan_sample(state, projection, filters, limit, sample)
+            .await?;
+        Ok(Arc::new(SchemaCastScanExec::new(input, self.schema())))
+    }
+}
+
 pub struct Retention {
     pub(crate) time_column: String,
     pub(crate) time_format: Option<TimeFormat>,
@@ -545,3 +971,66 @@ impl Retention {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn reports_unavailable_accelerations() {
+        let now = Utc::now();
+        let minute = Some(Duration::from_secs(60));
+        let state = |status, last_refresh, error: Option<&str>| ComponentState {
+            status,
+            last_refresh,
+            error: error.map(ToString::to_string),
+            violations: vec![],
+            pii_columns: std::collections::BTreeMap::new(),
+        };
+
+        assert_eq!(
+            unavailable_reason(&state(ComponentStatus::Refreshing, None, None), minute, now)
+                .as_deref(),
+            Some("hasn't loaded yet")
+        );
+        assert_eq!(
+            unavailable_reason(
+                &state(ComponentStatus::Error, None, Some("timed out")),
+                minute,
+                now
+            )
+            .as_deref(),
+            Some("failed to load: timed out")
+        );
+        assert_eq!(
+            unavailable_reason(
+                &state(
+                    ComponentStatus::Error,
+                    Some(now - TimeDelta::seconds(10)),
+                    Some("timed out")
+                ),
+                minute,
+                now
+            )
+            .as_deref(),
+            Some("failed its last refresh: timed out")
+        );
+
+        let refreshed = state(
+            ComponentStatus::Ready,
+            Some(now - TimeDelta::seconds(90)),
+            None,
+        );
+        assert_eq!(
+            unavailable_reason(&refreshed, minute, now).as_deref(),
+            Some("was last refreshed 90s ago, more than stale_after of 60s")
+        );
+        assert_eq!(unavailable_reason(&refreshed, None, now), None);
+        assert_eq!(
+            unavailable_reason(&refreshed, Some(Duration::from_secs(120)), now),
+            None
+        );
+    }
+}
@@ -0,0 +1,207 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Portable artifacts of an acceleration, to seed the acceleration of the same dataset on another runtime without
+//! loading it from the source.
+//!
+//! An artifact is an Arrow IPC stream of the accelerated data. The metadata of its schema records the artifact format,
+//! the schema version of the data, the refresh watermark (the latest value of the `time_column`) and when it was
+//! exported. An artifact is only imported into an acceleration with the same schema version, and append refreshes
+//! after an import continue from the latest `time_column` value of the imported data.
+
+use std::collections::HashMap;
+
+use arrow::datatypes::Schema;
+use chrono::{DateTime, SecondsFormat, Utc};
+use snafu::prelude::*;
+
+/// The version of the artifact format, increased with incompatible changes.
+pub const FORMAT_VERSION: &str = "1";
+
+const FORMAT_VERSION_KEY: &str = "spice.acceleration.format_version";
+const DATASET_KEY: &str = "spice.acceleration.dataset";
+const SCHEMA_VERSION_KEY: &str = "spice.acceleration.schema_version";
+const WATERMARK_KEY: &str = "spice.acceleration.watermark";
+const EXPORTED_AT_KEY: &str = "spice.acceleration.exported_at";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The artifact has no {key}. Import an artifact exported from a dataset acceleration"
+    ))]
+    MissingMetadata { key: &'static str },
+
+    #[snafu(display("Unsupported artifact format version {version}, expected {FORMAT_VERSION}"))]
+    UnsupportedFormatVersion { version: String },
+
+    #[snafu(display("Invalid {key} in the artifact: {value}"))]
+    InvalidMetadata { key: &'static str, value: String },
+
+    #[snafu(display("The artifact of {artifact_dataset} has schema version {artifact}, but the acceleration of {dataset} has {acceleration}. Export the artifact from a dataset with the same schema"))]
+    SchemaVersionMismatch {
+        artifact_dataset: String,
+        artifact: String,
+        dataset: String,
+        acceleration: String,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What an artifact records about the exported acceleration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtifactMetadata {
+    /// The dataset the artifact was exported from.
+    pub dataset: String,
+    pub schema_version: String,
+    /// The latest value of the `time_column` of the data, if the dataset has one.
+    pub watermark: Option<DateTime<Utc>>,
+    pub exported_at: DateTime<Utc>,
+}
+
+impl ArtifactMetadata {
+    /// The metadata to write to the schema of the artifact.
+    #[must_use]
+    pub fn to_schema_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::from([
+            (FORMAT_VERSION_KEY.to_string(), FORMAT_VERSION.to_string()),
+            (DATASET_KEY.to_string(), self.dataset.clone()),
+            (SCHEMA_VERSION_KEY.to_string(), self.schema_version.clone()),
+            (
+                EXPORTED_AT_KEY.to_string(),
+                self.exported_at
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+            ),
+        ]);
+        if let Some(watermark) = self.watermark {
+            metadata.insert(
+                WATERMARK_KEY.to_string(),
+                watermark.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            );
+        }
+        metadata
+    }
+
+    /// Reads the metadata from the schema of an artifact.
+    pub fn from_schema_metadata(metadata: &HashMap<String, String>) -> Result<Self> {
+        let get = |key: &'static str| {
+            metadata
+                .get(key)
+                .context(MissingMetadataSnafu { key })
+                .map(String::as_str)
+        };
+        let timestamp = |key: &'static str, value: &str| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|time| time.with_timezone(&Utc))
+                .ok()
+                .context(InvalidMetadataSnafu { key, value })
+        };
+
+        let version = get(FORMAT_VERSION_KEY)?;
+        ensure!(
+            version == FORMAT_VERSION,
+            UnsupportedFormatVersionSnafu { version }
+        );
+
+        Ok(Self {
+            dataset: get(DATASET_KEY)?.to_string(),
+            schema_version: get(SCHEMA_VERSION_KEY)?.to_string(),
+            watermark: metadata
+                .get(WATERMARK_KEY)
+                .map(|watermark| timestamp(WATERMARK_KEY, watermark))
+                .transpose()?,
+            exported_at: timestamp(EXPORTED_AT_KEY, get(EXPORTED_AT_KEY)?)?,
+        })
+    }
+
+    /// Checks the artifact can be imported into the acceleration of `dataset` with `schema`.
+    pub fn check_schema(&self, dataset: &str, schema: &Schema) -> Result<()> {
+        let acceleration = schema_version(schema);
+        ensure!(
+            self.schema_version == acceleration,
+            SchemaVersionMismatchSnafu {
+                artifact_dataset: self.dataset.clone(),
+                artifact: self.schema_version.clone(),
+                dataset,
+                acceleration,
+            }
+        );
+        Ok(())
+    }
+}
+
+/// A fingerprint of the names, types and nullability of the fields of a schema.
+///
+/// It is a 64-bit FNV-1a hash rather than the standard library's hasher, so it is the same on every runtime version.
+#[must_use]
+pub fn schema_version(schema: &Schema) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in schema.fields() {
+        let field = format!(
+            "{}:{}:{};",
+            field.name(),
+            field.data_type(),
+            field.is_nullable()
+        );
+        for byte in field.bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::{DataType, Field};
+
+    use super::*;
+
+    #[test]
+    fn test_artifact_metadata() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        let metadata = ArtifactMetadata {
+            dataset: "orders".to_string(),
+            schema_version: schema_version(&schema),
+            watermark: Some(DateTime::from_timestamp_nanos(1_720_000_000_123_456_789)),
+            exported_at: DateTime::from_timestamp_millis(1_720_000_100_000).unwrap_or_default(),
+        };
+
+        let read = ArtifactMetadata::from_schema_metadata(&metadata.to_schema_metadata())
+            .expect("valid metadata");
+        assert_eq!(read, metadata);
+        assert!(read.check_schema("orders", &schema).is_ok());
+
+        let renamed = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("title", DataType::Utf8, true),
+        ]);
+        assert!(matches!(
+            read.check_schema("orders", &renamed),
+            Err(Error::SchemaVersionMismatch { .. })
+        ));
+
+        let mut unversioned = metadata.to_schema_metadata();
+        unversioned.remove(FORMAT_VERSION_KEY);
+        assert!(matches!(
+            ArtifactMetadata::from_schema_metadata(&unversioned),
+            Err(Error::MissingMetadata { .. })
+        ));
+    }
+}
@@ -0,0 +1,375 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The progress of the refreshes of each accelerated dataset, as rows and bytes fetched from the source so far, the
+//! partition they came from and an estimate of the time left, so a slow refresh can be told apart from a hung one.
+//!
+//! Each refresh is a job, reported in the logs while it runs and by
+//! `GET /v1/datasets/{name}/acceleration/refresh/{job_id}` until it is one of the last [`MAX_JOBS_PER_DATASET`]
+//! jobs of its dataset.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use arrow::array::RecordBatch;
+use chrono::{DateTime, Utc};
+use datafusion::sql::TableReference;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::datafusion::SPICE_RUNTIME_SCHEMA;
+
+/// How many of the last jobs of a dataset are kept.
+pub const MAX_JOBS_PER_DATASET: usize = 10;
+
+/// How often the progress of a running refresh is logged.
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshState {
+    /// Triggered, and waiting for the refreshes before it to finish.
+    Queued,
+    Running,
+    /// All the data of the refresh was fetched from the source.
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshProgress {
+    pub job_id: Uuid,
+    pub dataset: String,
+    pub state: RefreshState,
+    pub rows_fetched: u64,
+    pub bytes_fetched: u64,
+    /// The partitions the source is read in, once the refresh has planned its scan.
+    pub partitions: Option<usize>,
+    pub partitions_completed: usize,
+    /// The partition the last fetched rows came from.
+    pub current_partition: Option<usize>,
+    /// The rows the refresh is expected to fetch, from the previous refresh or the statistics of the source.
+    pub expected_rows: Option<u64>,
+    pub eta_seconds: Option<u64>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RefreshProgress {
+    fn new(job_id: Uuid, dataset: &TableReference, state: RefreshState) -> Self {
+        Self {
+            job_id,
+            dataset: dataset.to_string(),
+            state,
+            rows_fetched: 0,
+            bytes_fetched: 0,
+            partitions: None,
+            partitions_completed: 0,
+            current_partition: None,
+            expected_rows: None,
+            eta_seconds: None,
+            started_at: None,
+            updated_at: Utc::now(),
+            error: None,
+        }
+    }
+
+    /// Extrapolates the time left from the rate rows were fetched at so far.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn update_eta(&mut self, elapsed: Duration) {
+        self.eta_seconds = match self.expected_rows {
+            Some(expected) if self.rows_fetched > 0 && self.rows_fetched < expected => {
+                let left = (expected - self.rows_fetched) as f64 / self.rows_fetched as f64;
+                Some((elapsed.as_secs_f64() * left).ceil() as u64)
+            }
+            _ => None,
+        };
+    }
+}
+
+static JOBS: Lazy<Mutex<HashMap<String, VecDeque<RefreshProgress>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn with_jobs<T>(
+    dataset: &TableReference,
+    f: impl FnOnce(&mut VecDeque<RefreshProgress>) -> T,
+) -> T {
+    let mut jobs = JOBS.lock().unwrap_or_else(PoisonError::into_inner);
+    let jobs = jobs.entry(dataset.to_string()).or_default();
+    let result = f(jobs);
+    while jobs.len() > MAX_JOBS_PER_DATASET {
+        jobs.pop_front();
+    }
+    result
+}
+
+/// Queues a job for the next refresh of `dataset`, returning its id.
+pub fn queue(dataset: &TableReference) -> Uuid {
+    let job_id = Uuid::new_v4();
+    with_jobs(dataset, |jobs| {
+        jobs.push_back(RefreshProgress::new(job_id, dataset, RefreshState::Queued));
+    });
+    job_id
+}
+
+/// Fails a queued job whose refresh could not be triggered.
+pub fn cancel(dataset: &TableReference, job_id: Uuid, error: String) {
+    with_jobs(dataset, |jobs| {
+        if let Some(job) = jobs.iter_mut().find(|job| job.job_id == job_id) {
+            job.state = RefreshState::Failed;
+            job.error = Some(error);
+            job.updated_at = Utc::now();
+        }
+    });
+}
+
+#[must_use]
+pub fn get(dataset: &TableReference, job_id: Uuid) -> Option<RefreshProgress> {
+    with_jobs(dataset, |jobs| {
+        jobs.iter().find(|job| job.job_id == job_id).cloned()
+    })
+}
+
+pub fn remove(dataset: &TableReference) {
+    JOBS.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&dataset.to_string());
+}
+
+/// Reports the progress of a running refresh. A job dropped before it completes has failed.
+pub struct RefreshJob {
+    dataset: TableReference,
+    job_id: Uuid,
+    started: Instant,
+    last_logged: Mutex<Instant>,
+    finished: bool,
+}
+
+impl RefreshJob {
+    /// Starts the oldest queued job of `dataset`, or a new job for a scheduled refresh.
+    pub(crate) fn start(dataset: &TableReference) -> Self {
+        let job_id = with_jobs(dataset, |jobs| {
+            let previous_rows = jobs
+                .iter()
+                .rev()
+                .find(|job| job.state == RefreshState::Completed)
+                .map(|job| job.rows_fetched);
+            let index = jobs
+                .iter()
+                .position(|job| job.state == RefreshState::Queued)
+                .unwrap_or_else(|| {
+                    jobs.push_back(RefreshProgress::new(
+                        Uuid::new_v4(),
+                        dataset,
+                        RefreshState::Queued,
+                    ));
+                    jobs.len() - 1
+                });
+            let job = &mut jobs[index];
+            job.state = RefreshState::Running;
+            job.expected_rows = previous_rows;
+            job.started_at = Some(Utc::now());
+            job.updated_at = Utc::now();
+            job.job_id
+        });
+
+        Self {
+            dataset: dataset.clone(),
+            job_id,
+            started: Instant::now(),
+            last_logged: Mutex::new(Instant::now()),
+            finished: false,
+        }
+    }
+
+    #[must_use]
+    pub fn job_id(&self) -> Uuid {
+        self.job_id
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RefreshProgress)) -> Option<RefreshProgress> {
+        with_jobs(&self.dataset, |jobs| {
+            let job = jobs.iter_mut().find(|job| job.job_id == self.job_id)?;
+            f(job);
+            job.update_eta(self.started.elapsed());
+            job.updated_at = Utc::now();
+            Some(job.clone())
+        })
+    }
+
+    /// Records the partitions of the scan of the source, and the rows its statistics expect if no previous refresh
+    /// fetched any.
+    pub(crate) fn planned(&self, partitions: usize, estimated_rows: Option<usize>) {
+        self.update(|job| {
+            job.partitions = Some(partitions);
+            if job.expected_rows.is_none() {
+                job.expected_rows = estimated_rows.map(|rows| rows as u64);
+            }
+        });
+    }
+
+    pub(crate) fn fetched(&self, partition: usize, batch: &RecordBatch) {
+        let progress = self.update(|job| {
+            job.rows_fetched += batch.num_rows() as u64;
+            job.bytes_fetched += batch.get_array_memory_size() as u64;
+            job.current_partition = Some(partition);
+        });
+
+        let mut last_logged = self
+            .last_logged
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if last_logged.elapsed() < PROGRESS_LOG_INTERVAL {
+            return;
+        }
+        *last_logged = Instant::now();
+        if let Some(progress) = progress {
+            self.log(&progress);
+        }
+    }
+
+    pub(crate) fn partition_completed(&self) {
+        self.update(|job| job.partitions_completed += 1);
+    }
+
+    pub(crate) fn complete(mut self) {
+        self.finish(RefreshState::Completed, None);
+    }
+
+    pub(crate) fn fail(mut self, error: String) {
+        self.finish(RefreshState::Failed, Some(error));
+    }
+
+    fn finish(&mut self, state: RefreshState, error: Option<String>) {
+        self.finished = true;
+        self.update(|job| {
+            job.state = state;
+            job.error = error;
+            if state == RefreshState::Completed {
+                job.expected_rows = Some(job.rows_fetched);
+            }
+        });
+    }
+
+    fn log(&self, progress: &RefreshProgress) {
+        let dataset = &self.dataset;
+        let rows =
+            util::pretty_print_number(usize::try_from(progress.rows_fetched).unwrap_or(usize::MAX));
+        let bytes = util::human_readable_bytes(
+            usize::try_from(progress.bytes_fetched).unwrap_or(usize::MAX),
+        );
+        let partition = match (progress.current_partition, progress.partitions) {
+            (Some(current), Some(partitions)) => {
+                format!(", partition {} of {partitions}", current + 1)
+            }
+            _ => String::new(),
+        };
+        let eta = progress
+            .eta_seconds
+            .map(|eta| format!(", about {eta}s left"))
+            .unwrap_or_default();
+
+        if dataset.schema() == Some(SPICE_RUNTIME_SCHEMA) {
+            tracing::debug!(
+                job_id = %self.job_id,
+                rows_fetched = progress.rows_fetched,
+                bytes_fetched = progress.bytes_fetched,
+                eta_seconds = progress.eta_seconds,
+                "Refreshing dataset {dataset}: fetched {rows} rows ({bytes}){partition}{eta}"
+            );
+        } else {
+            tracing::info!(
+                job_id = %self.job_id,
+                rows_fetched = progress.rows_fetched,
+                bytes_fetched = progress.bytes_fetched,
+                eta_seconds = progress.eta_seconds,
+                "Refreshing dataset {dataset}: fetched {rows} rows ({bytes}){partition}{eta}"
+            );
+        }
+    }
+}
+
+impl Drop for RefreshJob {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish(
+                RefreshState::Failed,
+                Some("The refresh was interrupted".to_string()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int64Array,
+        datatypes::{DataType, Field, Schema},
+    };
+
+    use super::*;
+
+    #[test]
+    fn reports_progress_of_queued_job() {
+        let dataset = TableReference::bare("progress_test");
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .expect("batch");
+
+        let job_id = queue(&dataset);
+        assert_eq!(
+            get(&dataset, job_id).map(|job| job.state),
+            Some(RefreshState::Queued)
+        );
+
+        let job = RefreshJob::start(&dataset);
+        assert_eq!(job.job_id(), job_id);
+        job.planned(2, Some(12));
+        job.fetched(1, &batch);
+        job.partition_completed();
+
+        let progress = get(&dataset, job_id).expect("job");
+        assert_eq!(progress.state, RefreshState::Running);
+        assert_eq!(progress.rows_fetched, 3);
+        assert_eq!(progress.current_partition, Some(1));
+        assert_eq!(progress.expected_rows, Some(12));
+        assert_eq!(progress.partitions_completed, 1);
+
+        job.complete();
+        let next = RefreshJob::start(&dataset);
+        assert_ne!(next.job_id(), job_id);
+        assert_eq!(
+            get(&dataset, next.job_id()).and_then(|job| job.expected_rows),
+            Some(3)
+        );
+        drop(next);
+        remove(&dataset);
+    }
+}
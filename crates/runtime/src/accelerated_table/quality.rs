@@ -0,0 +1,254 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runs the `quality_checks` of a dataset against the data of a refresh, before it is inserted into the accelerator.
+//!
+//! The checks query the refreshed data as a table named after the dataset, without its schema. For appends, the
+//! checks only see the appended rows, so `unique` doesn't catch duplicates of rows that are already accelerated.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{AsArray, RecordBatch},
+    datatypes::Int64Type,
+};
+use datafusion::{
+    datasource::MemTable, error::DataFusionError, execution::context::SessionContext,
+    sql::TableReference,
+};
+use spicepod::component::dataset::quality::{OnFailure, QualityCheck};
+
+use crate::dataupdate::DataUpdate;
+
+/// The checks the data of a refresh didn't pass, by whether they fail the refresh.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct QualityReport {
+    pub failures: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+pub(crate) async fn check(
+    dataset: &TableReference,
+    checks: &[QualityCheck],
+    data_update: &DataUpdate,
+) -> Result<QualityReport, DataFusionError> {
+    let mut report = QualityReport::default();
+    if checks.is_empty() {
+        return Ok(report);
+    }
+
+    let ctx = SessionContext::new();
+    let table = MemTable::try_new(
+        Arc::clone(&data_update.schema),
+        vec![data_update.data.clone()],
+    )?;
+    ctx.register_table(dataset.table(), Arc::new(table))?;
+    let table = ident(dataset.table());
+
+    for check in checks {
+        let violations = match run(&ctx, &table, check).await {
+            Ok(violations) => violations,
+            Err(e) => vec![format!("unable to run the check: {e}")],
+        };
+        match check.on_failure {
+            OnFailure::Fail => report.failures.extend(violations),
+            OnFailure::Warn => report.warnings.extend(violations),
+        }
+    }
+
+    Ok(report)
+}
+
+async fn run(
+    ctx: &SessionContext,
+    table: &str,
+    check: &QualityCheck,
+) -> Result<Vec<String>, DataFusionError> {
+    let mut violations = vec![];
+
+    for column in &check.not_null {
+        let rows = count(
+            ctx,
+            &format!(
+                "SELECT COUNT(*) FROM {table} WHERE {} IS NULL",
+                ident(column)
+            ),
+        )
+        .await?;
+        if rows > 0 {
+            violations.push(format!("{rows} rows have a null {column}"));
+        }
+    }
+
+    if !check.unique.is_empty() {
+        let columns = check
+            .unique
+            .iter()
+            .map(|column| ident(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let duplicates = count(
+            ctx,
+            &format!("SELECT COUNT(*) FROM (SELECT {columns} FROM {table} GROUP BY {columns} HAVING COUNT(*) > 1)"),
+        )
+        .await?;
+        if duplicates > 0 {
+            violations.push(format!(
+                "{duplicates} values of ({}) are duplicated",
+                check.unique.join(", ")
+            ));
+        }
+    }
+
+    if let Some(range) = &check.range {
+        let column = ident(&range.column);
+        let conditions = range
+            .min
+            .map(|min| format!("{column} < {min}"))
+            .into_iter()
+            .chain(range.max.map(|max| format!("{column} > {max}")))
+            .collect::<Vec<_>>();
+        if !conditions.is_empty() {
+            let rows = count(
+                ctx,
+                &format!(
+                    "SELECT COUNT(*) FROM {table} WHERE {}",
+                    conditions.join(" OR ")
+                ),
+            )
+            .await?;
+            if rows > 0 {
+                violations.push(format!(
+                    "{rows} values of {} are outside of [{}, {}]",
+                    range.column,
+                    range.min.map_or("-inf".to_string(), |min| min.to_string()),
+                    range.max.map_or("inf".to_string(), |max| max.to_string())
+                ));
+            }
+        }
+    }
+
+    if let Some(sql) = &check.sql {
+        let rows = ctx
+            .sql(sql)
+            .await?
+            .collect()
+            .await?
+            .iter()
+            .map(RecordBatch::num_rows)
+            .sum::<usize>();
+        if rows > 0 {
+            violations.push(format!("{rows} rows returned by: {sql}"));
+        }
+    }
+
+    Ok(violations)
+}
+
+async fn count(ctx: &SessionContext, sql: &str) -> Result<i64, DataFusionError> {
+    let batches = ctx.sql(sql).await?.collect().await?;
+    batches
+        .first()
+        .and_then(|batch| batch.column(0).as_primitive_opt::<Int64Type>())
+        .filter(|counts| !counts.is_empty())
+        .map(|counts| counts.value(0))
+        .ok_or_else(|| DataFusionError::Execution("COUNT(*) returned no rows".to_string()))
+}
+
+fn ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use spicepod::component::dataset::quality::RangeCheck;
+
+    use crate::dataupdate::UpdateType;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_failed_checks() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 2, 40])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    None,
+                    Some("c"),
+                    Some("d"),
+                ])),
+            ],
+        )
+        .expect("valid batch");
+        let data_update = DataUpdate {
+            schema,
+            data: vec![batch],
+            update_type: UpdateType::Overwrite,
+        };
+
+        let checks = vec![
+            QualityCheck {
+                not_null: vec!["id".to_string(), "name".to_string()],
+                ..QualityCheck::default()
+            },
+            QualityCheck {
+                unique: vec!["id".to_string()],
+                on_failure: OnFailure::Warn,
+                ..QualityCheck::default()
+            },
+            QualityCheck {
+                range: Some(RangeCheck {
+                    column: "id".to_string(),
+                    min: Some(1.0),
+                    max: Some(10.0),
+                }),
+                ..QualityCheck::default()
+            },
+            QualityCheck {
+                sql: Some("SELECT * FROM users WHERE name = 'c'".to_string()),
+                on_failure: OnFailure::Warn,
+                ..QualityCheck::default()
+            },
+        ];
+
+        let report = check(&TableReference::from("app.users"), &checks, &data_update)
+            .await
+            .expect("checks run");
+        assert_eq!(
+            report,
+            QualityReport {
+                failures: vec![
+                    "1 rows have a null name".to_string(),
+                    "1 values of id are outside of [1, 10]".to_string(),
+                ],
+                warnings: vec![
+                    "1 values of (id) are duplicated".to_string(),
+                    "1 rows returned by: SELECT * FROM users WHERE name = 'c'".to_string(),
+                ],
+            }
+        );
+    }
+}
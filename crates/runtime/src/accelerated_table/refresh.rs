@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -5,31 +6,62 @@ use crate::component::dataset::acceleration::RefreshMode;
 use crate::component::dataset::TimeFormat;
 use crate::datafusion::filter_converter::TimestampFilterConvert;
 use crate::datafusion::{schema, SPICE_RUNTIME_SCHEMA};
+use crate::execution_plan::traced::TraceScans;
 use crate::object_store_registry::default_runtime_env;
 use crate::{
+    connector_health,
     dataconnector::get_data,
     dataupdate::{DataUpdate, DataUpdateExecutionPlan, UpdateType},
-    status,
+    events::{self, RuntimeEvent},
+    quotas, shutdown, status,
     timing::TimeMeasurement,
 };
-use arrow::array::TimestampNanosecondArray;
+use arrow::array::{RecordBatch, TimestampNanosecondArray};
 use arrow::datatypes::DataType;
 use async_stream::stream;
 use cache::QueryResultsCacheProvider;
+use chrono::{DateTime, Utc};
 use datafusion::common::TableReference;
 use datafusion::error::DataFusionError;
 use datafusion::execution::config::SessionConfig;
 use datafusion::logical_expr::{cast, col, Expr, Operator};
 use datafusion::physical_plan::{collect, ExecutionPlanProperties};
 use datafusion::prelude::DataFrame;
-use datafusion::{datasource::TableProvider, execution::context::SessionContext};
+use datafusion::{
+    datasource::TableProvider,
+    execution::context::{SessionContext, SessionState},
+};
 use futures::Stream;
 use futures::{stream::BoxStream, StreamExt};
 use snafu::prelude::*;
+use spicepod::component::dataset::quality::QualityCheck;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::oneshot;
 use tokio::sync::RwLock;
 use tokio_stream::wrappers::ReceiverStream;
+use tracing::instrument;
+
+use super::progress::RefreshJob;
+use super::quality;
+use super::versions::RetainedVersions;
+
+/// Whether the refreshes of every accelerated dataset are paused, i.e. during a maintenance window of their sources.
+static REFRESHES_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Pauses scheduled refreshes and rejects manual ones until [`resume_refreshes`] is called. Datasets still load
+/// their initial data, and refreshes already running finish.
+pub fn pause_refreshes() {
+    REFRESHES_PAUSED.store(true, Ordering::Release);
+}
+
+pub fn resume_refreshes() {
+    REFRESHES_PAUSED.store(false, Ordering::Release);
+}
+
+#[must_use]
+pub fn refreshes_paused() -> bool {
+    REFRESHES_PAUSED.load(Ordering::Acquire)
+}
 
 #[derive(Clone, Debug)]
 pub struct Refresh {
@@ -87,6 +119,9 @@ pub struct Refresher {
     refresh: Arc<RwLock<Refresh>>,
     accelerator: Arc<dyn TableProvider>,
     cache_provider: Option<Arc<QueryResultsCacheProvider>>,
+    storage_file: Option<String>,
+    retained_versions: Option<RetainedVersions>,
+    quality_checks: Vec<QualityCheck>,
 }
 
 impl Refresher {
@@ -102,6 +137,9 @@ impl Refresher {
             refresh,
             accelerator,
             cache_provider: None,
+            storage_file: None,
+            retained_versions: None,
+            quality_checks: Vec::new(),
         }
     }
 
@@ -113,6 +151,21 @@ impl Refresher {
         self
     }
 
+    pub fn storage_file(&mut self, storage_file: Option<String>) -> &mut Self {
+        self.storage_file = storage_file;
+        self
+    }
+
+    pub fn retained_versions(&mut self, retained_versions: Option<RetainedVersions>) -> &mut Self {
+        self.retained_versions = retained_versions;
+        self
+    }
+
+    pub fn quality_checks(&mut self, quality_checks: Vec<QualityCheck>) -> &mut Self {
+        self.quality_checks = quality_checks;
+        self
+    }
+
     pub(crate) async fn start(
         &self,
         acceleration_refresh_mode: AccelerationRefreshMode,
@@ -127,14 +180,23 @@ impl Refresher {
 
         loop {
             let future_result = stream.next().await;
+            let _in_flight = shutdown::track();
 
             match future_result {
                 Some(result) => {
                     let (start_time, data_update) = match result {
                         Ok((start_time, data_update)) => (start_time, data_update),
+                        // The dataset keeps serving its accelerated data while its source is unavailable.
+                        Err(
+                            e @ (super::Error::SourceUnavailable { .. }
+                            | super::Error::RuntimeShuttingDown),
+                        ) => {
+                            tracing::warn!("Skipping the refresh of dataset {dataset_name}: {e}");
+                            continue;
+                        }
                         Err(e) => {
                             tracing::debug!("Error getting update for dataset {dataset_name}: {e}");
-                            self.mark_dataset_status(status::ComponentStatus::Error);
+                            self.mark_dataset_error(e.to_string());
                             continue;
                         }
                     };
@@ -153,7 +215,25 @@ impl Refresher {
                         continue;
                     };
 
+                    if !self.passes_quality_checks(&data_update).await {
+                        continue;
+                    }
+
                     let overwrite = data_update.update_type == UpdateType::Overwrite;
+                    let memory_size = data_update
+                        .data
+                        .iter()
+                        .map(RecordBatch::get_array_memory_size)
+                        .sum::<usize>();
+
+                    if let Err(e) =
+                        quotas::check_accelerated_size(&dataset_name, memory_size as u64, overwrite)
+                    {
+                        tracing::error!("{e}, keeping the accelerated data");
+                        self.mark_dataset_error(e.to_string());
+                        continue;
+                    }
+
                     match self
                         .accelerator
                         .insert_into(
@@ -166,22 +246,27 @@ impl Refresher {
                         Ok(plan) => {
                             if let Err(e) = collect(plan, ctx.task_ctx()).await {
                                 tracing::error!("Error adding data for {dataset_name}: {e}");
-                                self.mark_dataset_status(status::ComponentStatus::Error);
+                                self.mark_dataset_error(e.to_string());
                             } else {
-                                if let Some(start_time) = start_time {
-                                    let num_rows = data_update
-                                        .clone()
-                                        .data
-                                        .into_iter()
-                                        .map(|x| x.num_rows())
-                                        .sum::<usize>();
-
-                                    let memory_size = data_update
-                                        .data
-                                        .into_iter()
-                                        .map(|x| x.get_array_memory_size())
-                                        .sum::<usize>();
+                                let num_rows = data_update
+                                    .data
+                                    .iter()
+                                    .map(RecordBatch::num_rows)
+                                    .sum::<usize>();
+
+                                self.record_refresh_metrics(
+                                    start_time,
+                                    num_rows,
+                                    memory_size,
+                                    overwrite,
+                                )
+                                .await;
 
+                                if overwrite {
+                                    self.record_version(&data_update).await;
+                                }
+
+                                if let Some(start_time) = start_time {
                                     self.trace_dataset_loaded(
                                         start_time,
                                         num_rows,
@@ -206,7 +291,7 @@ impl Refresher {
                             };
                         }
                         Err(e) => {
-                            self.mark_dataset_status(status::ComponentStatus::Error);
+                            self.mark_dataset_error(e.to_string());
                             tracing::error!("Error adding data for {dataset_name}: {e}");
                         }
                     }
@@ -216,6 +301,147 @@ impl Refresher {
         }
     }
 
+    /// Loads data into the acceleration outside of a refresh, such as an imported artifact, returning the number of
+    /// rows loaded.
+    pub(crate) async fn load(&self, data_update: DataUpdate) -> super::Result<usize> {
+        let overwrite = data_update.update_type == UpdateType::Overwrite;
+        let memory_size = data_update
+            .data
+            .iter()
+            .map(RecordBatch::get_array_memory_size)
+            .sum::<usize>();
+        let num_rows = data_update
+            .data
+            .iter()
+            .map(RecordBatch::num_rows)
+            .sum::<usize>();
+        quotas::check_accelerated_size(&self.dataset_name, memory_size as u64, overwrite)
+            .context(super::AccelerationQuotaExceededSnafu)?;
+
+        let ctx = SessionContext::new();
+        let plan = self
+            .accelerator
+            .insert_into(
+                &ctx.state(),
+                Arc::new(DataUpdateExecutionPlan::new(data_update.clone())),
+                overwrite,
+            )
+            .await
+            .context(super::UnableToLoadDataSnafu)?;
+        collect(plan, ctx.task_ctx())
+            .await
+            .context(super::UnableToLoadDataSnafu)?;
+
+        self.record_refresh_metrics(None, num_rows, memory_size, overwrite)
+            .await;
+        if overwrite {
+            self.record_version(&data_update).await;
+        }
+        if let Some(cache_provider) = &self.cache_provider {
+            if let Err(e) = cache_provider
+                .invalidate_for_table(&self.dataset_name.to_string())
+                .await
+            {
+                tracing::error!(
+                    "Failed to invalidate cached results for dataset {}: {e}",
+                    self.dataset_name
+                );
+            }
+        }
+        self.notify_refresh_done(&mut None, status::ComponentStatus::Ready)
+            .await;
+
+        Ok(num_rows)
+    }
+
+    /// The latest value of the `time_column` in the acceleration, which append refreshes continue from.
+    pub(crate) async fn watermark(&self) -> Option<DateTime<Utc>> {
+        if self.refresh.read().await.time_column.is_none() {
+            return None;
+        }
+        match self.get_latest_timestamp().await {
+            Ok(nanos) => nanos
+                .and_then(|nanos| i64::try_from(nanos).ok())
+                .map(DateTime::from_timestamp_nanos),
+            Err(e) => {
+                tracing::debug!(
+                    "Unable to read the watermark of dataset {}: {e}",
+                    self.dataset_name
+                );
+                None
+            }
+        }
+    }
+
+    /// Keeps a full load as a version of the acceleration, if versions are retained.
+    async fn record_version(&self, data_update: &DataUpdate) {
+        let Some(retained_versions) = &self.retained_versions else {
+            return;
+        };
+
+        match retained_versions
+            .record(Arc::clone(&data_update.schema), data_update.data.clone())
+            .await
+        {
+            Ok(version) => tracing::debug!(
+                "Recorded version {} of dataset {}",
+                version.number,
+                self.dataset_name
+            ),
+            Err(e) => tracing::warn!(
+                "Unable to record a version of dataset {}: {e}",
+                self.dataset_name
+            ),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    async fn record_refresh_metrics(
+        &self,
+        start_time: Option<SystemTime>,
+        num_rows: usize,
+        memory_size: usize,
+        overwrite: bool,
+    ) {
+        let labels = [("dataset", self.dataset_name.to_string())];
+
+        if let Some(elapsed) = start_time.and_then(|start_time| start_time.elapsed().ok()) {
+            metrics::histogram!("datasets_acceleration_refresh_duration_ms", &labels)
+                .record(1000_f64 * elapsed.as_secs_f64());
+        }
+        metrics::counter!("datasets_acceleration_refresh_rows", &labels).increment(num_rows as u64);
+
+        // File accelerators report the size of their file, in-memory ones the size of the loaded batches.
+        let size = metrics::gauge!("datasets_acceleration_size_bytes", &labels);
+        match &self.storage_file {
+            Some(storage_file) => match tokio::fs::metadata(storage_file).await {
+                Ok(metadata) => {
+                    size.set(metadata.len() as f64);
+                    quotas::record_accelerated_size(
+                        &self.dataset_name,
+                        memory_size as u64,
+                        Some(metadata.len()),
+                        overwrite,
+                    );
+                }
+                Err(e) => tracing::debug!("Unable to read the size of {storage_file}: {e}"),
+            },
+            None => {
+                if overwrite {
+                    size.set(memory_size as f64);
+                } else {
+                    size.increment(memory_size as f64);
+                }
+                quotas::record_accelerated_size(
+                    &self.dataset_name,
+                    memory_size as u64,
+                    None,
+                    overwrite,
+                );
+            }
+        }
+    }
+
     fn trace_dataset_loaded(
         &self,
         start_time: SystemTime,
@@ -454,6 +680,7 @@ impl Refresher {
             .limit(0, Some(1))
     }
 
+    #[instrument(name = "acceleration_refresh", skip_all, fields(dataset = %self.dataset_name))]
     pub async fn get_full_or_incremental_append_update(
         &self,
         overwrite_timestamp_in_nano: Option<u128>,
@@ -462,10 +689,16 @@ impl Refresher {
         let refresh = self.refresh.read().await;
         let filter_converter = self.get_filter_converter(&refresh);
 
+        ensure!(!shutdown::is_draining(), super::RuntimeShuttingDownSnafu);
+        let _in_flight = shutdown::track();
+        connector_health::try_acquire(&dataset_name).context(super::SourceUnavailableSnafu)?;
+
+        let job = RefreshJob::start(&dataset_name);
+        let job_id = job.job_id();
         if dataset_name.schema() == Some(SPICE_RUNTIME_SCHEMA) {
-            tracing::debug!("Loading data for dataset {dataset_name}");
+            tracing::debug!(%job_id, "Loading data for dataset {dataset_name}");
         } else {
-            tracing::info!("Loading data for dataset {dataset_name}");
+            tracing::info!(%job_id, "Loading data for dataset {dataset_name}");
         }
         status::update_dataset(&dataset_name, status::ComponentStatus::Refreshing);
         let refresh = refresh.clone();
@@ -480,16 +713,26 @@ impl Refresher {
             }
         };
 
-        match self.get_data_update(filters).await {
-            Ok(data) => Ok(data),
+        match self.get_data_update(filters, &job).await {
+            Ok(data) => {
+                connector_health::record_success(&dataset_name);
+                job.complete();
+                Ok(data)
+            }
             Err(e) => {
                 tracing::error!("Failed to load data for dataset {dataset_name}: {e}");
+                connector_health::record_failure(&dataset_name, &e.to_string());
+                job.fail(e.to_string());
                 Err(e)
             }
         }
     }
 
-    async fn get_data_update(&self, filters: Vec<Expr>) -> super::Result<DataUpdate> {
+    async fn get_data_update(
+        &self,
+        filters: Vec<Expr>,
+        job: &RefreshJob,
+    ) -> super::Result<DataUpdate> {
         let refresh = self.refresh.read().await;
         let update_type = match refresh.mode {
             RefreshMode::Full => UpdateType::Overwrite,
@@ -504,6 +747,7 @@ impl Refresher {
             Arc::clone(&federated),
             refresh.sql.clone(),
             filters,
+            Some(job),
         )
         .await
         .map(|data| DataUpdate {
@@ -517,22 +761,21 @@ impl Refresher {
     }
 
     fn get_refresh_df_context(&self) -> SessionContext {
-        let ctx = SessionContext::new_with_config_rt(
+        let state = SessionState::new_with_config_rt(
             SessionConfig::new().set_bool(
                 "datafusion.execution.listing_table_ignore_subdirectory",
                 false,
             ),
             default_runtime_env(),
-        );
+        )
+        .add_physical_optimizer_rule(Arc::new(TraceScans::new()));
+        let ctx = SessionContext::new_with_state(state);
+        // The refresh_sql of the shards of a partitioned acceleration filters on `cluster_shard`.
+        ctx.register_udf(crate::cluster::shard::shard_udf());
 
         let ctx_state = ctx.state();
         let default_catalog = &ctx_state.config_options().catalog.default_catalog;
-        match schema::ensure_schema_exists(&ctx, default_catalog, &self.dataset_name) {
-            Ok(()) => (),
-            Err(_) => {
-                unreachable!("The default catalog should always exist");
-            }
-        };
+        schema::ensure_schema_exists(&ctx, default_catalog, &self.dataset_name);
 
         if let Err(e) = ctx.register_table(self.dataset_name.clone(), Arc::clone(&self.federated)) {
             tracing::error!("Unable to register federated table: {e}");
@@ -576,6 +819,13 @@ impl Refresher {
         };
 
         self.mark_dataset_status(status);
+        if status == status::ComponentStatus::Ready {
+            status::mark_dataset_refreshed(&self.dataset_name);
+            events::publish(RuntimeEvent::RefreshComplete {
+                dataset: self.dataset_name.clone(),
+                error: None,
+            });
+        }
 
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -597,6 +847,52 @@ impl Refresher {
             metrics::counter!("datasets_acceleration_refresh_errors", &labels).increment(1);
         }
     }
+
+    /// Runs the quality checks of the dataset on the data of a refresh, which is discarded if it fails a check.
+    async fn passes_quality_checks(&self, data_update: &DataUpdate) -> bool {
+        let dataset_name = &self.dataset_name;
+        let report = match quality::check(dataset_name, &self.quality_checks, data_update).await {
+            Ok(report) => report,
+            Err(e) => {
+                tracing::error!("Unable to run the quality checks of {dataset_name}: {e}");
+                self.mark_dataset_error(e.to_string());
+                return false;
+            }
+        };
+
+        if !report.warnings.is_empty() {
+            tracing::warn!(
+                "Refreshed data of {dataset_name} failed quality checks: {}",
+                report.warnings.join(", ")
+            );
+        }
+        if report.failures.is_empty() {
+            return true;
+        }
+
+        tracing::error!(
+            "Refreshed data of {dataset_name} failed quality checks, keeping the accelerated data: {}",
+            report.failures.join(", ")
+        );
+        let labels = [("dataset", dataset_name.to_string())];
+        metrics::counter!("datasets_acceleration_quality_check_failures", &labels).increment(1);
+        self.mark_dataset_error(format!(
+            "Quality checks failed: {}",
+            report.failures.join(", ")
+        ));
+        false
+    }
+
+    fn mark_dataset_error(&self, error: String) {
+        events::publish(RuntimeEvent::RefreshComplete {
+            dataset: self.dataset_name.clone(),
+            error: Some(error.clone()),
+        });
+        status::update_dataset_error(&self.dataset_name, error);
+
+        let labels = [("dataset", self.dataset_name.to_string())];
+        metrics::counter!("datasets_acceleration_refresh_errors", &labels).increment(1);
+    }
 }
 
 pub(crate) fn get_timestamp(time: SystemTime) -> u128 {
@@ -0,0 +1,140 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! When the scheduled refreshes of an accelerated dataset run: every `refresh_check_interval`, or at the times of a
+//! `refresh_cron` expression in the `refresh_cron_timezone`, i.e. `0 2 * * *` to refresh heavy datasets at 2am when
+//! their sources are quiet. Cron schedules are checked at the start of every minute, like the schedules of jobs.
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use snafu::prelude::*;
+
+use crate::jobs::{self, Schedule};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    InvalidCronExpression { source: jobs::Error },
+
+    #[snafu(display(
+        "Unknown timezone {timezone}, expected an IANA name such as America/New_York"
+    ))]
+    UnknownTimezone { timezone: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefreshSchedule {
+    Interval(Duration),
+    Cron(CronSchedule),
+}
+
+impl RefreshSchedule {
+    /// How long to wait from `now` until the schedule is next checked, and whether a refresh is due then.
+    #[must_use]
+    pub fn next_check(&self, now: DateTime<Utc>) -> (Duration, bool) {
+        match self {
+            Self::Interval(interval) => (*interval, true),
+            Self::Cron(cron) => {
+                let next_minute = jobs::next_minute(now);
+                (
+                    (next_minute - now).to_std().unwrap_or_default(),
+                    cron.matches(next_minute),
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for RefreshSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Interval(interval) => write!(f, "every {interval:?}"),
+            Self::Cron(cron) => write!(f, "at {} ({})", cron.expression, cron.timezone),
+        }
+    }
+}
+
+/// A cron expression, evaluated in a timezone so refreshes stay at the same local time across daylight saving
+/// changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    schedule: Schedule,
+    timezone: Tz,
+}
+
+impl CronSchedule {
+    /// Parses `expression` in `timezone`, UTC by default.
+    pub fn parse(expression: &str, timezone: Option<&str>) -> Result<Self> {
+        let timezone = match timezone {
+            Some(timezone) => timezone
+                .parse::<Tz>()
+                .map_err(|_| UnknownTimezoneSnafu { timezone }.build())?,
+            None => Tz::UTC,
+        };
+
+        Ok(Self {
+            expression: expression.to_string(),
+            schedule: expression.parse().context(InvalidCronExpressionSnafu)?,
+            timezone,
+        })
+    }
+
+    /// Returns whether a refresh is due in the minute of `time`.
+    #[must_use]
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        self.schedule.matches(&time.with_timezone(&self.timezone))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s)
+            .expect("valid timestamp")
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn matches_cron_in_timezone() {
+        let nightly =
+            CronSchedule::parse("0 2 * * *", Some("America/New_York")).expect("valid schedule");
+        // 2am in New York is 6am UTC in summer, and 7am UTC in winter.
+        assert!(nightly.matches(utc("2024-07-01T06:00:00Z")));
+        assert!(nightly.matches(utc("2024-12-01T07:00:00Z")));
+        assert!(!nightly.matches(utc("2024-07-01T02:00:00Z")));
+
+        let schedule = RefreshSchedule::Cron(nightly);
+        assert_eq!(
+            schedule.next_check(utc("2024-07-01T05:59:30Z")),
+            (Duration::from_secs(30), true)
+        );
+        assert_eq!(
+            schedule.next_check(utc("2024-07-01T06:00:30Z")),
+            (Duration::from_secs(30), false)
+        );
+
+        assert!(CronSchedule::parse("0 2 * *", None).is_err());
+        assert!(CronSchedule::parse("0 2 * * *", Some("Mars/Olympus")).is_err());
+    }
+}
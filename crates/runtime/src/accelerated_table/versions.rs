@@ -0,0 +1,244 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Keeps the previous loads of a `mode: file` acceleration as versions, so queries can compare the current data to a
+//! previous load with `FOR SYSTEM_TIME AS OF` or the `acceleration_version` table function.
+//!
+//! Each full refresh is also written as `<number>-<unix millis>.parquet` to the `<file>.versions` directory next to
+//! the acceleration file, and the oldest versions beyond the `retained_versions` previous loads are deleted. Versions
+//! are read back from the directory, so they survive restarts.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use chrono::{DateTime, Utc};
+use datafusion::parquet::{arrow::ArrowWriter, errors::ParquetError};
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to access the versions in {}: {source}", path.display()))]
+    UnableToAccessVersions {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Unable to write version {}: {source}", path.display()))]
+    UnableToWriteVersion { path: PathBuf, source: ParquetError },
+
+    #[snafu(display("Unable to write version: {source}"))]
+    UnableToJoinWriter { source: tokio::task::JoinError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A previous load of an acceleration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version {
+    /// Increases with each load, starting at 1.
+    pub number: u64,
+    pub loaded_at: DateTime<Utc>,
+    pub path: PathBuf,
+}
+
+impl Version {
+    /// Parses the version from its `<number>-<unix millis>.parquet` file name.
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.strip_suffix(".parquet")?;
+        let (number, millis) = name.split_once('-')?;
+        Some(Self {
+            number: number.parse().ok()?,
+            loaded_at: DateTime::from_timestamp_millis(millis.parse().ok()?)?,
+            path,
+        })
+    }
+}
+
+/// Which version of an acceleration a query reads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionSelector {
+    Number(u64),
+    /// The last version loaded at or before a time.
+    AsOf(DateTime<Utc>),
+}
+
+impl VersionSelector {
+    /// Selects the version from `versions`, ordered oldest first.
+    #[must_use]
+    pub fn select<'a>(&self, versions: &'a [Version]) -> Option<&'a Version> {
+        match self {
+            Self::Number(number) => versions.iter().find(|v| v.number == *number),
+            Self::AsOf(time) => versions.iter().rev().find(|v| v.loaded_at <= *time),
+        }
+    }
+}
+
+impl fmt::Display for VersionSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "version {number}"),
+            Self::AsOf(time) => write!(f, "as of {}", time.to_rfc3339()),
+        }
+    }
+}
+
+/// Where the versions of an acceleration are kept, and how many previous loads are retained.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetainedVersions {
+    dir: PathBuf,
+    retained: usize,
+}
+
+impl RetainedVersions {
+    /// Keeps `retained` previous loads of the acceleration stored in `storage_file`.
+    #[must_use]
+    pub fn new(storage_file: &str, retained: usize) -> Self {
+        Self {
+            dir: PathBuf::from(format!("{storage_file}.versions")),
+            retained,
+        }
+    }
+
+    /// Lists the versions, oldest first.
+    pub fn list(&self) -> Result<Vec<Version>> {
+        list(&self.dir)
+    }
+
+    /// Writes `batches` as the next version, deleting the versions beyond the retained previous loads.
+    pub(crate) async fn record(
+        &self,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> Result<Version> {
+        let dir = self.dir.clone();
+        let retained = self.retained;
+        tokio::task::spawn_blocking(move || record(&dir, &schema, &batches, retained))
+            .await
+            .context(UnableToJoinWriterSnafu)?
+    }
+}
+
+fn list(dir: &Path) -> Result<Vec<Version>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(source) => {
+            return Err(Error::UnableToAccessVersions {
+                path: dir.to_path_buf(),
+                source,
+            })
+        }
+    };
+
+    let mut versions = entries
+        .filter_map(|entry| Version::from_path(entry.ok()?.path()))
+        .collect::<Vec<_>>();
+    versions.sort_by_key(|v| v.number);
+    Ok(versions)
+}
+
+fn record(
+    dir: &Path,
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+    retained: usize,
+) -> Result<Version> {
+    fs::create_dir_all(dir).context(UnableToAccessVersionsSnafu { path: dir })?;
+    let mut versions = list(dir)?;
+
+    let number = versions.last().map_or(1, |v| v.number + 1);
+    let loaded_at = Utc::now();
+    let path = dir.join(format!("{number}-{}.parquet", loaded_at.timestamp_millis()));
+
+    // Written under a temporary name and renamed, so queries never read a partially written version.
+    let partial = dir.join(format!(".{number}.parquet.partial"));
+    let file = File::create(&partial).context(UnableToAccessVersionsSnafu { path: &partial })?;
+    let mut writer = ArrowWriter::try_new(file, Arc::clone(schema), None)
+        .context(UnableToWriteVersionSnafu { path: &partial })?;
+    for batch in batches {
+        writer
+            .write(batch)
+            .context(UnableToWriteVersionSnafu { path: &partial })?;
+    }
+    writer
+        .close()
+        .context(UnableToWriteVersionSnafu { path: &partial })?;
+    fs::rename(&partial, &path).context(UnableToAccessVersionsSnafu { path: &path })?;
+
+    let version = Version {
+        number,
+        loaded_at,
+        path,
+    };
+    versions.push(version.clone());
+
+    // The current load is kept along with the retained previous ones.
+    let expired = versions.len().saturating_sub(retained + 1);
+    for old in &versions[..expired] {
+        if let Err(e) = fs::remove_file(&old.path) {
+            tracing::warn!(
+                "Unable to delete expired version {}: {e}",
+                old.path.display()
+            );
+        }
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_versions() {
+        let versions: Vec<Version> = ["1-1719792000000.parquet", "2-1719878400000.parquet"]
+            .into_iter()
+            .map(|name| Version::from_path(PathBuf::from("taxi_trips.db.versions").join(name)))
+            .collect::<Option<_>>()
+            .expect("valid version file names");
+        assert_eq!(
+            Version::from_path(PathBuf::from(".3.parquet.partial")),
+            None
+        );
+
+        let at = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .expect("valid timestamp")
+                .with_timezone(&Utc)
+        };
+        let selected = |selector: VersionSelector| selector.select(&versions).map(|v| v.number);
+        assert_eq!(selected(VersionSelector::Number(2)), Some(2));
+        assert_eq!(selected(VersionSelector::Number(3)), None);
+        assert_eq!(
+            selected(VersionSelector::AsOf(at("2024-07-01T12:00:00Z"))),
+            Some(1)
+        );
+        assert_eq!(
+            selected(VersionSelector::AsOf(at("2024-07-02T00:00:00Z"))),
+            Some(2)
+        );
+        assert_eq!(
+            selected(VersionSelector::AsOf(at("2024-06-30T00:00:00Z"))),
+            None
+        );
+    }
+}
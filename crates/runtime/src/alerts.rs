@@ -0,0 +1,401 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks the alerts of the spicepod on their schedules and after the refreshes of their datasets, and notifies
+//! webhooks, Slack or PagerDuty when an alert starts or stops firing.
+//!
+//! An alert fires while its SQL returns rows, so a threshold is written as a condition on the rows, i.e.
+//! `SELECT avg(latency_ms) AS latency FROM requests HAVING avg(latency_ms) > 500`. Notifications are only sent when
+//! the alert changes between firing and resolved, not on every check.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use arrow::array::RecordBatch;
+use chrono::{DateTime, Utc};
+use datafusion::sql::TableReference;
+use futures::StreamExt;
+use reqwest::header::CONTENT_TYPE;
+use secrets::SecretsProvider;
+use serde_json::{json, Value};
+use snafu::{OptionExt, ResultExt, Snafu};
+use spicepod::component::alert::{Alert, AlertNotifier, AlertNotifierKind};
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{
+    datafusion::{
+        query::{Protocol, QueryBuilder},
+        DataFusion,
+    },
+    events::{self, RuntimeEvent},
+    jobs::{self, Schedule},
+    Runtime,
+};
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// The number of rows of the results included in notifications.
+const MAX_NOTIFIED_ROWS: usize = 10;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read secret {name}: {source}"))]
+    UnableToReadSecret {
+        name: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display(
+        "The {kind:?} notifier requires {setting}, set it or the {setting} key of its secret"
+    ))]
+    MissingNotifierSetting {
+        kind: AlertNotifierKind,
+        setting: &'static str,
+    },
+
+    #[snafu(display("Unable to send notification: {source}"))]
+    UnableToSendNotification { source: reqwest::Error },
+}
+
+/// What an alert is checked on.
+enum Trigger {
+    /// The start of a minute, for the alerts scheduled then.
+    Minute(DateTime<Utc>),
+    /// The refresh of a dataset.
+    Refresh(TableReference),
+}
+
+#[derive(Default)]
+struct AlertState {
+    checking: bool,
+    firing: bool,
+}
+
+/// Checks the alerts of the app when they are due, until the runtime stops.
+pub(crate) async fn watch(rt: &Runtime) {
+    let mut events = events::subscribe();
+    let states: Arc<Mutex<HashMap<String, AlertState>>> = Arc::default();
+    let client = reqwest::Client::new();
+    let mut next_minute = jobs::next_minute(Utc::now());
+
+    loop {
+        let trigger = tokio::select! {
+            () = jobs::sleep_until(next_minute) => {
+                let minute = next_minute;
+                next_minute = jobs::next_minute(minute);
+                Trigger::Minute(minute)
+            }
+            event = events.recv() => match event {
+                Ok(RuntimeEvent::RefreshComplete { dataset, error: None }) => {
+                    Trigger::Refresh(dataset)
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Alerts fell behind, {skipped} runtime events were skipped");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+
+        let alerts = match rt.app.read().await.as_ref() {
+            Some(app) => app.alerts.clone(),
+            None => continue,
+        };
+
+        for alert in alerts {
+            let due = match &trigger {
+                Trigger::Minute(minute) => alert
+                    .schedule
+                    .as_deref()
+                    .and_then(|schedule| schedule.parse::<Schedule>().ok())
+                    .is_some_and(|schedule| schedule.matches(minute)),
+                Trigger::Refresh(dataset) => alert
+                    .on_refresh
+                    .iter()
+                    .any(|name| TableReference::from(name.as_str()) == *dataset),
+            };
+            if !due {
+                continue;
+            }
+
+            // A check still running covers this one.
+            let started = states
+                .lock()
+                .map(|mut states| {
+                    let state = states.entry(alert.name.clone()).or_default();
+                    !std::mem::replace(&mut state.checking, true)
+                })
+                .unwrap_or(false);
+            if !started {
+                continue;
+            }
+
+            let df = Arc::clone(&rt.df);
+            let secrets_provider = Arc::clone(&rt.secrets_provider);
+            let client = client.clone();
+            let states = Arc::clone(&states);
+            tokio::spawn(async move {
+                check(&df, &secrets_provider, &client, &alert, &states).await;
+            });
+        }
+    }
+}
+
+/// Checks `alert`, and notifies if it started or stopped firing.
+async fn check(
+    df: &Arc<DataFusion>,
+    secrets_provider: &RwLock<SecretsProvider>,
+    client: &reqwest::Client,
+    alert: &Alert,
+    states: &Mutex<HashMap<String, AlertState>>,
+) {
+    let finish = |firing: Option<bool>| {
+        let Ok(mut states) = states.lock() else {
+            return false;
+        };
+        let state = states.entry(alert.name.clone()).or_default();
+        state.checking = false;
+        match firing {
+            Some(firing) => std::mem::replace(&mut state.firing, firing) != firing,
+            None => false,
+        }
+    };
+
+    let (rows, results) = match evaluate(df, &alert.sql, Protocol::Alert).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!("Unable to check alert {}: {e}", alert.name);
+            finish(None);
+            return;
+        }
+    };
+
+    let firing = rows > 0;
+    if !finish(Some(firing)) {
+        return;
+    }
+
+    let notification = Notification {
+        alert: &alert.name,
+        firing,
+        rows,
+        results,
+        time: Utc::now(),
+    };
+    tracing::info!("{}", notification.message());
+
+    for notifier in &alert.notify {
+        if let Err(e) = notify(client, secrets_provider, notifier, &notification).await {
+            tracing::warn!(
+                "Unable to notify {:?} of alert {}: {e}",
+                notifier.kind,
+                alert.name
+            );
+        }
+    }
+}
+
+/// Runs `sql`, returning the number of rows and the first rows as JSON.
+pub(crate) async fn evaluate(
+    df: &Arc<DataFusion>,
+    sql: &str,
+    protocol: Protocol,
+) -> Result<(usize, Value), String> {
+    let query = QueryBuilder::new(sql.to_string(), Arc::clone(df), protocol).build();
+    let mut data = query.run().await.map_err(|e| e.to_string())?.data;
+
+    let mut rows = 0;
+    let mut notified: Vec<RecordBatch> = vec![];
+    while let Some(batch) = data.next().await {
+        let batch = batch.map_err(|e| e.to_string())?;
+        let remaining = MAX_NOTIFIED_ROWS.saturating_sub(rows);
+        if remaining > 0 {
+            notified.push(batch.slice(0, remaining.min(batch.num_rows())));
+        }
+        rows += batch.num_rows();
+    }
+
+    let mut writer = arrow_json::ArrayWriter::new(Vec::new());
+    writer
+        .write_batches(&notified.iter().collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    let json = writer.into_inner();
+    let results = if json.is_empty() {
+        Value::Array(vec![])
+    } else {
+        serde_json::from_slice(&json).map_err(|e| e.to_string())?
+    };
+
+    Ok((rows, results))
+}
+
+pub(crate) struct Notification<'a> {
+    pub(crate) alert: &'a str,
+    pub(crate) firing: bool,
+    pub(crate) rows: usize,
+    pub(crate) results: Value,
+    pub(crate) time: DateTime<Utc>,
+}
+
+impl Notification<'_> {
+    fn status(&self) -> &'static str {
+        if self.firing {
+            "firing"
+        } else {
+            "resolved"
+        }
+    }
+
+    pub(crate) fn message(&self) -> String {
+        if self.firing {
+            format!(
+                "Alert {} is firing, returning {} rows",
+                self.alert, self.rows
+            )
+        } else {
+            format!("Alert {} is resolved", self.alert)
+        }
+    }
+
+    fn payload(&self) -> Value {
+        json!({
+            "alert": self.alert,
+            "status": self.status(),
+            "rows": self.rows,
+            "results": self.results,
+            "time": self.time.to_rfc3339(),
+        })
+    }
+
+    /// Replaces each `{{ name }}` in `template` with the value of the notification, leaving unknown names as they are.
+    fn render(&self, template: &str) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                break;
+            };
+
+            let value = match rest[start + 2..start + end].trim() {
+                "alert" => self.alert.to_string(),
+                "status" => self.status().to_string(),
+                "rows" => self.rows.to_string(),
+                "results" => self.results.to_string(),
+                "time" => self.time.to_rfc3339(),
+                _ => rest[start..start + end + 2].to_string(),
+            };
+            rendered.push_str(&rest[..start]);
+            rendered.push_str(&value);
+            rest = &rest[start + end + 2..];
+        }
+        rendered.push_str(rest);
+
+        rendered
+    }
+}
+
+pub(crate) async fn notify(
+    client: &reqwest::Client,
+    secrets_provider: &RwLock<SecretsProvider>,
+    notifier: &AlertNotifier,
+    notification: &Notification<'_>,
+) -> Result<(), Error> {
+    let secret = match &notifier.secret {
+        Some(name) => secrets_provider
+            .read()
+            .await
+            .get_secret(name)
+            .await
+            .context(UnableToReadSecretSnafu { name })?,
+        None => None,
+    };
+    let setting = |value: &Option<String>, setting: &'static str| {
+        value
+            .clone()
+            .or_else(|| {
+                secret
+                    .as_ref()
+                    .and_then(|secret| secret.get(setting))
+                    .map(ToString::to_string)
+            })
+            .context(MissingNotifierSettingSnafu {
+                kind: notifier.kind,
+                setting,
+            })
+    };
+    let message = match &notifier.template {
+        Some(template) => notification.render(template),
+        None => notification.message(),
+    };
+
+    let request = match notifier.kind {
+        AlertNotifierKind::Webhook => {
+            let request = client.post(setting(&notifier.url, "url")?);
+            match &notifier.template {
+                Some(_) => request
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(message),
+                None => request.json(&notification.payload()),
+            }
+        }
+        AlertNotifierKind::Slack => client
+            .post(setting(&notifier.url, "url")?)
+            .json(&json!({ "text": message })),
+        AlertNotifierKind::PagerDuty => client.post(PAGERDUTY_EVENTS_URL).json(&json!({
+            "routing_key": setting(&notifier.routing_key, "routing_key")?,
+            "event_action": if notification.firing { "trigger" } else { "resolve" },
+            "dedup_key": format!("spiceai-alert-{}", notification.alert),
+            "payload": {
+                "summary": message,
+                "source": "spiceai",
+                "severity": notifier.severity.as_deref().unwrap_or("error"),
+                "custom_details": notification.payload(),
+            },
+        })),
+    };
+
+    request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .context(UnableToSendNotificationSnafu)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_templates() {
+        let notification = Notification {
+            alert: "stale_orders",
+            firing: true,
+            rows: 2,
+            results: json!([{ "id": 1 }, { "id": 2 }]),
+            time: DateTime::default(),
+        };
+
+        assert_eq!(
+            notification.render(r#"{"text": "{{ alert }} is {{status}} with {{ rows }} rows: {{ results }}", "{{ other }}": 1}"#),
+            r#"{"text": "stale_orders is firing with 2 rows: [{"id":1},{"id":2}]", "{{ other }}": 1}"#
+        );
+    }
+}
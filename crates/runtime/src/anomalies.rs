@@ -0,0 +1,257 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Scores the datasets with `anomaly_detection` for anomalies after each refresh, with the anomaly window functions,
+//! and overwrites `runtime.<dataset>_anomalies` with the scores, i.e.
+//! `SELECT time, value, score FROM runtime.taxi_trips_anomalies WHERE anomalous`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{AsArray, BooleanArray},
+    datatypes::SchemaRef,
+};
+use datafusion::sql::TableReference;
+use spicepod::component::dataset::anomaly_detection::{AnomalyDetection, AnomalyMethod};
+use tokio::sync::broadcast;
+
+use crate::{
+    accelerated_table::refresh::Refresh,
+    component::dataset::acceleration::Acceleration,
+    datafusion::{anomaly::AnomalyFunctionKind, DataFusion, SPICE_RUNTIME_SCHEMA},
+    dataupdate::{DataUpdate, UpdateType},
+    events::{self, RuntimeEvent},
+    internal_table::create_internal_accelerated_table,
+    Runtime,
+};
+
+const DEFAULT_WINDOW: u64 = 24;
+const DEFAULT_MAX_ANOMALIES: u64 = 10;
+const DEFAULT_THRESHOLD: f64 = 3.0;
+
+struct Detection {
+    time_column: String,
+    config: AnomalyDetection,
+}
+
+/// Scores the datasets with `anomaly_detection` after their refreshes, until the runtime stops.
+pub(crate) async fn watch(rt: &Runtime) {
+    let mut events = events::subscribe();
+    // The schemas of the anomalies tables registered so far, which are recreated when the scores change shape.
+    let mut tables: HashMap<TableReference, SchemaRef> = HashMap::new();
+
+    loop {
+        let dataset = match events.recv().await {
+            Ok(RuntimeEvent::RefreshComplete {
+                dataset,
+                error: None,
+            }) => dataset,
+            Ok(RuntimeEvent::DatasetRemoved { dataset }) => {
+                let anomalies = anomalies_table(&dataset);
+                if tables.remove(&anomalies).is_some() {
+                    if let Err(e) = rt.df.remove_table(&anomalies) {
+                        tracing::warn!("Unable to remove {anomalies}: {e}");
+                    }
+                }
+                continue;
+            }
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!(
+                    "Anomaly detection fell behind, {skipped} runtime events were skipped"
+                );
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        let detection = rt.app.read().await.as_ref().and_then(|app| {
+            let ds = app
+                .datasets
+                .iter()
+                .find(|ds| TableReference::from(ds.name.as_str()) == dataset)?;
+            Some(Detection {
+                time_column: ds.time_column.clone()?,
+                config: ds.anomaly_detection.clone()?,
+            })
+        });
+        let Some(detection) = detection else {
+            continue;
+        };
+
+        if let Err(e) = detect(&rt.df, &dataset, &detection, &mut tables).await {
+            tracing::warn!("Unable to detect anomalies in {dataset}: {e}");
+        }
+    }
+}
+
+async fn detect(
+    df: &DataFusion,
+    dataset: &TableReference,
+    detection: &Detection,
+    tables: &mut HashMap<TableReference, SchemaRef>,
+) -> Result<(), String> {
+    let sql = scoring_sql(dataset, &detection.time_column, &detection.config);
+    let dataframe = df.ctx.sql(&sql).await.map_err(|e| e.to_string())?;
+    let schema: SchemaRef = Arc::new(dataframe.schema().as_arrow().clone());
+    let data = dataframe.collect().await.map_err(|e| e.to_string())?;
+
+    let anomalies = anomalies_table(dataset);
+    if tables.get(&anomalies) != Some(&schema) {
+        df.remove_table(&anomalies).map_err(|e| e.to_string())?;
+        let table = create_internal_accelerated_table(
+            anomalies.clone(),
+            Arc::clone(&schema),
+            Acceleration::default(),
+            Refresh::default(),
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        df.register_runtime_table(anomalies.clone(), table)
+            .map_err(|e| e.to_string())?;
+        tables.insert(anomalies.clone(), Arc::clone(&schema));
+    }
+
+    let anomalous = data
+        .iter()
+        .filter_map(|batch| batch.column_by_name("anomalous")?.as_boolean_opt())
+        .map(BooleanArray::true_count)
+        .sum::<usize>();
+    df.write_data(
+        anomalies.clone(),
+        DataUpdate {
+            schema,
+            data,
+            update_type: UpdateType::Overwrite,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if anomalous > 0 {
+        tracing::info!("Found {anomalous} anomalies in {dataset}, see {anomalies}");
+    }
+    Ok(())
+}
+
+/// The table the anomaly scores of a dataset are written to, i.e. `runtime.sales_orders_anomalies` for
+/// `sales.orders`.
+fn anomalies_table(dataset: &TableReference) -> TableReference {
+    let name = dataset.to_string().replace('.', "_");
+    TableReference::partial(SPICE_RUNTIME_SCHEMA, format!("{name}_anomalies"))
+}
+
+/// The query that scores the values of the anomaly detection column, with the `time`, `value`, `score` and
+/// `anomalous` columns after the `partition_by` columns.
+fn scoring_sql(dataset: &TableReference, time_column: &str, config: &AnomalyDetection) -> String {
+    let partition_columns = config
+        .partition_by
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>();
+    let partition_by = if partition_columns.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {} ", partition_columns.join(", "))
+    };
+    let series = format!("OVER ({})", partition_by.trim_end());
+    let ordered = format!(
+        "OVER ({partition_by}ORDER BY {})",
+        quote_identifier(time_column)
+    );
+    let value = format!("CAST({} AS DOUBLE)", quote_identifier(&config.column));
+    let threshold = config.threshold.unwrap_or(DEFAULT_THRESHOLD);
+
+    let (score, anomalous) = match config.method {
+        AnomalyMethod::Zscore => (
+            format!(
+                "{}({value}, {}) {ordered}",
+                AnomalyFunctionKind::Zscore.name(),
+                config.window.unwrap_or(DEFAULT_WINDOW)
+            ),
+            format!("abs(score) > {threshold:?}"),
+        ),
+        AnomalyMethod::Seasonal => (
+            format!(
+                "{}({value}, {}) {ordered}",
+                AnomalyFunctionKind::Seasonal.name(),
+                config.period.unwrap_or(DEFAULT_WINDOW)
+            ),
+            format!("abs(score) > {threshold:?}"),
+        ),
+        // The test flags the outliers, and the score is how far each value is from the mean of its series.
+        AnomalyMethod::Esd => (
+            format!("({value} - avg({value}) {series}) / stddev({value}) {series}"),
+            // Evaluated over the scored rows, where the time column is `time`.
+            format!(
+                "{}(value, {}) OVER ({partition_by}ORDER BY time)",
+                AnomalyFunctionKind::Esd.name(),
+                config.max_anomalies.unwrap_or(DEFAULT_MAX_ANOMALIES)
+            ),
+        ),
+    };
+
+    let mut scored = partition_columns.clone();
+    scored.extend([
+        format!("{} AS time", quote_identifier(time_column)),
+        format!("{value} AS value"),
+        format!("{score} AS score"),
+    ]);
+    let mut selected = partition_columns;
+    selected.extend(["time".to_string(), "value".to_string(), "score".to_string()]);
+    format!(
+        "SELECT {}, coalesce({anomalous}, false) AS anomalous FROM (SELECT {} FROM {})",
+        selected.join(", "),
+        scored.join(", "),
+        dataset.to_quoted_string()
+    )
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_scoring_sql() {
+        let config = AnomalyDetection {
+            column: "fare".to_string(),
+            method: AnomalyMethod::Esd,
+            window: None,
+            period: None,
+            max_anomalies: Some(5),
+            threshold: None,
+            partition_by: vec!["zone".to_string()],
+        };
+        let dataset = TableReference::from("taxi_trips");
+
+        assert_eq!(
+            scoring_sql(&dataset, "pickup_time", &config),
+            "SELECT \"zone\", time, value, score, coalesce(anomaly_esd(value, 5) OVER (PARTITION BY \"zone\" \
+             ORDER BY time), false) AS anomalous FROM (SELECT \"zone\", \"pickup_time\" AS time, CAST(\"fare\" \
+             AS DOUBLE) AS value, (CAST(\"fare\" AS DOUBLE) - avg(CAST(\"fare\" AS DOUBLE)) OVER (PARTITION BY \
+             \"zone\")) / stddev(CAST(\"fare\" AS DOUBLE)) OVER (PARTITION BY \"zone\") AS score FROM taxi_trips)"
+        );
+        assert_eq!(
+            anomalies_table(&TableReference::from("sales.orders")).to_string(),
+            "runtime.sales_orders_anomalies"
+        );
+    }
+}
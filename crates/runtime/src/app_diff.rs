@@ -0,0 +1,197 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Computes the components to load, reload and unload when the spicepod changes.
+
+use std::fmt;
+
+use app::App;
+use spicepod::component::{
+    dataset::Dataset, embeddings::Embeddings, function::Function, llms::Llm, model::Model,
+    view::View,
+};
+
+/// The components that were added, modified or removed between two versions of the app.
+#[derive(Debug, PartialEq)]
+pub struct ComponentDiff<'a, T> {
+    pub added: Vec<&'a T>,
+    pub updated: Vec<&'a T>,
+    pub removed: Vec<&'a T>,
+}
+
+impl<'a, T: PartialEq> ComponentDiff<'a, T> {
+    fn new(current: &'a [T], new: &'a [T], name: impl Fn(&T) -> &str) -> Self {
+        let mut diff = Self {
+            added: vec![],
+            updated: vec![],
+            removed: vec![],
+        };
+
+        for component in new {
+            match current.iter().find(|c| name(c) == name(component)) {
+                Some(current_component) if current_component != component => {
+                    diff.updated.push(component);
+                }
+                Some(_) => {}
+                None => diff.added.push(component),
+            }
+        }
+
+        diff.removed = current
+            .iter()
+            .filter(|c| !new.iter().any(|n| name(n) == name(c)))
+            .collect();
+
+        diff
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+
+    fn describe(&self, kind: &str, name: impl Fn(&T) -> &str, lines: &mut Vec<String>) {
+        lines.extend(self.added.iter().map(|c| format!("+ {kind} {}", name(c))));
+        lines.extend(self.updated.iter().map(|c| format!("~ {kind} {}", name(c))));
+        lines.extend(self.removed.iter().map(|c| format!("- {kind} {}", name(c))));
+    }
+}
+
+/// The changes to apply to the runtime when the spicepod changes. Components that are identical in both
+/// versions are left untouched, so unaffected accelerated tables keep their data.
+#[derive(Debug, PartialEq)]
+pub struct AppDiff<'a> {
+    pub datasets: ComponentDiff<'a, Dataset>,
+    pub views: ComponentDiff<'a, View>,
+    pub functions: ComponentDiff<'a, Function>,
+    pub models: ComponentDiff<'a, Model>,
+    pub llms: ComponentDiff<'a, Llm>,
+    pub embeddings: ComponentDiff<'a, Embeddings>,
+}
+
+impl<'a> AppDiff<'a> {
+    #[must_use]
+    pub fn new(current: &'a App, new: &'a App) -> Self {
+        Self {
+            datasets: ComponentDiff::new(&current.datasets, &new.datasets, |d| &d.name),
+            views: ComponentDiff::new(&current.views, &new.views, |v| &v.name),
+            functions: ComponentDiff::new(&current.functions, &new.functions, |f| &f.name),
+            models: ComponentDiff::new(&current.models, &new.models, |m| &m.name),
+            llms: ComponentDiff::new(&current.llms, &new.llms, |l| &l.name),
+            embeddings: ComponentDiff::new(&current.embeddings, &new.embeddings, |e| &e.name),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.datasets.is_empty()
+            && self.views.is_empty()
+            && self.functions.is_empty()
+            && self.models.is_empty()
+            && self.llms.is_empty()
+            && self.embeddings.is_empty()
+    }
+}
+
+impl fmt::Display for AppDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = vec![];
+        self.datasets.describe("dataset", |d| &d.name, &mut lines);
+        self.views.describe("view", |v| &v.name, &mut lines);
+        self.functions.describe("function", |f| &f.name, &mut lines);
+        self.models.describe("model", |m| &m.name, &mut lines);
+        self.llms.describe("llm", |l| &l.name, &mut lines);
+        self.embeddings
+            .describe("embeddings", |e| &e.name, &mut lines);
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use app::AppBuilder;
+    use spicepod::component::{dataset::Dataset, view::View};
+
+    use super::AppDiff;
+
+    fn dataset(from: &str, name: &str) -> Dataset {
+        Dataset::new(from.to_string(), name.to_string())
+    }
+
+    fn app(datasets: Vec<Dataset>) -> app::App {
+        let mut builder = AppBuilder::new("test");
+        for dataset in datasets {
+            builder = builder.with_dataset(dataset);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_diff_datasets() {
+        let current = app(vec![
+            dataset("spice.ai/eth.recent_blocks", "unchanged"),
+            dataset("spice.ai/eth.recent_blocks", "modified"),
+            dataset("spice.ai/eth.recent_blocks", "removed"),
+        ]);
+        let new = app(vec![
+            dataset("spice.ai/eth.recent_blocks", "unchanged"),
+            dataset("spice.ai/eth.blocks", "modified"),
+            dataset("spice.ai/eth.recent_blocks", "added"),
+        ]);
+
+        let diff = AppDiff::new(&current, &new);
+        let names =
+            |datasets: &[&Dataset]| datasets.iter().map(|d| d.name.clone()).collect::<Vec<_>>();
+
+        assert_eq!(names(&diff.datasets.added), vec!["added"]);
+        assert_eq!(names(&diff.datasets.updated), vec!["modified"]);
+        assert_eq!(names(&diff.datasets.removed), vec!["removed"]);
+        assert!(diff.models.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "+ dataset added\n~ dataset modified\n- dataset removed"
+        );
+    }
+
+    #[test]
+    fn test_diff_views() {
+        let view = |name: &str, sql: &str| {
+            let mut view = View::new(name.to_string());
+            view.sql = Some(sql.to_string());
+            view
+        };
+        let current = AppBuilder::new("test")
+            .with_view(view("unchanged", "SELECT 1"))
+            .with_view(view("modified", "SELECT 1"))
+            .build();
+        let new = AppBuilder::new("test")
+            .with_view(view("unchanged", "SELECT 1"))
+            .with_view(view("modified", "SELECT 2"))
+            .with_view(view("added", "SELECT 1"))
+            .build();
+
+        let diff = AppDiff::new(&current, &new);
+        assert!(diff.datasets.is_empty());
+        assert_eq!(diff.to_string(), "+ view added\n~ view modified");
+    }
+
+    #[test]
+    fn test_diff_identical_apps() {
+        let current = app(vec![dataset("spice.ai/eth.recent_blocks", "blocks")]);
+        let new = app(vec![dataset("spice.ai/eth.recent_blocks", "blocks")]);
+        assert!(AppDiff::new(&current, &new).is_empty());
+    }
+}
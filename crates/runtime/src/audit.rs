@@ -0,0 +1,698 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Structured audit events for queries, model access and admin actions.
+//!
+//! Events are numbered sequentially and chained by hash: each event includes the hash of the
+//! previous one, so removing, reordering or editing a recorded event is detectable with [`verify_chain`].
+
+use std::{
+    io::SeekFrom,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, SystemTime},
+};
+
+use arrow::{
+    array::{RecordBatch, StringArray, TimestampNanosecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use datafusion::sql::TableReference;
+use opentelemetry_proto::tonic::{
+    collector::logs::v1::{logs_service_client::LogsServiceClient, ExportLogsServiceRequest},
+    common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs, SeverityNumber},
+    resource::v1::Resource,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::prelude::*;
+use spicepod::component::runtime::{Audit, AuditSink};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::{mpsc, Notify},
+};
+use tonic_0_9_0::transport::{Channel, Endpoint};
+
+use crate::{
+    component::dataset::acceleration::Acceleration,
+    datafusion::{DataFusion, SPICE_RUNTIME_SCHEMA},
+    dataupdate::{DataUpdate, UpdateType},
+    internal_table::create_internal_accelerated_table,
+};
+
+pub const DEFAULT_AUDIT_LOG_TABLE: &str = "audit_log";
+const DEFAULT_AUDIT_LOG_PATH: &str = "audit.log";
+
+/// The `previous_hash` of the first event of a chain.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How much of the end of an existing audit file is read at first to continue its chain.
+const RESUME_READ_BYTES: u64 = 64 * 1024;
+
+/// The longest wait between the attempts to write events the sink failed to write.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+const MAX_BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to open audit log file {path}: {source}"))]
+    UnableToOpenAuditFile {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display(
+        "The last event of the audit log file {path} is corrupt, so its chain can't be continued. Move the file or \
+         repair its last line to start the runtime."
+    ))]
+    CorruptAuditFile { path: String },
+
+    #[snafu(display("Unable to write to the audit log file: {source}"))]
+    UnableToWriteAuditFile { source: std::io::Error },
+
+    #[snafu(display("The otlp audit sink requires runtime.audit.otlp_endpoint"))]
+    MissingOtlpEndpoint,
+
+    #[snafu(display("Invalid audit OTLP endpoint {endpoint}: {source}"))]
+    InvalidOtlpEndpoint {
+        endpoint: String,
+        source: tonic_0_9_0::transport::Error,
+    },
+
+    #[snafu(display("Unable to export audit events: {source}"))]
+    UnableToExportAuditEvents { source: tonic_0_9_0::Status },
+
+    #[snafu(display("Unable to create the audit_log table: {source}"))]
+    UnableToCreateAuditTable {
+        source: crate::internal_table::Error,
+    },
+
+    #[snafu(display("Unable to register the audit_log table: {source}"))]
+    UnableToRegisterAuditTable { source: crate::datafusion::Error },
+
+    #[snafu(display("Unable to write to the audit_log table: {source}"))]
+    UnableToWriteAuditTable { source: crate::datafusion::Error },
+
+    #[snafu(display("Unable to create audit_log row: {source}"))]
+    UnableToCreateRow { source: arrow::error::ArrowError },
+
+    #[snafu(display("Unable to serialize audit event: {source}"))]
+    UnableToSerializeEvent { source: serde_json::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Query,
+    ModelInference,
+    ChatCompletion,
+    DatasetRefresh,
+    RefreshPause,
+    RefreshResume,
+    AccelerationUpdate,
+    AccelerationExport,
+    AccelerationImport,
+    DatasetCreate,
+    DatasetReplace,
+    DatasetDelete,
+    DatasetIngest,
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditAction::Query => write!(f, "query"),
+            AuditAction::ModelInference => write!(f, "model_inference"),
+            AuditAction::ChatCompletion => write!(f, "chat_completion"),
+            AuditAction::DatasetRefresh => write!(f, "dataset_refresh"),
+            AuditAction::RefreshPause => write!(f, "refresh_pause"),
+            AuditAction::RefreshResume => write!(f, "refresh_resume"),
+            AuditAction::AccelerationUpdate => write!(f, "acceleration_update"),
+            AuditAction::AccelerationExport => write!(f, "acceleration_export"),
+            AuditAction::AccelerationImport => write!(f, "acceleration_import"),
+            AuditAction::DatasetCreate => write!(f, "dataset_create"),
+            AuditAction::DatasetReplace => write!(f, "dataset_replace"),
+            AuditAction::DatasetDelete => write!(f, "dataset_delete"),
+            AuditAction::DatasetIngest => write!(f, "dataset_ingest"),
+        }
+    }
+}
+
+/// An auditable action, before it is numbered and chained into an [`AuditEvent`].
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    pub action: AuditAction,
+    pub principal: Option<String>,
+    pub protocol: Option<String>,
+    /// The datasets or models accessed.
+    pub resources: Vec<String>,
+    pub sql: Option<String>,
+    pub rows: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    #[must_use]
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            principal: None,
+            protocol: None,
+            resources: vec![],
+            sql: None,
+            rows: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub sequence: u64,
+    pub timestamp: String,
+    pub action: AuditAction,
+    pub principal: Option<String>,
+    pub protocol: Option<String>,
+    pub resources: Vec<String>,
+    pub sql: Option<String>,
+    pub rows: Option<u64>,
+    pub error: Option<String>,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+impl AuditEvent {
+    /// The SHA-256 of the event with an empty `hash`, as lowercase hex.
+    #[must_use]
+    pub fn compute_hash(&self) -> String {
+        let mut unhashed = self.clone();
+        unhashed.hash = String::new();
+        let bytes = serde_json::to_vec(&unhashed).unwrap_or_default();
+        Sha256::digest(bytes)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+/// Returns `true` if the events are consecutive, and each is unmodified and chained to the previous one.
+#[must_use]
+pub fn verify_chain(events: &[AuditEvent]) -> bool {
+    events.iter().enumerate().all(|(i, event)| {
+        let chained = match i.checked_sub(1).map(|prev| &events[prev]) {
+            Some(prev) => event.sequence == prev.sequence + 1 && event.previous_hash == prev.hash,
+            None => true,
+        };
+        chained && event.hash == event.compute_hash()
+    })
+}
+
+struct ChainState {
+    sequence: u64,
+    last_hash: String,
+}
+
+/// Numbers, chains and hands off audit events to a background writer for the configured sink.
+pub struct AuditLog {
+    chain: Mutex<ChainState>,
+    sender: mpsc::UnboundedSender<AuditEvent>,
+    /// The events sent to the writer that it hasn't written yet.
+    pending: Arc<AtomicUsize>,
+    written: Arc<Notify>,
+}
+
+impl AuditLog {
+    pub async fn try_new(config: &Audit, df: &Arc<DataFusion>) -> Result<Arc<Self>> {
+        let (sink, last_event) = match config.sink {
+            AuditSink::File => {
+                let path = config
+                    .path
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_AUDIT_LOG_PATH.to_string());
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .read(true)
+                    .append(true)
+                    .open(&path)
+                    .await
+                    .context(UnableToOpenAuditFileSnafu { path: path.clone() })?;
+                let last_event = match read_last_event(&mut file)
+                    .await
+                    .context(UnableToOpenAuditFileSnafu { path: path.clone() })?
+                {
+                    LastEvent::Empty => None,
+                    LastEvent::Event(event) => Some(event),
+                    LastEvent::Corrupt => return CorruptAuditFileSnafu { path }.fail(),
+                };
+                (Sink::File(file), last_event)
+            }
+            AuditSink::Table => {
+                register_audit_table(df).await?;
+                (Sink::Table(Arc::downgrade(df)), None)
+            }
+            AuditSink::Otlp => {
+                let endpoint = config
+                    .otlp_endpoint
+                    .clone()
+                    .context(MissingOtlpEndpointSnafu)?;
+                let channel = Endpoint::from_shared(endpoint.clone())
+                    .context(InvalidOtlpEndpointSnafu { endpoint })?
+                    .connect_lazy();
+                (Sink::Otlp(LogsServiceClient::new(channel)), None)
+            }
+        };
+
+        // Continue the chain of an existing audit file, so restarts don't look like tampering.
+        let chain = match last_event {
+            Some(event) => ChainState {
+                sequence: event.sequence + 1,
+                last_hash: event.hash,
+            },
+            None => ChainState {
+                sequence: 0,
+                last_hash: GENESIS_HASH.to_string(),
+            },
+        };
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let written = Arc::new(Notify::new());
+        tokio::spawn(write_events(
+            receiver,
+            sink,
+            Arc::clone(&pending),
+            Arc::clone(&written),
+        ));
+
+        Ok(Arc::new(Self {
+            chain: Mutex::new(chain),
+            sender,
+            pending,
+            written,
+        }))
+    }
+
+    /// The events recorded that haven't been written to the sink yet.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    /// Waits until the events recorded so far have been written to the sink, i.e. before the runtime exits.
+    pub async fn flush(&self) {
+        loop {
+            // Created before checking, so a write that completes in between still wakes it.
+            let written = self.written.notified();
+            if self.pending.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            written.await;
+        }
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        let mut chain = match self.chain.lock() {
+            Ok(chain) => chain,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let mut event = AuditEvent {
+            sequence: chain.sequence,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true),
+            action: record.action,
+            principal: record.principal,
+            protocol: record.protocol,
+            resources: record.resources,
+            sql: record.sql,
+            rows: record.rows,
+            error: record.error,
+            previous_hash: chain.last_hash.clone(),
+            hash: String::new(),
+        };
+        event.hash = event.compute_hash();
+
+        chain.sequence += 1;
+        chain.last_hash.clone_from(&event.hash);
+
+        // Sent while holding the lock, so events reach the sink in sequence order.
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        if self.sender.send(event).is_err() {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            tracing::error!("Audit log writer stopped, dropping audit event");
+        }
+    }
+}
+
+/// The end of an existing audit file.
+#[derive(Debug, PartialEq)]
+enum LastEvent {
+    Empty,
+    Event(AuditEvent),
+    /// The last line isn't an unmodified event, i.e. it was cut off by a crash or edited.
+    Corrupt,
+}
+
+/// Reads the last event of an existing audit file, reading further back until its whole last line is read.
+async fn read_last_event(file: &mut File) -> std::io::Result<LastEvent> {
+    let len = file.metadata().await?.len();
+    let mut read_bytes = RESUME_READ_BYTES;
+    loop {
+        let start = len.saturating_sub(read_bytes);
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).await?;
+
+        let tail = String::from_utf8_lossy(&tail);
+        let tail = tail.trim_end();
+        match tail.rfind('\n') {
+            Some(newline) => return Ok(parse_last_event(&tail[newline + 1..])),
+            None if start == 0 => return Ok(parse_last_event(tail)),
+            None => read_bytes = read_bytes.saturating_mul(2),
+        }
+    }
+}
+
+fn parse_last_event(line: &str) -> LastEvent {
+    if line.trim().is_empty() {
+        return LastEvent::Empty;
+    }
+    match serde_json::from_str::<AuditEvent>(line) {
+        Ok(event) if event.hash == event.compute_hash() => LastEvent::Event(event),
+        _ => LastEvent::Corrupt,
+    }
+}
+
+enum Sink {
+    File(File),
+    Table(Weak<DataFusion>),
+    Otlp(LogsServiceClient<Channel>),
+}
+
+impl Sink {
+    async fn write(&mut self, events: &[AuditEvent]) -> Result<()> {
+        match self {
+            Sink::File(file) => {
+                let mut buf = Vec::new();
+                for event in events {
+                    serde_json::to_writer(&mut buf, event).context(UnableToSerializeEventSnafu)?;
+                    buf.push(b'\n');
+                }
+                file.write_all(&buf)
+                    .await
+                    .context(UnableToWriteAuditFileSnafu)?;
+                file.flush().await.context(UnableToWriteAuditFileSnafu)
+            }
+            Sink::Table(df) => {
+                let Some(df) = df.upgrade() else {
+                    return Ok(());
+                };
+                let data_update = DataUpdate {
+                    schema: audit_table_schema(),
+                    data: vec![to_record_batch(events)?],
+                    update_type: UpdateType::Append,
+                };
+                df.write_data(audit_table_reference(), data_update)
+                    .await
+                    .context(UnableToWriteAuditTableSnafu)
+            }
+            Sink::Otlp(client) => {
+                client
+                    .export(to_export_request(events))
+                    .await
+                    .context(UnableToExportAuditEventsSnafu)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn write_events(
+    mut receiver: mpsc::UnboundedReceiver<AuditEvent>,
+    mut sink: Sink,
+    pending: Arc<AtomicUsize>,
+    written: Arc<Notify>,
+) {
+    while let Some(event) = receiver.recv().await {
+        let mut events = vec![event];
+        while events.len() < MAX_BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(_) => break,
+            }
+        }
+
+        // Events are never dropped, so the chain has no gaps: the batch is retried until the sink accepts it, while
+        // the next events wait in the channel.
+        let mut backoff = Duration::from_millis(100);
+        while let Err(e) = sink.write(&events).await {
+            tracing::error!(
+                "Unable to write {} audit events, retrying in {}ms: {e}",
+                events.len(),
+                backoff.as_millis()
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+        }
+        pending.fetch_sub(events.len(), Ordering::AcqRel);
+        written.notify_waiters();
+    }
+}
+
+#[must_use]
+pub fn audit_table_reference() -> TableReference {
+    TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_AUDIT_LOG_TABLE)
+}
+
+fn audit_table_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("sequence", DataType::UInt64, false),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, Some(Arc::from("UTC"))),
+            false,
+        ),
+        Field::new("action", DataType::Utf8, false),
+        Field::new("principal", DataType::Utf8, true),
+        Field::new("protocol", DataType::Utf8, true),
+        Field::new("resources", DataType::Utf8, true),
+        Field::new("sql", DataType::Utf8, true),
+        Field::new("rows", DataType::UInt64, true),
+        Field::new("error", DataType::Utf8, true),
+        Field::new("previous_hash", DataType::Utf8, false),
+        Field::new("hash", DataType::Utf8, false),
+    ]))
+}
+
+async fn register_audit_table(df: &Arc<DataFusion>) -> Result<()> {
+    // Audit events are never expired by the runtime.
+    let table = create_internal_accelerated_table(
+        audit_table_reference(),
+        audit_table_schema(),
+        Acceleration::default(),
+        crate::accelerated_table::refresh::Refresh::default(),
+        None,
+    )
+    .await
+    .context(UnableToCreateAuditTableSnafu)?;
+
+    df.register_runtime_table(audit_table_reference(), table)
+        .context(UnableToRegisterAuditTableSnafu)
+}
+
+fn timestamp_nanos(event: &AuditEvent) -> i64 {
+    DateTime::parse_from_rfc3339(&event.timestamp)
+        .ok()
+        .and_then(|timestamp| timestamp.timestamp_nanos_opt())
+        .unwrap_or_default()
+}
+
+fn to_record_batch(events: &[AuditEvent]) -> Result<RecordBatch> {
+    RecordBatch::try_new(
+        audit_table_schema(),
+        vec![
+            Arc::new(UInt64Array::from_iter_values(
+                events.iter().map(|e| e.sequence),
+            )),
+            Arc::new(
+                TimestampNanosecondArray::from_iter_values(events.iter().map(timestamp_nanos))
+                    .with_timezone("UTC"),
+            ),
+            Arc::new(StringArray::from_iter_values(
+                events.iter().map(|e| e.action.to_string()),
+            )),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.principal.clone()),
+            )),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.protocol.clone()),
+            )),
+            Arc::new(StringArray::from_iter(events.iter().map(|e| {
+                (!e.resources.is_empty()).then(|| e.resources.join(","))
+            }))),
+            Arc::new(StringArray::from_iter(events.iter().map(|e| e.sql.clone()))),
+            Arc::new(UInt64Array::from_iter(events.iter().map(|e| e.rows))),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.error.clone()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                events.iter().map(|e| e.previous_hash.clone()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                events.iter().map(|e| e.hash.clone()),
+            )),
+        ],
+    )
+    .context(UnableToCreateRowSnafu)
+}
+
+fn string_attribute(key: &str, value: impl Into<String>) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.into())),
+        }),
+    }
+}
+
+fn to_export_request(events: &[AuditEvent]) -> ExportLogsServiceRequest {
+    let observed_time_unix_nano = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| u64::try_from(duration.as_nanos()).unwrap_or_default())
+        .unwrap_or_default();
+
+    let log_records = events
+        .iter()
+        .map(|event| {
+            let mut attributes = vec![
+                string_attribute("audit.sequence", event.sequence.to_string()),
+                string_attribute("audit.action", event.action.to_string()),
+                string_attribute("audit.previous_hash", event.previous_hash.clone()),
+                string_attribute("audit.hash", event.hash.clone()),
+            ];
+            if let Some(principal) = &event.principal {
+                attributes.push(string_attribute("enduser.id", principal.clone()));
+            }
+
+            LogRecord {
+                time_unix_nano: u64::try_from(timestamp_nanos(event)).unwrap_or_default(),
+                observed_time_unix_nano,
+                severity_number: SeverityNumber::Info as i32,
+                severity_text: "INFO".to_string(),
+                body: Some(AnyValue {
+                    value: Some(any_value::Value::StringValue(
+                        serde_json::to_string(event).unwrap_or_default(),
+                    )),
+                }),
+                attributes,
+                ..LogRecord::default()
+            }
+        })
+        .collect();
+
+    ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: Some(Resource {
+                attributes: vec![string_attribute("service.name", "spiced")],
+                dropped_attributes_count: 0,
+            }),
+            scope_logs: vec![ScopeLogs {
+                scope: Some(InstrumentationScope {
+                    name: "spice.audit".to_string(),
+                    ..InstrumentationScope::default()
+                }),
+                log_records,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_last_event, verify_chain, AuditAction, AuditEvent, LastEvent, GENESIS_HASH};
+
+    fn chain(len: u64) -> Vec<AuditEvent> {
+        let mut events: Vec<AuditEvent> = vec![];
+        for sequence in 0..len {
+            let mut event = AuditEvent {
+                sequence,
+                timestamp: "2024-06-01T00:00:00.000000000Z".to_string(),
+                action: AuditAction::Query,
+                principal: Some("alice".to_string()),
+                protocol: Some("http".to_string()),
+                resources: vec!["orders".to_string()],
+                sql: Some("SELECT * FROM orders".to_string()),
+                rows: Some(sequence),
+                error: None,
+                previous_hash: events
+                    .last()
+                    .map_or_else(|| GENESIS_HASH.to_string(), |prev| prev.hash.clone()),
+                hash: String::new(),
+            };
+            event.hash = event.compute_hash();
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        assert!(verify_chain(&chain(5)));
+    }
+
+    #[test]
+    fn test_verify_chain_detects_tampering() {
+        let mut edited = chain(5);
+        edited[2].rows = Some(1000);
+        assert!(!verify_chain(&edited));
+
+        let mut removed = chain(5);
+        removed.remove(2);
+        assert!(!verify_chain(&removed));
+
+        let mut reordered = chain(5);
+        reordered.swap(1, 3);
+        assert!(!verify_chain(&reordered));
+    }
+
+    #[test]
+    fn test_parse_last_event() {
+        let event = chain(1).remove(0);
+        let line = serde_json::to_string(&event).expect("serializable event");
+        assert_eq!(parse_last_event(&line), LastEvent::Event(event.clone()));
+        assert_eq!(parse_last_event(""), LastEvent::Empty);
+
+        // Cut off by a crash.
+        assert_eq!(
+            parse_last_event(&line[..line.len() / 2]),
+            LastEvent::Corrupt
+        );
+
+        let mut edited = event;
+        edited.rows = Some(1000);
+        let line = serde_json::to_string(&edited).expect("serializable event");
+        assert_eq!(parse_last_event(&line), LastEvent::Corrupt);
+    }
+}
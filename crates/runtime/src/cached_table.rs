@@ -0,0 +1,221 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The `cache` of a dataset, which caches the results of the scans of its source instead of accelerating the whole
+//! dataset, for sources that are too large to accelerate and are only read in small slices.
+//!
+//! A scan is identified by its projection, the filters pushed down to the source and its limit, so queries that
+//! read the same slice share the cached results until they expire. Concurrent scans of a slice that isn't cached
+//! only read it from the source once.
+
+use std::{any::Any, sync::Arc, time::Duration};
+
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use async_trait::async_trait;
+use byte_unit::Byte;
+use datafusion::{
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionState,
+    logical_expr::{Expr, TableProviderFilterPushDown},
+    physical_plan::{collect, memory::MemoryExec, ExecutionPlan},
+    sql::TableReference,
+};
+use moka::future::Cache;
+use spicepod::component::dataset::cache::Cache as CacheSettings;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_SIZE: u64 = 128 * 1024 * 1024; // 128 MiB
+
+#[derive(Clone)]
+struct CachedScan {
+    schema: SchemaRef,
+    batches: Arc<Vec<RecordBatch>>,
+}
+
+/// A table that caches the results of the scans of another table.
+pub struct CachedTable {
+    dataset_name: TableReference,
+    inner: Arc<dyn TableProvider>,
+    scans: Cache<String, CachedScan>,
+}
+
+impl CachedTable {
+    #[must_use]
+    pub fn new(
+        dataset_name: TableReference,
+        inner: Arc<dyn TableProvider>,
+        settings: &CacheSettings,
+    ) -> Self {
+        // Invalid settings are reported by the validation of the spicepod.
+        let ttl = settings
+            .ttl
+            .as_deref()
+            .and_then(|ttl| fundu::parse_duration(ttl).ok())
+            .unwrap_or(DEFAULT_TTL);
+        let max_size = settings
+            .max_size
+            .as_deref()
+            .and_then(|max_size| Byte::parse_str(max_size, true).ok())
+            .map_or(DEFAULT_MAX_SIZE, |max_size| max_size.as_u64());
+
+        let scans = Cache::builder()
+            .time_to_live(ttl)
+            .weigher(|_key, scan: &CachedScan| -> u32 {
+                let size: usize = scan
+                    .batches
+                    .iter()
+                    .map(RecordBatch::get_array_memory_size)
+                    .sum();
+                // Results larger than u32::MAX bytes are too large to cache.
+                size.try_into().unwrap_or(u32::MAX)
+            })
+            .max_capacity(max_size)
+            .eviction_policy(moka::policy::EvictionPolicy::lru())
+            .build();
+
+        Self {
+            dataset_name,
+            inner,
+            scans,
+        }
+    }
+
+    /// The table whose scans are cached.
+    #[must_use]
+    pub fn inner(&self) -> Arc<dyn TableProvider> {
+        Arc::clone(&self.inner)
+    }
+}
+
+fn scan_key(projection: Option<&Vec<usize>>, filters: &[Expr], limit: Option<usize>) -> String {
+    let filters = filters
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    format!("{projection:?};{filters};{limit:?}")
+}
+
+#[async_trait]
+impl TableProvider for CachedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    // The filters the source applies are part of the key of the cached results, so they stay exact.
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        self.inner.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let labels = [("dataset", self.dataset_name.to_string())];
+        let mut missed = false;
+        let scan = self
+            .scans
+            .try_get_with(scan_key(projection, filters, limit), async {
+                missed = true;
+                let plan = self.inner.scan(state, projection, filters, limit).await?;
+                let schema = plan.schema();
+                let batches = collect(plan, state.task_ctx()).await?;
+                Ok::<_, DataFusionError>(CachedScan {
+                    schema,
+                    batches: Arc::new(batches),
+                })
+            })
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        if missed {
+            metrics::counter!("datasets_cache_misses", &labels).increment(1);
+        } else {
+            metrics::counter!("datasets_cache_hits", &labels).increment(1);
+        }
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[scan.batches.to_vec()],
+            scan.schema,
+            None,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::Int64Array,
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{datasource::MemTable, execution::context::SessionContext};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn caches_each_distinct_scan() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .expect("valid batch");
+        let source = MemTable::try_new(schema, vec![vec![batch]]).expect("valid table");
+        let table = CachedTable::new(
+            TableReference::bare("t"),
+            Arc::new(source),
+            &CacheSettings {
+                enabled: true,
+                ttl: Some("1h".to_string()),
+                max_size: None,
+            },
+        );
+
+        let ctx = SessionContext::new();
+        let projection = vec![0];
+        for projection in [None, None, Some(&projection)] {
+            let plan = table
+                .scan(&ctx.state(), projection, &[], None)
+                .await
+                .expect("scan");
+            let rows = collect(plan, ctx.task_ctx())
+                .await
+                .expect("collect")
+                .iter()
+                .map(RecordBatch::num_rows)
+                .sum::<usize>();
+            assert_eq!(rows, 3);
+        }
+
+        table.scans.run_pending_tasks().await;
+        assert_eq!(table.scans.entry_count(), 2);
+    }
+}
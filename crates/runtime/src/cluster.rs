@@ -0,0 +1,482 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Shares the accelerated datasets of the spicepod between the members of a cluster of runtimes.
+//!
+//! Each member hashes the names of the accelerated datasets onto a ring of the members' virtual nodes, and the first
+//! healthy member clockwise of a dataset owns it: it accelerates and refreshes the dataset, and the other members
+//! scan its acceleration over Flight with the `cluster` data connector. As every member hashes the same names with
+//! the same members, they agree on the owners without coordinating, and only the datasets of a member that stops
+//! responding move when it does.
+//!
+//! Accelerations with `partition_by` are split across the members instead: the rows of the dataset are hashed by the
+//! column into one shard per member, each member accelerates its shards, and queries scan the shards of every healthy
+//! member over Flight (see [`exchange`]). The shards of a member that stops responding are accelerated by the next
+//! healthy member of the ring until it recovers. Queries fail rather than return the rows of a shard twice or not at
+//! all while the members reload their shards.
+//!
+//! The members authenticate to each other with the `token` of `runtime.cluster.secret`, over TLS.
+
+pub mod exchange;
+pub mod shard;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use datafusion::sql::sqlparser::parser::ParserError;
+use datafusion::sql::TableReference;
+use snafu::prelude::*;
+use spicepod::component::runtime::{Cluster as ClusterConfig, ClusterMember};
+use tokio::net::TcpStream;
+
+use crate::component::dataset::{Dataset, Mode};
+
+const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_VIRTUAL_NODES: usize = 64;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The cluster has no members. Add the runtimes of the cluster to runtime.cluster.members"
+    ))]
+    NoMembers,
+
+    #[snafu(display("Unable to determine which member of the cluster this runtime is. Set runtime.cluster.node or the SPICE_CLUSTER_NODE environment variable"))]
+    UnknownNode,
+
+    #[snafu(display("This runtime is {node}, which is not a member of the cluster. Add it to runtime.cluster.members"))]
+    NotAMember { node: String },
+
+    #[snafu(display("The cluster has no secret. Set runtime.cluster.secret to the secret whose token the members authenticate to each other with"))]
+    MissingSecret,
+
+    #[snafu(display("The members of the cluster must be reached over TLS, so their token isn't sent in plaintext. Set runtime.cluster.tls to true"))]
+    TlsRequired,
+
+    #[snafu(display("Unable to limit the refresh of {dataset} to its shards: {source}"))]
+    UnableToLimitRefresh {
+        dataset: TableReference,
+        source: ParserError,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The name of the member this runtime is: `node`, the `SPICE_CLUSTER_NODE` environment variable, or the hostname.
+#[must_use]
+pub fn node_name(config: &ClusterConfig) -> Option<String> {
+    config
+        .node
+        .clone()
+        .or_else(|| std::env::var("SPICE_CLUSTER_NODE").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+}
+
+/// Whether the dataset is owned by one member of the cluster. Datasets that aren't accelerated, or are written to,
+/// are loaded by every member.
+#[must_use]
+pub fn is_distributed(ds: &Dataset) -> bool {
+    ds.is_accelerated() && ds.mode == Mode::Read && ds.partition_by().is_none()
+}
+
+/// Whether the acceleration of the dataset is split across the members of the cluster.
+#[must_use]
+pub fn is_partitioned(ds: &Dataset) -> bool {
+    ds.is_accelerated() && ds.mode == Mode::Read && ds.partition_by().is_some()
+}
+
+#[derive(Debug)]
+pub struct Cluster {
+    node: usize,
+    members: Vec<ClusterMember>,
+    /// The points of the members' virtual nodes on the ring, sorted, with the index of their member.
+    ring: Vec<(u64, usize)>,
+    healthy: RwLock<Vec<bool>>,
+    health_check_interval: Duration,
+    secret: String,
+}
+
+impl Cluster {
+    pub fn new(config: &ClusterConfig) -> Result<Self> {
+        ensure!(!config.members.is_empty(), NoMembersSnafu);
+        let secret = config.secret.clone().context(MissingSecretSnafu)?;
+        ensure!(config.tls, TlsRequiredSnafu);
+        let node = node_name(config).context(UnknownNodeSnafu)?;
+        let node = config
+            .members
+            .iter()
+            .position(|member| member.name == node)
+            .context(NotAMemberSnafu { node })?;
+
+        let virtual_nodes = config.virtual_nodes.unwrap_or(DEFAULT_VIRTUAL_NODES).max(1);
+        let mut ring = config
+            .members
+            .iter()
+            .enumerate()
+            .flat_map(|(index, member)| {
+                (0..virtual_nodes).map(move |i| (hash(&format!("{}#{i}", member.name)), index))
+            })
+            .collect::<Vec<_>>();
+        ring.sort_unstable();
+
+        Ok(Self {
+            node,
+            members: config.members.clone(),
+            ring,
+            healthy: RwLock::new(vec![true; config.members.len()]),
+            // Invalid durations are reported by the validation of the spicepod.
+            health_check_interval: config
+                .health_check_interval
+                .as_ref()
+                .and_then(|interval| fundu::parse_duration(interval).ok())
+                .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL),
+            secret,
+        })
+    }
+
+    /// The member this runtime is.
+    #[must_use]
+    pub fn node(&self) -> &ClusterMember {
+        &self.members[self.node]
+    }
+
+    /// The secret whose `token` the members authenticate to each other with.
+    #[must_use]
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    #[must_use]
+    pub fn health_check_interval(&self) -> Duration {
+        self.health_check_interval
+    }
+
+    /// The members of the cluster, and whether each responded to the last health check.
+    #[must_use]
+    pub fn members(&self) -> Vec<(&ClusterMember, bool)> {
+        let healthy = self.healthy.read().unwrap_or_else(|e| e.into_inner());
+        self.members.iter().zip(healthy.iter().copied()).collect()
+    }
+
+    /// The member that owns `dataset`: the first healthy member clockwise of the dataset on the ring.
+    #[must_use]
+    pub fn owner(&self, dataset: &TableReference) -> &ClusterMember {
+        let healthy = self.healthy.read().unwrap_or_else(|e| e.into_inner());
+        &self.members[self.first_healthy(&healthy, &dataset.to_string())]
+    }
+
+    /// The shards of a partitioned dataset each member accelerates. Each member accelerates the shard of its
+    /// position, and the shards of unhealthy members are accelerated by the first healthy member clockwise of them.
+    #[must_use]
+    pub fn shards(&self, dataset: &TableReference) -> Vec<(&ClusterMember, Vec<usize>)> {
+        let healthy = self.healthy.read().unwrap_or_else(|e| e.into_inner());
+        let mut shards = vec![vec![]; self.members.len()];
+        for shard in 0..self.members.len() {
+            let owner = if healthy[shard] {
+                shard
+            } else {
+                self.first_healthy(&healthy, &format!("{dataset}#{shard}"))
+            };
+            shards[owner].push(shard);
+        }
+        self.members
+            .iter()
+            .zip(shards)
+            .filter(|(_, shards)| !shards.is_empty())
+            .collect()
+    }
+
+    /// The other members that accelerate shards of partitioned datasets, which are all the healthy members.
+    #[must_use]
+    pub fn peers(&self) -> Vec<ClusterMember> {
+        let healthy = self.healthy.read().unwrap_or_else(|e| e.into_inner());
+        self.members
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.node && healthy[*index])
+            .map(|(_, member)| member.clone())
+            .collect()
+    }
+
+    fn first_healthy(&self, healthy: &[bool], key: &str) -> usize {
+        let point = hash(key);
+        let start = self.ring.partition_point(|(p, _)| *p < point);
+        self.ring[start..]
+            .iter()
+            .chain(&self.ring[..start])
+            .map(|(_, index)| *index)
+            .find(|index| healthy[*index])
+            // This runtime is always healthy, so there is always an owner.
+            .unwrap_or(self.node)
+    }
+
+    #[must_use]
+    pub fn is_local(&self, dataset: &TableReference) -> bool {
+        self.owner(dataset).name == self.node().name
+    }
+
+    /// The dataset as this runtime loads it: the dataset itself, the dataset read from the member that owns it, or
+    /// the shards of a partitioned dataset this runtime accelerates.
+    pub fn route<'a>(&self, ds: &'a Dataset) -> Result<Cow<'a, Dataset>> {
+        if is_partitioned(ds) {
+            return self.local_shards(ds).map(Cow::Owned);
+        }
+        if !is_distributed(ds) || self.is_local(&ds.name) {
+            return Ok(Cow::Borrowed(ds));
+        }
+
+        let owner = self.owner(&ds.name);
+        Ok(Cow::Owned(Dataset {
+            from: format!("cluster:{}", owner.name),
+            params: HashMap::new(),
+            secret: None,
+            has_metadata_table: false,
+            replication: None,
+            acceleration: None,
+            columns: vec![],
+            column_types: HashMap::new(),
+            type_mappings: vec![],
+            embeddings: vec![],
+            quality_checks: vec![],
+            ..ds.clone()
+        }))
+    }
+
+    /// The dataset with its refresh limited to the rows of the shards this runtime accelerates. Without the limit,
+    /// the member would accelerate the rows of every shard, and queries would return the rows of the other members
+    /// twice.
+    fn local_shards(&self, ds: &Dataset) -> Result<Dataset> {
+        let Some(column) = ds.partition_by() else {
+            return Ok(ds.clone());
+        };
+        let node = self.node();
+        let shards = shard::Shards {
+            column: column.to_string(),
+            count: self.members.len(),
+            ids: self
+                .shards(&ds.name)
+                .into_iter()
+                .find(|(member, _)| member.name == node.name)
+                .map(|(_, shards)| shards)
+                .unwrap_or_default(),
+        };
+        let refresh_sql = shard::refresh_sql(&ds.name, ds.refresh_sql().as_deref(), &shards)
+            .context(UnableToLimitRefreshSnafu {
+                dataset: ds.name.clone(),
+            })?;
+
+        let mut ds = ds.clone();
+        if let Some(acceleration) = ds.acceleration.as_mut() {
+            acceleration.refresh_sql = Some(refresh_sql);
+            acceleration.shards = Some(shards);
+        }
+        Ok(ds)
+    }
+
+    /// Checks whether the other members accept connections on their Flight endpoints, returning whether the health of
+    /// any member changed.
+    pub async fn check_health(&self) -> bool {
+        let mut checked = Vec::with_capacity(self.members.len());
+        for (index, member) in self.members.iter().enumerate() {
+            let healthy = index == self.node
+                || matches!(
+                    tokio::time::timeout(
+                        HEALTH_CHECK_TIMEOUT,
+                        TcpStream::connect(&member.flight_endpoint)
+                    )
+                    .await,
+                    Ok(Ok(_))
+                );
+            checked.push(healthy);
+        }
+
+        let mut healthy = self.healthy.write().unwrap_or_else(|e| e.into_inner());
+        for (member, (was, is)) in self.members.iter().zip(healthy.iter().zip(&checked)) {
+            match (was, is) {
+                (true, false) => tracing::warn!(
+                    "Cluster member {} at {} is unavailable, moving its datasets",
+                    member.name,
+                    member.flight_endpoint
+                ),
+                (false, true) => tracing::info!("Cluster member {} is available", member.name),
+                _ => {}
+            }
+        }
+        let changed = *healthy != checked;
+        *healthy = checked;
+
+        #[allow(clippy::cast_precision_loss)]
+        metrics::gauge!("cluster_members_healthy")
+            .set(healthy.iter().filter(|healthy| **healthy).count() as f64);
+        changed
+    }
+
+    #[cfg(test)]
+    fn set_healthy(&self, member: &str, is_healthy: bool) {
+        let mut healthy = self.healthy.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = self.members.iter().position(|m| m.name == member) {
+            healthy[index] = is_healthy;
+        }
+    }
+}
+
+/// A 64-bit FNV-1a hash, finalized to spread similar names around the ring. Unlike the standard library's hasher, it
+/// is the same on every member and runtime version.
+fn hash(value: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in value.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_failover() {
+        let config = ClusterConfig {
+            enabled: true,
+            node: Some("a".to_string()),
+            members: ["a", "b", "c"]
+                .iter()
+                .map(|name| ClusterMember {
+                    name: (*name).to_string(),
+                    flight_endpoint: format!("{name}:50051"),
+                })
+                .collect(),
+            tls: true,
+            secret: Some("cluster".to_string()),
+            ..ClusterConfig::default()
+        };
+        let cluster = Cluster::new(&config).expect("valid cluster");
+        let datasets = (0..100)
+            .map(|i| TableReference::bare(format!("dataset_{i}")))
+            .collect::<Vec<_>>();
+        let owners = |cluster: &Cluster| {
+            datasets
+                .iter()
+                .map(|ds| cluster.owner(ds).name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let before = owners(&cluster);
+        assert_eq!(before, owners(&Cluster::new(&config).expect("valid")));
+        for member in ["a", "b", "c"] {
+            assert!(before.iter().any(|owner| owner == member));
+        }
+
+        cluster.set_healthy("b", false);
+        let after = owners(&cluster);
+        for (before, after) in before.iter().zip(&after) {
+            assert_ne!(after, "b");
+            if before != "b" {
+                assert_eq!(before, after);
+            }
+        }
+
+        let shards = |cluster: &Cluster| {
+            cluster
+                .shards(&datasets[0])
+                .into_iter()
+                .map(|(member, shards)| (member.name.clone(), shards))
+                .collect::<Vec<_>>()
+        };
+        let moved = shards(&cluster);
+        assert_eq!(moved.iter().map(|(_, s)| s.len()).sum::<usize>(), 3);
+        assert!(moved.iter().all(|(member, _)| member != "b"));
+
+        // Datasets owned by another member are scanned from its acceleration, authenticated with the cluster token.
+        let mut orders =
+            Dataset::try_new("postgres:orders".to_string(), "orders").expect("dataset");
+        orders.acceleration =
+            Some(crate::component::dataset::acceleration::Acceleration::default());
+        orders.secret = Some("pg".to_string());
+        let owner = cluster.owner(&orders.name).name.clone();
+        let routed = cluster.route(&orders).expect("routed dataset");
+        if owner == "a" {
+            assert_eq!(routed.as_ref(), &orders);
+        } else {
+            assert_eq!(routed.from, format!("cluster:{owner}"));
+            assert_eq!(routed.secret, None);
+            assert!(!routed.is_accelerated());
+        }
+
+        // Partitioned datasets are limited to the shards of this member, which its acceleration records.
+        let mut events = orders.clone();
+        events.name = TableReference::bare("events");
+        if let Some(acceleration) = events.acceleration.as_mut() {
+            acceleration.partition_by = Some("customer_id".to_string());
+        }
+        let routed = cluster.route(&events).expect("routed dataset");
+        let held = routed
+            .acceleration
+            .as_ref()
+            .and_then(|acceleration| acceleration.shards.clone())
+            .expect("shards");
+        assert_eq!(held.count, 3);
+        assert_eq!(
+            Some(held.ids),
+            cluster
+                .shards(&events.name)
+                .into_iter()
+                .find(|(member, _)| member.name == "a")
+                .map(|(_, shards)| shards)
+        );
+
+        cluster.set_healthy("b", true);
+        assert_eq!(before, owners(&cluster));
+        assert_eq!(
+            shards(&cluster),
+            vec![
+                ("a".to_string(), vec![0]),
+                ("b".to_string(), vec![1]),
+                ("c".to_string(), vec![2])
+            ]
+        );
+
+        assert!(matches!(
+            Cluster::new(&ClusterConfig {
+                tls: false,
+                ..config.clone()
+            }),
+            Err(Error::TlsRequired)
+        ));
+        assert!(matches!(
+            Cluster::new(&ClusterConfig {
+                secret: None,
+                ..config.clone()
+            }),
+            Err(Error::MissingSecret)
+        ));
+        assert!(matches!(
+            Cluster::new(&ClusterConfig {
+                node: Some("d".to_string()),
+                ..config
+            }),
+            Err(Error::NotAMember { .. })
+        ));
+    }
+}
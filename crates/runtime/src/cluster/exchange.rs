@@ -0,0 +1,688 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Scans the accelerations of other members of the cluster.
+//!
+//! The member a query runs on scans its own shards of a partitioned dataset, and requests the scan of each other shard
+//! from the member that accelerates it with a Flight `DoGet` of a [`ShardScan`] ticket, naming the shards it expects. Datasets owned by another member are
+//! read with the same scan from the owner, by the `cluster` data connector. The member scans its acceleration of the
+//! dataset, without requesting the shards of other members in turn, and streams the rows back.
+//!
+//! A scan only selects columns, filters rows and limits them: policies, masks and quotas are applied by the member
+//! the query runs on. Members authenticate their scans with the `token` of `runtime.cluster.secret`, and only scans
+//! are accepted with it.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use arrow::array::{RecordBatch, RecordBatchOptions};
+use arrow::datatypes::SchemaRef;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_client::FlightServiceClient;
+use arrow_flight::sql::Any as AnyMessage;
+use arrow_flight::Ticket;
+use async_trait::async_trait;
+use bytes::Bytes;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{Column, DFSchema};
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::SessionState;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableProviderFilterPushDown};
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning, PlanProperties,
+};
+use datafusion::sql::planner::{ParserOptions, PlannerContext, SqlToRel};
+use datafusion::sql::sqlparser::ast::{BinaryOperator, Expr as SqlExpr};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use datafusion::sql::sqlparser::tokenizer::Token;
+use datafusion::sql::unparser::expr_to_sql;
+use datafusion::sql::TableReference;
+use futures::{stream, StreamExt, TryStreamExt};
+use once_cell::sync::Lazy;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use spicepod::component::runtime::ClusterMember;
+use tonic::IntoRequest;
+
+use super::shard::Shards;
+use super::Cluster;
+use crate::dataconnector::localhost::LocalhostContextProvider;
+
+/// The type of the `Any` message of a [`ShardScan`] ticket.
+pub const SHARD_SCAN_TYPE_URL: &str = "type.spiceai.org/spice.cluster.ShardScan";
+
+/// The metadata the members authenticate their scans with.
+pub const CLUSTER_TOKEN_METADATA: &str = "x-spice-cluster-token";
+
+const MAX_MESSAGE_SIZE: usize = 100 * 1024 * 1024;
+
+struct Exchange {
+    cluster: Arc<Cluster>,
+    /// The `token` of `runtime.cluster.secret`, that authenticates to the other members.
+    token: Option<String>,
+}
+
+static EXCHANGE: Lazy<RwLock<Option<Exchange>>> = Lazy::new(|| RwLock::new(None));
+
+/// Scans the accelerations of the other members of `cluster`, authenticating with `token`.
+pub fn configure(cluster: Arc<Cluster>, token: Option<String>) {
+    *EXCHANGE.write().unwrap_or_else(PoisonError::into_inner) = Some(Exchange { cluster, token });
+}
+
+/// Marks the requests of the other members of the cluster, which are only allowed to scan accelerations.
+#[derive(Debug, Clone, Copy)]
+pub struct ClusterPeer;
+
+/// Whether `token` is the token of the members of the cluster.
+#[must_use]
+pub fn is_peer_token(token: &str) -> bool {
+    let exchange = EXCHANGE.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(expected) = exchange
+        .as_ref()
+        .and_then(|exchange| exchange.token.as_ref())
+    else {
+        return false;
+    };
+    // Compared in constant time, so the token can't be guessed from the time the comparison takes.
+    expected.len() == token.len()
+        && expected
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// The scan of the acceleration of a dataset on another member.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShardScan {
+    pub dataset: String,
+    /// The columns to return, all of them when unset. A scan without columns only counts rows.
+    pub columns: Option<Vec<String>>,
+    /// The conditions the rows must match, as SQL expressions of the columns of the dataset. Only comparisons of
+    /// columns and literals are accepted, see [`parse_filter`].
+    pub filters: Vec<String>,
+    pub limit: Option<usize>,
+    /// The shards of a partitioned dataset to return the rows of. The member fails the scan if it doesn't accelerate
+    /// all of them.
+    #[serde(default)]
+    pub shards: Option<Shards>,
+}
+
+impl ShardScan {
+    /// The ticket of a Flight `DoGet` of the scan.
+    pub fn to_ticket(&self) -> Result<Ticket> {
+        let value = serde_json::to_vec(self).map_err(|e| DataFusionError::External(e.into()))?;
+        let message = AnyMessage {
+            type_url: SHARD_SCAN_TYPE_URL.to_string(),
+            value: value.into(),
+        };
+        Ok(Ticket {
+            ticket: message.encode_to_vec().into(),
+        })
+    }
+
+    /// Reads the scan from the value of the `Any` message of a ticket.
+    pub fn from_message(value: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(value)
+    }
+}
+
+/// The scans of the shards of `dataset` this runtime doesn't accelerate, from the members that accelerate them,
+/// projected and filtered as the scan of the `local` shards.
+///
+/// Each shard is requested from a single member, which only returns the rows of the shards requested from it. The
+/// members can disagree on which of them are healthy, or not have reloaded their shards yet, so the scan fails if no
+/// other member accelerates one of the shards rather than return partial results.
+pub(crate) fn remote_scans(
+    dataset: &TableReference,
+    local: &Shards,
+    schema: &SchemaRef,
+    projection: Option<&Vec<usize>>,
+    filters: &[Expr],
+    limit: Option<usize>,
+) -> Result<Vec<Arc<dyn ExecutionPlan>>> {
+    let mut missing = local.missing();
+    if missing.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let exchange = EXCHANGE.read().unwrap_or_else(PoisonError::into_inner);
+    let mut scans: Vec<Arc<dyn ExecutionPlan>> = vec![];
+    if let Some(exchange) = exchange.as_ref() {
+        let (scan, schema) = scan_of(dataset, schema, projection, filters, limit)?;
+        let node = exchange.cluster.node();
+        for (member, ids) in exchange.cluster.shards(dataset) {
+            if member.name == node.name {
+                continue;
+            }
+            let ids = ids
+                .into_iter()
+                .filter(|id| missing.contains(id))
+                .collect::<Vec<_>>();
+            if ids.is_empty() {
+                continue;
+            }
+            missing.retain(|id| !ids.contains(id));
+            let scan = ShardScan {
+                shards: Some(Shards {
+                    ids,
+                    ..local.clone()
+                }),
+                ..scan.clone()
+            };
+            scans.push(Arc::new(scan_exec(
+                exchange,
+                member.clone(),
+                scan,
+                Arc::clone(&schema),
+            )?));
+        }
+    }
+
+    if !missing.is_empty() {
+        return Err(DataFusionError::Execution(format!(
+            "Unable to scan shards {} of {dataset}: no other member of the cluster accelerates them. Retry once the members have reloaded their shards.",
+            missing
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    Ok(scans)
+}
+
+/// The acceleration of `dataset` on the cluster member `member`, which owns the dataset.
+pub(crate) async fn member_table(member: &str, dataset: &TableReference) -> Result<MemberTable> {
+    let (member, url, token) = {
+        let exchange = EXCHANGE.read().unwrap_or_else(PoisonError::into_inner);
+        let exchange = exchange.as_ref().ok_or_else(|| {
+            DataFusionError::Plan("This runtime is not a member of a cluster".to_string())
+        })?;
+        let member = exchange
+            .cluster
+            .members()
+            .into_iter()
+            .map(|(member, _)| member)
+            .find(|m| m.name == member)
+            .cloned()
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!("{member} is not a member of the cluster"))
+            })?;
+        let (url, token) = connection(exchange, &member)?;
+        (member, url, token)
+    };
+
+    // The schema of the dataset is the schema of a scan without rows.
+    let scan = ShardScan {
+        dataset: dataset.to_string(),
+        columns: None,
+        filters: vec![],
+        limit: Some(0),
+        shards: None,
+    };
+    let mut batches = scan_member(url, token, scan.to_ticket()?)
+        .await
+        .map_err(|e| member_error(&member.name, &e))?;
+    while batches
+        .try_next()
+        .await
+        .map_err(|e| member_error(&member.name, &e))?
+        .is_some()
+    {}
+    let schema = batches.schema().cloned().ok_or_else(|| {
+        DataFusionError::Execution(format!(
+            "Cluster member {} returned no schema for {dataset}",
+            member.name
+        ))
+    })?;
+
+    Ok(MemberTable {
+        member,
+        dataset: dataset.clone(),
+        schema,
+    })
+}
+
+/// The endpoint of `member` and the token that authenticates to it.
+fn connection(exchange: &Exchange, member: &ClusterMember) -> Result<(String, String)> {
+    let token = exchange.token.clone().ok_or_else(|| {
+        DataFusionError::Execution(format!(
+            "Unable to scan the acceleration of cluster member {}: the token of runtime.cluster.secret is not loaded",
+            member.name
+        ))
+    })?;
+    // The members are reached over TLS, so the token isn't sent in plaintext.
+    Ok((format!("https://{}", member.flight_endpoint), token))
+}
+
+fn member_error(member: &str, e: &FlightError) -> DataFusionError {
+    DataFusionError::Execution(format!(
+        "Unable to scan the acceleration of cluster member {member}: {e}"
+    ))
+}
+
+/// The scan of `dataset`, projected and filtered as the scan of the local acceleration, and the schema of its rows.
+///
+/// Filters that can't be expressed as comparisons of columns and literals aren't pushed to the member, as the filters
+/// of the scan are re-applied to its results. A limit is only pushed without filters, as the results of the scan are
+/// limited after all the filters are applied.
+fn scan_of(
+    dataset: &TableReference,
+    schema: &SchemaRef,
+    projection: Option<&Vec<usize>>,
+    filters: &[Expr],
+    limit: Option<usize>,
+) -> Result<(ShardScan, SchemaRef)> {
+    let schema = match projection {
+        Some(projection) => Arc::new(schema.project(projection)?),
+        None => Arc::clone(schema),
+    };
+    let scan = ShardScan {
+        dataset: dataset.to_string(),
+        columns: Some(
+            schema
+                .fields()
+                .iter()
+                .map(|field| field.name().clone())
+                .collect(),
+        ),
+        filters: filters.iter().filter_map(pushable_filter).collect(),
+        limit: limit.filter(|_| filters.is_empty()),
+        shards: None,
+    };
+    Ok((scan, schema))
+}
+
+/// The execution of `scan` on `member`, returning rows of `schema`.
+fn scan_exec(
+    exchange: &Exchange,
+    member: ClusterMember,
+    scan: ShardScan,
+    schema: SchemaRef,
+) -> Result<ShardScanExec> {
+    let (url, token) = connection(exchange, &member)?;
+    let ticket = scan.to_ticket()?.ticket;
+    Ok(ShardScanExec::new(url, member, token, scan, ticket, schema))
+}
+
+/// The filter as the SQL expression of a [`ShardScan`], if the member accepts it.
+fn pushable_filter(filter: &Expr) -> Option<String> {
+    let filter = filter
+        .clone()
+        .transform_up(|expr| {
+            Ok(match expr {
+                Expr::Column(column) => {
+                    Transformed::yes(Expr::Column(Column::new_unqualified(column.name)))
+                }
+                expr => Transformed::no(expr),
+            })
+        })
+        .ok()?
+        .data;
+    let filter = expr_to_sql(&filter).ok()?;
+    is_supported(&filter).then(|| filter.to_string())
+}
+
+/// Parses a filter of a [`ShardScan`] on a dataset with `schema`. Only comparisons of columns and literals are
+/// accepted, so a scan can't call functions or read other tables.
+pub(crate) fn parse_filter(filter: &str, schema: &DFSchema) -> Result<Expr> {
+    let filter = Parser::new(&PostgreSqlDialect {})
+        .try_with_sql(filter)
+        .and_then(|mut parser| {
+            let filter = parser.parse_expr()?;
+            parser.expect_token(&Token::EOF)?;
+            Ok(filter)
+        })
+        .map_err(|e| DataFusionError::SQL(e, None))?;
+    if !is_supported(&filter) {
+        return Err(DataFusionError::Plan(format!(
+            "Unsupported filter of a shard scan: {filter}"
+        )));
+    }
+
+    let provider = LocalhostContextProvider::new();
+    // The columns are quoted by the member as needed, and matched as they are.
+    let options = ParserOptions {
+        enable_ident_normalization: false,
+        ..ParserOptions::default()
+    };
+    SqlToRel::new_with_options(&provider, options).sql_to_expr(
+        filter,
+        schema,
+        &mut PlannerContext::new(),
+    )
+}
+
+/// Whether the expression only compares columns and literals.
+fn is_supported(expr: &SqlExpr) -> bool {
+    match expr {
+        SqlExpr::Identifier(_) | SqlExpr::Value(_) | SqlExpr::TypedString { .. } => true,
+        SqlExpr::Nested(expr)
+        | SqlExpr::IsNull(expr)
+        | SqlExpr::IsNotNull(expr)
+        | SqlExpr::IsTrue(expr)
+        | SqlExpr::IsFalse(expr)
+        | SqlExpr::IsNotTrue(expr)
+        | SqlExpr::IsNotFalse(expr)
+        | SqlExpr::UnaryOp { expr, .. }
+        | SqlExpr::Cast { expr, .. } => is_supported(expr),
+        SqlExpr::BinaryOp { left, op, right } => {
+            matches!(
+                op,
+                BinaryOperator::Eq
+                    | BinaryOperator::NotEq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq
+                    | BinaryOperator::And
+                    | BinaryOperator::Or
+                    | BinaryOperator::Plus
+                    | BinaryOperator::Minus
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulo
+            ) && is_supported(left)
+                && is_supported(right)
+        }
+        SqlExpr::InList { expr, list, .. } => is_supported(expr) && list.iter().all(is_supported),
+        SqlExpr::Between {
+            expr, low, high, ..
+        } => is_supported(expr) && is_supported(low) && is_supported(high),
+        SqlExpr::Like { expr, pattern, .. } | SqlExpr::ILike { expr, pattern, .. } => {
+            is_supported(expr) && is_supported(pattern)
+        }
+        _ => false,
+    }
+}
+
+/// Requests the scan of `ticket` from the member at `url`.
+async fn scan_member(
+    url: String,
+    token: String,
+    ticket: Ticket,
+) -> Result<FlightRecordBatchStream, FlightError> {
+    let channel = flight_client::tls::new_tls_flight_channel(&url)
+        .await
+        .map_err(|e| FlightError::ExternalError(e.into()))?;
+    let mut client = FlightServiceClient::new(channel)
+        .max_encoding_message_size(MAX_MESSAGE_SIZE)
+        .max_decoding_message_size(MAX_MESSAGE_SIZE);
+
+    let mut request = ticket.into_request();
+    let token = token
+        .parse()
+        .map_err(|e: tonic::metadata::errors::InvalidMetadataValue| {
+            FlightError::ExternalError(e.into())
+        })?;
+    request.metadata_mut().insert(CLUSTER_TOKEN_METADATA, token);
+
+    let response = client.do_get(request).await?.into_inner();
+    Ok(FlightRecordBatchStream::new_from_flight_data(
+        response.map_err(FlightError::Tonic),
+    ))
+}
+
+/// A dataset owned by another member of the cluster, scanned from its acceleration.
+#[derive(Debug)]
+pub struct MemberTable {
+    member: ClusterMember,
+    dataset: TableReference,
+    schema: SchemaRef,
+}
+
+#[async_trait]
+impl TableProvider for MemberTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(vec![TableProviderFilterPushDown::Inexact; filters.len()])
+    }
+
+    async fn scan(
+        &self,
+        _state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let exchange = EXCHANGE.read().unwrap_or_else(PoisonError::into_inner);
+        let exchange = exchange.as_ref().ok_or_else(|| {
+            DataFusionError::Plan("This runtime is not a member of a cluster".to_string())
+        })?;
+        let (scan, schema) = scan_of(&self.dataset, &self.schema, projection, filters, limit)?;
+        Ok(Arc::new(scan_exec(
+            exchange,
+            self.member.clone(),
+            scan,
+            schema,
+        )?))
+    }
+}
+
+/// Streams the scan of the acceleration of a dataset on another member of the cluster.
+pub struct ShardScanExec {
+    url: String,
+    member: ClusterMember,
+    token: String,
+    scan: ShardScan,
+    ticket: Bytes,
+    schema: SchemaRef,
+    properties: PlanProperties,
+}
+
+impl ShardScanExec {
+    fn new(
+        url: String,
+        member: ClusterMember,
+        token: String,
+        scan: ShardScan,
+        ticket: Bytes,
+        schema: SchemaRef,
+    ) -> Self {
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(Arc::clone(&schema)),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Bounded,
+        );
+        Self {
+            url,
+            member,
+            token,
+            scan,
+            ticket,
+            schema,
+            properties,
+        }
+    }
+}
+
+impl fmt::Debug for ShardScanExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ShardScanExec member={} dataset={} shards={:?} filters=[{}] limit={:?}",
+            self.member.name,
+            self.scan.dataset,
+            self.scan.shards.as_ref().map(|shards| &shards.ids),
+            self.scan.filters.join(", "),
+            self.scan.limit
+        )
+    }
+}
+
+impl DisplayAs for ShardScanExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ShardScanExec member={} dataset={} shards={:?} filters=[{}] limit={:?}",
+            self.member.name,
+            self.scan.dataset,
+            self.scan.shards.as_ref().map(|shards| &shards.ids),
+            self.scan.filters.join(", "),
+            self.scan.limit
+        )
+    }
+}
+
+impl ExecutionPlan for ShardScanExec {
+    fn name(&self) -> &'static str {
+        "ShardScanExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        _context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition > 0 {
+            return Err(DataFusionError::Execution(format!(
+                "ShardScanExec only supports 1 partition, but partition {partition} was requested",
+            )));
+        }
+
+        let member = self.member.name.clone();
+        let ticket = Ticket {
+            ticket: self.ticket.clone(),
+        };
+        let schema = Arc::clone(&self.schema);
+
+        let batches = stream::once(scan_member(self.url.clone(), self.token.clone(), ticket))
+            .try_flatten()
+            .map({
+                let schema = Arc::clone(&schema);
+                move |batch| {
+                    let batch = batch.map_err(|e| member_error(&member, &e))?;
+                    to_schema(batch, &schema)
+                }
+            });
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+}
+
+/// Casts the rows a member scanned to the schema of the scan.
+fn to_schema(batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if schema.fields().is_empty() {
+        return Ok(RecordBatch::try_new_with_options(
+            Arc::clone(schema),
+            vec![],
+            &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+        )?);
+    }
+    arrow_tools::record_batch::try_cast_to(batch, Arc::clone(schema))
+        .map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::logical_expr::{col, lit};
+
+    use super::*;
+
+    #[test]
+    fn test_shard_scan_filters() {
+        let schema = DFSchema::try_from(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("Region", DataType::Utf8, true),
+        ]))
+        .expect("schema");
+
+        let filter = Expr::Column(Column::new(Some("orders"), "Region"))
+            .eq(lit("eu"))
+            .and(col("id").gt(lit(5)));
+        let pushed = pushable_filter(&filter).expect("pushable filter");
+        assert!(!pushed.contains("orders"));
+        assert_eq!(
+            parse_filter(&pushed, &schema).expect("valid filter"),
+            col("Region").eq(lit("eu")).and(col("id").gt(lit(5_i64)))
+        );
+
+        for filter in [
+            "id IN (SELECT id FROM other)",
+            "lower(\"Region\") = 'eu'",
+            "(SELECT count(*) FROM other) > 0",
+            "other.id = 1",
+            "id = 1; DROP TABLE orders",
+        ] {
+            assert!(parse_filter(filter, &schema).is_err(), "{filter}");
+        }
+
+        let scan = ShardScan {
+            dataset: "analytics.orders".to_string(),
+            columns: Some(vec!["id".to_string()]),
+            filters: vec![pushed],
+            limit: None,
+            shards: Some(Shards {
+                column: "Region".to_string(),
+                count: 3,
+                ids: vec![0, 2],
+            }),
+        };
+        let ticket = scan.to_ticket().expect("ticket");
+        let message = AnyMessage::decode(ticket.ticket).expect("any message");
+        assert_eq!(message.type_url, SHARD_SCAN_TYPE_URL);
+        assert_eq!(
+            ShardScan::from_message(&message.value).expect("shard scan"),
+            scan
+        );
+    }
+}
@@ -0,0 +1,281 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Splits the rows of a partitioned acceleration into shards, by the hash of the text of its `partition_by` column.
+//!
+//! Each member limits the refresh of the acceleration to its shards with the `cluster_shard(value, shard_count)`
+//! function, so the members only keep their share of the rows. Scans of the shards of other members name the shards
+//! they expect, and the members only return the rows of those shards.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::{
+    array::{AsArray, UInt32Array},
+    compute::cast,
+    datatypes::{DataType, UInt64Type},
+};
+use datafusion::{
+    common::{Column, Result},
+    logical_expr::{
+        lit, ColumnarValue, Expr as LogicalExpr, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+    },
+    sql::{
+        sqlparser::{
+            ast::{BinaryOperator, Expr, Ident, ObjectName, SetExpr, Statement},
+            dialect::PostgreSqlDialect,
+            parser::{Parser, ParserError},
+        },
+        TableReference,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+const SHARD_FUNCTION: &str = "cluster_shard";
+
+/// Shards of the rows of a partitioned dataset, by the hash of `column` among `count` shards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shards {
+    pub column: String,
+    pub count: usize,
+    pub ids: Vec<usize>,
+}
+
+impl Shards {
+    /// Whether the rows of `other` are all in these shards.
+    #[must_use]
+    pub fn contains(&self, other: &Shards) -> bool {
+        self.column == other.column
+            && self.count == other.count
+            && other.ids.iter().all(|id| self.ids.contains(id))
+    }
+
+    /// The shards of the `count` shards that aren't in these shards.
+    #[must_use]
+    pub fn missing(&self) -> Vec<usize> {
+        (0..self.count)
+            .filter(|id| !self.ids.contains(id))
+            .collect()
+    }
+
+    /// The condition matching the rows of the shards.
+    #[must_use]
+    pub fn filter(&self) -> LogicalExpr {
+        if self.ids.is_empty() {
+            return lit(false);
+        }
+        shard_udf()
+            .call(vec![
+                LogicalExpr::Column(Column::new_unqualified(&self.column)),
+                lit(self.count as u64),
+            ])
+            .in_list(
+                self.ids
+                    .iter()
+                    .map(|id| lit(u32::try_from(*id).unwrap_or(u32::MAX)))
+                    .collect(),
+                false,
+            )
+    }
+}
+
+/// The shard of `value` among `shard_count` shards, the same on every member.
+#[must_use]
+pub fn shard_of(value: &str, shard_count: u64) -> u64 {
+    super::hash(value) % shard_count.max(1)
+}
+
+/// The `refresh_sql` that limits the refresh of the acceleration of `dataset` to the rows of `shards`, adding the
+/// condition to the `refresh_sql` of the dataset if it has one.
+pub fn refresh_sql(
+    dataset: &TableReference,
+    refresh_sql: Option<&str>,
+    shards: &Shards,
+) -> Result<String, ParserError> {
+    let sql = refresh_sql.map_or_else(
+        || {
+            // Each part of the name is quoted, so `analytics.orders` is the table `orders` of the schema `analytics`.
+            let table = ObjectName(
+                [dataset.catalog(), dataset.schema(), Some(dataset.table())]
+                    .into_iter()
+                    .flatten()
+                    .map(|part| Ident::with_quote('"', part))
+                    .collect(),
+            );
+            format!("SELECT * FROM {table}")
+        },
+        ToString::to_string,
+    );
+    let dialect = PostgreSqlDialect {};
+    let mut statements = Parser::parse_sql(&dialect, &sql)?;
+    let shards = if shards.ids.is_empty() {
+        "FALSE".to_string()
+    } else {
+        format!(
+            "{SHARD_FUNCTION}({}, {}) IN ({})",
+            Ident::with_quote('"', &shards.column),
+            shards.count,
+            shards
+                .ids
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    let condition = Parser::new(&dialect).try_with_sql(&shards)?.parse_expr()?;
+
+    let [Statement::Query(query)] = statements.as_mut_slice() else {
+        return Err(ParserError::ParserError(
+            "expected a single SELECT statement".to_string(),
+        ));
+    };
+    let SetExpr::Select(select) = query.body.as_mut() else {
+        return Err(ParserError::ParserError(
+            "expected a single SELECT statement".to_string(),
+        ));
+    };
+    select.selection = Some(match select.selection.take() {
+        Some(selection) => Expr::BinaryOp {
+            left: Box::new(Expr::Nested(Box::new(selection))),
+            op: BinaryOperator::And,
+            right: Box::new(condition),
+        },
+        None => condition,
+    });
+
+    Ok(statements[0].to_string())
+}
+
+/// The `cluster_shard(value, shard_count)` function, registered in the context acceleration refreshes run in.
+#[must_use]
+pub fn shard_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(ClusterShard {
+        signature: Signature::any(2, Volatility::Immutable),
+    })
+}
+
+#[derive(Debug)]
+struct ClusterShard {
+    signature: Signature,
+}
+
+impl ScalarUDFImpl for ClusterShard {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        SHARD_FUNCTION
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(DataType::UInt32)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let values = cast(&args[0], &DataType::Utf8)?;
+        let shard_counts = cast(&args[1], &DataType::UInt64)?;
+
+        // Null values are in the first shard.
+        let shards = values
+            .as_string::<i32>()
+            .iter()
+            .zip(shard_counts.as_primitive::<UInt64Type>().iter())
+            .map(|(value, shard_count)| {
+                let shard_count = shard_count?;
+                let shard = value.map_or(0, |value| shard_of(value, shard_count));
+                u32::try_from(shard).ok()
+            })
+            .collect::<UInt32Array>();
+        Ok(ColumnarValue::Array(Arc::new(shards)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shards(ids: &[usize]) -> Shards {
+        Shards {
+            column: "customer_id".to_string(),
+            count: 3,
+            ids: ids.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_refresh_sql() {
+        let orders = TableReference::bare("orders");
+        assert_eq!(
+            refresh_sql(&orders, None, &shards(&[0, 2])).expect("valid SQL"),
+            r#"SELECT * FROM "orders" WHERE cluster_shard("customer_id", 3) IN (0, 2)"#
+        );
+        assert_eq!(
+            refresh_sql(
+                &TableReference::partial("analytics", "orders"),
+                None,
+                &shards(&[])
+            )
+            .expect("valid SQL"),
+            r#"SELECT * FROM "analytics"."orders" WHERE FALSE"#
+        );
+        assert_eq!(
+            refresh_sql(
+                &orders,
+                Some("SELECT * FROM orders WHERE region = 'eu'"),
+                &shards(&[1])
+            )
+            .expect("valid SQL"),
+            r#"SELECT * FROM orders WHERE (region = 'eu') AND cluster_shard("customer_id", 3) IN (1)"#
+        );
+
+        let shards = shard_udf()
+            .invoke(&[
+                ColumnarValue::Array(Arc::new(arrow::array::Int64Array::from(vec![
+                    Some(1),
+                    Some(2),
+                    None,
+                ]))),
+                ColumnarValue::Scalar(datafusion::common::ScalarValue::Int64(Some(3))),
+            ])
+            .and_then(|shards| shards.into_array(3))
+            .expect("shards");
+        let shards = shards.as_primitive::<arrow::datatypes::UInt32Type>();
+        assert_eq!(u64::from(shards.value(0)), shard_of("1", 3));
+        assert_eq!(u64::from(shards.value(1)), shard_of("2", 3));
+        assert_eq!(shards.value(2), 0);
+    }
+
+    #[test]
+    fn test_shards() {
+        let held = shards(&[0, 2]);
+        assert!(held.contains(&shards(&[2])));
+        assert!(held.contains(&shards(&[])));
+        assert!(!held.contains(&shards(&[1, 2])));
+        assert!(!held.contains(&Shards {
+            count: 4,
+            ..shards(&[2])
+        }));
+        assert_eq!(held.missing(), vec![1]);
+        assert_eq!(shards(&[]).filter(), lit(false));
+    }
+}
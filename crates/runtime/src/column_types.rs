@@ -0,0 +1,164 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The `column_types` of a file dataset, which override the types inferred from a sample of its CSV or JSON
+//! records, i.e. `amount: decimal(12, 2)` for a column whose sampled values all looked like integers.
+//!
+//! In strict mode, which the `schema_strict` param enables, overrides of columns that aren't in the files, or whose
+//! sampled values can't be read as the overridden type, fail the registration of the dataset instead of failing
+//! its queries.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+
+/// Parses a SQL or Arrow type name, i.e. `bigint`, `int64`, `decimal(12, 2)` or `timestamp`.
+#[must_use]
+pub fn parse_column_type(column_type: &str) -> Option<DataType> {
+    let column_type = column_type.trim().to_ascii_lowercase();
+    if let Some(args) = column_type
+        .strip_prefix("decimal")
+        .or_else(|| column_type.strip_prefix("numeric"))
+    {
+        let args = args.trim().strip_prefix('(')?.strip_suffix(')')?;
+        let (precision, scale) = args.split_once(',')?;
+        return Some(DataType::Decimal128(
+            precision.trim().parse().ok()?,
+            scale.trim().parse().ok()?,
+        ));
+    }
+
+    Some(match column_type.as_str() {
+        "boolean" | "bool" => DataType::Boolean,
+        "tinyint" | "int8" => DataType::Int8,
+        "smallint" | "int16" => DataType::Int16,
+        "int" | "integer" | "int32" => DataType::Int32,
+        "bigint" | "int64" => DataType::Int64,
+        "uint8" => DataType::UInt8,
+        "uint16" => DataType::UInt16,
+        "uint32" => DataType::UInt32,
+        "uint64" => DataType::UInt64,
+        "real" | "float" | "float32" => DataType::Float32,
+        "double" | "float64" => DataType::Float64,
+        "string" | "text" | "varchar" | "utf8" => DataType::Utf8,
+        "date" | "date32" => DataType::Date32,
+        "timestamp" => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        "timestamptz" => DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        "binary" | "bytea" => DataType::Binary,
+        _ => return None,
+    })
+}
+
+/// Whether values sampled as `inferred` can be read as `overridden`.
+fn readable_as(inferred: &DataType, overridden: &DataType) -> bool {
+    inferred == overridden
+        || inferred == &DataType::Null
+        || (inferred.is_numeric() && overridden.is_numeric())
+        || matches!(overridden, DataType::Utf8 | DataType::LargeUtf8)
+        || (matches!(inferred, DataType::Utf8 | DataType::LargeUtf8)
+            && matches!(overridden, DataType::Date32 | DataType::Timestamp(_, _)))
+}
+
+/// Replaces the types of the columns of the inferred schema with their overridden types.
+pub fn apply_column_types(
+    inferred: &SchemaRef,
+    column_types: &HashMap<String, String>,
+    strict: bool,
+) -> Result<SchemaRef, String> {
+    if column_types.is_empty() {
+        return Ok(Arc::clone(inferred));
+    }
+
+    let mut errors = vec![];
+    for column in column_types.keys() {
+        if inferred.field_with_name(column).is_err() {
+            if strict {
+                errors.push(format!("the column {column} was not found in the files"));
+            } else {
+                tracing::warn!(
+                    "The type of {column} is overridden, but the column was not found in the files"
+                );
+            }
+        }
+    }
+
+    let fields = inferred
+        .fields()
+        .iter()
+        .map(|field| {
+            let Some(column_type) = column_types.get(field.name()) else {
+                return Arc::clone(field);
+            };
+            let Some(data_type) = parse_column_type(column_type) else {
+                errors.push(format!(
+                    "the type {column_type} of {} is not supported",
+                    field.name()
+                ));
+                return Arc::clone(field);
+            };
+            if strict && !readable_as(field.data_type(), &data_type) {
+                errors.push(format!(
+                    "the values of {} were inferred as {}, which can't be read as {data_type}",
+                    field.name(),
+                    field.data_type()
+                ));
+            }
+            Arc::new(Field::new(field.name(), data_type, field.is_nullable()))
+        })
+        .collect::<Vec<_>>();
+
+    if !errors.is_empty() {
+        return Err(errors.join(", "));
+    }
+    Ok(Arc::new(Schema::new_with_metadata(
+        fields,
+        inferred.metadata().clone(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_inferred_types() {
+        let inferred = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("amount", DataType::Int64, true),
+            Field::new("created_at", DataType::Utf8, true),
+        ]));
+        let column_types = HashMap::from([
+            ("amount".to_string(), "decimal(12, 2)".to_string()),
+            ("created_at".to_string(), "timestamp".to_string()),
+        ]);
+
+        let schema = apply_column_types(&inferred, &column_types, true).expect("valid overrides");
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(1).data_type(), &DataType::Decimal128(12, 2));
+        assert_eq!(
+            schema.field(2).data_type(),
+            &DataType::Timestamp(TimeUnit::Nanosecond, None)
+        );
+
+        let column_types = HashMap::from([
+            ("id".to_string(), "date".to_string()),
+            ("missing".to_string(), "int".to_string()),
+        ]);
+        assert!(apply_column_types(&inferred, &column_types, true).is_err());
+        let schema = apply_column_types(&inferred, &column_types, false).expect("lenient");
+        assert_eq!(schema.field(0).data_type(), &DataType::Date32);
+    }
+}
@@ -14,10 +14,16 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::accelerated_table::schedule::CronSchedule;
 use datafusion::sql::TableReference;
 use snafu::prelude::*;
 use spicepod::component::{
-    dataset as spicepod_dataset, embeddings::ColumnEmbeddingConfig, params::Params,
+    dataset::{
+        self as spicepod_dataset, cache::Cache, policy::Policy, quality::QualityCheck,
+        type_mapping::TypeMapping,
+    },
+    embeddings::ColumnEmbeddingConfig,
+    params::Params,
 };
 use std::{collections::HashMap, time::Duration};
 
@@ -93,12 +99,20 @@ pub struct Dataset {
     pub name: TableReference,
     pub mode: Mode,
     pub params: HashMap<String, String>,
+    pub secret: Option<String>,
     pub has_metadata_table: bool,
     pub replication: Option<replication::Replication>,
     pub time_column: Option<String>,
     pub time_format: Option<TimeFormat>,
     pub acceleration: Option<acceleration::Acceleration>,
+    pub columns: Vec<String>,
+    pub column_types: HashMap<String, String>,
+    pub type_mappings: Vec<TypeMapping>,
     pub embeddings: Vec<ColumnEmbeddingConfig>,
+    pub policies: Vec<Policy>,
+    pub quality_checks: Vec<QualityCheck>,
+    pub cache: Option<Cache>,
+    pub namespace: Option<String>,
 }
 
 impl TryFrom<spicepod_dataset::Dataset> for Dataset {
@@ -110,7 +124,7 @@ impl TryFrom<spicepod_dataset::Dataset> for Dataset {
             .map(acceleration::Acceleration::try_from)
             .transpose()?;
 
-        let table_reference = Dataset::parse_table_reference(&dataset.name)?;
+        let table_reference = Dataset::parse_table_reference(&dataset.name);
 
         Ok(Dataset {
             from: dataset.from,
@@ -121,13 +135,21 @@ impl TryFrom<spicepod_dataset::Dataset> for Dataset {
                 .as_ref()
                 .map(Params::as_string_map)
                 .unwrap_or_default(),
+            secret: dataset.secret,
             has_metadata_table: dataset
                 .has_metadata_table
                 .unwrap_or(Dataset::have_metadata_table_by_default()),
             replication: dataset.replication.map(replication::Replication::from),
             time_column: dataset.time_column,
             time_format: dataset.time_format.map(TimeFormat::from),
+            columns: dataset.columns,
+            column_types: dataset.column_types,
+            type_mappings: dataset.type_mappings,
             embeddings: dataset.embeddings,
+            policies: dataset.policies,
+            quality_checks: dataset.quality_checks,
+            cache: dataset.cache,
+            namespace: dataset.namespace,
             acceleration,
         })
     }
@@ -137,15 +159,23 @@ impl Dataset {
     pub fn try_new(from: String, name: &str) -> std::result::Result<Self, crate::Error> {
         Ok(Dataset {
             from,
-            name: Self::parse_table_reference(name)?,
+            name: Self::parse_table_reference(name),
             mode: Mode::default(),
             params: HashMap::default(),
+            secret: None,
             has_metadata_table: Self::have_metadata_table_by_default(),
             replication: None,
             time_column: None,
             time_format: None,
             acceleration: None,
+            columns: Vec::default(),
+            column_types: HashMap::default(),
+            type_mappings: Vec::default(),
             embeddings: Vec::default(),
+            policies: Vec::default(),
+            quality_checks: Vec::default(),
+            cache: None,
+            namespace: None,
         })
     }
 
@@ -155,19 +185,10 @@ impl Dataset {
         false
     }
 
-    pub(crate) fn parse_table_reference(
-        name: &str,
-    ) -> std::result::Result<TableReference, crate::Error> {
-        match TableReference::parse_str(name) {
-            table_ref @ (TableReference::Bare { .. } | TableReference::Partial { .. }) => {
-                Ok(table_ref)
-            }
-            TableReference::Full { catalog, .. } => crate::DatasetNameIncludesCatalogSnafu {
-                catalog,
-                name: name.to_string(),
-            }
-            .fail(),
-        }
+    /// Parses a dataset name, which can be namespaced as `schema.table` or `catalog.schema.table`. Datasets without
+    /// a catalog are in the `spice` catalog, and datasets without a schema in its `public` schema.
+    pub(crate) fn parse_table_reference(name: &str) -> TableReference {
+        TableReference::parse_str(name)
     }
 
     /// Returns the dataset source - the first part of the `from` field before the first `:`.
@@ -202,6 +223,23 @@ impl Dataset {
         }
     }
 
+    /// Returns the name of the secret the data connector is created with - `secret`, or the dataset source.
+    #[must_use]
+    pub fn secret_name(&self) -> String {
+        self.scoped_secret_name(&self.secret.clone().unwrap_or_else(|| self.source()))
+    }
+
+    /// Returns the name `secret` is read with, prefixed with the namespace of the dataset so namespaces can't read
+    /// each other's secrets. No namespace is allowed to start with the name of another followed by `_`, so the
+    /// prefixed names of two namespaces never collide.
+    #[must_use]
+    pub fn scoped_secret_name(&self, secret: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{namespace}_{secret}"),
+            None => secret.to_string(),
+        }
+    }
+
     /// Returns the dataset path - the remainder of the `from` field after the first `:` or the whole string if no `:`.
     ///
     /// # Examples
@@ -256,6 +294,22 @@ impl Dataset {
         None
     }
 
+    #[must_use]
+    pub fn refresh_cron(&self) -> Option<CronSchedule> {
+        let acceleration = self.acceleration.as_ref()?;
+        let refresh_cron = acceleration.refresh_cron.as_ref()?;
+        match CronSchedule::parse(refresh_cron, acceleration.refresh_cron_timezone.as_deref()) {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                tracing::warn!(
+                    "Unable to parse refresh cron for dataset {}: {e}",
+                    self.name
+                );
+                None
+            }
+        }
+    }
+
     pub fn retention_check_interval(&self) -> Option<Duration> {
         if let Some(acceleration) = &self.acceleration {
             if let Some(retention_check_interval) = &acceleration.retention_check_interval {
@@ -273,6 +327,23 @@ impl Dataset {
         None
     }
 
+    pub fn compaction_interval(&self) -> Option<Duration> {
+        if let Some(acceleration) = &self.acceleration {
+            if let Some(compaction_interval) = &acceleration.compaction_interval {
+                if let Ok(duration) = fundu::parse_duration(compaction_interval) {
+                    return Some(duration);
+                }
+                tracing::warn!(
+                    "Unable to parse compaction interval for dataset {}: {}",
+                    self.name,
+                    compaction_interval
+                );
+            }
+        }
+
+        None
+    }
+
     pub fn retention_period(&self) -> Option<Duration> {
         if let Some(acceleration) = &self.acceleration {
             if let Some(retention_period) = &acceleration.retention_period {
@@ -290,6 +361,25 @@ impl Dataset {
         None
     }
 
+    /// How long after its last refresh the acceleration is considered stale.
+    #[must_use]
+    pub fn stale_after(&self) -> Option<Duration> {
+        if let Some(acceleration) = &self.acceleration {
+            if let Some(stale_after) = &acceleration.stale_after {
+                if let Ok(duration) = fundu::parse_duration(stale_after) {
+                    return Some(duration);
+                }
+                tracing::warn!(
+                    "Unable to parse stale_after for dataset {}: {}",
+                    self.name,
+                    stale_after
+                );
+            }
+        }
+
+        None
+    }
+
     #[must_use]
     pub fn refresh_sql(&self) -> Option<String> {
         if let Some(acceleration) = &self.acceleration {
@@ -299,6 +389,14 @@ impl Dataset {
         None
     }
 
+    /// The column the acceleration is split across the members of a cluster by, if it is.
+    #[must_use]
+    pub fn partition_by(&self) -> Option<&str> {
+        self.acceleration
+            .as_ref()
+            .and_then(|acceleration| acceleration.partition_by.as_deref())
+    }
+
     #[must_use]
     pub fn refresh_data_window(&self) -> Option<Duration> {
         if let Some(acceleration) = &self.acceleration {
@@ -424,6 +522,73 @@ pub mod acceleration {
         }
     }
 
+    /// Behavior when a query reads an accelerated table that hasn't loaded, failed its last refresh, or is older
+    /// than `stale_after`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum UnavailableAction {
+        /// Serve the accelerated data with a warning. This is the default.
+        #[default]
+        ServeStale,
+        /// Query the source table instead.
+        UseSource,
+        /// Fail the query.
+        Error,
+    }
+
+    impl From<spicepod_acceleration::UnavailableAction> for UnavailableAction {
+        fn from(unavailable_action: spicepod_acceleration::UnavailableAction) -> Self {
+            match unavailable_action {
+                spicepod_acceleration::UnavailableAction::ServeStale => {
+                    UnavailableAction::ServeStale
+                }
+                spicepod_acceleration::UnavailableAction::UseSource => UnavailableAction::UseSource,
+                spicepod_acceleration::UnavailableAction::Error => UnavailableAction::Error,
+            }
+        }
+    }
+
+    impl Display for UnavailableAction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                UnavailableAction::ServeStale => write!(f, "serve_stale"),
+                UnavailableAction::UseSource => write!(f, "use_source"),
+                UnavailableAction::Error => write!(f, "error"),
+            }
+        }
+    }
+
+    /// How the struct, list and map columns of a source are accelerated by an engine that can't store them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum NestedTypes {
+        /// Fail to accelerate the dataset. This is the default.
+        #[default]
+        Error,
+        /// Store the columns as JSON strings.
+        Json,
+        /// Store the fields of struct columns as columns with dotted names, and lists and maps as JSON strings.
+        Flatten,
+    }
+
+    impl From<spicepod_acceleration::NestedTypes> for NestedTypes {
+        fn from(nested_types: spicepod_acceleration::NestedTypes) -> Self {
+            match nested_types {
+                spicepod_acceleration::NestedTypes::Error => NestedTypes::Error,
+                spicepod_acceleration::NestedTypes::Json => NestedTypes::Json,
+                spicepod_acceleration::NestedTypes::Flatten => NestedTypes::Flatten,
+            }
+        }
+    }
+
+    impl Display for NestedTypes {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                NestedTypes::Error => write!(f, "error"),
+                NestedTypes::Json => write!(f, "json"),
+                NestedTypes::Flatten => write!(f, "flatten"),
+            }
+        }
+    }
+
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
     pub enum Engine {
         #[default]
@@ -515,6 +680,10 @@ pub mod acceleration {
 
         pub refresh_check_interval: Option<String>,
 
+        pub refresh_cron: Option<String>,
+
+        pub refresh_cron_timezone: Option<String>,
+
         pub refresh_sql: Option<String>,
 
         pub refresh_data_window: Option<String>,
@@ -523,20 +692,49 @@ pub mod acceleration {
 
         pub engine_secret: Option<String>,
 
+        /// The secret with the key the acceleration file is encrypted with at rest.
+        pub encryption_secret: Option<String>,
+
+        /// The number of previous loads kept as queryable versions, none by default.
+        pub retained_versions: usize,
+
         pub retention_period: Option<String>,
 
         pub retention_check_interval: Option<String>,
 
         pub retention_check_enabled: bool,
 
+        /// How often the accelerator merges the small batches of appends and reclaims the space of deleted rows.
+        pub compaction_interval: Option<String>,
+
         pub on_zero_results: ZeroResultsAction,
 
+        pub stale_after: Option<String>,
+
+        pub on_unavailable: UnavailableAction,
+
+        pub nested_types: NestedTypes,
+
         pub indexes: HashMap<String, IndexType>,
 
         pub primary_key: Vec<String>,
+
+        /// The column the acceleration is split across the members of a cluster by.
+        pub partition_by: Option<String>,
+
+        /// The shards of `partition_by` this member accelerates, set when the cluster routes the dataset.
+        pub shards: Option<crate::cluster::shard::Shards>,
     }
 
     impl Acceleration {
+        /// The secret with the accelerator's credentials, `engine_secret` or `<engine>_engine` by default.
+        #[must_use]
+        pub fn engine_secret_name(&self) -> String {
+            self.engine_secret
+                .clone()
+                .unwrap_or_else(|| format!("{}_engine", self.engine).to_lowercase())
+        }
+
         #[must_use]
         pub fn indexes_to_option_string(indexes: &HashMap<String, IndexType>) -> String {
             indexes
@@ -689,6 +887,8 @@ pub mod acceleration {
                 )?,
                 refresh_mode: RefreshMode::from(acceleration.refresh_mode),
                 refresh_check_interval: acceleration.refresh_check_interval,
+                refresh_cron: acceleration.refresh_cron,
+                refresh_cron_timezone: acceleration.refresh_cron_timezone,
                 refresh_sql: acceleration.refresh_sql,
                 refresh_data_window: acceleration.refresh_data_window,
                 params: acceleration
@@ -697,16 +897,24 @@ pub mod acceleration {
                     .map(Params::as_string_map)
                     .unwrap_or_default(),
                 engine_secret: acceleration.engine_secret,
+                encryption_secret: acceleration.encryption_secret,
+                retained_versions: acceleration.retained_versions.unwrap_or_default(),
                 retention_period: acceleration.retention_period,
                 retention_check_interval: acceleration.retention_check_interval,
                 retention_check_enabled: acceleration.retention_check_enabled,
+                compaction_interval: acceleration.compaction_interval,
                 on_zero_results: ZeroResultsAction::from(acceleration.on_zero_results),
+                stale_after: acceleration.stale_after,
+                on_unavailable: UnavailableAction::from(acceleration.on_unavailable),
+                nested_types: NestedTypes::from(acceleration.nested_types),
                 indexes: acceleration
                     .indexes
                     .into_iter()
                     .map(|(k, v)| (k, IndexType::from(v)))
                     .collect(),
                 primary_key,
+                partition_by: acceleration.partition_by,
+                shards: None,
             })
         }
     }
@@ -719,16 +927,26 @@ pub mod acceleration {
                 engine: Engine::default(),
                 refresh_mode: RefreshMode::Full,
                 refresh_check_interval: None,
+                refresh_cron: None,
+                refresh_cron_timezone: None,
                 refresh_sql: None,
                 refresh_data_window: None,
                 params: HashMap::default(),
                 engine_secret: None,
+                encryption_secret: None,
+                retained_versions: 0,
                 retention_period: None,
                 retention_check_interval: None,
                 retention_check_enabled: false,
+                compaction_interval: None,
                 on_zero_results: ZeroResultsAction::ReturnEmpty,
+                stale_after: None,
+                on_unavailable: UnavailableAction::ServeStale,
+                nested_types: NestedTypes::default(),
                 indexes: HashMap::default(),
                 primary_key: Vec::default(),
+                partition_by: None,
+                shards: None,
             }
         }
     }
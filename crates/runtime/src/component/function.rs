@@ -0,0 +1,141 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use snafu::prelude::*;
+use spicepod::component::function as spicepod_function;
+use std::fs;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    /// The SQL types of the parameters, in the order the arguments are passed.
+    pub param_types: Vec<String>,
+    pub kind: FunctionKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionKind {
+    /// A table function returning the results of SQL that refers to the parameters as `$1`, `$2`, ...
+    Sql(String),
+    /// A scalar function exported by a WASM module.
+    Wasm {
+        module: String,
+        export: String,
+        returns: String,
+    },
+}
+
+impl TryFrom<spicepod_function::Function> for Function {
+    type Error = crate::Error;
+
+    fn try_from(function: spicepod_function::Function) -> Result<Self, Self::Error> {
+        let param_types = function
+            .params
+            .iter()
+            .map(|p| p.data_type.clone())
+            .collect();
+
+        if let Some(module) = &function.wasm {
+            let returns =
+                function
+                    .returns
+                    .clone()
+                    .context(crate::NeedToSpecifyFunctionReturnTypeSnafu {
+                        name: &function.name,
+                    })?;
+
+            return Ok(Function {
+                param_types,
+                kind: FunctionKind::Wasm {
+                    module: module.clone(),
+                    export: function.export.unwrap_or_else(|| function.name.clone()),
+                    returns,
+                },
+                name: function.name,
+            });
+        }
+
+        let sql = if let Some(sql) = &function.sql {
+            sql.to_string()
+        } else if let Some(sql_ref) = &function.sql_ref {
+            fs::read_to_string(sql_ref)
+                .context(crate::UnableToLoadSqlFileSnafu { file: sql_ref })?
+        } else {
+            return Err(crate::Error::NeedToSpecifySQLFunction {
+                name: function.name,
+            });
+        };
+
+        let param_names: Vec<&str> = function.params.iter().map(|p| p.name.as_str()).collect();
+        let sql = bind_params(&sql, &param_names).map_err(|param| {
+            crate::Error::UnknownFunctionParam {
+                name: function.name.clone(),
+                param,
+            }
+        })?;
+
+        Ok(Function {
+            name: function.name,
+            param_types,
+            kind: FunctionKind::Sql(sql),
+        })
+    }
+}
+
+/// Replaces each `{{ param }}` in `sql` with the placeholder of the parameter, `$1` for the first one and so on.
+///
+/// Returns the name of the first parameter that isn't in `params`.
+fn bind_params(sql: &str, params: &[&str]) -> Result<String, String> {
+    let mut bound = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let param = rest[start + 2..start + end].trim();
+        let Some(position) = params.iter().position(|p| *p == param) else {
+            return Err(param.to_string());
+        };
+
+        bound.push_str(&rest[..start]);
+        bound.push_str(&format!("${}", position + 1));
+        rest = &rest[start + end + 2..];
+    }
+    bound.push_str(rest);
+
+    Ok(bound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binds_params_to_placeholders() {
+        assert_eq!(
+            bind_params(
+                "SELECT * FROM sales WHERE region = {{ region }} AND amount >= {{min_amount}} OR region = {{region}}",
+                &["region", "min_amount"]
+            ),
+            Ok("SELECT * FROM sales WHERE region = $1 AND amount >= $2 OR region = $1".to_string())
+        );
+        assert_eq!(
+            bind_params("SELECT {{ other }}", &["region"]),
+            Err("other".to_string())
+        );
+    }
+}
@@ -1,2 +1,3 @@
 pub mod dataset;
+pub mod function;
 pub mod view;
@@ -25,13 +25,15 @@ use super::dataset::Dataset;
 pub struct View {
     pub name: TableReference,
     pub sql: String,
+    /// The namespace of the view, whose catalog the tables of its SQL are resolved in.
+    pub namespace: Option<String>,
 }
 
 impl TryFrom<spicepod_view::View> for View {
     type Error = crate::Error;
 
     fn try_from(view: spicepod_view::View) -> Result<Self, Self::Error> {
-        let table_reference = Dataset::parse_table_reference(&view.name)?;
+        let table_reference = Dataset::parse_table_reference(&view.name);
 
         let sql = if let Some(view_sql) = &view.sql {
             view_sql.to_string()
@@ -46,6 +48,7 @@ impl TryFrom<spicepod_view::View> for View {
         Ok(View {
             name: table_reference,
             sql,
+            namespace: view.namespace,
         })
     }
 }
@@ -53,8 +56,9 @@ impl TryFrom<spicepod_view::View> for View {
 impl View {
     pub fn try_new(name: &str, sql: String) -> Result<Self, crate::Error> {
         Ok(Self {
-            name: Dataset::parse_table_reference(name)?,
+            name: Dataset::parse_table_reference(name),
             sql,
+            namespace: None,
         })
     }
 
@@ -0,0 +1,247 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The health of the data connector of each dataset, reported in `/v1/status`.
+//!
+//! Each data connector has a circuit breaker that opens after consecutive failures of the refreshes of the dataset,
+//! or of the federated queries that read it. While it is open, refreshes are skipped, so accelerated datasets keep
+//! serving their last refreshed data, and federated queries fail immediately. Once the reset timeout has passed,
+//! the next refresh or query probes the source, closing the circuit if it succeeds.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::Duration,
+};
+
+use datafusion::sql::TableReference;
+use db_connection_pool::health::{CircuitBreaker, CircuitState};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use snafu::prelude::*;
+
+const FAILURE_THRESHOLD: u32 = 5;
+const RESET_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The {connector} source of {dataset} is unavailable after {failures} consecutive failures, retrying in {}s",
+        retry_in.as_secs()
+    ))]
+    CircuitOpen {
+        dataset: String,
+        connector: String,
+        failures: u32,
+        retry_in: Duration,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Circuit {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl From<CircuitState> for Circuit {
+    fn from(state: CircuitState) -> Self {
+        match state {
+            CircuitState::Closed => Circuit::Closed,
+            CircuitState::Open => Circuit::Open,
+            CircuitState::HalfOpen => Circuit::HalfOpen,
+        }
+    }
+}
+
+/// The health of the data connector of a dataset, as reported by `GET /v1/status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectorState {
+    pub connector: String,
+    pub circuit: Circuit,
+    pub consecutive_failures: u32,
+
+    /// The error of the last failure, cleared once the source succeeds again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct ConnectorHealth {
+    connector: String,
+    breaker: CircuitBreaker,
+    last_error: Mutex<Option<String>>,
+}
+
+static CONNECTORS: Lazy<RwLock<HashMap<String, Arc<ConnectorHealth>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn get(dataset: &TableReference) -> Option<Arc<ConnectorHealth>> {
+    CONNECTORS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&dataset.to_string())
+        .cloned()
+}
+
+/// Tracks the health of the data connector of a dataset, resetting its circuit if it was already tracked.
+pub fn register(dataset: &TableReference, connector: &str) {
+    CONNECTORS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(
+            dataset.to_string(),
+            Arc::new(ConnectorHealth {
+                connector: connector.to_string(),
+                breaker: CircuitBreaker::new(FAILURE_THRESHOLD, RESET_TIMEOUT),
+                last_error: Mutex::new(None),
+            }),
+        );
+    record_circuit(dataset, Circuit::Closed);
+}
+
+pub fn remove(dataset: &TableReference) {
+    CONNECTORS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&dataset.to_string());
+}
+
+/// Whether the health of the data connector of the dataset is tracked, i.e. it isn't a view.
+#[must_use]
+pub fn is_tracked(dataset: &TableReference) -> bool {
+    get(dataset).is_some()
+}
+
+/// Allows a call to the source of the dataset, or fails while its circuit is open.
+pub fn try_acquire(dataset: &TableReference) -> Result<()> {
+    let Some(health) = get(dataset) else {
+        return Ok(());
+    };
+
+    match health.breaker.try_acquire() {
+        Ok(true) => {
+            tracing::info!("Probing the {} source of {dataset}", health.connector);
+            Ok(())
+        }
+        Ok(false) => Ok(()),
+        Err(retry_in) => {
+            let labels = [("dataset", dataset.to_string())];
+            metrics::counter!("datasets_connector_rejected_calls", &labels).increment(1);
+            CircuitOpenSnafu {
+                dataset: dataset.to_string(),
+                connector: health.connector.clone(),
+                failures: health.breaker.consecutive_failures(),
+                retry_in,
+            }
+            .fail()
+        }
+    }
+}
+
+pub fn record_success(dataset: &TableReference) {
+    let Some(health) = get(dataset) else {
+        return;
+    };
+
+    if health.breaker.state() != CircuitState::Closed {
+        tracing::info!("The {} source of {dataset} has recovered", health.connector);
+    }
+    health.breaker.record_success();
+    *health
+        .last_error
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = None;
+    record_circuit(dataset, Circuit::Closed);
+}
+
+pub fn record_failure(dataset: &TableReference, error: &str) {
+    let Some(health) = get(dataset) else {
+        return;
+    };
+
+    if health.breaker.record_failure() {
+        tracing::warn!(
+            "Skipping the refreshes and queries of {dataset} for {}s after {} consecutive failures of its {} source: {error}",
+            RESET_TIMEOUT.as_secs(),
+            health.breaker.consecutive_failures(),
+            health.connector
+        );
+    }
+    *health
+        .last_error
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(error.to_string());
+    record_circuit(dataset, health.breaker.state().into());
+}
+
+fn record_circuit(dataset: &TableReference, circuit: Circuit) {
+    let open = if circuit == Circuit::Closed { 0.0 } else { 1.0 };
+    metrics::gauge!("datasets_connector_circuit_open", "dataset" => dataset.to_string()).set(open);
+}
+
+/// A snapshot of the health of the data connectors of all datasets.
+#[must_use]
+pub fn connector_states() -> BTreeMap<String, ConnectorState> {
+    CONNECTORS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|(dataset, health)| {
+            let state = ConnectorState {
+                connector: health.connector.clone(),
+                circuit: health.breaker.state().into(),
+                consecutive_failures: health.breaker.consecutive_failures(),
+                last_error: health
+                    .last_error
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone(),
+            };
+            (dataset.clone(), state)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let dataset = TableReference::bare("connector_health_test");
+        register(&dataset, "postgres");
+
+        for _ in 0..FAILURE_THRESHOLD {
+            assert!(try_acquire(&dataset).is_ok());
+            record_failure(&dataset, "connection refused");
+        }
+        assert!(matches!(
+            try_acquire(&dataset),
+            Err(Error::CircuitOpen { failures, .. }) if failures == FAILURE_THRESHOLD
+        ));
+
+        let state = &connector_states()[&dataset.to_string()];
+        assert_eq!(state.circuit, Circuit::Open);
+        assert_eq!(state.last_error.as_deref(), Some("connection refused"));
+
+        record_success(&dataset);
+        assert!(try_acquire(&dataset).is_ok());
+        remove(&dataset);
+    }
+}
@@ -0,0 +1,164 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runs the `tests` of the spicepod against the runtime, so a CI pipeline can check the data of a spicepod with
+//! `POST /v1/tests/run` after starting it.
+//!
+//! A test is a SQL assertion that passes when it returns the expected number of rows, no rows by default, so a
+//! check is written as a query of the rows that violate it, i.e. `SELECT * FROM orders WHERE amount < 0`.
+
+use std::{sync::Arc, time::Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+use spicepod::component::test::Test;
+
+use crate::{
+    alerts,
+    datafusion::{query::Protocol, DataFusion},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TestStatus {
+    Passed,
+    Failed,
+    /// The SQL of the test could not be run.
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TestResult {
+    pub(crate) name: String,
+    pub(crate) status: TestStatus,
+    pub(crate) expected_rows: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rows: Option<u64>,
+    /// The first rows returned by a failed test.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) results: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    pub(crate) duration_ms: u128,
+}
+
+impl TestResult {
+    fn new(test: &Test, outcome: Result<(usize, Value), String>, duration_ms: u128) -> Self {
+        let expected_rows = test.expected_rows.unwrap_or(0);
+        let (status, rows, results, error) = match outcome {
+            Ok((rows, _)) if rows as u64 == expected_rows => {
+                (TestStatus::Passed, Some(rows as u64), None, None)
+            }
+            Ok((rows, results)) => (TestStatus::Failed, Some(rows as u64), Some(results), None),
+            Err(e) => (TestStatus::Error, None, None, Some(e)),
+        };
+
+        Self {
+            name: test.name.clone(),
+            status,
+            expected_rows,
+            rows,
+            results,
+            error,
+            duration_ms,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TestRun {
+    /// Whether every test passed.
+    pub(crate) success: bool,
+    pub(crate) passed: usize,
+    pub(crate) failed: usize,
+    pub(crate) errors: usize,
+    pub(crate) results: Vec<TestResult>,
+}
+
+impl FromIterator<TestResult> for TestRun {
+    fn from_iter<I: IntoIterator<Item = TestResult>>(iter: I) -> Self {
+        let results: Vec<TestResult> = iter.into_iter().collect();
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        let (passed, failed, errors) = (
+            count(TestStatus::Passed),
+            count(TestStatus::Failed),
+            count(TestStatus::Error),
+        );
+
+        Self {
+            success: failed == 0 && errors == 0,
+            passed,
+            failed,
+            errors,
+            results,
+        }
+    }
+}
+
+/// Runs `tests` one after the other, so they don't compete with each other for the sources.
+pub(crate) async fn run(df: &Arc<DataFusion>, tests: &[Test]) -> TestRun {
+    let mut results = Vec::with_capacity(tests.len());
+    for test in tests {
+        let start = Instant::now();
+        let outcome = alerts::evaluate(df, &test.sql, Protocol::Test).await;
+        let result = TestResult::new(test, outcome, start.elapsed().as_millis());
+        match (&result.status, &result.error) {
+            (TestStatus::Failed, _) => tracing::warn!(
+                "Test {} failed, expected {} rows and got {}",
+                test.name,
+                result.expected_rows,
+                result.rows.unwrap_or_default()
+            ),
+            (TestStatus::Error, Some(e)) => tracing::warn!("Unable to run test {}: {e}", test.name),
+            _ => {}
+        }
+        results.push(result);
+    }
+
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(expected_rows: Option<u64>) -> Test {
+        Test {
+            name: "no_negative_amounts".to_string(),
+            description: None,
+            sql: "SELECT * FROM orders WHERE amount < 0".to_string(),
+            expected_rows,
+        }
+    }
+
+    #[test]
+    fn compares_rows_to_expected() {
+        let run: TestRun = [
+            TestResult::new(&test(None), Ok((0, Value::Array(vec![]))), 1),
+            TestResult::new(&test(Some(3)), Ok((3, Value::Array(vec![]))), 1),
+            TestResult::new(&test(None), Ok((2, serde_json::json!([{"amount": -1}]))), 1),
+            TestResult::new(&test(None), Err("table not found".to_string()), 1),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(!run.success);
+        assert_eq!((run.passed, run.failed, run.errors), (2, 1, 1));
+        assert_eq!(run.results[2].rows, Some(2));
+        assert!(run.results[2].results.is_some());
+        assert_eq!(run.results[3].error.as_deref(), Some("table not found"));
+    }
+}
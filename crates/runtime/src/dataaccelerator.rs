@@ -41,6 +41,8 @@ use self::sqlite::SqliteAccelerator;
 pub mod arrow;
 #[cfg(feature = "duckdb")]
 pub mod duckdb;
+pub mod encryption;
+pub mod nested_types;
 // #[cfg(feature = "mysql")]
 // pub mod mysql;
 #[cfg(feature = "postgres")]
@@ -234,6 +236,34 @@ impl AcceleratorExternalTableBuilder {
     }
 }
 
+/// The file a dataset is accelerated into, for the engines that support `mode: file`.
+#[must_use]
+pub fn accelerated_file_path(
+    table_name: &TableReference,
+    acceleration_settings: &acceleration::Acceleration,
+) -> Option<String> {
+    if acceleration_settings.mode != Mode::File {
+        return None;
+    }
+
+    let params = &acceleration_settings.params;
+    match acceleration_settings.engine {
+        Engine::DuckDB => Some(
+            params
+                .get("duckdb_file")
+                .cloned()
+                .unwrap_or(format!("{table_name}.db")),
+        ),
+        Engine::Sqlite => Some(
+            params
+                .get("sqlite_file")
+                .cloned()
+                .unwrap_or(format!("{table_name}_sqlite.db")),
+        ),
+        Engine::Arrow | Engine::PostgreSQL => None,
+    }
+}
+
 pub async fn create_accelerator_table(
     table_name: TableReference,
     schema: SchemaRef,
@@ -250,6 +280,17 @@ pub async fn create_accelerator_table(
                 msg: format!("Unknown engine: {engine}"),
             })?;
 
+    let unsupported = nested_types::unsupported_columns(engine, &schema);
+    if !unsupported.is_empty() {
+        InvalidConfigurationSnafu {
+            msg: format!(
+                "The {engine} engine can't store the nested columns {}. Set acceleration.nested_types to json or flatten to store them as JSON strings or as flattened columns.",
+                unsupported.join(", ")
+            ),
+        }
+        .fail()?;
+    }
+
     if let Err(e) = acceleration_settings.validate_indexes(&schema) {
         InvalidConfigurationSnafu {
             msg: format!("{e}"),
@@ -0,0 +1,413 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Encrypts the files of file-mode accelerations at rest with `acceleration.encryption_secret`.
+//!
+//! DuckDB and SQLite read their files directly, so a file is decrypted when its dataset is loaded and encrypted
+//! again, with its write-ahead log, when the runtime shuts down and the accelerator is closed. The file is only
+//! plaintext while the runtime runs; if the runtime is killed before it drains, the plaintext file is left behind and
+//! encrypted on the next shutdown. The encrypted files are kept until they are replaced, so a failure to encrypt
+//! never loses the acceleration.
+//!
+//! An encrypted file is stored next to the file it encrypts with a `.enc` suffix. It is encrypted with AES-256-GCM
+//! in chunks of 1 MiB, each with a nonce made of a random prefix, the index of the chunk and whether it is the last,
+//! so chunks can't be reordered or the file truncated without failing to decrypt.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose, Engine};
+use once_cell::sync::Lazy;
+use secrets::Secret;
+use snafu::prelude::*;
+
+/// The key in the encryption secret holding the base64 encoded 256-bit key.
+const KEY: &str = "key";
+
+const MAGIC: &[u8; 8] = b"SPICEENC";
+const VERSION: u8 = 1;
+const NONCE_PREFIX_LEN: usize = 7;
+const CHUNK_SIZE: usize = 1024 * 1024;
+const TAG_LEN: usize = 16;
+
+/// The files the accelerators keep next to their database file, encrypted along with it.
+const SIDECAR_SUFFIXES: [&str; 5] = ["", ".wal", "-wal", "-shm", "-journal"];
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The encryption secret {secret} was not found, or has no {KEY} with the base64 encoded 256-bit key"
+    ))]
+    MissingEncryptionKey { secret: String },
+
+    #[snafu(display(
+        "The key of the encryption secret {secret} isn't a base64 encoded 256-bit key"
+    ))]
+    InvalidEncryptionKey { secret: String },
+
+    #[snafu(display("Unable to {action} {}: {source}", path.display()))]
+    UnableToAccessFile {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    #[snafu(display(
+        "Unable to decrypt {}, the file is corrupted or was encrypted with a different key",
+        path.display()
+    ))]
+    UnableToDecryptFile { path: PathBuf },
+
+    #[snafu(display("Unable to encrypt {}, the file is too large", path.display()))]
+    FileTooLarge { path: PathBuf },
+
+    #[snafu(display("The decryption of {} was interrupted", path.display()))]
+    DecryptionInterrupted { path: PathBuf },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The key the files of an acceleration are encrypted with.
+#[derive(Clone)]
+pub struct EncryptionKey(Aes256Gcm);
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey")
+    }
+}
+
+impl EncryptionKey {
+    /// Reads the key from the `key` of the secret `secret_name`.
+    pub fn try_from_secret(secret_name: &str, secret: Option<&Secret>) -> Result<Self> {
+        let encoded =
+            secret
+                .and_then(|secret| secret.get(KEY))
+                .context(MissingEncryptionKeySnafu {
+                    secret: secret_name,
+                })?;
+        let key = general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .context(InvalidEncryptionKeySnafu {
+                secret: secret_name,
+            })?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .ok()
+            .context(InvalidEncryptionKeySnafu {
+                secret: secret_name,
+            })?;
+        Ok(Self(cipher))
+    }
+}
+
+/// The acceleration files to encrypt on shutdown, with their keys.
+static ENCRYPTED_FILES: Lazy<Mutex<HashMap<PathBuf, EncryptionKey>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Decrypts the acceleration file at `path` for the accelerator to open, and registers it to be encrypted again on
+/// shutdown. A plaintext file left behind by a runtime that didn't drain takes precedence over its encrypted copy.
+pub fn decrypt_acceleration_file(path: &Path, key: &EncryptionKey) -> Result<()> {
+    // The database file is decrypted last, so it only exists once its sidecars are decrypted.
+    if !path.exists() {
+        for file in sidecars(path).rev() {
+            let encrypted = encrypted_path(&file);
+            if encrypted.exists() {
+                decrypt_file(&encrypted, &file, key)?;
+            }
+        }
+    }
+
+    ENCRYPTED_FILES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(path.to_path_buf(), key.clone());
+    Ok(())
+}
+
+/// Encrypts the acceleration files registered by [`decrypt_acceleration_file`], once the runtime has drained and their
+/// accelerators are closed. It does blocking I/O, so it runs outside of the async runtime.
+pub fn encrypt_acceleration_files() {
+    let files = std::mem::take(
+        &mut *ENCRYPTED_FILES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    );
+    for (path, key) in files {
+        if let Err(e) = encrypt_files(&path, &key) {
+            tracing::error!("Unable to encrypt the acceleration file: {e}");
+        }
+    }
+}
+
+/// Encrypts the database file at `path` and its sidecars. The plaintext files are only removed once they are all
+/// encrypted, the database file first, so the files left by an interrupted shutdown are always consistent.
+fn encrypt_files(path: &Path, key: &EncryptionKey) -> Result<()> {
+    let (plaintext, removed): (Vec<_>, Vec<_>) = sidecars(path).partition(|file| file.exists());
+    for file in &plaintext {
+        encrypt_file(file, &encrypted_path(file), key)?;
+    }
+
+    // A sidecar that no longer exists, i.e. a checkpointed write-ahead log, must not be restored on the next start.
+    let stale = removed
+        .iter()
+        .map(|file| encrypted_path(file))
+        .filter(|file| file.exists());
+    for file in stale.chain(plaintext) {
+        fs::remove_file(&file).context(UnableToAccessFileSnafu {
+            action: "remove",
+            path: &file,
+        })?;
+    }
+    Ok(())
+}
+
+fn sidecars(path: &Path) -> impl DoubleEndedIterator<Item = PathBuf> + '_ {
+    SIDECAR_SUFFIXES.iter().map(move |suffix| {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        PathBuf::from(file)
+    })
+}
+
+fn encrypted_path(path: &Path) -> PathBuf {
+    let mut encrypted = path.as_os_str().to_owned();
+    encrypted.push(".enc");
+    PathBuf::from(encrypted)
+}
+
+/// The nonce of a chunk: the random prefix of the file, the index of the chunk, and whether it is the last.
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LEN], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+/// Reads until `buf` is full or the reader is at its end, returning the bytes read.
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Writes to a temporary file renamed to `to` once complete, so an interrupted write never replaces a good file.
+fn write_atomically(
+    to: &Path,
+    write: impl FnOnce(&mut io::BufWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let mut partial = to.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let file = File::create(&partial).context(UnableToAccessFileSnafu {
+        action: "create",
+        path: &partial,
+    })?;
+    let mut writer = io::BufWriter::new(file);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)
+        .and_then(|file| file.sync_all())
+        .context(UnableToAccessFileSnafu {
+            action: "write",
+            path: &partial,
+        })?;
+    fs::rename(&partial, to).context(UnableToAccessFileSnafu {
+        action: "rename",
+        path: &partial,
+    })
+}
+
+fn encrypt_file(from: &Path, to: &Path, key: &EncryptionKey) -> Result<()> {
+    let mut reader = File::open(from).context(UnableToAccessFileSnafu {
+        action: "open",
+        path: from,
+    })?;
+    let mut prefix = [0; NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut prefix);
+
+    write_atomically(to, |writer| {
+        let write_error = |source| Error::UnableToAccessFile {
+            action: "write",
+            path: to.to_path_buf(),
+            source,
+        };
+        writer.write_all(MAGIC).map_err(write_error)?;
+        writer.write_all(&[VERSION]).map_err(write_error)?;
+        writer.write_all(&prefix).map_err(write_error)?;
+
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut index = 0u32;
+        loop {
+            let read = fill(&mut reader, &mut chunk).context(UnableToAccessFileSnafu {
+                action: "read",
+                path: from,
+            })?;
+            // Only the last chunk is shorter than a full chunk, even if it is empty.
+            let last = read < CHUNK_SIZE;
+            let nonce = chunk_nonce(&prefix, index, last);
+            let ciphertext = key
+                .0
+                .encrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &chunk[..read],
+                        aad: MAGIC,
+                    },
+                )
+                .ok()
+                .context(FileTooLargeSnafu { path: from })?;
+            writer.write_all(&ciphertext).map_err(write_error)?;
+
+            if last {
+                return Ok(());
+            }
+            index = index
+                .checked_add(1)
+                .context(FileTooLargeSnafu { path: from })?;
+        }
+    })
+}
+
+fn decrypt_file(from: &Path, to: &Path, key: &EncryptionKey) -> Result<()> {
+    let mut reader = File::open(from).context(UnableToAccessFileSnafu {
+        action: "open",
+        path: from,
+    })?;
+    let read_error = |source| Error::UnableToAccessFile {
+        action: "read",
+        path: from.to_path_buf(),
+        source,
+    };
+
+    let mut header = [0; MAGIC.len() + 1 + NONCE_PREFIX_LEN];
+    let read = fill(&mut reader, &mut header).map_err(read_error)?;
+    ensure!(
+        read == header.len() && header.starts_with(MAGIC) && header[MAGIC.len()] == VERSION,
+        UnableToDecryptFileSnafu { path: from }
+    );
+    let mut prefix = [0; NONCE_PREFIX_LEN];
+    prefix.copy_from_slice(&header[MAGIC.len() + 1..]);
+
+    write_atomically(to, |writer| {
+        let mut chunk = vec![0; CHUNK_SIZE + TAG_LEN];
+        let mut index = 0u32;
+        loop {
+            let read = fill(&mut reader, &mut chunk).map_err(read_error)?;
+            let last = read < chunk.len();
+            let nonce = chunk_nonce(&prefix, index, last);
+            let plaintext = key
+                .0
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &chunk[..read],
+                        aad: MAGIC,
+                    },
+                )
+                .ok()
+                .context(UnableToDecryptFileSnafu { path: from })?;
+            writer
+                .write_all(&plaintext)
+                .context(UnableToAccessFileSnafu {
+                    action: "write",
+                    path: to,
+                })?;
+
+            if last {
+                return Ok(());
+            }
+            index = index
+                .checked_add(1)
+                .context(UnableToDecryptFileSnafu { path: from })?;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypts_and_decrypts_files() {
+        let dir = std::env::temp_dir().join(format!("spice_encryption_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir");
+        let plaintext = dir.join("orders.db");
+        let encrypted = encrypted_path(&plaintext);
+        let data = (0..CHUNK_SIZE * 2 + 10)
+            .map(|i| u8::try_from(i % 251).unwrap_or_default())
+            .collect::<Vec<_>>();
+        fs::write(&plaintext, &data).expect("write file");
+
+        let secret = Secret::new(HashMap::from([(
+            KEY.to_string(),
+            general_purpose::STANDARD.encode([7u8; 32]),
+        )]));
+        let key = EncryptionKey::try_from_secret("orders_key", Some(&secret)).expect("valid key");
+        encrypt_file(&plaintext, &encrypted, &key).expect("encrypt");
+        assert_ne!(fs::read(&encrypted).expect("read file"), data);
+
+        fs::remove_file(&plaintext).expect("remove file");
+        decrypt_acceleration_file(&plaintext, &key).expect("decrypt");
+        assert_eq!(fs::read(&plaintext).expect("read file"), data);
+        // The encrypted file is kept until the file is encrypted again.
+        assert!(encrypted.exists());
+
+        let wal = dir.join("orders.db.wal");
+        fs::write(&wal, b"wal").expect("write file");
+        let stale_wal = encrypted_path(&dir.join("orders.db-wal"));
+        fs::write(&stale_wal, b"stale").expect("write file");
+        encrypt_files(&plaintext, &key).expect("encrypt");
+        assert!(!plaintext.exists() && !wal.exists() && !stale_wal.exists());
+        decrypt_acceleration_file(&plaintext, &key).expect("decrypt");
+        assert_eq!(fs::read(&plaintext).expect("read file"), data);
+        assert_eq!(fs::read(&wal).expect("read file"), b"wal");
+
+        let other = Secret::new(HashMap::from([(
+            KEY.to_string(),
+            general_purpose::STANDARD.encode([8u8; 32]),
+        )]));
+        let other = EncryptionKey::try_from_secret("other_key", Some(&other)).expect("valid key");
+        encrypt_file(&plaintext, &encrypted, &key).expect("encrypt");
+        assert!(decrypt_file(&encrypted, &plaintext, &other).is_err());
+        // Truncating the file at a chunk boundary is detected.
+        let truncated = fs::read(&encrypted).expect("read file");
+        fs::write(&encrypted, &truncated[..16 + CHUNK_SIZE + TAG_LEN]).expect("write file");
+        assert!(decrypt_file(&encrypted, &plaintext, &key).is_err());
+
+        fs::remove_dir_all(&dir).expect("remove temp dir");
+    }
+}
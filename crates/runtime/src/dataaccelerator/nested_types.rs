@@ -0,0 +1,460 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Stores the struct, list and map columns of an accelerated source as JSON strings, or with the fields of
+//! structs flattened into columns with dotted names, per the `nested_types` setting of the acceleration.
+
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{make_array, Array, ArrayRef, AsArray, RecordBatch, RecordBatchOptions, StringArray},
+    buffer::NullBuffer,
+    datatypes::{DataType, Field, FieldRef, Schema, SchemaRef},
+    error::ArrowError,
+};
+use async_trait::async_trait;
+use datafusion::{
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::{context::SessionState, SendableRecordBatchStream, TaskContext},
+    logical_expr::{utils::expr_to_columns, Expr, TableProviderFilterPushDown},
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionPlan,
+        ExecutionPlanProperties, PlanProperties,
+    },
+};
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::component::dataset::acceleration::{Engine, NestedTypes};
+
+/// Whether `engine` can store columns of `data_type`.
+fn can_store(engine: Engine, data_type: &DataType) -> bool {
+    match engine {
+        Engine::Arrow | Engine::DuckDB => true,
+        // Lists of primitives are stored as Postgres arrays.
+        Engine::PostgreSQL => match data_type {
+            DataType::List(field) => !field.data_type().is_nested(),
+            data_type => !data_type.is_nested(),
+        },
+        Engine::Sqlite => !data_type.is_nested(),
+    }
+}
+
+/// The names of the columns of `schema` that `engine` can't store.
+#[must_use]
+pub fn unsupported_columns(engine: Engine, schema: &Schema) -> Vec<String> {
+    schema
+        .fields()
+        .iter()
+        .filter(|field| !can_store(engine, field.data_type()))
+        .map(|field| field.name().clone())
+        .collect()
+}
+
+/// Whether `schema` has struct, list or map columns.
+#[must_use]
+pub fn has_nested_columns(schema: &Schema) -> bool {
+    schema
+        .fields()
+        .iter()
+        .any(|field| field.data_type().is_nested())
+}
+
+fn flattened_field(parent: &Field, child: &Field) -> FieldRef {
+    Arc::new(
+        child
+            .clone()
+            .with_name(format!("{}.{}", parent.name(), child.name()))
+            .with_nullable(true),
+    )
+}
+
+/// The fields a column of the source is stored as.
+fn stored_fields(field: &FieldRef, nested_types: NestedTypes) -> Vec<FieldRef> {
+    match (nested_types, field.data_type()) {
+        (NestedTypes::Error, _) => vec![Arc::clone(field)],
+        (NestedTypes::Flatten, DataType::Struct(children)) => children
+            .iter()
+            .flat_map(|child| stored_fields(&flattened_field(field, child), nested_types))
+            .collect(),
+        (_, data_type) if data_type.is_nested() => vec![Arc::new(
+            field
+                .as_ref()
+                .clone()
+                .with_data_type(DataType::Utf8)
+                .with_nullable(true),
+        )],
+        _ => vec![Arc::clone(field)],
+    }
+}
+
+/// The arrays a column of the source is stored as, in the order of [`stored_fields`].
+fn stored_columns(
+    field: &FieldRef,
+    array: &ArrayRef,
+    nested_types: NestedTypes,
+) -> Result<Vec<ArrayRef>, ArrowError> {
+    match (nested_types, field.data_type()) {
+        (NestedTypes::Error, _) => Ok(vec![Arc::clone(array)]),
+        (NestedTypes::Flatten, DataType::Struct(children)) => {
+            let array = array.as_struct();
+            let mut columns = vec![];
+            for (child, column) in children.iter().zip(array.columns()) {
+                // A field is null wherever its struct is.
+                let nulls = NullBuffer::union(array.nulls(), column.nulls());
+                let column = make_array(column.to_data().into_builder().nulls(nulls).build()?);
+                columns.extend(stored_columns(
+                    &flattened_field(field, child),
+                    &column,
+                    nested_types,
+                )?);
+            }
+            Ok(columns)
+        }
+        (_, data_type) if data_type.is_nested() => Ok(vec![to_json(array)?]),
+        _ => Ok(vec![Arc::clone(array)]),
+    }
+}
+
+/// Encodes the values of a nested column as JSON strings, with null values as nulls.
+fn to_json(array: &ArrayRef) -> Result<ArrayRef, ArrowError> {
+    let schema = Schema::new(vec![Field::new("v", array.data_type().clone(), true)]);
+    let batch = RecordBatch::try_new(Arc::new(schema), vec![Arc::clone(array)])?;
+
+    let mut writer = arrow_json::LineDelimitedWriter::new(Vec::new());
+    writer.write(&batch)?;
+    writer.finish()?;
+    let lines = writer.into_inner();
+
+    let values = lines
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut row: serde_json::Map<String, Value> =
+                serde_json::from_slice(line).map_err(|e| ArrowError::JsonError(e.to_string()))?;
+            Ok(match row.remove("v") {
+                None | Some(Value::Null) => None,
+                Some(value) => Some(value.to_string()),
+            })
+        })
+        .collect::<Result<StringArray, ArrowError>>()?;
+
+    Ok(Arc::new(values))
+}
+
+/// Converts a batch of the source to the stored columns in `schema`.
+fn store_batch(
+    batch: &RecordBatch,
+    nested_types: NestedTypes,
+    schema: &SchemaRef,
+) -> Result<RecordBatch, ArrowError> {
+    let mut stored = HashMap::new();
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        let names = stored_fields(field, nested_types)
+            .into_iter()
+            .map(|field| field.name().clone());
+        stored.extend(names.zip(stored_columns(field, column, nested_types)?));
+    }
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            stored.remove(field.name()).ok_or_else(|| {
+                ArrowError::SchemaError(format!("Missing stored column {}", field.name()))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    RecordBatch::try_new_with_options(
+        Arc::clone(schema),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )
+}
+
+/// A source whose nested columns are JSON-encoded or flattened so an accelerator engine can store them.
+pub struct NestedTypesTable {
+    inner: Arc<dyn TableProvider>,
+    nested_types: NestedTypes,
+    schema: SchemaRef,
+    /// The column of `inner` each column of the table is stored from.
+    sources: Vec<usize>,
+    /// The columns of `inner` that are stored as they are.
+    unchanged: HashSet<String>,
+}
+
+impl NestedTypesTable {
+    #[must_use]
+    pub fn new(inner: Arc<dyn TableProvider>, nested_types: NestedTypes) -> Self {
+        let mut fields = vec![];
+        let mut sources = vec![];
+        let mut unchanged = HashSet::new();
+        for (index, field) in inner.schema().fields().iter().enumerate() {
+            let stored = stored_fields(field, nested_types);
+            if stored.len() == 1 && stored[0] == *field {
+                unchanged.insert(field.name().clone());
+            }
+            sources.extend(std::iter::repeat(index).take(stored.len()));
+            fields.extend(stored);
+        }
+
+        Self {
+            inner,
+            nested_types,
+            schema: Arc::new(Schema::new(fields)),
+            sources,
+            unchanged,
+        }
+    }
+
+    fn references_transformed(&self, filter: &Expr) -> bool {
+        let mut columns = HashSet::new();
+        expr_to_columns(filter, &mut columns).is_err()
+            || columns
+                .iter()
+                .any(|column| !self.unchanged.contains(&column.name))
+    }
+}
+
+#[async_trait]
+impl TableProvider for NestedTypesTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        let pushable = filters
+            .iter()
+            .copied()
+            .filter(|filter| !self.references_transformed(filter))
+            .collect::<Vec<_>>();
+        let mut inner = self.inner.supports_filters_pushdown(&pushable)?.into_iter();
+
+        Ok(filters
+            .iter()
+            .map(|filter| {
+                if self.references_transformed(filter) {
+                    TableProviderFilterPushDown::Unsupported
+                } else {
+                    inner
+                        .next()
+                        .unwrap_or(TableProviderFilterPushDown::Unsupported)
+                }
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let filters = filters
+            .iter()
+            .filter(|filter| !self.references_transformed(filter))
+            .cloned()
+            .collect::<Vec<_>>();
+        let (schema, inner_projection) = match projection {
+            Some(projection) => {
+                let mut inner_projection = projection
+                    .iter()
+                    .map(|index| self.sources[*index])
+                    .collect::<Vec<_>>();
+                inner_projection.sort_unstable();
+                inner_projection.dedup();
+                (
+                    Arc::new(self.schema.project(projection)?),
+                    Some(inner_projection),
+                )
+            }
+            None => (Arc::clone(&self.schema), None),
+        };
+
+        let plan = self
+            .inner
+            .scan(state, inner_projection.as_ref(), &filters, limit)
+            .await?;
+        Ok(Arc::new(NestedTypesExec::new(
+            plan,
+            self.nested_types,
+            schema,
+        )))
+    }
+}
+
+/// `NestedTypesExec` converts the batches of its input to the stored columns of a [`NestedTypesTable`].
+pub struct NestedTypesExec {
+    input: Arc<dyn ExecutionPlan>,
+    nested_types: NestedTypes,
+    schema: SchemaRef,
+    properties: PlanProperties,
+}
+
+impl NestedTypesExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        nested_types: NestedTypes,
+        schema: SchemaRef,
+    ) -> Self {
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(Arc::clone(&schema)),
+            input.output_partitioning().clone(),
+            input.execution_mode(),
+        );
+        Self {
+            input,
+            nested_types,
+            schema,
+            properties,
+        }
+    }
+}
+
+impl fmt::Debug for NestedTypesExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NestedTypesExec nested_types: {}", self.nested_types)
+    }
+}
+
+impl DisplayAs for NestedTypesExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NestedTypesExec nested_types: {}", self.nested_types)
+    }
+}
+
+impl ExecutionPlan for NestedTypesExec {
+    fn name(&self) -> &'static str {
+        "NestedTypesExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        if children.len() == 1 {
+            Ok(Arc::new(NestedTypesExec::new(
+                Arc::clone(&children[0]),
+                self.nested_types,
+                Arc::clone(&self.schema),
+            )))
+        } else {
+            Err(DataFusionError::Execution(
+                "NestedTypesExec expects exactly one input".to_string(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let nested_types = self.nested_types;
+        let schema = Arc::clone(&self.schema);
+        let stream = self.input.execute(partition, context)?.map(move |batch| {
+            store_batch(&batch?, nested_types, &schema).map_err(DataFusionError::from)
+        });
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            Arc::clone(&self.schema),
+            stream,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int32Array, StructArray};
+    use arrow::datatypes::Fields;
+
+    use super::*;
+
+    #[test]
+    fn test_store_nested_columns() {
+        let children = Fields::from(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]);
+        let field = Arc::new(Field::new("s", DataType::Struct(children.clone()), true));
+        let array: ArrayRef = Arc::new(StructArray::new(
+            children,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("x"), None])),
+            ],
+            Some(NullBuffer::from(vec![true, false])),
+        ));
+        let schema = Schema::new(vec![Arc::clone(&field)]);
+        let batch = RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::clone(&array)])
+            .expect("batch");
+
+        assert_eq!(unsupported_columns(Engine::Sqlite, &schema), vec!["s"]);
+        assert!(unsupported_columns(Engine::DuckDB, &schema).is_empty());
+
+        let table_schema = Arc::new(Schema::new(stored_fields(&field, NestedTypes::Json)));
+        let stored = store_batch(&batch, NestedTypes::Json, &table_schema).expect("stored");
+        let json = stored.column(0).as_string::<i32>();
+        assert_eq!(json.value(0), r#"{"a":1,"b":"x"}"#);
+        assert!(json.is_null(1));
+
+        let table_schema = Arc::new(Schema::new(stored_fields(&field, NestedTypes::Flatten)));
+        assert_eq!(table_schema.field(0).name(), "s.a");
+        assert_eq!(table_schema.field(1).name(), "s.b");
+        let stored = store_batch(&batch, NestedTypes::Flatten, &table_schema).expect("stored");
+        let a = stored
+            .column(0)
+            .as_primitive::<arrow::datatypes::Int32Type>();
+        assert_eq!(a.value(0), 1);
+        assert!(a.is_null(1));
+    }
+}
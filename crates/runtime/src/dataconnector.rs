@@ -19,9 +19,11 @@ use arrow::datatypes::SchemaRef;
 use async_trait::async_trait;
 use data_components::object::metadata::ObjectStoreMetadataTable;
 use data_components::object::text::ObjectStoreTextTable;
+use datafusion::config::TableParquetOptions;
 use datafusion::dataframe::DataFrame;
 use datafusion::datasource::file_format::csv::CsvFormat;
 use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use datafusion::datasource::file_format::json::JsonFormat;
 use datafusion::datasource::file_format::parquet::ParquetFormat;
 use datafusion::datasource::file_format::FileFormat;
 use datafusion::datasource::listing::{
@@ -31,7 +33,9 @@ use datafusion::datasource::{DefaultTableSource, TableProvider};
 use datafusion::execution::config::SessionConfig;
 use datafusion::execution::context::SessionContext;
 use datafusion::logical_expr::{Expr, LogicalPlanBuilder};
+use datafusion::physical_plan::{execute_stream_partitioned, ExecutionPlanProperties};
 use datafusion::sql::TableReference;
+use futures::{future, stream, StreamExt};
 use lazy_static::lazy_static;
 use object_store::ObjectStore;
 use snafu::prelude::*;
@@ -47,25 +51,38 @@ use url::Url;
 use secrets::Secret;
 use std::future::Future;
 
+use crate::accelerated_table::progress::RefreshJob;
+use crate::column_types;
+use crate::execution_plan::parquet_metrics::ParquetMetricsTable;
 use crate::object_store_registry::default_runtime_env;
 
 #[cfg(feature = "clickhouse")]
 pub mod clickhouse;
+pub mod cluster;
+pub mod confluence;
 #[cfg(feature = "databricks")]
 pub mod databricks;
 #[cfg(feature = "dremio")]
 pub mod dremio;
 #[cfg(feature = "duckdb")]
 pub mod duckdb;
+pub mod fhir;
 pub mod file;
 #[cfg(feature = "flightsql")]
 pub mod flightsql;
 #[cfg(feature = "ftp")]
 pub mod ftp;
+pub mod github;
 pub mod graphql;
+#[cfg(feature = "hdfs")]
+pub mod hdfs;
+pub mod html;
+pub mod imap;
+pub(crate) mod incremental;
 pub mod localhost;
 #[cfg(feature = "mysql")]
 pub mod mysql;
+pub mod notion;
 #[cfg(feature = "odbc")]
 pub mod odbc;
 #[cfg(feature = "postgres")]
@@ -75,6 +92,7 @@ pub mod s3;
 pub mod sftp;
 #[cfg(feature = "spark")]
 pub mod spark;
+pub mod spice;
 pub mod spiceai;
 
 #[cfg(feature = "snowflake")]
@@ -230,8 +248,17 @@ pub async fn create_new_connector(
     }
 }
 
+/// Whether a connector is registered for the `from` prefix `name`.
+pub async fn is_registered(name: &str) -> bool {
+    DATA_CONNECTOR_FACTORY_REGISTRY
+        .lock()
+        .await
+        .contains_key(name)
+}
+
 pub async fn register_all() {
     register_connector_factory("localhost", localhost::LocalhostConnector::create).await;
+    register_connector_factory("cluster", cluster::Cluster::create).await;
     #[cfg(feature = "databricks")]
     register_connector_factory("databricks", databricks::Databricks::create).await;
     #[cfg(feature = "dremio")]
@@ -244,6 +271,9 @@ pub async fn register_all() {
     register_connector_factory("ftp", ftp::FTP::create).await;
     #[cfg(feature = "ftp")]
     register_connector_factory("sftp", sftp::SFTP::create).await;
+    #[cfg(feature = "hdfs")]
+    register_connector_factory("hdfs", hdfs::Hdfs::create).await;
+    register_connector_factory("spice", spice::Spice::create).await;
     register_connector_factory("spiceai", spiceai::SpiceAI::create).await;
     #[cfg(feature = "mysql")]
     register_connector_factory("mysql", mysql::MySQL::create).await;
@@ -254,6 +284,11 @@ pub async fn register_all() {
     #[cfg(feature = "clickhouse")]
     register_connector_factory("clickhouse", clickhouse::Clickhouse::create).await;
     register_connector_factory("graphql", graphql::GraphQL::create).await;
+    register_connector_factory("notion", notion::Notion::create).await;
+    register_connector_factory("github", github::GitHub::create).await;
+    register_connector_factory("confluence", confluence::Confluence::create).await;
+    register_connector_factory("imap", imap::Imap::create).await;
+    register_connector_factory("fhir", fhir::Fhir::create).await;
     #[cfg(feature = "odbc")]
     register_connector_factory("odbc", odbc::ODBC::create).await;
     #[cfg(feature = "spark")]
@@ -306,6 +341,7 @@ pub async fn get_data(
     table_provider: Arc<dyn TableProvider>,
     sql: Option<String>,
     filters: Vec<Expr>,
+    progress: Option<&RefreshJob>,
 ) -> Result<(SchemaRef, Vec<arrow::record_batch::RecordBatch>)> {
     let mut df = match sql {
         None => {
@@ -327,11 +363,65 @@ pub async fn get_data(
         df = df.filter(filter).context(UnableToFilterDataFrameSnafu {})?;
     }
 
-    let batches = df.collect().await.context(UnableToScanTableProviderSnafu)?;
+    let Some(progress) = progress else {
+        let batches = df.collect().await.context(UnableToScanTableProviderSnafu)?;
+        return Ok((table_provider.schema(), batches));
+    };
+
+    let plan = df
+        .create_physical_plan()
+        .await
+        .context(UnableToScanTableProviderSnafu)?;
+    let estimated_rows = plan
+        .statistics()
+        .ok()
+        .and_then(|statistics| statistics.num_rows.get_value().copied());
+    progress.planned(plan.output_partitioning().partition_count(), estimated_rows);
+
+    // The partitions are read side by side, each ending with `None`, so the progress reports where rows come from.
+    let partitions = execute_stream_partitioned(plan, ctx.task_ctx())
+        .context(UnableToScanTableProviderSnafu)?
+        .into_iter()
+        .enumerate()
+        .map(|(partition, batches)| {
+            batches
+                .map(move |batch| (partition, Some(batch)))
+                .chain(stream::once(future::ready((partition, None))))
+        });
+    let mut partitions = stream::select_all(partitions);
+
+    let mut batches = vec![];
+    while let Some((partition, batch)) = partitions.next().await {
+        match batch {
+            Some(batch) => {
+                let batch = batch.context(UnableToScanTableProviderSnafu)?;
+                progress.fetched(partition, &batch);
+                batches.push(batch);
+            }
+            None => progress.partition_completed(),
+        }
+    }
 
     Ok((table_provider.schema(), batches))
 }
 
+/// The number of records CSV and JSON schemas are inferred from, per the `schema_infer_max_records` param.
+fn schema_infer_max_records(params: &HashMap<String, String>) -> usize {
+    params
+        .get("schema_infer_max_records")
+        .map_or_else(|| 1000, |f| usize::from_str(f).map_or(1000, |f| f))
+}
+
+fn compression_type_from_extension(extension: &str) -> Option<FileCompressionType> {
+    match extension.to_ascii_lowercase().as_str() {
+        "gz" => Some(FileCompressionType::GZIP),
+        "bz2" => Some(FileCompressionType::BZIP2),
+        "xz" => Some(FileCompressionType::XZ),
+        "zst" | "zstd" => Some(FileCompressionType::ZSTD),
+        _ => None,
+    }
+}
+
 pub trait ListingTableConnector: DataConnector {
     fn as_any(&self) -> &dyn Any;
 
@@ -380,10 +470,32 @@ pub trait ListingTableConnector: DataConnector {
         let store = self.get_object_store(dataset)?;
         let (_, extension) = self.get_file_format_and_extension(dataset)?;
 
-        let table = ObjectStoreMetadataTable::try_new(store, &store_url, Some(extension.clone()))
-            .context(InvalidConfigurationSnafu {
+        let filename_regex = self.get_params().get("filename_regex").map(String::as_str);
+        let listing_cache_ttl = self
+            .get_params()
+            .get("metadata_listing_cache_ttl")
+            .map(|ttl| {
+                fundu::parse_duration(ttl)
+                    .boxed()
+                    .context(InvalidConfigurationSnafu {
+                        dataconnector: format!("{self}"),
+                        message: format!("Invalid metadata_listing_cache_ttl: {ttl}"),
+                    })
+            })
+            .transpose()?;
+
+        let table = ObjectStoreMetadataTable::try_new(
+            store,
+            &store_url,
+            Some(extension.clone()),
+            filename_regex,
+            listing_cache_ttl,
+        )
+        .context(InvalidConfigurationSnafu {
             dataconnector: format!("{self}"),
-            message: format!("Invalid extension ({extension}) for source ({store_url})"),
+            message: format!(
+                "Invalid extension ({extension}) or filename_regex for source ({store_url})"
+            ),
         })?;
         Ok(table as Arc<dyn TableProvider>)
     }
@@ -395,7 +507,10 @@ pub trait ListingTableConnector: DataConnector {
     /// unstructured formats. It supports the following tabular formats:
     ///  - parquet
     ///  - csv
+    ///  - json (newline-delimited)
     /// For tabular formats, file options can also be specified in the [`Dataset`]'s `param`s.
+    /// CSV and JSON files can be compressed, per the `compression_type` param or the extension of
+    /// the dataset, i.e. `trips.csv.gz`.
     ///
     /// For unstructured text formats, the [`Dataset`]'s `file_format` param key must be set. `Ok`
     /// responses, are always of the format `Ok((None, String))`. The data must be UTF8 compatible.
@@ -409,48 +524,94 @@ pub trait ListingTableConnector: DataConnector {
         let params = self.get_params();
         let extension = params.get("file_extension").cloned();
 
-        match params.get("file_format").map(String::as_str) {
-            Some("csv") => Ok((
-                Some(self.get_csv_format(params)?),
-                extension.unwrap_or(".csv".to_string()),
+        let mut path = std::path::PathBuf::from(dataset.path());
+        let path_compression = path
+            .extension()
+            .and_then(|ext| compression_type_from_extension(ext.to_str()?));
+        if path_compression.is_some() {
+            path.set_extension("");
+        }
+        let compression = match params.get("compression_type") {
+            Some(compression_type) => FileCompressionType::from_str(compression_type)
+                .boxed()
+                .context(InvalidConfigurationSnafu {
+                    dataconnector: format!("{self}"),
+                    message: format!("Invalid compression_type: {compression_type}, supported types are: GZIP, BZIP2, XZ, ZSTD, UNCOMPRESSED"),
+                })?,
+            None => path_compression.unwrap_or(FileCompressionType::UNCOMPRESSED),
+        };
+
+        let format = match params.get("file_format") {
+            Some(format) => format.to_ascii_lowercase(),
+            None => match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext)
+                    if ["csv", "parquet", "json", "jsonl"]
+                        .contains(&ext.to_ascii_lowercase().as_str()) =>
+                {
+                    ext.to_ascii_lowercase()
+                }
+                _ => {
+                    return Err(DataConnectorError::InvalidConfiguration {
+                        dataconnector: format!("{self}"),
+                        message: "Missing required file_format parameter.".to_string(),
+                        source: "Missing file format".into(),
+                    })
+                }
+            },
+        };
+
+        let compression_ext = compression.get_ext();
+        match format.as_str() {
+            "csv" => Ok((
+                Some(Self::get_csv_format(params, compression)),
+                extension.unwrap_or(format!(".csv{compression_ext}")),
+            )),
+            "json" | "jsonl" => Ok((
+                Some(Self::get_json_format(params, compression)),
+                extension.unwrap_or(format!(".{format}{compression_ext}")),
             )),
-            Some("parquet") => Ok((
-                Some(Arc::new(ParquetFormat::default())),
+            "parquet" => Ok((
+                Some(self.get_parquet_format(params)?),
                 extension.unwrap_or(".parquet".to_string()),
             )),
-            Some(format) => Ok((None, format!(".{format}"))),
-            None => {
-                if let Some(ext) = std::path::Path::new(dataset.path().as_str()).extension() {
-                    if ext.eq_ignore_ascii_case("csv") {
-                        return Ok((
-                            Some(self.get_csv_format(params)?),
-                            extension.unwrap_or(".csv".to_string()),
-                        ));
-                    }
-                    if ext.eq_ignore_ascii_case("parquet") {
-                        return Ok((
-                            Some(Arc::new(ParquetFormat::default())),
-                            extension.unwrap_or(".parquet".to_string()),
-                        ));
-                    }
-                }
-
-                Err(DataConnectorError::InvalidConfiguration {
-                    dataconnector: format!("{self}"),
-                    message: "Missing required file_format parameter.".to_string(),
-                    source: "Missing file format".into(),
-                })
-            }
+            format => Ok((None, format!(".{format}"))),
         }
     }
 
-    fn get_csv_format(
+    /// The Parquet reader of the dataset. Row groups are pruned with their statistics, bloom filters and page
+    /// indexes unless disabled with the `parquet_pruning`, `parquet_bloom_filter` and `parquet_page_index` params.
+    /// With `parquet_pushdown_filters`, filters are also applied while decoding the row groups.
+    fn get_parquet_format(
         &self,
         params: &HashMap<String, String>,
-    ) -> DataConnectorResult<Arc<CsvFormat>>
+    ) -> DataConnectorResult<Arc<ParquetFormat>>
     where
         Self: Display,
     {
+        let toggle = |param: &str, default: bool| match params.get(param).map(String::as_str) {
+            None => Ok(default),
+            Some("true") => Ok(true),
+            Some("false") => Ok(false),
+            Some(value) => Err(DataConnectorError::InvalidConfiguration {
+                dataconnector: format!("{self}"),
+                message: format!("Invalid {param}: {value}, expected true or false"),
+                source: "Invalid Parquet reader option".into(),
+            }),
+        };
+
+        let mut options = TableParquetOptions::default();
+        options.global.pruning = toggle("parquet_pruning", true)?;
+        options.global.enable_page_index = toggle("parquet_page_index", true)?;
+        options.global.bloom_filter_on_read = toggle("parquet_bloom_filter", true)?;
+        options.global.pushdown_filters = toggle("parquet_pushdown_filters", false)?;
+
+        Ok(Arc::new(ParquetFormat::default().with_options(options)))
+    }
+
+    fn get_csv_format(
+        params: &HashMap<String, String>,
+        compression: FileCompressionType,
+    ) -> Arc<CsvFormat> {
         let has_header = params.get("has_header").map_or(true, |f| f == "true");
         let quote = params
             .get("quote")
@@ -458,30 +619,31 @@ pub trait ListingTableConnector: DataConnector {
         let escape = params
             .get("escape")
             .and_then(|f| f.as_bytes().first().copied());
-        let schema_infer_max_rec = params
-            .get("schema_infer_max_records")
-            .map_or_else(|| 1000, |f| usize::from_str(f).map_or(1000, |f| f));
         let delimiter = params
             .get("delimiter")
             .map_or(b',', |f| *f.as_bytes().first().unwrap_or(&b','));
-        let compression_type = params.get("compression_type").map_or("", |f| f);
 
-        Ok(Arc::new(
+        Arc::new(
             CsvFormat::default()
                 .with_has_header(has_header)
                 .with_quote(quote)
                 .with_escape(escape)
-                .with_schema_infer_max_rec(schema_infer_max_rec)
+                .with_schema_infer_max_rec(schema_infer_max_records(params))
                 .with_delimiter(delimiter)
-                .with_file_compression_type(
-                    FileCompressionType::from_str(compression_type)
-                        .boxed()
-                        .context(InvalidConfigurationSnafu {
-                            dataconnector: format!("{self}"),
-                            message: format!("Invalid CSV compression_type: {compression_type}, supported types are: GZIP, BZIP2, XZ, ZSTD, UNCOMPRESSED"),
-                        })?,
-                ),
-        ))
+                .with_file_compression_type(compression),
+        )
+    }
+
+    /// The reader of newline-delimited JSON files.
+    fn get_json_format(
+        params: &HashMap<String, String>,
+        compression: FileCompressionType,
+    ) -> Arc<JsonFormat> {
+        Arc::new(
+            JsonFormat::default()
+                .with_schema_infer_max_rec(schema_infer_max_records(params))
+                .with_file_compression_type(compression),
+        )
     }
 }
 
@@ -528,13 +690,17 @@ impl<T: ListingTableConnector + Display> DataConnector for T {
                     self.get_object_store(dataset)?,
                     &url.clone(),
                     Some(extension.clone()),
+                    self.get_params().get("filename_regex").map(String::as_str),
                 )
                 .context(InvalidConfigurationSnafu {
                     dataconnector: format!("{self}"),
-                    message: format!("Invalid extension ({extension}) for source ({url})"),
+                    message: format!(
+                        "Invalid extension ({extension}) or filename_regex for source ({url})"
+                    ),
                 })?)
             }
             Some(file_format) => {
+                let is_parquet = file_format.as_any().is::<ParquetFormat>();
                 let options = ListingOptions::new(file_format).with_file_extension(&extension);
 
                 let resolved_schema = options
@@ -545,6 +711,21 @@ impl<T: ListingTableConnector + Display> DataConnector for T {
                         dataconnector: format!("{self}"),
                     })?;
 
+                let strict = self
+                    .get_params()
+                    .get("schema_strict")
+                    .is_some_and(|strict| strict.eq_ignore_ascii_case("true"));
+                let resolved_schema = column_types::apply_column_types(
+                    &resolved_schema,
+                    &dataset.column_types,
+                    strict,
+                )
+                .map_err(|e| DataConnectorError::InvalidConfiguration {
+                    dataconnector: format!("{self}"),
+                    message: format!("Invalid column_types of {}: {e}", dataset.name),
+                    source: e.into(),
+                })?;
+
                 let config = ListingTableConfig::new(table_path)
                     .with_listing_options(options)
                     .with_schema(resolved_schema);
@@ -557,6 +738,13 @@ impl<T: ListingTableConnector + Display> DataConnector for T {
                         code: "LTC-RP-LTTN".to_string(), // ListingTableConnector-ReadProvider-ListingTableTryNew
                     })?;
 
+                if is_parquet {
+                    return Ok(Arc::new(ParquetMetricsTable::new(
+                        dataset.name.clone(),
+                        Arc::new(table),
+                    )));
+                }
+
                 Ok(Arc::new(table))
             }
         }
@@ -685,4 +873,40 @@ mod tests {
             panic!("Unexpected error");
         }
     }
+
+    #[test]
+    fn test_get_parquet_format_toggles() {
+        let mut params = HashMap::new();
+        params.insert("parquet_page_index".to_string(), "false".to_string());
+        params.insert("parquet_pushdown_filters".to_string(), "true".to_string());
+        let (connector, _) = setup_connector("test:test.parquet".to_string(), params.clone());
+
+        let format = connector
+            .get_parquet_format(&params)
+            .expect("valid toggles");
+        let options = &format.options().global;
+        assert!(options.pruning);
+        assert!(!options.enable_page_index);
+        assert!(options.bloom_filter_on_read);
+        assert!(options.pushdown_filters);
+
+        params.insert("parquet_pruning".to_string(), "no".to_string());
+        let (connector, _) = setup_connector("test:test.parquet".to_string(), params.clone());
+        assert!(connector.get_parquet_format(&params).is_err());
+    }
+
+    #[test]
+    fn test_get_file_format_and_extension_detect_compressed_json() {
+        let (connector, dataset) =
+            setup_connector("test:events.jsonl.gz".to_string(), HashMap::new());
+
+        if let Ok((Some(file_format), extension)) =
+            connector.get_file_format_and_extension(&dataset)
+        {
+            assert_eq!(extension, ".jsonl.gz");
+            assert!(file_format.as_any().is::<JsonFormat>());
+        } else {
+            panic!("Unexpected error");
+        }
+    }
 }
@@ -0,0 +1,63 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reads the accelerations of the datasets another member of the cluster owns, i.e. `from: cluster:spice-1`. The
+//! runtime reads the accelerated datasets other members own with this connector (see [`crate::cluster`]).
+
+use super::DataConnector;
+use super::DataConnectorFactory;
+use crate::cluster::exchange;
+use crate::component::dataset::Dataset;
+use async_trait::async_trait;
+use datafusion::datasource::TableProvider;
+use secrets::Secret;
+use snafu::prelude::*;
+use std::any::Any;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::{collections::HashMap, future::Future};
+
+pub struct Cluster {}
+
+impl DataConnectorFactory for Cluster {
+    fn create(
+        _secret: Option<Secret>,
+        _params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        // The members authenticate to each other with the token of `runtime.cluster.secret`.
+        Box::pin(async move { Ok(Arc::new(Self {}) as Arc<dyn DataConnector>) })
+    }
+}
+
+#[async_trait]
+impl DataConnector for Cluster {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let table = exchange::member_table(&dataset.path(), &dataset.name)
+            .await
+            .boxed()
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "cluster",
+            })?;
+        Ok(Arc::new(table))
+    }
+}
@@ -0,0 +1,654 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! FHIR resources as a dataset, with a column per commonly queried element of the resource type.
+//!
+//! `from: fhir:<resource_type>`, such as `fhir:Observation`, searches the server at `fhir_url`. Every table has the
+//! `id`, `version_id` and `last_updated` of each resource, and the whole resource as JSON in `resource`; elements
+//! without a column can be read from it with the JSON functions.
+//!
+//! Servers that need authorization are called with the `fhir_token` secret, or with a token from SMART Backend
+//! Services: `fhir_client_id` with either the `fhir_client_secret` secret or the `fhir_private_key` secret (a PEM RSA
+//! or EC key, identified by `fhir_key_id`). The token endpoint is read from the server's SMART configuration unless
+//! `fhir_token_url` is set, and `fhir_scope` defaults to `system/<resource_type>.read`.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use datafusion::datasource::TableProvider;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, USER_AGENT};
+use secrets::{get_secret_or_param, Secret};
+use serde_json::{json, Map, Value};
+use snafu::prelude::*;
+use tokio::sync::Mutex;
+use url::Url;
+use uuid::Uuid;
+
+use super::incremental::{self, IncrementalSource, IncrementalTable};
+use super::{DataConnector, DataConnectorFactory};
+use crate::component::dataset::Dataset;
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// How long before a SMART access token expires that it is replaced.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// How long the JWT a client authenticates with is valid, the most SMART servers accept.
+const CLIENT_ASSERTION_LIFETIME: i64 = 300;
+
+pub struct Fhir {
+    client: reqwest::Client,
+    url: Url,
+    page_size: usize,
+    credentials: Credentials,
+}
+
+#[derive(Clone)]
+enum Credentials {
+    None,
+    Token(String),
+    Smart(Arc<SmartClient>),
+}
+
+impl DataConnectorFactory for Fhir {
+    fn create(
+        secret: Option<Secret>,
+        params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            let url = params
+                .get("fhir_url")
+                .ok_or("`fhir_url` not found in params".into())
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "fhir",
+                    message: "`fhir_url` not found in params",
+                })?;
+            // Resource types and the SMART configuration are joined onto the base URL.
+            let url = Url::parse(&format!("{}/", url.trim_end_matches('/')))
+                .map_err(Into::into)
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "fhir",
+                    message: "Invalid fhir_url",
+                })?;
+            let page_size =
+                match params.get("fhir_page_size") {
+                    Some(size) => size.parse().map_err(Into::into).context(
+                        super::InvalidConfigurationSnafu {
+                            dataconnector: "fhir",
+                            message: "Invalid fhir_page_size",
+                        },
+                    )?,
+                    None => DEFAULT_PAGE_SIZE,
+                };
+
+            let mut headers = HeaderMap::new();
+            headers.append(USER_AGENT, HeaderValue::from_static("spice"));
+            headers.append(ACCEPT, HeaderValue::from_static("application/fhir+json"));
+            let client = reqwest::Client::builder()
+                .default_headers(headers)
+                .build()
+                .map_err(Into::into)
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "fhir",
+                    message: "Unable to create the HTTP client",
+                })?;
+
+            let credentials = if let Some(client_id) = params.get("fhir_client_id") {
+                Credentials::Smart(Arc::new(SmartClient::new(
+                    client.clone(),
+                    &url,
+                    client_id,
+                    &params,
+                    &secret,
+                )?))
+            } else if let Some(token) =
+                get_secret_or_param(&params, &secret, "fhir_token_key", "fhir_token")
+            {
+                Credentials::Token(token)
+            } else {
+                Credentials::None
+            };
+
+            Ok(Arc::new(Self {
+                client,
+                url,
+                page_size,
+                credentials,
+            }) as Arc<dyn DataConnector>)
+        })
+    }
+}
+
+#[async_trait]
+impl DataConnector for Fhir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let resource_type = dataset.path();
+        if !resource_type.starts_with(|c: char| c.is_ascii_uppercase())
+            || !resource_type.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(super::DataConnectorError::InvalidConfiguration {
+                dataconnector: "fhir".to_string(),
+                message: format!(
+                    "Invalid resource type {resource_type}. Use a FHIR resource type, such as fhir:Patient"
+                ),
+                source: format!("Invalid resource type {resource_type}").into(),
+            });
+        }
+
+        Ok(Arc::new(IncrementalTable::new(FhirResources {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            page_size: self.page_size,
+            credentials: self.credentials.clone(),
+            columns: columns(&resource_type),
+            resource_type,
+        })))
+    }
+}
+
+/// How a client authenticates to the token endpoint.
+enum ClientCredential {
+    Secret(String),
+    PrivateKey {
+        key: EncodingKey,
+        algorithm: Algorithm,
+        key_id: Option<String>,
+    },
+}
+
+/// A client of SMART Backend Services, requesting access tokens with the client credentials grant.
+struct SmartClient {
+    client: reqwest::Client,
+    base_url: Url,
+    token_url: Option<Url>,
+    client_id: String,
+    credential: ClientCredential,
+    scope: Option<String>,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl SmartClient {
+    fn new(
+        client: reqwest::Client,
+        base_url: &Url,
+        client_id: &str,
+        params: &HashMap<String, String>,
+        secret: &Option<Secret>,
+    ) -> super::DataConnectorResult<Self> {
+        let credential = if let Some(pem) =
+            get_secret_or_param(params, secret, "fhir_private_key_key", "fhir_private_key")
+        {
+            // SMART requires RS384 or ES384 client assertions.
+            let (key, algorithm) = match EncodingKey::from_rsa_pem(pem.as_bytes()) {
+                Ok(key) => (key, Algorithm::RS384),
+                Err(_) => (
+                    EncodingKey::from_ec_pem(pem.as_bytes())
+                        .map_err(Into::into)
+                        .context(super::InvalidConfigurationSnafu {
+                            dataconnector: "fhir",
+                            message: "fhir_private_key is not a PEM RSA or EC private key",
+                        })?,
+                    Algorithm::ES384,
+                ),
+            };
+            ClientCredential::PrivateKey {
+                key,
+                algorithm,
+                key_id: params.get("fhir_key_id").cloned(),
+            }
+        } else {
+            let client_secret = get_secret_or_param(
+                params,
+                secret,
+                "fhir_client_secret_key",
+                "fhir_client_secret",
+            )
+            .ok_or("`fhir_client_secret` or `fhir_private_key` not found in secrets".into())
+            .context(super::InvalidConfigurationSnafu {
+                dataconnector: "fhir",
+                message:
+                    "Missing the fhir_client_secret or fhir_private_key secret for fhir_client_id",
+            })?;
+            ClientCredential::Secret(client_secret)
+        };
+
+        let token_url = params
+            .get("fhir_token_url")
+            .map(|url| Url::parse(url))
+            .transpose()
+            .map_err(Into::into)
+            .context(super::InvalidConfigurationSnafu {
+                dataconnector: "fhir",
+                message: "Invalid fhir_token_url",
+            })?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.clone(),
+            token_url,
+            client_id: client_id.to_string(),
+            credential,
+            scope: params.get("fhir_scope").cloned(),
+            token: Mutex::new(None),
+        })
+    }
+
+    async fn token_url(&self) -> incremental::Result<Url> {
+        if let Some(url) = &self.token_url {
+            return Ok(url.clone());
+        }
+        let configuration = self
+            .base_url
+            .join(".well-known/smart-configuration")
+            .map_err(|e| incremental::Error::UnexpectedResponse {
+                message: e.to_string(),
+            })?;
+        let configuration = incremental::send_json(self.client.get(configuration)).await?;
+        configuration["token_endpoint"]
+            .as_str()
+            .and_then(|url| Url::parse(url).ok())
+            .context(incremental::UnexpectedResponseSnafu {
+                message: "The SMART configuration has no token_endpoint. Set fhir_token_url",
+            })
+    }
+
+    /// An access token for `resource_type`, requesting a new one when the last is about to expire.
+    async fn access_token(&self, resource_type: &str) -> incremental::Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some((cached, expires_at)) = token.as_ref() {
+            if Instant::now() + TOKEN_EXPIRY_MARGIN < *expires_at {
+                return Ok(cached.clone());
+            }
+        }
+
+        let token_url = self.token_url().await?;
+        let scope = self
+            .scope
+            .clone()
+            .unwrap_or_else(|| format!("system/{resource_type}.read"));
+        let mut form = vec![
+            ("grant_type", "client_credentials".to_string()),
+            ("scope", scope),
+        ];
+        let mut request = self.client.post(token_url.clone());
+        match &self.credential {
+            ClientCredential::Secret(secret) => {
+                request = request.basic_auth(&self.client_id, Some(secret));
+            }
+            ClientCredential::PrivateKey {
+                key,
+                algorithm,
+                key_id,
+            } => {
+                let now = Utc::now().timestamp();
+                let claims = json!({
+                    "iss": self.client_id,
+                    "sub": self.client_id,
+                    "aud": token_url.as_str(),
+                    "exp": now + CLIENT_ASSERTION_LIFETIME,
+                    "jti": Uuid::new_v4().to_string(),
+                });
+                let mut header = Header::new(*algorithm);
+                header.kid.clone_from(key_id);
+                let assertion = jsonwebtoken::encode(&header, &claims, key).map_err(|e| {
+                    incremental::Error::UnexpectedResponse {
+                        message: format!("Unable to sign the client assertion: {e}"),
+                    }
+                })?;
+                form.push((
+                    "client_assertion_type",
+                    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer".to_string(),
+                ));
+                form.push(("client_assertion", assertion));
+            }
+        }
+
+        let response = incremental::send_json(request.form(&form)).await?;
+        let access_token = response["access_token"]
+            .as_str()
+            .context(incremental::UnexpectedResponseSnafu {
+                message: "The token response has no access_token",
+            })?
+            .to_string();
+        let expires_in = response["expires_in"].as_u64().unwrap_or(300);
+        *token = Some((
+            access_token.clone(),
+            Instant::now() + Duration::from_secs(expires_in),
+        ));
+        Ok(access_token)
+    }
+}
+
+/// The JSON type of a column.
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    Number,
+    Boolean,
+    Timestamp,
+}
+
+/// A column with the element of each resource at a JSON pointer.
+#[derive(Clone, Copy)]
+struct Column {
+    name: &'static str,
+    pointer: &'static str,
+    kind: Kind,
+}
+
+const fn column(name: &'static str, pointer: &'static str, kind: Kind) -> Column {
+    Column {
+        name,
+        pointer,
+        kind,
+    }
+}
+
+// FHIR dates can be partial, such as `2024` or `2024-07`, so they are kept as strings.
+const PATIENT: &[Column] = &[
+    column("active", "/active", Kind::Boolean),
+    column("family_name", "/name/0/family", Kind::String),
+    column("given_name", "/name/0/given/0", Kind::String),
+    column("gender", "/gender", Kind::String),
+    column("birth_date", "/birthDate", Kind::String),
+    column("deceased", "/deceasedBoolean", Kind::Boolean),
+    column("deceased_date", "/deceasedDateTime", Kind::String),
+    column("city", "/address/0/city", Kind::String),
+    column("state", "/address/0/state", Kind::String),
+    column("postal_code", "/address/0/postalCode", Kind::String),
+    column("country", "/address/0/country", Kind::String),
+];
+
+const OBSERVATION: &[Column] = &[
+    column("status", "/status", Kind::String),
+    column("category", "/category/0/coding/0/code", Kind::String),
+    column("code_system", "/code/coding/0/system", Kind::String),
+    column("code", "/code/coding/0/code", Kind::String),
+    column("code_display", "/code/coding/0/display", Kind::String),
+    column("subject", "/subject/reference", Kind::String),
+    column("encounter", "/encounter/reference", Kind::String),
+    column("effective", "/effectiveDateTime", Kind::String),
+    column("issued", "/issued", Kind::String),
+    column("value", "/valueQuantity/value", Kind::Number),
+    column("value_unit", "/valueQuantity/unit", Kind::String),
+    column(
+        "value_code",
+        "/valueCodeableConcept/coding/0/code",
+        Kind::String,
+    ),
+    column("value_string", "/valueString", Kind::String),
+    column(
+        "interpretation",
+        "/interpretation/0/coding/0/code",
+        Kind::String,
+    ),
+];
+
+const ENCOUNTER: &[Column] = &[
+    column("status", "/status", Kind::String),
+    column("class", "/class/code", Kind::String),
+    column("type", "/type/0/coding/0/code", Kind::String),
+    column("type_display", "/type/0/coding/0/display", Kind::String),
+    column("subject", "/subject/reference", Kind::String),
+    column("period_start", "/period/start", Kind::String),
+    column("period_end", "/period/end", Kind::String),
+    column(
+        "service_provider",
+        "/serviceProvider/reference",
+        Kind::String,
+    ),
+];
+
+const CONDITION: &[Column] = &[
+    column(
+        "clinical_status",
+        "/clinicalStatus/coding/0/code",
+        Kind::String,
+    ),
+    column(
+        "verification_status",
+        "/verificationStatus/coding/0/code",
+        Kind::String,
+    ),
+    column("category", "/category/0/coding/0/code", Kind::String),
+    column("code_system", "/code/coding/0/system", Kind::String),
+    column("code", "/code/coding/0/code", Kind::String),
+    column("code_display", "/code/coding/0/display", Kind::String),
+    column("subject", "/subject/reference", Kind::String),
+    column("encounter", "/encounter/reference", Kind::String),
+    column("onset", "/onsetDateTime", Kind::String),
+    column("abatement", "/abatementDateTime", Kind::String),
+    column("recorded_date", "/recordedDate", Kind::String),
+];
+
+const PROCEDURE: &[Column] = &[
+    column("status", "/status", Kind::String),
+    column("code_system", "/code/coding/0/system", Kind::String),
+    column("code", "/code/coding/0/code", Kind::String),
+    column("code_display", "/code/coding/0/display", Kind::String),
+    column("subject", "/subject/reference", Kind::String),
+    column("encounter", "/encounter/reference", Kind::String),
+    column("performed", "/performedDateTime", Kind::String),
+    column("performed_start", "/performedPeriod/start", Kind::String),
+    column("performed_end", "/performedPeriod/end", Kind::String),
+];
+
+const MEDICATION_REQUEST: &[Column] = &[
+    column("status", "/status", Kind::String),
+    column("intent", "/intent", Kind::String),
+    column(
+        "medication_code",
+        "/medicationCodeableConcept/coding/0/code",
+        Kind::String,
+    ),
+    column(
+        "medication_display",
+        "/medicationCodeableConcept/coding/0/display",
+        Kind::String,
+    ),
+    column("medication", "/medicationReference/reference", Kind::String),
+    column("subject", "/subject/reference", Kind::String),
+    column("encounter", "/encounter/reference", Kind::String),
+    column("requester", "/requester/reference", Kind::String),
+    column("authored_on", "/authoredOn", Kind::String),
+];
+
+/// The columns of every resource, followed by those of `resource_type`.
+fn columns(resource_type: &str) -> Vec<Column> {
+    let mut columns = vec![
+        column("id", "/id", Kind::String),
+        column("version_id", "/meta/versionId", Kind::String),
+        column("last_updated", "/meta/lastUpdated", Kind::Timestamp),
+    ];
+    columns.extend_from_slice(match resource_type {
+        "Patient" => PATIENT,
+        "Observation" => OBSERVATION,
+        "Encounter" => ENCOUNTER,
+        "Condition" => CONDITION,
+        "Procedure" => PROCEDURE,
+        "MedicationRequest" => MEDICATION_REQUEST,
+        _ => &[],
+    });
+    columns
+}
+
+/// Flattens a resource into a record of `columns` and the `resource` JSON.
+fn flatten(resource: &Value, columns: &[Column]) -> Value {
+    let mut record = Map::new();
+    for column in columns {
+        let value = match (column.kind, resource.pointer(column.pointer)) {
+            (_, None | Some(Value::Null)) => Value::Null,
+            (Kind::String, Some(Value::String(value))) => Value::String(value.clone()),
+            // Elements that aren't strings, such as a number where a code is expected, are kept as JSON.
+            (Kind::String, Some(value)) => Value::String(value.to_string()),
+            (Kind::Number, Some(value @ Value::Number(_)))
+            | (Kind::Boolean, Some(value @ Value::Bool(_)))
+            | (Kind::Timestamp, Some(value @ Value::String(_))) => value.clone(),
+            _ => Value::Null,
+        };
+        record.insert(column.name.to_string(), value);
+    }
+    record.insert("resource".to_string(), Value::String(resource.to_string()));
+    Value::Object(record)
+}
+
+struct FhirResources {
+    client: reqwest::Client,
+    url: Url,
+    page_size: usize,
+    credentials: Credentials,
+    resource_type: String,
+    columns: Vec<Column>,
+}
+
+impl FhirResources {
+    async fn get(&self, url: Url) -> incremental::Result<reqwest::RequestBuilder> {
+        let request = self.client.get(url);
+        Ok(match &self.credentials {
+            Credentials::None => request,
+            Credentials::Token(token) => request.bearer_auth(token),
+            Credentials::Smart(smart) => {
+                request.bearer_auth(smart.access_token(&self.resource_type).await?)
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl IncrementalSource for FhirResources {
+    fn schema(&self) -> SchemaRef {
+        let mut fields = self
+            .columns
+            .iter()
+            .map(|column| {
+                let data_type = match column.kind {
+                    Kind::String => DataType::Utf8,
+                    Kind::Number => DataType::Float64,
+                    Kind::Boolean => DataType::Boolean,
+                    Kind::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, None),
+                };
+                Field::new(column.name, data_type, column.name != "id")
+            })
+            .collect::<Vec<_>>();
+        fields.push(Field::new("resource", DataType::Utf8, false));
+        Arc::new(Schema::new(fields))
+    }
+
+    fn updated_column(&self) -> Option<&'static str> {
+        Some("last_updated")
+    }
+
+    async fn fetch(&self, updated_since: Option<DateTime<Utc>>) -> incremental::Result<Vec<Value>> {
+        let mut url = self.url.join(&self.resource_type).map_err(|e| {
+            incremental::Error::UnexpectedResponse {
+                message: e.to_string(),
+            }
+        })?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("_count", &self.page_size.to_string());
+            if let Some(since) = updated_since {
+                query.append_pair(
+                    "_lastUpdated",
+                    &format!("ge{}", since.to_rfc3339_opts(SecondsFormat::Millis, true)),
+                );
+            }
+        }
+
+        let mut records = vec![];
+        let mut next = Some(url);
+        while let Some(url) = next.take() {
+            let bundle = incremental::send_json(self.get(url).await?).await?;
+            ensure!(
+                bundle["resourceType"] == "Bundle",
+                incremental::UnexpectedResponseSnafu {
+                    message: format!(
+                        "Expected a Bundle of {} resources, got {}",
+                        self.resource_type, bundle["resourceType"]
+                    ),
+                }
+            );
+
+            for entry in bundle["entry"].as_array().into_iter().flatten() {
+                // Searches can include referenced resources and OperationOutcomes alongside the matches.
+                let mode = entry["search"]["mode"].as_str().unwrap_or("match");
+                let resource = &entry["resource"];
+                if mode == "match" && resource["resourceType"] == self.resource_type.as_str() {
+                    records.push(flatten(resource, &self.columns));
+                }
+            }
+
+            next = bundle["link"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|link| link["relation"] == "next")
+                .and_then(|link| link["url"].as_str())
+                .and_then(|next| self.url.join(next).ok());
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_observation() {
+        let observation = json!({
+            "resourceType": "Observation",
+            "id": "obs-1",
+            "meta": { "versionId": "2", "lastUpdated": "2024-07-01T10:00:00.000Z" },
+            "status": "final",
+            "code": { "coding": [{ "system": "http://loinc.org", "code": "8867-4", "display": "Heart rate" }] },
+            "subject": { "reference": "Patient/p-1" },
+            "valueQuantity": { "value": 72, "unit": "beats/minute" }
+        });
+
+        let record = flatten(&observation, &columns("Observation"));
+        assert_eq!(record["id"], "obs-1");
+        assert_eq!(record["version_id"], "2");
+        assert_eq!(record["last_updated"], "2024-07-01T10:00:00.000Z");
+        assert_eq!(record["code"], "8867-4");
+        assert_eq!(record["subject"], "Patient/p-1");
+        assert_eq!(record["value"], 72);
+        assert_eq!(record["encounter"], Value::Null);
+        assert_eq!(
+            serde_json::from_str::<Value>(record["resource"].as_str().unwrap_or_default())
+                .ok()
+                .as_ref(),
+            Some(&observation)
+        );
+    }
+}
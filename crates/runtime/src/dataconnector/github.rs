@@ -0,0 +1,477 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! GitHub repository issues, pull requests and files as datasets.
+//!
+//! `from: github:<owner>/<repo>/issues`, `github:<owner>/<repo>/pulls` and `github:<owner>/<repo>/files` are
+//! supported. Issues and pull requests refresh incrementally on `updated_at`. Files list the tree of `github_ref`
+//! (default the default branch), with their text in `content` when `github_include_content` is `true`.
+//! The token is read from the `github_token` secret, and `github_url` sets the API of a GitHub Enterprise server.
+
+use std::{any::Any, collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::datasource::TableProvider;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, LINK, USER_AGENT};
+use secrets::{get_secret_or_param, Secret};
+use serde_json::{json, Value};
+use snafu::prelude::*;
+
+use super::incremental::{self, IncrementalSource, IncrementalTable};
+use super::{DataConnector, DataConnectorFactory};
+use crate::component::dataset::Dataset;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+const PAGE_SIZE: usize = 100;
+
+/// Files larger than this aren't read into `content`.
+const MAX_CONTENT_SIZE: i64 = 1024 * 1024;
+
+#[derive(Clone)]
+struct GitHubClient {
+    client: reqwest::Client,
+    api_url: String,
+}
+
+impl GitHubClient {
+    /// Gets every page of a list, following the `next` links, until `done` returns true for an item.
+    async fn get_all(
+        &self,
+        url: String,
+        query: &[(&str, String)],
+        mut done: impl FnMut(&Value) -> bool + Send,
+    ) -> incremental::Result<Vec<Value>> {
+        let mut items = vec![];
+        let mut request = Some(self.client.get(url).query(query));
+        while let Some(next) = request.take() {
+            let response = incremental::send(next).await?;
+            let next_url = next_link(response.headers());
+            let page: Value = response
+                .json()
+                .await
+                .context(incremental::UnableToReadResponseSnafu)?;
+
+            for item in page.as_array().into_iter().flatten() {
+                if done(item) {
+                    return Ok(items);
+                }
+                items.push(item.clone());
+            }
+            request = next_url.map(|url| self.client.get(url));
+        }
+        Ok(items)
+    }
+}
+
+/// The URL of the next page from a `Link` header.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, rel) = part.split_once(';')?;
+        rel.contains("rel=\"next\"").then(|| {
+            url.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+    })
+}
+
+pub struct GitHub {
+    client: GitHubClient,
+    params: Arc<HashMap<String, String>>,
+}
+
+impl DataConnectorFactory for GitHub {
+    fn create(
+        secret: Option<Secret>,
+        params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            let mut headers = HeaderMap::new();
+            headers.append(USER_AGENT, HeaderValue::from_static("spice"));
+            headers.append(
+                ACCEPT,
+                HeaderValue::from_static("application/vnd.github+json"),
+            );
+            headers.append(
+                "X-GitHub-Api-Version",
+                HeaderValue::from_static("2022-11-28"),
+            );
+            if let Some(token) =
+                get_secret_or_param(&params, &secret, "github_token_key", "github_token")
+            {
+                let mut authorization = HeaderValue::from_str(&format!("Bearer {token}"))
+                    .map_err(Into::into)
+                    .context(super::InvalidConfigurationSnafu {
+                        dataconnector: "github",
+                        message: "Invalid github_token",
+                    })?;
+                authorization.set_sensitive(true);
+                headers.append(reqwest::header::AUTHORIZATION, authorization);
+            }
+
+            let client = reqwest::Client::builder()
+                .default_headers(headers)
+                .build()
+                .map_err(Into::into)
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "github",
+                    message: "Unable to create the HTTP client",
+                })?;
+            let api_url = params
+                .get("github_url")
+                .map_or(DEFAULT_API_URL, String::as_str)
+                .trim_end_matches('/')
+                .to_string();
+
+            Ok(Arc::new(Self {
+                client: GitHubClient { client, api_url },
+                params,
+            }) as Arc<dyn DataConnector>)
+        })
+    }
+}
+
+#[async_trait]
+impl DataConnector for GitHub {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let path = dataset.path();
+        let invalid_path = |source: String| super::DataConnectorError::InvalidConfiguration {
+            dataconnector: "github".to_string(),
+            message: "Expected a dataset path of <owner>/<repo>/<issues|pulls|files>".to_string(),
+            source: source.into(),
+        };
+        let parts = path.split('/').collect::<Vec<_>>();
+        let [owner, repo, kind] = parts.as_slice() else {
+            return Err(invalid_path(format!("Invalid dataset path {path}")));
+        };
+        let full_name = format!("{owner}/{repo}");
+        let repo = format!("{}/repos/{full_name}", self.client.api_url);
+
+        match *kind {
+            "issues" => Ok(Arc::new(IncrementalTable::new(Issues {
+                client: self.client.clone(),
+                repo,
+            }))),
+            "pulls" => Ok(Arc::new(IncrementalTable::new(PullRequests {
+                client: self.client.clone(),
+                repo,
+            }))),
+            "files" => Ok(Arc::new(IncrementalTable::new(Files {
+                client: self.client.clone(),
+                repo,
+                full_name,
+                git_ref: self.params.get("github_ref").cloned(),
+                include_content: self
+                    .params
+                    .get("github_include_content")
+                    .is_some_and(|value| value == "true"),
+            }))),
+            _ => Err(invalid_path(format!("Unknown GitHub dataset {kind}"))),
+        }
+    }
+}
+
+/// The web URL of the GitHub server with the API at `api_url`.
+fn web_url(api_url: &str) -> &str {
+    if api_url == DEFAULT_API_URL {
+        "https://github.com"
+    } else {
+        // GitHub Enterprise serves its API at /api/v3.
+        api_url.trim_end_matches("/api/v3")
+    }
+}
+
+fn timestamp_field(name: &str) -> Field {
+    Field::new(name, DataType::Timestamp(TimeUnit::Millisecond, None), true)
+}
+
+fn labels(item: &Value) -> Option<String> {
+    let labels = item["labels"]
+        .as_array()?
+        .iter()
+        .filter_map(|label| label["name"].as_str())
+        .collect::<Vec<_>>();
+    (!labels.is_empty()).then(|| labels.join(", "))
+}
+
+struct Issues {
+    client: GitHubClient,
+    repo: String,
+}
+
+#[async_trait]
+impl IncrementalSource for Issues {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("number", DataType::Int64, false),
+            Field::new("title", DataType::Utf8, true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("author", DataType::Utf8, true),
+            Field::new("labels", DataType::Utf8, true),
+            Field::new("body", DataType::Utf8, true),
+            Field::new("url", DataType::Utf8, true),
+            Field::new("comments", DataType::Int64, true),
+            timestamp_field("created_at"),
+            timestamp_field("updated_at"),
+            timestamp_field("closed_at"),
+        ]))
+    }
+
+    fn updated_column(&self) -> Option<&'static str> {
+        Some("updated_at")
+    }
+
+    async fn fetch(&self, updated_since: Option<DateTime<Utc>>) -> incremental::Result<Vec<Value>> {
+        let mut query = vec![
+            ("state", "all".to_string()),
+            ("sort", "updated".to_string()),
+            ("direction", "asc".to_string()),
+            ("per_page", PAGE_SIZE.to_string()),
+        ];
+        if let Some(since) = updated_since {
+            query.push(("since", since.to_rfc3339()));
+        }
+        let items = self
+            .client
+            .get_all(format!("{}/issues", self.repo), &query, |_| false)
+            .await?;
+
+        // The issues API lists pull requests too.
+        Ok(items
+            .iter()
+            .filter(|item| item.get("pull_request").is_none())
+            .map(|item| {
+                json!({
+                    "number": item["number"],
+                    "title": item["title"],
+                    "state": item["state"],
+                    "author": item["user"]["login"],
+                    "labels": labels(item),
+                    "body": item["body"],
+                    "url": item["html_url"],
+                    "comments": item["comments"],
+                    "created_at": item["created_at"],
+                    "updated_at": item["updated_at"],
+                    "closed_at": item["closed_at"],
+                })
+            })
+            .collect())
+    }
+}
+
+struct PullRequests {
+    client: GitHubClient,
+    repo: String,
+}
+
+#[async_trait]
+impl IncrementalSource for PullRequests {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("number", DataType::Int64, false),
+            Field::new("title", DataType::Utf8, true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("author", DataType::Utf8, true),
+            Field::new("labels", DataType::Utf8, true),
+            Field::new("body", DataType::Utf8, true),
+            Field::new("url", DataType::Utf8, true),
+            Field::new("draft", DataType::Boolean, true),
+            Field::new("base_ref", DataType::Utf8, true),
+            Field::new("head_ref", DataType::Utf8, true),
+            timestamp_field("created_at"),
+            timestamp_field("updated_at"),
+            timestamp_field("closed_at"),
+            timestamp_field("merged_at"),
+        ]))
+    }
+
+    fn updated_column(&self) -> Option<&'static str> {
+        Some("updated_at")
+    }
+
+    async fn fetch(&self, updated_since: Option<DateTime<Utc>>) -> incremental::Result<Vec<Value>> {
+        let query = [
+            ("state", "all".to_string()),
+            ("sort", "updated".to_string()),
+            ("direction", "desc".to_string()),
+            ("per_page", PAGE_SIZE.to_string()),
+        ];
+        // The pulls API can't filter on update times, but lists the most recently updated first.
+        let items = self
+            .client
+            .get_all(format!("{}/pulls", self.repo), &query, |item| {
+                let updated = item["updated_at"]
+                    .as_str()
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+                matches!((updated_since, updated), (Some(since), Some(updated)) if updated < since)
+            })
+            .await?;
+
+        Ok(items
+            .iter()
+            .map(|item| {
+                json!({
+                    "number": item["number"],
+                    "title": item["title"],
+                    "state": item["state"],
+                    "author": item["user"]["login"],
+                    "labels": labels(item),
+                    "body": item["body"],
+                    "url": item["html_url"],
+                    "draft": item["draft"],
+                    "base_ref": item["base"]["ref"],
+                    "head_ref": item["head"]["ref"],
+                    "created_at": item["created_at"],
+                    "updated_at": item["updated_at"],
+                    "closed_at": item["closed_at"],
+                    "merged_at": item["merged_at"],
+                })
+            })
+            .collect())
+    }
+}
+
+struct Files {
+    client: GitHubClient,
+    repo: String,
+    /// The `<owner>/<repo>` name of the repository.
+    full_name: String,
+    /// The branch, tag or commit to list, or none for the default branch.
+    git_ref: Option<String>,
+    include_content: bool,
+}
+
+impl Files {
+    async fn content(&self, path: &str, git_ref: &str) -> incremental::Result<Option<String>> {
+        let request = self
+            .client
+            .client
+            .get(format!("{}/contents/{path}", self.repo))
+            .query(&[("ref", git_ref)])
+            .header(ACCEPT, "application/vnd.github.raw");
+        let bytes = incremental::send(request)
+            .await?
+            .bytes()
+            .await
+            .context(incremental::UnableToReadResponseSnafu)?;
+        // Binary files have no text content.
+        Ok(String::from_utf8(bytes.to_vec()).ok())
+    }
+}
+
+#[async_trait]
+impl IncrementalSource for Files {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("path", DataType::Utf8, false),
+            Field::new("sha", DataType::Utf8, true),
+            Field::new("size", DataType::Int64, true),
+            Field::new("url", DataType::Utf8, true),
+            Field::new("content", DataType::Utf8, true),
+        ]))
+    }
+
+    fn updated_column(&self) -> Option<&'static str> {
+        None
+    }
+
+    async fn fetch(
+        &self,
+        _updated_since: Option<DateTime<Utc>>,
+    ) -> incremental::Result<Vec<Value>> {
+        let git_ref = match &self.git_ref {
+            Some(git_ref) => git_ref.clone(),
+            None => {
+                let repo = incremental::send_json(self.client.client.get(&self.repo)).await?;
+                repo["default_branch"]
+                    .as_str()
+                    .context(incremental::UnexpectedResponseSnafu {
+                        message: "GitHub repository without a default branch",
+                    })?
+                    .to_string()
+            }
+        };
+
+        let tree = incremental::send_json(
+            self.client
+                .client
+                .get(format!("{}/git/trees/{git_ref}", self.repo))
+                .query(&[("recursive", "1")]),
+        )
+        .await?;
+        if tree["truncated"].as_bool().unwrap_or(false) {
+            tracing::warn!("The GitHub tree of {} at {git_ref} is truncated", self.repo);
+        }
+
+        let mut records = vec![];
+        for entry in tree["tree"].as_array().into_iter().flatten() {
+            let (Some("blob"), Some(path)) = (entry["type"].as_str(), entry["path"].as_str())
+            else {
+                continue;
+            };
+            let size = entry["size"].as_i64();
+            let content = if self.include_content && size.unwrap_or(0) <= MAX_CONTENT_SIZE {
+                self.content(path, &git_ref).await?
+            } else {
+                None
+            };
+            records.push(json!({
+                "path": path,
+                "sha": entry["sha"],
+                "size": size,
+                "url": format!(
+                    "{}/{}/blob/{git_ref}/{path}",
+                    web_url(&self.client.api_url),
+                    self.full_name
+                ),
+                "content": content,
+            }));
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_link() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            LINK,
+            HeaderValue::from_static(
+                r#"<https://api.github.com/repositories/1/issues?page=2>; rel="next", <https://api.github.com/repositories/1/issues?page=5>; rel="last""#,
+            ),
+        );
+        assert_eq!(
+            next_link(&headers),
+            Some("https://api.github.com/repositories/1/issues?page=2".to_string())
+        );
+        assert_eq!(next_link(&HeaderMap::new()), None);
+    }
+}
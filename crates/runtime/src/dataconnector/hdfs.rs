@@ -0,0 +1,86 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::component::dataset::Dataset;
+use secrets::Secret;
+use snafu::prelude::*;
+use std::any::Any;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::{collections::HashMap, future::Future};
+use url::Url;
+
+use super::{DataConnector, DataConnectorFactory, DataConnectorResult, ListingTableConnector};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to log in with Kerberos: {source}"))]
+    UnableToLogInWithKerberos { source: crate::kerberos::Error },
+}
+
+/// Reads files from HDFS, i.e. `from: hdfs://namenode:8020/warehouse/orders/`. Clusters secured with Kerberos are
+/// accessed as the `kerberos_principal` of the dataset.
+pub struct Hdfs {
+    params: Arc<HashMap<String, String>>,
+}
+
+impl std::fmt::Display for Hdfs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HDFS")
+    }
+}
+
+impl DataConnectorFactory for Hdfs {
+    fn create(
+        secret: Option<Secret>,
+        params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            // The HDFS client authenticates with GSSAPI from the process credential cache.
+            crate::kerberos::login(&params, &secret)
+                .await
+                .context(UnableToLogInWithKerberosSnafu)?;
+
+            Ok(Arc::new(Self { params }) as Arc<dyn DataConnector>)
+        })
+    }
+}
+
+impl ListingTableConnector for Hdfs {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    fn get_object_store_url(&self, dataset: &Dataset) -> DataConnectorResult<Url> {
+        let mut hdfs_url =
+            Url::parse(&dataset.from)
+                .boxed()
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: format!("{self}"),
+                    message: format!("{} is not a valid URL", dataset.from),
+                })?;
+
+        if dataset.from.ends_with('/') {
+            hdfs_url.set_fragment(Some("dfiscollectionbugworkaround=hack/"));
+        }
+
+        Ok(hdfs_url)
+    }
+}
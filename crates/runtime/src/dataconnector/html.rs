@@ -0,0 +1,167 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Plain text extraction from HTML documents, such as wiki pages and email bodies, for search and embeddings.
+
+/// Tags that start a new line of text.
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Tags whose contents aren't text.
+const SKIPPED_TAGS: &[&str] = &["head", "script", "style", "title"];
+
+/// Extracts the text of an HTML document, with a line per block element.
+#[must_use]
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut skipping: Option<String> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            if skipping.is_none() {
+                text.push_str(&cdata[..end]);
+            }
+            rest = cdata.get(end + 3..).unwrap_or_default();
+        } else if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map_or(comment.len(), |end| end + 3);
+            rest = &comment[end..];
+        } else if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let tag = &rest[1..end.saturating_sub(1).max(1)];
+            let closing = tag.starts_with('/');
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+
+            match &skipping {
+                Some(skipped) if closing && *skipped == name => skipping = None,
+                None if !closing && SKIPPED_TAGS.contains(&name.as_str()) => {
+                    skipping = Some(name);
+                }
+                None if BLOCK_TAGS.contains(&name.as_str()) => text.push('\n'),
+                None if name == "td" || name == "th" => text.push(' '),
+                _ => {}
+            }
+            rest = &rest[end..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            if skipping.is_none() {
+                text.push_str(&decode_entities(&rest[..end]));
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    normalize_whitespace(&text)
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .and_then(|end| decode_entity(&rest[1..=end]).map(|c| (c, end + 2)));
+        if let Some((c, len)) = entity {
+            decoded.push(c);
+            rest = &rest[len..];
+        } else {
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Collapses runs of spaces within lines and drops empty lines.
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = r#"<html><head><title>Ignored</title><style>p { color: red; }</style></head>
+            <body><h1>Release  notes</h1><p>Fixes &amp; improvements&#33;</p>
+            <ul><li>One</li><li><a href="/two">Two</a></li></ul>
+            <!-- a comment --><ac:plain-text-body><![CDATA[let x = 1 < 2;]]></ac:plain-text-body>
+            <table><tr><td>a</td><td>b</td></tr></table></body></html>"#;
+
+        assert_eq!(
+            html_to_text(html),
+            "Release notes\nFixes & improvements!\nOne\nTwo\nlet x = 1 < 2;\na b"
+        );
+    }
+}
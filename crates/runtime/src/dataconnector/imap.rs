@@ -0,0 +1,765 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Email in an IMAP mailbox as a dataset, with the headers, body text and attachment metadata of each message.
+//!
+//! `from: imap:<mailbox>`, such as `imap:INBOX`, with `imap_host`, `imap_port` (default 993), `imap_user` and the
+//! `imap_password` secret. Connections use TLS, verified with the CA certificates in `imap_ca_file` or the system's.
+//!
+//! Filters on `uid` and `received_at` are sent to the server as a UID search, so an append refresh with
+//! `time_column: received_at` only fetches the messages with UIDs the search returns. `uid_validity` changes when
+//! the server renumbers the mailbox, after which UIDs can't be compared with earlier ones.
+
+use std::{any::Any, collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use data_components::arrow::write::MemTable;
+use datafusion::{
+    common::ScalarValue,
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionState,
+    logical_expr::{BinaryExpr, Expr, Operator, TableProviderFilterPushDown},
+    physical_plan::ExecutionPlan,
+};
+use secrets::{get_secret_or_param, Secret};
+use serde_json::{json, Value};
+use snafu::prelude::*;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{self, pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+
+use super::incremental::{self, lower_bound};
+use super::{DataConnector, DataConnectorFactory};
+use crate::component::dataset::Dataset;
+
+mod mime;
+
+const DEFAULT_PORT: u16 = 993;
+
+/// The messages fetched with each `UID FETCH`.
+const FETCH_CHUNK_SIZE: usize = 100;
+
+/// Where common distributions keep their CA certificate bundle.
+const SYSTEM_CA_FILES: &[&str] = &[
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to connect to {host}: {source}"))]
+    UnableToConnect {
+        host: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Unable to read CA certificates from {path}: {source}"))]
+    UnableToReadCaFile {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display(
+        "No CA certificates found. Set imap_ca_file to a PEM file of CA certificates"
+    ))]
+    MissingCaCertificates,
+
+    #[snafu(display("Invalid CA certificate: {source}"))]
+    InvalidCaCertificate { source: rustls::Error },
+
+    #[snafu(display("Invalid IMAP host {host}"))]
+    InvalidHost { host: String },
+
+    #[snafu(display("IMAP {command} failed: {response}"))]
+    CommandFailed { command: String, response: String },
+
+    #[snafu(display("The IMAP server closed the connection"))]
+    ConnectionClosed,
+
+    #[snafu(display("Unable to communicate with the IMAP server: {source}"))]
+    UnableToCommunicate { source: std::io::Error },
+
+    #[snafu(display("Invalid IMAP response: {message}"))]
+    InvalidResponse { message: String },
+
+    #[snafu(display("{source}"))]
+    UnableToConvertMessages { source: incremental::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+struct ImapConfig {
+    host: String,
+    port: u16,
+    user: String,
+    password: String,
+    ca_file: Option<String>,
+}
+
+pub struct Imap {
+    config: Arc<ImapConfig>,
+}
+
+impl DataConnectorFactory for Imap {
+    fn create(
+        secret: Option<Secret>,
+        params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            let required = |name: &str| {
+                params
+                    .get(name)
+                    .cloned()
+                    .ok_or(format!("`{name}` not found in params").into())
+                    .context(super::InvalidConfigurationSnafu {
+                        dataconnector: "imap",
+                        message: format!("`{name}` not found in params"),
+                    })
+            };
+            let host = required("imap_host")?;
+            let user = required("imap_user")?;
+            let port =
+                match params.get("imap_port") {
+                    Some(port) => port.parse().map_err(Into::into).context(
+                        super::InvalidConfigurationSnafu {
+                            dataconnector: "imap",
+                            message: "Invalid imap_port",
+                        },
+                    )?,
+                    None => DEFAULT_PORT,
+                };
+            let password =
+                get_secret_or_param(&params, &secret, "imap_password_key", "imap_password")
+                    .ok_or("`imap_password` not found in secrets".into())
+                    .context(super::InvalidConfigurationSnafu {
+                        dataconnector: "imap",
+                        message: "Missing the imap_password secret",
+                    })?;
+
+            let config = ImapConfig {
+                host,
+                port,
+                user,
+                password,
+                ca_file: params.get("imap_ca_file").cloned(),
+            };
+            Ok(Arc::new(Self {
+                config: Arc::new(config),
+            }) as Arc<dyn DataConnector>)
+        })
+    }
+}
+
+#[async_trait]
+impl DataConnector for Imap {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        Ok(Arc::new(MailboxTable {
+            config: Arc::clone(&self.config),
+            mailbox: dataset.path(),
+        }))
+    }
+}
+
+fn tls_config(ca_file: Option<&str>) -> Result<ClientConfig> {
+    let path = match ca_file {
+        Some(path) => path,
+        None => SYSTEM_CA_FILES
+            .iter()
+            .copied()
+            .find(|path| std::path::Path::new(path).exists())
+            .context(MissingCaCertificatesSnafu)?,
+    };
+    let pem = std::fs::read(path).context(UnableToReadCaFileSnafu { path })?;
+
+    let mut roots = RootCertStore::empty();
+    for certificate in rustls_pemfile::certs(&mut pem.as_slice()) {
+        let certificate = certificate.context(UnableToReadCaFileSnafu { path })?;
+        roots.add(certificate).context(InvalidCaCertificateSnafu)?;
+    }
+    ensure!(!roots.is_empty(), MissingCaCertificatesSnafu);
+
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Quotes a string argument of a command.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The length of the literal that ends a response line, as in `BODY[] {1024}`.
+fn literal_len(line: &[u8]) -> Option<usize> {
+    let line = line
+        .strip_suffix(b"\r\n")
+        .or_else(|| line.strip_suffix(b"\n"))?;
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|b| *b == b'{')?;
+    std::str::from_utf8(&line[start + 1..]).ok()?.parse().ok()
+}
+
+/// An authenticated IMAP connection.
+struct Session {
+    stream: BufReader<TlsStream<TcpStream>>,
+    tag: usize,
+}
+
+impl Session {
+    async fn connect(config: &ImapConfig) -> Result<Self> {
+        let connector = TlsConnector::from(Arc::new(tls_config(config.ca_file.as_deref())?));
+        let server_name = ServerName::try_from(config.host.as_str())
+            .ok()
+            .map(|name| name.to_owned())
+            .context(InvalidHostSnafu {
+                host: config.host.clone(),
+            })?;
+        let tcp = TcpStream::connect((config.host.as_str(), config.port))
+            .await
+            .context(UnableToConnectSnafu {
+                host: config.host.clone(),
+            })?;
+        let tls = connector
+            .connect(server_name, tcp)
+            .await
+            .context(UnableToConnectSnafu {
+                host: config.host.clone(),
+            })?;
+
+        let mut session = Self {
+            stream: BufReader::new(tls),
+            tag: 0,
+        };
+        // The server greets before any command.
+        session.read_item().await?;
+        session
+            .command(&format!(
+                "LOGIN {} {}",
+                quote(&config.user),
+                quote(&config.password)
+            ))
+            .await?;
+        Ok(session)
+    }
+
+    /// Reads a response line, with any literals it ends with.
+    async fn read_item(&mut self) -> Result<Vec<u8>> {
+        let mut item = vec![];
+        loop {
+            let start = item.len();
+            let read = self
+                .stream
+                .read_until(b'\n', &mut item)
+                .await
+                .context(UnableToCommunicateSnafu)?;
+            ensure!(read > 0, ConnectionClosedSnafu);
+
+            let Some(len) = literal_len(&item[start..]) else {
+                return Ok(item);
+            };
+            let literal_start = item.len();
+            item.resize(literal_start + len, 0);
+            self.stream
+                .read_exact(&mut item[literal_start..])
+                .await
+                .context(UnableToCommunicateSnafu)?;
+        }
+    }
+
+    /// Runs a command, returning its untagged responses.
+    async fn command(&mut self, command: &str) -> Result<Vec<Vec<u8>>> {
+        self.tag += 1;
+        let tag = format!("a{} ", self.tag);
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{tag}{command}\r\n").as_bytes())
+            .await
+            .context(UnableToCommunicateSnafu)?;
+        stream.flush().await.context(UnableToCommunicateSnafu)?;
+
+        let mut untagged = vec![];
+        loop {
+            let item = self.read_item().await?;
+            let Some(status) = item.strip_prefix(tag.as_bytes()) else {
+                untagged.push(item);
+                continue;
+            };
+            ensure!(
+                status.starts_with(b"OK"),
+                CommandFailedSnafu {
+                    // Arguments such as the password of LOGIN aren't logged.
+                    command: command.split(' ').next().unwrap_or_default(),
+                    response: String::from_utf8_lossy(status).trim().to_string(),
+                }
+            );
+            return Ok(untagged);
+        }
+    }
+
+    /// Selects a mailbox, returning its UIDVALIDITY.
+    async fn select(&mut self, mailbox: &str) -> Result<Option<u64>> {
+        let responses = self.command(&format!("EXAMINE {}", quote(mailbox))).await?;
+        Ok(responses.iter().find_map(|response| {
+            let response = String::from_utf8_lossy(response);
+            let start = response.find("[UIDVALIDITY ")? + "[UIDVALIDITY ".len();
+            let end = response[start..].find(']')? + start;
+            response[start..end].trim().parse().ok()
+        }))
+    }
+
+    /// The UIDs of the messages matching a search, in ascending order.
+    async fn search(&mut self, criteria: &str) -> Result<Vec<u32>> {
+        let responses = self.command(&format!("UID SEARCH {criteria}")).await?;
+        let mut uids = responses
+            .iter()
+            .filter_map(|response| response.strip_prefix(b"* SEARCH"))
+            .flat_map(|uids| {
+                String::from_utf8_lossy(uids)
+                    .split_whitespace()
+                    .filter_map(|uid| uid.parse().ok())
+                    .collect::<Vec<u32>>()
+            })
+            .collect::<Vec<_>>();
+        uids.sort_unstable();
+        Ok(uids)
+    }
+
+    async fn fetch(&mut self, uids: &[u32]) -> Result<Vec<FetchedMessage>> {
+        let set = uids
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let responses = self
+            .command(&format!(
+                "UID FETCH {set} (UID INTERNALDATE RFC822.SIZE BODY.PEEK[])"
+            ))
+            .await?;
+        responses
+            .iter()
+            .filter(|response| response.starts_with(b"* ") && find(response, b" FETCH (").is_some())
+            .map(|response| FetchedMessage::parse(response))
+            .collect()
+    }
+
+    async fn logout(mut self) {
+        if let Err(e) = self.command("LOGOUT").await {
+            tracing::debug!("Unable to log out of the IMAP server: {e}");
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// A value of a FETCH response.
+#[derive(Debug, PartialEq)]
+enum Item {
+    Atom(String),
+    String(Vec<u8>),
+    List(Vec<Item>),
+}
+
+impl Item {
+    fn text(&self) -> Option<String> {
+        match self {
+            Item::Atom(atom) if atom.eq_ignore_ascii_case("NIL") => None,
+            Item::Atom(atom) => Some(atom.clone()),
+            Item::String(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            Item::List(_) => None,
+        }
+    }
+}
+
+/// Parses the items of a response, such as the parenthesized list of a FETCH.
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn invalid(&self, message: &str) -> Error {
+        Error::InvalidResponse {
+            message: format!("{message} at byte {}", self.position),
+        }
+    }
+
+    fn parse(&mut self) -> Result<Item> {
+        while self.peek() == Some(b' ') {
+            self.position += 1;
+        }
+        match self.peek() {
+            Some(b'(') => {
+                self.position += 1;
+                let mut items = vec![];
+                loop {
+                    while self.peek() == Some(b' ') {
+                        self.position += 1;
+                    }
+                    match self.peek() {
+                        Some(b')') => {
+                            self.position += 1;
+                            return Ok(Item::List(items));
+                        }
+                        Some(_) => items.push(self.parse()?),
+                        None => return Err(self.invalid("Unterminated list")),
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.position += 1;
+                let mut value = vec![];
+                loop {
+                    match self.peek() {
+                        Some(b'\\') => {
+                            value.extend(self.input.get(self.position + 1));
+                            self.position += 2;
+                        }
+                        Some(b'"') => {
+                            self.position += 1;
+                            return Ok(Item::String(value));
+                        }
+                        Some(b) => {
+                            value.push(b);
+                            self.position += 1;
+                        }
+                        None => return Err(self.invalid("Unterminated string")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                let rest = &self.input[self.position..];
+                let header_end =
+                    find(rest, b"}\r\n").ok_or_else(|| self.invalid("Invalid literal"))?;
+                let len: usize = std::str::from_utf8(&rest[1..header_end])
+                    .ok()
+                    .and_then(|len| len.parse().ok())
+                    .ok_or_else(|| self.invalid("Invalid literal length"))?;
+                let start = self.position + header_end + 3;
+                let value = self
+                    .input
+                    .get(start..start + len)
+                    .ok_or_else(|| self.invalid("Truncated literal"))?;
+                self.position = start + len;
+                Ok(Item::String(value.to_vec()))
+            }
+            Some(_) => {
+                // Atoms such as BODY[] can contain brackets.
+                let start = self.position;
+                let mut depth = 0;
+                while let Some(b) = self.peek() {
+                    match b {
+                        b'[' => depth += 1,
+                        b']' => depth -= 1,
+                        b' ' | b'(' | b')' | b'\r' | b'\n' if depth == 0 => break,
+                        _ => {}
+                    }
+                    self.position += 1;
+                }
+                Ok(Item::Atom(
+                    String::from_utf8_lossy(&self.input[start..self.position]).into_owned(),
+                ))
+            }
+            None => Err(self.invalid("Unexpected end of response")),
+        }
+    }
+}
+
+struct FetchedMessage {
+    uid: u32,
+    received_at: Option<String>,
+    size: Option<u64>,
+    body: Vec<u8>,
+}
+
+impl FetchedMessage {
+    /// Parses an untagged response such as `* 12 FETCH (UID 5 INTERNALDATE "..." BODY[] {42}...)`.
+    fn parse(response: &[u8]) -> Result<Self> {
+        let start = find(response, b" FETCH (").map_or(0, |start| start + " FETCH ".len());
+        let mut parser = Parser {
+            input: response,
+            position: start,
+        };
+        let Item::List(items) = parser.parse()? else {
+            return InvalidResponseSnafu {
+                message: "FETCH without a list",
+            }
+            .fail();
+        };
+
+        let mut message = Self {
+            uid: 0,
+            received_at: None,
+            size: None,
+            body: vec![],
+        };
+        for pair in items.chunks(2) {
+            let [Item::Atom(name), value] = pair else {
+                continue;
+            };
+            match (name.to_ascii_uppercase().as_str(), value) {
+                ("UID", value) => {
+                    message.uid = value.text().and_then(|uid| uid.parse().ok()).unwrap_or(0);
+                }
+                ("INTERNALDATE", value) => {
+                    message.received_at = value.text().and_then(|date| {
+                        DateTime::parse_from_str(date.trim(), "%d-%b-%Y %H:%M:%S %z")
+                            .ok()
+                            .map(|date| date.to_rfc3339())
+                    });
+                }
+                ("RFC822.SIZE", value) => {
+                    message.size = value.text().and_then(|size| size.parse().ok());
+                }
+                ("BODY[]", Item::String(body)) => message.body.clone_from(body),
+                _ => {}
+            }
+        }
+        ensure!(
+            message.uid > 0,
+            InvalidResponseSnafu {
+                message: "FETCH without a UID",
+            }
+        );
+        Ok(message)
+    }
+
+    fn to_record(&self, uid_validity: Option<u64>) -> Value {
+        let parsed = mime::parse(&self.body);
+        json!({
+            "uid": self.uid,
+            "uid_validity": uid_validity,
+            "message_id": parsed.id,
+            "subject": parsed.subject,
+            "from": parsed.from,
+            "to": parsed.to,
+            "cc": parsed.cc,
+            "date": parsed.date,
+            "received_at": self.received_at,
+            "size": self.size,
+            "body": parsed.body,
+            "attachments": parsed.attachments.iter().map(|attachment| json!({
+                "filename": attachment.filename,
+                "content_type": attachment.content_type,
+                "size": attachment.size,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// The UID `filter` requires messages to be at or after, for filters such as `uid > 10`.
+fn uid_lower_bound(filter: &Expr) -> Option<u32> {
+    let Expr::BinaryExpr(BinaryExpr { left, op, right }) = filter else {
+        return None;
+    };
+    let (Expr::Column(column), Expr::Literal(value)) = (left.as_ref(), right.as_ref()) else {
+        return None;
+    };
+    if column.name != "uid" {
+        return None;
+    }
+    let ScalarValue::Int64(Some(uid)) = value.cast_to(&DataType::Int64).ok()? else {
+        return None;
+    };
+    let uid = match op {
+        Operator::Gt => uid.saturating_add(1),
+        Operator::GtEq => uid,
+        _ => return None,
+    };
+    u32::try_from(uid.max(1)).ok()
+}
+
+struct MailboxTable {
+    config: Arc<ImapConfig>,
+    mailbox: String,
+}
+
+impl MailboxTable {
+    async fn fetch(
+        &self,
+        min_uid: Option<u32>,
+        since: Option<DateTime<Utc>>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Value>> {
+        let mut session = Session::connect(&self.config).await?;
+        let uid_validity = session.select(&self.mailbox).await?;
+
+        let mut criteria = vec![];
+        if let Some(min_uid) = min_uid {
+            criteria.push(format!("UID {min_uid}:*"));
+        }
+        // SINCE compares dates in the server's time zone, so the day before covers any offset.
+        if let Some(since) = since {
+            let since = since - chrono::Duration::days(1);
+            criteria.push(format!("SINCE {}", since.format("%d-%b-%Y")));
+        }
+        if criteria.is_empty() {
+            criteria.push("ALL".to_string());
+        }
+
+        let mut uids = session.search(&criteria.join(" ")).await?;
+        // `n:*` matches the last message even when its UID is below n.
+        uids.retain(|uid| min_uid.map_or(true, |min_uid| *uid >= min_uid));
+        if let Some(limit) = limit {
+            uids.truncate(limit);
+        }
+
+        let mut records = Vec::with_capacity(uids.len());
+        for chunk in uids.chunks(FETCH_CHUNK_SIZE) {
+            for message in session.fetch(chunk).await? {
+                records.push(message.to_record(uid_validity));
+            }
+        }
+        session.logout().await;
+        Ok(records)
+    }
+}
+
+#[async_trait]
+impl TableProvider for MailboxTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        let timestamp = DataType::Timestamp(TimeUnit::Millisecond, None);
+        let attachment = Fields::from(vec![
+            Field::new("filename", DataType::Utf8, true),
+            Field::new("content_type", DataType::Utf8, true),
+            Field::new("size", DataType::Int64, true),
+        ]);
+        Arc::new(Schema::new(vec![
+            Field::new("uid", DataType::Int64, false),
+            Field::new("uid_validity", DataType::Int64, true),
+            Field::new("message_id", DataType::Utf8, true),
+            Field::new("subject", DataType::Utf8, true),
+            Field::new("from", DataType::Utf8, true),
+            Field::new("to", DataType::Utf8, true),
+            Field::new("cc", DataType::Utf8, true),
+            Field::new("date", timestamp.clone(), true),
+            Field::new("received_at", timestamp, true),
+            Field::new("size", DataType::Int64, true),
+            Field::new("body", DataType::Utf8, true),
+            Field::new(
+                "attachments",
+                DataType::List(Arc::new(Field::new(
+                    "item",
+                    DataType::Struct(attachment),
+                    true,
+                ))),
+                true,
+            ),
+        ]))
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| {
+                if uid_lower_bound(filter).is_some() || lower_bound(filter, "received_at").is_some()
+                {
+                    TableProviderFilterPushDown::Inexact
+                } else {
+                    TableProviderFilterPushDown::Unsupported
+                }
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let min_uid = filters.iter().filter_map(uid_lower_bound).max();
+        let since = filters
+            .iter()
+            .filter_map(|filter| lower_bound(filter, "received_at"))
+            .max();
+
+        let records = self
+            .fetch(min_uid, since, limit)
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let batches = incremental::to_record_batches(self.schema(), &records)
+            .context(UnableToConvertMessagesSnafu)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let table = MemTable::try_new(self.schema(), vec![batches])?;
+        table.scan(state, projection, filters, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fetch_response() {
+        let body = "Subject: Hello\r\n\r\nHi there\r\n";
+        let response = format!(
+            "* 3 FETCH (UID 42 INTERNALDATE \" 2-Jul-2024 10:00:00 +0200\" RFC822.SIZE {} BODY[] {{{}}}\r\n{body})\r\n",
+            body.len(),
+            body.len()
+        );
+        assert_eq!(literal_len(b"* 3 FETCH (BODY[] {27}\r\n"), Some(27));
+
+        let message = FetchedMessage::parse(response.as_bytes()).expect("parsed");
+        assert_eq!(message.uid, 42);
+        assert_eq!(
+            message.received_at.as_deref(),
+            Some("2024-07-02T10:00:00+02:00")
+        );
+        assert_eq!(message.body, body.as_bytes());
+
+        let record = message.to_record(Some(7));
+        assert_eq!(record["subject"], "Hello");
+        assert_eq!(record["body"], "Hi there\r\n");
+    }
+}
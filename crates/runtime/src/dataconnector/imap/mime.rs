@@ -0,0 +1,398 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Parses the headers, text and attachments of MIME email messages.
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::dataconnector::html::html_to_text;
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Message {
+    /// The `Message-ID` header.
+    pub id: Option<String>,
+    pub subject: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub cc: Option<String>,
+    /// The `Date` header, in RFC 3339.
+    pub date: Option<String>,
+    /// The plain text body, or the text of the HTML body.
+    pub body: Option<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Attachment {
+    pub filename: Option<String>,
+    pub content_type: String,
+    /// The decoded size in bytes.
+    pub size: usize,
+}
+
+#[derive(Default)]
+struct Bodies {
+    plain: Option<String>,
+    html: Option<String>,
+}
+
+/// Parses a message, as fetched with `BODY[]`.
+pub(crate) fn parse(raw: &[u8]) -> Message {
+    let (header, body) = split_header(raw);
+    let headers = parse_headers(header);
+    let get = |name: &str| header_value(&headers, name).map(decode_words);
+
+    let mut message = Message {
+        id: get("message-id"),
+        subject: get("subject"),
+        from: get("from"),
+        to: get("to"),
+        cc: get("cc"),
+        date: header_value(&headers, "date").and_then(|date| {
+            chrono::DateTime::parse_from_rfc2822(date.trim())
+                .ok()
+                .map(|date| date.to_rfc3339())
+        }),
+        ..Message::default()
+    };
+
+    let mut bodies = Bodies::default();
+    walk(&headers, body, &mut message.attachments, &mut bodies);
+    message.body = bodies
+        .plain
+        .or_else(|| bodies.html.as_deref().map(html_to_text));
+    message
+}
+
+fn walk(
+    headers: &[(String, String)],
+    body: &[u8],
+    attachments: &mut Vec<Attachment>,
+    bodies: &mut Bodies,
+) {
+    let (content_type, params) = header_value(headers, "content-type")
+        .map_or_else(|| ("text/plain".to_string(), HashMap::new()), parse_params);
+
+    if content_type.starts_with("multipart/") {
+        if let Some(boundary) = params.get("boundary") {
+            for part in split_multipart(body, boundary) {
+                let (header, body) = split_header(part);
+                walk(&parse_headers(header), body, attachments, bodies);
+            }
+        }
+        return;
+    }
+
+    let (disposition, disposition_params) =
+        header_value(headers, "content-disposition").map_or_else(Default::default, parse_params);
+    let filename = disposition_params
+        .get("filename")
+        .or_else(|| params.get("name"))
+        .map(|filename| decode_words(filename));
+    let decoded = decode_transfer(
+        body,
+        header_value(headers, "content-transfer-encoding").unwrap_or_default(),
+    );
+
+    let is_text = content_type == "text/plain" || content_type == "text/html";
+    if disposition == "attachment" || filename.is_some() || !is_text {
+        attachments.push(Attachment {
+            filename,
+            content_type,
+            size: decoded.len(),
+        });
+        return;
+    }
+
+    let text = decode_charset(
+        &decoded,
+        params.get("charset").map_or("utf-8", String::as_str),
+    );
+    let slot = if content_type == "text/plain" {
+        &mut bodies.plain
+    } else {
+        &mut bodies.html
+    };
+    slot.get_or_insert(text);
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Splits a message or part at the empty line ending its headers.
+fn split_header(raw: &[u8]) -> (&[u8], &[u8]) {
+    match (find(raw, b"\r\n\r\n"), find(raw, b"\n\n")) {
+        (Some(crlf), Some(lf)) if lf < crlf => (&raw[..lf], &raw[lf + 2..]),
+        (Some(crlf), _) => (&raw[..crlf], &raw[crlf + 4..]),
+        (None, Some(lf)) => (&raw[..lf], &raw[lf + 2..]),
+        (None, None) => (raw, &[]),
+    }
+}
+
+/// The headers of a message, with lowercase names and folded lines joined.
+fn parse_headers(header: &[u8]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in String::from_utf8_lossy(header).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Splits a header such as `text/plain; charset="utf-8"` into its lowercase value and parameters.
+fn parse_params(value: &str) -> (String, HashMap<String, String>) {
+    let mut parts = value.split(';');
+    let value = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((
+                name.trim().to_ascii_lowercase(),
+                value.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    (value, params)
+}
+
+/// The parts of a multipart body, between its `--boundary` lines.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = vec![];
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+
+    while offset < body.len() {
+        let end = body[offset..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(body.len(), |i| offset + i + 1);
+        let mut line = &body[offset..end];
+        while let [rest @ .., b'\r' | b'\n' | b' ' | b'\t'] = line {
+            line = rest;
+        }
+
+        if line.starts_with(delimiter.as_bytes()) {
+            if let Some(start) = start {
+                // The line break before a delimiter belongs to the delimiter.
+                let mut part_end = offset;
+                if body[..part_end].ends_with(b"\r\n") {
+                    part_end -= 2;
+                } else if body[..part_end].ends_with(b"\n") {
+                    part_end -= 1;
+                }
+                parts.push(&body[start..part_end.max(start)]);
+            }
+            if line[delimiter.len()..].starts_with(b"--") {
+                return parts;
+            }
+            start = Some(end);
+        }
+        offset = end;
+    }
+    parts
+}
+
+fn decode_transfer(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "base64" => {
+            let encoded: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            STANDARD.decode(&encoded).unwrap_or_else(|_| body.to_vec())
+        }
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    char::from(b)
+        .to_digit(16)
+        .and_then(|digit| u8::try_from(digit).ok())
+}
+
+/// Decodes quoted-printable text, or the Q encoding of encoded words when `q` is set.
+fn decode_quoted_printable(encoded: &[u8], q: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        match encoded[i] {
+            b'=' if encoded[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if encoded[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' => {
+                if let (Some(high), Some(low)) = (
+                    encoded.get(i + 1).copied().and_then(hex_value),
+                    encoded.get(i + 2).copied().and_then(hex_value),
+                ) {
+                    decoded.push(high << 4 | low);
+                    i += 3;
+                } else {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            }
+            b'_' if q => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_ascii_lowercase().as_str() {
+        // Windows-1252 only differs from Latin-1 in characters that are rare in mail.
+        "iso-8859-1" | "latin1" | "windows-1252" => bytes.iter().map(|b| char::from(*b)).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decodes one `=?charset?encoding?text?=` encoded word.
+fn decode_word(word: &str) -> Option<String> {
+    let mut parts = word.strip_prefix("=?")?.strip_suffix("?=")?.splitn(3, '?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let text = parts.next()?;
+    let bytes = match encoding {
+        "B" | "b" => STANDARD.decode(text).ok()?,
+        "Q" | "q" => decode_quoted_printable(text.as_bytes(), true),
+        _ => return None,
+    };
+    // Charsets can carry a language, as in `utf-8*en`.
+    Some(decode_charset(
+        &bytes,
+        charset.split('*').next().unwrap_or_default(),
+    ))
+}
+
+/// Decodes the encoded words of a header value.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word_end = rest[start + 2..]
+            .match_indices("?=")
+            .map(|(i, _)| start + 2 + i + 2)
+            // The first `?=` can close the charset or encoding; the word ends after its three fields.
+            .find(|end| rest[start..*end].matches('?').count() >= 4);
+        let Some(end) = word_end else {
+            break;
+        };
+        let Some(word) = decode_word(&rest[start..end]) else {
+            decoded.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+
+        // Whitespace between adjacent encoded words isn't part of the text.
+        let between = &rest[..start];
+        if !(after_word && between.trim().is_empty()) {
+            decoded.push_str(between);
+        }
+        decoded.push_str(&word);
+        rest = &rest[end..];
+        after_word = true;
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multipart_message() {
+        let raw = concat!(
+            "Message-ID: <1@example.com>\r\n",
+            "From: =?utf-8?B?SsO8cmdlbg==?= <j@example.com>\r\n",
+            "To: support@example.com\r\n",
+            "Subject: =?iso-8859-1?Q?Caf=E9?=\r\n",
+            "  =?utf-8?Q?_order?=\r\n",
+            "Date: Tue, 2 Jul 2024 10:00:00 +0200\r\n",
+            "Content-Type: multipart/mixed; boundary=\"outer\"\r\n",
+            "\r\n",
+            "preamble\r\n",
+            "--outer\r\n",
+            "Content-Type: multipart/alternative; boundary=inner\r\n",
+            "\r\n",
+            "--inner\r\n",
+            "Content-Type: text/html; charset=utf-8\r\n",
+            "\r\n",
+            "<p>Hello</p>\r\n",
+            "--inner\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "Content-Transfer-Encoding: quoted-printable\r\n",
+            "\r\n",
+            "Hello, the order =\r\nis late =E2=80=94 sorry.\r\n",
+            "--inner--\r\n",
+            "--outer\r\n",
+            "Content-Type: application/pdf; name=\"invoice.pdf\"\r\n",
+            "Content-Disposition: attachment; filename=\"invoice.pdf\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "JVBERi0x\r\n",
+            "LjQ=\r\n",
+            "--outer--\r\n",
+        );
+
+        let message = parse(raw.as_bytes());
+        assert_eq!(message.id.as_deref(), Some("<1@example.com>"));
+        assert_eq!(message.from.as_deref(), Some("Jürgen <j@example.com>"));
+        assert_eq!(message.subject.as_deref(), Some("Café order"));
+        assert_eq!(message.date.as_deref(), Some("2024-07-02T10:00:00+02:00"));
+        assert_eq!(
+            message.body.as_deref(),
+            Some("Hello, the order is late \u{2014} sorry.")
+        );
+        assert_eq!(
+            message.attachments,
+            vec![Attachment {
+                filename: Some("invoice.pdf".to_string()),
+                content_type: "application/pdf".to_string(),
+                size: 8,
+            }]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Tables over REST APIs that can list records updated since a time.
+//!
+//! An append refresh filters on the dataset's `time_column`; when that is the source's update time column, the
+//! lower bound of the filter is sent to the API so only records updated since the last refresh are fetched.
+
+use std::{any::Any, sync::Arc, time::Duration};
+
+use arrow::{array::RecordBatch, datatypes::SchemaRef, error::ArrowError};
+use arrow_json::ReaderBuilder;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use data_components::arrow::write::MemTable;
+use datafusion::{
+    common::ScalarValue,
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionState,
+    logical_expr::{BinaryExpr, Expr, Operator, TableProviderFilterPushDown},
+    physical_plan::ExecutionPlan,
+};
+use reqwest::{header::HeaderMap, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use snafu::prelude::*;
+
+/// Attempts at a request that is rate limited before giving up.
+const MAX_ATTEMPTS: usize = 5;
+
+/// The longest a rate limited request waits before it is retried.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    #[snafu(display("Unable to send request: {source}"))]
+    UnableToSendRequest { source: reqwest::Error },
+
+    #[snafu(display("HTTP {status}: {message}"))]
+    InvalidResponseStatus { status: StatusCode, message: String },
+
+    #[snafu(display("Unable to read response: {source}"))]
+    UnableToReadResponse { source: reqwest::Error },
+
+    #[snafu(display("Unexpected response: {message}"))]
+    UnexpectedResponse { message: String },
+
+    #[snafu(display("Unable to convert records to Arrow: {source}"))]
+    UnableToConvertRecords { source: ArrowError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A REST API listing records with the time they were last updated.
+#[async_trait]
+pub(crate) trait IncrementalSource: Send + Sync + 'static {
+    fn schema(&self) -> SchemaRef;
+
+    /// The column with the time each record was last updated, if records have one.
+    fn updated_column(&self) -> Option<&'static str>;
+
+    /// Lists the records updated since `updated_since`, or all records, as JSON objects matching the schema.
+    async fn fetch(&self, updated_since: Option<DateTime<Utc>>) -> Result<Vec<Value>>;
+}
+
+pub(crate) struct IncrementalTable<S> {
+    source: S,
+}
+
+impl<S: IncrementalSource> IncrementalTable<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self { source }
+    }
+
+    fn updated_since(&self, filter: &Expr) -> Option<DateTime<Utc>> {
+        lower_bound(filter, self.source.updated_column()?)
+    }
+}
+
+#[async_trait]
+impl<S: IncrementalSource> TableProvider for IncrementalTable<S> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.source.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        // APIs compare update times at their own granularity, so the filters are still applied to the records.
+        Ok(filters
+            .iter()
+            .map(|filter| match self.updated_since(filter) {
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let updated_since = filters
+            .iter()
+            .filter_map(|filter| self.updated_since(filter))
+            .max();
+
+        let records = self
+            .source
+            .fetch(updated_since)
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let batches = to_record_batches(self.source.schema(), &records)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let table = MemTable::try_new(self.source.schema(), vec![batches])?;
+        table.scan(state, projection, filters, limit).await
+    }
+}
+
+/// The time `filter` requires `column` to be after, for filters such as `column > timestamp`.
+pub(crate) fn lower_bound(filter: &Expr, column: &str) -> Option<DateTime<Utc>> {
+    let Expr::BinaryExpr(BinaryExpr { left, op, right }) = filter else {
+        return None;
+    };
+    if !matches!(op, Operator::Gt | Operator::GtEq) {
+        return None;
+    }
+
+    let left = match left.as_ref() {
+        Expr::Cast(cast) => cast.expr.as_ref(),
+        Expr::TryCast(cast) => cast.expr.as_ref(),
+        left => left,
+    };
+    match (left, right.as_ref()) {
+        (Expr::Column(col), Expr::Literal(value)) if col.name == column => to_datetime(value),
+        _ => None,
+    }
+}
+
+fn to_datetime(value: &ScalarValue) -> Option<DateTime<Utc>> {
+    match value {
+        ScalarValue::TimestampSecond(Some(secs), _) => DateTime::from_timestamp(*secs, 0),
+        ScalarValue::TimestampMillisecond(Some(millis), _) => {
+            DateTime::from_timestamp_millis(*millis)
+        }
+        ScalarValue::TimestampMicrosecond(Some(micros), _) => {
+            DateTime::from_timestamp_micros(*micros)
+        }
+        ScalarValue::TimestampNanosecond(Some(nanos), _) => {
+            Some(DateTime::from_timestamp_nanos(*nanos))
+        }
+        ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|time| time.with_timezone(&Utc))
+        }
+        _ => None,
+    }
+}
+
+/// Converts JSON records to record batches of `schema`.
+pub(crate) fn to_record_batches(schema: SchemaRef, records: &[Value]) -> Result<Vec<RecordBatch>> {
+    let mut decoder = ReaderBuilder::new(schema)
+        .build_decoder()
+        .context(UnableToConvertRecordsSnafu)?;
+
+    let mut batches = vec![];
+    for chunk in records.chunks(1024) {
+        decoder
+            .serialize(chunk)
+            .context(UnableToConvertRecordsSnafu)?;
+        if let Some(batch) = decoder.flush().context(UnableToConvertRecordsSnafu)? {
+            batches.push(batch);
+        }
+    }
+    Ok(batches)
+}
+
+/// How long a rate limited response asks to wait, from `Retry-After` or `X-RateLimit-Reset`.
+fn retry_wait(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let exhausted = header("x-ratelimit-remaining") == Some("0");
+    if status != StatusCode::TOO_MANY_REQUESTS && !(status == StatusCode::FORBIDDEN && exhausted) {
+        return None;
+    }
+
+    let wait = if let Some(seconds) = header("retry-after").and_then(|s| s.parse::<u64>().ok()) {
+        Duration::from_secs(seconds)
+    } else if let Some(reset) = header("x-ratelimit-reset").and_then(|s| s.parse::<i64>().ok()) {
+        let seconds = reset.saturating_sub(Utc::now().timestamp()).max(1);
+        Duration::from_secs(seconds.unsigned_abs())
+    } else {
+        Duration::from_secs(1)
+    };
+    Some(wait.min(MAX_RETRY_WAIT))
+}
+
+/// Sends a request, waiting out rate limits, and fails on unsuccessful responses.
+pub(crate) async fn send(mut request: RequestBuilder) -> Result<Response> {
+    let mut attempt = 1;
+    loop {
+        let retry = request.try_clone();
+        let response = request.send().await.context(UnableToSendRequestSnafu)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        match (retry_wait(status, response.headers()), retry) {
+            (Some(wait), Some(retry)) if attempt < MAX_ATTEMPTS => {
+                tracing::debug!("Rate limited with HTTP {status}, retrying in {wait:?}");
+                tokio::time::sleep(wait).await;
+                request = retry;
+                attempt += 1;
+            }
+            _ => {
+                let message = response.text().await.unwrap_or_default();
+                return InvalidResponseStatusSnafu { status, message }.fail();
+            }
+        }
+    }
+}
+
+/// Sends a request and reads the JSON response.
+pub(crate) async fn send_json(request: RequestBuilder) -> Result<Value> {
+    send(request)
+        .await?
+        .json()
+        .await
+        .context(UnableToReadResponseSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::logical_expr::{cast, col, lit};
+
+    use super::*;
+
+    #[test]
+    fn test_lower_bound() {
+        let timestamp = Expr::Literal(ScalarValue::TimestampMillisecond(
+            Some(1_720_000_000_000),
+            None,
+        ));
+        let expected = DateTime::from_timestamp_millis(1_720_000_000_000);
+
+        let filter = col("updated_at").gt(timestamp.clone());
+        assert_eq!(lower_bound(&filter, "updated_at"), expected);
+
+        let filter = cast(
+            col("updated_at"),
+            arrow::datatypes::DataType::Timestamp(arrow::datatypes::TimeUnit::Millisecond, None),
+        )
+        .gt_eq(timestamp.clone());
+        assert_eq!(lower_bound(&filter, "updated_at"), expected);
+
+        assert_eq!(
+            lower_bound(&col("updated_at").lt(timestamp), "updated_at"),
+            None
+        );
+        assert_eq!(lower_bound(&col("id").gt(lit(1)), "updated_at"), None);
+    }
+}
@@ -134,7 +134,7 @@ impl DataConnector for LocalhostConnector {
     }
 }
 
-struct LocalhostContextProvider {
+pub(crate) struct LocalhostContextProvider {
     options: ConfigOptions,
 }
 
@@ -0,0 +1,360 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Notion pages as a dataset, with the text of each page in `content`.
+//!
+//! `from: notion:pages` lists the pages shared with the integration, and `from: notion:<database_id>` the pages of a
+//! database. The integration token is read from the `notion_token` secret.
+
+use std::{any::Any, collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::datasource::TableProvider;
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use secrets::{get_secret_or_param, Secret};
+use serde_json::{json, Value};
+use snafu::prelude::*;
+
+use super::incremental::{self, IncrementalSource, IncrementalTable};
+use super::{DataConnector, DataConnectorFactory};
+use crate::component::dataset::Dataset;
+
+const API_URL: &str = "https://api.notion.com/v1";
+const API_VERSION: &str = "2022-06-28";
+const PAGE_SIZE: usize = 100;
+
+pub struct Notion {
+    client: reqwest::Client,
+}
+
+impl DataConnectorFactory for Notion {
+    fn create(
+        secret: Option<Secret>,
+        params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            let token = get_secret_or_param(&params, &secret, "notion_token_key", "notion_token")
+                .ok_or("`notion_token` not found in secrets".into())
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "notion",
+                    message: "Missing the notion_token secret",
+                })?;
+
+            let mut headers = HeaderMap::new();
+            headers.append(USER_AGENT, HeaderValue::from_static("spice"));
+            headers.append("Notion-Version", HeaderValue::from_static(API_VERSION));
+            let mut authorization = HeaderValue::from_str(&format!("Bearer {token}"))
+                .map_err(Into::into)
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "notion",
+                    message: "Invalid notion_token",
+                })?;
+            authorization.set_sensitive(true);
+            headers.append(reqwest::header::AUTHORIZATION, authorization);
+
+            let client = reqwest::Client::builder()
+                .default_headers(headers)
+                .build()
+                .map_err(Into::into)
+                .context(super::InvalidConfigurationSnafu {
+                    dataconnector: "notion",
+                    message: "Unable to create the HTTP client",
+                })?;
+
+            Ok(Arc::new(Self { client }) as Arc<dyn DataConnector>)
+        })
+    }
+}
+
+#[async_trait]
+impl DataConnector for Notion {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let path = dataset.path();
+        let database_id = (path != "pages").then(|| path.replace('-', ""));
+        Ok(Arc::new(IncrementalTable::new(NotionPages {
+            client: self.client.clone(),
+            database_id,
+        })))
+    }
+}
+
+struct NotionPages {
+    client: reqwest::Client,
+    /// The database to list the pages of, or none for every page shared with the integration.
+    database_id: Option<String>,
+}
+
+impl NotionPages {
+    /// Lists pages edited since `updated_since`, most recently edited first.
+    async fn list_pages(
+        &self,
+        updated_since: Option<DateTime<Utc>>,
+    ) -> incremental::Result<Vec<Value>> {
+        let mut pages = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let (url, mut body) = match &self.database_id {
+                Some(database_id) => {
+                    let mut body = json!({
+                        "page_size": PAGE_SIZE,
+                        "sorts": [{ "timestamp": "last_edited_time", "direction": "descending" }],
+                    });
+                    if let Some(since) = updated_since {
+                        body["filter"] = json!({
+                            "timestamp": "last_edited_time",
+                            "last_edited_time": { "on_or_after": since.to_rfc3339() },
+                        });
+                    }
+                    (format!("{API_URL}/databases/{database_id}/query"), body)
+                }
+                None => (
+                    format!("{API_URL}/search"),
+                    json!({
+                        "page_size": PAGE_SIZE,
+                        "filter": { "property": "object", "value": "page" },
+                        "sort": { "timestamp": "last_edited_time", "direction": "descending" },
+                    }),
+                ),
+            };
+            if let Some(cursor) = &cursor {
+                body["start_cursor"] = json!(cursor);
+            }
+            let request = self.client.post(url).json(&body);
+
+            let response = incremental::send_json(request).await?;
+            let results = response["results"].as_array().cloned().unwrap_or_default();
+
+            // Search can't filter on edit times, but its results are sorted by them.
+            let mut reached_since = false;
+            for page in results {
+                let edited = page["last_edited_time"]
+                    .as_str()
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+                if let (Some(since), Some(edited)) = (updated_since, edited) {
+                    if edited < since {
+                        reached_since = true;
+                        break;
+                    }
+                }
+                pages.push(page);
+            }
+
+            cursor = response["next_cursor"].as_str().map(ToString::to_string);
+            if reached_since || !response["has_more"].as_bool().unwrap_or(false) || cursor.is_none()
+            {
+                return Ok(pages);
+            }
+        }
+    }
+
+    /// The text of a block and its children, a line per block.
+    fn block_text<'a>(
+        &'a self,
+        block_id: &'a str,
+    ) -> BoxFuture<'a, incremental::Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut lines = vec![];
+            let mut cursor: Option<String> = None;
+            loop {
+                let mut request = self
+                    .client
+                    .get(format!("{API_URL}/blocks/{block_id}/children"))
+                    .query(&[("page_size", PAGE_SIZE.to_string())]);
+                if let Some(cursor) = &cursor {
+                    request = request.query(&[("start_cursor", cursor)]);
+                }
+                let response = incremental::send_json(request).await?;
+
+                for block in response["results"].as_array().into_iter().flatten() {
+                    let block_type = block["type"].as_str().unwrap_or_default();
+                    let text = rich_text(&block[block_type]["rich_text"]);
+                    if !text.is_empty() {
+                        lines.push(text);
+                    }
+                    // Child pages are listed as pages of their own.
+                    if block["has_children"].as_bool().unwrap_or(false)
+                        && block_type != "child_page"
+                        && block_type != "child_database"
+                    {
+                        if let Some(id) = block["id"].as_str() {
+                            lines.extend(self.block_text(id).await?);
+                        }
+                    }
+                }
+
+                cursor = response["next_cursor"].as_str().map(ToString::to_string);
+                if !response["has_more"].as_bool().unwrap_or(false) || cursor.is_none() {
+                    return Ok(lines);
+                }
+            }
+        })
+    }
+}
+
+/// The plain text of a rich text array.
+fn rich_text(value: &Value) -> String {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|text| text["plain_text"].as_str())
+        .collect()
+}
+
+/// The title of a page, from its title property.
+fn page_title(page: &Value) -> Option<String> {
+    page["properties"]
+        .as_object()?
+        .values()
+        .find(|property| property["type"] == "title")
+        .map(|property| rich_text(&property["title"]))
+}
+
+#[async_trait]
+impl IncrementalSource for NotionPages {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("title", DataType::Utf8, true),
+            Field::new("url", DataType::Utf8, true),
+            Field::new(
+                "created_time",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            Field::new(
+                "last_edited_time",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            Field::new("archived", DataType::Boolean, true),
+            Field::new("content", DataType::Utf8, true),
+        ]))
+    }
+
+    fn updated_column(&self) -> Option<&'static str> {
+        Some("last_edited_time")
+    }
+
+    async fn fetch(&self, updated_since: Option<DateTime<Utc>>) -> incremental::Result<Vec<Value>> {
+        let pages = self.list_pages(updated_since).await?;
+
+        let mut records = Vec::with_capacity(pages.len());
+        for page in pages {
+            let Some(id) = page["id"].as_str() else {
+                return incremental::UnexpectedResponseSnafu {
+                    message: "Notion page without an id",
+                }
+                .fail();
+            };
+            let content = self.block_text(id).await?.join("\n");
+            records.push(page_record(&page, &content));
+        }
+        Ok(records)
+    }
+}
+
+/// The record of a page with the text of its blocks.
+fn page_record(page: &Value, content: &str) -> Value {
+    json!({
+        "id": page["id"],
+        "title": page_title(page),
+        "url": page["url"],
+        "created_time": page["created_time"],
+        "last_edited_time": page["last_edited_time"],
+        "archived": page["archived"],
+        "content": content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+    use arrow::datatypes::TimestampMillisecondType;
+
+    use super::*;
+
+    #[test]
+    fn test_page_record() {
+        let page = json!({
+            "object": "page",
+            "id": "6a2b1f0e-5c3d-4e8f-9a7b-1c2d3e4f5a6b",
+            "url": "https://www.notion.so/Roadmap-6a2b1f0e5c3d4e8f9a7b1c2d3e4f5a6b",
+            "created_time": "2024-07-01T09:00:00.000Z",
+            "last_edited_time": "2024-07-10T12:30:00.000Z",
+            "archived": false,
+            "properties": {
+                "Status": { "type": "select", "select": { "name": "Draft" } },
+                "Name": {
+                    "type": "title",
+                    "title": [{ "plain_text": "Road" }, { "plain_text": "map" }],
+                },
+            },
+        });
+        let source = NotionPages {
+            client: reqwest::Client::new(),
+            database_id: None,
+        };
+
+        let record = page_record(&page, "Q3 goals\nShip it");
+        let batches =
+            incremental::to_record_batches(source.schema(), &[record]).expect("record batches");
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(
+            batch
+                .column_by_name("title")
+                .expect("title")
+                .as_string::<i32>()
+                .value(0),
+            "Roadmap"
+        );
+        assert_eq!(
+            batch
+                .column_by_name("last_edited_time")
+                .expect("last_edited_time")
+                .as_primitive::<TimestampMillisecondType>()
+                .value(0),
+            1_720_614_600_000
+        );
+        assert!(!batch
+            .column_by_name("archived")
+            .expect("archived")
+            .as_boolean()
+            .value(0));
+        assert_eq!(
+            batch
+                .column_by_name("content")
+                .expect("content")
+                .as_string::<i32>()
+                .value(0),
+            "Q3 goals\nShip it"
+        );
+
+        assert_eq!(page_title(&json!({ "properties": {} })), None);
+        assert_eq!(rich_text(&Value::Null), "");
+    }
+}
@@ -34,6 +34,9 @@ use super::{DataConnector, DataConnectorFactory};
 pub enum Error {
     #[snafu(display("Unable to create ODBC connection pool: {source}"))]
     UnableToCreateODBCConnectionPool { source: db_connection_pool::Error },
+
+    #[snafu(display("Unable to log in with Kerberos: {source}"))]
+    UnableToLogInWithKerberos { source: crate::kerberos::Error },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -54,6 +57,11 @@ where
         params: Arc<HashMap<String, String>>,
     ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
         Box::pin(async move {
+            // ODBC drivers such as Hive's authenticate with GSSAPI from the process credential cache.
+            crate::kerberos::login(&params, &secret)
+                .await
+                .context(UnableToLogInWithKerberosSnafu)?;
+
             let pool: Arc<ODBCDbConnectionPool<'a>> = Arc::new(
                 ODBCPool::new(params, &secret).context(UnableToCreateODBCConnectionPoolSnafu)?,
             );
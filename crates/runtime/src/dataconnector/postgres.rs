@@ -15,6 +15,7 @@ limitations under the License.
 */
 
 use crate::component::dataset::Dataset;
+use crate::kerberos;
 use async_trait::async_trait;
 use data_components::postgres::PostgresTableFactory;
 use data_components::Read;
@@ -45,6 +46,21 @@ impl DataConnectorFactory for Postgres {
         params: Arc<HashMap<String, String>>,
     ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
         Box::pin(async move {
+            // The Postgres client has no GSSAPI support, so Kerberos connections go through the PostgreSQL ODBC
+            // driver, whose libpq authenticates from the credential cache.
+            if params.contains_key(kerberos::PRINCIPAL_PARAM) {
+                #[cfg(feature = "odbc")]
+                return super::odbc::ODBC::create(secret, Arc::new(odbc_params(&params))).await;
+                #[cfg(not(feature = "odbc"))]
+                return Err(DataConnectorError::InvalidConfiguration {
+                    dataconnector: "postgres".to_string(),
+                    message: "Kerberos authentication requires the odbc feature and the PostgreSQL ODBC driver."
+                        .to_string(),
+                    source: "the postgres client doesn't support GSSAPI authentication".into(),
+                }
+                .into());
+            }
+
             match PostgresConnectionPool::new(params, secret).await {
                 Ok(pool) => {
                     let postgres_factory = PostgresTableFactory::new(Arc::new(pool));
@@ -80,6 +96,40 @@ impl DataConnectorFactory for Postgres {
     }
 }
 
+/// The parameters of the ODBC connector for a Postgres connection authenticated with Kerberos. `pg_odbc_driver` is the
+/// name of the PostgreSQL ODBC driver (default `PostgreSQL Unicode`), and `pg_krbsrvname` the Kerberos service name of
+/// the server (default `postgres`).
+#[cfg(feature = "odbc")]
+fn odbc_params(params: &HashMap<String, String>) -> HashMap<String, String> {
+    let driver = params
+        .get("pg_odbc_driver")
+        .map_or("PostgreSQL Unicode", String::as_str);
+    let service_name = params
+        .get("pg_krbsrvname")
+        .map_or("postgres", String::as_str);
+    let mut connection_string = format!("Driver={{{driver}}};");
+    for (param, key) in [
+        ("pg_host", "Servername"),
+        ("pg_port", "Port"),
+        ("pg_db", "Database"),
+        ("pg_user", "Username"),
+        ("pg_sslmode", "SSLMode"),
+    ] {
+        if let Some(value) = params.get(param) {
+            connection_string.push_str(&format!("{key}={{{value}}};"));
+        }
+    }
+    connection_string.push_str(&format!("pqopt={{krbsrvname={service_name}}};"));
+
+    let mut odbc_params: HashMap<String, String> = params
+        .iter()
+        .filter(|(key, _)| key.starts_with("kerberos_"))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    odbc_params.insert("odbc_connection_string".to_string(), connection_string);
+    odbc_params
+}
+
 #[async_trait]
 impl DataConnector for Postgres {
     fn as_any(&self) -> &dyn Any {
@@ -115,3 +165,32 @@ impl DataConnector for Postgres {
         }
     }
 }
+
+#[cfg(all(test, feature = "odbc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kerberos_odbc_params() {
+        let params = HashMap::from([
+            ("pg_host".to_string(), "db.example.com".to_string()),
+            ("pg_port".to_string(), "5432".to_string()),
+            ("pg_db".to_string(), "sales".to_string()),
+            (
+                kerberos::PRINCIPAL_PARAM.to_string(),
+                "spice@EXAMPLE.COM".to_string(),
+            ),
+            (
+                "kerberos_keytab_path".to_string(),
+                "/etc/spice.keytab".to_string(),
+            ),
+        ]);
+        let odbc_params = odbc_params(&params);
+        assert_eq!(
+            odbc_params["odbc_connection_string"],
+            "Driver={PostgreSQL Unicode};Servername={db.example.com};Port={5432};Database={sales};pqopt={krbsrvname=postgres};"
+        );
+        assert_eq!(odbc_params[kerberos::PRINCIPAL_PARAM], "spice@EXAMPLE.COM");
+        assert!(!odbc_params.contains_key("pg_host"));
+    }
+}
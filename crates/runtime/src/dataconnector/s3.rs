@@ -27,6 +27,22 @@ use std::sync::Arc;
 use std::{collections::HashMap, future::Future};
 use url::{form_urlencoded, Url};
 
+/// The parameters of a dataset passed on to its object store.
+const S3_PARAMS: &[&str] = &[
+    "region",
+    "endpoint",
+    "force_path_style",
+    "s3_express",
+    "requester_pays",
+    "timeout",
+    "connect_timeout",
+    "max_retries",
+    "retry_timeout",
+    "retry_initial_backoff",
+    "retry_max_backoff",
+    "max_concurrent_requests",
+];
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("No AWS access secret provided for credentials"))]
@@ -78,22 +94,17 @@ impl ListingTableConnector for S3 {
         let mut fragments = vec![];
         let mut fragment_builder = form_urlencoded::Serializer::new(String::new());
 
-        if let Some(region) = self.params.get("region") {
-            fragment_builder.append_pair("region", region);
-        }
-        if let Some(endpoint) = self.params.get("endpoint") {
-            fragment_builder.append_pair("endpoint", endpoint);
+        for param in S3_PARAMS {
+            if let Some(value) = self.params.get(*param) {
+                fragment_builder.append_pair(param, value);
+            }
         }
         if let Some(secret) = &self.secret {
-            if let Some(key) = secret.get("key") {
-                fragment_builder.append_pair("key", key);
-            };
-            if let Some(secret) = secret.get("secret") {
-                fragment_builder.append_pair("secret", secret);
-            };
-        }
-        if let Some(timeout) = self.params.get("timeout") {
-            fragment_builder.append_pair("timeout", timeout);
+            for key in ["key", "secret", "session_token"] {
+                if let Some(value) = secret.get(key) {
+                    fragment_builder.append_pair(key, value);
+                }
+            }
         }
         fragments.push(fragment_builder.finish());
 
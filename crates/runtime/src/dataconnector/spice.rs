@@ -0,0 +1,195 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reads the datasets of another Spice runtime over its Flight endpoint, i.e.
+//! `from: spice://hub.internal:50051/taxi_trips`, so edge runtimes can accelerate datasets a hub runtime already
+//! accelerates.
+//!
+//! The port is 50051 by default. With the `tls` parameter set to `true` the runtime is reached over TLS, and the
+//! `token` of the secret of the dataset, if any, authenticates to a runtime that requires authentication.
+
+use super::DataConnector;
+use super::DataConnectorFactory;
+use crate::component::dataset::Dataset;
+use async_trait::async_trait;
+use data_components::flight::FlightFactory;
+use data_components::{Read, ReadWrite};
+use datafusion::datasource::TableProvider;
+use flight_client::FlightClient;
+use ns_lookup::verify_endpoint_connection;
+use secrets::Secret;
+use snafu::prelude::*;
+use std::any::Any;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::{collections::HashMap, future::Future};
+
+const DEFAULT_FLIGHT_PORT: u16 = 50051;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Unable to parse the Spice runtime dataset {from}, expected spice://<host>[:<port>]/<dataset>"
+    ))]
+    UnableToParseDatasetPath { from: String },
+
+    #[snafu(display(r#"Unable to connect to endpoint "{endpoint}": {source}"#))]
+    UnableToVerifyEndpointConnection {
+        source: ns_lookup::Error,
+        endpoint: String,
+    },
+
+    #[snafu(display("Unable to create flight client: {source}"))]
+    UnableToCreateFlightClient { source: flight_client::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub struct Spice {
+    token: Option<String>,
+    tls: bool,
+}
+
+impl DataConnectorFactory for Spice {
+    fn create(
+        secret: Option<Secret>,
+        params: Arc<HashMap<String, String>>,
+    ) -> Pin<Box<dyn Future<Output = super::NewDataConnectorResult> + Send>> {
+        Box::pin(async move {
+            let token = secret
+                .as_ref()
+                .and_then(|secret| secret.get("token"))
+                .map(ToString::to_string);
+            let tls = params
+                .get("tls")
+                .is_some_and(|tls| tls.eq_ignore_ascii_case("true"));
+
+            Ok(Arc::new(Self { token, tls }) as Arc<dyn DataConnector>)
+        })
+    }
+}
+
+impl Spice {
+    /// Connects to the runtime of the dataset, returning a factory for its table.
+    async fn flight_factory(&self, dataset: &Dataset) -> Result<(FlightFactory, String)> {
+        let (host, table) =
+            parse_dataset_path(&dataset.path()).context(UnableToParseDatasetPathSnafu {
+                from: dataset.from.clone(),
+            })?;
+        let scheme = if self.tls { "https" } else { "http" };
+        let endpoint = format!("{scheme}://{host}");
+
+        verify_endpoint_connection(&endpoint)
+            .await
+            .with_context(|_| UnableToVerifyEndpointConnectionSnafu {
+                endpoint: endpoint.clone(),
+            })?;
+
+        let mut flight_client = FlightClient::new(&endpoint, "", "")
+            .await
+            .context(UnableToCreateFlightClientSnafu)?;
+        if let Some(token) = &self.token {
+            flight_client = flight_client.with_bearer_token(token.clone());
+        }
+
+        Ok((FlightFactory::new("spice", flight_client), table))
+    }
+}
+
+/// Splits the path of `spice://<host>[:<port>]/<dataset>` into the host with its port and the dataset.
+fn parse_dataset_path(path: &str) -> Option<(String, String)> {
+    let (host, table) = path.strip_prefix("//")?.split_once('/')?;
+    if host.is_empty() || table.is_empty() {
+        return None;
+    }
+
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:{DEFAULT_FLIGHT_PORT}")
+    };
+    Some((host, table.replace('/', ".")))
+}
+
+#[async_trait]
+impl DataConnector for Spice {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> super::DataConnectorResult<Arc<dyn TableProvider>> {
+        let (flight_factory, table) = self.flight_factory(dataset).await.boxed().context(
+            super::UnableToGetReadProviderSnafu {
+                dataconnector: "spice",
+            },
+        )?;
+
+        Ok(Read::table_provider(&flight_factory, table.as_str().into())
+            .await
+            .context(super::UnableToGetReadProviderSnafu {
+                dataconnector: "spice",
+            })?)
+    }
+
+    async fn read_write_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<super::DataConnectorResult<Arc<dyn TableProvider>>> {
+        let flight_factory = self.flight_factory(dataset).await.boxed().context(
+            super::UnableToGetReadWriteProviderSnafu {
+                dataconnector: "spice",
+            },
+        );
+        let read_write_result = match flight_factory {
+            Ok((flight_factory, table)) => {
+                ReadWrite::table_provider(&flight_factory, table.as_str().into())
+                    .await
+                    .context(super::UnableToGetReadWriteProviderSnafu {
+                        dataconnector: "spice",
+                    })
+            }
+            Err(e) => Err(e),
+        };
+
+        Some(read_write_result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dataset_paths() {
+        assert_eq!(
+            parse_dataset_path("//hub:50052/taxi_trips"),
+            Some(("hub:50052".to_string(), "taxi_trips".to_string()))
+        );
+        assert_eq!(
+            parse_dataset_path("//hub.internal/analytics/orders"),
+            Some((
+                "hub.internal:50051".to_string(),
+                "analytics.orders".to_string()
+            ))
+        );
+        assert_eq!(parse_dataset_path("hub/taxi_trips"), None);
+        assert_eq!(parse_dataset_path("//hub"), None);
+        assert_eq!(parse_dataset_path("//hub/"), None);
+    }
+}
@@ -19,22 +19,41 @@ use std::collections::HashSet;
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 
-use crate::accelerated_table::{refresh::Refresh, AcceleratedTable, Retention};
+use crate::accelerated_table::{
+    export::ArtifactMetadata, progress, refresh::Refresh, schedule::RefreshSchedule,
+    versions::RetainedVersions, AcceleratedTable, Retention,
+};
+use crate::audit::{AuditLog, AuditRecord};
+use crate::auth::Principal;
+use crate::cached_table::CachedTable;
+use crate::component::dataset::acceleration::NestedTypes;
 use crate::component::dataset::{Dataset, Mode};
-use crate::dataaccelerator::{self, create_accelerator_table};
+use crate::connector_health;
+use crate::dataaccelerator::nested_types::{self, NestedTypesTable};
+use crate::dataaccelerator::{self, accelerated_file_path, create_accelerator_table};
 use crate::dataconnector::{DataConnector, DataConnectorError};
-use crate::dataupdate::{DataUpdate, DataUpdateExecutionPlan, UpdateType};
+use crate::dataupdate::{
+    DataUpdate, DataUpdateExecutionPlan, StreamingDataUpdateExecutionPlan, UpdateType,
+};
+use crate::execution_plan::traced::TraceScans;
+use crate::inference_log::{InferenceLog, InferenceRecord};
 use crate::object_store_registry::default_runtime_env;
-use crate::{embeddings, get_dependent_table_names};
+use crate::quotas;
+use crate::{embeddings, get_dependent_table_names, resolve_table};
 
-use arrow::datatypes::Schema;
+use arrow::array::{RecordBatch, UInt64Array};
+use arrow::datatypes::{Schema, SchemaRef};
 use arrow_tools::schema::verify_schema;
 use cache::QueryResultsCacheProvider;
+use data_components::object::metadata::LatestObjectsPushdown;
 use datafusion::catalog::schema::SchemaProvider;
 use datafusion::catalog::{CatalogProvider, MemoryCatalogProvider};
-use datafusion::datasource::{TableProvider, ViewTable};
+use datafusion::dataframe::DataFrame;
+use datafusion::datasource::{provider_as_source, TableProvider, ViewTable};
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::{SessionConfig, SessionContext, SessionState};
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::logical_expr::{LogicalPlan, LogicalPlanBuilder};
 use datafusion::physical_plan::collect;
 use datafusion::sql::parser::DFParser;
 use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
@@ -43,16 +62,32 @@ use datafusion_federation::{FederatedQueryPlanner, FederationAnalyzerRule};
 use query::{Protocol, QueryBuilder};
 use secrets::Secret;
 use snafu::prelude::*;
+use spicepod::component::dataset::policy::Policy;
 use tokio::spawn;
 use tokio::sync::oneshot;
 use tokio::time::{sleep, Instant};
+use uuid::Uuid;
 
 pub mod query;
 
+pub mod ai;
+pub mod anomaly;
 pub mod filter_converter;
+pub(crate) mod information_schema;
 pub mod initial_load;
+pub mod json;
+pub mod policy;
+pub mod predict;
 pub mod refresh_sql;
+pub(crate) mod sample;
 pub mod schema;
+pub mod sketch;
+pub mod spatial;
+pub(crate) mod table_function;
+pub mod time_series;
+pub(crate) mod time_travel;
+#[cfg(feature = "wasm")]
+pub mod wasm_udf;
 
 use self::schema::SpiceSchemaProvider;
 
@@ -74,6 +109,22 @@ pub enum Error {
     #[snafu(display("Unable to create view: {reason}"))]
     UnableToCreateView { reason: String },
 
+    #[snafu(display("Unable to resolve the tables of the view: {source}"))]
+    UnableToResolveViewTables { source: DataFusionError },
+
+    #[snafu(display("Unable to create table function {name}: {source}"))]
+    UnableToCreateTableFunction {
+        name: String,
+        source: DataFusionError,
+    },
+
+    #[cfg(feature = "wasm")]
+    #[snafu(display("Unable to create WASM function: {source}"))]
+    UnableToCreateWasmFunction { source: wasm_udf::Error },
+
+    #[snafu(display("WASM functions require the runtime to be built with the `wasm` feature"))]
+    WasmFunctionsNotEnabled,
+
     #[snafu(display("Unable to delete table: {reason}"))]
     UnableToDeleteTable { reason: String },
 
@@ -141,9 +192,27 @@ pub enum Error {
         source: crate::accelerated_table::Error,
     },
 
+    #[snafu(display("Unable to update the refresh schedule of {table_name}: {source}"))]
+    UnableToUpdateRefreshSchedule {
+        table_name: String,
+        source: crate::accelerated_table::Error,
+    },
+
     #[snafu(display("Table {table_name} is not accelerated"))]
     NotAcceleratedTable { table_name: String },
 
+    #[snafu(display("Unable to export the acceleration of {table_name}: {source}"))]
+    UnableToExportAcceleration {
+        table_name: String,
+        source: crate::accelerated_table::Error,
+    },
+
+    #[snafu(display("Unable to import the acceleration of {table_name}: {source}"))]
+    UnableToImportAcceleration {
+        table_name: String,
+        source: crate::accelerated_table::Error,
+    },
+
     #[snafu(display("Schema mismatch: {source}"))]
     SchemaMismatch { source: arrow_tools::schema::Error },
 
@@ -169,6 +238,15 @@ pub enum Error {
 
     #[snafu(display("Unable to get the lock of data writers"))]
     UnableToLockDataWriters {},
+
+    #[snafu(display("Unable to sample the source of {table_name}: {source}"))]
+    UnableToSampleSource {
+        table_name: String,
+        source: DataFusionError,
+    },
+
+    #[snafu(display("{source}"))]
+    AccessPolicy { source: policy::Error },
 }
 
 pub enum Table {
@@ -185,6 +263,12 @@ pub struct DataFusion {
     pub ctx: Arc<SessionContext>,
     data_writers: RwLock<HashSet<TableReference>>,
     cache_provider: RwLock<Option<Arc<QueryResultsCacheProvider>>>,
+    policies: RwLock<policy::DatasetPolicies>,
+    mask_key: RwLock<Arc<[u8]>>,
+    audit_log: RwLock<Option<Arc<AuditLog>>>,
+    copy_targets: RwLock<Vec<String>>,
+    inference_log: RwLock<Option<Arc<InferenceLog>>>,
+    table_functions: table_function::TableFunctions,
 
     /// Has the initial load of the data been completed? It is the responsibility of the caller to call `mark_initial_load_complete` when the initial load is complete.
     initial_load_complete: Mutex<bool>,
@@ -216,10 +300,17 @@ impl DataFusion {
 
         let state = SessionState::new_with_config_rt(df_config, default_runtime_env())
             .add_analyzer_rule(Arc::new(FederationAnalyzerRule::new()))
-            .with_query_planner(Arc::new(FederatedQueryPlanner::new()));
+            .with_query_planner(Arc::new(FederatedQueryPlanner::new()))
+            .add_physical_optimizer_rule(Arc::new(LatestObjectsPushdown::new()))
+            .add_physical_optimizer_rule(Arc::new(TraceScans::new()));
 
         let ctx = SessionContext::new_with_state(state);
         ctx.register_udf(embeddings::array_distance::ArrayDistance::new().into());
+        json::register_json_udfs(&ctx);
+        sketch::register_sketch_udfs(&ctx);
+        spatial::register_spatial_udfs(&ctx);
+        time_series::register_time_series_udfs(&ctx);
+        anomaly::register_anomaly_udwfs(&ctx);
         let catalog = MemoryCatalogProvider::new();
         let default_schema = SpiceSchemaProvider::new();
         let runtime_schema = SpiceSchemaProvider::new();
@@ -246,12 +337,28 @@ impl DataFusion {
             }
         }
 
-        ctx.register_catalog(SPICE_DEFAULT_CATALOG, Arc::new(catalog));
+        if let Err(e) = information_schema::register_key_constraint_views(&catalog) {
+            panic!("Unable to register key constraint views: {e}");
+        }
+
+        let catalog = Arc::new(catalog);
+        sample::register_sample_udtf(&ctx, Arc::clone(&catalog) as Arc<dyn CatalogProvider>);
+        time_travel::register_time_travel_udtfs(
+            &ctx,
+            Arc::clone(&catalog) as Arc<dyn CatalogProvider>,
+        );
+        ctx.register_catalog(SPICE_DEFAULT_CATALOG, catalog);
 
         DataFusion {
             ctx: Arc::new(ctx),
             data_writers: RwLock::new(HashSet::new()),
             cache_provider: RwLock::new(cache_provider),
+            policies: RwLock::new(policy::DatasetPolicies::new()),
+            mask_key: RwLock::new(random_mask_key()),
+            audit_log: RwLock::new(None),
+            copy_targets: RwLock::new(vec![]),
+            inference_log: RwLock::new(None),
+            table_functions: table_function::TableFunctions::default(),
             initial_load_complete: Mutex::new(false),
         }
     }
@@ -265,13 +372,11 @@ impl DataFusion {
         None
     }
 
+    /// Returns the schema of `table_reference`, in the default catalog unless it names another one.
     #[must_use]
-    fn schema(&self, schema_name: &str) -> Option<Arc<dyn SchemaProvider>> {
-        if let Some(catalog) = self.ctx.catalog(SPICE_DEFAULT_CATALOG) {
-            return catalog.schema(schema_name);
-        }
-
-        None
+    fn schema(&self, table_reference: &TableReference) -> Option<Arc<dyn SchemaProvider>> {
+        let catalog = table_reference.catalog().unwrap_or(SPICE_DEFAULT_CATALOG);
+        self.ctx.catalog(catalog)?.schema(table_reference.schema()?)
     }
 
     pub fn set_cache_provider(&self, cache_provider: QueryResultsCacheProvider) {
@@ -283,13 +388,11 @@ impl DataFusion {
     pub async fn has_table(&self, table_reference: &TableReference) -> bool {
         let table_name = table_reference.table();
 
-        if let Some(schema_name) = table_reference.schema() {
-            if let Some(schema) = self.schema(schema_name) {
-                return match schema.table(table_name).await {
-                    Ok(table) => table.is_some(),
-                    Err(_) => false,
-                };
-            }
+        if let Some(schema) = self.schema(table_reference) {
+            return match schema.table(table_name).await {
+                Ok(table) => table.is_some(),
+                Err(_) => false,
+            };
         }
 
         self.ctx.table(table_name).await.is_ok()
@@ -324,7 +427,13 @@ impl DataFusion {
     pub async fn register_table(&self, dataset: impl Borrow<Dataset>, table: Table) -> Result<()> {
         let dataset = dataset.borrow();
 
-        schema::ensure_schema_exists(&self.ctx, SPICE_DEFAULT_CATALOG, &dataset.name)?;
+        schema::ensure_schema_exists(&self.ctx, SPICE_DEFAULT_CATALOG, &dataset.name);
+
+        self.set_policies(&dataset.name, &dataset.policies);
+
+        if !matches!(table, Table::View(_)) {
+            connector_health::register(&dataset.name, &dataset.source());
+        }
 
         match table {
             Table::Accelerated {
@@ -347,7 +456,9 @@ impl DataFusion {
                     .await?;
             }
             Table::Federated(source) => self.register_federated_table(dataset, source).await?,
-            Table::View(sql) => self.register_view(dataset.name.clone(), sql)?,
+            Table::View(sql) => {
+                self.register_view(dataset.name.clone(), sql, dataset.namespace.clone())?;
+            }
         }
 
         if matches!(dataset.mode(), Mode::ReadWrite) {
@@ -376,7 +487,7 @@ impl DataFusion {
         let table_name = table_reference.table();
 
         if let Some(schema_name) = table_reference.schema() {
-            if let Some(schema) = self.schema(schema_name) {
+            if let Some(schema) = self.schema(table_reference) {
                 let table_provider = schema
                     .table(table_name)
                     .await
@@ -445,6 +556,49 @@ impl DataFusion {
         Ok(())
     }
 
+    /// Appends the batches of a stream to a writable dataset as they arrive, committing them when the stream ends.
+    /// If the stream fails, none of its batches are written. Returns the number of rows written.
+    pub async fn write_stream(
+        &self,
+        table_reference: TableReference,
+        stream: SendableRecordBatchStream,
+    ) -> Result<u64> {
+        if !self.is_writable(&table_reference) {
+            TableNotWritableSnafu {
+                table_name: table_reference.to_string(),
+            }
+            .fail()?;
+        }
+
+        let table_provider = self.get_table_provider(&table_reference).await?;
+
+        verify_schema(table_provider.schema().fields(), stream.schema().fields())
+            .context(SchemaMismatchSnafu)?;
+
+        let insert_plan = table_provider
+            .insert_into(
+                &self.ctx.state(),
+                Arc::new(StreamingDataUpdateExecutionPlan::new(stream)),
+                false,
+            )
+            .await
+            .context(UnableToPlanTableInsertSnafu {
+                table_name: table_reference.to_string(),
+            })?;
+
+        let results = collect(insert_plan, self.ctx.task_ctx()).await.context(
+            UnableToExecuteTableInsertSnafu {
+                table_name: table_reference.to_string(),
+            },
+        )?;
+
+        Ok(results
+            .first()
+            .and_then(|batch| batch.column(0).as_any().downcast_ref::<UInt64Array>())
+            .and_then(|counts| counts.values().first().copied())
+            .unwrap_or_default())
+    }
+
     pub async fn get_arrow_schema(&self, dataset: &str) -> Result<Schema> {
         let data_frame = self
             .ctx
@@ -471,6 +625,11 @@ impl DataFusion {
             .fail();
         }
 
+        self.set_policies(dataset_name, &[]);
+        connector_health::remove(dataset_name);
+        progress::remove(dataset_name);
+        quotas::remove(dataset_name);
+
         if self.is_writable(dataset_name) {
             self.data_writers
                 .write()
@@ -505,7 +664,6 @@ impl DataFusion {
                 .context(UnableToResolveTableProviderSnafu)?,
         };
 
-        let source_schema = source_table_provider.schema();
         let acceleration_settings =
             dataset
                 .acceleration
@@ -514,6 +672,20 @@ impl DataFusion {
                     name: dataset.name.to_string(),
                 })?;
 
+        let source_table_provider = match (acceleration_settings.nested_types, dataset.mode()) {
+            (NestedTypes::Json | NestedTypes::Flatten, Mode::Read)
+                if nested_types::has_nested_columns(&source_table_provider.schema()) =>
+            {
+                Arc::new(NestedTypesTable::new(
+                    source_table_provider,
+                    acceleration_settings.nested_types,
+                ))
+            }
+            _ => source_table_provider,
+        };
+
+        let source_schema = source_table_provider.schema();
+
         let accelerated_table_provider = create_accelerator_table(
             dataset.name.clone(),
             source_schema,
@@ -549,14 +721,137 @@ impl DataFusion {
             dataset.retention_check_interval(),
             acceleration_settings.retention_check_enabled,
         ));
+        accelerated_table_builder.refresh_cron(dataset.refresh_cron());
+        accelerated_table_builder.compaction_interval(dataset.compaction_interval());
 
-        accelerated_table_builder.zero_results_action(acceleration_settings.on_zero_results);
+        accelerated_table_builder
+            .zero_results_action(acceleration_settings.on_zero_results.clone());
+
+        accelerated_table_builder
+            .unavailable_action(acceleration_settings.on_unavailable, dataset.stale_after());
 
         accelerated_table_builder.cache_provider(self.cache_provider());
 
+        let storage_file = accelerated_file_path(&dataset.name, &acceleration_settings);
+        // Versions are written unencrypted, so they aren't kept for encrypted acceleration files.
+        accelerated_table_builder.retained_versions(
+            storage_file
+                .as_deref()
+                .filter(|_| {
+                    acceleration_settings.retained_versions > 0
+                        && acceleration_settings.encryption_secret.is_none()
+                })
+                .map(|storage_file| {
+                    RetainedVersions::new(storage_file, acceleration_settings.retained_versions)
+                }),
+        );
+        accelerated_table_builder.storage_file(storage_file);
+
+        accelerated_table_builder.quality_checks(dataset.quality_checks.clone());
+        accelerated_table_builder.shards(acceleration_settings.shards.clone());
+
         Ok(accelerated_table_builder.build().await)
     }
 
+    fn set_policies(&self, table: &TableReference, policies: &[Policy]) {
+        let Ok(mut all_policies) = self.policies.write() else {
+            return;
+        };
+
+        let key = policy::policy_key(table);
+        if policies.is_empty() {
+            all_policies.remove(&key);
+        } else {
+            all_policies.insert(key, Arc::new(policies.to_vec()));
+        }
+    }
+
+    /// Restricts the datasets scanned by `plan` to the rows and columns `principal` can access.
+    pub fn apply_policies(
+        &self,
+        plan: LogicalPlan,
+        principal: Option<&Principal>,
+        state: &SessionState,
+    ) -> policy::Result<LogicalPlan> {
+        let mask_key = self.mask_key();
+        match self.policies.read() {
+            Ok(policies) => policy::apply_policies(plan, &policies, &mask_key, principal, state),
+            Err(poisoned) => {
+                policy::apply_policies(plan, &poisoned.into_inner(), &mask_key, principal, state)
+            }
+        }
+    }
+
+    /// Whether dataset `table` has access policies, which only apply to reads planned with [`Self::apply_policies`].
+    #[must_use]
+    pub fn has_policies(&self, table: &TableReference) -> bool {
+        let key = policy::policy_key(table);
+        match self.policies.read() {
+            Ok(policies) => policies.contains_key(&key),
+            Err(poisoned) => poisoned.into_inner().contains_key(&key),
+        }
+    }
+
+    /// Sets the key of the HMAC `hash` masks are computed with, from `runtime.masks.hash_secret`.
+    pub fn set_mask_key(&self, key: &[u8]) {
+        if let Ok(mut k) = self.mask_key.write() {
+            *k = Arc::from(key);
+        };
+    }
+
+    fn mask_key(&self) -> Arc<[u8]> {
+        match self.mask_key.read() {
+            Ok(key) => Arc::clone(&key),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Reads up to `limit` rows from the source of a dataset, bypassing its acceleration or cache, with the access
+    /// policies of the dataset applied for `principal`.
+    pub async fn sample_source(
+        &self,
+        dataset_name: &TableReference,
+        limit: usize,
+        principal: Option<&Principal>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        let table = self
+            .ctx
+            .table_provider(dataset_name.clone())
+            .await
+            .context(UnableToGetTableSnafu)?;
+        let source = if let Some(accelerated) = table.as_any().downcast_ref::<AcceleratedTable>() {
+            accelerated.get_federated_table()
+        } else if let Some(cached) = table.as_any().downcast_ref::<CachedTable>() {
+            cached.inner()
+        } else {
+            table
+        };
+
+        let state = self.ctx.state();
+        let scan = LogicalPlanBuilder::scan(dataset_name.clone(), provider_as_source(source), None)
+            .and_then(LogicalPlanBuilder::build)
+            .context(UnableToSampleSourceSnafu {
+                table_name: dataset_name.to_string(),
+            })?;
+        let scan = self
+            .apply_policies(scan, principal, &state)
+            .context(AccessPolicySnafu)?;
+        let plan = LogicalPlanBuilder::from(scan)
+            .limit(0, Some(limit))
+            .and_then(LogicalPlanBuilder::build)
+            .context(UnableToSampleSourceSnafu {
+                table_name: dataset_name.to_string(),
+            })?;
+
+        let df = DataFrame::new(state, plan);
+        let schema = Arc::new(df.schema().as_arrow().clone());
+        let batches = df.collect().await.context(UnableToSampleSourceSnafu {
+            table_name: dataset_name.to_string(),
+        })?;
+
+        Ok((schema, batches))
+    }
+
     pub fn cache_provider(&self) -> Option<Arc<QueryResultsCacheProvider>> {
         let Ok(provider) = self.cache_provider.read() else {
             return None;
@@ -565,6 +860,59 @@ impl DataFusion {
         provider.clone()
     }
 
+    pub fn set_audit_log(&self, audit_log: Arc<AuditLog>) {
+        if let Ok(mut a) = self.audit_log.write() {
+            *a = Some(audit_log);
+        };
+    }
+
+    pub fn audit_log(&self) -> Option<Arc<AuditLog>> {
+        let Ok(audit_log) = self.audit_log.read() else {
+            return None;
+        };
+
+        audit_log.clone()
+    }
+
+    /// Records an audit event, if auditing is enabled.
+    pub fn audit(&self, record: AuditRecord) {
+        if let Some(audit_log) = self.audit_log() {
+            audit_log.record(record);
+        }
+    }
+
+    /// Sets the URL prefixes `COPY ... TO` can export to, from `runtime.copy.allowed_targets`.
+    pub fn set_copy_targets(&self, targets: Vec<String>) {
+        if let Ok(mut t) = self.copy_targets.write() {
+            *t = targets;
+        };
+    }
+
+    #[must_use]
+    pub fn copy_targets(&self) -> Vec<String> {
+        match self.copy_targets.read() {
+            Ok(targets) => targets.clone(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn set_inference_log(&self, inference_log: Arc<InferenceLog>) {
+        if let Ok(mut i) = self.inference_log.write() {
+            *i = Some(inference_log);
+        };
+    }
+
+    /// Records a call of a model, if the inference log is enabled.
+    pub fn log_inference(&self, record: InferenceRecord) {
+        let inference_log = match self.inference_log.read() {
+            Ok(inference_log) => inference_log.clone(),
+            Err(_) => None,
+        };
+        if let Some(inference_log) = inference_log {
+            inference_log.record(record);
+        }
+    }
+
     async fn register_accelerated_table(
         &self,
         dataset: &Dataset,
@@ -585,28 +933,82 @@ impl DataFusion {
         Ok(())
     }
 
-    pub async fn refresh_table(&self, dataset_name: &str) -> Result<()> {
+    /// Triggers a refresh of an accelerated dataset, returning the id of the job that reports its progress.
+    pub async fn refresh_table(&self, dataset_name: &str) -> Result<Uuid> {
         let table = self
             .ctx
             .table_provider(TableReference::bare(dataset_name.to_string()))
             .await
             .context(UnableToGetTableSnafu)?;
 
-        if let Some(accelerated_table) = table.as_any().downcast_ref::<AcceleratedTable>() {
-            accelerated_table
-                .trigger_refresh()
-                .await
-                .context(UnableToTriggerRefreshSnafu {
-                    table_name: dataset_name.to_string(),
-                })?;
-        } else {
-            NotAcceleratedTableSnafu {
+        let Some(accelerated_table) = table.as_any().downcast_ref::<AcceleratedTable>() else {
+            return NotAcceleratedTableSnafu {
                 table_name: dataset_name.to_string(),
             }
-            .fail()?;
-        }
+            .fail();
+        };
 
-        Ok(())
+        accelerated_table
+            .trigger_refresh()
+            .await
+            .context(UnableToTriggerRefreshSnafu {
+                table_name: dataset_name.to_string(),
+            })
+    }
+
+    /// Exports the acceleration of a dataset as a portable artifact, see [`crate::accelerated_table::export`].
+    pub async fn export_acceleration(
+        &self,
+        dataset_name: &str,
+    ) -> Result<(ArtifactMetadata, SendableRecordBatchStream)> {
+        let table = self
+            .ctx
+            .table_provider(TableReference::parse_str(dataset_name))
+            .await
+            .context(UnableToGetTableSnafu)?;
+
+        let Some(accelerated_table) = table.as_any().downcast_ref::<AcceleratedTable>() else {
+            return NotAcceleratedTableSnafu {
+                table_name: dataset_name.to_string(),
+            }
+            .fail();
+        };
+
+        accelerated_table
+            .export()
+            .await
+            .context(UnableToExportAccelerationSnafu {
+                table_name: dataset_name.to_string(),
+            })
+    }
+
+    /// Replaces the acceleration of a dataset with an artifact exported from another runtime, returning the number of
+    /// rows imported.
+    pub async fn import_acceleration(
+        &self,
+        dataset_name: &str,
+        metadata: &ArtifactMetadata,
+        data: Vec<RecordBatch>,
+    ) -> Result<usize> {
+        let table = self
+            .ctx
+            .table_provider(TableReference::parse_str(dataset_name))
+            .await
+            .context(UnableToGetTableSnafu)?;
+
+        let Some(accelerated_table) = table.as_any().downcast_ref::<AcceleratedTable>() else {
+            return NotAcceleratedTableSnafu {
+                table_name: dataset_name.to_string(),
+            }
+            .fail();
+        };
+
+        accelerated_table
+            .import(metadata, data)
+            .await
+            .context(UnableToImportAccelerationSnafu {
+                table_name: dataset_name.to_string(),
+            })
     }
 
     pub async fn update_refresh_sql(
@@ -636,6 +1038,34 @@ impl DataFusion {
         Ok(())
     }
 
+    /// Changes when an accelerated dataset is refreshed until it is reloaded, without changing its spicepod.
+    pub async fn update_refresh_schedule(
+        &self,
+        dataset_name: TableReference,
+        schedule: Option<RefreshSchedule>,
+    ) -> Result<()> {
+        let table = self
+            .ctx
+            .table_provider(dataset_name.clone())
+            .await
+            .context(UnableToGetTableSnafu)?;
+
+        if let Some(accelerated_table) = table.as_any().downcast_ref::<AcceleratedTable>() {
+            accelerated_table
+                .update_refresh_schedule(schedule)
+                .await
+                .context(UnableToUpdateRefreshScheduleSnafu {
+                    table_name: dataset_name.to_string(),
+                })?;
+        } else {
+            NotAcceleratedTableSnafu {
+                table_name: dataset_name.to_string(),
+            }
+            .fail()?;
+        }
+        Ok(())
+    }
+
     /// Federated tables are attached directly as tables visible in the public `DataFusion` context.
     async fn register_federated_table(
         &self,
@@ -668,6 +1098,17 @@ impl DataFusion {
         self.register_metadata_table(dataset, Arc::clone(&source))
             .await?;
 
+        // Writes go to the source, so only the scans of read-only datasets are cached.
+        let source_table_provider: Arc<dyn TableProvider> =
+            match dataset.cache.as_ref().filter(|cache| cache.enabled) {
+                Some(cache) if dataset.mode() == Mode::Read => Arc::new(CachedTable::new(
+                    dataset.name.clone(),
+                    source_table_provider,
+                    cache,
+                )),
+                _ => source_table_provider,
+            };
+
         self.ctx
             .register_table(dataset.name.clone(), source_table_provider)
             .context(UnableToRegisterTableToDataFusionSnafu)?;
@@ -698,7 +1139,64 @@ impl DataFusion {
         Ok(())
     }
 
-    pub(crate) fn register_view(&self, table: TableReference, view: String) -> Result<()> {
+    /// Registers the table function `name`, or replaces it if it is already registered. `sql` refers to the
+    /// parameters, whose SQL types are `param_types`, as `$1`, `$2`, ...
+    pub async fn register_table_function(
+        &self,
+        name: &str,
+        param_types: &[String],
+        sql: &str,
+    ) -> Result<()> {
+        self.table_functions
+            .register(&self.ctx, name, param_types, sql)
+            .await
+            .context(UnableToCreateTableFunctionSnafu { name })
+    }
+
+    /// Registers the scalar function `name` exported as `export` by the WASM module at `module`, or replaces it if
+    /// it is already registered.
+    #[cfg(feature = "wasm")]
+    pub fn register_wasm_function(
+        &self,
+        name: &str,
+        module: &str,
+        export: &str,
+        param_types: &[String],
+        returns: &str,
+    ) -> Result<()> {
+        let udf = wasm_udf::create_udf(name, module, export, param_types, returns)
+            .context(UnableToCreateWasmFunctionSnafu)?;
+        self.ctx.register_udf(udf);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[allow(clippy::unused_self)]
+    pub fn register_wasm_function(
+        &self,
+        _name: &str,
+        _module: &str,
+        _export: &str,
+        _param_types: &[String],
+        _returns: &str,
+    ) -> Result<()> {
+        WasmFunctionsNotEnabledSnafu.fail()
+    }
+
+    /// Removes the table or scalar function `name`.
+    pub fn remove_function(&self, name: &str) {
+        self.table_functions.remove(name);
+        self.ctx.deregister_udf(name);
+    }
+
+    /// Registers the view `table` with the SQL `view`. The tables of the SQL of a view of a namespace are resolved in
+    /// the catalog of the namespace, and can't be in another catalog.
+    pub(crate) fn register_view(
+        &self,
+        table: TableReference,
+        view: String,
+        namespace: Option<String>,
+    ) -> Result<()> {
         let table_exists = self.ctx.table_exist(table.clone()).unwrap_or(false);
         if table_exists {
             return TableAlreadyExistsSnafu.fail();
@@ -717,6 +1215,30 @@ impl DataFusion {
             .fail();
         }
 
+        let mut state = self.ctx.state();
+        if let Some(namespace) = &namespace {
+            state
+                .config_mut()
+                .options_mut()
+                .catalog
+                .default_catalog
+                .clone_from(namespace);
+            let references = state
+                .resolve_table_references(&statements[0])
+                .context(UnableToResolveViewTablesSnafu)?;
+            if let Some(reference) = references.iter().find(|reference| {
+                resolve_table(reference, namespace).catalog() != Some(namespace.as_str())
+            }) {
+                return UnableToCreateViewSnafu {
+                    reason: format!(
+                        "{reference} is not in the catalog of namespace {namespace}, which views of the namespace can't read"
+                    ),
+                }
+                .fail();
+            }
+        }
+        let default_catalog = namespace.unwrap_or_else(|| SPICE_DEFAULT_CATALOG.to_string());
+
         let ctx = Arc::clone(&self.ctx);
         spawn(async move {
             // Tables are currently lazily created (i.e. not created until first data is received) so that we know the table schema.
@@ -725,7 +1247,10 @@ impl DataFusion {
 
             let deadline = Instant::now() + Duration::from_secs(60);
             let mut unresolved_dependent_table: Option<TableReference> = None;
-            let dependent_table_names = get_dependent_table_names(&statements[0]);
+            let dependent_table_names = get_dependent_table_names(&statements[0])
+                .iter()
+                .map(|table| resolve_table(table, &default_catalog))
+                .collect::<Vec<_>>();
             for dependent_table_name in dependent_table_names {
                 let mut attempts = 0;
 
@@ -759,7 +1284,7 @@ impl DataFusion {
                 return;
             }
 
-            let plan = match ctx.state().statement_to_plan(statements[0].clone()).await {
+            let plan = match state.statement_to_plan(statements[0].clone()).await {
                 Ok(plan) => plan,
                 Err(e) => {
                     tracing::error!("Failed to create view: {e}");
@@ -808,3 +1333,80 @@ impl Default for DataFusion {
         Self::new()
     }
 }
+
+/// The mask key until `runtime.masks.hash_secret` sets one, unique to this runtime.
+fn random_mask_key() -> Arc<[u8]> {
+    [Uuid::new_v4(), Uuid::new_v4()]
+        .iter()
+        .flat_map(|uuid| uuid.into_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int64Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::{
+        datasource::MemTable, error::DataFusionError, execution::SendableRecordBatchStream,
+        physical_plan::stream::RecordBatchStreamAdapter, sql::TableReference,
+    };
+
+    use super::{DataFusion, SPICE_RUNTIME_SCHEMA};
+
+    #[tokio::test]
+    async fn write_stream_rolls_back_when_the_stream_fails() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .expect("valid batch");
+        let table = MemTable::try_new(Arc::clone(&schema), vec![vec![]]).expect("valid table");
+
+        let df = DataFusion::new();
+        let name = TableReference::partial(SPICE_RUNTIME_SCHEMA, "ingest_test");
+        df.register_runtime_table(name.clone(), Arc::new(table))
+            .expect("registered table");
+
+        // Two batches, followed by an error if `fail`.
+        let stream = |fail: bool| -> SendableRecordBatchStream {
+            let mut batches = vec![Ok(batch.clone()), Ok(batch.clone())];
+            if fail {
+                batches.push(Err(DataFusionError::Execution(
+                    "connection reset".to_string(),
+                )));
+            }
+            Box::pin(RecordBatchStreamAdapter::new(
+                Arc::clone(&schema),
+                futures::stream::iter(batches),
+            ))
+        };
+        let rows = || async {
+            df.ctx
+                .sql("SELECT * FROM runtime.ingest_test")
+                .await
+                .expect("valid query")
+                .collect()
+                .await
+                .expect("query results")
+                .iter()
+                .map(RecordBatch::num_rows)
+                .sum::<usize>()
+        };
+
+        assert!(df.write_stream(name.clone(), stream(true)).await.is_err());
+        assert_eq!(rows().await, 0);
+
+        assert_eq!(
+            df.write_stream(name.clone(), stream(false))
+                .await
+                .expect("written stream"),
+            6
+        );
+        assert_eq!(rows().await, 6);
+    }
+}
@@ -0,0 +1,383 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! `ai(prompt[, model])`, also named `llm_generate`, returns the response of an LLM of the spicepod to each prompt.
+//! The model can be left out when the spicepod has a single LLM.
+//!
+//! The model is called once per distinct prompt of a batch of rows, so a constant prompt is only sent once per batch.
+//! A query that would call the models more than `runtime.ai_functions.max_calls_per_query` times fails rather than
+//! calling them, and at most `runtime.ai_functions.max_concurrency` calls are in flight across all queries. Each call
+//! is counted against the `runtime.rate_limits` of the principal running the query.
+//!
+//! Calls are bound to their query by [`bind_ai_calls`], so `ai()` fails in plans not run through [`super::query`].
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Instant,
+};
+
+use app::App;
+use arrow::{
+    array::{Array, ArrayRef, AsArray, StringArray},
+    compute::cast,
+    datatypes::DataType,
+};
+use async_openai::types::{ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs};
+use chrono::Utc;
+use datafusion::{
+    common::{
+        exec_datafusion_err, exec_err,
+        tree_node::{Transformed, TreeNode},
+        Result,
+    },
+    execution::context::SessionContext,
+    logical_expr::{
+        expr::ScalarFunction, ColumnarValue, Expr, LogicalPlan, LogicalPlanBuilder, ScalarUDF,
+        ScalarUDFImpl, Signature, TypeSignature, Volatility,
+    },
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use spicepod::component::runtime::AiFunctions;
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    sync::{RwLock, Semaphore},
+};
+use tracing::Instrument;
+
+use crate::{
+    inference_log::{InferenceKind, InferenceRecord},
+    model::LLMModelStore,
+    quotas,
+    rate_limits::RateLimiter,
+};
+
+use super::DataFusion;
+
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+const DEFAULT_MAX_CALLS_PER_QUERY: usize = 100;
+
+/// Registers `ai` in `ctx`, calling the LLMs of `llms` within the rate limits of `app`, and recording the calls in
+/// the inference log of `df`.
+pub fn register_ai_udf(
+    ctx: &SessionContext,
+    df: Weak<DataFusion>,
+    llms: Arc<RwLock<LLMModelStore>>,
+    app: Arc<RwLock<Option<App>>>,
+    rate_limiter: Arc<RateLimiter>,
+    config: &AiFunctions,
+) {
+    let max_concurrency = config
+        .max_concurrency
+        .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+        .max(1);
+
+    ctx.register_udf(ScalarUDF::new_from_impl(AiFunction {
+        df,
+        llms,
+        app,
+        rate_limiter,
+        permits: Arc::new(Semaphore::new(max_concurrency)),
+        max_concurrency,
+        max_calls_per_query: config
+            .max_calls_per_query
+            .unwrap_or(DEFAULT_MAX_CALLS_PER_QUERY),
+        query: None,
+        signature: Signature::one_of(
+            vec![
+                TypeSignature::Exact(vec![DataType::Utf8]),
+                TypeSignature::Exact(vec![DataType::Utf8, DataType::Utf8]),
+            ],
+            // Responses to the same prompt can differ.
+            Volatility::Volatile,
+        ),
+        aliases: vec!["llm_generate".to_string()],
+    }));
+}
+
+/// Binds the `ai()` calls of `plan`, including the calls inside views and subqueries, to a query of `principal`, so
+/// they count towards the calls of the query and the rate limits of the principal.
+pub(crate) fn bind_ai_calls(plan: LogicalPlan, principal: Option<&str>) -> Result<LogicalPlan> {
+    let query = Arc::new(QueryCalls {
+        principal: principal.map(ToString::to_string),
+        calls: AtomicUsize::new(0),
+    });
+    bind(plan, &query).map(|transformed| transformed.data)
+}
+
+fn bind(plan: LogicalPlan, query: &Arc<QueryCalls>) -> Result<Transformed<LogicalPlan>> {
+    plan.transform_up_with_subqueries(|node| {
+        if let LogicalPlan::TableScan(scan) = &node {
+            // Views are only inlined during analysis, so bind the calls of their plans here.
+            let Some(view_plan) = scan.source.get_logical_plan() else {
+                return Ok(Transformed::no(node));
+            };
+            let view = bind(view_plan.clone(), query)?;
+            if !view.transformed {
+                return Ok(Transformed::no(node));
+            }
+            return LogicalPlanBuilder::from(view.data)
+                .alias(scan.table_name.clone())?
+                .build()
+                .map(Transformed::yes);
+        }
+
+        node.map_expressions(|expr| {
+            expr.transform_up(|expr| match expr {
+                Expr::ScalarFunction(function) => {
+                    match function.func.inner().as_any().downcast_ref::<AiFunction>() {
+                        Some(ai) => Ok(Transformed::yes(Expr::ScalarFunction(
+                            ScalarFunction::new_udf(
+                                Arc::new(ScalarUDF::new_from_impl(ai.bound(query))),
+                                function.args,
+                            ),
+                        ))),
+                        None => Ok(Transformed::no(Expr::ScalarFunction(function))),
+                    }
+                }
+                expr => Ok(Transformed::no(expr)),
+            })
+        })
+    })
+}
+
+/// The `ai()` calls of a query.
+#[derive(Debug)]
+struct QueryCalls {
+    principal: Option<String>,
+    calls: AtomicUsize,
+}
+
+#[derive(Clone)]
+struct AiFunction {
+    df: Weak<DataFusion>,
+    llms: Arc<RwLock<LLMModelStore>>,
+    app: Arc<RwLock<Option<App>>>,
+    rate_limiter: Arc<RateLimiter>,
+    /// Limits the calls in flight across all queries.
+    permits: Arc<Semaphore>,
+    max_concurrency: usize,
+    max_calls_per_query: usize,
+    /// The query the calls are bound to, unset for the function registered in the session.
+    query: Option<Arc<QueryCalls>>,
+    signature: Signature,
+    aliases: Vec<String>,
+}
+
+impl std::fmt::Debug for AiFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AiFunction")
+            .field("max_concurrency", &self.max_concurrency)
+            .field("max_calls_per_query", &self.max_calls_per_query)
+            .field("query", &self.query)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScalarUDFImpl for AiFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "ai"
+    }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let Some(query) = &self.query else {
+            return exec_err!("ai() can only be called in queries run by the runtime");
+        };
+        // `invoke` is synchronous, so the calls block the thread, which only other threads can make progress on.
+        let handle = match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() != RuntimeFlavor::CurrentThread => handle,
+            _ => return exec_err!("ai() can only be called on a multi-threaded runtime"),
+        };
+
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let prompts = cast(&args[0], &DataType::Utf8)?;
+        let prompts = prompts.as_string::<i32>();
+        let models = args
+            .get(1)
+            .map(|models| cast(models, &DataType::Utf8))
+            .transpose()?;
+        let models = models.as_ref().map(|models| models.as_string::<i32>());
+
+        // Each distinct model and prompt is only sent once.
+        let mut requests: HashMap<(Option<&str>, &str), usize> = HashMap::new();
+        let mut rows = Vec::with_capacity(prompts.len());
+        for row in 0..prompts.len() {
+            let model = match models {
+                Some(models) if models.is_null(row) => {
+                    rows.push(None);
+                    continue;
+                }
+                Some(models) => Some(models.value(row)),
+                None => None,
+            };
+            if prompts.is_null(row) {
+                rows.push(None);
+                continue;
+            }
+
+            let next = requests.len();
+            rows.push(Some(
+                *requests.entry((model, prompts.value(row))).or_insert(next),
+            ));
+        }
+
+        let calls = query.calls.fetch_add(requests.len(), Ordering::SeqCst) + requests.len();
+        if calls > self.max_calls_per_query {
+            return exec_err!(
+                "ai() would call the models {calls} times for the query, more than runtime.ai_functions.max_calls_per_query ({}). Add a LIMIT or filter the rows.",
+                self.max_calls_per_query
+            );
+        }
+
+        let mut ordered: Vec<_> = requests.into_iter().collect();
+        ordered.sort_by_key(|(_, index)| *index);
+        let responses = tokio::task::block_in_place(|| {
+            handle.block_on(
+                stream::iter(ordered)
+                    .map(|((model, prompt), _)| self.generate(query, model, prompt))
+                    .buffered(self.max_concurrency)
+                    .try_collect::<Vec<_>>(),
+            )
+        })?;
+
+        let array: ArrayRef = Arc::new(StringArray::from_iter(
+            rows.into_iter()
+                .map(|request| request.and_then(|i| responses[i].as_deref())),
+        ));
+        Ok(ColumnarValue::Array(array))
+    }
+}
+
+impl AiFunction {
+    fn bound(&self, query: &Arc<QueryCalls>) -> Self {
+        Self {
+            query: Some(Arc::clone(query)),
+            ..self.clone()
+        }
+    }
+
+    async fn generate(
+        &self,
+        query: &QueryCalls,
+        model: Option<&str>,
+        prompt: &str,
+    ) -> Result<Option<String>> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| exec_datafusion_err!("{e}"))?;
+
+        let llms = self.llms.read().await;
+        let (model_id, llm) = match model {
+            Some(model) => (model, llms.get(model)),
+            None if llms.len() == 1 => match llms.iter().next() {
+                Some((model, llm)) => (model.as_str(), Some(llm)),
+                None => ("", None),
+            },
+            None => {
+                return exec_err!(
+                    "ai() expects a model when the spicepod doesn't have exactly one LLM: ai(prompt, model)"
+                )
+            }
+        };
+        let Some(llm) = llm else {
+            return exec_err!("The LLM {model_id} was not found");
+        };
+
+        let principal = query.principal.as_deref();
+        quotas::check_llm_tokens(principal, Utc::now())
+            .map_err(|e| exec_datafusion_err!("ai() can't call {model_id}: {e}"))?;
+        {
+            let app = self.app.read().await;
+            let limits = app
+                .as_ref()
+                .map_or(&[][..], |app| app.runtime.rate_limits.as_slice());
+            self.rate_limiter
+                .acquire(limits, principal, model_id, Utc::now())
+                .map_err(|exceeded| {
+                    exec_datafusion_err!("ai() can't call {model_id}: {exceeded}")
+                })?;
+        }
+
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(model_id)
+            .messages(vec![ChatCompletionRequestUserMessageArgs::default()
+                .content(prompt)
+                .build()
+                .map_err(|e| exec_datafusion_err!("{e}"))?
+                .into()])
+            .build()
+            .map_err(|e| exec_datafusion_err!("{e}"))?;
+
+        metrics::counter!("ai_function_calls", "model" => model_id.to_string()).increment(1);
+        let start = Instant::now();
+        let result = llm
+            .read()
+            .await
+            .chat_request(request)
+            .instrument(tracing::info_span!("llm_inference", model = %model_id))
+            .await
+            .map(|response| {
+                if let Some(usage) = &response.usage {
+                    self.rate_limiter.record_tokens(
+                        principal,
+                        model_id,
+                        u64::from(usage.total_tokens),
+                        Utc::now(),
+                    );
+                    quotas::record_llm_tokens(principal, u64::from(usage.total_tokens), Utc::now());
+                }
+                response
+                    .choices
+                    .into_iter()
+                    .find_map(|choice| choice.message.content)
+            });
+
+        if let Some(df) = self.df.upgrade() {
+            let mut record =
+                InferenceRecord::new(InferenceKind::AiFunction, model_id, start.elapsed());
+            record.input = Some(prompt.to_string());
+            match &result {
+                Ok(response) => record.output.clone_from(response),
+                Err(e) => record.error = Some(e.to_string()),
+            }
+            df.log_inference(record);
+        }
+
+        result.map_err(|e| exec_datafusion_err!("Unable to call the LLM {model_id}: {e}"))
+    }
+}
@@ -0,0 +1,461 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Window functions that score the values of a time series for anomalies, over the rows of their window partition in
+//! the order of its `ORDER BY`:
+//!
+//! - `anomaly_zscore(value, window)`: how many standard deviations each value is from the mean of the `window`
+//!   values before it, i.e. `anomaly_zscore(fare, 24) OVER (ORDER BY pickup_time)`.
+//! - `anomaly_esd(value, max_anomalies[, alpha])`: whether each value is one of the up to `max_anomalies` outliers
+//!   the generalized ESD test finds at the significance level `alpha`, 0.05 by default.
+//! - `anomaly_seasonal(value, period)`: how many standard deviations the residual of each value is from zero, after
+//!   removing the trend and the seasonality of `period` values with a classical additive decomposition.
+//!
+//! Null values are ignored, and have null scores.
+
+use std::{any::Any, collections::VecDeque, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, BooleanArray, Float64Array},
+    compute::cast,
+    datatypes::{DataType, Float64Type, UInt64Type},
+};
+use datafusion::{
+    common::{exec_err, plan_err, Result},
+    execution::context::SessionContext,
+    logical_expr::{
+        PartitionEvaluator, Signature, TypeSignature, Volatility, WindowUDF, WindowUDFImpl,
+    },
+};
+
+const DEFAULT_ESD_ALPHA: f64 = 0.05;
+
+/// Registers the anomaly scoring window functions in `ctx`.
+pub fn register_anomaly_udwfs(ctx: &SessionContext) {
+    for kind in [
+        AnomalyFunctionKind::Zscore,
+        AnomalyFunctionKind::Esd,
+        AnomalyFunctionKind::Seasonal,
+    ] {
+        ctx.register_udwf(WindowUDF::new_from_impl(AnomalyFunction::new(kind)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AnomalyFunctionKind {
+    Zscore,
+    Esd,
+    Seasonal,
+}
+
+impl AnomalyFunctionKind {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            AnomalyFunctionKind::Zscore => "anomaly_zscore",
+            AnomalyFunctionKind::Esd => "anomaly_esd",
+            AnomalyFunctionKind::Seasonal => "anomaly_seasonal",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AnomalyFunction {
+    kind: AnomalyFunctionKind,
+    signature: Signature,
+}
+
+impl AnomalyFunction {
+    fn new(kind: AnomalyFunctionKind) -> Self {
+        let signature = match kind {
+            AnomalyFunctionKind::Esd => Signature::one_of(
+                vec![TypeSignature::Any(2), TypeSignature::Any(3)],
+                Volatility::Immutable,
+            ),
+            AnomalyFunctionKind::Zscore | AnomalyFunctionKind::Seasonal => {
+                Signature::any(2, Volatility::Immutable)
+            }
+        };
+
+        Self { kind, signature }
+    }
+}
+
+impl WindowUDFImpl for AnomalyFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, args: &[DataType]) -> Result<DataType> {
+        if !args[0].is_numeric() {
+            return plan_err!(
+                "{} expects numeric values, received {}",
+                self.kind.name(),
+                args[0]
+            );
+        }
+        if !args[1..]
+            .iter()
+            .all(|arg| arg.is_numeric() || arg.is_null())
+        {
+            return plan_err!("{} expects numeric parameters", self.kind.name());
+        }
+
+        Ok(match self.kind {
+            AnomalyFunctionKind::Esd => DataType::Boolean,
+            AnomalyFunctionKind::Zscore | AnomalyFunctionKind::Seasonal => DataType::Float64,
+        })
+    }
+
+    fn partition_evaluator(&self) -> Result<Box<dyn PartitionEvaluator>> {
+        Ok(Box::new(AnomalyEvaluator { kind: self.kind }))
+    }
+}
+
+#[derive(Debug)]
+struct AnomalyEvaluator {
+    kind: AnomalyFunctionKind,
+}
+
+impl AnomalyEvaluator {
+    /// The value of a parameter, which is the same for every row.
+    fn parameter(&self, values: &[ArrayRef], index: usize, name: &str) -> Result<Option<f64>> {
+        let Some(parameter) = values.get(index) else {
+            return Ok(None);
+        };
+        let parameter = cast(parameter, &DataType::Float64)?;
+        match parameter
+            .as_primitive::<Float64Type>()
+            .iter()
+            .flatten()
+            .next()
+        {
+            Some(value) if value.is_finite() && value > 0.0 => Ok(Some(value)),
+            Some(value) => exec_err!(
+                "{} expects a positive {name}, received {value}",
+                self.kind.name()
+            ),
+            None => Ok(None),
+        }
+    }
+
+    /// The value of a parameter that counts rows.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn count_parameter(&self, values: &[ArrayRef], index: usize, name: &str) -> Result<usize> {
+        match self.parameter(values, index, name)? {
+            Some(value) if value.fract() == 0.0 => Ok(value as usize),
+            Some(value) => exec_err!(
+                "{} expects a whole {name}, received {value}",
+                self.kind.name()
+            ),
+            None => exec_err!("{} expects a {name}", self.kind.name()),
+        }
+    }
+}
+
+impl PartitionEvaluator for AnomalyEvaluator {
+    fn evaluate_all(&mut self, values: &[ArrayRef], num_rows: usize) -> Result<ArrayRef> {
+        let series = cast(&values[0], &DataType::Float64)?;
+        let series: Vec<Option<f64>> = series
+            .as_primitive::<Float64Type>()
+            .iter()
+            .map(|value| value.filter(|value| value.is_finite()))
+            .collect();
+        debug_assert_eq!(series.len(), num_rows);
+
+        Ok(match self.kind {
+            AnomalyFunctionKind::Zscore => {
+                let window = self.count_parameter(values, 1, "window")?;
+                Arc::new(Float64Array::from(rolling_zscores(&series, window)))
+            }
+            AnomalyFunctionKind::Esd => {
+                let max_anomalies = self.count_parameter(values, 1, "max_anomalies")?;
+                let alpha = self
+                    .parameter(values, 2, "alpha")?
+                    .unwrap_or(DEFAULT_ESD_ALPHA);
+                if alpha >= 1.0 {
+                    return exec_err!("anomaly_esd expects an alpha below 1, received {alpha}");
+                }
+                Arc::new(BooleanArray::from(esd_outliers(
+                    &series,
+                    max_anomalies,
+                    alpha,
+                )))
+            }
+            AnomalyFunctionKind::Seasonal => {
+                let period = self.count_parameter(values, 1, "period")?;
+                Arc::new(Float64Array::from(seasonal_scores(&series, period)))
+            }
+        })
+    }
+}
+
+fn mean_and_deviation(values: impl Iterator<Item = f64> + Clone) -> Option<(f64, f64)> {
+    let (count, sum) = values
+        .clone()
+        .fold((0.0, 0.0), |(n, s), v| (n + 1.0, s + v));
+    if count < 2.0 {
+        return None;
+    }
+    let mean = sum / count;
+    let variance = values.map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1.0);
+    Some((mean, variance.sqrt()))
+}
+
+/// Scores each value against the mean and sample standard deviation of the `window` non-null values before it.
+#[allow(clippy::cast_precision_loss)]
+fn rolling_zscores(series: &[Option<f64>], window: usize) -> Vec<Option<f64>> {
+    let mut previous: VecDeque<f64> = VecDeque::with_capacity(window);
+    let (mut sum, mut sum_of_squares) = (0.0, 0.0);
+
+    series
+        .iter()
+        .map(|value| {
+            let value = (*value)?;
+            let count = previous.len() as f64;
+            let score = (count >= 2.0)
+                .then(|| {
+                    let mean = sum / count;
+                    let variance = (sum_of_squares - count * mean * mean) / (count - 1.0);
+                    (variance > f64::EPSILON * sum_of_squares.abs().max(1.0))
+                        .then(|| (value - mean) / variance.sqrt())
+                })
+                .flatten();
+
+            if window > 0 {
+                if previous.len() == window {
+                    if let Some(oldest) = previous.pop_front() {
+                        sum -= oldest;
+                        sum_of_squares -= oldest * oldest;
+                    }
+                }
+                previous.push_back(value);
+                sum += value;
+                sum_of_squares += value * value;
+            }
+            score
+        })
+        .collect()
+}
+
+/// Finds the up to `max_anomalies` outliers of the generalized ESD test (Rosner, 1983).
+#[allow(clippy::cast_precision_loss)]
+fn esd_outliers(series: &[Option<f64>], max_anomalies: usize, alpha: f64) -> Vec<Option<bool>> {
+    let mut remaining: Vec<(usize, f64)> = series
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| Some((i, (*value)?)))
+        .collect();
+    let n = remaining.len();
+
+    let mut candidates = Vec::new();
+    let mut outliers = 0;
+    for i in 1..=max_anomalies.min(n.saturating_sub(2)) {
+        let Some((mean, deviation)) = mean_and_deviation(remaining.iter().map(|(_, v)| *v)) else {
+            break;
+        };
+        if deviation == 0.0 {
+            break;
+        }
+        let Some((position, statistic)) = remaining
+            .iter()
+            .enumerate()
+            .map(|(position, (_, v))| (position, (v - mean).abs() / deviation))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        else {
+            break;
+        };
+
+        let (n, i) = (n as f64, i as f64);
+        let p = 1.0 - alpha / (2.0 * (n - i + 1.0));
+        let t = t_quantile(p, n - i - 1.0);
+        let critical = (n - i) * t / ((n - i - 1.0 + t * t) * (n - i + 1.0)).sqrt();
+
+        candidates.push(remaining.swap_remove(position).0);
+        if statistic > critical {
+            outliers = candidates.len();
+        }
+    }
+
+    let mut flags: Vec<Option<bool>> = series.iter().map(|v| v.map(|_| false)).collect();
+    for &row in &candidates[..outliers] {
+        flags[row] = Some(true);
+    }
+    flags
+}
+
+/// Scores the residuals of a classical additive decomposition of the series into a centered moving average trend, the
+/// average deviation from the trend at each position of the `period`, and the residual.
+#[allow(clippy::cast_precision_loss)]
+fn seasonal_scores(series: &[Option<f64>], period: usize) -> Vec<Option<f64>> {
+    let n = series.len();
+    if period < 2 || n < 2 * period {
+        return vec![None; n];
+    }
+
+    // An even period is averaged over period + 1 values, with half weights at both ends, to stay centered.
+    let half = period / 2;
+    let trend: Vec<Option<f64>> = (0..n)
+        .map(|i| {
+            if i < half || i + half >= n {
+                return None;
+            }
+            let mut sum = 0.0;
+            for (offset, value) in series[i - half..=i + half].iter().enumerate() {
+                let weight = if period % 2 == 0 && (offset == 0 || offset == 2 * half) {
+                    0.5
+                } else {
+                    1.0
+                };
+                sum += weight * (*value)?;
+            }
+            Some(sum / period as f64)
+        })
+        .collect();
+
+    let mut seasonal = vec![(0.0, 0.0); period];
+    for (i, (value, trend)) in series.iter().zip(&trend).enumerate() {
+        if let (Some(value), Some(trend)) = (value, trend) {
+            seasonal[i % period].0 += value - trend;
+            seasonal[i % period].1 += 1.0;
+        }
+    }
+    let seasonal: Vec<f64> = seasonal
+        .iter()
+        .map(|(sum, count)| if *count > 0.0 { sum / count } else { 0.0 })
+        .collect();
+    let seasonal_mean = seasonal.iter().sum::<f64>() / period as f64;
+
+    let residuals: Vec<Option<f64>> = (0..n)
+        .map(|i| Some(series[i]? - trend[i]? - (seasonal[i % period] - seasonal_mean)))
+        .collect();
+    let Some((_, deviation)) = mean_and_deviation(residuals.iter().flatten().copied()) else {
+        return vec![None; n];
+    };
+    if deviation == 0.0 {
+        return vec![None; n];
+    }
+
+    residuals
+        .into_iter()
+        .map(|residual| residual.map(|residual| residual / deviation))
+        .collect()
+}
+
+/// The quantile of Student's t distribution, approximated from the normal quantile with a Cornish-Fisher expansion.
+fn t_quantile(p: f64, degrees_of_freedom: f64) -> f64 {
+    let z = normal_quantile(p);
+    let v = degrees_of_freedom;
+    let (z3, z5, z7, z9) = (z.powi(3), z.powi(5), z.powi(7), z.powi(9));
+    z + (z3 + z) / (4.0 * v)
+        + (5.0 * z5 + 16.0 * z3 + 3.0 * z) / (96.0 * v.powi(2))
+        + (3.0 * z7 + 19.0 * z5 + 17.0 * z3 - 15.0 * z) / (384.0 * v.powi(3))
+        + (79.0 * z9 + 776.0 * z7 + 1482.0 * z5 - 1920.0 * z3 - 945.0 * z) / (92160.0 * v.powi(4))
+}
+
+/// The quantile of the standard normal distribution, with Acklam's rational approximation.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.024_25;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_anomalies() {
+        assert!((normal_quantile(0.975) - 1.959_964).abs() < 1e-5);
+        assert!((t_quantile(0.975, 10.0) - 2.228_139).abs() < 1e-3);
+
+        let mut series: Vec<Option<f64>> = (0..48)
+            .map(|i| Some(10.0 + f64::from(i % 4) + f64::from(i % 3) * 0.1))
+            .collect();
+        series[30] = Some(40.0);
+        series[45] = None;
+
+        let zscores = rolling_zscores(&series, 8);
+        assert_eq!(zscores[0], None);
+        assert_eq!(zscores[45], None);
+        assert!(zscores[30].is_some_and(|z| z > 10.0));
+        assert!(zscores[29].is_some_and(|z| z.abs() < 3.0));
+
+        let outliers = esd_outliers(&series, 5, DEFAULT_ESD_ALPHA);
+        assert_eq!(outliers[30], Some(true));
+        assert_eq!(outliers[45], None);
+        assert_eq!(outliers.iter().flatten().filter(|o| **o).count(), 1);
+
+        let seasonal = seasonal_scores(&series, 4);
+        let largest = seasonal
+            .iter()
+            .enumerate()
+            .filter_map(|(i, score)| Some((i, score?.abs())))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i);
+        assert_eq!(largest, Some(30));
+    }
+}
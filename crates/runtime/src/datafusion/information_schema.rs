@@ -0,0 +1,379 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The key constraint views of `information_schema`: `table_constraints` and `key_column_usage`.
+//!
+//! DataFusion provides `tables`, `views`, `columns` and `schemata` itself, but resolves every table in
+//! `information_schema` from a fixed list. The key constraint views are registered in the runtime schema instead, and
+//! references to them in `information_schema` are rewritten to it before planning.
+
+use std::{any::Any, ops::ControlFlow, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Int32Builder, RecordBatch, StringBuilder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use async_trait::async_trait;
+use datafusion::{
+    catalog::{CatalogProvider, CatalogProviderList},
+    common::Constraint,
+    datasource::{TableProvider, TableType},
+    error::Result,
+    execution::context::SessionState,
+    logical_expr::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+    sql::{
+        parser::Statement as DFStatement,
+        sqlparser::ast::{visit_relations_mut, Ident, ObjectName},
+    },
+};
+
+use super::{SPICE_DEFAULT_CATALOG, SPICE_RUNTIME_SCHEMA};
+
+const INFORMATION_SCHEMA: &str = "information_schema";
+const TABLE_CONSTRAINTS: &str = "table_constraints";
+const KEY_COLUMN_USAGE: &str = "key_column_usage";
+
+/// A primary key or unique constraint of a registered table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyConstraint {
+    pub(crate) catalog: String,
+    pub(crate) schema: String,
+    pub(crate) table: String,
+    pub(crate) name: String,
+    pub(crate) primary_key: bool,
+    pub(crate) columns: Vec<String>,
+}
+
+impl KeyConstraint {
+    fn new(
+        catalog: &str,
+        schema: &str,
+        table: &str,
+        primary_key: bool,
+        columns: Vec<String>,
+    ) -> Self {
+        // Named the way PostgreSQL names constraints that weren't given a name.
+        let name = if primary_key {
+            format!("{table}_pkey")
+        } else {
+            format!("{table}_{}_key", columns.join("_"))
+        };
+
+        Self {
+            catalog: catalog.to_string(),
+            schema: schema.to_string(),
+            table: table.to_string(),
+            name,
+            primary_key,
+            columns,
+        }
+    }
+
+    fn constraint_type(&self) -> &'static str {
+        if self.primary_key {
+            "PRIMARY KEY"
+        } else {
+            "UNIQUE"
+        }
+    }
+}
+
+/// Returns the key constraints of every table in `catalog_list`.
+pub(crate) async fn key_constraints(
+    catalog_list: &dyn CatalogProviderList,
+) -> Result<Vec<KeyConstraint>> {
+    let mut key_constraints = vec![];
+    for catalog_name in catalog_list.catalog_names() {
+        let Some(catalog) = catalog_list.catalog(&catalog_name) else {
+            continue;
+        };
+
+        for schema_name in catalog.schema_names() {
+            let Some(schema) = catalog.schema(&schema_name) else {
+                continue;
+            };
+
+            for table_name in schema.table_names() {
+                let Some(table) = schema.table(&table_name).await? else {
+                    continue;
+                };
+                let Some(constraints) = table.constraints() else {
+                    continue;
+                };
+
+                let table_schema = table.schema();
+                for constraint in constraints.iter() {
+                    let (primary_key, indices) = match constraint {
+                        Constraint::PrimaryKey(indices) => (true, indices),
+                        Constraint::Unique(indices) => (false, indices),
+                    };
+                    let columns = indices
+                        .iter()
+                        .filter_map(|i| table_schema.fields().get(*i))
+                        .map(|field| field.name().clone())
+                        .collect();
+
+                    key_constraints.push(KeyConstraint::new(
+                        &catalog_name,
+                        &schema_name,
+                        &table_name,
+                        primary_key,
+                        columns,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(key_constraints)
+}
+
+/// Registers the key constraint views in the runtime schema of `catalog`.
+pub(crate) fn register_key_constraint_views(catalog: &dyn CatalogProvider) -> Result<()> {
+    let Some(schema) = catalog.schema(SPICE_RUNTIME_SCHEMA) else {
+        return Ok(());
+    };
+
+    schema.register_table(
+        TABLE_CONSTRAINTS.to_string(),
+        Arc::new(KeyConstraintsTable::table_constraints()),
+    )?;
+    schema.register_table(
+        KEY_COLUMN_USAGE.to_string(),
+        Arc::new(KeyConstraintsTable::key_column_usage()),
+    )?;
+
+    Ok(())
+}
+
+/// Rewrites references to `information_schema.table_constraints` and `information_schema.key_column_usage` to the
+/// views registered in the runtime schema.
+pub(crate) fn rewrite_key_constraint_views(statement: &mut DFStatement) {
+    match statement {
+        DFStatement::Statement(statement) => {
+            let _ = visit_relations_mut(statement.as_mut(), |name: &mut ObjectName| {
+                if let Some(view) = key_constraint_view(name) {
+                    *name = ObjectName(vec![
+                        Ident::new(SPICE_DEFAULT_CATALOG),
+                        Ident::new(SPICE_RUNTIME_SCHEMA),
+                        Ident::new(view),
+                    ]);
+                }
+                ControlFlow::<()>::Continue(())
+            });
+        }
+        DFStatement::Explain(explain) => rewrite_key_constraint_views(&mut explain.statement),
+        _ => {}
+    }
+}
+
+fn key_constraint_view(name: &ObjectName) -> Option<&'static str> {
+    let normalized = |ident: &Ident| match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_ascii_lowercase(),
+    };
+
+    let [.., schema, table] = &name.0[..] else {
+        return None;
+    };
+    if name.0.len() > 3 || normalized(schema) != INFORMATION_SCHEMA {
+        return None;
+    }
+
+    match normalized(table).as_str() {
+        TABLE_CONSTRAINTS => Some(TABLE_CONSTRAINTS),
+        KEY_COLUMN_USAGE => Some(KEY_COLUMN_USAGE),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum KeyConstraintsView {
+    TableConstraints,
+    KeyColumnUsage,
+}
+
+/// Lists the key constraints of the tables registered when it is scanned.
+struct KeyConstraintsTable {
+    view: KeyConstraintsView,
+    schema: SchemaRef,
+}
+
+impl KeyConstraintsTable {
+    fn table_constraints() -> Self {
+        Self {
+            view: KeyConstraintsView::TableConstraints,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("constraint_catalog", DataType::Utf8, false),
+                Field::new("constraint_schema", DataType::Utf8, false),
+                Field::new("constraint_name", DataType::Utf8, false),
+                Field::new("table_catalog", DataType::Utf8, false),
+                Field::new("table_schema", DataType::Utf8, false),
+                Field::new("table_name", DataType::Utf8, false),
+                Field::new("constraint_type", DataType::Utf8, false),
+                Field::new("is_deferrable", DataType::Utf8, false),
+                Field::new("initially_deferred", DataType::Utf8, false),
+            ])),
+        }
+    }
+
+    fn key_column_usage() -> Self {
+        Self {
+            view: KeyConstraintsView::KeyColumnUsage,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("constraint_catalog", DataType::Utf8, false),
+                Field::new("constraint_schema", DataType::Utf8, false),
+                Field::new("constraint_name", DataType::Utf8, false),
+                Field::new("table_catalog", DataType::Utf8, false),
+                Field::new("table_schema", DataType::Utf8, false),
+                Field::new("table_name", DataType::Utf8, false),
+                Field::new("column_name", DataType::Utf8, false),
+                Field::new("ordinal_position", DataType::Int32, false),
+                Field::new("position_in_unique_constraint", DataType::Int32, true),
+            ])),
+        }
+    }
+
+    fn record_batch(&self, key_constraints: &[KeyConstraint]) -> Result<RecordBatch> {
+        let mut catalogs = StringBuilder::new();
+        let mut schemas = StringBuilder::new();
+        let mut names = StringBuilder::new();
+        let mut tables = StringBuilder::new();
+
+        let columns: Vec<ArrayRef> = match self.view {
+            KeyConstraintsView::TableConstraints => {
+                let mut constraint_types = StringBuilder::new();
+                let mut deferrable = StringBuilder::new();
+                for key_constraint in key_constraints {
+                    catalogs.append_value(&key_constraint.catalog);
+                    schemas.append_value(&key_constraint.schema);
+                    names.append_value(&key_constraint.name);
+                    tables.append_value(&key_constraint.table);
+                    constraint_types.append_value(key_constraint.constraint_type());
+                    deferrable.append_value("NO");
+                }
+
+                let catalogs = Arc::new(catalogs.finish());
+                let schemas = Arc::new(schemas.finish());
+                let deferrable = Arc::new(deferrable.finish());
+                vec![
+                    Arc::clone(&catalogs) as ArrayRef,
+                    Arc::clone(&schemas) as ArrayRef,
+                    Arc::new(names.finish()),
+                    catalogs,
+                    schemas,
+                    Arc::new(tables.finish()),
+                    Arc::new(constraint_types.finish()),
+                    Arc::clone(&deferrable) as ArrayRef,
+                    deferrable,
+                ]
+            }
+            KeyConstraintsView::KeyColumnUsage => {
+                let mut column_names = StringBuilder::new();
+                let mut ordinal_positions = Int32Builder::new();
+                let mut unique_positions = Int32Builder::new();
+                for key_constraint in key_constraints {
+                    for (position, column) in (1..).zip(&key_constraint.columns) {
+                        catalogs.append_value(&key_constraint.catalog);
+                        schemas.append_value(&key_constraint.schema);
+                        names.append_value(&key_constraint.name);
+                        tables.append_value(&key_constraint.table);
+                        column_names.append_value(column);
+                        ordinal_positions.append_value(position);
+                        // Only set for foreign keys, which tables don't have.
+                        unique_positions.append_null();
+                    }
+                }
+
+                let catalogs = Arc::new(catalogs.finish());
+                let schemas = Arc::new(schemas.finish());
+                vec![
+                    Arc::clone(&catalogs) as ArrayRef,
+                    Arc::clone(&schemas) as ArrayRef,
+                    Arc::new(names.finish()),
+                    catalogs,
+                    schemas,
+                    Arc::new(tables.finish()),
+                    Arc::new(column_names.finish()),
+                    Arc::new(ordinal_positions.finish()),
+                    Arc::new(unique_positions.finish()),
+                ]
+            }
+        };
+
+        Ok(RecordBatch::try_new(Arc::clone(&self.schema), columns)?)
+    }
+}
+
+#[async_trait]
+impl TableProvider for KeyConstraintsTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let key_constraints = key_constraints(state.catalog_list().as_ref()).await?;
+        let batch = self.record_batch(&key_constraints)?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::sql::parser::DFParser;
+
+    use super::*;
+
+    #[test]
+    fn rewrites_key_constraint_views() {
+        let mut statements = DFParser::parse_sql(
+            "SELECT k.column_name FROM information_schema.key_column_usage k \
+             JOIN spice.INFORMATION_SCHEMA.Table_Constraints c ON k.constraint_name = c.constraint_name \
+             JOIN information_schema.tables t ON t.table_name = c.table_name",
+        )
+        .expect("valid SQL");
+        let statement = statements.front_mut().expect("one statement");
+
+        rewrite_key_constraint_views(statement);
+
+        assert_eq!(
+            statement.to_string(),
+            "SELECT k.column_name FROM spice.runtime.key_column_usage AS k \
+             JOIN spice.runtime.table_constraints AS c ON k.constraint_name = c.constraint_name \
+             JOIN information_schema.tables AS t ON t.table_name = c.table_name"
+        );
+    }
+}
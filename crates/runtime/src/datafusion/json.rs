@@ -0,0 +1,283 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Functions for JSON stored in strings:
+//!
+//! - `json_extract(json, path)`: the JSON text of the value at `path`.
+//! - `json_extract_scalar(json, path)`: the string, number or boolean at `path` as a string.
+//! - `json_keys(json[, path])`: the keys of the object at `path`.
+//! - `json_array_elements(json[, path])`: the JSON text of each element of the array at `path`.
+//!
+//! Paths are a subset of JSONPath: `$`, followed by `.key`, `["key"]` or `[index]` for each step. The result is null
+//! when the JSON is invalid or doesn't have the expected value at `path`.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, ListBuilder, StringArray, StringBuilder},
+    compute::cast,
+    datatypes::DataType,
+};
+use datafusion::{
+    common::{exec_err, DataFusionError, Result},
+    execution::context::SessionContext,
+    logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, TypeSignature, Volatility},
+};
+use serde_json::Value;
+
+/// Registers the JSON functions in `ctx`.
+pub fn register_json_udfs(ctx: &SessionContext) {
+    for kind in [
+        JsonFunctionKind::Extract,
+        JsonFunctionKind::ExtractScalar,
+        JsonFunctionKind::Keys,
+        JsonFunctionKind::ArrayElements,
+    ] {
+        ctx.register_udf(ScalarUDF::new_from_impl(JsonFunction::new(kind)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonFunctionKind {
+    Extract,
+    ExtractScalar,
+    Keys,
+    ArrayElements,
+}
+
+#[derive(Debug)]
+struct JsonFunction {
+    kind: JsonFunctionKind,
+    signature: Signature,
+}
+
+impl JsonFunction {
+    fn new(kind: JsonFunctionKind) -> Self {
+        let with_path = TypeSignature::Exact(vec![DataType::Utf8, DataType::Utf8]);
+        let signature = match kind {
+            JsonFunctionKind::Extract | JsonFunctionKind::ExtractScalar => with_path,
+            JsonFunctionKind::Keys | JsonFunctionKind::ArrayElements => {
+                TypeSignature::OneOf(vec![TypeSignature::Exact(vec![DataType::Utf8]), with_path])
+            }
+        };
+
+        Self {
+            kind,
+            signature: Signature::new(signature, Volatility::Immutable),
+        }
+    }
+
+    /// Returns the result for the value at the path, or `None` if it is null.
+    fn evaluate(&self, value: &Value) -> Option<JsonResult> {
+        match (self.kind, value) {
+            (JsonFunctionKind::Extract, value) => Some(JsonResult::Text(value.to_string())),
+            (JsonFunctionKind::ExtractScalar, Value::String(s)) => {
+                Some(JsonResult::Text(s.clone()))
+            }
+            (JsonFunctionKind::ExtractScalar, Value::Number(_) | Value::Bool(_)) => {
+                Some(JsonResult::Text(value.to_string()))
+            }
+            (JsonFunctionKind::Keys, Value::Object(object)) => {
+                Some(JsonResult::List(object.keys().cloned().collect()))
+            }
+            (JsonFunctionKind::ArrayElements, Value::Array(elements)) => Some(JsonResult::List(
+                elements.iter().map(Value::to_string).collect(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+enum JsonResult {
+    Text(String),
+    List(Vec<String>),
+}
+
+impl ScalarUDFImpl for JsonFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        match self.kind {
+            JsonFunctionKind::Extract => "json_extract",
+            JsonFunctionKind::ExtractScalar => "json_extract_scalar",
+            JsonFunctionKind::Keys => "json_keys",
+            JsonFunctionKind::ArrayElements => "json_array_elements",
+        }
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(match self.kind {
+            JsonFunctionKind::Extract | JsonFunctionKind::ExtractScalar => DataType::Utf8,
+            JsonFunctionKind::Keys | JsonFunctionKind::ArrayElements => {
+                DataType::new_list(DataType::Utf8, true)
+            }
+        })
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let json = as_strings(&args[0])?;
+        let paths = args.get(1).map(as_strings).transpose()?;
+
+        let mut results = Vec::with_capacity(json.len());
+        for row in 0..json.len() {
+            let path = match &paths {
+                Some(paths) if paths.is_null(row) => None,
+                Some(paths) => Some(paths.value(row)),
+                None => Some("$"),
+            };
+            let (Some(path), false) = (path, json.is_null(row)) else {
+                results.push(None);
+                continue;
+            };
+
+            let Some(path) = parse_path(path) else {
+                return exec_err!("{} received an invalid JSON path: {path}", self.name());
+            };
+            let result = serde_json::from_str::<Value>(json.value(row))
+                .ok()
+                .and_then(|value| lookup(&value, &path).and_then(|v| self.evaluate(v)));
+            results.push(result);
+        }
+
+        let array: ArrayRef = match self.kind {
+            JsonFunctionKind::Extract | JsonFunctionKind::ExtractScalar => Arc::new(
+                StringArray::from_iter(results.into_iter().map(|r| match r {
+                    Some(JsonResult::Text(text)) => Some(text),
+                    _ => None,
+                })),
+            ),
+            JsonFunctionKind::Keys | JsonFunctionKind::ArrayElements => {
+                let mut builder = ListBuilder::new(StringBuilder::new());
+                for result in results {
+                    match result {
+                        Some(JsonResult::List(items)) => {
+                            for item in items {
+                                builder.values().append_value(item);
+                            }
+                            builder.append(true);
+                        }
+                        _ => builder.append(false),
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+        };
+
+        Ok(ColumnarValue::Array(array))
+    }
+}
+
+fn as_strings(array: &ArrayRef) -> Result<StringArray> {
+    let array = cast(array, &DataType::Utf8)?;
+    array
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .cloned()
+        .ok_or_else(|| DataFusionError::Internal("Expected a string array".to_string()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PathStep {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses a JSONPath like `$.store.books[0]["title"]`, returning `None` if it is invalid.
+fn parse_path(path: &str) -> Option<Vec<PathStep>> {
+    let mut rest = path.trim().strip_prefix('$')?;
+    let mut steps = vec![];
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            if end == 0 {
+                return None;
+            }
+            steps.push(PathStep::Key(after_dot[..end].to_string()));
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']')?;
+            let inner = after_bracket[..end].trim();
+            let quoted = inner
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')));
+            steps.push(match quoted {
+                Some(key) => PathStep::Key(key.to_string()),
+                None => PathStep::Index(inner.parse().ok()?),
+            });
+            rest = &after_bracket[end + 1..];
+        } else {
+            return None;
+        }
+    }
+
+    Some(steps)
+}
+
+fn lookup<'a>(value: &'a Value, path: &[PathStep]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, step| match step {
+        PathStep::Key(key) => value.get(key),
+        PathStep::Index(index) => value.get(index),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_values_at_paths() {
+        let value: Value = serde_json::from_str(
+            r#"{"store": {"books": [{"title": "Dune", "price": 9.5}], "open": true}}"#,
+        )
+        .expect("valid JSON");
+
+        let path = parse_path(r#"$.store.books[0]["title"]"#).expect("valid path");
+        assert_eq!(
+            path,
+            vec![
+                PathStep::Key("store".to_string()),
+                PathStep::Key("books".to_string()),
+                PathStep::Index(0),
+                PathStep::Key("title".to_string()),
+            ]
+        );
+        assert_eq!(lookup(&value, &path), Some(&Value::from("Dune")));
+
+        let extract_scalar = JsonFunction::new(JsonFunctionKind::ExtractScalar);
+        let price = lookup(
+            &value,
+            &parse_path("$.store.books[0].price").expect("valid path"),
+        );
+        assert!(matches!(
+            price.and_then(|v| extract_scalar.evaluate(v)),
+            Some(JsonResult::Text(text)) if text == "9.5"
+        ));
+        let store = lookup(&value, &parse_path("$.store").expect("valid path"));
+        assert!(store.and_then(|v| extract_scalar.evaluate(v)).is_none());
+
+        assert!(parse_path("store.books").is_none());
+        assert!(parse_path("$.store..books").is_none());
+        assert!(parse_path("$[x]").is_none());
+    }
+}
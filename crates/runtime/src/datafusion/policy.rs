@@ -0,0 +1,518 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Enforces the row and column level access policies of datasets by rewriting the logical plan of a query.
+//!
+//! The masks of a policy are applied last, replacing the columns of the scan with their masked values under the same
+//! names, so the query filters, joins and aggregates on the masked values.
+
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{AsArray, StringArray},
+    datatypes::DataType,
+};
+use datafusion::{
+    common::{
+        tree_node::{Transformed, TreeNode},
+        Column, DFSchema, ScalarValue,
+    },
+    error::DataFusionError,
+    execution::context::SessionState,
+    logical_expr::{
+        ColumnarValue, Expr, LogicalPlan, LogicalPlanBuilder, ScalarUDF, ScalarUDFImpl, Signature,
+        Volatility,
+    },
+    sql::TableReference,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use snafu::prelude::*;
+use spicepod::component::dataset::policy::{Mask, Policy};
+
+use crate::auth::Principal;
+
+use super::{SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA};
+
+static PRINCIPAL_PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\$\{\s*principal\.([A-Za-z0-9_\-:.]+)\s*\}")
+        .unwrap_or_else(|_| panic!("Invalid principal placeholder regex"))
+});
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Access to dataset {dataset} is denied"))]
+    AccessDenied { dataset: String },
+
+    #[snafu(display(
+        "Access to dataset {dataset} is denied: its row filter requires the {claim} claim"
+    ))]
+    MissingClaim { dataset: String, claim: String },
+
+    #[snafu(display("Unable to apply the row filter of dataset {dataset}: {source}"))]
+    InvalidRowFilter {
+        dataset: String,
+        source: DataFusionError,
+    },
+
+    #[snafu(display("Unable to mask column {column} of dataset {dataset}: {source}"))]
+    InvalidMask {
+        dataset: String,
+        column: String,
+        source: DataFusionError,
+    },
+
+    #[snafu(display("The query references a column that is not accessible: {source}"))]
+    ColumnNotAccessible { source: DataFusionError },
+
+    #[snafu(display("Unable to apply access policies: {source}"))]
+    UnableToRewritePlan { source: DataFusionError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The access policies of datasets, keyed by their fully resolved table name.
+pub type DatasetPolicies = HashMap<String, Arc<Vec<Policy>>>;
+
+#[must_use]
+pub fn policy_key(table: &TableReference) -> String {
+    table
+        .clone()
+        .resolve(SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA)
+        .to_string()
+}
+
+/// Rewrites `plan` so that every scan of a dataset with policies only returns the rows and columns the
+/// principal can access, including scans inside views and subqueries. `hash` masks are keyed by `mask_key`.
+pub fn apply_policies(
+    plan: LogicalPlan,
+    policies: &DatasetPolicies,
+    mask_key: &[u8],
+    principal: Option<&Principal>,
+    state: &SessionState,
+) -> Result<LogicalPlan> {
+    if policies.is_empty() {
+        return Ok(plan);
+    }
+
+    match rewrite(plan, policies, mask_key, principal, state) {
+        Ok(transformed) => Ok(transformed.data),
+        Err(DataFusionError::External(e)) => match e.downcast::<Error>() {
+            Ok(e) => Err(*e),
+            Err(e) => Err(Error::UnableToRewritePlan {
+                source: DataFusionError::External(e),
+            }),
+        },
+        Err(e) => Err(Error::UnableToRewritePlan { source: e }),
+    }
+}
+
+fn rewrite(
+    plan: LogicalPlan,
+    policies: &DatasetPolicies,
+    mask_key: &[u8],
+    principal: Option<&Principal>,
+    state: &SessionState,
+) -> Result<Transformed<LogicalPlan>, DataFusionError> {
+    let mut rewritten = false;
+
+    plan.transform_up_with_subqueries(|node| {
+        let scan = match node {
+            LogicalPlan::TableScan(scan) => scan,
+            node if !rewritten => return Ok(Transformed::no(node)),
+            // The inputs of the node changed, so the columns it references must still exist.
+            node => {
+                return node
+                    .recompute_schema()
+                    .map(Transformed::yes)
+                    .map_err(|source| external(Error::ColumnNotAccessible { source }))
+            }
+        };
+
+        // Views are only inlined during analysis, so check the datasets they read from here.
+        if let Some(view_plan) = scan.source.get_logical_plan() {
+            let view = rewrite(view_plan.clone(), policies, mask_key, principal, state)?;
+            if !view.transformed {
+                return Ok(Transformed::no(LogicalPlan::TableScan(scan)));
+            }
+            rewritten = true;
+            return LogicalPlanBuilder::from(view.data)
+                .alias(scan.table_name.clone())?
+                .build()
+                .map(Transformed::yes);
+        }
+
+        // A sample or a version of a dataset has the policies of the dataset.
+        let dataset = policy_key(
+            &super::sample::sampled_table(&scan)
+                .or_else(|| super::time_travel::versioned_table(&scan))
+                .unwrap_or_else(|| scan.table_name.clone()),
+        );
+        let Some(dataset_policies) = policies.get(&dataset) else {
+            return Ok(Transformed::no(LogicalPlan::TableScan(scan)));
+        };
+
+        let policy = select_policy(dataset_policies, principal).ok_or_else(|| {
+            external(Error::AccessDenied {
+                dataset: dataset.clone(),
+            })
+        })?;
+
+        let table_name = scan.table_name.clone();
+        let schema = Arc::clone(&scan.projected_schema);
+        let mut builder = LogicalPlanBuilder::from(LogicalPlan::TableScan(scan));
+
+        if let Some(row_filter) = &policy.row_filter {
+            let sql = bind_principal(row_filter, principal, &dataset).map_err(external)?;
+            builder = state
+                .create_logical_expr(&sql, &schema)
+                .and_then(|predicate| builder.filter(predicate))
+                .map_err(|source| {
+                    external(Error::InvalidRowFilter {
+                        dataset: dataset.clone(),
+                        source,
+                    })
+                })?;
+        }
+
+        if let Some(columns) = &policy.columns {
+            let columns = schema
+                .columns()
+                .into_iter()
+                .filter(|column| columns.iter().any(|allowed| allowed == &column.name))
+                .map(|column| Expr::Column(Column::new(Some(table_name.clone()), column.name)));
+            builder = builder.project(columns)?;
+        }
+
+        if !policy.masks.is_empty() {
+            let schema = Arc::clone(builder.schema());
+            let columns = schema
+                .columns()
+                .into_iter()
+                .map(|column| match policy.masks.get(&column.name) {
+                    Some(mask) => mask_column(*mask, &column, &schema, mask_key, state)
+                        .map(|masked| masked.alias_qualified(column.relation.clone(), &column.name))
+                        .map_err(|source| {
+                            external(Error::InvalidMask {
+                                dataset: dataset.clone(),
+                                column: column.name.clone(),
+                                source,
+                            })
+                        }),
+                    None => Ok(Expr::Column(column)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            builder = builder.project(columns)?;
+        }
+
+        rewritten = true;
+        builder.build().map(Transformed::yes)
+    })
+}
+
+/// The expression replacing the values of `column` with their masked values.
+fn mask_column(
+    mask: Mask,
+    column: &Column,
+    schema: &DFSchema,
+    mask_key: &[u8],
+    state: &SessionState,
+) -> Result<Expr, DataFusionError> {
+    let value = format!("CAST(\"{}\" AS VARCHAR)", column.name.replace('"', "\"\""));
+    let sql = match mask {
+        Mask::Nullify => {
+            let field = schema.field_from_column(column)?;
+            return ScalarValue::try_from(field.data_type()).map(Expr::Literal);
+        }
+        // The key is part of the expression rather than a registered function, so queries can't hash guesses with it.
+        Mask::Hash => {
+            let value = state.create_logical_expr(&value, schema)?;
+            return Ok(ScalarUDF::new_from_impl(HashMask::new(mask_key)).call(vec![value]));
+        }
+        Mask::Redact => format!("CASE WHEN {value} IS NULL THEN NULL ELSE '[REDACTED]' END"),
+        Mask::Partial => format!(
+            "CASE WHEN character_length({value}) > 4 \
+             THEN concat(repeat('*', character_length({value}) - 4), substr({value}, character_length({value}) - 3)) \
+             ELSE repeat('*', character_length({value})) END"
+        ),
+    };
+    state.create_logical_expr(&sql, schema)
+}
+
+/// The hex encoded HMAC-SHA256 of string values, keyed by the mask key of the runtime.
+#[derive(Debug)]
+struct HashMask {
+    key: Vec<u8>,
+    signature: Signature,
+}
+
+impl HashMask {
+    fn new(key: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            signature: Signature::exact(vec![DataType::Utf8], Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for HashMask {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "mask_hash"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let hashes = args[0]
+            .as_string::<i32>()
+            .iter()
+            .map(|value| value.map(|value| hex(&hmac_sha256(&self.key, value.as_bytes()))))
+            .collect::<StringArray>();
+        Ok(ColumnarValue::Array(Arc::new(hashes)))
+    }
+}
+
+/// HMAC-SHA256, as in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0_u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Returns the first policy whose conditions are all satisfied by the claims of the principal.
+fn select_policy<'a>(policies: &'a [Policy], principal: Option<&Principal>) -> Option<&'a Policy> {
+    policies.iter().find(|policy| {
+        policy.when.iter().all(|(claim, expected)| {
+            principal
+                .and_then(|principal| principal.claim(claim))
+                .is_some_and(|value| &value == expected)
+        })
+    })
+}
+
+/// Replaces the `${principal.<claim>}` placeholders of a row filter with the claim values as string literals.
+fn bind_principal(
+    row_filter: &str,
+    principal: Option<&Principal>,
+    dataset: &str,
+) -> Result<String> {
+    let mut missing_claim = None;
+    let bound = PRINCIPAL_PLACEHOLDER.replace_all(row_filter, |captures: &regex::Captures| {
+        let claim = &captures[1];
+        match principal.and_then(|principal| principal.claim(claim)) {
+            Some(value) => format!("'{}'", value.replace('\'', "''")),
+            None => {
+                missing_claim.get_or_insert_with(|| claim.to_string());
+                String::new()
+            }
+        }
+    });
+
+    if let Some(claim) = missing_claim {
+        return MissingClaimSnafu {
+            dataset: dataset.to_string(),
+            claim,
+        }
+        .fail();
+    }
+
+    Ok(bound.into_owned())
+}
+
+fn external(e: Error) -> DataFusionError {
+    DataFusionError::External(Box::new(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use arrow::array::{ArrayRef, AsArray, Int64Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use datafusion::{dataframe::DataFrame, prelude::SessionContext, sql::TableReference};
+    use serde_json::{json, Map, Value};
+    use spicepod::component::dataset::policy::{Mask, Policy};
+
+    use crate::auth::Principal;
+
+    use super::{
+        apply_policies, bind_principal, hex, hmac_sha256, policy_key, select_policy,
+        DatasetPolicies,
+    };
+
+    fn principal(claims: Value) -> Principal {
+        let claims: Map<String, Value> = serde_json::from_value(claims).expect("claims object");
+        Principal {
+            subject: "alice".to_string(),
+            claims,
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn test_bind_principal_quotes_claims() {
+        let principal = principal(json!({"sub": "alice", "region": "o'hare"}));
+        let bound = bind_principal(
+            "region = ${principal.region} AND owner = ${ principal.sub }",
+            Some(&principal),
+            "orders",
+        )
+        .expect("claims are bound");
+        assert_eq!(bound, "region = 'o''hare' AND owner = 'alice'");
+    }
+
+    #[test]
+    fn test_bind_principal_missing_claim() {
+        let principal = principal(json!({"sub": "alice"}));
+        assert!(
+            bind_principal("region = ${principal.region}", Some(&principal), "orders").is_err()
+        );
+        assert!(bind_principal("region = ${principal.region}", None, "orders").is_err());
+    }
+
+    #[test]
+    fn test_select_policy_first_match() {
+        let policies = vec![
+            Policy {
+                when: HashMap::from([("role".to_string(), "admin".to_string())]),
+                ..Policy::default()
+            },
+            Policy {
+                columns: Some(vec!["id".to_string()]),
+                ..Policy::default()
+            },
+        ];
+
+        let admin = principal(json!({"role": "admin"}));
+        let analyst = principal(json!({"role": "analyst"}));
+
+        assert_eq!(select_policy(&policies, Some(&admin)), Some(&policies[0]));
+        assert_eq!(select_policy(&policies, Some(&analyst)), Some(&policies[1]));
+        assert_eq!(select_policy(&policies[..1], None), None);
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_masks_columns() {
+        let ctx = SessionContext::new();
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "email",
+                Arc::new(StringArray::from(vec![Some("a@b.co"), None])) as ArrayRef,
+            ),
+            (
+                "ssn",
+                Arc::new(StringArray::from(vec!["123-45-6789", "12"])) as ArrayRef,
+            ),
+            (
+                "phone",
+                Arc::new(StringArray::from(vec![Some("555-0100"), None])) as ArrayRef,
+            ),
+            ("age", Arc::new(Int64Array::from(vec![30, 40])) as ArrayRef),
+        ])
+        .expect("valid batch");
+        ctx.register_batch("people", batch)
+            .expect("table is registered");
+
+        let policies = DatasetPolicies::from([(
+            policy_key(&TableReference::bare("people")),
+            Arc::new(vec![Policy {
+                masks: HashMap::from([
+                    ("email".to_string(), Mask::Hash),
+                    ("ssn".to_string(), Mask::Partial),
+                    ("phone".to_string(), Mask::Redact),
+                    ("age".to_string(), Mask::Nullify),
+                ]),
+                ..Policy::default()
+            }]),
+        )]);
+        let plan = ctx
+            .sql("SELECT email, ssn, phone, age FROM people")
+            .await
+            .expect("valid query")
+            .into_unoptimized_plan();
+        let plan =
+            apply_policies(plan, &policies, b"mask-key", None, &ctx.state()).expect("masks apply");
+        let batches = DataFrame::new(ctx.state(), plan)
+            .collect()
+            .await
+            .expect("query runs");
+
+        let strings = |column: usize| {
+            batches[0]
+                .column(column)
+                .as_string::<i32>()
+                .iter()
+                .map(|value| value.map(ToString::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            strings(0),
+            vec![
+                Some(
+                    "924542cf4c9f27f9780e6f26fd3e84c1c9d5fb72b442804558dca3deeb371cdc".to_string()
+                ),
+                None
+            ]
+        );
+        assert_eq!(
+            strings(1),
+            vec![Some("*******6789".to_string()), Some("**".to_string())]
+        );
+        assert_eq!(strings(2), vec![Some("[REDACTED]".to_string()), None]);
+        assert_eq!(batches[0].column(3).null_count(), 2);
+    }
+}
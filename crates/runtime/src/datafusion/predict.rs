@@ -0,0 +1,132 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! `predict(model, feature1[, feature2, ...])` returns the prediction of a tabular model of the spicepod for each
+//! row, i.e. `SELECT predict('churn', tenure, monthly_charges) FROM customers`.
+//!
+//! The features of each batch of rows are passed to the model as one Arrow batch, in the order of the arguments,
+//! for ONNX models with an input of the form `[batch_size, num_features]`.
+
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{Array, RecordBatch},
+    compute::cast,
+    datatypes::{DataType, Field, Schema},
+};
+use datafusion::{
+    common::{exec_datafusion_err, exec_err, plan_err, Result, ScalarValue},
+    logical_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility},
+};
+use model_components::model::Model;
+use tokio::{runtime::Handle, sync::RwLock};
+
+pub struct Predict {
+    models: Arc<RwLock<HashMap<String, Model>>>,
+    signature: Signature,
+}
+
+impl std::fmt::Debug for Predict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Predict")
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Predict {
+    #[must_use]
+    pub fn new(models: Arc<RwLock<HashMap<String, Model>>>) -> Self {
+        Self {
+            models,
+            signature: Signature::variadic_any(Volatility::Stable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for Predict {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "predict"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, args: &[DataType]) -> Result<DataType> {
+        match args {
+            [DataType::Utf8, _, ..] => Ok(DataType::Float32),
+            _ => plan_err!(
+                "predict expects the name of a model and its features: predict(model, feature1[, feature2, ...])"
+            ),
+        }
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let Some(ColumnarValue::Scalar(ScalarValue::Utf8(Some(model)))) = args.first() else {
+            return exec_err!("predict expects the name of the model as a string literal");
+        };
+        let all_scalars = args
+            .iter()
+            .all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+
+        let features = ColumnarValue::values_to_arrays(&args[1..])?;
+        let num_rows = features.first().map_or(0, Array::len);
+        let schema = Schema::new(
+            features
+                .iter()
+                .enumerate()
+                .map(|(i, feature)| Field::new(format!("x{i}"), feature.data_type().clone(), true))
+                .collect::<Vec<_>>(),
+        );
+        let batch = RecordBatch::try_new(Arc::new(schema), features)?;
+
+        let predictions = tokio::task::block_in_place(|| {
+            Handle::current().block_on(async {
+                let models = self.models.read().await;
+                let Some(loaded) = models.get(model) else {
+                    return exec_err!("The model {model} was not found");
+                };
+                loaded
+                    .run(vec![batch])
+                    .map_err(|e| exec_datafusion_err!("Unable to run {model}: {e}"))
+            })
+        })?;
+
+        let Some(predictions) = predictions.column_by_name("y") else {
+            return exec_err!("The model {model} didn't return predictions");
+        };
+        if predictions.len() != num_rows {
+            return exec_err!(
+                "The model {model} returned {} predictions for {num_rows} rows",
+                predictions.len()
+            );
+        }
+        let predictions = cast(predictions, &DataType::Float32)?;
+
+        if all_scalars {
+            return Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                &predictions,
+                0,
+            )?));
+        }
+        Ok(ColumnarValue::Array(predictions))
+    }
+}
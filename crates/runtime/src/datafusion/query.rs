@@ -16,25 +16,47 @@ limitations under the License.
 
 use std::{collections::HashSet, string, sync::Arc, time::SystemTime};
 
-use arrow::datatypes::Schema;
+use arrow::datatypes::{DataType, Field, Schema};
 use arrow_tools::schema::verify_schema;
 use cache::{
     cache_is_enabled_for_plan, get_logical_plan_input_tables, to_cached_record_batch_stream,
     QueryResult,
 };
 use datafusion::{
+    common::ParamValues,
+    dataframe::DataFrame,
+    datasource::source_as_provider,
     error::DataFusionError,
     execution::{context::SQLOptions, SendableRecordBatchStream},
-    physical_plan::{memory::MemoryStream, stream::RecordBatchStreamAdapter},
+    logical_expr::LogicalPlan,
+    physical_plan::{
+        execute_stream, memory::MemoryStream, stream::RecordBatchStreamAdapter,
+        ExecutionPlanProperties,
+    },
+    sql::TableReference,
 };
 use error_code::ErrorCode;
 use snafu::Snafu;
 use tokio::time::Instant;
+use tracing::{info_span, instrument, Instrument};
 use uuid::Uuid;
 
+use crate::accelerated_table::AcceleratedTable;
+use crate::audit::{AuditAction, AuditRecord};
+use crate::auth::Principal;
+use crate::connector_health;
+use crate::events::{self, QueryFinished, QueryStarted, RuntimeEvent};
+use crate::execution_plan::{query_comment, scan_quota};
+use crate::quotas;
+use crate::shutdown::{self, InFlight};
+
+pub mod async_query;
 pub mod builder;
+pub(crate) mod copy;
+pub mod labels;
 #[allow(clippy::module_name_repetitions)]
 pub mod query_history;
+mod show;
 #[allow(clippy::module_name_repetitions)]
 pub use builder::QueryBuilder;
 
@@ -61,12 +83,39 @@ pub enum Error {
 
     #[snafu(display("Schema mismatch: {source}"))]
     SchemaMismatch { source: arrow_tools::schema::Error },
+
+    #[snafu(display(
+        "The query plan has {actual} partitions, but the request expected {expected}. Retry the request."
+    ))]
+    PartitionCountMismatch { expected: usize, actual: usize },
+
+    #[snafu(display("Invalid partitions requested: {reason}"))]
+    InvalidPartitions { reason: String },
+
+    #[snafu(display("{source}"))]
+    AccessDenied {
+        source: crate::datafusion::policy::Error,
+    },
+
+    #[snafu(display("{source}"))]
+    SourceUnavailable {
+        source: crate::connector_health::Error,
+    },
+
+    #[snafu(display("The runtime is shutting down and no longer accepts queries"))]
+    ShuttingDown,
 }
 
 #[derive(Debug, Clone)]
 pub enum Protocol {
     Http,
     Flight,
+    /// A scheduled job of the spicepod.
+    Job,
+    /// The check of an alert of the spicepod.
+    Alert,
+    /// A test of the spicepod, run with `POST /v1/tests/run`.
+    Test,
 }
 
 impl std::fmt::Display for Protocol {
@@ -74,6 +123,9 @@ impl std::fmt::Display for Protocol {
         match self {
             Protocol::Http => write!(f, "http"),
             Protocol::Flight => write!(f, "flight"),
+            Protocol::Job => write!(f, "job"),
+            Protocol::Alert => write!(f, "alert"),
+            Protocol::Test => write!(f, "test"),
         }
     }
 }
@@ -94,7 +146,50 @@ pub struct Query {
     error_code: Option<ErrorCode>,
     timer: Instant,
     datasets: Arc<HashSet<String>>,
+    /// The federated datasets the query reads from their sources, whose connectors record its outcome.
+    connectors: Vec<TableReference>,
+    /// Keeps the runtime from exiting while the query runs, until its results have been streamed.
+    _in_flight: InFlight,
     protocol: Protocol,
+    partitions: Option<QueryPartitions>,
+    principal: Option<Principal>,
+    labels: labels::QueryLabels,
+    /// The values of the `$1`, `$2`, ... placeholders of a prepared statement.
+    parameters: Option<ParamValues>,
+}
+
+/// Selects a subset of the output partitions of a query's physical plan to execute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryPartitions {
+    /// The total number of output partitions the plan is expected to have.
+    pub partition_count: usize,
+    /// The output partitions to execute.
+    pub partitions: Vec<usize>,
+}
+
+impl QueryPartitions {
+    /// Checks that the partitions are distinct output partitions of the plan, as the indices come from the client.
+    fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::with_capacity(self.partitions.len());
+        for partition in &self.partitions {
+            if *partition >= self.partition_count {
+                return InvalidPartitionsSnafu {
+                    reason: format!(
+                        "partition {partition} is out of range for {} partitions",
+                        self.partition_count
+                    ),
+                }
+                .fail();
+            }
+            if !seen.insert(*partition) {
+                return InvalidPartitionsSnafu {
+                    reason: format!("partition {partition} is requested more than once"),
+                }
+                .fail();
+            }
+        }
+        Ok(())
+    }
 }
 
 macro_rules! handle_error {
@@ -108,12 +203,48 @@ macro_rules! handle_error {
 }
 
 impl Query {
+    #[instrument(name = "query", skip_all, fields(query_id = %self.query_id, protocol = %self.protocol))]
     pub async fn run(self) -> Result<QueryResult> {
         let session = self.df.ctx.state();
 
+        events::publish(RuntimeEvent::QueryStart(QueryStarted {
+            query_id: self.query_id,
+            sql: self.sql.clone(),
+            protocol: self.protocol.to_string(),
+            principal: self.principal_subject(),
+        }));
+
         let mut ctx = self;
 
-        let plan = match session.create_logical_plan(&ctx.sql).await {
+        if shutdown::is_draining() {
+            let error = Error::ShuttingDown;
+            ctx.finish_with_error(error.to_string(), ErrorCode::InternalError)
+                .await;
+            return Err(error);
+        }
+
+        let dialect = session.config().options().sql_parser.dialect.clone();
+        let sql = show::rewrite_show_statement(&ctx.sql)
+            .or_else(|| copy::rewrite_copy_statement(&ctx.sql));
+        let sql =
+            super::time_travel::rewrite_time_travel(sql.as_deref().unwrap_or(&ctx.sql)).or(sql);
+        let mut statement = match info_span!("sql_parse")
+            .in_scope(|| session.sql_to_statement(sql.as_deref().unwrap_or(&ctx.sql), &dialect))
+        {
+            Ok(statement) => statement,
+            Err(e) => {
+                let error_code = ErrorCode::from(&e);
+                handle_error!(ctx, error_code, e, UnableToExecuteQuery)
+            }
+        };
+
+        super::information_schema::rewrite_key_constraint_views(&mut statement);
+
+        let plan = match session
+            .statement_to_plan(statement)
+            .instrument(info_span!("sql_plan"))
+            .await
+        {
             Ok(plan) => plan,
             Err(e) => {
                 let error_code = ErrorCode::from(&e);
@@ -121,7 +252,49 @@ impl Query {
             }
         };
 
-        if let Some(cache_provider) = &ctx.df.cache_provider() {
+        let plan = match ctx
+            .parameters
+            .clone()
+            .map(|params| plan.with_param_values(params))
+        {
+            None => plan,
+            Some(Ok(plan)) => plan,
+            Some(Err(e)) => {
+                handle_error!(ctx, ErrorCode::QueryPlanningError, e, UnableToExecuteQuery)
+            }
+        };
+
+        let plan =
+            match copy::verify_copy_target(&plan, ctx.principal.as_ref(), &ctx.df.copy_targets()) {
+                Ok(Some(target)) => match copy::register_copy_target(&ctx.df.ctx, plan, &target) {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        handle_error!(ctx, ErrorCode::QueryPlanningError, e, UnableToExecuteQuery)
+                    }
+                },
+                Ok(None) => plan,
+                Err(e) => handle_error!(ctx, ErrorCode::AccessDenied, e, UnableToExecuteQuery),
+            };
+
+        // Policies are applied before the cache lookup, so principals with different access never share results.
+        let plan = match ctx
+            .df
+            .apply_policies(plan, ctx.principal.as_ref(), &session)
+        {
+            Ok(plan) => plan,
+            Err(e) => handle_error!(ctx, ErrorCode::AccessDenied, e, AccessDenied),
+        };
+
+        let warnings = stale_acceleration_warnings(&plan);
+
+        // A partitioned query only produces a subset of the results, so it can't use the results cache.
+        let cache_provider = if ctx.partitions.is_some() {
+            None
+        } else {
+            ctx.df.cache_provider()
+        };
+
+        if let Some(cache_provider) = &cache_provider {
             if let Some(cached_result) = match cache_provider.get(&plan).await {
                 Ok(Some(v)) => Some(v),
                 Ok(None) => None,
@@ -145,7 +318,8 @@ impl Query {
                 return Ok(QueryResult::new(
                     attach_query_context_to_stream(ctx, Box::pin(record_batch_stream)),
                     Some(true),
-                ));
+                )
+                .with_warnings(warnings));
             }
 
             ctx = ctx.results_cache_hit(false);
@@ -159,9 +333,36 @@ impl Query {
 
         ctx = ctx.datasets(Arc::new(get_logical_plan_input_tables(&plan)));
 
+        // Queries of sources that keep failing fail immediately, until the circuit of their connector closes again.
+        let connectors = federated_datasets(&plan);
+        for dataset in &connectors {
+            if let Err(e) = connector_health::try_acquire(dataset) {
+                handle_error!(ctx, ErrorCode::QueryExecutionError, e, SourceUnavailable)
+            }
+        }
+        ctx.connectors = connectors;
+
         let plan_copy = plan.clone();
 
-        let df = match ctx.df.ctx.execute_logical_plan(plan).await {
+        let plan = match super::time_series::rewrite_time_series_plan(plan) {
+            Ok(plan) => plan,
+            Err(e) => handle_error!(ctx, ErrorCode::QueryPlanningError, e, UnableToExecuteQuery),
+        };
+
+        let plan = match super::ai::bind_ai_calls(plan, ctx.principal_subject().as_deref()) {
+            Ok(plan) => plan,
+            Err(e) => handle_error!(ctx, ErrorCode::QueryPlanningError, e, UnableToExecuteQuery),
+        };
+
+        let execute_span = info_span!("sql_execute");
+
+        let df = match ctx
+            .df
+            .ctx
+            .execute_logical_plan(plan)
+            .instrument(execute_span.clone())
+            .await
+        {
             Ok(df) => df,
             Err(e) => {
                 let error_code = ErrorCode::from(&e);
@@ -171,11 +372,30 @@ impl Query {
 
         let df_schema: Arc<Schema> = df.schema().clone().into();
 
-        let res_stream: SendableRecordBatchStream = match df.execute_stream().await {
+        let datasets = ctx
+            .datasets
+            .iter()
+            .map(|dataset| TableReference::parse_str(dataset))
+            .collect::<Vec<_>>();
+        let rows_scanned_quota = quotas::max_rows_scanned(&datasets);
+
+        let res_stream: SendableRecordBatchStream = match execute(
+            df,
+            ctx.partitions.as_ref(),
+            rows_scanned_quota,
+            labels::to_comment(&ctx.labels),
+        )
+        .instrument(execute_span)
+        .await
+        {
             Ok(stream) => stream,
             Err(e) => {
-                let error_code = ErrorCode::from(&e);
-                handle_error!(ctx, error_code, e, UnableToExecuteQuery)
+                let error_code = match &e {
+                    Error::UnableToExecuteQuery { source } => ErrorCode::from(source),
+                    _ => ErrorCode::QueryPlanningError,
+                };
+                ctx.finish_with_error(e.to_string(), error_code).await;
+                return Err(e);
             }
         };
 
@@ -186,7 +406,7 @@ impl Query {
         };
 
         if cache_is_enabled_for_plan(&plan_copy) {
-            if let Some(cache_provider) = &ctx.df.cache_provider() {
+            if let Some(cache_provider) = &cache_provider {
                 let record_batch_stream = to_cached_record_batch_stream(
                     Arc::clone(cache_provider),
                     res_stream,
@@ -197,21 +417,71 @@ impl Query {
                 return Ok(QueryResult::new(
                     attach_query_context_to_stream(ctx, record_batch_stream),
                     Some(false),
-                ));
+                )
+                .with_warnings(warnings));
             }
         }
 
-        Ok(QueryResult::new(
-            attach_query_context_to_stream(ctx, res_stream),
-            None,
-        ))
+        Ok(
+            QueryResult::new(attach_query_context_to_stream(ctx, res_stream), None)
+                .with_warnings(warnings),
+        )
     }
 
     pub async fn get_schema(&self) -> Result<Schema, DataFusionError> {
-        let df = self.df.ctx.sql(&self.sql).await?;
+        let df = self.dataframe().await?;
         Ok(df.schema().into())
     }
 
+    /// Returns the number of output partitions of the query's physical plan.
+    pub async fn get_output_partition_count(&self) -> Result<usize, DataFusionError> {
+        let df = self.dataframe().await?;
+        let plan = df.create_physical_plan().await?;
+        Ok(plan.output_partitioning().partition_count())
+    }
+
+    /// Returns the schema of the `$1`, `$2`, ... placeholders of the query, in order, with the types inferred from
+    /// where they are used.
+    pub async fn get_parameter_schema(&self) -> Result<Schema, DataFusionError> {
+        let df = self.df.ctx.sql(&self.sql).await?;
+        let mut parameters = df
+            .logical_plan()
+            .get_parameter_types()?
+            .into_iter()
+            .collect::<Vec<_>>();
+        parameters.sort_by_key(|(name, _)| {
+            name.trim_start_matches('$')
+                .parse::<usize>()
+                .unwrap_or(usize::MAX)
+        });
+        Ok(Schema::new(
+            parameters
+                .into_iter()
+                .map(|(name, data_type)| {
+                    Field::new(name, data_type.unwrap_or(DataType::Null), true)
+                })
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    /// Plans the query with the access policies of the principal applied, without running it.
+    async fn dataframe(&self) -> Result<DataFrame, DataFusionError> {
+        let sql = show::rewrite_show_statement(&self.sql)
+            .or_else(|| copy::rewrite_copy_statement(&self.sql));
+        let sql =
+            super::time_travel::rewrite_time_travel(sql.as_deref().unwrap_or(&self.sql)).or(sql);
+        let mut df = self.df.ctx.sql(sql.as_deref().unwrap_or(&self.sql)).await?;
+        if let Some(parameters) = &self.parameters {
+            df = df.with_param_values(parameters.clone())?;
+        }
+        let (state, plan) = df.into_parts();
+        let plan = self
+            .df
+            .apply_policies(plan, self.principal.as_ref(), &state)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        Ok(DataFrame::new(state, plan))
+    }
+
     pub async fn finish_with_error(mut self, error_message: String, error_code: ErrorCode) {
         tracing::debug!(
             "Query '{}' finished with error: {error_message}; code: {error_code}",
@@ -260,19 +530,78 @@ impl Query {
             ),
             ("protocol", self.protocol.to_string()),
         ];
+        if let Some(query_labels) = labels::join(&self.labels) {
+            labels.push(("labels", query_labels));
+        }
 
         metrics::histogram!("query_duration_seconds", &labels).record(duration.as_secs_f32());
+        metrics::counter!("query_count", "protocol" => self.protocol.to_string()).increment(1);
+        metrics::counter!("query_rows_returned", "protocol" => self.protocol.to_string())
+            .increment(self.rows_produced);
 
         if let Some(err) = &self.error_code {
             labels.push(("err_code", err.to_string()));
             metrics::counter!("query_failures", &labels).increment(1);
         }
 
+        // The queries that read federated datasets wait on their sources, so their duration is the latency of the
+        // requests to the connectors of those datasets.
+        let status = if self.error_code.is_some() {
+            "error"
+        } else {
+            "ok"
+        };
+        for dataset in &self.connectors {
+            metrics::histogram!(
+                "connector_request_duration_ms",
+                "dataset" => dataset.to_string(),
+                "status" => status
+            )
+            .record(1000_f64 * duration.as_secs_f64());
+        }
+
+        for dataset in &self.connectors {
+            match (&self.error_code, &self.error_message) {
+                (None, _) => connector_health::record_success(dataset),
+                (Some(ErrorCode::QueryExecutionError), Some(error)) => {
+                    connector_health::record_failure(dataset, error);
+                }
+                _ => {}
+            }
+        }
+
+        events::publish(RuntimeEvent::QueryEnd(QueryFinished {
+            query_id: self.query_id,
+            sql: self.sql.clone(),
+            protocol: self.protocol.to_string(),
+            principal: self.principal_subject(),
+            datasets: self.datasets.iter().cloned().collect(),
+            rows_produced: self.rows_produced,
+            duration,
+            error: self.error_message.clone(),
+        }));
+
+        self.df.audit(AuditRecord {
+            principal: self.principal_subject(),
+            protocol: Some(self.protocol.to_string()),
+            resources: self.datasets.iter().cloned().collect(),
+            sql: Some(self.sql.clone()),
+            rows: Some(self.rows_produced),
+            error: self.error_message.clone(),
+            ..AuditRecord::new(AuditAction::Query)
+        });
+
         if let Err(err) = self.write_query_history().await {
             tracing::error!("Error writing query history: {err}");
         };
     }
 
+    fn principal_subject(&self) -> Option<String> {
+        self.principal
+            .as_ref()
+            .map(|principal| principal.subject.clone())
+    }
+
     #[must_use]
     fn schema(mut self, schema: Arc<Schema>) -> Self {
         self.schema = Some(schema);
@@ -298,6 +627,110 @@ impl Query {
     }
 }
 
+/// Executes the physical plan for `df`, or only its selected output partitions merged into a single stream. The scans
+/// of the plan fail once they have read more rows than `rows_scanned_quota`, the quota of a namespace, and append
+/// `comment` to the SQL they push down.
+async fn execute(
+    df: DataFrame,
+    partitions: Option<&QueryPartitions>,
+    rows_scanned_quota: Option<(String, u64)>,
+    comment: Option<String>,
+) -> Result<SendableRecordBatchStream> {
+    let task_ctx = Arc::new(df.task_ctx());
+    let mut plan = df
+        .create_physical_plan()
+        .await
+        .map_err(|source| Error::UnableToExecuteQuery { source })?;
+
+    if let Some((namespace, limit)) = rows_scanned_quota {
+        plan = scan_quota::limit_rows_scanned(plan, &namespace, limit)
+            .map_err(|source| Error::UnableToExecuteQuery { source })?;
+    }
+
+    if let Some(comment) = comment {
+        plan = query_comment::comment_scans(plan, &comment)
+            .map_err(|source| Error::UnableToExecuteQuery { source })?;
+    }
+
+    let Some(partitions) = partitions else {
+        return execute_stream(plan, task_ctx)
+            .map_err(|source| Error::UnableToExecuteQuery { source });
+    };
+
+    let actual = plan.output_partitioning().partition_count();
+    if actual != partitions.partition_count {
+        return Err(Error::PartitionCountMismatch {
+            expected: partitions.partition_count,
+            actual,
+        });
+    }
+    partitions.validate()?;
+
+    let mut streams = Vec::with_capacity(partitions.partitions.len());
+    for partition in &partitions.partitions {
+        let stream = plan
+            .execute(*partition, Arc::clone(&task_ctx))
+            .map_err(|source| Error::UnableToExecuteQuery { source })?;
+        streams.push(stream);
+    }
+
+    Ok(Box::pin(RecordBatchStreamAdapter::new(
+        plan.schema(),
+        futures::stream::select_all(streams),
+    )))
+}
+
+/// The federated datasets the plan reads, including through views.
+fn federated_datasets(plan: &LogicalPlan) -> Vec<TableReference> {
+    fn collect(plan: &LogicalPlan, datasets: &mut Vec<TableReference>) {
+        if let LogicalPlan::TableScan(scan) = plan {
+            if let Ok(provider) = source_as_provider(&scan.source) {
+                // Accelerated datasets are queried without calling their sources.
+                let accelerated = provider.as_any().is::<AcceleratedTable>();
+                if let Some(view) = provider.get_logical_plan() {
+                    collect(view, datasets);
+                } else if !accelerated
+                    && connector_health::is_tracked(&scan.table_name)
+                    && !datasets.contains(&scan.table_name)
+                {
+                    datasets.push(scan.table_name.clone());
+                }
+            }
+        }
+        for input in plan.inputs() {
+            collect(input, datasets);
+        }
+    }
+
+    let mut datasets = vec![];
+    collect(plan, &mut datasets);
+    datasets
+}
+
+/// The warnings of the accelerations the plan reads while they are unavailable, including through views.
+fn stale_acceleration_warnings(plan: &LogicalPlan) -> Vec<String> {
+    fn collect(plan: &LogicalPlan, warnings: &mut Vec<String>) {
+        if let LogicalPlan::TableScan(scan) = plan {
+            if let Ok(provider) = source_as_provider(&scan.source) {
+                if let Some(accelerated) = provider.as_any().downcast_ref::<AcceleratedTable>() {
+                    warnings.extend(accelerated.stale_warning());
+                } else if let Some(view) = provider.get_logical_plan() {
+                    collect(view, warnings);
+                }
+            }
+        }
+        for input in plan.inputs() {
+            collect(input, warnings);
+        }
+    }
+
+    let mut warnings = vec![];
+    collect(plan, &mut warnings);
+    warnings.sort_unstable();
+    warnings.dedup();
+    warnings
+}
+
 #[must_use]
 /// Attaches a query context to a stream of record batches.
 ///
@@ -324,10 +757,15 @@ fn attach_query_context_to_stream(
                     yield batch_result
                 }
                 Err(e) => {
+                    // Exceeding a quota isn't a failure of the data sources the query reads.
+                    let error_code = match e.find_root() {
+                        DataFusionError::ResourcesExhausted(_) => ErrorCode::QuotaExceeded,
+                        _ => ErrorCode::QueryExecutionError,
+                    };
                     ctx
                     .schema(schema_copy)
                     .rows_produced(num_records)
-                    .finish_with_error(e.to_string(), ErrorCode::QueryExecutionError).await;
+                    .finish_with_error(e.to_string(), error_code).await;
                     yield batch_result;
                     return;
                 }
@@ -346,3 +784,25 @@ fn attach_query_context_to_stream(
         Box::pin(updated_stream),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_partitions() {
+        let partitions = |partitions: Vec<usize>| QueryPartitions {
+            partition_count: 4,
+            partitions,
+        };
+        assert!(partitions(vec![0, 3, 1]).validate().is_ok());
+        assert!(matches!(
+            partitions(vec![0, 4]).validate(),
+            Err(Error::InvalidPartitions { .. })
+        ));
+        assert!(matches!(
+            partitions(vec![2, 2]).validate(),
+            Err(Error::InvalidPartitions { .. })
+        ));
+    }
+}
@@ -0,0 +1,483 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use byte_unit::Byte;
+use dashmap::DashMap;
+use datafusion::execution::context::SQLOptions;
+use futures::StreamExt;
+use serde::Serialize;
+use snafu::prelude::*;
+use spicepod::component::runtime::AsyncQueries;
+use uuid::Uuid;
+
+use crate::{auth::Principal, datafusion::DataFusion};
+
+use super::{labels::QueryLabels, Protocol, QueryBuilder};
+
+const DEFAULT_MAX_RESULT_SIZE: u64 = 100 * 1024 * 1024; // 100 MiB
+const DEFAULT_RESULTS_TTL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_MAX_STORED_QUERIES: usize = 1000;
+const DEFAULT_MAX_TOTAL_SIZE: u64 = 1024 * 1024 * 1024; // 1 GiB
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to parse max_result_size value: {source}"))]
+    FailedToParseMaxResultSize { source: byte_unit::ParseError },
+
+    #[snafu(display("Failed to parse results_ttl value: {source}"))]
+    FailedToParseResultsTtl { source: fundu::ParseError },
+
+    #[snafu(display("Failed to parse max_total_size value: {source}"))]
+    FailedToParseMaxTotalSize { source: byte_unit::ParseError },
+
+    #[snafu(display(
+        "The results of {max_stored_queries} queries are already stored. Retry once earlier results expire, or increase runtime.async_queries.max_stored_queries."
+    ))]
+    TooManyStoredQueries { max_stored_queries: usize },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status", content = "error")]
+pub enum AsyncQueryStatus {
+    Running,
+    Completed,
+    Failed(String),
+}
+
+struct AsyncQuery {
+    /// The subject of the principal that ran the query, the only one that can read its results.
+    owner: Option<String>,
+    status: AsyncQueryStatus,
+    schema: Option<SchemaRef>,
+    batches: Arc<Vec<RecordBatch>>,
+    num_rows: usize,
+    /// The in-memory size of the batches, counted towards `max_total_size`.
+    size: u64,
+    finished_at: Option<Instant>,
+}
+
+impl AsyncQuery {
+    fn is_owned_by(&self, principal: Option<&Principal>) -> bool {
+        self.owner.as_deref() == principal.map(|principal| principal.subject.as_str())
+    }
+}
+
+/// A page of the results of an asynchronous query.
+pub struct AsyncQueryResults {
+    pub status: AsyncQueryStatus,
+    /// The schema of the results, once the query has started returning them.
+    pub schema: Option<SchemaRef>,
+    pub total_rows: usize,
+    pub batches: Vec<RecordBatch>,
+}
+
+/// Runs queries in the background and keeps their results in memory until they expire,
+/// so that clients can poll for and page through results instead of holding a request open.
+pub struct AsyncQueryStore {
+    queries: DashMap<Uuid, AsyncQuery>,
+    max_result_size: u64,
+    results_ttl: Duration,
+    max_stored_queries: usize,
+    max_total_size: u64,
+    /// The size of the results of all stored queries.
+    stored_size: AtomicU64,
+    /// Held while checking `max_stored_queries` and inserting a query, so concurrent submissions can't exceed it.
+    admission: Mutex<()>,
+}
+
+impl AsyncQueryStore {
+    pub fn try_new(config: &AsyncQueries) -> Result<Self> {
+        let max_result_size = match &config.max_result_size {
+            Some(max_result_size) => Byte::parse_str(max_result_size, true)
+                .context(FailedToParseMaxResultSizeSnafu)?
+                .as_u64(),
+            None => DEFAULT_MAX_RESULT_SIZE,
+        };
+
+        let results_ttl = match &config.results_ttl {
+            Some(results_ttl) => {
+                fundu::parse_duration(results_ttl).context(FailedToParseResultsTtlSnafu)?
+            }
+            None => DEFAULT_RESULTS_TTL,
+        };
+
+        let max_total_size = match &config.max_total_size {
+            Some(max_total_size) => Byte::parse_str(max_total_size, true)
+                .context(FailedToParseMaxTotalSizeSnafu)?
+                .as_u64(),
+            None => DEFAULT_MAX_TOTAL_SIZE,
+        };
+
+        Ok(Self {
+            queries: DashMap::new(),
+            max_result_size,
+            results_ttl,
+            max_stored_queries: config
+                .max_stored_queries
+                .unwrap_or(DEFAULT_MAX_STORED_QUERIES),
+            max_total_size,
+            stored_size: AtomicU64::new(0),
+            admission: Mutex::new(()),
+        })
+    }
+
+    /// Starts running `sql` in the background and returns the id used to retrieve its results.
+    pub fn submit(
+        self: &Arc<Self>,
+        df: Arc<DataFusion>,
+        sql: String,
+        restricted_sql_options: Option<SQLOptions>,
+        principal: Option<Principal>,
+        labels: QueryLabels,
+    ) -> Result<Uuid> {
+        self.evict_expired();
+        let query_id = self.admit(AsyncQuery {
+            owner: principal
+                .as_ref()
+                .map(|principal| principal.subject.clone()),
+            status: AsyncQueryStatus::Running,
+            schema: None,
+            batches: Arc::new(vec![]),
+            num_rows: 0,
+            size: 0,
+            finished_at: None,
+        })?;
+
+        let store = Arc::clone(self);
+        tokio::spawn(async move {
+            let query = QueryBuilder::new(sql, df, Protocol::Http)
+                .query_id(query_id)
+                .restricted_sql_options(restricted_sql_options)
+                .principal(principal)
+                .labels(labels)
+                .build();
+
+            let result = match query.run().await {
+                Ok(query_result) => {
+                    let schema = query_result.data.schema();
+                    store
+                        .collect(query_result.data)
+                        .await
+                        .map(|batches| (schema, batches))
+                }
+                Err(e) => Err(e.to_string()),
+            };
+
+            if let Some(mut entry) = store.queries.get_mut(&query_id) {
+                match result {
+                    Ok((schema, batches)) => {
+                        entry.num_rows = batches.iter().map(RecordBatch::num_rows).sum();
+                        entry.size = store.store_size(&batches);
+                        entry.schema = Some(schema);
+                        entry.batches = Arc::new(batches);
+                        entry.status = AsyncQueryStatus::Completed;
+                    }
+                    Err(e) => {
+                        tracing::debug!("Async query {query_id} failed: {e}");
+                        entry.status = AsyncQueryStatus::Failed(e);
+                    }
+                }
+                entry.finished_at = Some(Instant::now());
+            }
+        });
+
+        Ok(query_id)
+    }
+
+    /// Keeps the results of a query that already ran, so they can be paged through like those of an asynchronous
+    /// query until they expire.
+    pub fn insert_completed(
+        &self,
+        principal: Option<&Principal>,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> Result<Uuid> {
+        self.evict_expired();
+        let query_id = self.admit(AsyncQuery {
+            owner: principal.map(|principal| principal.subject.clone()),
+            status: AsyncQueryStatus::Completed,
+            schema: Some(schema),
+            num_rows: batches.iter().map(RecordBatch::num_rows).sum(),
+            size: batches_size(&batches),
+            batches: Arc::new(batches),
+            finished_at: Some(Instant::now()),
+        })?;
+
+        Ok(query_id)
+    }
+
+    /// Stores `query` under a new id, unless `max_stored_queries` are already stored. The check and the insert happen
+    /// under one lock, so concurrent submissions can't both take the last slot.
+    fn admit(&self, query: AsyncQuery) -> Result<Uuid> {
+        let _admission = match self.admission.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        ensure!(
+            self.queries.len() < self.max_stored_queries,
+            TooManyStoredQueriesSnafu {
+                max_stored_queries: self.max_stored_queries
+            }
+        );
+
+        let query_id = Uuid::new_v4();
+        self.stored_size.fetch_add(query.size, Ordering::Relaxed);
+        self.queries.insert(query_id, query);
+        Ok(query_id)
+    }
+
+    /// Counts the size of `batches` towards the stored size, returning it.
+    fn store_size(&self, batches: &[RecordBatch]) -> u64 {
+        let size = batches_size(batches);
+        self.stored_size.fetch_add(size, Ordering::Relaxed);
+        size
+    }
+
+    /// Collects the results of a query, failing if they exceed `max_result_size`, or don't fit in `max_total_size`
+    /// along with the results already stored.
+    pub(crate) async fn collect(
+        &self,
+        mut data: datafusion::execution::SendableRecordBatchStream,
+    ) -> std::result::Result<Vec<RecordBatch>, String> {
+        let mut batches = vec![];
+        let mut size = 0u64;
+        while let Some(batch) = data.next().await {
+            let batch = batch.map_err(|e| e.to_string())?;
+            size += batch.get_array_memory_size() as u64;
+            if size > self.max_result_size {
+                return Err(format!(
+                    "Query results exceed the maximum size of {:.2}. Run the query synchronously or increase runtime.async_queries.max_result_size.",
+                    Byte::from_u64(self.max_result_size).get_adjusted_unit(byte_unit::Unit::MiB)
+                ));
+            }
+            if self.stored_size.load(Ordering::Relaxed) + size > self.max_total_size {
+                return Err(format!(
+                    "The stored query results exceed the maximum total size of {:.2}. Retry once earlier results expire, or increase runtime.async_queries.max_total_size.",
+                    Byte::from_u64(self.max_total_size).get_adjusted_unit(byte_unit::Unit::MiB)
+                ));
+            }
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// The status of the query, if it was run by `principal`.
+    #[must_use]
+    pub fn status(
+        &self,
+        query_id: &Uuid,
+        principal: Option<&Principal>,
+    ) -> Option<AsyncQueryStatus> {
+        self.evict_expired();
+        self.queries
+            .get(query_id)
+            .filter(|q| q.is_owned_by(principal))
+            .map(|q| q.status.clone())
+    }
+
+    /// Returns up to `limit` rows of the results of the query, starting at row `offset`, if it was run by
+    /// `principal`.
+    #[must_use]
+    pub fn results(
+        &self,
+        query_id: &Uuid,
+        principal: Option<&Principal>,
+        offset: usize,
+        limit: usize,
+    ) -> Option<AsyncQueryResults> {
+        self.evict_expired();
+        let query = self
+            .queries
+            .get(query_id)
+            .filter(|q| q.is_owned_by(principal))?;
+
+        Some(AsyncQueryResults {
+            status: query.status.clone(),
+            schema: query.schema.clone(),
+            total_rows: query.num_rows,
+            batches: slice_batches(&query.batches, offset, limit),
+        })
+    }
+
+    fn evict_expired(&self) {
+        let ttl = self.results_ttl;
+        self.queries.retain(|_, q| {
+            let keep = q
+                .finished_at
+                .map_or(true, |finished_at| finished_at.elapsed() < ttl);
+            if !keep {
+                self.stored_size.fetch_sub(q.size, Ordering::Relaxed);
+            }
+            keep
+        });
+    }
+}
+
+fn batches_size(batches: &[RecordBatch]) -> u64 {
+    batches
+        .iter()
+        .map(|batch| batch.get_array_memory_size() as u64)
+        .sum()
+}
+
+/// Returns the rows in `offset..offset + limit` across `batches`, without copying the underlying data.
+pub(crate) fn slice_batches(
+    batches: &[RecordBatch],
+    offset: usize,
+    limit: usize,
+) -> Vec<RecordBatch> {
+    let mut sliced = vec![];
+    let mut skip = offset;
+    let mut remaining = limit;
+
+    for batch in batches {
+        if remaining == 0 {
+            break;
+        }
+        if skip >= batch.num_rows() {
+            skip -= batch.num_rows();
+            continue;
+        }
+
+        let length = remaining.min(batch.num_rows() - skip);
+        sliced.push(batch.slice(skip, length));
+        remaining -= length;
+        skip = 0;
+    }
+
+    sliced
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+
+    use serde_json::Map;
+    use spicepod::component::runtime::AsyncQueries;
+
+    use crate::auth::Principal;
+
+    use super::{slice_batches, AsyncQueryStore, Error};
+
+    fn batch(values: Vec<i32>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(values))]).expect("valid batch")
+    }
+
+    #[test]
+    fn test_slice_batches_across_boundaries() {
+        let batches = vec![
+            batch(vec![0, 1, 2]),
+            batch(vec![3, 4]),
+            batch(vec![5, 6, 7]),
+        ];
+
+        let sliced = slice_batches(&batches, 2, 4);
+        let rows: Vec<usize> = sliced.iter().map(RecordBatch::num_rows).collect();
+        assert_eq!(rows, vec![1, 2, 1]);
+
+        let first = sliced[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .expect("int32 column");
+        assert_eq!(first.value(0), 2);
+    }
+
+    #[test]
+    fn test_slice_batches_past_end() {
+        let batches = vec![batch(vec![0, 1, 2])];
+        assert!(slice_batches(&batches, 5, 10).is_empty());
+        assert_eq!(slice_batches(&batches, 1, 10)[0].num_rows(), 2);
+    }
+
+    #[test]
+    fn test_results_are_private_and_capped() {
+        let store = AsyncQueryStore::try_new(&AsyncQueries {
+            max_stored_queries: Some(1),
+            ..AsyncQueries::default()
+        })
+        .expect("valid config");
+        let owner = Principal {
+            subject: "alice".to_string(),
+            claims: Map::new(),
+            admin: false,
+        };
+        let other = Principal {
+            subject: "bob".to_string(),
+            claims: Map::new(),
+            admin: false,
+        };
+
+        let batches = vec![batch(vec![0, 1, 2])];
+        let query_id = store
+            .insert_completed(Some(&owner), batches[0].schema(), batches)
+            .expect("stored");
+        assert!(store.results(&query_id, Some(&owner), 0, 10).is_some());
+        assert!(store.results(&query_id, Some(&other), 0, 10).is_none());
+        assert!(store.results(&query_id, None, 0, 10).is_none());
+        assert!(store.status(&query_id, Some(&other)).is_none());
+
+        assert!(matches!(
+            store.insert_completed(None, batch(vec![3]).schema(), vec![batch(vec![3])]),
+            Err(Error::TooManyStoredQueries { .. })
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_inserts_respect_cap() {
+        let store = Arc::new(
+            AsyncQueryStore::try_new(&AsyncQueries {
+                max_stored_queries: Some(8),
+                ..AsyncQueries::default()
+            })
+            .expect("valid config"),
+        );
+
+        let handles: Vec<_> = (0..32)
+            .map(|i| {
+                let store = Arc::clone(&store);
+                std::thread::spawn(move || {
+                    store
+                        .insert_completed(None, batch(vec![i]).schema(), vec![batch(vec![i])])
+                        .is_ok()
+                })
+            })
+            .collect();
+        let stored = handles
+            .into_iter()
+            .map(|handle| handle.join().expect("thread completes"))
+            .filter(|stored| *stored)
+            .count();
+        assert_eq!(stored, 8);
+    }
+}
@@ -16,13 +16,16 @@ limitations under the License.
 
 use std::{collections::HashSet, sync::Arc, time::SystemTime};
 
-use datafusion::execution::context::SQLOptions;
+use datafusion::{common::ParamValues, execution::context::SQLOptions};
 use tokio::time::Instant;
 use uuid::Uuid;
 
-use crate::datafusion::DataFusion;
+use crate::{auth::Principal, datafusion::DataFusion, shutdown};
 
-use super::{Protocol, Query};
+use super::{
+    labels::{self, QueryLabels},
+    Protocol, Query, QueryPartitions,
+};
 
 #[allow(clippy::module_name_repetitions)]
 pub struct QueryBuilder {
@@ -32,6 +35,10 @@ pub struct QueryBuilder {
     nsql: Option<String>,
     restricted_sql_options: Option<SQLOptions>,
     protocol: Protocol,
+    partitions: Option<QueryPartitions>,
+    principal: Option<Principal>,
+    labels: QueryLabels,
+    parameters: Option<ParamValues>,
 }
 
 impl QueryBuilder {
@@ -43,6 +50,10 @@ impl QueryBuilder {
             nsql: None,
             restricted_sql_options: None,
             protocol,
+            partitions: None,
+            principal: None,
+            labels: QueryLabels::new(),
+            parameters: None,
         }
     }
 
@@ -70,8 +81,37 @@ impl QueryBuilder {
         self
     }
 
+    /// Only execute the given output partitions of the query plan, instead of the full result.
+    #[must_use]
+    pub fn partitions(mut self, partitions: Option<QueryPartitions>) -> Self {
+        self.partitions = partitions;
+        self
+    }
+
+    /// The authenticated caller the query runs on behalf of.
+    #[must_use]
+    pub fn principal(mut self, principal: Option<Principal>) -> Self {
+        self.principal = principal;
+        self
+    }
+
+    /// The labels the client attributes the query to, merged with the labels hinted in its SQL.
+    #[must_use]
+    pub fn labels(mut self, labels: QueryLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// The values bound to the placeholders of a prepared statement.
+    #[must_use]
+    pub fn parameters(mut self, parameters: Option<ParamValues>) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
     #[must_use]
     pub fn build(self) -> Query {
+        let labels = labels::resolve(self.labels, &self.sql);
         Query {
             df: self.df,
             sql: self.sql,
@@ -87,8 +127,14 @@ impl QueryBuilder {
             error_message: None,
             error_code: None,
             datasets: Arc::new(HashSet::default()),
+            connectors: vec![],
+            _in_flight: shutdown::track(),
             timer: Instant::now(),
             protocol: self.protocol,
+            partitions: self.partitions,
+            principal: self.principal,
+            labels,
+            parameters: self.parameters,
         }
     }
 }
@@ -0,0 +1,284 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! `COPY (<query>) TO '<url>' (FORMAT PARQUET | CSV | JSON [, <option> <value>, ...])` exports the results of a query
+//! to object storage, i.e. `COPY (SELECT * FROM taxi_trips) TO 's3://bucket/trips/' (FORMAT PARQUET)`.
+//!
+//! The statement is rewritten into DataFusion's `COPY ... TO ... STORED AS <format> OPTIONS (...)`, where the other
+//! options are format options like `compression zstd`. The credentials of the bucket come from the environment, in a store only exports
+//! write with.
+//!
+//! Only admins can export, and only to the prefixes of `runtime.copy.allowed_targets`.
+
+use datafusion::{
+    error::DataFusionError,
+    execution::context::SessionContext,
+    logical_expr::{dml::CopyTo, LogicalPlan},
+};
+use url::Url;
+
+use crate::{auth::Principal, object_store_registry::copy_target_store};
+
+/// Returns the DataFusion statement for `sql` if it is a `COPY` statement with a `(FORMAT ...)` clause.
+pub(crate) fn rewrite_copy_statement(sql: &str) -> Option<String> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    if !sql
+        .split_whitespace()
+        .next()
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("COPY"))
+    {
+        return None;
+    }
+
+    // The clause is the last parenthesis outside of string literals, right after the target URL.
+    let open = last_top_level_group(sql)?;
+    let (statement, clause) = (sql[..open].trim_end(), &sql[open + 1..sql.len() - 1]);
+    if !statement.ends_with('\'') {
+        return None;
+    }
+
+    let mut format = None;
+    let mut options = vec![];
+    for option in tokens(clause)?.split(|token| token.is_none()) {
+        let [Some(key), Some(value)] = option else {
+            return None;
+        };
+        if key.eq_ignore_ascii_case("FORMAT") {
+            format = Some(value.to_ascii_uppercase());
+            continue;
+        }
+
+        let key = key.to_ascii_lowercase();
+        let key = if key.contains('.') {
+            key
+        } else {
+            format!("format.{key}")
+        };
+        options.push(format!("{} {}", literal(&key), literal(value)));
+    }
+
+    let format = format?;
+    if options.is_empty() {
+        Some(format!("{statement} STORED AS {format}"))
+    } else {
+        Some(format!(
+            "{statement} STORED AS {format} OPTIONS ({})",
+            options.join(", ")
+        ))
+    }
+}
+
+/// Splits the options of the clause into words and unquoted string literals, with `None` for the commas between
+/// options.
+fn tokens(clause: &str) -> Option<Vec<Option<String>>> {
+    let mut tokens = vec![];
+    let mut chars = clause.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => tokens.push(None),
+            '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next()? {
+                        '\'' if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            value.push('\'');
+                        }
+                        '\'' => break,
+                        c => value.push(c),
+                    }
+                }
+                tokens.push(Some(value));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ',' || c == '\'' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Some(word));
+            }
+        }
+    }
+    Some(tokens)
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Returns the start of the parenthesized group `sql` ends with, ignoring parentheses in string literals.
+fn last_top_level_group(sql: &str) -> Option<usize> {
+    if !sql.ends_with(')') {
+        return None;
+    }
+
+    let mut in_string = false;
+    let mut depth = 0_usize;
+    let mut start = None;
+    for (i, c) in sql.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '(' if !in_string => {
+                if depth == 0 {
+                    start = Some(i);
+                }
+                depth += 1;
+            }
+            ')' if !in_string => depth = depth.checked_sub(1)?,
+            _ => {}
+        }
+    }
+
+    if in_string || depth != 0 {
+        return None;
+    }
+    start
+}
+
+/// Checks that `principal` can run a `COPY` statement, and that it exports to one of `allowed_targets`, returning
+/// the target of the statement.
+pub(crate) fn verify_copy_target(
+    plan: &LogicalPlan,
+    principal: Option<&Principal>,
+    allowed_targets: &[String],
+) -> Result<Option<Url>, DataFusionError> {
+    let LogicalPlan::Copy(copy) = plan else {
+        return Ok(None);
+    };
+
+    if principal.is_some_and(|principal| !principal.admin) {
+        return Err(DataFusionError::Plan(
+            "COPY requires the admin claim of runtime.auth.oidc.admin".to_string(),
+        ));
+    }
+
+    let url = match Url::parse(&copy.output_url) {
+        Ok(url) if url.scheme() != "file" => url,
+        _ => {
+            return Err(DataFusionError::Plan(format!(
+                "COPY can only export to object storage, such as s3://bucket/path, received {}",
+                copy.output_url
+            )))
+        }
+    };
+    if !allowed_targets
+        .iter()
+        .any(|prefix| is_under_prefix(url.as_str(), prefix))
+    {
+        return Err(DataFusionError::Plan(format!(
+            "COPY can't export to {url}, which is not under runtime.copy.allowed_targets"
+        )));
+    }
+    Ok(Some(url))
+}
+
+/// Whether `target` is `prefix`, or a path under it, so `s3://exports` doesn't allow `s3://exports-other/`.
+fn is_under_prefix(target: &str, prefix: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'))
+}
+
+/// The scheme the stores of S3 targets are registered under, so they don't replace the stores the datasets of the
+/// same buckets are read with.
+const COPY_TARGET_SCHEME: &str = "spice-copy+s3";
+
+/// Registers the store of an S3 target with signed requests under a scheme of its own, returning `plan` exporting to
+/// that store, so the export uses the credentials of the environment.
+pub(crate) fn register_copy_target(
+    ctx: &SessionContext,
+    plan: LogicalPlan,
+    target: &Url,
+) -> Result<LogicalPlan, DataFusionError> {
+    let LogicalPlan::Copy(copy) = plan else {
+        return Ok(plan);
+    };
+    if target.scheme() != "s3" {
+        return Ok(LogicalPlan::Copy(copy));
+    }
+    let Some(bucket_name) = target.host_str() else {
+        return Ok(LogicalPlan::Copy(copy));
+    };
+
+    let output_url = copy_target_url(target);
+    let url = Url::parse(&output_url).map_err(|e| DataFusionError::External(Box::new(e)))?;
+    ctx.runtime_env()
+        .register_object_store(&url, copy_target_store(bucket_name)?);
+    Ok(LogicalPlan::Copy(CopyTo { output_url, ..copy }))
+}
+
+/// The URL of an S3 target under [`COPY_TARGET_SCHEME`].
+fn copy_target_url(target: &Url) -> String {
+    format!(
+        "{COPY_TARGET_SCHEME}{}",
+        &target.as_str()[target.scheme().len()..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_copy_statements() {
+        assert_eq!(
+            rewrite_copy_statement(
+                "COPY (SELECT * FROM t WHERE a IN (1, 2)) TO 's3://b/p/' (format parquet);"
+            )
+            .as_deref(),
+            Some("COPY (SELECT * FROM t WHERE a IN (1, 2)) TO 's3://b/p/' STORED AS PARQUET")
+        );
+        assert_eq!(
+            rewrite_copy_statement("copy (select 1) to 's3://b/p.csv' (FORMAT CSV, DELIMITER ',', 'compression' gzip)")
+                .as_deref(),
+            Some("copy (select 1) to 's3://b/p.csv' STORED AS CSV OPTIONS ('format.delimiter' ',', 'format.compression' 'gzip')")
+        );
+
+        // Left to DataFusion.
+        assert_eq!(
+            rewrite_copy_statement("COPY (SELECT 1) TO 's3://b/p.json' STORED AS JSON"),
+            None
+        );
+        assert_eq!(
+            rewrite_copy_statement("COPY (SELECT 1) TO 's3://b/(p)'"),
+            None
+        );
+        assert_eq!(rewrite_copy_statement("SELECT (1)"), None);
+    }
+
+    #[test]
+    fn restricts_copy_targets() {
+        let allowed = ["s3://exports".to_string(), "s3://shared/spice/".to_string()];
+        assert!(is_under_prefix("s3://exports/trips/", &allowed[0]));
+        assert!(is_under_prefix("s3://exports", &allowed[0]));
+        assert!(!is_under_prefix("s3://exports-other/trips/", &allowed[0]));
+        assert!(is_under_prefix("s3://shared/spice/trips.csv", &allowed[1]));
+        assert!(!is_under_prefix("s3://shared/other/trips.csv", &allowed[1]));
+    }
+
+    #[test]
+    fn registers_copy_targets_under_their_own_scheme() {
+        let target = Url::parse("s3://exports/trips/").expect("valid url");
+        let url = Url::parse(&copy_target_url(&target)).expect("valid url");
+        assert_eq!(url.as_str(), "spice-copy+s3://exports/trips/");
+        assert_eq!(url.host_str(), Some("exports"));
+    }
+}
@@ -21,6 +21,8 @@ pub enum ErrorCode {
     SyntaxError,
     QueryPlanningError,
     QueryExecutionError,
+    AccessDenied,
+    QuotaExceeded,
     InternalError,
 }
 
@@ -30,6 +32,8 @@ impl std::fmt::Display for ErrorCode {
             ErrorCode::SyntaxError => write!(f, "SyntaxError"),
             ErrorCode::QueryPlanningError => write!(f, "QueryPlanningError"),
             ErrorCode::QueryExecutionError => write!(f, "QueryExecutionError"),
+            ErrorCode::AccessDenied => write!(f, "AccessDenied"),
+            ErrorCode::QuotaExceeded => write!(f, "QuotaExceeded"),
             ErrorCode::InternalError => write!(f, "InternalError"),
         }
     }
@@ -41,6 +45,8 @@ impl From<&ErrorCode> for i8 {
             ErrorCode::SyntaxError => -10,
             ErrorCode::QueryPlanningError => -20,
             ErrorCode::QueryExecutionError => -30,
+            ErrorCode::AccessDenied => -40,
+            ErrorCode::QuotaExceeded => -50,
             ErrorCode::InternalError => -120,
         }
     }
@@ -56,6 +62,7 @@ impl From<&DataFusionError> for ErrorCode {
             DataFusionError::ObjectStore(..)
             | DataFusionError::External(..)
             | DataFusionError::Execution(..) => ErrorCode::QueryExecutionError,
+            DataFusionError::ResourcesExhausted(..) => ErrorCode::QuotaExceeded,
             DataFusionError::Context(_, err) => ErrorCode::from(err.as_ref()),
             _ => ErrorCode::InternalError,
         }
@@ -0,0 +1,123 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Labels clients attach to queries to attribute them to a workload, i.e. a team or a dashboard.
+//!
+//! Labels are `key=value` pairs separated by commas, given in the `X-Spice-Query-Labels` HTTP header or Flight
+//! metadata, or in a `/*+ labels(team=growth, app=dashboard) */` hint in the SQL, which takes precedence over the
+//! header for the same key. They are recorded in the query history and metrics, and sent to the sources a query is
+//! federated to as a SQL comment.
+//!
+//! Keys may only contain letters, digits, `_`, `.` and `-`, and values `/`, `:` and `@` too, so that labels can't
+//! break out of the comment. Invalid labels are ignored.
+
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// The HTTP header and Flight metadata key clients set labels with.
+pub const LABELS_HEADER: &str = "x-spice-query-labels";
+
+const MAX_LABELS: usize = 8;
+const MAX_LENGTH: usize = 64;
+
+static LABELS_HINT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)/\*\+\s*labels\s*\(([^)]*)\)\s*\*/")
+        .unwrap_or_else(|_| panic!("Invalid labels hint regex"))
+});
+
+pub type QueryLabels = BTreeMap<String, String>;
+
+/// Parses labels formatted as `team=growth,app=dashboard`.
+#[must_use]
+pub fn parse_labels(value: &str) -> QueryLabels {
+    let mut labels = QueryLabels::new();
+    for pair in value.split(',') {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if is_valid(key, &['_', '.', '-']) && is_valid(value, &['_', '.', '-', '/', ':', '@']) {
+            labels.insert(key.to_string(), value.to_string());
+        }
+    }
+    labels
+}
+
+/// The labels of a query, merging the labels hinted in its SQL over the labels given by the client.
+#[must_use]
+pub fn resolve(mut labels: QueryLabels, sql: &str) -> QueryLabels {
+    if let Some(hint) = LABELS_HINT.captures(sql).and_then(|hint| hint.get(1)) {
+        labels.extend(parse_labels(hint.as_str()));
+    }
+    labels.into_iter().take(MAX_LABELS).collect()
+}
+
+/// Formats labels as `app=dashboard,team=growth` for the query history and metrics.
+#[must_use]
+pub fn join(labels: &QueryLabels) -> Option<String> {
+    (!labels.is_empty()).then(|| {
+        labels
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    })
+}
+
+/// Formats labels as a sqlcommenter comment, i.e. `app='dashboard',team='growth'`.
+#[must_use]
+pub fn to_comment(labels: &QueryLabels) -> Option<String> {
+    (!labels.is_empty()).then(|| {
+        labels
+            .iter()
+            .map(|(key, value)| format!("{key}='{value}'"))
+            .collect::<Vec<_>>()
+            .join(",")
+    })
+}
+
+fn is_valid(s: &str, allowed: &[char]) -> bool {
+    !s.is_empty()
+        && s.len() <= MAX_LENGTH
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || allowed.contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_labels_from_header_and_hint() {
+        let header = parse_labels("team=growth, app=dashboard, bad key=x, quote=it's, empty=");
+        let labels = resolve(
+            header,
+            "SELECT /*+ LABELS(app=churn-report, owner=ana@example.com) */ * FROM orders",
+        );
+
+        assert_eq!(
+            join(&labels).as_deref(),
+            Some("app=churn-report,owner=ana@example.com,team=growth")
+        );
+        assert_eq!(
+            to_comment(&labels).as_deref(),
+            Some("app='churn-report',owner='ana@example.com',team='growth'")
+        );
+        assert_eq!(join(&resolve(QueryLabels::new(), "SELECT 1")), None);
+    }
+}
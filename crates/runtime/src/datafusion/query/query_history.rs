@@ -38,7 +38,7 @@ use crate::{
     internal_table::create_internal_accelerated_table,
 };
 
-use super::Query;
+use super::{labels, Query};
 
 pub const DEFAULT_QUERY_HISTORY_TABLE: &str = "query_history";
 
@@ -89,6 +89,8 @@ fn table_schema() -> Schema {
         Field::new("rows_produced", DataType::UInt64, false),
         Field::new("results_cache_hit", DataType::Boolean, false),
         Field::new("error_message", DataType::Utf8, true),
+        Field::new("principal", DataType::Utf8, true),
+        Field::new("labels", DataType::Utf8, true),
     ])
 }
 
@@ -199,6 +201,11 @@ impl Query {
                     .results_cache_hit
                     .unwrap_or(false)])),
                 Arc::new(StringArray::from(vec![self.error_message.clone()])),
+                Arc::new(StringArray::from(vec![self
+                    .principal
+                    .as_ref()
+                    .map(|principal| principal.subject.clone())])),
+                Arc::new(StringArray::from(vec![labels::join(&self.labels)])),
             ],
         )
         .boxed()
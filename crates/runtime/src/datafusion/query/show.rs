@@ -0,0 +1,123 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Rewrites the `SHOW` statements DataFusion doesn't plan into queries on `information_schema`:
+//!
+//! - `SHOW CATALOGS`
+//! - `SHOW SCHEMAS [FROM | IN <catalog>]`
+//! - `SHOW TABLES FROM | IN [<catalog>.]<schema>`
+
+/// Returns the `information_schema` query for `sql` if it is one of the supported `SHOW` statements.
+pub(crate) fn rewrite_show_statement(sql: &str) -> Option<String> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    let tokens: Vec<&str> = sql.split_whitespace().collect();
+    let keyword = |i: usize, expected: &str| {
+        tokens
+            .get(i)
+            .is_some_and(|token| token.eq_ignore_ascii_case(expected))
+    };
+    let is_from = |i: usize| keyword(i, "FROM") || keyword(i, "IN");
+
+    if !keyword(0, "SHOW") {
+        return None;
+    }
+
+    if keyword(1, "CATALOGS") && tokens.len() == 2 {
+        return Some(
+            "SELECT DISTINCT catalog_name FROM information_schema.schemata ORDER BY catalog_name"
+                .to_string(),
+        );
+    }
+
+    if keyword(1, "SCHEMAS") {
+        let filter = match tokens.len() {
+            2 => String::new(),
+            4 if is_from(2) => {
+                let [catalog] = identifiers(tokens[3])?[..] else {
+                    return None;
+                };
+                format!(" WHERE catalog_name = {}", literal(&catalog))
+            }
+            _ => return None,
+        };
+        return Some(format!(
+            "SELECT catalog_name, schema_name FROM information_schema.schemata{filter} ORDER BY catalog_name, schema_name"
+        ));
+    }
+
+    if keyword(1, "TABLES") && tokens.len() == 4 && is_from(2) {
+        let filter = match &identifiers(tokens[3])?[..] {
+            [schema] => format!("table_schema = {}", literal(schema)),
+            [catalog, schema] => format!(
+                "table_catalog = {} AND table_schema = {}",
+                literal(catalog),
+                literal(schema)
+            ),
+            _ => return None,
+        };
+        return Some(format!(
+            "SELECT table_catalog, table_schema, table_name, table_type FROM information_schema.tables WHERE {filter} ORDER BY table_catalog, table_schema, table_name"
+        ));
+    }
+
+    None
+}
+
+/// Splits a dotted name into its identifiers, unquoting any quoted with `"`.
+fn identifiers(name: &str) -> Option<Vec<String>> {
+    name.split('.')
+        .map(|part| {
+            let part = match part.strip_prefix('"') {
+                Some(quoted) => quoted.strip_suffix('"')?.to_string(),
+                None => part.to_ascii_lowercase(),
+            };
+            (!part.is_empty()).then_some(part)
+        })
+        .collect()
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_show_statements() {
+        assert_eq!(
+            rewrite_show_statement("show catalogs;").as_deref(),
+            Some(
+                "SELECT DISTINCT catalog_name FROM information_schema.schemata ORDER BY catalog_name"
+            )
+        );
+        assert_eq!(
+            rewrite_show_statement("SHOW SCHEMAS IN \"Sales\"").as_deref(),
+            Some("SELECT catalog_name, schema_name FROM information_schema.schemata WHERE catalog_name = 'Sales' ORDER BY catalog_name, schema_name")
+        );
+        assert_eq!(
+            rewrite_show_statement("SHOW TABLES FROM sales.EU").as_deref(),
+            Some("SELECT table_catalog, table_schema, table_name, table_type FROM information_schema.tables WHERE table_catalog = 'sales' AND table_schema = 'eu' ORDER BY table_catalog, table_schema, table_name")
+        );
+
+        // Left to DataFusion.
+        assert_eq!(rewrite_show_statement("SHOW TABLES"), None);
+        assert_eq!(rewrite_show_statement("SHOW COLUMNS FROM t"), None);
+        assert_eq!(rewrite_show_statement("SELECT 1"), None);
+        assert_eq!(rewrite_show_statement("SHOW TABLES FROM a.b.c"), None);
+    }
+}
@@ -0,0 +1,225 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The `sample(table, percent[, method[, seed]])` table function, which returns about `percent` percent of the rows
+//! of a dataset, i.e. `SELECT * FROM sample('taxi_trips', 1, 'system')`.
+//!
+//! The method is `bernoulli` (the default), which keeps each row with the sampling probability, or `system`, which
+//! keeps blocks of rows. Accelerations that can sample in their engine, like DuckDB, only read the sampled rows.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::datatypes::{DataType, SchemaRef};
+use async_trait::async_trait;
+use data_components::sample::{
+    get_sample_provider, SampleMethod, SampleTableProvider, TableSample,
+};
+use datafusion::{
+    catalog::CatalogProvider,
+    datasource::{function::TableFunctionImpl, source_as_provider, TableProvider, TableType},
+    error::{DataFusionError, Result},
+    execution::context::{SessionContext, SessionState},
+    logical_expr::{Expr, TableProviderFilterPushDown, TableScan},
+    physical_plan::ExecutionPlan,
+    scalar::ScalarValue,
+    sql::TableReference,
+};
+use tokio::runtime::Handle;
+
+use crate::{accelerated_table::AcceleratedTable, execution_plan::sample::SampleExec};
+
+use super::{SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA};
+
+/// Registers `sample` in `ctx`, sampling the tables of `catalog`.
+pub(crate) fn register_sample_udtf(ctx: &SessionContext, catalog: Arc<dyn CatalogProvider>) {
+    ctx.register_udtf("sample", Arc::new(SampleFunction { catalog }));
+}
+
+/// Returns the dataset a scan samples, if it is a scan of the `sample` table function.
+pub(crate) fn sampled_table(scan: &TableScan) -> Option<TableReference> {
+    let provider = source_as_provider(&scan.source).ok()?;
+    provider
+        .as_any()
+        .downcast_ref::<SampleTable>()
+        .map(|sample| sample.table.clone())
+}
+
+struct SampleFunction {
+    catalog: Arc<dyn CatalogProvider>,
+}
+
+impl TableFunctionImpl for SampleFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let (table, percent, method, seed) = match args {
+            [table, percent] => (table, percent, None, None),
+            [table, percent, method] => (table, percent, Some(method), None),
+            [table, percent, method, seed] => (table, percent, Some(method), Some(seed)),
+            _ => {
+                return Err(DataFusionError::Plan(
+                    "sample expects a table and a percentage, and optionally a method and a seed: sample(table, percent[, method[, seed]])".to_string(),
+                ))
+            }
+        };
+
+        let Some(ScalarValue::Utf8(Some(table))) = literal(table, &DataType::Utf8)? else {
+            return Err(DataFusionError::Plan(
+                "sample expects the name of the table as a string, i.e. sample('taxi_trips', 10)"
+                    .to_string(),
+            ));
+        };
+        let percent = match literal(percent, &DataType::Float64)? {
+            Some(ScalarValue::Float64(Some(percent))) if (0.0..=100.0).contains(&percent) => {
+                percent
+            }
+            _ => {
+                return Err(DataFusionError::Plan(
+                    "sample expects a percentage between 0 and 100".to_string(),
+                ))
+            }
+        };
+        let method = match method
+            .map(|method| literal(method, &DataType::Utf8))
+            .transpose()?
+        {
+            None => SampleMethod::Bernoulli,
+            Some(Some(ScalarValue::Utf8(Some(method))))
+                if method.eq_ignore_ascii_case("bernoulli") =>
+            {
+                SampleMethod::Bernoulli
+            }
+            Some(Some(ScalarValue::Utf8(Some(method))))
+                if method.eq_ignore_ascii_case("system") =>
+            {
+                SampleMethod::System
+            }
+            Some(_) => {
+                return Err(DataFusionError::Plan(
+                    "sample expects the method bernoulli or system".to_string(),
+                ))
+            }
+        };
+        let seed = match seed
+            .map(|seed| literal(seed, &DataType::UInt64))
+            .transpose()?
+        {
+            None => None,
+            Some(Some(ScalarValue::UInt64(Some(seed)))) => Some(seed),
+            Some(_) => {
+                return Err(DataFusionError::Plan(
+                    "sample expects a non-negative integer seed".to_string(),
+                ))
+            }
+        };
+
+        let table = TableReference::from(table.as_str());
+        let resolved = table
+            .clone()
+            .resolve(SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA);
+        let not_found = || DataFusionError::Plan(format!("Table {table} not found"));
+        if resolved.catalog.as_ref() != SPICE_DEFAULT_CATALOG {
+            return Err(not_found());
+        }
+        let schema = self
+            .catalog
+            .schema(&resolved.schema)
+            .ok_or_else(not_found)?;
+        let provider = tokio::task::block_in_place(|| {
+            Handle::current().block_on(schema.table(&resolved.table))
+        })?
+        .ok_or_else(not_found)?;
+        // The access policies of the datasets of a view only apply to its plan, which a sample doesn't use.
+        if provider.get_logical_plan().is_some() {
+            return Err(DataFusionError::Plan(format!(
+                "sample only supports tables, {table} is a view"
+            )));
+        }
+
+        Ok(Arc::new(SampleTable {
+            table: TableReference::full(resolved.catalog, resolved.schema, resolved.table),
+            provider,
+            sample: TableSample {
+                method,
+                percent,
+                seed,
+            },
+        }))
+    }
+}
+
+fn literal(arg: &Expr, data_type: &DataType) -> Result<Option<ScalarValue>> {
+    match arg {
+        Expr::Literal(value) => value.cast_to(data_type).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// A sample of a table, scanned by the table when it can sample in its engine.
+struct SampleTable {
+    table: TableReference,
+    provider: Arc<dyn TableProvider>,
+    sample: TableSample,
+}
+
+impl SampleTable {
+    fn sample_provider(&self) -> Option<&dyn SampleTableProvider> {
+        match self.provider.as_any().downcast_ref::<AcceleratedTable>() {
+            Some(accelerated) if accelerated.supports_sampling() => Some(accelerated),
+            Some(_) => None,
+            None => get_sample_provider(self.provider.as_ref()),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for SampleTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.provider.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.provider.table_type()
+    }
+
+    // A filter keeps the same rows before and after sampling each row independently.
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        self.provider.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if let Some(provider) = self.sample_provider() {
+            return provider
+                .scan_sample(state, projection, filters, limit, &self.sample)
+                .await;
+        }
+
+        // The limit applies to the sampled rows.
+        let input = self.provider.scan(state, projection, filters, None).await?;
+        Ok(Arc::new(SampleExec::new(input, self.sample.clone())))
+    }
+}
@@ -19,13 +19,12 @@ use std::{any::Any, sync::Arc};
 use async_trait::async_trait;
 use dashmap::DashMap;
 use datafusion::{
-    catalog::schema::SchemaProvider,
+    catalog::{schema::SchemaProvider, CatalogProvider, MemoryCatalogProvider},
     datasource::TableProvider,
     error::{DataFusionError, Result},
     execution::context::SessionContext,
     sql::TableReference,
 };
-use snafu::prelude::*;
 
 // Copy of default MemorySchemaProvider that allows `register_table` to atomically overwrite any existing tables
 // https://github.com/apache/datafusion/blob/deebda78a34251b2bddf0c5f66edfaa112c4559b/datafusion/core/src/catalog/schema.rs#L84
@@ -82,29 +81,39 @@ impl SchemaProvider for SpiceSchemaProvider {
     }
 }
 
+/// Creates the catalog and schema of `table_reference` if they don't exist yet. References without a catalog are in
+/// `default_catalog`.
 pub(crate) fn ensure_schema_exists(
     ctx: &SessionContext,
-    catalog: &str,
+    default_catalog: &str,
     table_reference: &TableReference,
-) -> Result<(), super::Error> {
-    let catalog_provider = ctx
-        .catalog(catalog)
-        .context(super::CatalogMissingSnafu { catalog })?;
+) {
+    let catalog = table_reference.catalog().unwrap_or(default_catalog);
+    let catalog_provider = match ctx.catalog(catalog) {
+        Some(catalog_provider) => catalog_provider,
+        None => {
+            let catalog_provider: Arc<dyn CatalogProvider> = Arc::new(MemoryCatalogProvider::new());
+            ctx.register_catalog(catalog, Arc::clone(&catalog_provider));
+            catalog_provider
+        }
+    };
 
     // This TableReference doesn't have a schema component, nothing to do.
     let Some(schema_name) = table_reference.schema() else {
-        return Ok(());
+        return;
     };
 
     // If the schema exists, nothing to do.
     if catalog_provider.schema(schema_name).is_some() {
-        return Ok(());
+        return;
     };
 
     // Create the schema
     let schema_provider = Arc::new(SpiceSchemaProvider::new());
-    match catalog_provider.register_schema(schema_name, schema_provider) {
-        Ok(_) => Ok(()),
-        Err(_) => unreachable!("register_schema will never fail"),
+    if catalog_provider
+        .register_schema(schema_name, schema_provider)
+        .is_err()
+    {
+        unreachable!("register_schema will never fail");
     }
 }
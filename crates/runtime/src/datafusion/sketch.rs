@@ -0,0 +1,424 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Aggregates that estimate their results from sketches of bounded size:
+//!
+//! - `approx_percentile(value, percentile)`: the value at `percentile`, between 0 and 1, estimated with a t-digest.
+//! - `approx_count_distinct(value)`: the number of distinct values, estimated with HyperLogLog.
+//! - `tdigest(value)` and `hll(value)`: the sketch of the values, as binary.
+//! - `tdigest_merge(sketch)` and `hll_merge(sketch)`: the sketch of the values of all the sketches.
+//!
+//! And the scalar functions `tdigest_percentile(sketch, percentile)` and `hll_count(sketch)` to estimate the results
+//! from a sketch. Sketches can be stored in a dataset, for example one per hour, and merged by queries over any range
+//! of hours.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, BinaryArray, Float64Array, UInt64Array},
+    compute::cast,
+    datatypes::{DataType, Field, Float64Type},
+    row::{RowConverter, SortField},
+};
+use datafusion::{
+    common::{exec_datafusion_err, exec_err, plan_err, DataFusionError, Result, ScalarValue},
+    execution::context::SessionContext,
+    logical_expr::{
+        function::{AccumulatorArgs, StateFieldsArgs},
+        Accumulator, AggregateUDF, AggregateUDFImpl, ColumnarValue, ScalarUDF, ScalarUDFImpl,
+        Signature, Volatility,
+    },
+};
+
+mod hll;
+mod tdigest;
+
+use hll::HyperLogLog;
+use tdigest::TDigest;
+
+/// Registers the sketch aggregates and functions in `ctx`.
+pub fn register_sketch_udfs(ctx: &SessionContext) {
+    for kind in [
+        SketchAggregateKind::ApproxPercentile,
+        SketchAggregateKind::ApproxCountDistinct,
+        SketchAggregateKind::Tdigest,
+        SketchAggregateKind::TdigestMerge,
+        SketchAggregateKind::Hll,
+        SketchAggregateKind::HllMerge,
+    ] {
+        ctx.register_udaf(AggregateUDF::new_from_impl(SketchAggregate::new(kind)));
+    }
+
+    for kind in [
+        SketchEstimateKind::TdigestPercentile,
+        SketchEstimateKind::HllCount,
+    ] {
+        ctx.register_udf(ScalarUDF::new_from_impl(SketchEstimate::new(kind)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SketchAggregateKind {
+    ApproxPercentile,
+    ApproxCountDistinct,
+    Tdigest,
+    TdigestMerge,
+    Hll,
+    HllMerge,
+}
+
+impl SketchAggregateKind {
+    /// Whether the aggregate merges sketches, rather than sketching values.
+    fn merges(self) -> bool {
+        matches!(
+            self,
+            SketchAggregateKind::TdigestMerge | SketchAggregateKind::HllMerge
+        )
+    }
+}
+
+#[derive(Debug)]
+struct SketchAggregate {
+    kind: SketchAggregateKind,
+    signature: Signature,
+}
+
+impl SketchAggregate {
+    fn new(kind: SketchAggregateKind) -> Self {
+        let signature = match kind {
+            SketchAggregateKind::ApproxPercentile => Signature::any(2, Volatility::Immutable),
+            SketchAggregateKind::Tdigest => Signature::numeric(1, Volatility::Immutable),
+            SketchAggregateKind::ApproxCountDistinct | SketchAggregateKind::Hll => {
+                Signature::any(1, Volatility::Immutable)
+            }
+            SketchAggregateKind::TdigestMerge | SketchAggregateKind::HllMerge => {
+                Signature::exact(vec![DataType::Binary], Volatility::Immutable)
+            }
+        };
+
+        Self { kind, signature }
+    }
+}
+
+impl AggregateUDFImpl for SketchAggregate {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        match self.kind {
+            SketchAggregateKind::ApproxPercentile => "approx_percentile",
+            SketchAggregateKind::ApproxCountDistinct => "approx_count_distinct",
+            SketchAggregateKind::Tdigest => "tdigest",
+            SketchAggregateKind::TdigestMerge => "tdigest_merge",
+            SketchAggregateKind::Hll => "hll",
+            SketchAggregateKind::HllMerge => "hll_merge",
+        }
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, args: &[DataType]) -> Result<DataType> {
+        match self.kind {
+            SketchAggregateKind::ApproxPercentile if !args[0].is_numeric() => plan_err!(
+                "approx_percentile expects numeric values, received {}",
+                args[0]
+            ),
+            SketchAggregateKind::ApproxPercentile => Ok(DataType::Float64),
+            SketchAggregateKind::ApproxCountDistinct => Ok(DataType::UInt64),
+            _ => Ok(DataType::Binary),
+        }
+    }
+
+    fn accumulator(&self, _args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(SketchAccumulator::new(self.kind)))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        let mut fields = vec![Field::new(
+            format!("{}[sketch]", args.name),
+            DataType::Binary,
+            true,
+        )];
+        if self.kind == SketchAggregateKind::ApproxPercentile {
+            fields.push(Field::new(
+                format!("{}[percentile]", args.name),
+                DataType::Float64,
+                true,
+            ));
+        }
+        Ok(fields)
+    }
+}
+
+#[derive(Debug)]
+enum Sketch {
+    TDigest(TDigest),
+    Hll(HyperLogLog),
+}
+
+impl Sketch {
+    /// Reads a sketch of the same kind from `bytes`.
+    fn decode(&self, bytes: &[u8]) -> Result<Sketch> {
+        match self {
+            Sketch::TDigest(_) => TDigest::from_bytes(bytes)
+                .map(Sketch::TDigest)
+                .ok_or_else(|| exec_datafusion_err!("Invalid t-digest sketch")),
+            Sketch::Hll(_) => HyperLogLog::from_bytes(bytes)
+                .map(Sketch::Hll)
+                .ok_or_else(|| exec_datafusion_err!("Invalid HyperLogLog sketch")),
+        }
+    }
+
+    fn merge(&mut self, other: &Sketch) {
+        match (self, other) {
+            (Sketch::TDigest(digest), Sketch::TDigest(other)) => digest.merge(other),
+            (Sketch::Hll(hll), Sketch::Hll(other)) => hll.merge(other),
+            _ => {}
+        }
+    }
+
+    fn to_bytes(&mut self) -> Vec<u8> {
+        match self {
+            Sketch::TDigest(digest) => digest.to_bytes(),
+            Sketch::Hll(hll) => hll.to_bytes(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SketchAccumulator {
+    kind: SketchAggregateKind,
+    sketch: Sketch,
+    percentile: Option<f64>,
+}
+
+impl SketchAccumulator {
+    fn new(kind: SketchAggregateKind) -> Self {
+        let sketch = match kind {
+            SketchAggregateKind::ApproxPercentile
+            | SketchAggregateKind::Tdigest
+            | SketchAggregateKind::TdigestMerge => Sketch::TDigest(TDigest::default()),
+            SketchAggregateKind::ApproxCountDistinct
+            | SketchAggregateKind::Hll
+            | SketchAggregateKind::HllMerge => Sketch::Hll(HyperLogLog::default()),
+        };
+
+        Self {
+            kind,
+            sketch,
+            percentile: None,
+        }
+    }
+
+    fn merge_sketches(&mut self, sketches: &ArrayRef) -> Result<()> {
+        let sketches = cast(sketches, &DataType::Binary)?;
+        for bytes in sketches.as_binary::<i32>().iter().flatten() {
+            let other = self.sketch.decode(bytes)?;
+            self.sketch.merge(&other);
+        }
+        Ok(())
+    }
+
+    /// Keeps the first percentile of `percentiles`, which are expected to be the same for all rows.
+    fn set_percentile(&mut self, percentiles: &ArrayRef) -> Result<()> {
+        if self.percentile.is_some() {
+            return Ok(());
+        }
+
+        let percentiles = cast(percentiles, &DataType::Float64)?;
+        if let Some(percentile) = percentiles
+            .as_primitive::<Float64Type>()
+            .iter()
+            .flatten()
+            .next()
+        {
+            if !(0.0..=1.0).contains(&percentile) {
+                return exec_err!(
+                    "approx_percentile expects a percentile between 0 and 1, received {percentile}"
+                );
+            }
+            self.percentile = Some(percentile);
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for SketchAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        if self.kind.merges() {
+            return self.merge_sketches(&values[0]);
+        }
+        if self.kind == SketchAggregateKind::ApproxPercentile {
+            self.set_percentile(&values[1])?;
+        }
+
+        match &mut self.sketch {
+            Sketch::TDigest(digest) => {
+                let values = cast(&values[0], &DataType::Float64)?;
+                for value in values.as_primitive::<Float64Type>().iter().flatten() {
+                    digest.add(value);
+                }
+            }
+            Sketch::Hll(hll) => {
+                // Values are identified by their row format, which is the same for equal values.
+                let converter =
+                    RowConverter::new(vec![SortField::new(values[0].data_type().clone())])?;
+                let rows = converter.convert_columns(&values[..1])?;
+                for row in 0..values[0].len() {
+                    if values[0].is_valid(row) {
+                        hll.add(rows.row(row).as_ref());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.merge_sketches(&states[0])?;
+        if self.kind == SketchAggregateKind::ApproxPercentile {
+            self.set_percentile(&states[1])?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let mut state = vec![ScalarValue::Binary(Some(self.sketch.to_bytes()))];
+        if self.kind == SketchAggregateKind::ApproxPercentile {
+            state.push(ScalarValue::Float64(self.percentile));
+        }
+        Ok(state)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        match (self.kind, &mut self.sketch) {
+            (SketchAggregateKind::ApproxPercentile, Sketch::TDigest(digest)) => Ok(
+                ScalarValue::Float64(self.percentile.and_then(|p| digest.quantile(p))),
+            ),
+            (SketchAggregateKind::ApproxCountDistinct, Sketch::Hll(hll)) => {
+                Ok(ScalarValue::UInt64(Some(hll.count())))
+            }
+            (_, sketch) => Ok(ScalarValue::Binary(Some(sketch.to_bytes()))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + match &self.sketch {
+                Sketch::TDigest(digest) => digest.size(),
+                Sketch::Hll(hll) => hll.size(),
+            }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SketchEstimateKind {
+    TdigestPercentile,
+    HllCount,
+}
+
+#[derive(Debug)]
+struct SketchEstimate {
+    kind: SketchEstimateKind,
+    signature: Signature,
+}
+
+impl SketchEstimate {
+    fn new(kind: SketchEstimateKind) -> Self {
+        let args = match kind {
+            SketchEstimateKind::TdigestPercentile => vec![DataType::Binary, DataType::Float64],
+            SketchEstimateKind::HllCount => vec![DataType::Binary],
+        };
+
+        Self {
+            kind,
+            signature: Signature::exact(args, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for SketchEstimate {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        match self.kind {
+            SketchEstimateKind::TdigestPercentile => "tdigest_percentile",
+            SketchEstimateKind::HllCount => "hll_count",
+        }
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(match self.kind {
+            SketchEstimateKind::TdigestPercentile => DataType::Float64,
+            SketchEstimateKind::HllCount => DataType::UInt64,
+        })
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let sketches = args[0]
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .ok_or_else(|| DataFusionError::Internal("Expected a binary array".to_string()))?;
+
+        let array: ArrayRef = match self.kind {
+            SketchEstimateKind::TdigestPercentile => {
+                let percentiles = args[1].as_primitive::<Float64Type>();
+                let estimates = sketches
+                    .iter()
+                    .zip(percentiles.iter())
+                    .map(|(sketch, percentile)| {
+                        let (Some(sketch), Some(percentile)) = (sketch, percentile) else {
+                            return Ok(None);
+                        };
+                        let mut digest = TDigest::from_bytes(sketch)
+                            .ok_or_else(|| exec_datafusion_err!("Invalid t-digest sketch"))?;
+                        Ok(digest.quantile(percentile))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Arc::new(Float64Array::from(estimates))
+            }
+            SketchEstimateKind::HllCount => {
+                let counts = sketches
+                    .iter()
+                    .map(|sketch| {
+                        sketch
+                            .map(|sketch| {
+                                HyperLogLog::from_bytes(sketch)
+                                    .map(|hll| hll.count())
+                                    .ok_or_else(|| {
+                                        exec_datafusion_err!("Invalid HyperLogLog sketch")
+                                    })
+                            })
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Arc::new(UInt64Array::from(counts))
+            }
+        };
+
+        Ok(ColumnarValue::Array(array))
+    }
+}
@@ -0,0 +1,142 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A HyperLogLog sketch with 2^14 registers, which estimates distinct counts with a standard error of about 0.8%.
+
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone)]
+pub(crate) struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    /// Adds a value, as the bytes that identify it.
+    pub(crate) fn add(&mut self, value: &[u8]) {
+        let hash = hash(value);
+        // The first bits of the hash select the register, which keeps the longest run of leading zeros of the rest.
+        let index = usize::try_from(hash >> (64 - PRECISION)).unwrap_or_default();
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = u8::try_from(rest.leading_zeros() + 1).unwrap_or(u8::MAX);
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// The size of the sketch in memory, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.registers.capacity()
+    }
+
+    pub(crate) fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn count(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2_f64.powi(-i32::from(rank)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        // Linear counting is more accurate for small counts.
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        let estimate = if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        };
+
+        estimate.round() as u64
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(1 + REGISTERS);
+        bytes.push(VERSION);
+        bytes.extend(&self.registers);
+        bytes
+    }
+
+    /// Reads a sketch written by [`HyperLogLog::to_bytes`], returning `None` if the bytes aren't a valid sketch.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes.split_first() {
+            Some((&VERSION, registers)) if registers.len() == REGISTERS => Some(Self {
+                registers: registers.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// FNV-1a followed by the finalizer of MurmurHash3, so the sketches are the same across processes and versions.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_distinct_values_of_merged_sketches() {
+        let mut even = HyperLogLog::default();
+        let mut odd = HyperLogLog::default();
+        for value in 0_u32..100_000 {
+            let sketch = if value % 2 == 0 { &mut even } else { &mut odd };
+            sketch.add(&value.to_le_bytes());
+            // Duplicates don't change the count.
+            sketch.add(&value.to_le_bytes());
+        }
+
+        let mut sketch = HyperLogLog::from_bytes(&even.to_bytes()).expect("valid sketch");
+        sketch.merge(&odd);
+        let count = sketch.count();
+        assert!(
+            count.abs_diff(100_000) <= 3_000,
+            "estimated {count} distinct values"
+        );
+
+        assert_eq!(HyperLogLog::default().count(), 0);
+        assert!(HyperLogLog::from_bytes(&[VERSION, 0]).is_none());
+    }
+}
@@ -0,0 +1,244 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A merging t-digest, which estimates quantiles from at most about `COMPRESSION` centroids, with the most accuracy
+//! at the extreme quantiles.
+
+use std::f64::consts::PI;
+
+const COMPRESSION: f64 = 100.0;
+
+/// The number of added values and centroids kept before they are merged into the digest.
+const PENDING_LIMIT: usize = 512;
+
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TDigest {
+    centroids: Vec<Centroid>,
+    pending: Vec<Centroid>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub(crate) fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.push(Centroid {
+            mean: value,
+            weight: 1.0,
+        });
+    }
+
+    pub(crate) fn merge(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+
+        // The extremes of the other digest can be outside of the means of its centroids.
+        let (min, max) = if self.is_empty() {
+            (other.min, other.max)
+        } else {
+            (self.min.min(other.min), self.max.max(other.max))
+        };
+        for centroid in other.centroids.iter().chain(&other.pending) {
+            self.push(*centroid);
+        }
+        self.min = min;
+        self.max = max;
+    }
+
+    fn push(&mut self, centroid: Centroid) {
+        if self.is_empty() {
+            self.min = centroid.mean;
+            self.max = centroid.mean;
+        } else {
+            self.min = self.min.min(centroid.mean);
+            self.max = self.max.max(centroid.mean);
+        }
+
+        self.pending.push(centroid);
+        if self.pending.len() >= PENDING_LIMIT {
+            self.compress();
+        }
+    }
+
+    /// The size of the digest in memory, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        std::mem::size_of::<Centroid>() * (self.centroids.capacity() + self.pending.capacity())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.centroids.is_empty() && self.pending.is_empty()
+    }
+
+    /// Merges the pending values into the centroids, so each centroid covers at most one unit of the scale function.
+    fn compress(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let mut sorted = std::mem::take(&mut self.centroids);
+        sorted.append(&mut self.pending);
+        sorted.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+        let total: f64 = sorted.iter().map(|c| c.weight).sum();
+
+        let mut centroids = vec![];
+        let mut current = sorted[0];
+        let mut weight_before = 0.0;
+        let mut limit = total * quantile(scale(0.0) + 1.0);
+        for centroid in &sorted[1..] {
+            if weight_before + current.weight + centroid.weight <= limit {
+                let weight = current.weight + centroid.weight;
+                current.mean += (centroid.mean - current.mean) * centroid.weight / weight;
+                current.weight = weight;
+            } else {
+                weight_before += current.weight;
+                centroids.push(current);
+                limit = total * quantile(scale(weight_before / total) + 1.0);
+                current = *centroid;
+            }
+        }
+        centroids.push(current);
+
+        self.centroids = centroids;
+    }
+
+    /// Returns the estimated value at the quantile `q`, between 0 and 1, or `None` if the digest is empty.
+    pub(crate) fn quantile(&mut self, q: f64) -> Option<f64> {
+        self.compress();
+        let centroids = &self.centroids;
+        let (first, last) = (centroids.first()?, centroids.last()?);
+        if centroids.len() == 1 {
+            return Some(first.mean);
+        }
+
+        let total: f64 = centroids.iter().map(|c| c.weight).sum();
+        let target = q.clamp(0.0, 1.0) * total;
+
+        if target < first.weight / 2.0 {
+            return Some(self.min + (first.mean - self.min) * target / (first.weight / 2.0));
+        }
+
+        let mut weight_before = 0.0;
+        for pair in centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_center = weight_before + left.weight / 2.0;
+            let right_center = weight_before + left.weight + right.weight / 2.0;
+            if target <= right_center {
+                let ratio = (target - left_center) / (right_center - left_center);
+                return Some(left.mean + (right.mean - left.mean) * ratio);
+            }
+            weight_before += left.weight;
+        }
+
+        let last_center = total - last.weight / 2.0;
+        let ratio = ((target - last_center) / (last.weight / 2.0)).min(1.0);
+        Some(last.mean + (self.max - last.mean) * ratio)
+    }
+
+    pub(crate) fn to_bytes(&mut self) -> Vec<u8> {
+        self.compress();
+
+        let mut bytes = Vec::with_capacity(1 + 16 * (self.centroids.len() + 1));
+        bytes.push(VERSION);
+        bytes.extend(self.min.to_le_bytes());
+        bytes.extend(self.max.to_le_bytes());
+        for centroid in &self.centroids {
+            bytes.extend(centroid.mean.to_le_bytes());
+            bytes.extend(centroid.weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a digest written by [`TDigest::to_bytes`], returning `None` if the bytes aren't a valid digest.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&version, rest) = bytes.split_first()?;
+        if version != VERSION || rest.len() < 16 || rest.len() % 16 != 0 {
+            return None;
+        }
+
+        let mut values = rest
+            .chunks_exact(8)
+            .map(|chunk| chunk.try_into().map(f64::from_le_bytes));
+        let min = values.next()?.ok()?;
+        let max = values.next()?.ok()?;
+        let mut centroids = vec![];
+        while let (Some(mean), Some(weight)) = (values.next(), values.next()) {
+            let (mean, weight) = (mean.ok()?, weight.ok()?);
+            if mean.is_nan() || weight.is_nan() || weight <= 0.0 {
+                return None;
+            }
+            centroids.push(Centroid { mean, weight });
+        }
+
+        Some(Self {
+            centroids,
+            pending: vec![],
+            min,
+            max,
+        })
+    }
+}
+
+/// The scale function, which maps a quantile to the number of centroids below it.
+fn scale(q: f64) -> f64 {
+    COMPRESSION / (2.0 * PI) * (2.0 * q - 1.0).asin()
+}
+
+/// The inverse of [`scale`].
+fn quantile(k: f64) -> f64 {
+    let angle = (k * 2.0 * PI / COMPRESSION).min(PI / 2.0);
+    (angle.sin() + 1.0) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_quantiles_of_merged_digests() {
+        let mut low = TDigest::default();
+        let mut high = TDigest::default();
+        for value in 0..5_000 {
+            low.add(f64::from(value));
+            high.add(f64::from(value + 5_000));
+        }
+
+        let mut digest = TDigest::from_bytes(&low.to_bytes()).expect("valid digest");
+        digest.merge(&TDigest::from_bytes(&high.to_bytes()).expect("valid digest"));
+
+        for (q, expected) in [(0.0, 0.0), (0.5, 5_000.0), (0.99, 9_900.0), (1.0, 9_999.0)] {
+            let estimate = digest.quantile(q).expect("not empty");
+            assert!(
+                (estimate - expected).abs() <= 50.0,
+                "quantile {q} estimated at {estimate}, expected {expected}"
+            );
+        }
+        assert!(digest.centroids.len() <= 200);
+
+        assert!(TDigest::default().quantile(0.5).is_none());
+        assert!(TDigest::from_bytes(&[VERSION, 1, 2]).is_none());
+    }
+}
@@ -0,0 +1,290 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Geospatial functions over geometries stored as WKB (binary columns) or WKT (string columns):
+//!
+//! - `st_point(x, y)`: the WKB of a point.
+//! - `st_geomfromtext(wkt)`: the WKB of a WKT geometry.
+//! - `st_astext(geometry)`: the WKT of a geometry.
+//! - `st_distance(a, b)`: the Euclidean distance between two geometries, in the units of their coordinates.
+//! - `st_contains(a, b)`: whether `a` contains `b`.
+//! - `st_within_bbox(geometry, min_x, min_y, max_x, max_y)`: whether the geometry is within the bounding box.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::{
+    array::{
+        Array, ArrayRef, BinaryArray, BooleanArray, Float64Array, GenericBinaryArray, StringArray,
+    },
+    compute::cast,
+    datatypes::DataType,
+};
+use datafusion::{
+    common::{exec_datafusion_err, DataFusionError, Result},
+    execution::context::SessionContext,
+    logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, TypeSignature, Volatility},
+};
+use geo::{BoundingRect, Contains, EuclideanDistance, Geometry, Point};
+use wkt::{ToWkt, TryFromWkt};
+
+/// Registers the geospatial functions in `ctx`.
+pub fn register_spatial_udfs(ctx: &SessionContext) {
+    for kind in [
+        SpatialFunctionKind::Point,
+        SpatialFunctionKind::GeomFromText,
+        SpatialFunctionKind::AsText,
+        SpatialFunctionKind::Distance,
+        SpatialFunctionKind::Contains,
+        SpatialFunctionKind::WithinBbox,
+    ] {
+        ctx.register_udf(ScalarUDF::new_from_impl(SpatialFunction::new(kind)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpatialFunctionKind {
+    Point,
+    GeomFromText,
+    AsText,
+    Distance,
+    Contains,
+    WithinBbox,
+}
+
+#[derive(Debug)]
+struct SpatialFunction {
+    kind: SpatialFunctionKind,
+    signature: Signature,
+}
+
+impl SpatialFunction {
+    fn new(kind: SpatialFunctionKind) -> Self {
+        let geometry_types = [DataType::Binary, DataType::Utf8];
+        let signature = match kind {
+            SpatialFunctionKind::Point => {
+                TypeSignature::Exact(vec![DataType::Float64, DataType::Float64])
+            }
+            SpatialFunctionKind::GeomFromText => TypeSignature::Exact(vec![DataType::Utf8]),
+            SpatialFunctionKind::AsText => TypeSignature::OneOf(
+                geometry_types
+                    .iter()
+                    .map(|t| TypeSignature::Exact(vec![t.clone()]))
+                    .collect(),
+            ),
+            SpatialFunctionKind::Distance | SpatialFunctionKind::Contains => TypeSignature::OneOf(
+                geometry_types
+                    .iter()
+                    .flat_map(|a| {
+                        geometry_types
+                            .iter()
+                            .map(|b| TypeSignature::Exact(vec![a.clone(), b.clone()]))
+                    })
+                    .collect(),
+            ),
+            SpatialFunctionKind::WithinBbox => TypeSignature::OneOf(
+                geometry_types
+                    .iter()
+                    .map(|t| {
+                        let mut args = vec![t.clone()];
+                        args.extend(std::iter::repeat(DataType::Float64).take(4));
+                        TypeSignature::Exact(args)
+                    })
+                    .collect(),
+            ),
+        };
+
+        Self {
+            kind,
+            signature: Signature::new(signature, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for SpatialFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        match self.kind {
+            SpatialFunctionKind::Point => "st_point",
+            SpatialFunctionKind::GeomFromText => "st_geomfromtext",
+            SpatialFunctionKind::AsText => "st_astext",
+            SpatialFunctionKind::Distance => "st_distance",
+            SpatialFunctionKind::Contains => "st_contains",
+            SpatialFunctionKind::WithinBbox => "st_within_bbox",
+        }
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        Ok(match self.kind {
+            SpatialFunctionKind::Point | SpatialFunctionKind::GeomFromText => DataType::Binary,
+            SpatialFunctionKind::AsText => DataType::Utf8,
+            SpatialFunctionKind::Distance => DataType::Float64,
+            SpatialFunctionKind::Contains | SpatialFunctionKind::WithinBbox => DataType::Boolean,
+        })
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let args = ColumnarValue::values_to_arrays(args)?;
+
+        let array: ArrayRef = match self.kind {
+            SpatialFunctionKind::Point => {
+                let x = as_floats(&args[0])?;
+                let y = as_floats(&args[1])?;
+                let points = x
+                    .iter()
+                    .zip(y.iter())
+                    .map(|(x, y)| match (x, y) {
+                        (Some(x), Some(y)) => to_wkb(&Geometry::Point(Point::new(x, y))).map(Some),
+                        _ => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Arc::new(BinaryArray::from_iter(points))
+            }
+            SpatialFunctionKind::GeomFromText => {
+                let geometries = geometries(&args[0])?
+                    .iter()
+                    .map(|g| g.as_ref().map(to_wkb).transpose())
+                    .collect::<Result<Vec<_>>>()?;
+                Arc::new(BinaryArray::from_iter(geometries))
+            }
+            SpatialFunctionKind::AsText => Arc::new(StringArray::from_iter(
+                geometries(&args[0])?
+                    .iter()
+                    .map(|g| g.as_ref().map(ToWkt::wkt_string)),
+            )),
+            SpatialFunctionKind::Distance => Arc::new(Float64Array::from_iter(
+                geometries(&args[0])?
+                    .iter()
+                    .zip(geometries(&args[1])?.iter())
+                    .map(|(a, b)| Some(a.as_ref()?.euclidean_distance(b.as_ref()?))),
+            )),
+            SpatialFunctionKind::Contains => Arc::new(BooleanArray::from_iter(
+                geometries(&args[0])?
+                    .iter()
+                    .zip(geometries(&args[1])?.iter())
+                    .map(|(a, b)| Some(a.as_ref()?.contains(b.as_ref()?))),
+            )),
+            SpatialFunctionKind::WithinBbox => {
+                let bounds = args[1..]
+                    .iter()
+                    .map(as_floats)
+                    .collect::<Result<Vec<_>>>()?;
+                Arc::new(BooleanArray::from_iter(
+                    geometries(&args[0])?.iter().enumerate().map(|(row, g)| {
+                        let bbox = bounds
+                            .iter()
+                            .map(|b| (!b.is_null(row)).then(|| b.value(row)))
+                            .collect::<Option<Vec<f64>>>()?;
+                        Some(within_bbox(g.as_ref()?, bbox[0], bbox[1], bbox[2], bbox[3]))
+                    }),
+                ))
+            }
+        };
+
+        Ok(ColumnarValue::Array(array))
+    }
+}
+
+/// Whether the bounding rectangle of `geometry` is within the bounding box.
+fn within_bbox(geometry: &Geometry, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> bool {
+    geometry.bounding_rect().is_some_and(|rect| {
+        rect.min().x >= min_x
+            && rect.min().y >= min_y
+            && rect.max().x <= max_x
+            && rect.max().y <= max_y
+    })
+}
+
+/// Parses the geometries in `array`, from WKB if it is binary and from WKT otherwise.
+fn geometries(array: &ArrayRef) -> Result<Vec<Option<Geometry>>> {
+    if let Some(wkb) = array.as_any().downcast_ref::<BinaryArray>() {
+        return from_wkb(wkb);
+    }
+
+    let wkt = cast(array, &DataType::Utf8)?;
+    let wkt = wkt
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| DataFusionError::Internal("Expected a string array".to_string()))?;
+    wkt.iter()
+        .map(|wkt| {
+            wkt.map(|wkt| {
+                Geometry::try_from_wkt_str(wkt)
+                    .map_err(|e| exec_datafusion_err!("Invalid WKT geometry {wkt}: {e}"))
+            })
+            .transpose()
+        })
+        .collect()
+}
+
+fn from_wkb(array: &GenericBinaryArray<i32>) -> Result<Vec<Option<Geometry>>> {
+    array
+        .iter()
+        .map(|wkb| {
+            wkb.map(|mut wkb| {
+                wkb::wkb_to_geom(&mut wkb)
+                    .map_err(|e| exec_datafusion_err!("Invalid WKB geometry: {e:?}"))
+            })
+            .transpose()
+        })
+        .collect()
+}
+
+fn to_wkb(geometry: &Geometry) -> Result<Vec<u8>> {
+    wkb::geom_to_wkb(geometry).map_err(|e| exec_datafusion_err!("Unable to write WKB: {e:?}"))
+}
+
+fn as_floats(array: &ArrayRef) -> Result<Float64Array> {
+    let array = cast(array, &DataType::Float64)?;
+    array
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .cloned()
+        .ok_or_else(|| DataFusionError::Internal("Expected a float array".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometries_from_wkt_and_wkb() {
+        let wkt: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("POLYGON((0 0, 10 0, 10 10, 0 10, 0 0))"),
+            None,
+        ]));
+        let polygons = geometries(&wkt).expect("valid WKT");
+        let polygon = polygons[0].as_ref().expect("a polygon");
+        assert!(polygons[1].is_none());
+
+        let point = Geometry::Point(Point::new(2.0, 3.0));
+        let wkb: ArrayRef = Arc::new(BinaryArray::from_iter_values([
+            to_wkb(&point).expect("valid point")
+        ]));
+        let points = geometries(&wkb).expect("valid WKB");
+        assert_eq!(points[0].as_ref(), Some(&point));
+
+        assert!(polygon.contains(&point));
+        assert!(within_bbox(polygon, 0.0, 0.0, 10.0, 10.0));
+        assert!(!within_bbox(polygon, 1.0, 0.0, 10.0, 10.0));
+    }
+}
@@ -0,0 +1,140 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Table functions declared in the spicepod.
+//!
+//! A function's SQL is planned once, as a prepared statement with a placeholder for each parameter, and each call
+//! binds its arguments to the placeholders.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use arrow::datatypes::DataType;
+use datafusion::{
+    datasource::{function::TableFunctionImpl, TableProvider, ViewTable},
+    error::{DataFusionError, Result},
+    execution::context::SessionContext,
+    logical_expr::{Expr, LogicalPlan},
+    scalar::ScalarValue,
+};
+
+/// A function planned from its SQL, with the types its arguments are cast to.
+struct PreparedFunction {
+    param_types: Vec<DataType>,
+    plan: LogicalPlan,
+}
+
+/// The table functions registered in DataFusion, which look up their current definition on each call so they can be
+/// replaced and removed.
+#[derive(Default)]
+pub(crate) struct TableFunctions {
+    functions: Arc<RwLock<HashMap<String, Arc<PreparedFunction>>>>,
+}
+
+impl TableFunctions {
+    /// Plans `sql`, which refers to the parameters as `$1`, `$2`, ..., and registers it as the table function `name`.
+    pub(crate) async fn register(
+        &self,
+        ctx: &SessionContext,
+        name: &str,
+        param_types: &[String],
+        sql: &str,
+    ) -> Result<()> {
+        let prepare = if param_types.is_empty() {
+            format!("PREPARE {name} AS {sql}")
+        } else {
+            format!("PREPARE {name}({}) AS {sql}", param_types.join(", "))
+        };
+        let plan = ctx.state().create_logical_plan(&prepare).await?;
+        let LogicalPlan::Prepare(prepared) = &plan else {
+            return Err(DataFusionError::Plan(format!(
+                "The SQL of table function {name} must be a query"
+            )));
+        };
+
+        let function = Arc::new(PreparedFunction {
+            param_types: prepared.data_types.clone(),
+            plan,
+        });
+        self.functions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.to_string(), function);
+
+        ctx.register_udtf(
+            name,
+            Arc::new(SpicepodTableFunction {
+                name: name.to_string(),
+                functions: Arc::clone(&self.functions),
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Removes the table function `name`, so calls to it fail until it is registered again.
+    pub(crate) fn remove(&self, name: &str) {
+        self.functions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(name);
+    }
+}
+
+struct SpicepodTableFunction {
+    name: String,
+    functions: Arc<RwLock<HashMap<String, Arc<PreparedFunction>>>>,
+}
+
+impl TableFunctionImpl for SpicepodTableFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let function = self
+            .functions
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&self.name)
+            .cloned()
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!("Table function {} was removed", self.name))
+            })?;
+
+        if args.len() != function.param_types.len() {
+            return Err(DataFusionError::Plan(format!(
+                "Table function {} expects {} arguments, received {}",
+                self.name,
+                function.param_types.len(),
+                args.len()
+            )));
+        }
+
+        let values = args
+            .iter()
+            .zip(&function.param_types)
+            .map(|(arg, data_type)| match arg {
+                Expr::Literal(value) => value.cast_to(data_type),
+                _ => Err(DataFusionError::Plan(format!(
+                    "The arguments of table function {} must be literals, received {arg}",
+                    self.name
+                ))),
+            })
+            .collect::<Result<Vec<ScalarValue>>>()?;
+
+        let plan = function.plan.clone().with_param_values(values)?;
+        Ok(Arc::new(ViewTable::try_new(plan, None)?))
+    }
+}
@@ -0,0 +1,402 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Functions for time series:
+//!
+//! - `date_bin_gapfill(stride, time[, origin])`: bins `time` like `date_bin`. When a query is grouped by it, the
+//!   results have a row for every bin between the first and last bins of the results, in each group.
+//! - `locf(value)`: fills the rows added by `date_bin_gapfill` with the last non-null value of their group.
+//! - `interpolate(value)`: fills the rows added by `date_bin_gapfill` by linear interpolation between the non-null
+//!   values of their group around them.
+//! - `asof(left.time >= right.time)`: in the `ON` clause of a join, joins each row of the left side to the row of the
+//!   right side with the latest time at or before its own, among the rows matching the other conditions. `>`, `<=`
+//!   and `<` select the latest earlier row, the earliest row at or after, and the earliest later row.
+//!
+//! Gap filling queries and ASOF joins are rewritten into scans of tables that compute their results from the results
+//! of their inputs.
+
+use std::{any::Any, fmt, sync::Arc};
+
+use arrow::{
+    array::RecordBatch,
+    compute::concat_batches,
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use async_trait::async_trait;
+use datafusion::{
+    common::{
+        plan_err,
+        tree_node::{Transformed, TreeNode, TreeNodeRecursion},
+        Column, DFSchema,
+    },
+    datasource::{provider_as_source, TableProvider, TableType},
+    error::{DataFusionError, Result},
+    execution::{
+        context::{SessionContext, SessionState},
+        SendableRecordBatchStream, TaskContext,
+    },
+    functions::datetime::date_bin,
+    logical_expr::{
+        ColumnarValue, Expr, LogicalPlan, LogicalPlanBuilder, ScalarUDF, ScalarUDFImpl, Signature,
+        Volatility,
+    },
+    physical_expr::EquivalenceProperties,
+    physical_plan::{
+        collect, stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionMode,
+        ExecutionPlan, Partitioning, PlanProperties,
+    },
+};
+use futures::stream;
+
+mod asof_join;
+mod gap_fill;
+
+/// Registers the time series functions in `ctx`.
+pub fn register_time_series_udfs(ctx: &SessionContext) {
+    for kind in [
+        TimeSeriesFunctionKind::DateBinGapfill,
+        TimeSeriesFunctionKind::Locf,
+        TimeSeriesFunctionKind::Interpolate,
+        TimeSeriesFunctionKind::Asof,
+    ] {
+        ctx.register_udf(ScalarUDF::new_from_impl(TimeSeriesFunction::new(kind)));
+    }
+}
+
+/// Rewrites the queries grouped by `date_bin_gapfill` and the joins with an `asof` condition in `plan`.
+pub(crate) fn rewrite_time_series_plan(plan: LogicalPlan) -> Result<LogicalPlan> {
+    let mut rewritten = false;
+
+    let plan = plan
+        .transform_up_with_subqueries(|node| {
+            let replacement = match &node {
+                LogicalPlan::Projection(projection) => gap_fill::rewrite(projection)?,
+                LogicalPlan::Join(join) => asof_join::rewrite(join)?,
+                _ => None,
+            };
+
+            match replacement {
+                Some(replacement) => {
+                    rewritten = true;
+                    Ok(Transformed::yes(replacement))
+                }
+                None if rewritten => node.recompute_schema().map(Transformed::yes),
+                None => Ok(Transformed::no(node)),
+            }
+        })?
+        .data;
+
+    // Gaps are only filled when the query selects from the aggregate directly.
+    plan.apply_with_subqueries(|node| match node {
+        LogicalPlan::Aggregate(aggregate)
+            if aggregate.group_expr.iter().any(gap_fill::is_gap_fill) =>
+        {
+            plan_err!("date_bin_gapfill can't be used in queries with HAVING")
+        }
+        _ => Ok(TreeNodeRecursion::Continue),
+    })?;
+
+    Ok(plan)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeSeriesFunctionKind {
+    DateBinGapfill,
+    Locf,
+    Interpolate,
+    Asof,
+}
+
+impl TimeSeriesFunctionKind {
+    fn name(self) -> &'static str {
+        match self {
+            TimeSeriesFunctionKind::DateBinGapfill => "date_bin_gapfill",
+            TimeSeriesFunctionKind::Locf => "locf",
+            TimeSeriesFunctionKind::Interpolate => "interpolate",
+            TimeSeriesFunctionKind::Asof => "asof",
+        }
+    }
+}
+
+/// Returns the arguments of `expr` if it is a call to the function `kind`.
+fn function_args(expr: &Expr, kind: TimeSeriesFunctionKind) -> Option<&[Expr]> {
+    match expr {
+        Expr::ScalarFunction(function) if function.func.name() == kind.name() => {
+            Some(&function.args)
+        }
+        _ => None,
+    }
+}
+
+/// `date_bin_gapfill` computes bins with `date_bin`, while the other functions only mark the expressions the plan
+/// rewrites, so they fail if they are left in a plan.
+#[derive(Debug)]
+struct TimeSeriesFunction {
+    kind: TimeSeriesFunctionKind,
+    signature: Signature,
+}
+
+impl TimeSeriesFunction {
+    fn new(kind: TimeSeriesFunctionKind) -> Self {
+        let signature = match kind {
+            TimeSeriesFunctionKind::DateBinGapfill => date_bin().signature().clone(),
+            TimeSeriesFunctionKind::Locf | TimeSeriesFunctionKind::Interpolate => {
+                Signature::any(1, Volatility::Immutable)
+            }
+            TimeSeriesFunctionKind::Asof => {
+                Signature::exact(vec![DataType::Boolean], Volatility::Immutable)
+            }
+        };
+
+        Self { kind, signature }
+    }
+}
+
+impl ScalarUDFImpl for TimeSeriesFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, args: &[DataType]) -> Result<DataType> {
+        match self.kind {
+            TimeSeriesFunctionKind::DateBinGapfill => date_bin().return_type(args),
+            TimeSeriesFunctionKind::Locf | TimeSeriesFunctionKind::Interpolate => {
+                Ok(args[0].clone())
+            }
+            TimeSeriesFunctionKind::Asof => Ok(DataType::Boolean),
+        }
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        match self.kind {
+            TimeSeriesFunctionKind::DateBinGapfill => date_bin().invoke(args),
+            TimeSeriesFunctionKind::Locf | TimeSeriesFunctionKind::Interpolate => plan_err!(
+                "{} can only be used on the selected values of a query grouped by date_bin_gapfill",
+                self.name()
+            ),
+            TimeSeriesFunctionKind::Asof => {
+                plan_err!("asof can only be used in the ON clause of an inner or left join")
+            }
+        }
+    }
+}
+
+/// Computes the rows of a table from all the rows of each of its inputs.
+trait TimeSeriesOperation: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the rows of the table, whose columns have the types of `schema`.
+    fn compute(&self, schema: &SchemaRef, inputs: Vec<RecordBatch>) -> Result<RecordBatch>;
+}
+
+/// Returns a plan scanning the results of `operation` on `inputs`, with the columns of `schema`.
+fn plan_operation(
+    operation: Arc<dyn TimeSeriesOperation>,
+    inputs: Vec<LogicalPlan>,
+    schema: &DFSchema,
+) -> Result<LogicalPlan> {
+    let table_name = operation.name();
+
+    // The columns are renamed by position, as their qualified names may not be unique within a single table.
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| Field::new(format!("c{i}"), field.data_type().clone(), true))
+        .collect();
+    let table = TimeSeriesTable {
+        operation,
+        inputs,
+        schema: Arc::new(Schema::new(fields)),
+    };
+
+    let columns = schema.iter().enumerate().map(|(i, (qualifier, field))| {
+        Expr::Column(Column::new(Some(table_name), format!("c{i}")))
+            .alias_qualified(qualifier.cloned(), field.name())
+    });
+
+    LogicalPlanBuilder::scan(table_name, provider_as_source(Arc::new(table)), None)?
+        .project(columns)?
+        .build()
+}
+
+struct TimeSeriesTable {
+    operation: Arc<dyn TimeSeriesOperation>,
+    inputs: Vec<LogicalPlan>,
+    schema: SchemaRef,
+}
+
+#[async_trait]
+impl TableProvider for TimeSeriesTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        for input in &self.inputs {
+            inputs.push(state.create_physical_plan(input).await?);
+        }
+
+        Ok(Arc::new(TimeSeriesExec::try_new(
+            Arc::clone(&self.operation),
+            inputs,
+            self.schema(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+struct TimeSeriesExec {
+    operation: Arc<dyn TimeSeriesOperation>,
+    inputs: Vec<Arc<dyn ExecutionPlan>>,
+    schema: SchemaRef,
+    projection: Option<Vec<usize>>,
+    properties: PlanProperties,
+}
+
+impl TimeSeriesExec {
+    fn try_new(
+        operation: Arc<dyn TimeSeriesOperation>,
+        inputs: Vec<Arc<dyn ExecutionPlan>>,
+        schema: SchemaRef,
+        projection: Option<Vec<usize>>,
+    ) -> Result<Self> {
+        let projected_schema = match &projection {
+            Some(projection) => Arc::new(schema.project(projection)?),
+            None => Arc::clone(&schema),
+        };
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(projected_schema),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Bounded,
+        );
+
+        Ok(Self {
+            operation,
+            inputs,
+            schema,
+            projection,
+            properties,
+        })
+    }
+}
+
+impl fmt::Debug for TimeSeriesExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TimeSeriesExec: {}", self.operation.name())
+    }
+}
+
+impl DisplayAs for TimeSeriesExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TimeSeriesExec: {:?}", self.operation)
+    }
+}
+
+impl ExecutionPlan for TimeSeriesExec {
+    fn name(&self) -> &'static str {
+        "TimeSeriesExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(self.properties.eq_properties.schema())
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        self.inputs.iter().collect()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() != self.inputs.len() {
+            return Err(DataFusionError::Execution(format!(
+                "TimeSeriesExec expects {} inputs",
+                self.inputs.len()
+            )));
+        }
+
+        Ok(Arc::new(TimeSeriesExec::try_new(
+            Arc::clone(&self.operation),
+            children,
+            Arc::clone(&self.schema),
+            self.projection.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        _partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let operation = Arc::clone(&self.operation);
+        let inputs = self.inputs.clone();
+        let schema = Arc::clone(&self.schema);
+        let projection = self.projection.clone();
+
+        let batch = async move {
+            let mut batches = Vec::with_capacity(inputs.len());
+            for input in inputs {
+                let input_schema = input.schema();
+                let results = collect(input, Arc::clone(&context)).await?;
+                batches.push(concat_batches(&input_schema, &results)?);
+            }
+
+            let batch = operation.compute(&schema, batches)?;
+            match projection {
+                Some(projection) => Ok(batch.project(&projection)?),
+                None => Ok(batch),
+            }
+        };
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream::once(batch),
+        )))
+    }
+}
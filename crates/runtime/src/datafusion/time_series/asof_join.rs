@@ -0,0 +1,352 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, Int64Array, RecordBatch, UInt64Array},
+    compute::{cast, take},
+    datatypes::{DataType, Int64Type, SchemaRef, TimeUnit},
+    row::{RowConverter, Rows, SortField},
+};
+use datafusion::{
+    common::{internal_err, plan_err},
+    error::Result,
+    logical_expr::{
+        utils::split_conjunction, BinaryExpr, Expr, Join, JoinType, LogicalPlan, Operator,
+    },
+};
+
+use super::{function_args, TimeSeriesFunctionKind, TimeSeriesOperation};
+
+/// Rewrites a join with an `asof` condition into a scan of the results of the ASOF join.
+pub(super) fn rewrite(join: &Join) -> Result<Option<LogicalPlan>> {
+    let Some(filter) = &join.filter else {
+        return Ok(None);
+    };
+
+    let mut asof = None;
+    let mut keys = join.on.clone();
+    let mut others = vec![];
+    for condition in split_conjunction(filter) {
+        if let Some(args) = function_args(condition, TimeSeriesFunctionKind::Asof) {
+            if asof.replace(&args[0]).is_some() {
+                return plan_err!("A join can only have one asof condition");
+            }
+        } else if let Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        }) = condition
+        {
+            keys.push((left.as_ref().clone(), right.as_ref().clone()));
+        } else {
+            others.push(condition);
+        }
+    }
+
+    let Some(asof) = asof else {
+        return Ok(None);
+    };
+    if let Some(other) = others.first() {
+        return plan_err!(
+            "ASOF joins only support equality conditions besides asof, received {other}"
+        );
+    }
+    let keep_unmatched = match join.join_type {
+        JoinType::Inner => false,
+        JoinType::Left => true,
+        join_type => {
+            return plan_err!("ASOF joins must be inner or left joins, received {join_type}")
+        }
+    };
+
+    let Expr::BinaryExpr(BinaryExpr { left, op, right }) = asof else {
+        return plan_err!(
+            "asof expects a comparison of times, such as asof(left.time >= right.time)"
+        );
+    };
+    let Some((left_time, right_time, swapped)) = columns(join, left, right) else {
+        return plan_err!(
+            "asof expects a comparison of a column of each side of the join, received {asof}"
+        );
+    };
+    let op = if swapped { op.swap() } else { Some(*op) };
+    let direction = match op {
+        Some(Operator::GtEq) => Direction::Backward { inclusive: true },
+        Some(Operator::Gt) => Direction::Backward { inclusive: false },
+        Some(Operator::LtEq) => Direction::Forward { inclusive: true },
+        Some(Operator::Lt) => Direction::Forward { inclusive: false },
+        _ => return plan_err!("asof expects one of >=, >, <= or <, received {asof}"),
+    };
+
+    let mut left_keys = Vec::with_capacity(keys.len());
+    let mut right_keys = Vec::with_capacity(keys.len());
+    for (a, b) in &keys {
+        let Some((left_key, right_key, _)) = columns(join, a, b) else {
+            return plan_err!("ASOF joins only support equality conditions on a column of each side, received {a} = {b}");
+        };
+        left_keys.push(left_key);
+        right_keys.push(right_key);
+    }
+
+    let asof_join = AsofJoin {
+        left_keys,
+        right_keys,
+        left_time,
+        right_time,
+        direction,
+        keep_unmatched,
+    };
+
+    super::plan_operation(
+        Arc::new(asof_join),
+        vec![join.left.as_ref().clone(), join.right.as_ref().clone()],
+        &join.schema,
+    )
+    .map(Some)
+}
+
+/// Returns the indexes of the columns of the left and right sides of the join `a` and `b` refer to, and whether `a`
+/// refers to the right side.
+fn columns(join: &Join, a: &Expr, b: &Expr) -> Option<(usize, usize, bool)> {
+    let (Expr::Column(a), Expr::Column(b)) = (a, b) else {
+        return None;
+    };
+    let (left, right) = (join.left.schema(), join.right.schema());
+
+    if let (Ok(l), Ok(r)) = (left.index_of_column(a), right.index_of_column(b)) {
+        return Some((l, r, false));
+    }
+    if let (Ok(l), Ok(r)) = (left.index_of_column(b), right.index_of_column(a)) {
+        return Some((l, r, true));
+    }
+    None
+}
+
+/// Which row of the right side a row of the left side is joined to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// The latest row before the row of the left side.
+    Backward { inclusive: bool },
+    /// The earliest row after the row of the left side.
+    Forward { inclusive: bool },
+}
+
+impl Direction {
+    /// Returns the row to join to a row at `time`, from the matching rows of the right side sorted by time.
+    fn find(self, rows: &[(i64, usize)], time: i64) -> Option<usize> {
+        match self {
+            Direction::Backward { inclusive } => {
+                let end = rows.partition_point(|(t, _)| *t < time || (inclusive && *t == time));
+                end.checked_sub(1).map(|i| rows[i].1)
+            }
+            Direction::Forward { inclusive } => {
+                let start = rows.partition_point(|(t, _)| *t < time || (!inclusive && *t == time));
+                rows.get(start).map(|(_, row)| *row)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AsofJoin {
+    left_keys: Vec<usize>,
+    right_keys: Vec<usize>,
+    left_time: usize,
+    right_time: usize,
+    direction: Direction,
+    /// Whether the rows of the left side without a match are kept, as in a left join.
+    keep_unmatched: bool,
+}
+
+impl TimeSeriesOperation for AsofJoin {
+    fn name(&self) -> &'static str {
+        "asof_join"
+    }
+
+    fn compute(&self, schema: &SchemaRef, inputs: Vec<RecordBatch>) -> Result<RecordBatch> {
+        let [left, right] = &inputs[..] else {
+            return internal_err!("ASOF joins expect two inputs");
+        };
+
+        let (left_rows, right_rows) = self.matches(left.columns(), right.columns())?;
+        let left_rows: UInt64Array = left_rows.into_iter().map(|row| row as u64).collect();
+        let right_rows: UInt64Array = right_rows
+            .into_iter()
+            .map(|row| row.map(|row| row as u64))
+            .collect();
+
+        let columns = left
+            .columns()
+            .iter()
+            .map(|column| take(column.as_ref(), &left_rows, None))
+            .chain(
+                right
+                    .columns()
+                    .iter()
+                    .map(|column| take(column.as_ref(), &right_rows, None)),
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RecordBatch::try_new(Arc::clone(schema), columns)?)
+    }
+}
+
+impl AsofJoin {
+    /// Returns the joined rows of the left and right sides.
+    fn matches(
+        &self,
+        left: &[ArrayRef],
+        right: &[ArrayRef],
+    ) -> Result<(Vec<usize>, Vec<Option<usize>>)> {
+        let left_keys: Vec<ArrayRef> = self
+            .left_keys
+            .iter()
+            .map(|&i| Arc::clone(&left[i]))
+            .collect();
+        // The keys of the right side are cast to the types of the left side so that equal keys have equal rows.
+        let right_keys = self
+            .right_keys
+            .iter()
+            .zip(&left_keys)
+            .map(|(&i, left_key)| cast(&right[i], left_key.data_type()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let left_times = times(&left[self.left_time])?;
+        let right_times = times(&right[self.right_time])?;
+
+        let (left_rows, right_rows) = if left_keys.is_empty() {
+            (None, None)
+        } else {
+            let converter = RowConverter::new(
+                left_keys
+                    .iter()
+                    .map(|key| SortField::new(key.data_type().clone()))
+                    .collect(),
+            )?;
+            (
+                Some(converter.convert_columns(&left_keys)?),
+                Some(converter.convert_columns(&right_keys)?),
+            )
+        };
+
+        // The rows of the right side with each key, sorted by time.
+        let mut candidates: HashMap<Vec<u8>, Vec<(i64, usize)>> = HashMap::new();
+        for row in 0..right_times.len() {
+            if right_times.is_valid(row) && right_keys.iter().all(|key| key.is_valid(row)) {
+                candidates
+                    .entry(key(right_rows.as_ref(), row))
+                    .or_default()
+                    .push((right_times.value(row), row));
+            }
+        }
+        for rows in candidates.values_mut() {
+            rows.sort_unstable();
+        }
+
+        let mut joined_left = Vec::with_capacity(left_times.len());
+        let mut joined_right = Vec::with_capacity(left_times.len());
+        for row in 0..left_times.len() {
+            let matched =
+                if left_times.is_valid(row) && left_keys.iter().all(|key| key.is_valid(row)) {
+                    candidates
+                        .get(&key(left_rows.as_ref(), row))
+                        .and_then(|rows| self.direction.find(rows, left_times.value(row)))
+                } else {
+                    None
+                };
+
+            if matched.is_some() || self.keep_unmatched {
+                joined_left.push(row);
+                joined_right.push(matched);
+            }
+        }
+
+        Ok((joined_left, joined_right))
+    }
+}
+
+fn key(rows: Option<&Rows>, row: usize) -> Vec<u8> {
+    rows.map_or_else(Vec::new, |rows| rows.row(row).as_ref().to_vec())
+}
+
+/// Returns the times in `array` as integers, in nanoseconds for timestamps and dates.
+fn times(array: &ArrayRef) -> Result<Int64Array> {
+    let array = match array.data_type() {
+        DataType::Timestamp(_, tz) => cast(
+            array,
+            &DataType::Timestamp(TimeUnit::Nanosecond, tz.clone()),
+        )?,
+        DataType::Date32 | DataType::Date64 => {
+            cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?
+        }
+        _ => Arc::clone(array),
+    };
+
+    Ok(cast(&array, &DataType::Int64)?
+        .as_primitive::<Int64Type>()
+        .clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{
+        Float64Array, StringArray, TimestampMillisecondArray, TimestampSecondArray,
+    };
+
+    use super::*;
+
+    #[test]
+    fn joins_the_latest_earlier_rows() {
+        let trades: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(vec!["a", "b", "a", "c"])),
+            Arc::new(TimestampSecondArray::from(vec![10, 10, 20, 10])),
+        ];
+        let quotes: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(vec!["a", "a", "b", "a"])),
+            Arc::new(TimestampMillisecondArray::from(vec![
+                5_000, 15_000, 11_000, 20_000,
+            ])),
+            Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])),
+        ];
+
+        let mut asof_join = AsofJoin {
+            left_keys: vec![0],
+            right_keys: vec![0],
+            left_time: 1,
+            right_time: 1,
+            direction: Direction::Backward { inclusive: true },
+            keep_unmatched: true,
+        };
+        assert_eq!(
+            asof_join.matches(&trades, &quotes).expect("joined"),
+            (vec![0, 1, 2, 3], vec![Some(0), None, Some(3), None])
+        );
+
+        asof_join.direction = Direction::Backward { inclusive: false };
+        asof_join.keep_unmatched = false;
+        assert_eq!(
+            asof_join.matches(&trades, &quotes).expect("joined"),
+            (vec![0, 2], vec![Some(0), Some(1)])
+        );
+
+        asof_join.direction = Direction::Forward { inclusive: true };
+        assert_eq!(
+            asof_join.matches(&trades, &quotes).expect("joined"),
+            (vec![0, 1, 2], vec![Some(1), Some(2), Some(3)])
+        );
+    }
+}
@@ -0,0 +1,484 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef, AsArray, Float64Array, Int64Array, RecordBatch, UInt64Array},
+    compute::{cast, lexsort_to_indices, max, min, take, SortColumn},
+    datatypes::{DataType, Float64Type, Int64Type, SchemaRef, TimeUnit},
+    row::{RowConverter, Rows, SortField},
+};
+use datafusion::{
+    common::{exec_err, internal_err, plan_err},
+    error::Result,
+    logical_expr::{Aggregate, Expr, LogicalPlan, Projection},
+    scalar::ScalarValue,
+};
+
+use super::{function_args, TimeSeriesFunctionKind, TimeSeriesOperation};
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// The most rows a group can have once its gaps are filled.
+const MAX_BINS: usize = 1_000_000;
+
+pub(super) fn is_gap_fill(expr: &Expr) -> bool {
+    gap_fill_args(expr).is_some()
+}
+
+fn gap_fill_args(expr: &Expr) -> Option<&[Expr]> {
+    match expr {
+        Expr::Alias(alias) => gap_fill_args(&alias.expr),
+        expr => function_args(expr, TimeSeriesFunctionKind::DateBinGapfill),
+    }
+}
+
+/// Rewrites a projection of an aggregate grouped by `date_bin_gapfill` into a scan of its results with the gaps
+/// filled.
+pub(super) fn rewrite(projection: &Projection) -> Result<Option<LogicalPlan>> {
+    let LogicalPlan::Aggregate(aggregate) = projection.input.as_ref() else {
+        return Ok(None);
+    };
+    let Some((bin, args)) = aggregate
+        .group_expr
+        .iter()
+        .enumerate()
+        .find_map(|(i, expr)| gap_fill_args(expr).map(|args| (i, args)))
+    else {
+        return Ok(None);
+    };
+    let stride = stride(&args[0])?;
+
+    let mut exprs = Vec::with_capacity(projection.expr.len());
+    let mut roles = Vec::with_capacity(projection.expr.len());
+    for (i, expr) in projection.expr.iter().enumerate() {
+        let expr = match expr {
+            Expr::Alias(alias) => alias.expr.as_ref(),
+            expr => expr,
+        };
+        let (value, fill) = if let Some(args) = function_args(expr, TimeSeriesFunctionKind::Locf) {
+            (&args[0], Fill::Locf)
+        } else if let Some(args) = function_args(expr, TimeSeriesFunctionKind::Interpolate) {
+            (&args[0], Fill::Interpolate)
+        } else {
+            (expr, Fill::Null)
+        };
+
+        roles.push(role(value, aggregate, bin, fill));
+
+        // Keep the name of the column, as `locf` and `interpolate` are removed.
+        let (qualifier, field) = projection.schema.qualified_field(i);
+        exprs.push(
+            value
+                .clone()
+                .alias_qualified(qualifier.cloned(), field.name()),
+        );
+    }
+
+    let Some(time) = roles.iter().position(|role| *role == Role::Time) else {
+        return plan_err!("A query grouped by date_bin_gapfill must select its bins");
+    };
+
+    let input = LogicalPlan::Projection(Projection::try_new(exprs, Arc::clone(&projection.input))?);
+    let gap_fill = GapFill {
+        time,
+        stride,
+        roles,
+    };
+
+    super::plan_operation(Arc::new(gap_fill), vec![input], &projection.schema).map(Some)
+}
+
+fn role(value: &Expr, aggregate: &Aggregate, bin: usize, fill: Fill) -> Role {
+    let index = match value {
+        Expr::Column(column) => aggregate.schema.index_of_column(column).ok(),
+        _ => None,
+    };
+
+    match index {
+        Some(index) if index == bin => Role::Time,
+        Some(index) if index < aggregate.group_expr.len() => Role::Group,
+        _ => Role::Value(fill),
+    }
+}
+
+/// Returns the stride of `date_bin_gapfill` in nanoseconds.
+fn stride(expr: &Expr) -> Result<i64> {
+    let stride = match expr {
+        Expr::Literal(ScalarValue::IntervalMonthDayNano(Some(interval)))
+            if interval.months == 0 =>
+        {
+            i64::from(interval.days)
+                .checked_mul(NANOS_PER_DAY)
+                .and_then(|days| days.checked_add(interval.nanoseconds))
+        }
+        Expr::Literal(ScalarValue::IntervalDayTime(Some(interval))) => i64::from(interval.days)
+            .checked_mul(NANOS_PER_DAY)
+            .and_then(|days| days.checked_add(i64::from(interval.milliseconds) * 1_000_000)),
+        _ => None,
+    };
+
+    match stride {
+        Some(stride) if stride > 0 => Ok(stride),
+        _ => plan_err!(
+            "The stride of date_bin_gapfill must be a positive interval without months, received {expr}"
+        ),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Time,
+    Group,
+    Value(Fill),
+}
+
+/// How the values of the added rows are filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fill {
+    Null,
+    Locf,
+    Interpolate,
+}
+
+#[derive(Debug)]
+struct GapFill {
+    /// The column of the bins.
+    time: usize,
+    /// The stride of the bins, in nanoseconds.
+    stride: i64,
+    roles: Vec<Role>,
+}
+
+impl TimeSeriesOperation for GapFill {
+    fn name(&self) -> &'static str {
+        "gap_fill"
+    }
+
+    fn compute(&self, schema: &SchemaRef, inputs: Vec<RecordBatch>) -> Result<RecordBatch> {
+        let [input] = &inputs[..] else {
+            return internal_err!("Gap filling expects one input");
+        };
+
+        let columns = self.fill(input.columns())?;
+        Ok(RecordBatch::try_new(Arc::clone(schema), columns)?)
+    }
+}
+
+impl GapFill {
+    /// Returns the columns sorted by group and time, with a row for each missing bin of each group.
+    fn fill(&self, columns: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        let groups: Vec<usize> = (0..self.roles.len())
+            .filter(|&i| self.roles[i] == Role::Group)
+            .collect();
+
+        let sort_columns: Vec<SortColumn> = groups
+            .iter()
+            .chain([&self.time])
+            .map(|&i| SortColumn {
+                values: Arc::clone(&columns[i]),
+                options: None,
+            })
+            .collect();
+        let sorted = lexsort_to_indices(&sort_columns, None)?;
+        let columns = columns
+            .iter()
+            .map(|column| take(column.as_ref(), &sorted, None))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let time_type = columns[self.time].data_type().clone();
+        let DataType::Timestamp(unit, _) = &time_type else {
+            return exec_err!("date_bin_gapfill expects timestamps, received {time_type}");
+        };
+        let stride = self.stride / nanos_per_unit(*unit);
+        if stride == 0 {
+            return exec_err!("The stride of date_bin_gapfill is smaller than its time unit");
+        }
+
+        let times = cast(&columns[self.time], &DataType::Int64)?;
+        let times = times.as_primitive::<Int64Type>();
+        let (Some(first), Some(last)) = (min(times), max(times)) else {
+            return Ok(columns);
+        };
+        if usize::try_from((last - first) / stride).map_or(true, |bins| bins >= MAX_BINS) {
+            return exec_err!(
+                "date_bin_gapfill would add more than {MAX_BINS} rows to a group. Use a larger stride or a smaller time range."
+            );
+        }
+
+        let keys = if groups.is_empty() {
+            None
+        } else {
+            let converter = RowConverter::new(
+                groups
+                    .iter()
+                    .map(|&i| SortField::new(columns[i].data_type().clone()))
+                    .collect(),
+            )?;
+            let group_columns: Vec<ArrayRef> =
+                groups.iter().map(|&i| Arc::clone(&columns[i])).collect();
+            Some(converter.convert_columns(&group_columns)?)
+        };
+
+        let mut rows = FilledRows::default();
+        for series in series(keys.as_ref(), times.len()) {
+            rows.fill_series(series, times, first, last, stride);
+        }
+
+        let mut filled = Vec::with_capacity(columns.len());
+        for (i, column) in columns.iter().enumerate() {
+            filled.push(match self.roles[i] {
+                Role::Time => cast(&Int64Array::from(rows.times.clone()), &time_type)?,
+                Role::Group => take(
+                    column.as_ref(),
+                    &indices(rows.groups.iter().copied().map(Some)),
+                    None,
+                )?,
+                Role::Value(Fill::Null) => {
+                    take(column.as_ref(), &indices(rows.sources.clone()), None)?
+                }
+                Role::Value(Fill::Locf) => {
+                    take(column.as_ref(), &indices(rows.locf(column)), None)?
+                }
+                Role::Value(Fill::Interpolate) => rows.interpolate(column)?,
+            });
+        }
+
+        Ok(filled)
+    }
+}
+
+/// Returns the ranges of consecutive rows in the same group.
+fn series(keys: Option<&Rows>, len: usize) -> Vec<Range<usize>> {
+    let mut series = vec![];
+    let mut start = 0;
+    for row in 1..=len {
+        let same_group = row < len && keys.map_or(true, |keys| keys.row(row) == keys.row(start));
+        if !same_group {
+            series.push(start..row);
+            start = row;
+        }
+    }
+    series
+}
+
+fn indices(rows: impl IntoIterator<Item = Option<usize>>) -> UInt64Array {
+    rows.into_iter()
+        .map(|row| row.map(|row| row as u64))
+        .collect()
+}
+
+/// The rows of the filled results.
+#[derive(Default)]
+struct FilledRows {
+    /// The row of the sorted results each row is from, or `None` for the added rows.
+    sources: Vec<Option<usize>>,
+    /// A row of the sorted results in the group of each row.
+    groups: Vec<usize>,
+    times: Vec<Option<i64>>,
+    /// The ranges of the rows of each group.
+    series: Vec<Range<usize>>,
+}
+
+impl FilledRows {
+    /// Adds the rows of a group, which are sorted by time, and the rows for its missing bins between `first` and
+    /// `last`.
+    fn fill_series(
+        &mut self,
+        series: Range<usize>,
+        times: &Int64Array,
+        first: i64,
+        last: i64,
+        stride: i64,
+    ) {
+        let start = self.sources.len();
+        let group = series.start;
+        let mut row = series.start;
+        let mut bin = Some(first);
+
+        while row < series.end || bin.is_some_and(|bin| bin <= last) {
+            let time = (row < series.end).then(|| times.is_valid(row).then(|| times.value(row)));
+            match (time, bin) {
+                // The rows without a time and any after the last bin are kept as they are.
+                (Some(time), bin) if time.zip(bin).map_or(true, |(time, bin)| time <= bin) => {
+                    if time == bin {
+                        bin = bin.and_then(|bin| bin.checked_add(stride));
+                    }
+                    self.sources.push(Some(row));
+                    self.times.push(time);
+                    row += 1;
+                }
+                (_, Some(missing)) if missing <= last => {
+                    self.sources.push(None);
+                    self.times.push(Some(missing));
+                    bin = missing.checked_add(stride);
+                }
+                (Some(time), _) => {
+                    self.sources.push(Some(row));
+                    self.times.push(time);
+                    row += 1;
+                }
+                (None, _) => break,
+            }
+            self.groups.push(group);
+        }
+
+        self.series.push(start..self.sources.len());
+    }
+
+    /// Returns the row of the last non-null value of `column` at or before each row of its group.
+    fn locf(&self, column: &ArrayRef) -> Vec<Option<usize>> {
+        let mut indices = Vec::with_capacity(self.sources.len());
+        for series in &self.series {
+            let mut last = None;
+            for source in &self.sources[series.clone()] {
+                if let Some(source) = *source {
+                    if column.is_valid(source) {
+                        last = Some(source);
+                    }
+                }
+                indices.push(last);
+            }
+        }
+        indices
+    }
+
+    /// Returns the values of `column`, with its nulls between two non-null values of their group interpolated
+    /// linearly by time.
+    #[allow(clippy::cast_precision_loss)]
+    fn interpolate(&self, column: &ArrayRef) -> Result<ArrayRef> {
+        let values = take(column.as_ref(), &indices(self.sources.clone()), None)?;
+        let values = cast(&values, &DataType::Float64)?;
+        let mut interpolated: Vec<Option<f64>> =
+            values.as_primitive::<Float64Type>().iter().collect();
+
+        for series in &self.series {
+            let mut previous: Option<(usize, i64, f64)> = None;
+            for row in series.clone() {
+                let (Some(time), Some(value)) = (self.times[row], interpolated[row]) else {
+                    continue;
+                };
+                if let Some((previous_row, previous_time, previous_value)) = previous {
+                    for between in previous_row + 1..row {
+                        if let Some(between_time) = self.times[between] {
+                            let ratio = (between_time - previous_time) as f64
+                                / (time - previous_time) as f64;
+                            interpolated[between] =
+                                Some(previous_value + (value - previous_value) * ratio);
+                        }
+                    }
+                }
+                previous = Some((row, time, value));
+            }
+        }
+
+        Ok(cast(&Float64Array::from(interpolated), column.data_type())?)
+    }
+}
+
+fn nanos_per_unit(unit: TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int64Array, StringArray, TimestampSecondArray};
+
+    use super::*;
+
+    #[test]
+    fn fills_missing_bins_of_each_group() {
+        let gap_fill = GapFill {
+            time: 0,
+            stride: 60 * 1_000_000_000,
+            roles: vec![
+                Role::Time,
+                Role::Group,
+                Role::Value(Fill::Null),
+                Role::Value(Fill::Locf),
+                Role::Value(Fill::Interpolate),
+            ],
+        };
+        let values = || Arc::new(Int64Array::from(vec![Some(30), Some(10), Some(40)])) as ArrayRef;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampSecondArray::from(vec![180, 0, 0])),
+            Arc::new(StringArray::from(vec!["a", "a", "b"])),
+            values(),
+            values(),
+            values(),
+        ];
+
+        let filled = gap_fill.fill(&columns).expect("gaps filled");
+
+        assert_eq!(
+            filled[0]
+                .as_primitive::<arrow::datatypes::TimestampSecondType>()
+                .values()
+                .to_vec(),
+            vec![0, 60, 120, 180, 0, 60, 120, 180]
+        );
+        assert_eq!(
+            filled[1]
+                .as_string::<i32>()
+                .iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            vec!["a", "a", "a", "a", "b", "b", "b", "b"]
+        );
+        let values = |i: usize| {
+            filled[i]
+                .as_primitive::<Int64Type>()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            values(2),
+            vec![Some(10), None, None, Some(30), Some(40), None, None, None]
+        );
+        assert_eq!(
+            values(3),
+            vec![
+                Some(10),
+                Some(10),
+                Some(10),
+                Some(30),
+                Some(40),
+                Some(40),
+                Some(40),
+                Some(40)
+            ]
+        );
+        assert_eq!(
+            values(4),
+            vec![
+                Some(10),
+                Some(16),
+                Some(23),
+                Some(30),
+                Some(40),
+                None,
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Queries the previous loads of accelerated datasets that retain versions, to compare the current data to a previous
+//! load:
+//!
+//! - `acceleration_version(dataset, version)` reads a version by its number, or the version loaded at or before a
+//!   time, i.e. `SELECT * FROM acceleration_version('taxi_trips', '2024-07-01T00:00:00Z')`.
+//! - `acceleration_versions(dataset)` lists the versions, with when they were loaded.
+//! - `SELECT * FROM taxi_trips FOR SYSTEM_TIME AS OF '2024-07-01T00:00:00Z'` is rewritten to `acceleration_version`.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::{
+    array::{TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use chrono::DateTime;
+use datafusion::{
+    catalog::CatalogProvider,
+    datasource::{
+        file_format::parquet::ParquetFormat,
+        function::TableFunctionImpl,
+        listing::{ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl},
+        source_as_provider, MemTable, TableProvider, TableType,
+    },
+    error::{DataFusionError, Result},
+    execution::context::{SessionContext, SessionState},
+    logical_expr::{Expr, TableProviderFilterPushDown, TableScan},
+    physical_plan::ExecutionPlan,
+    scalar::ScalarValue,
+    sql::{
+        sqlparser::{
+            dialect::PostgreSqlDialect,
+            keywords::Keyword,
+            tokenizer::{Location, Token, Tokenizer},
+        },
+        TableReference,
+    },
+};
+use tokio::runtime::Handle;
+
+use crate::accelerated_table::{
+    versions::{RetainedVersions, VersionSelector},
+    AcceleratedTable,
+};
+
+use super::{SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA};
+
+const VERSION_FUNCTION: &str = "acceleration_version";
+const VERSIONS_FUNCTION: &str = "acceleration_versions";
+
+/// Registers `acceleration_version` and `acceleration_versions` in `ctx`, reading the datasets of `catalog`.
+pub(crate) fn register_time_travel_udtfs(ctx: &SessionContext, catalog: Arc<dyn CatalogProvider>) {
+    ctx.register_udtf(
+        VERSION_FUNCTION,
+        Arc::new(VersionFunction {
+            catalog: Arc::clone(&catalog),
+        }),
+    );
+    ctx.register_udtf(VERSIONS_FUNCTION, Arc::new(VersionsFunction { catalog }));
+}
+
+/// Returns the dataset a scan reads a version of, if it is a scan of `acceleration_version`.
+pub(crate) fn versioned_table(scan: &TableScan) -> Option<TableReference> {
+    let provider = source_as_provider(&scan.source).ok()?;
+    provider
+        .as_any()
+        .downcast_ref::<VersionTable>()
+        .map(|version| version.table.clone())
+}
+
+/// Finds the accelerated dataset `table` of `catalog`, with the versions it retains.
+fn retained_versions(
+    catalog: &Arc<dyn CatalogProvider>,
+    table: &str,
+) -> Result<(TableReference, SchemaRef, RetainedVersions)> {
+    let table = TableReference::from(table);
+    let resolved = table
+        .clone()
+        .resolve(SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA);
+    let not_found = || DataFusionError::Plan(format!("Table {table} not found"));
+    if resolved.catalog.as_ref() != SPICE_DEFAULT_CATALOG {
+        return Err(not_found());
+    }
+    let schema = catalog.schema(&resolved.schema).ok_or_else(not_found)?;
+    let provider =
+        tokio::task::block_in_place(|| Handle::current().block_on(schema.table(&resolved.table)))?
+            .ok_or_else(not_found)?;
+
+    let versions = provider
+        .as_any()
+        .downcast_ref::<AcceleratedTable>()
+        .and_then(AcceleratedTable::retained_versions)
+        .ok_or_else(|| {
+            DataFusionError::Plan(format!(
+                "Dataset {table} doesn't retain versions, set retained_versions in its acceleration"
+            ))
+        })?;
+
+    Ok((
+        TableReference::full(resolved.catalog, resolved.schema, resolved.table),
+        provider.schema(),
+        versions.clone(),
+    ))
+}
+
+fn dataset_name(arg: &Expr, function: &str) -> Result<String> {
+    match arg {
+        Expr::Literal(ScalarValue::Utf8(Some(table))) => Ok(table.clone()),
+        _ => Err(DataFusionError::Plan(format!(
+            "{function} expects the name of the dataset as a string, i.e. {function}('taxi_trips')"
+        ))),
+    }
+}
+
+/// Reads a version by its number, or the version loaded at or before a time.
+fn version_selector(arg: &Expr) -> Result<VersionSelector> {
+    let invalid = || {
+        DataFusionError::Plan(format!(
+            "{VERSION_FUNCTION} expects a version number or a timestamp, i.e. {VERSION_FUNCTION}('taxi_trips', 2)"
+        ))
+    };
+    let Expr::Literal(value) = arg else {
+        return Err(invalid());
+    };
+
+    if value.data_type().is_integer() {
+        return match value.cast_to(&DataType::UInt64) {
+            Ok(ScalarValue::UInt64(Some(number))) => Ok(VersionSelector::Number(number)),
+            _ => Err(invalid()),
+        };
+    }
+
+    match value.cast_to(&DataType::Timestamp(
+        TimeUnit::Nanosecond,
+        Some("UTC".into()),
+    )) {
+        Ok(ScalarValue::TimestampNanosecond(Some(nanos), _)) => {
+            Ok(VersionSelector::AsOf(DateTime::from_timestamp_nanos(nanos)))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+struct VersionFunction {
+    catalog: Arc<dyn CatalogProvider>,
+}
+
+impl TableFunctionImpl for VersionFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let [table, version] = args else {
+            return Err(DataFusionError::Plan(format!(
+                "{VERSION_FUNCTION} expects a dataset and a version: {VERSION_FUNCTION}(dataset, version)"
+            )));
+        };
+        let table = dataset_name(table, VERSION_FUNCTION)?;
+        let selector = version_selector(version)?;
+
+        let (table, schema, versions) = retained_versions(&self.catalog, &table)?;
+        let versions = versions
+            .list()
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let version = selector.select(&versions).ok_or_else(|| {
+            DataFusionError::Plan(format!("Dataset {table} has no version {selector}"))
+        })?;
+
+        let url = ListingTableUrl::parse(version.path.to_string_lossy())?;
+        let options =
+            ListingOptions::new(Arc::new(ParquetFormat::default())).with_file_extension(".parquet");
+        let config = ListingTableConfig::new(url)
+            .with_listing_options(options)
+            .with_schema(schema);
+
+        Ok(Arc::new(VersionTable {
+            table,
+            provider: Arc::new(ListingTable::try_new(config)?),
+        }))
+    }
+}
+
+/// A version of a dataset, which has the access policies of the dataset.
+struct VersionTable {
+    table: TableReference,
+    provider: Arc<dyn TableProvider>,
+}
+
+#[async_trait]
+impl TableProvider for VersionTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.provider.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        self.provider.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        self.provider.scan(state, projection, filters, limit).await
+    }
+}
+
+struct VersionsFunction {
+    catalog: Arc<dyn CatalogProvider>,
+}
+
+impl TableFunctionImpl for VersionsFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let [table] = args else {
+            return Err(DataFusionError::Plan(format!(
+                "{VERSIONS_FUNCTION} expects a dataset: {VERSIONS_FUNCTION}(dataset)"
+            )));
+        };
+        let table = dataset_name(table, VERSIONS_FUNCTION)?;
+        let (_, _, versions) = retained_versions(&self.catalog, &table)?;
+        let versions = versions
+            .list()
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("version", DataType::UInt64, false),
+            Field::new(
+                "loaded_at",
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                false,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(UInt64Array::from_iter_values(
+                    versions.iter().map(|v| v.number),
+                )),
+                Arc::new(
+                    TimestampMillisecondArray::from_iter_values(
+                        versions.iter().map(|v| v.loaded_at.timestamp_millis()),
+                    )
+                    .with_timezone("UTC"),
+                ),
+            ],
+        )?;
+
+        Ok(Arc::new(MemTable::try_new(schema, vec![vec![batch]])?))
+    }
+}
+
+/// Rewrites `<dataset> FOR SYSTEM_TIME AS OF [TIMESTAMP] '<time>'` to the `acceleration_version` of the dataset,
+/// which the PostgreSQL dialect doesn't parse.
+pub(crate) fn rewrite_time_travel(sql: &str) -> Option<String> {
+    if !sql.to_ascii_uppercase().contains("SYSTEM_TIME") {
+        return None;
+    }
+
+    let tokens = Tokenizer::new(&PostgreSqlDialect {}, sql)
+        .tokenize_with_location()
+        .ok()?;
+    let significant: Vec<usize> = (0..tokens.len())
+        .filter(|&i| !matches!(tokens[i].token, Token::Whitespace(_)))
+        .collect();
+    let token = |i: usize| significant.get(i).map(|&t| &tokens[t].token);
+    let keyword = |i: usize| match token(i) {
+        Some(Token::Word(word)) if word.quote_style.is_none() => Some(word.keyword),
+        _ => None,
+    };
+    let offset = |i: usize| {
+        tokens
+            .get(i)
+            .map_or(sql.len(), |t| byte_offset(sql, &t.location))
+    };
+
+    let mut rewritten = String::new();
+    let mut copied = 0;
+    for i in 1..significant.len() {
+        if [Keyword::FOR, Keyword::SYSTEM_TIME, Keyword::AS, Keyword::OF]
+            .iter()
+            .enumerate()
+            .any(|(j, expected)| keyword(i + j) != Some(*expected))
+        {
+            continue;
+        }
+        let time = if keyword(i + 4) == Some(Keyword::TIMESTAMP) {
+            i + 5
+        } else {
+            i + 4
+        };
+        let Some(Token::SingleQuotedString(time_value)) = token(time) else {
+            continue;
+        };
+
+        // The dataset is the dotted name before `FOR`.
+        let Some(Token::Word(last)) = token(i - 1) else {
+            continue;
+        };
+        let mut start = i - 1;
+        while start >= 2
+            && matches!(token(start - 1), Some(Token::Period))
+            && matches!(token(start - 2), Some(Token::Word(_)))
+        {
+            start -= 2;
+        }
+        let name_start = offset(significant[start]);
+        let name = &sql[name_start..offset(significant[i - 1] + 1)];
+
+        rewritten.push_str(&sql[copied..name_start]);
+        rewritten.push_str(&format!(
+            "{VERSION_FUNCTION}({}, {})",
+            literal(name),
+            literal(time_value)
+        ));
+        // Columns qualified with the dataset name keep resolving, unless the query aliases the dataset.
+        let aliased = match token(time + 1) {
+            Some(Token::Word(word)) => {
+                word.quote_style.is_some()
+                    || matches!(word.keyword, Keyword::AS | Keyword::NoKeyword)
+            }
+            _ => false,
+        };
+        if !aliased {
+            rewritten.push_str(&format!(" AS {last}"));
+        }
+        copied = offset(significant[time] + 1);
+    }
+
+    if copied == 0 {
+        return None;
+    }
+    rewritten.push_str(&sql[copied..]);
+    Some(rewritten)
+}
+
+/// The byte offset in `sql` of a location, which counts lines and characters from 1.
+fn byte_offset(sql: &str, location: &Location) -> usize {
+    let (mut line, mut column) = (1, 1);
+    for (i, c) in sql.char_indices() {
+        if line == location.line && column == location.column {
+            return i;
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    sql.len()
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_system_time() {
+        assert_eq!(
+            rewrite_time_travel(
+                "SELECT count(*) FROM taxi_trips FOR SYSTEM_TIME AS OF '2024-07-01T00:00:00Z' WHERE taxi_trips.fare > 0"
+            )
+            .as_deref(),
+            Some("SELECT count(*) FROM acceleration_version('taxi_trips', '2024-07-01T00:00:00Z') AS taxi_trips WHERE taxi_trips.fare > 0")
+        );
+        assert_eq!(
+            rewrite_time_travel(
+                "SELECT * FROM spice.public.\"Orders\" FOR SYSTEM_TIME AS OF TIMESTAMP '2024-07-01'\nAS previous"
+            )
+            .as_deref(),
+            Some("SELECT * FROM acceleration_version('spice.public.\"Orders\"', '2024-07-01')\nAS previous")
+        );
+        assert_eq!(
+            rewrite_time_travel("SELECT 'FOR SYSTEM_TIME AS OF' FROM t"),
+            None
+        );
+    }
+}
@@ -0,0 +1,304 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Scalar functions implemented by WASM modules.
+//!
+//! A function is called once per batch. The module must export its `memory`, an `alloc(size: i32) -> i32` function,
+//! and the function itself, taking a pointer to the values of each argument followed by the number of rows and a
+//! pointer to write the results to: `fn(arg_1: i32, ..., arg_n: i32, rows: i32, out: i32)`.
+//!
+//! Values are passed as the little-endian Arrow buffers of the arguments, so only fixed-width numeric types are
+//! supported. A row is null in the result when it is null in any argument.
+//!
+//! Each call runs with limited fuel and memory, so a function that loops or allocates without bound fails the query
+//! instead of blocking or exhausting the runtime.
+
+use std::{any::Any, fmt, path::Path};
+
+use arrow::{
+    array::{make_array, Array, ArrayData, ArrayRef},
+    buffer::{Buffer, NullBuffer},
+    datatypes::DataType,
+};
+use datafusion::{
+    error::{DataFusionError, Result as DataFusionResult},
+    logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility},
+};
+use snafu::prelude::*;
+use wasmtime::{
+    Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+    Val,
+};
+
+/// The size the memory of a module can grow to during a call.
+const MAX_MEMORY: usize = 256 * 1024 * 1024;
+
+/// The fuel of a call, roughly the number of WASM instructions it can run, on top of the fuel for each row.
+const BASE_FUEL: u64 = 10_000_000;
+const FUEL_PER_ROW: u64 = 10_000;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to load the WASM module {path}: {reason}"))]
+    UnableToLoadModule { path: String, reason: String },
+
+    #[snafu(display(
+        "The type {data_type} is not supported by WASM functions. Use INT, BIGINT, REAL or DOUBLE."
+    ))]
+    UnsupportedType { data_type: String },
+
+    #[snafu(display("WASM functions take at least one argument"))]
+    NoArguments,
+}
+
+/// Returns the Arrow type of a SQL type WASM functions support.
+fn data_type(sql_type: &str) -> Result<DataType, Error> {
+    match sql_type.trim().to_ascii_uppercase().as_str() {
+        "INT" | "INTEGER" | "INT4" => Ok(DataType::Int32),
+        "BIGINT" | "INT8" => Ok(DataType::Int64),
+        "REAL" | "FLOAT" | "FLOAT4" => Ok(DataType::Float32),
+        "DOUBLE" | "DOUBLE PRECISION" | "FLOAT8" => Ok(DataType::Float64),
+        _ => UnsupportedTypeSnafu {
+            data_type: sql_type,
+        }
+        .fail(),
+    }
+}
+
+/// Compiles the module at `path` and returns its `export` function as the scalar function `name`.
+pub fn create_udf(
+    name: &str,
+    path: &str,
+    export: &str,
+    param_types: &[String],
+    return_type: &str,
+) -> Result<ScalarUDF, Error> {
+    ensure!(!param_types.is_empty(), NoArgumentsSnafu);
+    let arg_types = param_types
+        .iter()
+        .map(|t| data_type(t))
+        .collect::<Result<Vec<_>, _>>()?;
+    let return_type = data_type(return_type)?;
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config).map_err(|e| {
+        UnableToLoadModuleSnafu {
+            path,
+            reason: e.to_string(),
+        }
+        .build()
+    })?;
+    let module = Module::from_file(&engine, Path::new(path)).map_err(|e| {
+        UnableToLoadModuleSnafu {
+            path,
+            reason: e.to_string(),
+        }
+        .build()
+    })?;
+
+    Ok(ScalarUDF::new_from_impl(WasmUdf {
+        name: name.to_string(),
+        export: export.to_string(),
+        signature: Signature::exact(arg_types, Volatility::Immutable),
+        return_type,
+        engine,
+        module,
+    }))
+}
+
+struct WasmUdf {
+    name: String,
+    export: String,
+    signature: Signature,
+    return_type: DataType,
+    engine: Engine,
+    module: Module,
+}
+
+impl fmt::Debug for WasmUdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmUdf")
+            .field("name", &self.name)
+            .field("export", &self.export)
+            .field("return_type", &self.return_type)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScalarUDFImpl for WasmUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> DataFusionResult<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> DataFusionResult<ColumnarValue> {
+        let args = ColumnarValue::values_to_arrays(args)?;
+        let rows = args.first().map_or(0, |arg| arg.len());
+
+        let values = self.call(&args, rows).map_err(|e| {
+            DataFusionError::Execution(format!("Unable to call WASM function {}: {e}", self.name))
+        })?;
+
+        let nulls = args.iter().fold(None, |nulls, arg| {
+            NullBuffer::union(nulls.as_ref(), arg.nulls())
+        });
+        let data = ArrayData::builder(self.return_type.clone())
+            .len(rows)
+            .align_buffers(true)
+            .add_buffer(Buffer::from_vec(values))
+            .nulls(nulls)
+            .build()?;
+
+        Ok(ColumnarValue::Array(make_array(data)))
+    }
+}
+
+impl WasmUdf {
+    /// Calls the function on the values of `args` and returns the bytes of the results.
+    fn call(&self, args: &[ArrayRef], rows: usize) -> wasmtime::Result<Vec<u8>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(
+            FUEL_PER_ROW
+                .saturating_mul(u64::try_from(rows)?)
+                .saturating_add(BASE_FUEL),
+        )?;
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("the module doesn't export its memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let function = instance.get_func(&mut store, &self.export).ok_or_else(|| {
+            wasmtime::Error::msg(format!("the module doesn't export {}", self.export))
+        })?;
+
+        let mut params = Vec::with_capacity(args.len() + 2);
+        for arg in args {
+            let data = arg.to_data();
+            let width = data_width(arg.data_type());
+            let values = &data.buffers()[0].as_slice()
+                [data.offset() * width..(data.offset() + rows) * width];
+            params.push(Val::I32(write(&mut store, &memory, &alloc, values)?));
+        }
+
+        let out_len = rows * data_width(&self.return_type);
+        let out = alloc.call(&mut store, i32::try_from(out_len)?)?;
+        params.push(Val::I32(i32::try_from(rows)?));
+        params.push(Val::I32(out));
+        function.call(&mut store, &params, &mut [])?;
+
+        let mut results = vec![0; out_len];
+        memory.read(&store, usize::try_from(out)?, &mut results)?;
+        Ok(results)
+    }
+}
+
+/// Copies `bytes` into memory allocated by the module, returning the pointer to them.
+fn write(
+    store: &mut Store<StoreLimits>,
+    memory: &Memory,
+    alloc: &TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> wasmtime::Result<i32> {
+    let ptr = alloc.call(&mut *store, i32::try_from(bytes.len())?)?;
+    memory.write(store, usize::try_from(ptr)?, bytes)?;
+    Ok(ptr)
+}
+
+fn data_width(data_type: &DataType) -> usize {
+    data_type.primitive_width().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_sql_types() {
+        assert_eq!(data_type("bigint").expect("supported"), DataType::Int64);
+        assert_eq!(data_type("Double").expect("supported"), DataType::Float64);
+        assert!(data_type("VARCHAR").is_err());
+    }
+
+    fn udf(name: &str, wat: &str) -> ScalarUDF {
+        let path = std::env::temp_dir().join(format!("{name}-{}.wat", std::process::id()));
+        std::fs::write(&path, wat).expect("module is written");
+        let udf = create_udf(
+            name,
+            &path.to_string_lossy(),
+            name,
+            &["INT".to_string()],
+            "INT",
+        );
+        std::fs::remove_file(&path).expect("module is removed");
+        udf.expect("module compiles")
+    }
+
+    fn call(udf: &ScalarUDF) -> DataFusionResult<ColumnarValue> {
+        let values: ArrayRef = std::sync::Arc::new(arrow::array::Int32Array::from(vec![1, 2, 3]));
+        udf.invoke(&[ColumnarValue::Array(values)])
+    }
+
+    #[test]
+    fn limits_calls() {
+        let spin = udf(
+            "spin",
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "spin") (param i32 i32 i32) (loop br 0)))"#,
+        );
+        let err = call(&spin).expect_err("the call runs out of fuel");
+        assert!(err
+            .to_string()
+            .contains("Unable to call WASM function spin"));
+
+        // 8192 pages of 64KiB are larger than the memory limit.
+        let large = udf(
+            "large",
+            r#"(module
+                (memory (export "memory") 8192)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "large") (param i32 i32 i32)))"#,
+        );
+        assert!(call(&large).is_err());
+
+        let noop = udf(
+            "noop",
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "noop") (param i32 i32 i32)))"#,
+        );
+        assert!(call(&noop).is_ok());
+    }
+}
@@ -14,11 +14,11 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 use std::{any::Any, fmt, sync::Arc};
 
 use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
-use datafusion::error::Result as DataFusionResult;
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
 use datafusion::execution::{SendableRecordBatchStream, TaskContext};
 use datafusion::physical_expr::EquivalenceProperties;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
@@ -121,3 +121,84 @@ impl ExecutionPlan for DataUpdateExecutionPlan {
         Ok(Box::pin(stream_adapter))
     }
 }
+
+/// Feeds the batches of a stream into an insert as they arrive, rather than once they are all in memory. The stream
+/// can only be executed once.
+pub struct StreamingDataUpdateExecutionPlan {
+    stream: Mutex<Option<SendableRecordBatchStream>>,
+    schema: SchemaRef,
+    properties: PlanProperties,
+}
+
+impl StreamingDataUpdateExecutionPlan {
+    #[must_use]
+    pub fn new(stream: SendableRecordBatchStream) -> Self {
+        let schema = stream.schema();
+        Self {
+            stream: Mutex::new(Some(stream)),
+            schema: Arc::clone(&schema),
+            properties: PlanProperties::new(
+                EquivalenceProperties::new(schema),
+                Partitioning::UnknownPartitioning(1),
+                ExecutionMode::Unbounded,
+            ),
+        }
+    }
+}
+
+impl std::fmt::Debug for StreamingDataUpdateExecutionPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "StreamingDataUpdateExecutionPlan")
+    }
+}
+
+impl DisplayAs for StreamingDataUpdateExecutionPlan {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "StreamingDataUpdateExecutionPlan")
+    }
+}
+
+impl ExecutionPlan for StreamingDataUpdateExecutionPlan {
+    fn name(&self) -> &'static str {
+        "StreamingDataUpdateExecutionPlan"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        _partition: usize,
+        _context: Arc<TaskContext>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let mut stream_guard = match self.stream.lock() {
+            Ok(guard) => guard,
+            Err(e) => e.into_inner(),
+        };
+        stream_guard.take().ok_or_else(|| {
+            DataFusionError::Execution(
+                "StreamingDataUpdateExecutionPlan can only be executed once".to_string(),
+            )
+        })
+    }
+}
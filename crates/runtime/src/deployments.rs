@@ -0,0 +1,268 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Routes the requests for a deployment to its primary or its candidate, and records the result of each in
+//! `spice.runtime.deployment_results`, to compare them with SQL, i.e.
+//! `SELECT variant, avg(duration_ms) FROM runtime.deployment_results GROUP BY variant`.
+//!
+//! A request for a name that isn't a deployment goes to the model or LLM of that name, and isn't recorded.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use app::App;
+use arrow::{
+    array::{BooleanArray, RecordBatch, StringArray, TimestampNanosecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use datafusion::sql::TableReference;
+use snafu::{ResultExt, Snafu};
+use spicepod::component::deployment::DeploymentMode;
+
+use crate::{
+    accelerated_table::{refresh::Refresh, AcceleratedTable, Retention},
+    component::dataset::{acceleration::Acceleration, TimeFormat},
+    datafusion::{DataFusion, SPICE_RUNTIME_SCHEMA},
+    dataupdate::{DataUpdate, UpdateType},
+    internal_table::create_internal_accelerated_table,
+};
+
+pub const DEFAULT_DEPLOYMENT_RESULTS_TABLE: &str = "deployment_results";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Error registering table: {source}"))]
+    UnableToRegisterTable {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Error writing to deployment_results table: {source}"))]
+    UnableToWriteToTable {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Error creating deployment_results row: {source}"))]
+    UnableToCreateRow { source: arrow::error::ArrowError },
+}
+
+pub async fn instantiate_deployment_results_table() -> Result<Arc<AcceleratedTable>, Error> {
+    let retention = Retention::new(
+        Some("time".to_string()),
+        Some(TimeFormat::UnixSeconds),
+        Some(Duration::from_secs(7 * 24 * 60 * 60)), // 7 days
+        Some(Duration::from_secs(300)),
+        true,
+    );
+    create_internal_accelerated_table(
+        TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_DEPLOYMENT_RESULTS_TABLE),
+        Arc::new(table_schema()),
+        Acceleration::default(),
+        Refresh::default(),
+        retention,
+    )
+    .await
+    .boxed()
+    .context(UnableToRegisterTableSnafu)
+}
+
+fn table_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("request_id", DataType::Utf8, false),
+        Field::new("deployment", DataType::Utf8, false),
+        Field::new("variant", DataType::Utf8, false),
+        Field::new("model", DataType::Utf8, false),
+        Field::new("served", DataType::Boolean, false),
+        Field::new(
+            "time",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new("duration_ms", DataType::UInt64, false),
+        Field::new("output", DataType::Utf8, true),
+        Field::new("error_message", DataType::Utf8, true),
+    ])
+}
+
+/// A model or LLM of a deployment.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Variant {
+    /// `primary` or `candidate`.
+    pub(crate) kind: &'static str,
+    pub(crate) model: String,
+}
+
+/// The model or LLM serving a request for a name, and the one running in its shadow.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Route {
+    /// The deployment of the name, if it is one.
+    pub(crate) deployment: Option<String>,
+    pub(crate) served: Variant,
+    pub(crate) shadow: Option<Variant>,
+}
+
+impl Route {
+    pub(crate) fn new(app: Option<&App>, name: &str) -> Self {
+        let roll = RandomState::new().build_hasher().finish();
+        Self::with_roll(app, name, roll)
+    }
+
+    fn with_roll(app: Option<&App>, name: &str, roll: u64) -> Self {
+        let deployment = app.and_then(|app| app.deployments.iter().find(|d| d.name == name));
+        let Some(deployment) = deployment else {
+            return Self {
+                deployment: None,
+                served: Variant {
+                    kind: "primary",
+                    model: name.to_string(),
+                },
+                shadow: None,
+            };
+        };
+
+        let primary = Variant {
+            kind: "primary",
+            model: deployment.primary.clone(),
+        };
+        let candidate = Variant {
+            kind: "candidate",
+            model: deployment.candidate.clone(),
+        };
+        let (served, shadow) = match deployment.mode {
+            DeploymentMode::Shadow => (primary, Some(candidate)),
+            DeploymentMode::Split if roll % 100 < u64::from(deployment.traffic) => {
+                (candidate, None)
+            }
+            DeploymentMode::Split => (primary, None),
+        };
+
+        Self {
+            deployment: Some(deployment.name.clone()),
+            served,
+            shadow,
+        }
+    }
+}
+
+/// The result of a variant of a deployment for a request.
+pub(crate) struct Outcome {
+    pub(crate) variant: Variant,
+    /// Whether the result was returned to the client, rather than computed in the shadow of the primary.
+    pub(crate) served: bool,
+    pub(crate) time: SystemTime,
+    pub(crate) duration: Duration,
+    pub(crate) result: Result<String, String>,
+}
+
+/// Records `outcome` in the background, so that recording doesn't delay the response.
+pub(crate) fn record(df: &Arc<DataFusion>, deployment: &str, request_id: &str, outcome: Outcome) {
+    let df = Arc::clone(df);
+    let (deployment, request_id) = (deployment.to_string(), request_id.to_string());
+    tokio::spawn(async move {
+        if let Err(e) = write_result(&df, deployment.clone(), request_id, outcome).await {
+            tracing::warn!("Unable to record the result of deployment {deployment}: {e}");
+        }
+    });
+}
+
+async fn write_result(
+    df: &DataFusion,
+    deployment: String,
+    request_id: String,
+    outcome: Outcome,
+) -> Result<(), Error> {
+    let time = outcome
+        .time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .and_then(|duration| i64::try_from(duration.as_nanos()).ok());
+    let duration_ms = u64::try_from(outcome.duration.as_millis()).unwrap_or(u64::MAX);
+    let (output, error_message) = match outcome.result {
+        Ok(output) => (Some(output), None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let schema = Arc::new(table_schema());
+    let data = RecordBatch::try_new(
+        Arc::clone(&schema),
+        vec![
+            Arc::new(StringArray::from(vec![request_id])),
+            Arc::new(StringArray::from(vec![deployment])),
+            Arc::new(StringArray::from(vec![outcome.variant.kind])),
+            Arc::new(StringArray::from(vec![outcome.variant.model])),
+            Arc::new(BooleanArray::from(vec![outcome.served])),
+            Arc::new(TimestampNanosecondArray::from(vec![time])),
+            Arc::new(UInt64Array::from(vec![duration_ms])),
+            Arc::new(StringArray::from(vec![output])),
+            Arc::new(StringArray::from(vec![error_message])),
+        ],
+    )
+    .context(UnableToCreateRowSnafu)?;
+
+    df.write_data(
+        TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_DEPLOYMENT_RESULTS_TABLE),
+        DataUpdate {
+            schema,
+            data: vec![data],
+            update_type: UpdateType::Append,
+        },
+    )
+    .await
+    .boxed()
+    .context(UnableToWriteToTableSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use app::AppBuilder;
+    use spicepod::component::deployment::Deployment;
+
+    use super::*;
+
+    #[test]
+    fn routes_requests_of_deployments() {
+        let mut split = Deployment::new("split".into(), "v1".into(), "v2".into());
+        split.mode = DeploymentMode::Split;
+        split.traffic = 10;
+        let app = AppBuilder::new("test")
+            .with_deployment(Deployment::new("shadow".into(), "v1".into(), "v2".into()))
+            .with_deployment(split)
+            .build();
+
+        let shadow = Route::with_roll(Some(&app), "shadow", 0);
+        assert_eq!(shadow.served.model, "v1");
+        assert_eq!(shadow.shadow.map(|v| v.model).as_deref(), Some("v2"));
+
+        assert_eq!(
+            Route::with_roll(Some(&app), "split", 109).served.kind,
+            "candidate"
+        );
+        assert_eq!(
+            Route::with_roll(Some(&app), "split", 110).served.kind,
+            "primary"
+        );
+        assert!(Route::with_roll(Some(&app), "split", 0).shadow.is_none());
+
+        let direct = Route::with_roll(Some(&app), "v1", 0);
+        assert_eq!(
+            (direct.deployment, direct.served.model),
+            (None, "v1".to_string())
+        );
+    }
+}
@@ -0,0 +1,206 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{any::Any, collections::HashMap, future::Future, sync::Arc};
+
+use arrow::{
+    array::{Array, AsArray, FixedSizeListArray, Float32Array},
+    buffer::NullBuffer,
+    compute::cast,
+    datatypes::{DataType, Field},
+};
+use async_openai::types::EmbeddingInput;
+use datafusion::{
+    common::{exec_datafusion_err, exec_err, plan_err, ExprSchema, Result, ScalarValue},
+    logical_expr::{ColumnarValue, Expr, ScalarUDFImpl, Signature, TypeSignature, Volatility},
+};
+use tokio::{runtime::Handle, sync::RwLock};
+
+use crate::EmbeddingModelStore;
+
+/// `embed(text[, model])` returns the embedding of `text` by an embedding model of the spicepod, as a
+/// `FixedSizeList<Float32>` that can be compared with `array_distance`. The model can be left out when the spicepod
+/// has a single embedding model.
+pub struct Embed {
+    embeds: Arc<RwLock<EmbeddingModelStore>>,
+    signature: Signature,
+}
+
+impl std::fmt::Debug for Embed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Embed")
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Embed {
+    #[must_use]
+    pub fn new(embeds: Arc<RwLock<EmbeddingModelStore>>) -> Self {
+        Self {
+            embeds,
+            signature: Signature::one_of(
+                vec![
+                    TypeSignature::Exact(vec![DataType::Utf8]),
+                    TypeSignature::Exact(vec![DataType::Utf8, DataType::Utf8]),
+                ],
+                Volatility::Stable,
+            ),
+        }
+    }
+
+    /// Returns the name of the model to use and the size of its embeddings.
+    fn model(&self, model: Option<&str>) -> Result<(String, i32)> {
+        block_on(async {
+            let embeds = self.embeds.read().await;
+            let (name, embed) = match model {
+                Some(model) => (model.to_string(), embeds.get(model)),
+                None if embeds.len() == 1 => match embeds.iter().next() {
+                    Some((name, embed)) => (name.clone(), Some(embed)),
+                    None => (String::new(), None),
+                },
+                None => {
+                    return plan_err!(
+                        "embed expects a model when the spicepod doesn't have exactly one embedding model: embed(text, model)"
+                    )
+                }
+            };
+            let Some(embed) = embed else {
+                return plan_err!("The embedding model {name} was not found");
+            };
+
+            let size = embed.read().await.size();
+            Ok((name, size))
+        })
+    }
+}
+
+impl ScalarUDFImpl for Embed {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "embed"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _args: &[DataType]) -> Result<DataType> {
+        // The size of the embeddings depends on the model, see `return_type_from_exprs`.
+        let (_, size) = self.model(None)?;
+        Ok(DataType::new_fixed_size_list(
+            DataType::Float32,
+            size,
+            false,
+        ))
+    }
+
+    fn return_type_from_exprs(
+        &self,
+        args: &[Expr],
+        _schema: &dyn ExprSchema,
+        _arg_types: &[DataType],
+    ) -> Result<DataType> {
+        let model = match args.get(1) {
+            None => None,
+            Some(Expr::Literal(ScalarValue::Utf8(Some(model)))) => Some(model.as_str()),
+            Some(model) => {
+                return plan_err!("embed expects the model as a string literal, received {model}")
+            }
+        };
+
+        let (_, size) = self.model(model)?;
+        Ok(DataType::new_fixed_size_list(
+            DataType::Float32,
+            size,
+            false,
+        ))
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let model = match args.get(1) {
+            None => None,
+            Some(ColumnarValue::Scalar(ScalarValue::Utf8(Some(model)))) => Some(model.as_str()),
+            Some(_) => return exec_err!("embed expects the model as a string literal"),
+        };
+        let (model, size) = self.model(model)?;
+        let length = usize::try_from(size).map_err(|e| exec_datafusion_err!("{e}"))?;
+
+        let texts = ColumnarValue::values_to_arrays(&args[..1])?;
+        let texts = cast(&texts[0], &DataType::Utf8)?;
+        let texts = texts.as_string::<i32>();
+
+        // Each distinct text is only embedded once, so a constant text is embedded once per batch.
+        let mut distinct: HashMap<&str, usize> = HashMap::new();
+        let mut rows = Vec::with_capacity(texts.len());
+        for text in texts {
+            rows.push(text.map(|text| {
+                let next = distinct.len();
+                *distinct.entry(text).or_insert(next)
+            }));
+        }
+        let mut inputs = vec![String::new(); distinct.len()];
+        for (text, i) in distinct {
+            inputs[i] = text.to_string();
+        }
+
+        let embeddings = if inputs.is_empty() {
+            vec![]
+        } else {
+            block_on(async {
+                let embeds = self.embeds.read().await;
+                let Some(embed) = embeds.get(&model) else {
+                    return exec_err!("The embedding model {model} was not found");
+                };
+                let mut embed = embed.write().await;
+                embed
+                    .embed(EmbeddingInput::StringArray(inputs))
+                    .await
+                    .map_err(|e| exec_datafusion_err!("Unable to embed with {model}: {e}"))
+            })?
+        };
+        if let Some(embedding) = embeddings.iter().find(|e| e.len() != length) {
+            return exec_err!(
+                "The embedding model {model} returned an embedding of size {}, expected {length}",
+                embedding.len()
+            );
+        }
+
+        let zeros = vec![0.0; length];
+        let values: Float32Array = rows
+            .iter()
+            .flat_map(|row| row.map_or(&zeros, |i| &embeddings[i]))
+            .copied()
+            .collect();
+        let nulls = NullBuffer::from(rows.iter().map(Option::is_some).collect::<Vec<_>>());
+        let array = FixedSizeListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, false)),
+            size,
+            Arc::new(values),
+            Some(nulls),
+        )?;
+
+        Ok(ColumnarValue::Array(Arc::new(array)))
+    }
+}
+
+/// Waits for `future` on the current Tokio runtime, from the synchronous methods of [`ScalarUDFImpl`].
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| Handle::current().block_on(future))
+}
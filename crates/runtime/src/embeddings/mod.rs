@@ -15,5 +15,6 @@ limitations under the License.
 */
 pub mod array_distance;
 pub mod connector;
+pub mod embed;
 pub mod execution_plan;
 pub mod table;
@@ -0,0 +1,383 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A stable code, the failing component, whether a retry can succeed and a hint of how to fix it, for the errors
+//! of the connectors, accelerators and queries. HTTP and Flight responses carry them next to the message, so clients
+//! can act on an error without parsing its text.
+
+use std::error::Error as StdError;
+
+use datafusion::error::DataFusionError;
+use serde::Serialize;
+
+use crate::{
+    connector_health, dataaccelerator,
+    dataconnector::DataConnectorError,
+    datafusion::{policy, query},
+    extension,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorComponent {
+    Connector,
+    Accelerator,
+    Query,
+    Policy,
+    Extension,
+    Runtime,
+}
+
+impl std::fmt::Display for ErrorComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorComponent::Connector => write!(f, "connector"),
+            ErrorComponent::Accelerator => write!(f, "accelerator"),
+            ErrorComponent::Query => write!(f, "query"),
+            ErrorComponent::Policy => write!(f, "policy"),
+            ErrorComponent::Extension => write!(f, "extension"),
+            ErrorComponent::Runtime => write!(f, "runtime"),
+        }
+    }
+}
+
+/// What went wrong, which decides the HTTP status and gRPC code of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The request or the spicepod is invalid, and fails the same way until it is fixed.
+    InvalidInput,
+    AccessDenied,
+    NotFound,
+    /// A source or the runtime is temporarily unavailable.
+    Unavailable,
+    ResourceExhausted,
+    Internal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ErrorInfo {
+    pub code: &'static str,
+    pub component: ErrorComponent,
+    pub kind: ErrorKind,
+    pub retryable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+impl ErrorInfo {
+    #[must_use]
+    pub fn new(code: &'static str, component: ErrorComponent, kind: ErrorKind) -> Self {
+        Self {
+            code,
+            component,
+            kind,
+            retryable: matches!(kind, ErrorKind::Unavailable | ErrorKind::ResourceExhausted),
+            hint: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_hint(mut self, hint: &'static str) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    #[must_use]
+    pub fn internal(component: ErrorComponent) -> Self {
+        Self::new("INTERNAL", component, ErrorKind::Internal)
+            .with_hint("Report a bug on GitHub (github.com/spiceai/spiceai) with the error message")
+    }
+}
+
+impl std::fmt::Display for ErrorInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}]", self.code)
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+pub trait ToErrorInfo {
+    fn error_info(&self) -> ErrorInfo;
+}
+
+/// The info of the first error of the chain of `error` that has one, i.e. the connector error a `DataFusionError`
+/// wraps, or an internal error of `component` if none does.
+#[must_use]
+pub fn classify(error: &(dyn StdError + 'static), component: ErrorComponent) -> ErrorInfo {
+    find(error).unwrap_or_else(|| ErrorInfo::internal(component))
+}
+
+fn find(error: &(dyn StdError + 'static)) -> Option<ErrorInfo> {
+    if let Some(e) = error.downcast_ref::<DataFusionError>() {
+        return Some(e.error_info());
+    }
+    if let Some(e) = error.downcast_ref::<query::Error>() {
+        return Some(e.error_info());
+    }
+    if let Some(e) = error.downcast_ref::<DataConnectorError>() {
+        return Some(e.error_info());
+    }
+    if let Some(e) = error.downcast_ref::<dataaccelerator::Error>() {
+        return Some(e.error_info());
+    }
+    if let Some(e) = error.downcast_ref::<policy::Error>() {
+        return Some(e.error_info());
+    }
+    if let Some(e) = error.downcast_ref::<connector_health::Error>() {
+        return Some(e.error_info());
+    }
+    if let Some(e) = error.downcast_ref::<extension::Error>() {
+        return Some(e.error_info());
+    }
+    find(error.source()?)
+}
+
+impl ToErrorInfo for DataFusionError {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            DataFusionError::SQL(..) => ErrorInfo::new(
+                "QUERY_SYNTAX",
+                ErrorComponent::Query,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Check the SQL syntax of the query"),
+            DataFusionError::Plan(..) | DataFusionError::SchemaError(..) => ErrorInfo::new(
+                "QUERY_PLANNING",
+                ErrorComponent::Query,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Check that the tables, columns and functions of the query exist"),
+            DataFusionError::ResourcesExhausted(..) => ErrorInfo::new(
+                "QUERY_RESOURCES_EXHAUSTED",
+                ErrorComponent::Query,
+                ErrorKind::ResourceExhausted,
+            )
+            .with_hint("Retry later, or reduce the data the query reads"),
+            DataFusionError::ObjectStore(..) => ErrorInfo::new(
+                "SOURCE_IO",
+                ErrorComponent::Connector,
+                ErrorKind::Unavailable,
+            ),
+            DataFusionError::External(e) => find(e.as_ref()).unwrap_or_else(|| {
+                ErrorInfo::new(
+                    "QUERY_EXECUTION",
+                    ErrorComponent::Query,
+                    ErrorKind::Internal,
+                )
+            }),
+            DataFusionError::Context(_, e) => e.error_info(),
+            DataFusionError::Execution(..) | DataFusionError::ArrowError(..) => ErrorInfo::new(
+                "QUERY_EXECUTION",
+                ErrorComponent::Query,
+                ErrorKind::Internal,
+            ),
+            _ => ErrorInfo::internal(ErrorComponent::Query),
+        }
+    }
+}
+
+impl ToErrorInfo for query::Error {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            query::Error::UnableToExecuteQuery { source }
+            | query::Error::UnableToCreateMemoryStream { source }
+            | query::Error::UnableToCollectResults { source } => source.error_info(),
+            query::Error::FailedToAccessCache { .. } => ErrorInfo::new(
+                "RESULTS_CACHE",
+                ErrorComponent::Runtime,
+                ErrorKind::Unavailable,
+            ),
+            query::Error::SchemaMismatch { .. } => ErrorInfo::internal(ErrorComponent::Query),
+            query::Error::PartitionCountMismatch { .. } => ErrorInfo::new(
+                "QUERY_PLAN_CHANGED",
+                ErrorComponent::Query,
+                ErrorKind::Unavailable,
+            ),
+            query::Error::InvalidPartitions { .. } => ErrorInfo::new(
+                "INVALID_PARTITIONS",
+                ErrorComponent::Query,
+                ErrorKind::InvalidInput,
+            ),
+            query::Error::AccessDenied { source } => source.error_info(),
+            query::Error::SourceUnavailable { source } => source.error_info(),
+            query::Error::ShuttingDown => ErrorInfo::new(
+                "RUNTIME_SHUTTING_DOWN",
+                ErrorComponent::Runtime,
+                ErrorKind::Unavailable,
+            )
+            .with_hint("Retry the query against another runtime"),
+        }
+    }
+}
+
+impl ToErrorInfo for DataConnectorError {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            DataConnectorError::UnableToConnectInternal { .. }
+            | DataConnectorError::UnableToGetReadProvider { .. }
+            | DataConnectorError::UnableToGetReadWriteProvider { .. } => ErrorInfo::new(
+                "CONNECTOR_UNAVAILABLE",
+                ErrorComponent::Connector,
+                ErrorKind::Unavailable,
+            )
+            .with_hint("Check that the source is reachable from the runtime"),
+            DataConnectorError::UnableToConnectInvalidHostOrPort { .. } => ErrorInfo::new(
+                "CONNECTOR_INVALID_HOST",
+                ErrorComponent::Connector,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Check the host and port parameters of the dataset"),
+            DataConnectorError::UnableToConnectInvalidUsernameOrPassword { .. } => ErrorInfo::new(
+                "CONNECTOR_AUTHENTICATION_FAILED",
+                ErrorComponent::Connector,
+                ErrorKind::AccessDenied,
+            )
+            .with_hint("Check the username and password secrets of the dataset"),
+            DataConnectorError::UnableToConnectTlsError { .. } => ErrorInfo::new(
+                "CONNECTOR_TLS",
+                ErrorComponent::Connector,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint(
+                "Set the sslmode parameter of the dataset to match the TLS setup of the source",
+            ),
+            DataConnectorError::InvalidConfiguration { .. } => ErrorInfo::new(
+                "CONNECTOR_INVALID_CONFIGURATION",
+                ErrorComponent::Connector,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Check the params of the dataset in the spicepod"),
+            DataConnectorError::InvalidTableName { .. } => ErrorInfo::new(
+                "CONNECTOR_TABLE_NOT_FOUND",
+                ErrorComponent::Connector,
+                ErrorKind::NotFound,
+            )
+            .with_hint("Check the from path of the dataset in the spicepod"),
+            DataConnectorError::InternalWithSource { source, .. } => {
+                classify(source.as_ref(), ErrorComponent::Connector)
+            }
+            DataConnectorError::Internal { .. } => ErrorInfo::internal(ErrorComponent::Connector),
+        }
+    }
+}
+
+impl ToErrorInfo for dataaccelerator::Error {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            dataaccelerator::Error::InvalidConfiguration { .. } => ErrorInfo::new(
+                "ACCELERATOR_INVALID_CONFIGURATION",
+                ErrorComponent::Accelerator,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Check the acceleration of the dataset in the spicepod"),
+            dataaccelerator::Error::UnknownEngine { .. } => ErrorInfo::new(
+                "ACCELERATOR_UNKNOWN_ENGINE",
+                ErrorComponent::Accelerator,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Use an engine the runtime was built with: arrow, duckdb, postgres or sqlite"),
+            dataaccelerator::Error::AccelerationCreationFailed { source } => {
+                find(source.as_ref()).unwrap_or_else(|| {
+                    ErrorInfo::new(
+                        "ACCELERATOR_CREATION_FAILED",
+                        ErrorComponent::Accelerator,
+                        ErrorKind::Internal,
+                    )
+                    .with_hint("Check the engine params of the acceleration and that its files are writable")
+                })
+            }
+        }
+    }
+}
+
+impl ToErrorInfo for policy::Error {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            policy::Error::AccessDenied { .. }
+            | policy::Error::MissingClaim { .. }
+            | policy::Error::ColumnNotAccessible { .. } => ErrorInfo::new(
+                "ACCESS_DENIED",
+                ErrorComponent::Policy,
+                ErrorKind::AccessDenied,
+            )
+            .with_hint("Ask an administrator for access to the dataset"),
+            policy::Error::InvalidRowFilter { .. } | policy::Error::InvalidMask { .. } => {
+                ErrorInfo::new(
+                    "POLICY_INVALID",
+                    ErrorComponent::Policy,
+                    ErrorKind::InvalidInput,
+                )
+                .with_hint("Check the row filters and masks of the policies in the spicepod")
+            }
+            policy::Error::UnableToRewritePlan { .. } => {
+                ErrorInfo::internal(ErrorComponent::Policy)
+            }
+        }
+    }
+}
+
+impl ToErrorInfo for connector_health::Error {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            connector_health::Error::CircuitOpen { .. } => ErrorInfo::new(
+                "SOURCE_UNAVAILABLE",
+                ErrorComponent::Connector,
+                ErrorKind::Unavailable,
+            )
+            .with_hint("Retry after the delay in the message, or query the accelerated data"),
+        }
+    }
+}
+
+impl ToErrorInfo for extension::Error {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            extension::Error::UnableToInitializeExtension { source }
+            | extension::Error::UnableToStartExtension { source } => {
+                classify(source.as_ref(), ErrorComponent::Extension)
+            }
+            extension::Error::Coded { info, .. } => info.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_wrapped_connector_errors() {
+        let error = DataFusionError::Context(
+            "scan".to_string(),
+            Box::new(DataFusionError::External(Box::new(
+                DataConnectorError::UnableToConnectInvalidUsernameOrPassword {
+                    dataconnector: "postgres".to_string(),
+                },
+            ))),
+        );
+
+        let info = error.error_info();
+        assert_eq!(info.code, "CONNECTOR_AUTHENTICATION_FAILED");
+        assert_eq!(info.component, ErrorComponent::Connector);
+        assert!(!info.retryable);
+
+        let info = query::Error::ShuttingDown.error_info();
+        assert_eq!(info.kind, ErrorKind::Unavailable);
+        assert!(info.retryable);
+    }
+}
@@ -0,0 +1,79 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Events about datasets and queries, published from wherever they happen in the runtime and delivered to the
+//! lifecycle hooks of extensions.
+
+use std::time::Duration;
+
+use datafusion::sql::TableReference;
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// How many events are kept for subscribers that fall behind, before the oldest are dropped.
+const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum RuntimeEvent {
+    DatasetRegistered {
+        dataset: TableReference,
+    },
+    DatasetRemoved {
+        dataset: TableReference,
+    },
+    /// A refresh of an accelerated dataset finished, with its error if it failed.
+    RefreshComplete {
+        dataset: TableReference,
+        error: Option<String>,
+    },
+    QueryStart(QueryStarted),
+    QueryEnd(QueryFinished),
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryStarted {
+    pub query_id: Uuid,
+    pub sql: String,
+    pub protocol: String,
+    pub principal: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryFinished {
+    pub query_id: Uuid,
+    pub sql: String,
+    pub protocol: String,
+    pub principal: Option<String>,
+    pub datasets: Vec<String>,
+    pub rows_produced: u64,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+static EVENTS: Lazy<broadcast::Sender<RuntimeEvent>> =
+    Lazy::new(|| broadcast::channel(EVENT_CAPACITY).0);
+
+/// Publishes `event` to the current subscribers. Events published while nothing is subscribed are dropped.
+pub fn publish(event: RuntimeEvent) {
+    let _ = EVENTS.send(event);
+}
+
+/// Subscribes to the events published from now on.
+#[must_use]
+pub fn subscribe() -> broadcast::Receiver<RuntimeEvent> {
+    EVENTS.subscribe()
+}
@@ -24,9 +24,14 @@ use datafusion::physical_plan::ExecutionPlan;
 use std::sync::Arc;
 
 pub mod fallback_on_zero_results;
+pub mod parquet_metrics;
+pub mod query_comment;
+pub mod sample;
+pub mod scan_quota;
 pub mod schema_cast;
 pub mod slice;
 pub mod tee;
+pub mod traced;
 
 #[derive(Clone)]
 pub struct TableScanParams {
@@ -0,0 +1,221 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reports how many row groups the scans of Parquet datasets pruned and how many they read, so the effect of the
+//! statistics, bloom filters and page indexes of the files on a query can be verified.
+
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use datafusion::common::Statistics;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::SessionState;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{Expr, TableProviderFilterPushDown};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use datafusion::sql::TableReference;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// The counters of the Parquet scans, and the metric of `ParquetExec` each counter reports.
+const PARQUET_METRICS: [(&str, &str, &str); 5] = [
+    (
+        "datasets_parquet_row_groups_pruned",
+        "statistics",
+        "row_groups_pruned_statistics",
+    ),
+    (
+        "datasets_parquet_row_groups_matched",
+        "statistics",
+        "row_groups_matched_statistics",
+    ),
+    (
+        "datasets_parquet_row_groups_pruned",
+        "bloom_filter",
+        "row_groups_pruned_bloom_filter",
+    ),
+    (
+        "datasets_parquet_row_groups_matched",
+        "bloom_filter",
+        "row_groups_matched_bloom_filter",
+    ),
+    (
+        "datasets_parquet_rows_pruned",
+        "page_index",
+        "page_index_rows_filtered",
+    ),
+];
+
+/// A Parquet table whose scans report the row groups they pruned.
+pub struct ParquetMetricsTable {
+    dataset_name: TableReference,
+    inner: Arc<dyn TableProvider>,
+}
+
+impl ParquetMetricsTable {
+    #[must_use]
+    pub fn new(dataset_name: TableReference, inner: Arc<dyn TableProvider>) -> Self {
+        Self {
+            dataset_name,
+            inner,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for ParquetMetricsTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        self.inner.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let input = self.inner.scan(state, projection, filters, limit).await?;
+        Ok(Arc::new(ParquetMetricsExec::new(
+            self.dataset_name.clone(),
+            input,
+        )))
+    }
+}
+
+/// `ParquetMetricsExec` records the pruning metrics of the Parquet scans of its input once the plan is dropped,
+/// after all of its partitions have executed.
+#[allow(clippy::module_name_repetitions)]
+pub struct ParquetMetricsExec {
+    dataset_name: TableReference,
+    input: Arc<dyn ExecutionPlan>,
+}
+
+impl ParquetMetricsExec {
+    pub fn new(dataset_name: TableReference, input: Arc<dyn ExecutionPlan>) -> Self {
+        Self {
+            dataset_name,
+            input,
+        }
+    }
+}
+
+/// Sums a metric over the plan and its children, as the Parquet scan may be below other plans.
+fn sum_metric(plan: &Arc<dyn ExecutionPlan>, name: &str) -> usize {
+    let own = plan
+        .metrics()
+        .and_then(|metrics| metrics.sum_by_name(name))
+        .map_or(0, |value| value.as_usize());
+    own + plan
+        .children()
+        .into_iter()
+        .map(|child| sum_metric(child, name))
+        .sum::<usize>()
+}
+
+impl Drop for ParquetMetricsExec {
+    fn drop(&mut self) {
+        for (counter, by, metric) in PARQUET_METRICS {
+            let value = sum_metric(&self.input, metric);
+            if value > 0 {
+                let labels = [
+                    ("dataset", self.dataset_name.to_string()),
+                    ("by", by.to_string()),
+                ];
+                metrics::counter!(counter, &labels)
+                    .increment(u64::try_from(value).unwrap_or(u64::MAX));
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ParquetMetricsExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ParquetMetricsExec")
+    }
+}
+
+impl DisplayAs for ParquetMetricsExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ParquetMetricsExec")
+    }
+}
+
+impl ExecutionPlan for ParquetMetricsExec {
+    fn name(&self) -> &'static str {
+        "ParquetMetricsExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() == 1 {
+            Ok(Arc::new(ParquetMetricsExec::new(
+                self.dataset_name.clone(),
+                Arc::clone(&children[0]),
+            )))
+        } else {
+            Err(DataFusionError::Execution(
+                "ParquetMetricsExec expects exactly one input".to_string(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
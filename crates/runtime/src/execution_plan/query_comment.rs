@@ -0,0 +1,122 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow::datatypes::SchemaRef;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::Statistics;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use db_connection_pool::query_comment::with_query_comment;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// `QueryCommentExec` executes its input with a comment set for the SQL the input pushes down to its source.
+///
+/// Scans may be executed in tasks spawned by the operators above them, so the comment is set by a wrapper of each
+/// leaf rather than around the execution of the whole plan.
+#[allow(clippy::module_name_repetitions)]
+pub struct QueryCommentExec {
+    input: Arc<dyn ExecutionPlan>,
+    comment: String,
+}
+
+impl QueryCommentExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, comment: String) -> Self {
+        Self { input, comment }
+    }
+}
+
+impl fmt::Debug for QueryCommentExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "QueryCommentExec comment={}", self.comment)
+    }
+}
+
+impl DisplayAs for QueryCommentExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "QueryCommentExec comment={}", self.comment)
+    }
+}
+
+impl ExecutionPlan for QueryCommentExec {
+    fn name(&self) -> &'static str {
+        "QueryCommentExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() == 1 {
+            Ok(Arc::new(QueryCommentExec::new(
+                Arc::clone(&children[0]),
+                self.comment.clone(),
+            )))
+        } else {
+            Err(DataFusionError::Execution(
+                "QueryCommentExec expects exactly one input".to_string(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        with_query_comment(Some(self.comment.clone()), || {
+            self.input.execute(partition, context)
+        })
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+/// Wraps the leaves of `plan` in a [`QueryCommentExec`] with `comment`.
+pub fn comment_scans(
+    plan: Arc<dyn ExecutionPlan>,
+    comment: &str,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    plan.transform_up(|plan| {
+        if !plan.children().is_empty() {
+            return Ok(Transformed::no(plan));
+        }
+        Ok(Transformed::yes(
+            Arc::new(QueryCommentExec::new(plan, comment.to_string())) as Arc<dyn ExecutionPlan>,
+        ))
+    })
+    .map(|transformed| transformed.data)
+}
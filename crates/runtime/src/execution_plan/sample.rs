@@ -0,0 +1,201 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow::array::{BooleanArray, RecordBatch};
+use arrow::compute::filter_record_batch;
+use arrow::datatypes::SchemaRef;
+use data_components::sample::{SampleMethod, TableSample};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, ExecutionPlanProperties, PlanProperties,
+};
+use futures::StreamExt;
+use std::any::Any;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+
+/// `SampleExec` keeps a random sample of the rows of its input, for tables that can't sample in their engine.
+#[allow(clippy::module_name_repetitions)]
+pub struct SampleExec {
+    input: Arc<dyn ExecutionPlan>,
+    sample: TableSample,
+    properties: PlanProperties,
+}
+
+impl SampleExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, sample: TableSample) -> Self {
+        let eq_properties = input.equivalence_properties().clone();
+        let execution_mode = input.execution_mode();
+        let partitioning = input.output_partitioning().clone();
+        Self {
+            input,
+            sample,
+            properties: PlanProperties::new(eq_properties, partitioning, execution_mode),
+        }
+    }
+}
+
+impl fmt::Debug for SampleExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SampleExec sample: {:?}", self.sample)
+    }
+}
+
+impl DisplayAs for SampleExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SampleExec method={} percent={}",
+            self.sample.method, self.sample.percent
+        )
+    }
+}
+
+impl ExecutionPlan for SampleExec {
+    fn name(&self) -> &'static str {
+        "SampleExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() == 1 {
+            Ok(Arc::new(SampleExec::new(
+                Arc::clone(&children[0]),
+                self.sample.clone(),
+            )))
+        } else {
+            Err(DataFusionError::Execution(
+                "SampleExec expects exactly one input".to_string(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let seed = self
+            .sample
+            .seed
+            .unwrap_or_else(|| RandomState::new().build_hasher().finish());
+        let mut sampler = Sampler::new(&self.sample, seed ^ partition as u64);
+
+        let stream = self
+            .input
+            .execute(partition, context)?
+            .map(move |batch| sampler.sample(&batch?));
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+}
+
+struct Sampler {
+    method: SampleMethod,
+    fraction: f64,
+    state: u64,
+}
+
+impl Sampler {
+    fn new(sample: &TableSample, seed: u64) -> Self {
+        Self {
+            method: sample.method,
+            fraction: (sample.percent / 100.0).clamp(0.0, 1.0),
+            state: seed,
+        }
+    }
+
+    fn sample(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        match self.method {
+            SampleMethod::Bernoulli => {
+                let keep: BooleanArray = (0..batch.num_rows())
+                    .map(|_| Some(self.next() < self.fraction))
+                    .collect();
+                Ok(filter_record_batch(batch, &keep)?)
+            }
+            SampleMethod::System if self.next() < self.fraction => Ok(batch.clone()),
+            SampleMethod::System => Ok(batch.slice(0, 0)),
+        }
+    }
+
+    /// Returns a uniform random number in `[0, 1)`, from SplitMix64.
+    #[allow(clippy::cast_precision_loss)]
+    fn next(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int64Array, RecordBatch};
+
+    use super::*;
+
+    #[test]
+    fn keeps_about_the_sampled_fraction_of_rows() {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "a",
+            Arc::new(Int64Array::from_iter_values(0..10_000)) as _,
+        )])
+        .expect("valid batch");
+        let sample = TableSample {
+            method: SampleMethod::Bernoulli,
+            percent: 10.0,
+            seed: Some(42),
+        };
+
+        let rows = Sampler::new(&sample, 42)
+            .sample(&batch)
+            .expect("sampled")
+            .num_rows();
+        assert!((900..=1_100).contains(&rows), "sampled {rows} rows");
+
+        // The same seed samples the same rows.
+        assert_eq!(
+            Sampler::new(&sample, 42).sample(&batch).expect("sampled"),
+            Sampler::new(&sample, 42).sample(&batch).expect("sampled")
+        );
+    }
+}
@@ -0,0 +1,168 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow::datatypes::SchemaRef;
+use async_stream::stream;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::Statistics;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use futures::StreamExt;
+use std::any::Any;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// `ScanQuotaExec` counts the rows a scan reads towards the `max_rows_scanned` quota of a namespace, failing the
+/// query once the scans of the plan have read more rows than the quota.
+#[allow(clippy::module_name_repetitions)]
+pub struct ScanQuotaExec {
+    input: Arc<dyn ExecutionPlan>,
+    namespace: String,
+    limit: u64,
+    /// The rows read by every scan of the plan.
+    scanned: Arc<AtomicU64>,
+}
+
+impl ScanQuotaExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        namespace: String,
+        limit: u64,
+        scanned: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            input,
+            namespace,
+            limit,
+            scanned,
+        }
+    }
+}
+
+impl fmt::Debug for ScanQuotaExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ScanQuotaExec limit={}", self.limit)
+    }
+}
+
+impl DisplayAs for ScanQuotaExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ScanQuotaExec limit={}", self.limit)
+    }
+}
+
+impl ExecutionPlan for ScanQuotaExec {
+    fn name(&self) -> &'static str {
+        "ScanQuotaExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() == 1 {
+            Ok(Arc::new(ScanQuotaExec::new(
+                Arc::clone(&children[0]),
+                self.namespace.clone(),
+                self.limit,
+                Arc::clone(&self.scanned),
+            )))
+        } else {
+            Err(DataFusionError::Execution(
+                "ScanQuotaExec expects exactly one input".to_string(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut input = self.input.execute(partition, context)?;
+        let schema = input.schema();
+        let namespace = self.namespace.clone();
+        let limit = self.limit;
+        let scanned = Arc::clone(&self.scanned);
+
+        let counted = stream! {
+            while let Some(batch) = input.next().await {
+                if let Ok(batch) = &batch {
+                    let rows = batch.num_rows() as u64;
+                    let previous = scanned.fetch_add(rows, Ordering::AcqRel);
+                    if previous + rows > limit {
+                        // Only the scan that crosses the quota counts the rejection.
+                        if previous <= limit {
+                            metrics::counter!("namespace_quota_rejections", "namespace" => namespace.clone(), "quota" => "max_rows_scanned")
+                                .increment(1);
+                        }
+                        yield Err(DataFusionError::ResourcesExhausted(format!(
+                            "The query scanned more than {limit} rows, the max_rows_scanned quota of namespace {namespace}"
+                        )));
+                        return;
+                    }
+                }
+                yield batch;
+            }
+        };
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, counted)))
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+/// Wraps the leaves of `plan` in a [`ScanQuotaExec`] sharing a count of the rows they read.
+pub fn limit_rows_scanned(
+    plan: Arc<dyn ExecutionPlan>,
+    namespace: &str,
+    limit: u64,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let scanned = Arc::new(AtomicU64::new(0));
+    plan.transform_up(|plan| {
+        if !plan.children().is_empty() {
+            return Ok(Transformed::no(plan));
+        }
+        Ok(Transformed::yes(Arc::new(ScanQuotaExec::new(
+            plan,
+            namespace.to_string(),
+            limit,
+            Arc::clone(&scanned),
+        )) as Arc<dyn ExecutionPlan>))
+    })
+    .map(|transformed| transformed.data)
+}
@@ -0,0 +1,184 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow::datatypes::SchemaRef;
+use async_stream::stream;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::Statistics;
+use datafusion::config::ConfigOptions;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    displayable, DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+};
+use futures::StreamExt;
+use opentelemetry::trace::TraceContextExt;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Execution plans that produce their data locally, without calling out to a data source.
+const LOCAL_EXECS: [&str; 3] = ["EmptyExec", "PlaceholderRowExec", "MemoryExec"];
+
+/// `TracedExec` records a span for each partition its input executes, from the start of execution until
+/// the partition's stream is dropped.
+///
+/// It wraps the leaves of a physical plan, which are the calls to data connectors and accelerators.
+#[allow(clippy::module_name_repetitions)]
+pub struct TracedExec {
+    input: Arc<dyn ExecutionPlan>,
+    /// The span the plan was created in, as partitions may be executed outside of it.
+    parent: Span,
+}
+
+impl TracedExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, parent: Span) -> Self {
+        Self { input, parent }
+    }
+}
+
+impl fmt::Debug for TracedExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TracedExec")
+    }
+}
+
+impl DisplayAs for TracedExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "TracedExec")
+    }
+}
+
+impl ExecutionPlan for TracedExec {
+    fn name(&self) -> &'static str {
+        "TracedExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() == 1 {
+            Ok(Arc::new(TracedExec::new(
+                Arc::clone(&children[0]),
+                self.parent.clone(),
+            )))
+        } else {
+            Err(DataFusionError::Execution(
+                "TracedExec expects exactly one input".to_string(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let span = tracing::info_span!(
+            parent: &self.parent,
+            "scan",
+            otel.name = %format!("scan {}", self.input.name()),
+            otel.kind = "client",
+            plan = %displayable(self.input.as_ref()).one_line(),
+            partition,
+            rows = tracing::field::Empty,
+        );
+
+        let mut input = span.in_scope(|| self.input.execute(partition, context))?;
+        let schema = input.schema();
+
+        let traced = stream! {
+            let mut rows = 0;
+            while let Some(batch) = input.next().instrument(span.clone()).await {
+                if let Ok(batch) = &batch {
+                    rows += batch.num_rows();
+                }
+                yield batch;
+            }
+            span.record("rows", rows);
+        };
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, traced)))
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+/// Wraps the leaves of physical plans in a [`TracedExec`] when the plan is created within an exported trace.
+#[derive(Debug, Default)]
+pub struct TraceScans {}
+
+impl TraceScans {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PhysicalOptimizerRule for TraceScans {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        // Leave plans untouched unless traces are exported, so they aren't cluttered in `EXPLAIN`.
+        let parent = Span::current();
+        if !parent.context().span().span_context().is_valid() {
+            return Ok(plan);
+        }
+
+        plan.transform_up(|plan| {
+            if !plan.children().is_empty() || LOCAL_EXECS.contains(&plan.name()) {
+                return Ok(Transformed::no(plan));
+            }
+            Ok(Transformed::yes(
+                Arc::new(TracedExec::new(plan, parent.clone())) as Arc<dyn ExecutionPlan>,
+            ))
+        })
+        .map(|transformed| transformed.data)
+    }
+
+    fn name(&self) -> &str {
+        "trace_scans"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,245 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks the `expectations` of datasets after each refresh and every minute, marking a dataset as degraded in
+//! `/v1/status` while it has fewer rows than expected or its acceleration hasn't refreshed for longer than expected.
+//!
+//! Accelerated datasets are counted after their refreshes, and federated datasets on every check. The notifiers of
+//! the expectations are notified when a dataset becomes degraded and when it recovers, as for alerts.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use arrow::{array::AsArray, datatypes::Int64Type};
+use chrono::{DateTime, Utc};
+use datafusion::sql::TableReference;
+use serde_json::Value;
+use spicepod::component::dataset::expectations::Expectations;
+use tokio::sync::broadcast;
+
+use crate::{
+    alerts::{self, Notification},
+    datafusion::DataFusion,
+    events::{self, RuntimeEvent},
+    status, Runtime,
+};
+
+/// How often the staleness of the datasets, and the rows of federated datasets, are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Checked {
+    dataset: TableReference,
+    expectations: Expectations,
+    accelerated: bool,
+}
+
+/// Checks the expectations of the datasets of the app, until the runtime stops.
+pub(crate) async fn watch(rt: &Runtime) {
+    let mut events = events::subscribe();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    // The violation of the minimum rows of each dataset when it was last counted.
+    let mut row_violations: HashMap<TableReference, Option<String>> = HashMap::new();
+    let client = reqwest::Client::new();
+
+    loop {
+        let refreshed = tokio::select! {
+            _ = interval.tick() => None,
+            event = events.recv() => match event {
+                Ok(RuntimeEvent::RefreshComplete { dataset, error: None }) => Some(dataset),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Dataset expectations fell behind, {skipped} runtime events were skipped");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+
+        let datasets: Vec<Checked> = match rt.app.read().await.as_ref() {
+            Some(app) => app
+                .datasets
+                .iter()
+                .filter_map(|ds| {
+                    Some(Checked {
+                        dataset: TableReference::from(ds.name.as_str()),
+                        expectations: ds.expectations.clone()?,
+                        accelerated: ds.acceleration.as_ref().is_some_and(|a| a.enabled),
+                    })
+                })
+                .collect(),
+            None => continue,
+        };
+        row_violations.retain(|dataset, _| datasets.iter().any(|c| &c.dataset == dataset));
+
+        let states = status::component_states();
+        for checked in datasets {
+            if refreshed
+                .as_ref()
+                .is_some_and(|refreshed| *refreshed != checked.dataset)
+            {
+                continue;
+            }
+            let Some(state) = states.datasets.get(&checked.dataset.to_string()) else {
+                continue;
+            };
+            if !state.is_loaded() {
+                continue;
+            }
+
+            let mut violations = vec![];
+            if let Some(min_rows) = checked.expectations.min_rows {
+                let count = refreshed.is_some()
+                    || !checked.accelerated
+                    || !row_violations.contains_key(&checked.dataset);
+                if count {
+                    match count_rows(&rt.df, &checked.dataset).await {
+                        Ok(rows) => {
+                            row_violations
+                                .insert(checked.dataset.clone(), rows_violation(rows, min_rows));
+                        }
+                        Err(e) => {
+                            tracing::warn!("Unable to count the rows of {}: {e}", checked.dataset);
+                        }
+                    }
+                }
+                violations.extend(row_violations.get(&checked.dataset).cloned().flatten());
+            }
+            // Invalid durations are reported by the validation of the spicepod.
+            if let Some(max_staleness) = checked
+                .expectations
+                .max_staleness
+                .as_deref()
+                .and_then(|staleness| fundu::parse_duration(staleness).ok())
+            {
+                violations.extend(staleness_violation(
+                    state.last_refresh,
+                    max_staleness,
+                    Utc::now(),
+                ));
+            }
+
+            let was_degraded = !state.violations.is_empty();
+            let degraded = !violations.is_empty();
+            if degraded != was_degraded {
+                if degraded {
+                    tracing::warn!(
+                        "Dataset {} is degraded: {}",
+                        checked.dataset,
+                        violations.join(", ")
+                    );
+                } else {
+                    tracing::info!("Dataset {} meets its expectations again", checked.dataset);
+                }
+                notify(rt, &client, &checked, &violations);
+            }
+            status::update_dataset_violations(&checked.dataset, violations);
+        }
+    }
+}
+
+async fn count_rows(df: &DataFusion, dataset: &TableReference) -> Result<u64, String> {
+    let sql = format!("SELECT COUNT(*) FROM {}", dataset.to_quoted_string());
+    let batches = df
+        .ctx
+        .sql(&sql)
+        .await
+        .map_err(|e| e.to_string())?
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    batches
+        .first()
+        .and_then(|batch| batch.column(0).as_primitive_opt::<Int64Type>())
+        .filter(|counts| !counts.is_empty())
+        .and_then(|counts| u64::try_from(counts.value(0)).ok())
+        .ok_or_else(|| "COUNT(*) returned no rows".to_string())
+}
+
+fn rows_violation(rows: u64, min_rows: u64) -> Option<String> {
+    (rows < min_rows).then(|| format!("has {rows} rows, expected at least {min_rows}"))
+}
+
+fn staleness_violation(
+    last_refresh: Option<DateTime<Utc>>,
+    max_staleness: Duration,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let staleness = (now - last_refresh?).to_std().ok()?;
+    (staleness > max_staleness).then(|| {
+        format!(
+            "last refreshed {}s ago, expected within {}s",
+            staleness.as_secs(),
+            max_staleness.as_secs()
+        )
+    })
+}
+
+/// Notifies the notifiers of the expectations that the dataset became degraded, or recovered.
+fn notify(rt: &Runtime, client: &reqwest::Client, checked: &Checked, violations: &[String]) {
+    if checked.expectations.notify.is_empty() {
+        return;
+    }
+
+    let secrets_provider = Arc::clone(&rt.secrets_provider);
+    let client = client.clone();
+    let name = format!("{} expectations", checked.dataset);
+    let notifiers = checked.expectations.notify.clone();
+    let violations = violations.to_vec();
+    tokio::spawn(async move {
+        let notification = Notification {
+            alert: &name,
+            firing: !violations.is_empty(),
+            rows: violations.len(),
+            results: Value::from(violations),
+            time: Utc::now(),
+        };
+        for notifier in &notifiers {
+            if let Err(e) =
+                alerts::notify(&client, &secrets_provider, notifier, &notification).await
+            {
+                tracing::warn!("Unable to notify {:?} of {name}: {e}", notifier.kind);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn checks_rows_and_staleness() {
+        assert_eq!(rows_violation(10, 10), None);
+        assert_eq!(
+            rows_violation(9, 10).as_deref(),
+            Some("has 9 rows, expected at least 10")
+        );
+
+        let now = Utc::now();
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            staleness_violation(Some(now - TimeDelta::minutes(30)), hour, now),
+            None
+        );
+        assert_eq!(
+            staleness_violation(Some(now - TimeDelta::minutes(90)), hour, now).as_deref(),
+            Some("last refreshed 5400s ago, expected within 3600s")
+        );
+        assert_eq!(staleness_violation(None, hour, now), None);
+    }
+}
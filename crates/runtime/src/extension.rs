@@ -1,6 +1,9 @@
 use async_trait::async_trait;
+use datafusion::sql::TableReference;
 use snafu::prelude::*;
 
+use crate::error_info::ErrorInfo;
+use crate::events::{QueryFinished, QueryStarted, RuntimeEvent};
 use crate::Runtime;
 use spicepod::component::extension::Extension as ExtensionComponent;
 
@@ -18,6 +21,13 @@ pub enum Error {
     UnableToStartExtension {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    /// An error the extension classified itself, e.g. as a failed connection to its service.
+    #[snafu(display("{source}"))]
+    Coded {
+        info: ErrorInfo,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -37,6 +47,37 @@ pub trait Extension: Send + Sync {
     async fn initialize(&mut self, runtime: &mut Runtime) -> Result<()>;
 
     async fn on_start(&mut self, runtime: &Runtime) -> Result<()>;
+
+    // The hooks below are called once the extension has started, one event at a time, so they should return quickly
+    // and hand any slow work off to a task of their own.
+
+    async fn on_dataset_registered(&self, _dataset: &TableReference) {}
+
+    async fn on_dataset_removed(&self, _dataset: &TableReference) {}
+
+    /// Called after each refresh of an accelerated dataset, with its error if it failed.
+    async fn on_refresh_complete(&self, _dataset: &TableReference, _error: Option<&str>) {}
+
+    async fn on_query_start(&self, _query: &QueryStarted) {}
+
+    async fn on_query_end(&self, _query: &QueryFinished) {}
+}
+
+/// Calls the hook of `extension` for `event`.
+pub(crate) async fn dispatch(extension: &dyn Extension, event: &RuntimeEvent) {
+    match event {
+        RuntimeEvent::DatasetRegistered { dataset } => {
+            extension.on_dataset_registered(dataset).await;
+        }
+        RuntimeEvent::DatasetRemoved { dataset } => extension.on_dataset_removed(dataset).await,
+        RuntimeEvent::RefreshComplete { dataset, error } => {
+            extension
+                .on_refresh_complete(dataset, error.as_deref())
+                .await;
+        }
+        RuntimeEvent::QueryStart(query) => extension.on_query_start(query).await,
+        RuntimeEvent::QueryEnd(query) => extension.on_query_end(query).await,
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -14,18 +14,26 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::auth::{OidcValidator, Principal};
+use crate::cluster::exchange::{self, ClusterPeer};
 use crate::datafusion::query::error_code::ErrorCode;
-use crate::datafusion::query::{Protocol, QueryBuilder};
+use crate::datafusion::query::labels::{parse_labels, QueryLabels, LABELS_HEADER};
+use crate::datafusion::query::{Protocol, QueryBuilder, QueryPartitions};
 use crate::datafusion::DataFusion;
 use crate::dataupdate::DataUpdate;
+use crate::error_info::{ErrorInfo, ErrorKind, ToErrorInfo};
+use crate::ipc_compression::{self, COMPRESSION_HEADER};
 use crate::measure_scope_ms;
+use crate::tls::TlsConfig;
+use crate::tracing_util;
 use arrow::array::RecordBatch;
 use arrow::datatypes::Schema;
 use arrow::ipc::writer::{DictionaryTracker, IpcDataGenerator};
 use arrow_flight::encode::FlightDataEncoderBuilder;
 use arrow_flight::{Action, ActionType, Criteria, IpcMessage, PollInfo, SchemaResult};
-use arrow_ipc::writer::IpcWriteOptions;
+use arrow_ipc::{writer::IpcWriteOptions, CompressionType};
 use bytes::Bytes;
+use datafusion::common::ParamValues;
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::SQLOptions;
 use datafusion::sql::sqlparser::parser::ParserError;
@@ -33,10 +41,12 @@ use datafusion::sql::TableReference;
 use futures::stream::{self, BoxStream, StreamExt};
 use futures::{Stream, TryStreamExt};
 use snafu::prelude::*;
+use spicepod::component::runtime::Flight as FlightConfig;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::broadcast::Sender;
 use tokio::sync::RwLock;
+use tonic::metadata::MetadataValue;
 use tonic::transport::Server;
 use tonic::{Request, Response, Status, Streaming};
 
@@ -47,7 +57,9 @@ mod do_put;
 mod flight_utils;
 mod flightsql;
 mod get_flight_info;
+mod get_schema;
 mod handshake;
+mod shard_scan;
 
 use arrow_flight::{
     flight_service_server::{FlightService, FlightServiceServer},
@@ -55,9 +67,18 @@ use arrow_flight::{
     SchemaAsIpc, Ticket,
 };
 
+/// The metadata of an error status with the code, component, retryability and hint of the error.
+const ERROR_CODE_METADATA: &str = "x-spice-error-code";
+const ERROR_COMPONENT_METADATA: &str = "x-spice-error-component";
+const ERROR_RETRYABLE_METADATA: &str = "x-spice-error-retryable";
+const ERROR_HINT_METADATA: &str = "x-spice-error-hint";
+
 pub struct Service {
     datafusion: Arc<DataFusion>,
     channel_map: Arc<RwLock<HashMap<TableReference, Arc<Sender<DataUpdate>>>>>,
+
+    /// The maximum number of endpoints a query result is partitioned into by `GetFlightInfo`.
+    max_endpoints: usize,
 }
 
 #[tonic::async_trait]
@@ -72,9 +93,10 @@ impl FlightService for Service {
 
     async fn handshake(
         &self,
-        _request: Request<Streaming<HandshakeRequest>>,
+        request: Request<Streaming<HandshakeRequest>>,
     ) -> Result<Response<Self::HandshakeStream>, Status> {
         metrics::counter!("flight_handshake_requests").increment(1);
+        reject_cluster_peer(&request)?;
         handshake::handle()
     }
 
@@ -93,6 +115,7 @@ impl FlightService for Service {
     ) -> Result<Response<FlightInfo>, Status> {
         measure_scope_ms!("flight_get_flight_info_request_duration_ms");
         metrics::counter!("flight_get_flight_info_requests").increment(1);
+        reject_cluster_peer(&request)?;
         Box::pin(get_flight_info::handle(self, request)).await
     }
 
@@ -105,11 +128,11 @@ impl FlightService for Service {
 
     async fn get_schema(
         &self,
-        _request: Request<FlightDescriptor>,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<SchemaResult>, Status> {
         metrics::counter!("flight_get_schema_requests").increment(1);
-        tracing::trace!("get_schema - unimplemented");
-        Err(Status::unimplemented("Not yet implemented"))
+        reject_cluster_peer(&request)?;
+        Box::pin(get_schema::handle(self, request)).await
     }
 
     async fn do_get(
@@ -125,6 +148,7 @@ impl FlightService for Service {
         request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoPutStream>, Status> {
         metrics::counter!("flight_do_put_requests").increment(1);
+        reject_cluster_peer(&request)?;
         do_put::handle(self, request).await
     }
 
@@ -133,6 +157,7 @@ impl FlightService for Service {
         request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoExchangeStream>, Status> {
         metrics::counter!("flight_do_exchange_requests").increment(1);
+        reject_cluster_peer(&request)?;
         do_exchange::handle(self, request).await
     }
 
@@ -141,21 +166,31 @@ impl FlightService for Service {
         request: Request<Action>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
         metrics::counter!("flight_do_action_requests").increment(1);
+        reject_cluster_peer(&request)?;
         Box::pin(actions::do_action(self, request)).await
     }
 
     async fn list_actions(
         &self,
-        _request: Request<arrow_flight::Empty>,
+        request: Request<arrow_flight::Empty>,
     ) -> Result<Response<Self::ListActionsStream>, Status> {
         metrics::counter!("flight_list_actions_requests").increment(1);
+        reject_cluster_peer(&request)?;
         Ok(actions::list())
     }
 }
 
 impl Service {
-    async fn get_arrow_schema(datafusion: Arc<DataFusion>, sql: String) -> Result<Schema, Status> {
-        let query = QueryBuilder::new(sql, datafusion, Protocol::Flight).build();
+    async fn get_arrow_schema(
+        datafusion: Arc<DataFusion>,
+        sql: String,
+        parameters: Option<ParamValues>,
+        principal: Option<Principal>,
+    ) -> Result<Schema, Status> {
+        let query = QueryBuilder::new(sql, datafusion, Protocol::Flight)
+            .parameters(parameters)
+            .principal(principal)
+            .build();
 
         let schema = match query.get_schema().await {
             Ok(schema) => schema,
@@ -181,6 +216,11 @@ impl Service {
     async fn sql_to_flight_stream(
         datafusion: Arc<DataFusion>,
         sql: String,
+        parameters: Option<ParamValues>,
+        partitions: Option<QueryPartitions>,
+        principal: Option<Principal>,
+        labels: QueryLabels,
+        compression: Option<CompressionType>,
     ) -> Result<(BoxStream<'static, Result<FlightData, Status>>, Option<bool>), Status> {
         let restricted_sql_options = SQLOptions::new()
             .with_allow_ddl(false)
@@ -190,12 +230,19 @@ impl Service {
         let query = QueryBuilder::new(sql, Arc::clone(&datafusion), Protocol::Flight)
             .restricted_sql_options(Some(restricted_sql_options))
             .protocol(Protocol::Flight)
+            .parameters(parameters)
+            .partitions(partitions)
+            .principal(principal)
+            .labels(labels)
             .build();
 
-        let query_result = query.run().await.map_err(to_tonic_err)?;
+        let query_result = query
+            .run()
+            .await
+            .map_err(|e| coded_status(e.to_string(), &e.error_info()))?;
 
         let schema = query_result.data.schema();
-        let options = datafusion::arrow::ipc::writer::IpcWriteOptions::default();
+        let options = ipc_compression::write_options(compression);
         let schema_as_ipc = SchemaAsIpc::new(&schema, &options);
         let schema_flight_data = FlightData::from(schema_as_ipc);
 
@@ -218,7 +265,7 @@ impl Service {
                             flights.push(flight_batch.into());
                             Ok(flights)
                         }
-                        Err(e) => Err(Status::internal(e.to_string())),
+                        Err(e) => Err(coded_status(e.to_string(), &e.error_info())),
                     }
                 }
             })
@@ -245,6 +292,27 @@ fn record_batches_to_flight_stream(
         .map_err(to_tonic_err)
 }
 
+/// The labels the client attributes its query to with the `x-spice-query-labels` metadata.
+fn query_labels<T>(request: &Request<T>) -> QueryLabels {
+    request
+        .metadata()
+        .get(LABELS_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_labels)
+        .unwrap_or_default()
+}
+
+/// The codec the record batches of a query are compressed with, negotiated with the `x-spice-arrow-compression`
+/// metadata.
+fn arrow_compression<T>(request: &Request<T>) -> Option<CompressionType> {
+    ipc_compression::negotiate(
+        request
+            .metadata()
+            .get(COMPRESSION_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    )
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn to_tonic_err<E>(e: E) -> Status
 where
@@ -259,7 +327,8 @@ where
 
 #[allow(clippy::needless_pass_by_value)]
 fn handle_datafusion_error(e: DataFusionError) -> Status {
-    match e {
+    let info = e.error_info();
+    let status = match e {
         DataFusionError::Plan(err_msg) | DataFusionError::Execution(err_msg) => {
             Status::invalid_argument(err_msg)
         }
@@ -274,8 +343,41 @@ fn handle_datafusion_error(e: DataFusionError) -> Status {
         DataFusionError::SchemaError(schema_err, _) => {
             Status::invalid_argument(format!("{schema_err}"))
         }
-        _ => to_tonic_err(e),
+        _ => return coded_status(e.to_string(), &info),
+    };
+    with_error_info(status, &info)
+}
+
+/// A status with the gRPC code of the kind of an error, and its code, retryability and hint as metadata.
+fn coded_status(message: String, info: &ErrorInfo) -> Status {
+    let status = match info.kind {
+        ErrorKind::InvalidInput => Status::invalid_argument(message),
+        ErrorKind::AccessDenied => Status::permission_denied(message),
+        ErrorKind::NotFound => Status::not_found(message),
+        ErrorKind::Unavailable => Status::unavailable(message),
+        ErrorKind::ResourceExhausted => Status::resource_exhausted(message),
+        ErrorKind::Internal => Status::internal(message),
+    };
+    with_error_info(status, info)
+}
+
+fn with_error_info(mut status: Status, info: &ErrorInfo) -> Status {
+    let metadata = status.metadata_mut();
+    metadata.insert(ERROR_CODE_METADATA, MetadataValue::from_static(info.code));
+    if let Ok(component) = MetadataValue::try_from(info.component.to_string()) {
+        metadata.insert(ERROR_COMPONENT_METADATA, component);
     }
+    metadata.insert(
+        ERROR_RETRYABLE_METADATA,
+        MetadataValue::from_static(if info.retryable { "true" } else { "false" }),
+    );
+    if let Some(hint) = info
+        .hint
+        .and_then(|hint| MetadataValue::try_from(hint).ok())
+    {
+        metadata.insert(ERROR_HINT_METADATA, hint);
+    }
+    status
 }
 
 #[derive(Debug, Snafu)]
@@ -290,21 +392,113 @@ pub enum Error {
 
     #[snafu(display("Unable to start Flight server: {source}"))]
     UnableToStartFlightServer { source: tonic::transport::Error },
+
+    #[snafu(display("Unable to configure TLS: {source}"))]
+    UnableToConfigureTls { source: tonic::transport::Error },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
-pub async fn start(bind_address: std::net::SocketAddr, df: Arc<DataFusion>) -> Result<()> {
+/// Validates the bearer token of each request when authentication is configured, attaching the
+/// resulting [`Principal`] to the request extensions.
+///
+/// Requests with the token of the cluster are from the other members, and are marked with [`ClusterPeer`] instead.
+#[allow(clippy::result_large_err)]
+fn authenticate(auth: Option<&OidcValidator>, mut req: Request<()>) -> Result<Request<()>, Status> {
+    if let Some(token) = req.metadata().get(exchange::CLUSTER_TOKEN_METADATA) {
+        if token.to_str().is_ok_and(exchange::is_peer_token) {
+            req.extensions_mut().insert(ClusterPeer);
+            return Ok(req);
+        }
+        metrics::counter!("flight_requests_unauthenticated").increment(1);
+        return Err(Status::unauthenticated("Invalid cluster token"));
+    }
+
+    let Some(auth) = auth else {
+        return Ok(req);
+    };
+
+    let authorization = req
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok());
+
+    match auth.validate_authorization(authorization) {
+        Ok(principal) => {
+            req.extensions_mut().insert(principal);
+            Ok(req)
+        }
+        Err(e) => {
+            tracing::debug!("Rejected unauthenticated Flight request: {e}");
+            metrics::counter!("flight_requests_unauthenticated").increment(1);
+            Err(Status::unauthenticated(e.to_string()))
+        }
+    }
+}
+
+/// Rejects the requests of the other members of the cluster, which are only allowed to scan accelerations.
+#[allow(clippy::result_large_err)]
+fn reject_cluster_peer<T>(request: &Request<T>) -> Result<(), Status> {
+    if request.extensions().get::<ClusterPeer>().is_some() {
+        return Err(Status::permission_denied(
+            "The token of the cluster only allows the scans of accelerations",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects writes to datasets by principals that aren't admins, as the access policies of datasets only restrict
+/// reads. Writes are allowed to everyone when authentication is disabled.
+#[allow(clippy::result_large_err)]
+fn require_admin(principal: Option<&Principal>) -> Result<(), Status> {
+    match principal {
+        Some(principal) if !principal.admin => Err(Status::permission_denied(format!(
+            "{} is not an admin, and only admins can write to datasets. Admin access is granted by runtime.auth.oidc.admin.",
+            principal.subject
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub async fn start(
+    bind_address: std::net::SocketAddr,
+    df: Arc<DataFusion>,
+    config: FlightConfig,
+    auth: Option<Arc<OidcValidator>>,
+    tls: Option<TlsConfig>,
+) -> Result<()> {
     let service = Service {
         datafusion: Arc::clone(&df),
         channel_map: Arc::new(RwLock::new(HashMap::new())),
+        max_endpoints: config.max_endpoints.unwrap_or(1).max(1),
     };
-    let svc = FlightServiceServer::new(service);
+    let svc = FlightServiceServer::with_interceptor(service, move |req| {
+        authenticate(auth.as_deref(), req)
+    });
 
     tracing::info!("Spice Runtime Flight listening on {bind_address}");
     metrics::counter!("spiced_runtime_flight_server_start").increment(1);
 
-    Server::builder()
+    let mut server = Server::builder().trace_fn(|req| {
+        let span = tracing::info_span!(
+            "flight_request",
+            otel.name = %req.uri().path(),
+            otel.kind = "server",
+        );
+        tracing_util::set_remote_parent(&span, |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        });
+        span
+    });
+    if let Some(tls) = tls {
+        server = server
+            .tls_config(tls.tonic_config())
+            .context(UnableToConfigureTlsSnafu)?;
+    }
+
+    server
         .add_service(svc)
         .serve(bind_address)
         .await
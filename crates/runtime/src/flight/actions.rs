@@ -20,6 +20,7 @@ use prost::Message;
 use tonic::{Request, Response, Status};
 
 use crate::{
+    auth::Principal,
     flight::{flightsql::prepared_statement_query, to_tonic_err, Service},
     timing::{TimeMeasurement, TimedStream},
 };
@@ -111,9 +112,11 @@ pub(crate) async fn do_action(
                         "Unable to unpack ActionCreatePreparedStatementRequest.",
                     )
                 })?;
-            let stmt =
-                prepared_statement_query::do_action_create_prepared_statement(flight_svc, cmd)
-                    .await?;
+            let principal = request.extensions().get::<Principal>().cloned();
+            let stmt = prepared_statement_query::do_action_create_prepared_statement(
+                flight_svc, cmd, principal,
+            )
+            .await?;
             futures::stream::iter(vec![Ok(arrow_flight::Result {
                 body: stmt.as_any().encode_to_vec().into(),
             })])
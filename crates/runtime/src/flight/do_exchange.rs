@@ -14,24 +14,48 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use arrow_flight::{flight_service_server::FlightService, FlightData, SchemaAsIpc};
-use arrow_ipc::writer::{self, DictionaryTracker, IpcDataGenerator};
-use datafusion::sql::TableReference;
-use futures::{stream, StreamExt};
-use tokio::sync::broadcast;
+use arrow::array::RecordBatch;
+use arrow_flight::{
+    flight_service_server::FlightService, utils::flight_data_to_arrow_batch, FlightData,
+    SchemaAsIpc,
+};
+use arrow_ipc::{
+    convert::try_schema_from_flatbuffer_bytes,
+    writer::{self, DictionaryTracker, IpcDataGenerator},
+};
+use datafusion::{
+    error::{DataFusionError, Result as DataFusionResult},
+    physical_plan::stream::RecordBatchStreamAdapter,
+    sql::TableReference,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status, Streaming};
 
-use crate::dataupdate::{DataUpdate, UpdateType};
+use crate::{
+    auth::Principal,
+    datafusion::query::Protocol,
+    dataupdate::{DataUpdate, UpdateType},
+};
 
-use super::Service;
+use super::{require_admin, Service};
+
+/// The `app_metadata` of the first message of an exchange that ingests its batches into the dataset, rather than
+/// subscribing to the updates of the dataset.
+const INGEST_METADATA: &[u8] = b"ingest";
+
+/// The number of decoded batches that can wait to be written before the client stops being read from.
+const INGEST_BUFFER_SIZE: usize = 8;
 
 #[allow(clippy::too_many_lines)]
 pub(crate) async fn handle(
     flight_svc: &Service,
     request: Request<Streaming<FlightData>>,
 ) -> Result<Response<<Service as FlightService>::DoExchangeStream>, Status> {
+    let principal = request.extensions().get::<Principal>().cloned();
     let mut streaming_request = request.into_inner();
     let req = streaming_request.next().await;
     let Some(subscription_request) = req else {
@@ -50,7 +74,7 @@ pub(crate) async fn handle(
     };
 
     // TODO: Support multiple flight descriptors to subscribe to multiple data sources
-    let Some(flight_descriptor) = subscription_request.flight_descriptor else {
+    let Some(flight_descriptor) = &subscription_request.flight_descriptor else {
         return Err(Status::invalid_argument(
             "Flight descriptor required to indicate which data to subscribe to",
         ));
@@ -70,6 +94,23 @@ pub(crate) async fn handle(
         )));
     };
 
+    if subscription_request.app_metadata.as_ref() == INGEST_METADATA {
+        require_admin(principal.as_ref())?;
+        return ingest(
+            flight_svc,
+            data_path,
+            &subscription_request,
+            streaming_request,
+        );
+    }
+
+    // The updates of the dataset are sent to subscribers as they are written, so its policies can't be applied to them.
+    if flight_svc.datafusion.has_policies(&data_path) {
+        return Err(Status::permission_denied(format!(
+            "Dataset {data_path} has access policies, so it can't be subscribed to"
+        )));
+    }
+
     let channel_map = Arc::clone(&flight_svc.channel_map);
     let channel_map_read = channel_map.read().await;
     let (tx, rx) = if let Some(channel) = channel_map_read.get(&data_path) {
@@ -128,14 +169,16 @@ pub(crate) async fn handle(
 
     let datafusion = Arc::clone(&flight_svc.datafusion);
     tokio::spawn(async move {
-        let Ok(df) = datafusion
-            .ctx
-            .sql(&format!(r#"SELECT * FROM {data_path}"#))
+        let Ok(query_result) = datafusion
+            .query_builder(format!(r#"SELECT * FROM {data_path}"#), Protocol::Flight)
+            .principal(principal)
+            .build()
+            .run()
             .await
         else {
             return;
         };
-        let Ok(results) = df.collect().await else {
+        let Ok(results) = query_result.data.try_collect::<Vec<_>>().await else {
             return;
         };
         if results.is_empty() {
@@ -155,3 +198,69 @@ pub(crate) async fn handle(
 
     Ok(Response::new(response_stream.boxed()))
 }
+
+/// Streams the batches of the exchange into the dataset. The batches are committed together once the client ends
+/// the stream, and a single message whose `app_metadata` is the number of rows written is sent back. If any batch
+/// can't be decoded or written, none of them are.
+fn ingest(
+    flight_svc: &Service,
+    data_path: TableReference,
+    first_message: &FlightData,
+    mut streaming_request: Streaming<FlightData>,
+) -> Result<Response<<Service as FlightService>::DoExchangeStream>, Status> {
+    let schema = Arc::new(
+        try_schema_from_flatbuffer_bytes(&first_message.data_header).map_err(|e| {
+            Status::invalid_argument(format!("Failed to get schema from data header: {e}"))
+        })?,
+    );
+    let dictionaries_by_id = HashMap::new();
+
+    // The first message may only contain the schema and no data.
+    let first_batch =
+        flight_data_to_arrow_batch(first_message, Arc::clone(&schema), &dictionaries_by_id).ok();
+
+    let (tx, rx) = mpsc::channel::<DataFusionResult<RecordBatch>>(INGEST_BUFFER_SIZE);
+    let decode_schema = Arc::clone(&schema);
+    tokio::spawn(async move {
+        if let Some(first_batch) = first_batch {
+            if tx.send(Ok(first_batch)).await.is_err() {
+                return;
+            }
+        }
+
+        loop {
+            let batch = match streaming_request.message().await {
+                Ok(Some(message)) => flight_data_to_arrow_batch(
+                    &message,
+                    Arc::clone(&decode_schema),
+                    &dictionaries_by_id,
+                )
+                .map_err(|e| DataFusionError::ArrowError(e, None)),
+                Ok(None) => return,
+                Err(e) => Err(DataFusionError::External(Box::new(e))),
+            };
+
+            let failed = batch.is_err();
+            // Waits while the buffer is full, which stops reading from the client until the writes catch up.
+            if tx.send(batch).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let datafusion = Arc::clone(&flight_svc.datafusion);
+    let stream = RecordBatchStreamAdapter::new(schema, ReceiverStream::new(rx));
+    let response_stream = stream::once(async move {
+        let rows = datafusion
+            .write_stream(data_path.clone(), Box::pin(stream))
+            .await
+            .map_err(|e| Status::internal(format!("Error writing data: {e}")))?;
+
+        metrics::counter!("flight_do_exchange_ingested_rows", "path" => data_path.to_string())
+            .increment(rows);
+
+        Ok::<_, Status>(FlightData::new().with_app_metadata(rows.to_string()))
+    });
+
+    Ok(Response::new(response_stream.boxed()))
+}
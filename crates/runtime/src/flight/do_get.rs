@@ -25,28 +25,76 @@ use prost::Message;
 use tonic::{Request, Response, Status};
 
 use crate::{
+    auth::Principal,
+    cluster,
     flight::flight_utils::attach_cache_metadata,
     timing::{TimeMeasurement, TimedStream},
 };
 
-use super::{flightsql, to_tonic_err, Service};
+use super::{
+    arrow_compression, flightsql, query_labels, reject_cluster_peer, shard_scan, to_tonic_err,
+    Service,
+};
 
 pub(crate) async fn handle(
     flight_svc: &Service,
     request: Request<Ticket>,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
+    let principal = request.extensions().get::<Principal>().cloned();
+    let labels = query_labels(&request);
+    let compression = arrow_compression(&request);
+
     let msg: Any = match Message::decode(&*request.get_ref().ticket) {
         Ok(msg) => msg,
-        Err(_) => return Box::pin(do_get_simple(flight_svc, request)).await,
+        Err(_) => {
+            reject_cluster_peer(&request)?;
+            return Box::pin(do_get_simple(flight_svc, request)).await;
+        }
     };
 
+    if msg.type_url == cluster::exchange::SHARD_SCAN_TYPE_URL {
+        let peer = request
+            .extensions()
+            .get::<cluster::exchange::ClusterPeer>()
+            .is_some();
+        return Box::pin(shard_scan::do_get(
+            flight_svc,
+            &msg.value,
+            peer,
+            compression,
+        ))
+        .await;
+    }
+    reject_cluster_peer(&request)?;
+
     match Command::try_from(msg).map_err(to_tonic_err)? {
         Command::CommandStatementQuery(command) => {
-            Box::pin(flightsql::statement_query::do_get(flight_svc, command)).await
+            Box::pin(flightsql::statement_query::do_get(
+                flight_svc,
+                command,
+                principal,
+                labels,
+                compression,
+            ))
+            .await
+        }
+        Command::TicketStatementQuery(ticket) => {
+            Box::pin(flightsql::statement_query::do_get_ticket(
+                flight_svc,
+                ticket,
+                principal,
+                labels,
+                compression,
+            ))
+            .await
         }
         Command::CommandPreparedStatementQuery(command) => {
             Box::pin(flightsql::prepared_statement_query::do_get(
-                flight_svc, command,
+                flight_svc,
+                command,
+                principal,
+                labels,
+                compression,
             ))
             .await
         }
@@ -60,7 +108,7 @@ pub(crate) async fn handle(
             flightsql::get_tables::do_get(flight_svc, command).await
         }
         Command::CommandGetPrimaryKeys(command) => {
-            flightsql::get_primary_keys::do_get(flight_svc, &command)
+            flightsql::get_primary_keys::do_get(flight_svc, &command).await
         }
         Command::CommandGetTableTypes(command) => flightsql::get_table_types::do_get(&command),
         Command::CommandGetSqlInfo(command) => flightsql::get_sql_info::do_get(command),
@@ -73,13 +121,24 @@ async fn do_get_simple(
     request: Request<Ticket>,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let datafusion = Arc::clone(&flight_svc.datafusion);
+    let principal = request.extensions().get::<Principal>().cloned();
+    let labels = query_labels(&request);
+    let compression = arrow_compression(&request);
     let ticket = request.into_inner();
     tracing::trace!("do_get_simple: {ticket:?}");
     match std::str::from_utf8(&ticket.ticket) {
         Ok(sql) => {
             let start = TimeMeasurement::new("flight_do_get_simple_duration_ms", vec![]);
-            let (output, from_cache) =
-                Box::pin(Service::sql_to_flight_stream(datafusion, sql.to_owned())).await?;
+            let (output, from_cache) = Box::pin(Service::sql_to_flight_stream(
+                datafusion,
+                sql.to_owned(),
+                None,
+                None,
+                principal,
+                labels,
+                compression,
+            ))
+            .await?;
 
             let timed_output = TimedStream::new(output, move || start);
 
@@ -16,19 +16,30 @@ limitations under the License.
 
 use std::{collections::HashMap, sync::Arc};
 
-use arrow_flight::{flight_service_server::FlightService, FlightData, PutResult};
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use arrow_flight::{
+    flight_service_server::FlightService,
+    sql::{Any, Command},
+    utils::flight_data_to_batches,
+    FlightData, PutResult,
+};
 use arrow_ipc::convert::try_schema_from_flatbuffer_bytes;
+use arrow_tools::schema::verify_schema;
+use async_stream::stream;
 use datafusion::sql::TableReference;
-use futures::stream;
+use prost::Message;
+use serde::Serialize;
 use tokio::sync::{broadcast::Sender, RwLock};
 use tonic::{Request, Response, Status, Streaming};
 
 use crate::{
+    auth::Principal,
+    datafusion::DataFusion,
     dataupdate::{DataUpdate, UpdateType},
     timing::{TimeMeasurement, TimedStream},
 };
 
-use super::Service;
+use super::{flightsql, require_admin, to_tonic_err, Service};
 
 async fn get_sender_channel(
     channel_map: Arc<RwLock<HashMap<TableReference, Arc<Sender<DataUpdate>>>>>,
@@ -43,11 +54,33 @@ async fn get_sender_channel(
     }
 }
 
+async fn do_put_command(
+    command: Any,
+    first_message: FlightData,
+    mut streaming_flight: Streaming<FlightData>,
+) -> Result<Response<<Service as FlightService>::DoPutStream>, Status> {
+    match Command::try_from(command).map_err(to_tonic_err)? {
+        Command::CommandPreparedStatementQuery(query) => {
+            let mut messages = vec![first_message];
+            while let Some(message) = streaming_flight.message().await? {
+                messages.push(message);
+            }
+            let parameters = flight_data_to_batches(&messages).map_err(to_tonic_err)?;
+            flightsql::prepared_statement_query::do_put(&query, &parameters)
+        }
+        Command::CommandStatementUpdate(_) | Command::CommandPreparedStatementUpdate(_) => Err(
+            Status::unimplemented("Updates through Flight SQL are not supported"),
+        ),
+        _ => Err(Status::unimplemented("Not yet implemented")),
+    }
+}
+
 pub(crate) async fn handle(
     flight_svc: &Service,
     request: Request<Streaming<FlightData>>,
 ) -> Result<Response<<Service as FlightService>::DoPutStream>, Status> {
     let mut duration_metric = TimeMeasurement::new("flight_do_put_duration_ms", vec![]);
+    let principal = request.extensions().get::<Principal>().cloned();
     let mut streaming_flight = request.into_inner();
 
     let Ok(Some(message)) = streaming_flight.message().await else {
@@ -56,24 +89,39 @@ pub(crate) async fn handle(
     let Some(fd) = &message.flight_descriptor else {
         return Err(Status::invalid_argument("No flight descriptor provided"));
     };
+
+    // Flight SQL commands are sent in the `cmd` of the descriptor, while writes to a dataset name it in the `path`.
+    if !fd.cmd.is_empty() {
+        let command = Any::decode(&*fd.cmd).map_err(to_tonic_err)?;
+        return do_put_command(command, message, streaming_flight).await;
+    }
     if fd.path.is_empty() {
         return Err(Status::invalid_argument("No path provided"));
     };
+    require_admin(principal.as_ref())?;
 
     let path = TableReference::parse_str(&fd.path.join("."));
 
     duration_metric.with_labels(vec![("path", path.to_string())]);
 
-    if !flight_svc.datafusion.is_writable(&path) {
-        return Err(Status::invalid_argument(format!(
-            "Path doesn't exist or is not writable: {path}",
+    let df = Arc::clone(&flight_svc.datafusion);
+    let Some(table) = df.get_table(path.clone()).await else {
+        return Err(Status::not_found(format!("Dataset {path} doesn't exist")));
+    };
+    if !df.is_writable(&path) {
+        return Err(Status::failed_precondition(format!(
+            "Dataset {path} is not writable. Set its mode to read_write to write to it.",
         )));
     };
 
     let schema = try_schema_from_flatbuffer_bytes(&message.data_header)
         .map_err(|e| Status::internal(format!("Failed to get schema from data header: {e}")))?;
     let schema = Arc::new(schema);
-    let dictionaries_by_id = Arc::new(HashMap::new());
+    // Reject a mismatched schema before any data is sent, rather than when writing the first batch.
+    verify_schema(table.schema().fields(), schema.fields()).map_err(|e| {
+        Status::invalid_argument(format!("The schema doesn't match dataset {path}: {e}"))
+    })?;
+    let dictionaries_by_id = HashMap::new();
 
     // Sometimes the first message only contains the schema and no data
     let first_batch = arrow_flight::utils::flight_data_to_arrow_batch(
@@ -83,68 +131,144 @@ pub(crate) async fn handle(
     )
     .ok();
 
-    let mut batches = vec![];
-    if let Some(first_batch) = first_batch {
-        batches.push(first_batch);
-    }
-
     let channel_map = Arc::clone(&flight_svc.channel_map);
-    let df = Arc::clone(&flight_svc.datafusion);
 
-    let response_stream = stream::unfold(streaming_flight, move |mut flight| {
-        let schema = Arc::clone(&schema);
-        let df = Arc::clone(&df);
-        let dictionaries_by_id = Arc::clone(&dictionaries_by_id);
-        let path = path.clone();
-        let channel_map = Arc::clone(&channel_map);
-        async move {
-            match flight.message().await {
-                Ok(Some(message)) => {
-                    let new_batch = match arrow_flight::utils::flight_data_to_arrow_batch(
+    // Each batch is acknowledged once written. Writing stops at the first batch that fails, reporting its index, so
+    // clients know which batches were written.
+    let response_stream = stream! {
+        let mut index = 0;
+        let mut next_batch = first_batch;
+        loop {
+            let batch = match next_batch.take() {
+                Some(batch) => batch,
+                None => match streaming_flight.message().await {
+                    Ok(Some(message)) => match arrow_flight::utils::flight_data_to_arrow_batch(
                         &message,
                         Arc::clone(&schema),
                         &dictionaries_by_id,
                     ) {
-                        Ok(batches) => batches,
+                        Ok(batch) => batch,
                         Err(e) => {
-                            tracing::error!("Failed to convert flight data to batches: {e}");
-                            return None;
+                            yield Err(Status::invalid_argument(format!(
+                                "Batch {index} is not valid Arrow data: {e}"
+                            )));
+                            break;
                         }
-                    };
-                    tracing::trace!("Received batch with {} rows", new_batch.num_rows());
-
-                    let data_update = DataUpdate {
-                        data: vec![new_batch],
-                        schema: Arc::clone(&schema),
-                        update_type: UpdateType::Append,
-                    };
-
-                    if let Some(channel) = get_sender_channel(channel_map, &path).await {
-                        let _ = channel.send(data_update.clone());
-                    };
-
-                    if let Err(e) = df.write_data(path.clone(), data_update).await {
-                        return Some((
-                            Err(Status::internal(format!("Error writing data: {e}"))),
-                            flight,
-                        ));
-                    };
-
-                    Some((Ok(PutResult::default()), flight))
-                }
-                Ok(None) => {
+                    },
                     // End of the stream
-                    None
-                }
-                Err(e) => Some((
-                    Err(Status::internal(format!("Error reading message: {e}"))),
-                    flight,
-                )),
+                    Ok(None) => break,
+                    Err(e) => {
+                        yield Err(Status::internal(format!("Error reading message: {e}")));
+                        break;
+                    }
+                },
+            };
+            tracing::trace!("Received batch {index} with {} rows", batch.num_rows());
+
+            let result = write_batch(&df, &channel_map, &path, &schema, batch, index).await;
+            let failed = result.is_err();
+            yield result;
+            if failed {
+                break;
             }
+            index += 1;
         }
-    });
+    };
 
     let timed_stream = TimedStream::new(response_stream, move || duration_metric);
 
     Ok(Response::new(Box::pin(timed_stream)))
 }
+
+/// Acknowledges a batch written with `DoPut`, in the `app_metadata` of its `PutResult`.
+#[derive(Debug, Serialize)]
+struct BatchWritten {
+    batch: usize,
+    rows: usize,
+}
+
+async fn write_batch(
+    df: &DataFusion,
+    channel_map: &Arc<RwLock<HashMap<TableReference, Arc<Sender<DataUpdate>>>>>,
+    path: &TableReference,
+    schema: &SchemaRef,
+    batch: RecordBatch,
+    index: usize,
+) -> Result<PutResult, Status> {
+    let rows = batch.num_rows();
+    let data_update = DataUpdate {
+        data: vec![batch],
+        schema: Arc::clone(schema),
+        update_type: UpdateType::Append,
+    };
+
+    df.write_data(path.clone(), data_update.clone())
+        .await
+        .map_err(|e| Status::internal(format!("Error writing batch {index}: {e}")))?;
+
+    // Subscribers only see the data once it is written.
+    if let Some(channel) = get_sender_channel(Arc::clone(channel_map), path).await {
+        let _ = channel.send(data_update);
+    };
+
+    let app_metadata =
+        serde_json::to_vec(&BatchWritten { batch: index, rows }).map_err(to_tonic_err)?;
+    Ok(PutResult {
+        app_metadata: app_metadata.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::datasource::MemTable;
+    use tokio::sync::broadcast;
+
+    use crate::datafusion::SPICE_RUNTIME_SCHEMA;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn acknowledges_written_batches_only() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .expect("valid batch");
+        let other_schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Utf8, false)]));
+        let other_batch = RecordBatch::try_new(
+            Arc::clone(&other_schema),
+            vec![Arc::new(StringArray::from(vec!["a"]))],
+        )
+        .expect("valid batch");
+
+        let df = DataFusion::new();
+        let path = TableReference::partial(SPICE_RUNTIME_SCHEMA, "do_put_test");
+        let table = MemTable::try_new(Arc::clone(&schema), vec![vec![]]).expect("valid table");
+        df.register_runtime_table(path.clone(), Arc::new(table))
+            .expect("registered table");
+        let (sender, mut receiver) = broadcast::channel(16);
+        let channel_map = Arc::new(RwLock::new(HashMap::from([(
+            path.clone(),
+            Arc::new(sender),
+        )])));
+
+        let written = write_batch(&df, &channel_map, &path, &schema, batch, 0)
+            .await
+            .expect("written batch");
+        let ack: serde_json::Value =
+            serde_json::from_slice(&written.app_metadata).expect("JSON acknowledgement");
+        assert_eq!(ack, serde_json::json!({"batch": 0, "rows": 3}));
+        assert_eq!(receiver.try_recv().expect("update").data[0].num_rows(), 3);
+
+        let failed = write_batch(&df, &channel_map, &path, &other_schema, other_batch, 1)
+            .await
+            .expect_err("mismatched batch");
+        assert!(failed.message().contains("batch 1"));
+        assert!(receiver.try_recv().is_err());
+    }
+}
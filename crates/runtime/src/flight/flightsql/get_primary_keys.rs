@@ -17,7 +17,7 @@ limitations under the License.
 use std::sync::Arc;
 
 use arrow::{
-    array::RecordBatch,
+    array::{ArrayRef, Int32Builder, RecordBatch, StringBuilder},
     datatypes::{DataType, Field, Schema},
 };
 use arrow_flight::{
@@ -26,7 +26,8 @@ use arrow_flight::{
 use tonic::{Request, Response, Status};
 
 use crate::{
-    flight::{record_batches_to_flight_stream, Service},
+    datafusion::information_schema::key_constraints,
+    flight::{record_batches_to_flight_stream, to_tonic_err, Service},
     timing::{TimeMeasurement, TimedStream},
 };
 
@@ -54,9 +55,8 @@ pub(crate) fn get_flight_info(
 ///   `column_name`: utf8 not null,
 ///   `key_name`: utf8,
 ///   `key_sequence`: int32 not null
-#[allow(clippy::unnecessary_wraps)]
-pub(crate) fn do_get(
-    _flight_svc: &Service,
+pub(crate) async fn do_get(
+    flight_svc: &Service,
     query: &sql::CommandGetPrimaryKeys,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let start = TimeMeasurement::new("flight_do_get_get_primary_keys_duration_ms", vec![]);
@@ -71,7 +71,47 @@ pub(crate) fn do_get(
         Field::new("key_sequence", DataType::Int32, false),
     ]));
 
-    let record_batch = RecordBatch::new_empty(Arc::clone(&schema));
+    let state = flight_svc.datafusion.ctx.state();
+    let primary_keys = key_constraints(state.catalog_list().as_ref())
+        .await
+        .map_err(to_tonic_err)?
+        .into_iter()
+        .filter(|key| {
+            key.primary_key
+                && key.table == query.table
+                && query.catalog.as_ref().map_or(true, |c| *c == key.catalog)
+                && query.db_schema.as_ref().map_or(true, |s| *s == key.schema)
+        });
+
+    let mut catalogs = StringBuilder::new();
+    let mut schemas = StringBuilder::new();
+    let mut tables = StringBuilder::new();
+    let mut columns = StringBuilder::new();
+    let mut key_names = StringBuilder::new();
+    let mut key_sequences = Int32Builder::new();
+    for key in primary_keys {
+        for (sequence, column) in (1..).zip(&key.columns) {
+            catalogs.append_value(&key.catalog);
+            schemas.append_value(&key.schema);
+            tables.append_value(&key.table);
+            columns.append_value(column);
+            key_names.append_value(&key.name);
+            key_sequences.append_value(sequence);
+        }
+    }
+
+    let record_batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(catalogs.finish()) as ArrayRef,
+            Arc::new(schemas.finish()),
+            Arc::new(tables.finish()),
+            Arc::new(columns.finish()),
+            Arc::new(key_names.finish()),
+            Arc::new(key_sequences.finish()),
+        ],
+    )
+    .map_err(to_tonic_err)?;
 
     Ok(Response::new(Box::pin(TimedStream::new(
         record_batches_to_flight_stream(vec![record_batch]),
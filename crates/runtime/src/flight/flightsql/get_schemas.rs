@@ -31,7 +31,7 @@ use crate::{
 pub(crate) fn get_flight_info(
     query: &sql::CommandGetDbSchemas,
     request: Request<FlightDescriptor>,
-) -> Response<FlightInfo> {
+) -> Result<Response<FlightInfo>, Status> {
     tracing::trace!("get_flight_info");
     let fd = request.into_inner();
 
@@ -41,9 +41,11 @@ pub(crate) fn get_flight_info(
 
     let info = FlightInfo::new()
         .with_endpoint(endpoint)
+        .try_with_schema(&query.clone().into_builder().schema())
+        .map_err(to_tonic_err)?
         .with_descriptor(fd);
 
-    Response::new(info)
+    Ok(Response::new(info))
 }
 
 pub(crate) fn do_get(
@@ -28,17 +28,21 @@ use crate::{
 pub(crate) fn get_flight_info(
     query: &sql::CommandGetTables,
     request: Request<FlightDescriptor>,
-) -> Response<FlightInfo> {
+) -> Result<Response<FlightInfo>, Status> {
     let fd = request.into_inner();
     tracing::trace!("get_flight_info: {query:?}");
-    Response::new(FlightInfo {
+    let info = FlightInfo {
         flight_descriptor: Some(fd.clone()),
         endpoint: vec![FlightEndpoint {
             ticket: Some(Ticket { ticket: fd.cmd }),
             ..Default::default()
         }],
         ..Default::default()
-    })
+    }
+    // Clients such as ADBC read the result schema, which depends on `include_schema`, from the `FlightInfo`.
+    .try_with_schema(&query.clone().into_builder().schema())
+    .map_err(to_tonic_err)?;
+    Ok(Response::new(info))
 }
 
 pub(crate) async fn do_get(
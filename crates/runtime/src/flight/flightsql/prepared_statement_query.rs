@@ -14,41 +14,147 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::sync::Arc;
+use std::{io::Cursor, sync::Arc};
 
+use arrow::array::RecordBatch;
 use arrow_flight::{
     flight_service_server::FlightService,
     sql::{self, ProstMessageExt},
-    FlightDescriptor, FlightEndpoint, FlightInfo, Ticket,
+    FlightDescriptor, FlightEndpoint, FlightInfo, PutResult, Ticket,
 };
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter, CompressionType};
+use base64::{engine::general_purpose, Engine};
+use datafusion::{common::ParamValues, scalar::ScalarValue};
 use prost::Message;
+use serde::{Deserialize, Serialize};
 use tonic::{Request, Response, Status};
 
 use crate::{
-    flight::{flight_utils::attach_cache_metadata, to_tonic_err, Service},
+    auth::Principal,
+    datafusion::query::{labels::QueryLabels, Protocol, QueryBuilder},
+    flight::{flight_utils::attach_cache_metadata, handle_datafusion_error, to_tonic_err, Service},
     timing::{TimeMeasurement, TimedStream},
 };
 
+/// The handle of a prepared statement with parameters bound to it by `DoPut`. The handle of a prepared statement
+/// without parameters is its SQL.
+#[derive(Debug, Serialize, Deserialize)]
+struct BoundStatementHandle {
+    query: String,
+    /// The row of parameter values, as a base64 encoded Arrow IPC stream.
+    parameters: String,
+}
+
+/// Decodes the SQL of a prepared statement handle and the parameter values bound to it, if any.
+pub(crate) fn decode_handle(handle: &[u8]) -> Result<(String, Option<ParamValues>), Status> {
+    if let Ok(bound) = serde_json::from_slice::<BoundStatementHandle>(handle) {
+        let ipc = general_purpose::STANDARD
+            .decode(&bound.parameters)
+            .map_err(|e| {
+                Status::invalid_argument(format!("Invalid prepared statement handle: {e}"))
+            })?;
+        let batches = StreamReader::try_new(Cursor::new(ipc), None)
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(|e| {
+                Status::invalid_argument(format!("Invalid prepared statement handle: {e}"))
+            })?;
+        let parameters = parameter_row(&batches)?;
+        let values = parameters
+            .columns()
+            .iter()
+            .map(|column| ScalarValue::try_from_array(column, 0))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_tonic_err)?;
+        return Ok((bound.query, Some(ParamValues::List(values))));
+    }
+
+    match std::str::from_utf8(handle) {
+        Ok(sql) => Ok((sql.to_string(), None)),
+        Err(e) => Err(Status::invalid_argument(format!(
+            "Invalid prepared statement handle: {e}"
+        ))),
+    }
+}
+
+/// The single row of parameter values bound to a query.
+fn parameter_row(batches: &[RecordBatch]) -> Result<&RecordBatch, Status> {
+    let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+    match batches.iter().find(|batch| batch.num_rows() > 0) {
+        Some(batch) if rows == 1 => Ok(batch),
+        _ => Err(Status::invalid_argument(format!(
+            "A query takes a single row of parameters, but {rows} rows were bound"
+        ))),
+    }
+}
+
 /// Create a prepared statement from given SQL statement.
 pub(crate) async fn do_action_create_prepared_statement(
     flight_svc: &Service,
     statement: sql::ActionCreatePreparedStatementRequest,
+    principal: Option<Principal>,
 ) -> Result<sql::ActionCreatePreparedStatementResult, Status> {
     tracing::trace!("do_action_create_prepared_statement: {statement:?}");
-    let arrow_schema =
-        Service::get_arrow_schema(Arc::clone(&flight_svc.datafusion), statement.query.clone())
-            .await
-            .map_err(to_tonic_err)?;
+    let arrow_schema = Service::get_arrow_schema(
+        Arc::clone(&flight_svc.datafusion),
+        statement.query.clone(),
+        None,
+        principal.clone(),
+    )
+    .await
+    .map_err(to_tonic_err)?;
+
+    let parameter_schema = QueryBuilder::new(
+        statement.query.clone(),
+        Arc::clone(&flight_svc.datafusion),
+        Protocol::Flight,
+    )
+    .principal(principal)
+    .build()
+    .get_parameter_schema()
+    .await
+    .map_err(handle_datafusion_error)?;
 
     let schema_bytes = Service::serialize_schema(&arrow_schema)?;
+    let parameter_schema_bytes = Service::serialize_schema(&parameter_schema)?;
 
     Ok(sql::ActionCreatePreparedStatementResult {
         prepared_statement_handle: statement.query.into(),
         dataset_schema: schema_bytes,
-        ..Default::default()
+        parameter_schema: parameter_schema_bytes,
     })
 }
 
+/// Binds the row of parameters sent with `DoPut` to a prepared statement, returning the handle of the statement with
+/// the parameters bound in a `DoPutPreparedStatementResult`.
+pub(crate) fn do_put(
+    query: &sql::CommandPreparedStatementQuery,
+    parameters: &[RecordBatch],
+) -> Result<Response<<Service as FlightService>::DoPutStream>, Status> {
+    tracing::trace!("do_put: {query:?}");
+    let (sql, _) = decode_handle(&query.prepared_statement_handle)?;
+    let parameters = parameter_row(parameters)?;
+
+    let mut writer =
+        StreamWriter::try_new(Vec::new(), &parameters.schema()).map_err(to_tonic_err)?;
+    writer.write(parameters).map_err(to_tonic_err)?;
+    let ipc = writer.into_inner().map_err(to_tonic_err)?;
+
+    let handle = serde_json::to_vec(&BoundStatementHandle {
+        query: sql,
+        parameters: general_purpose::STANDARD.encode(ipc),
+    })
+    .map_err(to_tonic_err)?;
+    let result = sql::DoPutPreparedStatementResult {
+        prepared_statement_handle: Some(handle.into()),
+    };
+
+    Ok(Response::new(Box::pin(futures::stream::iter(vec![Ok(
+        PutResult {
+            app_metadata: result.encode_to_vec().into(),
+        },
+    )]))))
+}
+
 pub(crate) async fn get_flight_info(
     flight_svc: &Service,
     handle: sql::CommandPreparedStatementQuery,
@@ -56,18 +162,17 @@ pub(crate) async fn get_flight_info(
 ) -> Result<Response<FlightInfo>, Status> {
     tracing::trace!("get_flight_info: {handle:?}");
 
-    let sql = match std::str::from_utf8(&handle.prepared_statement_handle) {
-        Ok(sql) => sql.to_string(),
-        Err(e) => {
-            return Err(Status::invalid_argument(format!(
-                "Invalid prepared statement handle: {e}"
-            )))
-        }
-    };
+    let (sql, parameters) = decode_handle(&handle.prepared_statement_handle)?;
 
-    let arrow_schema = Service::get_arrow_schema(Arc::clone(&flight_svc.datafusion), sql)
-        .await
-        .map_err(to_tonic_err)?;
+    let principal = request.extensions().get::<Principal>().cloned();
+    let arrow_schema = Service::get_arrow_schema(
+        Arc::clone(&flight_svc.datafusion),
+        sql,
+        parameters,
+        principal,
+    )
+    .await
+    .map_err(to_tonic_err)?;
 
     tracing::trace!("get_flight_info_prepared_statement: arrow_schema={arrow_schema:?}");
 
@@ -89,24 +194,70 @@ pub(crate) async fn get_flight_info(
 pub(crate) async fn do_get(
     flight_svc: &Service,
     query: sql::CommandPreparedStatementQuery,
+    principal: Option<Principal>,
+    labels: QueryLabels,
+    compression: Option<CompressionType>,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let datafusion = Arc::clone(&flight_svc.datafusion);
     tracing::trace!("do_get: {query:?}");
-    match std::str::from_utf8(&query.prepared_statement_handle) {
-        Ok(sql) => {
-            let start =
-                TimeMeasurement::new("flight_do_get_prepared_statement_query_duration_ms", vec![]);
-            let (output, from_cache) =
-                Box::pin(Service::sql_to_flight_stream(datafusion, sql.to_owned())).await?;
-            let timed_output = TimedStream::new(output, move || start);
-
-            let mut response =
-                Response::new(Box::pin(timed_output) as <Service as FlightService>::DoGetStream);
-            attach_cache_metadata(&mut response, from_cache);
-            Ok(response)
-        }
-        Err(e) => Err(Status::invalid_argument(format!(
-            "Invalid prepared statement handle: {e}"
-        ))),
+    let (sql, parameters) = decode_handle(&query.prepared_statement_handle)?;
+
+    let start = TimeMeasurement::new("flight_do_get_prepared_statement_query_duration_ms", vec![]);
+    let (output, from_cache) = Box::pin(Service::sql_to_flight_stream(
+        datafusion,
+        sql,
+        parameters,
+        None,
+        principal,
+        labels,
+        compression,
+    ))
+    .await?;
+    let timed_output = TimedStream::new(output, move || start);
+
+    let mut response =
+        Response::new(Box::pin(timed_output) as <Service as FlightService>::DoGetStream);
+    attach_cache_metadata(&mut response, from_cache);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{ArrayRef, Int64Array};
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn binds_parameters_to_handle() {
+        let query = sql::CommandPreparedStatementQuery {
+            prepared_statement_handle: "SELECT $1".into(),
+        };
+        let parameters = |values: Vec<i64>| {
+            RecordBatch::try_from_iter([("$1", Arc::new(Int64Array::from(values)) as ArrayRef)])
+                .expect("valid batch")
+        };
+
+        assert!(do_put(&query, &[parameters(vec![1, 2])]).is_err());
+
+        let result = do_put(&query, &[parameters(vec![42])])
+            .expect("parameters are bound")
+            .into_inner()
+            .next()
+            .await
+            .expect("a result")
+            .expect("a result");
+        let bound = sql::DoPutPreparedStatementResult::decode(result.app_metadata)
+            .expect("valid result")
+            .prepared_statement_handle
+            .expect("a handle");
+
+        let (sql, parameters) = decode_handle(&bound).expect("valid handle");
+        assert_eq!(sql, "SELECT $1");
+        let Some(ParamValues::List(values)) = parameters else {
+            panic!("expected positional parameters");
+        };
+        assert_eq!(values, vec![ScalarValue::Int64(Some(42))]);
+        assert!(matches!(decode_handle(b"SELECT 1"), Ok((_, None))));
     }
 }
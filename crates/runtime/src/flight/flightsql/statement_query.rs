@@ -21,14 +21,27 @@ use arrow_flight::{
     sql::{self, ProstMessageExt},
     FlightDescriptor, FlightEndpoint, FlightInfo, Ticket,
 };
+use arrow_ipc::CompressionType;
 use prost::Message;
+use serde::{Deserialize, Serialize};
 use tonic::{Request, Response, Status};
 
 use crate::{
-    flight::{flight_utils::attach_cache_metadata, to_tonic_err, Service},
+    auth::Principal,
+    datafusion::query::{labels::QueryLabels, Protocol, QueryBuilder, QueryPartitions},
+    flight::{flight_utils::attach_cache_metadata, handle_datafusion_error, to_tonic_err, Service},
     timing::{TimeMeasurement, TimedStream},
 };
 
+/// The statement handle of a `TicketStatementQuery`, identifying which output partitions
+/// of a query are served by a single `FlightEndpoint`.
+#[derive(Debug, Serialize, Deserialize)]
+struct PartitionedStatementHandle {
+    query: String,
+    partition_count: usize,
+    partitions: Vec<usize>,
+}
+
 /// Get a `FlightInfo` for executing a SQL query.
 pub(crate) async fn get_flight_info(
     flight_svc: &Service,
@@ -38,36 +51,147 @@ pub(crate) async fn get_flight_info(
     tracing::trace!("get_flight_info: {query:?}");
 
     let sql = query.query.as_str();
+    let principal = request.extensions().get::<Principal>().cloned();
 
-    let arrow_schema =
-        Service::get_arrow_schema(Arc::clone(&flight_svc.datafusion), sql.to_string())
-            .await
-            .map_err(to_tonic_err)?;
+    let arrow_schema = Service::get_arrow_schema(
+        Arc::clone(&flight_svc.datafusion),
+        sql.to_string(),
+        None,
+        principal.clone(),
+    )
+    .await
+    .map_err(to_tonic_err)?;
 
     let fd = request.into_inner();
 
-    let endpoint = FlightEndpoint::new().with_ticket(Ticket {
-        ticket: query.as_any().encode_to_vec().into(),
-    });
+    let endpoints = match partitioned_endpoints(flight_svc, sql, principal).await? {
+        Some(endpoints) => endpoints,
+        None => vec![FlightEndpoint::new().with_ticket(Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        })],
+    };
 
-    let info = FlightInfo::new()
-        .with_endpoint(endpoint)
+    let mut info = FlightInfo::new()
         .try_with_schema(&arrow_schema)
         .map_err(to_tonic_err)?
         .with_descriptor(fd);
+    for endpoint in endpoints {
+        info = info.with_endpoint(endpoint);
+    }
 
     Ok(Response::new(info))
 }
 
+/// Splits the output partitions of the query plan across up to `max_endpoints` endpoints, so that clients
+/// can fetch a large result in parallel streams.
+///
+/// Returns `None` if the result should be served from a single endpoint.
+async fn partitioned_endpoints(
+    flight_svc: &Service,
+    sql: &str,
+    principal: Option<Principal>,
+) -> Result<Option<Vec<FlightEndpoint>>, Status> {
+    if flight_svc.max_endpoints <= 1 {
+        return Ok(None);
+    }
+
+    let partition_count = QueryBuilder::new(
+        sql.to_string(),
+        Arc::clone(&flight_svc.datafusion),
+        Protocol::Flight,
+    )
+    .principal(principal)
+    .build()
+    .get_output_partition_count()
+    .await
+    .map_err(handle_datafusion_error)?;
+
+    if partition_count <= 1 {
+        return Ok(None);
+    }
+
+    let num_endpoints = partition_count.min(flight_svc.max_endpoints);
+    let mut endpoints = Vec::with_capacity(num_endpoints);
+    for endpoint_idx in 0..num_endpoints {
+        let handle = PartitionedStatementHandle {
+            query: sql.to_string(),
+            partition_count,
+            partitions: (endpoint_idx..partition_count)
+                .step_by(num_endpoints)
+                .collect(),
+        };
+        let statement_handle = serde_json::to_vec(&handle).map_err(to_tonic_err)?;
+        let ticket = sql::TicketStatementQuery {
+            statement_handle: statement_handle.into(),
+        };
+
+        endpoints.push(FlightEndpoint::new().with_ticket(Ticket {
+            ticket: ticket.as_any().encode_to_vec().into(),
+        }));
+    }
+
+    tracing::trace!("Partitioned query into {num_endpoints} endpoints: {sql}");
+
+    Ok(Some(endpoints))
+}
+
 pub(crate) async fn do_get(
     flight_svc: &Service,
     cmd: sql::CommandStatementQuery,
+    principal: Option<Principal>,
+    labels: QueryLabels,
+    compression: Option<CompressionType>,
 ) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
     let datafusion = Arc::clone(&flight_svc.datafusion);
     tracing::trace!("do_get_statement: {cmd:?}");
     let start = TimeMeasurement::new("flight_do_get_statement_query_duration_ms", vec![]);
-    let (output, from_cache) =
-        Box::pin(Service::sql_to_flight_stream(datafusion, cmd.query)).await?;
+    let (output, from_cache) = Box::pin(Service::sql_to_flight_stream(
+        datafusion,
+        cmd.query,
+        None,
+        None,
+        principal,
+        labels,
+        compression,
+    ))
+    .await?;
+    let timed_output = TimedStream::new(output, move || start);
+
+    let mut response =
+        Response::new(Box::pin(timed_output) as <Service as FlightService>::DoGetStream);
+    attach_cache_metadata(&mut response, from_cache);
+    Ok(response)
+}
+
+/// Executes the partitions of a query referenced by a `TicketStatementQuery` returned from `get_flight_info`.
+pub(crate) async fn do_get_ticket(
+    flight_svc: &Service,
+    ticket: sql::TicketStatementQuery,
+    principal: Option<Principal>,
+    labels: QueryLabels,
+    compression: Option<CompressionType>,
+) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
+    let datafusion = Arc::clone(&flight_svc.datafusion);
+    tracing::trace!("do_get_ticket: {ticket:?}");
+
+    let handle: PartitionedStatementHandle = serde_json::from_slice(&ticket.statement_handle)
+        .map_err(|e| Status::invalid_argument(format!("Invalid statement handle: {e}")))?;
+
+    let start = TimeMeasurement::new("flight_do_get_statement_query_duration_ms", vec![]);
+    let partitions = QueryPartitions {
+        partition_count: handle.partition_count,
+        partitions: handle.partitions,
+    };
+    let (output, from_cache) = Box::pin(Service::sql_to_flight_stream(
+        datafusion,
+        handle.query,
+        None,
+        Some(partitions),
+        principal,
+        labels,
+        compression,
+    ))
+    .await?;
     let timed_output = TimedStream::new(output, move || start);
 
     let mut response =
@@ -39,14 +39,12 @@ pub(crate) async fn handle(
             flightsql::prepared_statement_query::get_flight_info(flight_svc, handle, request).await
         }
         Command::CommandGetCatalogs(token) => {
-            Ok(flightsql::get_catalogs::get_flight_info(&token, request))
+            flightsql::get_catalogs::get_flight_info(&token, request)
         }
         Command::CommandGetDbSchemas(token) => {
-            Ok(flightsql::get_schemas::get_flight_info(&token, request))
-        }
-        Command::CommandGetTables(token) => {
-            Ok(flightsql::get_tables::get_flight_info(&token, request))
+            flightsql::get_schemas::get_flight_info(&token, request)
         }
+        Command::CommandGetTables(token) => flightsql::get_tables::get_flight_info(&token, request),
         Command::CommandGetSqlInfo(token) => {
             flightsql::get_sql_info::get_flight_info(&token, request)
         }
@@ -0,0 +1,59 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use arrow_flight::{
+    sql::{Any, Command},
+    FlightDescriptor, SchemaAsIpc, SchemaResult,
+};
+use arrow_ipc::writer::IpcWriteOptions;
+use prost::Message;
+use tonic::{Request, Response, Status};
+
+use crate::auth::Principal;
+
+use super::{flightsql, to_tonic_err, Service};
+
+/// Returns the schema of the results of a Flight SQL query or prepared statement, without running it.
+pub(crate) async fn handle(
+    flight_svc: &Service,
+    request: Request<FlightDescriptor>,
+) -> Result<Response<SchemaResult>, Status> {
+    let principal = request.extensions().get::<Principal>().cloned();
+    let message = Any::decode(&*request.get_ref().cmd).map_err(to_tonic_err)?;
+
+    let (sql, parameters) = match Command::try_from(message).map_err(to_tonic_err)? {
+        Command::CommandStatementQuery(command) => (command.query, None),
+        Command::CommandPreparedStatementQuery(handle) => {
+            flightsql::prepared_statement_query::decode_handle(&handle.prepared_statement_handle)?
+        }
+        _ => return Err(Status::unimplemented("Not yet implemented")),
+    };
+
+    let schema = Service::get_arrow_schema(
+        Arc::clone(&flight_svc.datafusion),
+        sql,
+        parameters,
+        principal,
+    )
+    .await?;
+
+    let result = SchemaResult::try_from(SchemaAsIpc::new(&schema, &IpcWriteOptions::default()))
+        .map_err(to_tonic_err)?;
+
+    Ok(Response::new(result))
+}
@@ -0,0 +1,121 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use arrow_flight::{encode::FlightDataEncoderBuilder, error::FlightError, FlightService};
+use arrow_ipc::CompressionType;
+use datafusion::{
+    common::Column,
+    execution::context::SessionContext,
+    logical_expr::{lit, Expr},
+    sql::TableReference,
+};
+use futures::{StreamExt, TryStreamExt};
+use tonic::{Response, Status};
+
+use crate::{
+    accelerated_table::AcceleratedTable,
+    cluster::exchange::{self, ShardScan},
+    ipc_compression,
+};
+
+use super::{handle_datafusion_error, to_tonic_err, Service};
+
+/// Streams the rows of the acceleration of a dataset, for the scan of the dataset by another member of the cluster.
+///
+/// Only the members of the cluster can scan accelerations, and a scan only selects columns, filters rows and limits
+/// them: the policies of the dataset are applied by the member the query runs on. A scan of shards only returns the
+/// rows of the requested shards, and fails if this member doesn't accelerate all of them.
+pub(crate) async fn do_get(
+    flight_svc: &Service,
+    scan: &[u8],
+    peer: bool,
+    compression: Option<CompressionType>,
+) -> Result<Response<<Service as FlightService>::DoGetStream>, Status> {
+    if !peer {
+        return Err(Status::permission_denied(
+            "Accelerations are only scanned by the other members of the cluster",
+        ));
+    }
+    let scan = ShardScan::from_message(scan)
+        .map_err(|e| Status::invalid_argument(format!("Invalid shard scan: {e}")))?;
+    tracing::trace!("do_get_shard_scan: {scan:?}");
+
+    let table = flight_svc
+        .datafusion
+        .ctx
+        .table_provider(TableReference::parse_str(&scan.dataset))
+        .await
+        .map_err(|_| Status::not_found(format!("Dataset {} not found", scan.dataset)))?;
+    let Some(accelerated) = table.as_any().downcast_ref::<AcceleratedTable>() else {
+        return Err(Status::failed_precondition(format!(
+            "Dataset {} is not accelerated by this runtime",
+            scan.dataset
+        )));
+    };
+
+    if let Some(shards) = &scan.shards {
+        if !accelerated
+            .shards()
+            .is_some_and(|held| held.contains(shards))
+        {
+            return Err(Status::failed_precondition(format!(
+                "Shards {:?} of dataset {} are not accelerated by this runtime",
+                shards.ids, scan.dataset
+            )));
+        }
+    }
+
+    // Only the acceleration is scanned, so the scan doesn't request the shards of the other members in turn.
+    let ctx = SessionContext::new();
+    let mut df = ctx
+        .read_table(accelerated.get_accelerator())
+        .map_err(handle_datafusion_error)?;
+    // The acceleration can also have the rows of shards the member the query runs on requests from other members.
+    if let Some(shards) = &scan.shards {
+        df = df
+            .filter(shards.filter())
+            .map_err(handle_datafusion_error)?;
+    }
+    for filter in &scan.filters {
+        let filter = exchange::parse_filter(filter, df.schema())
+            .map_err(|e| Status::invalid_argument(format!("Invalid shard scan filter: {e}")))?;
+        df = df.filter(filter).map_err(handle_datafusion_error)?;
+    }
+    df = match &scan.columns {
+        // A scan without columns only counts rows.
+        Some(columns) if columns.is_empty() => df.select(vec![lit(1).alias("count")]),
+        Some(columns) => df.select(
+            columns
+                .iter()
+                .map(|column| Expr::Column(Column::new_unqualified(column)))
+                .collect(),
+        ),
+        None => Ok(df),
+    }
+    .map_err(handle_datafusion_error)?;
+    if let Some(limit) = scan.limit {
+        df = df.limit(0, Some(limit)).map_err(handle_datafusion_error)?;
+    }
+    let batches = df.execute_stream().await.map_err(handle_datafusion_error)?;
+
+    let flights = FlightDataEncoderBuilder::new()
+        .with_options(ipc_compression::write_options(compression))
+        .with_schema(batches.schema())
+        .build(batches.map_err(|e| FlightError::ExternalError(Box::new(e))))
+        .map_err(to_tonic_err);
+
+    Ok(Response::new(flights.boxed()))
+}
@@ -17,14 +17,25 @@ limitations under the License.
 use std::{collections::HashMap, fmt::Debug, net::SocketAddr, sync::Arc};
 
 use app::App;
+use axum::{routing::get, Router};
+use metrics_exporter_prometheus::PrometheusHandle;
 use model_components::model::Model;
 use snafu::prelude::*;
+use spicepod::component::runtime::AsyncQueries;
 use tokio::{
     net::{TcpListener, ToSocketAddrs},
     sync::RwLock,
 };
 
-use crate::{config, datafusion::DataFusion, model::LLMModelStore, EmbeddingModelStore};
+use crate::{
+    auth::OidcValidator,
+    config,
+    datafusion::{query::async_query::AsyncQueryStore, DataFusion},
+    model::LLMModelStore,
+    shutdown,
+    tls::{self, TlsConfig},
+    EmbeddingModelStore, Runtime,
+};
 
 mod routes;
 mod v1;
@@ -36,6 +47,14 @@ pub enum Error {
 
     #[snafu(display("Unable to start HTTP server: {source}"))]
     UnableToStartHttpServer { source: std::io::Error },
+
+    #[snafu(display("Unable to configure TLS: {source}"))]
+    UnableToConfigureTls { source: crate::tls::Error },
+
+    #[snafu(display("Unable to create async query store: {source}"))]
+    UnableToCreateAsyncQueryStore {
+        source: crate::datafusion::query::async_query::Error,
+    },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -50,11 +69,40 @@ pub(crate) async fn start<A>(
     embeddings: Arc<RwLock<EmbeddingModelStore>>,
     config: Arc<config::Config>,
     with_metrics: Option<SocketAddr>,
+    auth: Option<Arc<OidcValidator>>,
+    tls: Option<TlsConfig>,
+    runtime: Arc<Runtime>,
 ) -> Result<()>
 where
     A: ToSocketAddrs + Debug,
 {
-    let routes = routes::routes(app, df, models, llms, embeddings, config, with_metrics);
+    let async_queries_config = app
+        .read()
+        .await
+        .as_ref()
+        .map(|app| app.runtime.async_queries.clone())
+        .unwrap_or_default();
+    let async_queries = match AsyncQueryStore::try_new(&async_queries_config) {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::warn!("Invalid async query configuration, using defaults: {e}");
+            AsyncQueryStore::try_new(&AsyncQueries::default())
+                .context(UnableToCreateAsyncQueryStoreSnafu)?
+        }
+    };
+
+    let routes = routes::routes(
+        app,
+        df,
+        models,
+        llms,
+        embeddings,
+        config,
+        with_metrics,
+        Arc::new(async_queries),
+        auth,
+        runtime,
+    );
 
     let listener = TcpListener::bind(&bind_address)
         .await
@@ -63,8 +111,46 @@ where
 
     metrics::counter!("spiced_runtime_http_server_start").increment(1);
 
-    axum::serve(listener, routes)
+    match tls {
+        Some(tls) => {
+            let server_config = tls.server_config().context(UnableToConfigureTlsSnafu)?;
+            tls::serve(listener, routes, server_config).await;
+        }
+        None => axum::serve(listener, routes)
+            .with_graceful_shutdown(shutdown::drained())
+            .await
+            .context(UnableToStartHttpServerSnafu)?,
+    }
+    Ok(())
+}
+
+/// Serves the Prometheus metrics recorded by the runtime on `/metrics`.
+pub(crate) async fn start_metrics<A>(
+    bind_address: A,
+    handle: PrometheusHandle,
+    tls: Option<TlsConfig>,
+) -> Result<()>
+where
+    A: ToSocketAddrs + Debug,
+{
+    let routes = Router::new()
+        .route("/health", get(|| async { "OK" }))
+        .route("/metrics", get(move || std::future::ready(handle.render())));
+
+    let listener = TcpListener::bind(&bind_address)
         .await
-        .context(UnableToStartHttpServerSnafu)?;
+        .context(UnableToBindServerToPortSnafu)?;
+    tracing::info!("Metrics listening on {bind_address:?}");
+
+    match tls {
+        Some(tls) => {
+            let server_config = tls.server_config().context(UnableToConfigureTlsSnafu)?;
+            tls::serve(listener, routes, server_config).await;
+        }
+        None => axum::serve(listener, routes)
+            .with_graceful_shutdown(shutdown::drained())
+            .await
+            .context(UnableToStartHttpServerSnafu)?,
+    }
     Ok(())
 }
@@ -14,25 +14,29 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use crate::auth::{OidcValidator, Principal};
+use crate::datafusion::query::async_query::AsyncQueryStore;
 use crate::model::LLMModelStore;
-use crate::EmbeddingModelStore;
+use crate::tracing_util;
 use crate::{config, datafusion::DataFusion};
+use crate::{EmbeddingModelStore, Runtime};
 use app::App;
-use axum::routing::patch;
+use axum::routing::{patch, put};
 use model_components::model::Model;
 use std::net::SocketAddr;
 use std::{collections::HashMap, sync::Arc};
 
 use axum::{
     body::Body,
-    extract::MatchedPath,
-    http::Request,
+    extract::{MatchedPath, State},
+    http::{header, Request, StatusCode},
     middleware::{self, Next},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     routing::{get, post, Router},
     Extension,
 };
 use tokio::{sync::RwLock, time::Instant};
+use tracing::Instrument;
 
 use super::v1;
 
@@ -45,21 +49,82 @@ pub(crate) fn routes(
     embeddings: Arc<RwLock<EmbeddingModelStore>>,
     config: Arc<config::Config>,
     with_metrics: Option<SocketAddr>,
+    async_queries: Arc<AsyncQueryStore>,
+    auth: Option<Arc<OidcValidator>>,
+    runtime: Arc<Runtime>,
 ) -> Router {
     let mut router = Router::new()
         .route("/health", get(|| async { "ok\n" }))
+        .route("/health/live", get(|| async { "ok\n" }))
+        .route("/health/ready", get(v1::ready::get))
         .route("/v1/sql", post(v1::query::post))
+        .route("/v1/queries/:id", get(v1::queries::get))
+        .route("/v1/queries/:id/results", get(v1::queries::results))
+        .route("/v1/watch", get(v1::watch::get))
         .route("/v1/status", get(v1::status::get))
-        .route("/v1/datasets", get(v1::datasets::get))
+        .route("/v1/cluster", get(v1::cluster::get))
+        .route(
+            "/v1/datasets",
+            post(v1::datasets::create)
+                .route_layer(middleware::from_fn(require_admin))
+                .get(v1::datasets::get),
+        )
+        .route(
+            "/v1/datasets/:name",
+            put(v1::datasets::replace)
+                .delete(v1::datasets::delete)
+                .route_layer(middleware::from_fn(require_admin)),
+        )
+        .route("/v1/datasets/:name/sample", get(v1::datasets::sample))
+        .route(
+            "/v1/datasets/:name/metadata/invalidate",
+            post(v1::datasets::invalidate_listing).route_layer(middleware::from_fn(require_admin)),
+        )
         .route(
             "/v1/datasets/:name/acceleration/refresh",
-            post(v1::datasets::refresh),
+            post(v1::datasets::refresh).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            "/v1/datasets/:name/acceleration/refresh/:job_id",
+            get(v1::datasets::refresh_progress),
         )
         .route(
             "/v1/datasets/:name/acceleration",
-            patch(v1::datasets::acceleration),
+            patch(v1::datasets::acceleration).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            "/v1/datasets/:name/acceleration/export",
+            get(v1::datasets::export_acceleration).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            "/v1/datasets/:name/acceleration/import",
+            post(v1::datasets::import_acceleration).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            "/v1/datasets/:name/ingest",
+            post(v1::datasets::ingest).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            "/v1/refresh",
+            post(v1::refresh::post)
+                .route_layer(middleware::from_fn(require_admin))
+                .get(v1::refresh::get),
+        )
+        .route(
+            "/v1/refresh/pause",
+            post(v1::refresh::pause).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            "/v1/refresh/resume",
+            post(v1::refresh::resume).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route("/v1/lineage", get(v1::lineage::get))
+        .route(
+            "/v1/tests/run",
+            post(v1::tests::run).route_layer(middleware::from_fn(require_admin)),
         )
         .route("/v1/spicepods", get(v1::spicepods::get))
+        .route("/v1/spicepods/validate", get(v1::spicepods::validate))
         .route("/v1/ready", get(v1::ready::get))
         .route_layer(middleware::from_fn(track_metrics));
 
@@ -77,14 +142,109 @@ pub(crate) fn routes(
             .layer(Extension(embeddings));
     }
 
+    if let Some(auth) = auth {
+        router = router.layer(middleware::from_fn_with_state(auth, authenticate));
+    }
+
     router = router
+        .layer(middleware::from_fn(trace_request))
         .layer(Extension(app))
         .layer(Extension(df))
         .layer(Extension(with_metrics))
-        .layer(Extension(config));
+        .layer(Extension(async_queries))
+        .layer(Extension(config))
+        .layer(Extension(runtime));
     router
 }
 
+/// Rejects requests without a valid bearer token, and makes the token's [`Principal`](crate::auth::Principal)
+/// available to handlers as a request extension.
+async fn authenticate(
+    State(auth): State<Arc<OidcValidator>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    if matches!(
+        req.uri().path(),
+        "/health" | "/health/live" | "/health/ready"
+    ) {
+        return next.run(req).await;
+    }
+
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    match auth.validate_authorization(authorization) {
+        Ok(principal) => {
+            req.extensions_mut().insert(principal);
+            next.run(req).await
+        }
+        Err(e) => {
+            tracing::debug!(
+                "Rejected unauthenticated request to {}: {e}",
+                req.uri().path()
+            );
+            metrics::counter!("http_requests_unauthenticated").increment(1);
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                e.to_string(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Rejects the requests of principals without the admin claim, for the routes that change datasets or the runtime,
+/// or export data without the policies of the principal. Without authentication, there is no principal to check.
+async fn require_admin(
+    principal: Option<Extension<Principal>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    match check_admin(principal.as_deref()) {
+        Ok(()) => next.run(req).await,
+        Err(message) => {
+            tracing::debug!("Rejected request to {}: {message}", req.uri().path());
+            (StatusCode::FORBIDDEN, message).into_response()
+        }
+    }
+}
+
+fn check_admin(principal: Option<&Principal>) -> Result<(), String> {
+    match principal {
+        Some(principal) if !principal.admin => Err(format!(
+            "{} is not an admin. Admin access is granted by runtime.auth.oidc.admin.",
+            principal.subject
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Runs each request in a span, continuing the trace of the caller when it sends a `traceparent` header.
+async fn trace_request(req: Request<Body>, next: Next) -> Response {
+    let span = tracing::info_span!(
+        "http_request",
+        otel.name = %format!("{} {}", req.method(), req.uri().path()),
+        otel.kind = "server",
+        http.method = %req.method(),
+        http.route = %req.uri().path(),
+        http.status_code = tracing::field::Empty,
+    );
+    tracing_util::set_remote_parent(&span, |name| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    });
+
+    let response = next.run(req).instrument(span.clone()).await;
+    span.record("http.status_code", response.status().as_u16());
+
+    response
+}
+
 async fn track_metrics(req: Request<Body>, next: Next) -> impl IntoResponse {
     let start = Instant::now();
     let path = if let Some(matched_path) = req.extensions().get::<MatchedPath>() {
@@ -110,3 +270,23 @@ async fn track_metrics(req: Request<Body>, next: Next) -> impl IntoResponse {
 
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+
+    use super::*;
+
+    #[test]
+    fn test_check_admin() {
+        let principal = |admin| Principal {
+            subject: "alice".to_string(),
+            claims: Map::new(),
+            admin,
+        };
+        assert!(check_admin(Some(&principal(true))).is_ok());
+        assert!(check_admin(Some(&principal(false))).is_err());
+        // Without authentication, every caller can use the admin routes, as every other route.
+        assert!(check_admin(None).is_ok());
+    }
+}
@@ -34,13 +34,17 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
-use tracing::instrument;
+use tracing::{instrument, Instrument};
 
-use futures::StreamExt;
+use futures::{StreamExt, TryStreamExt};
 
 use crate::{
-    accelerated_table::AcceleratedTable, datafusion::DataFusion, embeddings::table::EmbeddingTable,
-    model::LLMModelStore, EmbeddingModelStore,
+    accelerated_table::AcceleratedTable,
+    auth::Principal,
+    datafusion::{query::Protocol, DataFusion},
+    embeddings::table::EmbeddingTable,
+    model::LLMModelStore,
+    EmbeddingModelStore,
 };
 
 pub(crate) struct VectorSearchResponse {
@@ -189,6 +193,7 @@ async fn find_relevant_embedding_models(
 /// `table_primary_keys`: Optional for each [`TableReference`], the primary keys of the table. If not
 ///     provided, only the column underlying the embedding column will be returned.
 /// n: The number of results to return, per [`TableReference`].
+/// principal: The caller, whose access policies restrict the rows and columns searched.
 ///
 /// ## Limitations
 /// - Only supports one embedding column per table.
@@ -197,6 +202,7 @@ async fn vector_search(
     embedded_inputs: HashMap<TableReference, Vec<Vec<f32>>>,
     table_primary_keys: HashMap<TableReference, Vec<String>>,
     n: usize,
+    principal: Option<&Principal>,
 ) -> Result<VectorSearchResponse, Box<dyn std::error::Error>> {
     let mut response = VectorSearchResponse {
         retrieved_entries: HashMap::new(),
@@ -228,8 +234,15 @@ async fn vector_search(
                     "SELECT {} FROM {tbl} ORDER BY array_distance({embedding_column}_embedding, {embedding:?}) LIMIT {n}", select_keys.join(", ")
                 );
 
-                let result = df.ctx.sql(&sql_query).await?;
-                let batch = result.collect().await?;
+                let batch = df
+                    .query_builder(sql_query, Protocol::Http)
+                    .principal(principal.cloned())
+                    .build()
+                    .run()
+                    .await?
+                    .data
+                    .try_collect::<Vec<_>>()
+                    .await?;
 
                 let outt: Vec<_> = batch
                     .iter()
@@ -308,6 +321,7 @@ async fn prepare_and_run_vector_search(
     df: Arc<DataFusion>,
     embeddings: Arc<RwLock<EmbeddingModelStore>>,
     payload: Request,
+    principal: Option<&Principal>,
 ) -> Result<VectorSearchResponse, Box<dyn std::error::Error>> {
     let input_tables: Vec<TableReference> = payload
         .data_source
@@ -336,7 +350,14 @@ async fn prepare_and_run_vector_search(
     .await?;
 
     // Get relevant data from data sources.
-    vector_search(Arc::clone(&df), per_table_embeddings, tbl_to_pks, 3).await
+    vector_search(
+        Arc::clone(&df),
+        per_table_embeddings,
+        tbl_to_pks,
+        3,
+        principal,
+    )
+    .await
 }
 
 /// For each embedding column that a [`TableReference`] contains, calculate the embeddings vector between the query and the column.
@@ -463,7 +484,13 @@ async fn context_aware_chat(
     vector_search_data: &VectorSearchResponse,
     model_input: String,
 ) -> Response {
-    match model.write().await.run(model_input).await {
+    match model
+        .write()
+        .await
+        .run(model_input)
+        .instrument(tracing::info_span!("llm_inference"))
+        .await
+    {
         Ok(Some(assist)) => {
             match create_assist_response(assist, &vector_search_data.retrieved_public_keys) {
                 Ok(assist_response) => (StatusCode::OK, Json(assist_response)).into_response(),
@@ -522,9 +549,12 @@ pub(crate) async fn post(
     Extension(df): Extension<Arc<DataFusion>>,
     Extension(embeddings): Extension<Arc<RwLock<EmbeddingModelStore>>>,
     Extension(llms): Extension<Arc<RwLock<LLMModelStore>>>,
+    principal: Option<Extension<Principal>>,
     Query(params): Query<QueryParams>,
     Json(payload): Json<Request>,
 ) -> Response {
+    let principal = principal.map(|Extension(principal)| principal);
+
     // For now, force the user to specify which data.
     if payload.data_source.is_empty() {
         return (StatusCode::BAD_REQUEST, "No data sources provided").into_response();
@@ -535,6 +565,7 @@ pub(crate) async fn post(
         Arc::clone(&df),
         Arc::clone(&embeddings),
         payload.clone(),
+        principal.as_ref(),
     )
     .await
     {
@@ -14,28 +14,192 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
-use async_openai::types::CreateChatCompletionRequest;
+use app::App;
+use async_openai::types::{CreateChatCompletionRequest, CreateChatCompletionResponse};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use chrono::Utc;
 use tokio::sync::RwLock;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    audit::AuditAction,
+    auth::Principal,
+    datafusion::DataFusion,
+    deployments::{self, Outcome, Route, Variant},
+    inference_log::{InferenceKind, InferenceRecord},
+    model::{record_token_usage, LLMModelStore},
+    quotas, Runtime,
+};
 
-use crate::model::LLMModelStore;
+use super::{acquire_rate_limit, audit, check_llm_token_quota, record_rate_limited_tokens};
 
 pub(crate) async fn post(
+    Extension(rt): Extension<Arc<Runtime>>,
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
     Extension(llms): Extension<Arc<RwLock<LLMModelStore>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
     Json(req): Json<CreateChatCompletionRequest>,
 ) -> Response {
     let model_id = req.model.clone();
-    match llms.read().await.get(&model_id) {
-        Some(model) => match model.write().await.chat_request(req).await {
-            Ok(response) => Json(response).into_response(),
-            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-        },
-        None => StatusCode::NOT_FOUND.into_response(),
+    if let Err(response) = check_llm_token_quota(principal.as_deref()) {
+        audit(
+            &df,
+            principal.as_deref(),
+            AuditAction::ChatCompletion,
+            &model_id,
+            Some("LLM token quota exceeded".to_string()),
+        );
+        return response;
+    }
+    if let Err(response) = acquire_rate_limit(&rt, principal.as_deref(), &model_id).await {
+        audit(
+            &df,
+            principal.as_deref(),
+            AuditAction::ChatCompletion,
+            &model_id,
+            Some("Rate limit exceeded".to_string()),
+        );
+        return response;
+    }
+    let route = Route::new(app.read().await.as_ref(), &model_id);
+
+    let time = SystemTime::now();
+    let start = Instant::now();
+    let result = chat(&df, &llms, &route.served.model, req.clone()).await;
+    let (response, error) = match &result {
+        Ok(response) => {
+            if let Some(usage) = &response.usage {
+                record_rate_limited_tokens(
+                    &rt,
+                    principal.as_deref(),
+                    &model_id,
+                    usage.total_tokens,
+                );
+                quotas::record_llm_tokens(
+                    principal
+                        .as_deref()
+                        .map(|principal| principal.subject.as_str()),
+                    u64::from(usage.total_tokens),
+                    Utc::now(),
+                );
+            }
+            (Json(response).into_response(), None)
+        }
+        Err((status, e)) => (status.into_response(), Some(e.clone())),
+    };
+
+    if let Some(deployment) = route.deployment {
+        let request_id = Uuid::new_v4().to_string();
+        deployments::record(
+            &df,
+            &deployment,
+            &request_id,
+            outcome(route.served, true, time, start.elapsed(), &result),
+        );
+
+        if let Some(shadow) = route.shadow {
+            let (llms, df) = (Arc::clone(&llms), Arc::clone(&df));
+            tokio::spawn(async move {
+                let time = SystemTime::now();
+                let start = Instant::now();
+                let result = chat(&df, &llms, &shadow.model, req).await;
+                deployments::record(
+                    &df,
+                    &deployment,
+                    &request_id,
+                    outcome(shadow, false, time, start.elapsed(), &result),
+                );
+            });
+        }
+    }
+
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::ChatCompletion,
+        &model_id,
+        error,
+    );
+    response
+}
+
+/// Runs `req` with the LLM `name`.
+async fn chat(
+    df: &DataFusion,
+    llms: &RwLock<LLMModelStore>,
+    name: &str,
+    req: CreateChatCompletionRequest,
+) -> Result<CreateChatCompletionResponse, (StatusCode, String)> {
+    let llms = llms.read().await;
+    let Some(model) = llms.get(name) else {
+        return Err((StatusCode::NOT_FOUND, format!("Model {name} not found")));
+    };
+
+    let start = Instant::now();
+    let mut record = InferenceRecord::new(InferenceKind::Chat, name, Duration::ZERO);
+    record.input = serde_json::to_string(&req.messages).ok();
+    record.params = serde_json::to_string(&CreateChatCompletionRequest {
+        messages: vec![],
+        ..req.clone()
+    })
+    .ok();
+
+    let result = model
+        .write()
+        .await
+        .chat_request(req)
+        .instrument(tracing::info_span!("llm_inference", model = %name))
+        .await;
+    record.duration = start.elapsed();
+    match &result {
+        Ok(response) => {
+            record_token_usage(name, response.usage.as_ref());
+            record.output = response
+                .choices
+                .first()
+                .and_then(|choice| choice.message.content.clone());
+            record.prompt_tokens = response.usage.as_ref().map(|u| u.prompt_tokens);
+            record.completion_tokens = response.usage.as_ref().map(|u| u.completion_tokens);
+        }
+        Err(e) => record.error = Some(e.to_string()),
+    }
+    df.log_inference(record);
+
+    result.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn outcome(
+    variant: Variant,
+    served: bool,
+    time: SystemTime,
+    duration: Duration,
+    result: &Result<CreateChatCompletionResponse, (StatusCode, String)>,
+) -> Outcome {
+    let result = match result {
+        Ok(response) => Ok(response
+            .choices
+            .first()
+            .and_then(|choice| choice.message.content.clone())
+            .unwrap_or_default()),
+        Err((_, e)) => Err(e.clone()),
+    };
+
+    Outcome {
+        variant,
+        served,
+        time,
+        duration,
+        result,
     }
 }
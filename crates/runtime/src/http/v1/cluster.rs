@@ -0,0 +1,86 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Serialize;
+
+use crate::{cluster, Runtime};
+
+#[derive(Serialize)]
+struct ClusterStatus {
+    node: String,
+    members: Vec<MemberStatus>,
+    /// The member that owns each accelerated dataset.
+    datasets: BTreeMap<String, String>,
+    /// The shards each member accelerates, for each partitioned dataset.
+    shards: BTreeMap<String, BTreeMap<String, Vec<usize>>>,
+}
+
+#[derive(Serialize)]
+struct MemberStatus {
+    name: String,
+    flight_endpoint: String,
+    healthy: bool,
+}
+
+/// The members of the cluster, and which member accelerates each dataset or shard.
+pub(crate) async fn get(Extension(rt): Extension<Arc<Runtime>>) -> Response {
+    let Some(cluster) = &rt.cluster else {
+        return (StatusCode::NOT_FOUND, "Cluster mode is not enabled").into_response();
+    };
+
+    let datasets = match rt.app.read().await.as_ref() {
+        Some(app) => Runtime::get_valid_datasets(app, false),
+        None => vec![],
+    };
+
+    Json(ClusterStatus {
+        node: cluster.node().name.clone(),
+        members: cluster
+            .members()
+            .into_iter()
+            .map(|(member, healthy)| MemberStatus {
+                name: member.name.clone(),
+                flight_endpoint: member.flight_endpoint.clone(),
+                healthy,
+            })
+            .collect(),
+        datasets: datasets
+            .iter()
+            .filter(|ds| cluster::is_distributed(ds))
+            .map(|ds| (ds.name.to_string(), cluster.owner(&ds.name).name.clone()))
+            .collect(),
+        shards: datasets
+            .iter()
+            .filter(|ds| cluster::is_partitioned(ds))
+            .map(|ds| {
+                let shards = cluster
+                    .shards(&ds.name)
+                    .into_iter()
+                    .map(|(member, shards)| (member.name.clone(), shards))
+                    .collect();
+                (ds.name.to_string(), shards)
+            })
+            .collect(),
+    })
+    .into_response()
+}
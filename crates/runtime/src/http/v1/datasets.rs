@@ -13,25 +13,58 @@ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 See the License for the specific language governing permissions and
 limitations under the License.
 */
-use std::sync::Arc;
+use std::{io::Cursor, sync::Arc};
 
-use crate::{component::dataset::Dataset, Runtime};
+use crate::{
+    accelerated_table::{
+        self,
+        export::ArtifactMetadata,
+        progress,
+        schedule::{CronSchedule, RefreshSchedule},
+    },
+    audit::AuditAction,
+    auth::Principal,
+    component::dataset::Dataset,
+    dataupdate::{DataUpdate, UpdateType},
+    record_format::RecordDecoder,
+    Error, Runtime,
+};
 use app::App;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter};
+use arrow_tools::schema::verify_schema;
+use async_stream::stream;
 use axum::{
+    body::{Body, Bytes},
     extract::Path,
     extract::Query,
-    http::status,
+    http::{header, status, HeaderMap},
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use datafusion::sql::TableReference;
+use chrono::{DateTime, Utc};
+use data_components::object::metadata::ObjectStoreMetadataTable;
+use datafusion::{execution::SendableRecordBatchStream, sql::TableReference};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use spicepod::component::dataset::Dataset as SpicepodDataset;
 use tokio::sync::RwLock;
 use tract_core::tract_data::itertools::Itertools;
+use uuid::Uuid;
+
+use crate::{
+    datafusion::{DataFusion, Error as DataFusionError, SPICE_METADATA_SCHEMA},
+    status::ComponentStatus,
+};
+
+use super::{audit, convert_entry_to_csv, dataset_status, Format};
+
+const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
 
-use crate::{datafusion::DataFusion, status::ComponentStatus};
+/// The largest artifact an import reads, as the artifact is decoded in memory before it replaces the acceleration.
+const MAX_IMPORT_SIZE: usize = 1024 * 1024 * 1024;
 
-use super::{convert_entry_to_csv, dataset_status, Format};
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct DatasetFilter {
@@ -116,14 +149,49 @@ pub(crate) struct MessageResponse {
     pub message: String,
 }
 
+#[derive(Debug, Serialize)]
+pub(crate) struct RefreshTriggeredResponse {
+    pub message: String,
+    /// The job that reports the progress of the refresh.
+    pub job_id: Uuid,
+}
+
 #[derive(Deserialize)]
 pub struct AccelerationRequest {
     pub refresh_sql: Option<String>,
+    /// The interval between scheduled refreshes until the dataset is reloaded, i.e. `1h`. `0s` stops scheduled
+    /// refreshes.
+    pub refresh_check_interval: Option<String>,
+    /// A cron expression to refresh at until the dataset is reloaded, instead of `refresh_check_interval`.
+    pub refresh_cron: Option<String>,
+    pub refresh_cron_timezone: Option<String>,
+}
+
+impl AccelerationRequest {
+    /// The refresh schedule the request changes to, if it changes it.
+    fn refresh_schedule(&self) -> Result<Option<Option<RefreshSchedule>>, String> {
+        match (&self.refresh_check_interval, &self.refresh_cron) {
+            (Some(_), Some(_)) => {
+                Err("Set either refresh_check_interval or refresh_cron, not both".to_string())
+            }
+            (Some(interval), None) => match fundu::parse_duration(interval) {
+                Ok(interval) => Ok(Some(
+                    (!interval.is_zero()).then_some(RefreshSchedule::Interval(interval)),
+                )),
+                Err(e) => Err(format!("Invalid refresh_check_interval: {e}")),
+            },
+            (None, Some(cron)) => CronSchedule::parse(cron, self.refresh_cron_timezone.as_deref())
+                .map(|cron| Some(Some(RefreshSchedule::Cron(cron))))
+                .map_err(|e| format!("Invalid refresh_cron: {e}")),
+            (None, None) => Ok(None),
+        }
+    }
 }
 
 pub(crate) async fn refresh(
     Extension(app): Extension<Arc<RwLock<Option<App>>>>,
     Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
     Path(dataset_name): Path<String>,
 ) -> Response {
     let app_lock = app.read().await;
@@ -157,11 +225,21 @@ pub(crate) async fn refresh(
             .into_response();
     };
 
-    match df.refresh_table(&dataset.name).await {
-        Ok(()) => (
+    let result = df.refresh_table(&dataset.name).await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::DatasetRefresh,
+        &dataset.name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
+        Ok(job_id) => (
             status::StatusCode::CREATED,
-            Json(MessageResponse {
+            Json(RefreshTriggeredResponse {
                 message: format!("Dataset refresh triggered for {dataset_name}."),
+                job_id,
             }),
         )
             .into_response(),
@@ -175,9 +253,42 @@ pub(crate) async fn refresh(
     }
 }
 
+/// Returns the progress of a refresh job of a dataset, with the id returned when the refresh was triggered or logged
+/// when it started.
+pub(crate) async fn refresh_progress(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Path((dataset_name, job_id)): Path<(String, String)>,
+) -> Response {
+    let app_lock = app.read().await;
+    let Some(readable_app) = &*app_lock else {
+        return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+    };
+
+    let progress = readable_app
+        .datasets
+        .iter()
+        .find(|d| d.name.to_lowercase() == dataset_name.to_lowercase())
+        .zip(Uuid::parse_str(&job_id).ok())
+        .and_then(|(dataset, job_id)| {
+            progress::get(&TableReference::from(dataset.name.as_str()), job_id)
+        });
+
+    match progress {
+        Some(progress) => (status::StatusCode::OK, Json(progress)).into_response(),
+        None => (
+            status::StatusCode::NOT_FOUND,
+            Json(MessageResponse {
+                message: format!("Refresh job {job_id} of dataset {dataset_name} not found"),
+            }),
+        )
+            .into_response(),
+    }
+}
+
 pub(crate) async fn acceleration(
     Extension(app): Extension<Arc<RwLock<Option<App>>>>,
     Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
     Path(dataset_name): Path<String>,
     Json(payload): Json<AccelerationRequest>,
 ) -> Response {
@@ -200,17 +311,41 @@ pub(crate) async fn acceleration(
             .into_response();
     };
 
-    if payload.refresh_sql.is_none() {
+    let schedule = match payload.refresh_schedule() {
+        Ok(schedule) => schedule,
+        Err(message) => {
+            return (
+                status::StatusCode::BAD_REQUEST,
+                Json(MessageResponse { message }),
+            )
+                .into_response();
+        }
+    };
+    if payload.refresh_sql.is_none() && schedule.is_none() {
         return (status::StatusCode::OK).into_response();
     }
 
-    match df
-        .update_refresh_sql(
-            TableReference::parse_str(&dataset.name),
-            payload.refresh_sql,
-        )
-        .await
-    {
+    let dataset_name = TableReference::parse_str(&dataset.name);
+    let mut result = Ok(());
+    if let Some(schedule) = schedule {
+        result = df
+            .update_refresh_schedule(dataset_name.clone(), schedule)
+            .await;
+    }
+    if result.is_ok() && payload.refresh_sql.is_some() {
+        result = df
+            .update_refresh_sql(dataset_name, payload.refresh_sql)
+            .await;
+    }
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::AccelerationUpdate,
+        &dataset.name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
         Ok(()) => (status::StatusCode::OK).into_response(),
         Err(e) => (
             status::StatusCode::INTERNAL_SERVER_ERROR,
@@ -221,3 +356,642 @@ pub(crate) async fn acceleration(
             .into_response(),
     }
 }
+
+/// The name of an accelerated dataset of the app, or the response to return when it isn't one.
+fn accelerated_dataset_name(app: &App, dataset_name: &str) -> Result<String, Response> {
+    let Some(dataset) = app
+        .datasets
+        .iter()
+        .find(|d| d.name.to_lowercase() == dataset_name.to_lowercase())
+    else {
+        return Err((
+            status::StatusCode::NOT_FOUND,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} not found"),
+            }),
+        )
+            .into_response());
+    };
+
+    if !dataset.acceleration.as_ref().is_some_and(|f| f.enabled) {
+        return Err((
+            status::StatusCode::BAD_REQUEST,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} does not have acceleration enabled"),
+            }),
+        )
+            .into_response());
+    }
+    Ok(dataset.name.clone())
+}
+
+/// Streams the acceleration of a dataset as a portable artifact, an Arrow IPC stream with the artifact metadata in
+/// its schema, to import into the same dataset on another runtime.
+///
+/// The artifact holds the raw rows of the acceleration, without the policies and masks of the caller, so the route is
+/// restricted to admins.
+pub(crate) async fn export_acceleration(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Path(dataset_name): Path<String>,
+) -> Response {
+    let app_lock = app.read().await;
+    let Some(readable_app) = &*app_lock else {
+        return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+    };
+    let dataset_name = match accelerated_dataset_name(readable_app, &dataset_name) {
+        Ok(dataset_name) => dataset_name,
+        Err(response) => return response,
+    };
+    drop(app_lock);
+
+    let result = df.export_acceleration(&dataset_name).await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::AccelerationExport,
+        &dataset_name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    let (metadata, data) = match result {
+        Ok(export) => export,
+        Err(e) => {
+            return (
+                status::StatusCode::INTERNAL_SERVER_ERROR,
+                Json(MessageResponse {
+                    message: format!("Failed to export the acceleration of {dataset_name}: {e}"),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    let schema = Arc::new(Schema::new_with_metadata(
+        data.schema().fields().clone(),
+        metadata.to_schema_metadata(),
+    ));
+    let headers = [
+        (header::CONTENT_TYPE, ARROW_STREAM_CONTENT_TYPE.to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{dataset_name}.arrows\""),
+        ),
+    ];
+    (
+        status::StatusCode::OK,
+        headers,
+        Body::from_stream(artifact_stream(schema, data)),
+    )
+        .into_response()
+}
+
+/// Encodes the data of an artifact as an Arrow IPC stream with `schema`, as the client consumes it.
+fn artifact_stream(
+    schema: SchemaRef,
+    mut data: SendableRecordBatchStream,
+) -> impl futures::Stream<Item = Result<Bytes, BoxError>> {
+    stream! {
+        let mut writer = match StreamWriter::try_new(Vec::new(), &schema) {
+            Ok(writer) => writer,
+            Err(e) => {
+                yield Err(e.into());
+                return;
+            }
+        };
+        yield Ok(Bytes::from(std::mem::take(writer.get_mut())));
+
+        while let Some(batch) = data.next().await {
+            let batch = match batch {
+                Ok(batch) => batch,
+                Err(e) => {
+                    tracing::debug!("Error exporting an acceleration: {e}");
+                    yield Err(e.into());
+                    return;
+                }
+            };
+            if let Err(e) = writer.write(&batch) {
+                yield Err(e.into());
+                return;
+            }
+            yield Ok(Bytes::from(std::mem::take(writer.get_mut())));
+        }
+
+        if let Err(e) = writer.finish() {
+            yield Err(e.into());
+            return;
+        }
+        yield Ok(Bytes::from(std::mem::take(writer.get_mut())));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportResponse {
+    pub message: String,
+    pub rows: usize,
+    pub schema_version: String,
+    /// The latest `time_column` value of the imported data, which append refreshes continue from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<DateTime<Utc>>,
+}
+
+/// Replaces the acceleration of a dataset with an artifact exported from another runtime, sent as the request body.
+pub(crate) async fn import_acceleration(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Path(dataset_name): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let app_lock = app.read().await;
+    let Some(readable_app) = &*app_lock else {
+        return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+    };
+    let dataset_name = match accelerated_dataset_name(readable_app, &dataset_name) {
+        Ok(dataset_name) => dataset_name,
+        Err(response) => return response,
+    };
+    drop(app_lock);
+
+    let bad_request = |message: String| {
+        (
+            status::StatusCode::BAD_REQUEST,
+            Json(MessageResponse { message }),
+        )
+            .into_response()
+    };
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > MAX_IMPORT_SIZE) {
+        return (
+            status::StatusCode::PAYLOAD_TOO_LARGE,
+            Json(MessageResponse {
+                message: format!(
+                    "The artifact exceeds the import limit of {MAX_IMPORT_SIZE} bytes"
+                ),
+            }),
+        )
+            .into_response();
+    }
+    let bytes = match axum::body::to_bytes(body, MAX_IMPORT_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return bad_request(format!(
+                "Unable to read the artifact, of at most {MAX_IMPORT_SIZE} bytes: {e}"
+            ))
+        }
+    };
+    let reader = match StreamReader::try_new(Cursor::new(bytes), None) {
+        Ok(reader) => reader,
+        Err(e) => return bad_request(format!("The artifact is not an Arrow IPC stream: {e}")),
+    };
+    let metadata = match ArtifactMetadata::from_schema_metadata(reader.schema().metadata()) {
+        Ok(metadata) => metadata,
+        Err(e) => return bad_request(e.to_string()),
+    };
+    let data = match reader.collect::<Result<Vec<_>, _>>() {
+        Ok(data) => data,
+        Err(e) => return bad_request(format!("Unable to read the artifact: {e}")),
+    };
+
+    let result = df.import_acceleration(&dataset_name, &metadata, data).await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::AccelerationImport,
+        &dataset_name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
+        Ok(rows) => (
+            status::StatusCode::OK,
+            Json(ImportResponse {
+                message: format!(
+                    "Imported the acceleration of {} into {dataset_name}.",
+                    metadata.dataset
+                ),
+                rows,
+                schema_version: metadata.schema_version,
+                watermark: metadata.watermark,
+            }),
+        )
+            .into_response(),
+        Err(
+            e @ DataFusionError::UnableToImportAcceleration {
+                source: accelerated_table::Error::InvalidArtifact { .. },
+                ..
+            },
+        ) => (
+            status::StatusCode::CONFLICT,
+            Json(MessageResponse {
+                message: e.to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            status::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(MessageResponse {
+                message: format!("Failed to import the acceleration of {dataset_name}: {e}"),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct IngestResponse {
+    pub rows: usize,
+}
+
+/// Appends the records of the body, Avro or Protobuf in the `ingest_format` of the dataset, for webhooks and other
+/// producers that can't write Arrow with Flight `DoPut`.
+pub(crate) async fn ingest(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Path(dataset_name): Path<String>,
+    body: Bytes,
+) -> Response {
+    let message = |status: status::StatusCode, message: String| {
+        (status, Json(MessageResponse { message })).into_response()
+    };
+
+    let (dataset_name, decoder) = {
+        let app_lock = app.read().await;
+        let Some(readable_app) = &*app_lock else {
+            return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+        };
+        let Some(dataset) = readable_app
+            .datasets
+            .iter()
+            .find(|d| d.name.to_lowercase() == dataset_name.to_lowercase())
+        else {
+            return message(
+                status::StatusCode::NOT_FOUND,
+                format!("Dataset {dataset_name} not found"),
+            );
+        };
+        let params = dataset
+            .params
+            .as_ref()
+            .map(spicepod::component::params::Params::as_string_map)
+            .unwrap_or_default();
+        match RecordDecoder::from_params(&params) {
+            Ok(Some(decoder)) => (dataset.name.clone(), decoder),
+            Ok(None) => {
+                return message(
+                    status::StatusCode::BAD_REQUEST,
+                    format!(
+                    "Dataset {dataset_name} doesn't set the ingest_format param to ingest records"
+                ),
+                )
+            }
+            Err(e) => {
+                return message(
+                    status::StatusCode::BAD_REQUEST,
+                    format!("Dataset {dataset_name} has invalid ingest params: {e}"),
+                )
+            }
+        }
+    };
+
+    let table = TableReference::parse_str(&dataset_name);
+    if !df.is_writable(&table) {
+        return message(
+            status::StatusCode::CONFLICT,
+            format!("Dataset {dataset_name} is not writable. Set its mode to read_write to ingest into it."),
+        );
+    }
+    let Some(provider) = df.get_table(table.clone()).await else {
+        return message(
+            status::StatusCode::NOT_FOUND,
+            format!("Dataset {dataset_name} is not loaded"),
+        );
+    };
+
+    let batch = match decoder.decode(&body).await {
+        Ok(batch) => batch,
+        Err(e) => return message(status::StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    if let Err(e) = verify_schema(provider.schema().fields(), batch.schema().fields()) {
+        return message(
+            status::StatusCode::BAD_REQUEST,
+            format!("The records don't match dataset {dataset_name}: {e}"),
+        );
+    }
+
+    let rows = batch.num_rows();
+    let result = df
+        .write_data(
+            table,
+            DataUpdate {
+                schema: batch.schema(),
+                data: vec![batch],
+                update_type: UpdateType::Append,
+            },
+        )
+        .await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::DatasetIngest,
+        &dataset_name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
+        Ok(()) => (status::StatusCode::OK, Json(IngestResponse { rows })).into_response(),
+        Err(e) => message(
+            status::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to ingest into {dataset_name}: {e}"),
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PersistParams {
+    /// Write the change back to the spicepod, so it survives a restart.
+    #[serde(default)]
+    persist: bool,
+}
+
+pub(crate) async fn create(
+    Extension(rt): Extension<Arc<Runtime>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<PersistParams>,
+    Json(dataset): Json<SpicepodDataset>,
+) -> Response {
+    let name = dataset.name.clone();
+    if df.table_exists(TableReference::parse_str(&name)) {
+        return (
+            status::StatusCode::CONFLICT,
+            Json(MessageResponse {
+                message: format!("Dataset {name} already exists"),
+            }),
+        )
+            .into_response();
+    }
+
+    let result = rt.upsert_dataset(dataset, params.persist).await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::DatasetCreate,
+        &name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
+        Ok(()) => (
+            status::StatusCode::CREATED,
+            Json(MessageResponse {
+                message: format!("Dataset {name} created."),
+            }),
+        )
+            .into_response(),
+        Err(err) => dataset_error_response(&err),
+    }
+}
+
+pub(crate) async fn replace(
+    Extension(rt): Extension<Arc<Runtime>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Path(dataset_name): Path<String>,
+    Query(params): Query<PersistParams>,
+    Json(dataset): Json<SpicepodDataset>,
+) -> Response {
+    if dataset.name != dataset_name {
+        return (
+            status::StatusCode::BAD_REQUEST,
+            Json(MessageResponse {
+                message: format!(
+                    "Dataset name {} does not match {dataset_name} in the path",
+                    dataset.name
+                ),
+            }),
+        )
+            .into_response();
+    }
+
+    let existed = df.table_exists(TableReference::parse_str(&dataset_name));
+    let result = rt.upsert_dataset(dataset, params.persist).await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::DatasetReplace,
+        &dataset_name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
+        Ok(()) if existed => (
+            status::StatusCode::OK,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} updated."),
+            }),
+        )
+            .into_response(),
+        Ok(()) => (
+            status::StatusCode::CREATED,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} created."),
+            }),
+        )
+            .into_response(),
+        Err(err) => dataset_error_response(&err),
+    }
+}
+
+pub(crate) async fn delete(
+    Extension(rt): Extension<Arc<Runtime>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Path(dataset_name): Path<String>,
+    Query(params): Query<PersistParams>,
+) -> Response {
+    let result = rt.delete_dataset(&dataset_name, params.persist).await;
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::DatasetDelete,
+        &dataset_name,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    match result {
+        Ok(true) => (
+            status::StatusCode::OK,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} deleted."),
+            }),
+        )
+            .into_response(),
+        Ok(false) => (
+            status::StatusCode::NOT_FOUND,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} not found"),
+            }),
+        )
+            .into_response(),
+        Err(err) => dataset_error_response(&err),
+    }
+}
+
+/// The most rows a sample of the source of a dataset returns.
+const MAX_SAMPLE_ROWS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SampleParams {
+    #[serde(default = "default_sample_rows")]
+    n: usize,
+}
+
+fn default_sample_rows() -> usize {
+    100
+}
+
+/// Drops the cached listing of the objects of the metadata table of a dataset, so its next query lists the store.
+pub(crate) async fn invalidate_listing(
+    Extension(df): Extension<Arc<DataFusion>>,
+    Path(dataset_name): Path<String>,
+) -> Response {
+    let table = TableReference::partial(SPICE_METADATA_SCHEMA, dataset_name.as_str());
+    let provider = df.ctx.table_provider(table).await.ok();
+    let Some(listing_cache) = provider.as_ref().and_then(|provider| {
+        provider
+            .as_any()
+            .downcast_ref::<ObjectStoreMetadataTable>()?
+            .listing_cache()
+    }) else {
+        return (
+            status::StatusCode::NOT_FOUND,
+            Json(MessageResponse {
+                message: format!(
+                    "Dataset {dataset_name} has no metadata table with a metadata_listing_cache_ttl"
+                ),
+            }),
+        )
+            .into_response();
+    };
+
+    listing_cache.invalidate().await;
+    (
+        status::StatusCode::OK,
+        Json(MessageResponse {
+            message: format!("Invalidated the metadata listing of {dataset_name}."),
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SampleField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SampleResponse {
+    pub schema: Vec<SampleField>,
+    pub rows: serde_json::Value,
+}
+
+/// Returns the first rows of the source of a dataset, without waiting for or reading its acceleration, to check
+/// the configuration of its connector.
+pub(crate) async fn sample(
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    Path(dataset_name): Path<String>,
+    Query(params): Query<SampleParams>,
+) -> Response {
+    let table = TableReference::parse_str(&dataset_name);
+    if !df.table_exists(table.clone()) {
+        return (
+            status::StatusCode::NOT_FOUND,
+            Json(MessageResponse {
+                message: format!("Dataset {dataset_name} not found"),
+            }),
+        )
+            .into_response();
+    }
+
+    let limit = params.n.min(MAX_SAMPLE_ROWS);
+    let (schema, batches) = match df.sample_source(&table, limit, principal.as_deref()).await {
+        Ok(sample) => sample,
+        Err(err) => {
+            let code = match err {
+                DataFusionError::AccessPolicy { .. } => status::StatusCode::FORBIDDEN,
+                _ => status::StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            return (
+                code,
+                Json(MessageResponse {
+                    message: format!("Unable to sample {dataset_name}: {err}"),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    let mut writer = arrow_json::ArrayWriter::new(Vec::new());
+    let rows = writer
+        .write_batches(&batches.iter().collect::<Vec<_>>())
+        .and_then(|()| writer.finish())
+        .map_err(|e| e.to_string())
+        .and_then(|()| {
+            let buf = writer.into_inner();
+            if buf.is_empty() {
+                return Ok(serde_json::Value::Array(vec![]));
+            }
+            serde_json::from_slice(&buf).map_err(|e| e.to_string())
+        });
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::debug!("Error converting the sample of {dataset_name} to JSON: {e}");
+            return (status::StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+
+    let schema = schema
+        .fields()
+        .iter()
+        .map(|field| SampleField {
+            name: field.name().clone(),
+            data_type: field.data_type().to_string(),
+            nullable: field.is_nullable(),
+        })
+        .collect();
+
+    (
+        status::StatusCode::OK,
+        Json(SampleResponse { schema, rows }),
+    )
+        .into_response()
+}
+
+fn dataset_error_response(err: &Error) -> Response {
+    let code = match err {
+        Error::InvalidSpicepodDataset { .. } | Error::NoSpicepodToPersistDataset { .. } => {
+            status::StatusCode::BAD_REQUEST
+        }
+        _ => status::StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (
+        code,
+        Json(MessageResponse {
+            message: format!("Request failed. {err}"),
+        }),
+    )
+        .into_response()
+}
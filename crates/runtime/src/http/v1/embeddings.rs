@@ -14,9 +14,17 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::EmbeddingModelStore;
+use crate::{
+    auth::Principal,
+    datafusion::DataFusion,
+    inference_log::{InferenceKind, InferenceRecord},
+    EmbeddingModelStore, Runtime,
+};
 use async_openai::types::{CreateEmbeddingRequest, EmbeddingInput, EncodingFormat};
 use axum::{
     http::StatusCode,
@@ -28,8 +36,13 @@ use tokio::sync::RwLock;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
+use super::{acquire_rate_limit, record_rate_limited_tokens};
+
 pub(crate) async fn post(
+    Extension(rt): Extension<Arc<Runtime>>,
     Extension(embeddings): Extension<Arc<RwLock<EmbeddingModelStore>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
     body: String,
 ) -> Response {
     let req: LocalCreateEmbeddingRequest = match serde_json::from_str(&body) {
@@ -40,10 +53,23 @@ pub(crate) async fn post(
     };
 
     let model_id = req.model.clone().to_string();
+    if let Err(response) = acquire_rate_limit(&rt, principal.as_deref(), &model_id).await {
+        return response;
+    }
     match embeddings.read().await.get(&model_id) {
         Some(model_lock) => {
+            let start = Instant::now();
+            let mut record =
+                InferenceRecord::new(InferenceKind::Embedding, &model_id, Duration::ZERO);
+            record.input = serde_json::to_string(&req.input).ok();
+            record.params = serde_json::to_string(&serde_json::json!({
+                "encoding_format": req.encoding_format,
+                "dimensions": req.dimensions,
+            }))
+            .ok();
+
             let mut model = model_lock.write().await;
-            match model
+            let result = model
                 .embed_request(CreateEmbeddingRequest {
                     model: req.model,
                     input: to_openai_embedding_input(req.input),
@@ -51,11 +77,27 @@ pub(crate) async fn post(
                     user: req.user,
                     dimensions: req.dimensions,
                 })
-                .await
-            {
-                Ok(response) => Json(response).into_response(),
-                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-            }
+                .await;
+            record.duration = start.elapsed();
+
+            let response = match result {
+                Ok(response) => {
+                    record.prompt_tokens = Some(response.usage.prompt_tokens);
+                    record_rate_limited_tokens(
+                        &rt,
+                        principal.as_deref(),
+                        &model_id,
+                        response.usage.total_tokens,
+                    );
+                    Json(response).into_response()
+                }
+                Err(e) => {
+                    record.error = Some(e.to_string());
+                    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+                }
+            };
+            df.log_inference(record);
+            response
         }
         None => (StatusCode::NOT_FOUND, "model not found").into_response(),
     }
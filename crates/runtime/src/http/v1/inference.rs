@@ -13,10 +13,19 @@ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 See the License for the specific language governing permissions and
 limitations under the License.
 */
-use crate::{datafusion::DataFusion, model::run};
+use crate::{
+    audit::AuditAction,
+    auth::Principal,
+    datafusion::DataFusion,
+    deployments::{self, Outcome, Route, Variant},
+    inference_log::{InferenceKind, InferenceRecord},
+    model::{run, run_series},
+};
+
+use super::audit;
 
 use app::App;
-use arrow::array::Float32Array;
+use arrow::array::{Float32Array, RecordBatch};
 use axum::{
     extract::Path,
     http::StatusCode,
@@ -25,10 +34,12 @@ use axum::{
 };
 use model_components::{model::Model, modelsource};
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
+use tracing::Instrument;
 use tract_core::tract_data::itertools::Itertools;
+use uuid::Uuid;
 
 #[derive(Deserialize)]
 pub struct BatchPredictRequest {
@@ -39,6 +50,11 @@ pub struct BatchPredictRequest {
 #[derive(Deserialize)]
 pub struct PredictRequest {
     pub model_name: String,
+
+    /// The keys of the series to forecast, for a model bound with `forecast.series_key`. Defaults to every series of
+    /// the dataset.
+    #[serde(default)]
+    pub series: Option<Vec<String>>,
 }
 
 #[derive(Serialize)]
@@ -62,9 +78,23 @@ pub struct PredictResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prediction: Option<Vec<f32>>,
 
+    /// The forecast of each series, for a model bound with `forecast.series_key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<Vec<SeriesPrediction>>,
+
+    /// The model that made the prediction, when `model_name` is a deployment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub served_by: Option<String>,
+
     pub duration_ms: u128,
 }
 
+#[derive(Serialize)]
+pub struct SeriesPrediction {
+    pub key: String,
+    pub prediction: Vec<f32>,
+}
+
 #[derive(Serialize)]
 pub enum PredictStatus {
     Success,
@@ -75,10 +105,13 @@ pub enum PredictStatus {
 pub(crate) async fn get(
     Extension(app): Extension<Arc<RwLock<Option<App>>>>,
     Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
     Path(model_name): Path<String>,
     Extension(models): Extension<Arc<RwLock<HashMap<String, Model>>>>,
 ) -> Response {
-    let model_predict_response = run_inference(app, df, models, model_name).await;
+    let model_predict_response =
+        run_inference(app, Arc::clone(&df), models, model_name, None).await;
+    audit_prediction(&df, principal.as_deref(), &model_predict_response);
 
     match model_predict_response.status {
         PredictStatus::Success => (StatusCode::OK, Json(model_predict_response)).into_response(),
@@ -96,6 +129,7 @@ pub(crate) async fn get(
 pub(crate) async fn post(
     Extension(app): Extension<Arc<RwLock<Option<App>>>>,
     Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
     Extension(models): Extension<Arc<RwLock<HashMap<String, Model>>>>,
     Json(payload): Json<BatchPredictRequest>,
 ) -> Response {
@@ -109,12 +143,15 @@ pub(crate) async fn post(
             Arc::clone(&df),
             Arc::clone(&models),
             model_predict_request.model_name,
+            model_predict_request.series,
         );
         model_prediction_futures.push(prediction_future);
     }
 
     for prediction_future in model_prediction_futures {
-        model_predictions.push(prediction_future.await);
+        let model_predict_response = prediction_future.await;
+        audit_prediction(&df, principal.as_deref(), &model_predict_response);
+        model_predictions.push(model_predict_response);
     }
 
     (
@@ -127,11 +164,92 @@ pub(crate) async fn post(
         .into_response()
 }
 
+fn audit_prediction(df: &DataFusion, principal: Option<&Principal>, response: &PredictResponse) {
+    audit(
+        df,
+        principal,
+        AuditAction::ModelInference,
+        &response.model_name,
+        response.error_message.clone(),
+    );
+}
+
+/// Runs the model of `model_name`, or the model serving the request when it is a deployment, recording the
+/// results of the deployment's models.
 async fn run_inference(
     app: Arc<RwLock<Option<App>>>,
     df: Arc<DataFusion>,
     models: Arc<RwLock<HashMap<String, Model>>>,
     model_name: String,
+    series: Option<Vec<String>>,
+) -> PredictResponse {
+    let route = Route::new(app.read().await.as_ref(), &model_name);
+    let Some(deployment) = route.deployment else {
+        return infer(app, df, models, model_name, series).await;
+    };
+    let request_id = Uuid::new_v4().to_string();
+
+    let time = SystemTime::now();
+    let mut response = infer(
+        Arc::clone(&app),
+        Arc::clone(&df),
+        Arc::clone(&models),
+        route.served.model.clone(),
+        series.clone(),
+    )
+    .await;
+    deployments::record(
+        &df,
+        &deployment,
+        &request_id,
+        outcome(route.served, true, time, &response),
+    );
+
+    if let Some(shadow) = route.shadow {
+        let df = Arc::clone(&df);
+        tokio::spawn(async move {
+            let time = SystemTime::now();
+            let response = infer(app, Arc::clone(&df), models, shadow.model.clone(), series).await;
+            deployments::record(
+                &df,
+                &deployment,
+                &request_id,
+                outcome(shadow, false, time, &response),
+            );
+        });
+    }
+
+    response.served_by = Some(std::mem::replace(&mut response.model_name, model_name));
+    response
+}
+
+fn outcome(
+    variant: Variant,
+    served: bool,
+    time: SystemTime,
+    response: &PredictResponse,
+) -> Outcome {
+    let result = match (&response.error_message, &response.series) {
+        (Some(e), _) => Err(e.clone()),
+        (None, Some(series)) => serde_json::to_string(series).map_err(|e| e.to_string()),
+        (None, None) => serde_json::to_string(&response.prediction).map_err(|e| e.to_string()),
+    };
+
+    Outcome {
+        variant,
+        served,
+        time,
+        duration: Duration::from_millis(u64::try_from(response.duration_ms).unwrap_or(u64::MAX)),
+        result,
+    }
+}
+
+async fn infer(
+    app: Arc<RwLock<Option<App>>>,
+    df: Arc<DataFusion>,
+    models: Arc<RwLock<HashMap<String, Model>>>,
+    model_name: String,
+    series: Option<Vec<String>>,
 ) -> PredictResponse {
     let start_time = Instant::now();
 
@@ -143,6 +261,8 @@ async fn run_inference(
             model_name,
             model_version: None,
             prediction: None,
+            series: None,
+            served_by: None,
             duration_ms: start_time.elapsed().as_millis(),
         };
     };
@@ -156,6 +276,8 @@ async fn run_inference(
             model_name,
             model_version: None,
             prediction: None,
+            series: None,
+            served_by: None,
             duration_ms: start_time.elapsed().as_millis(),
         };
     };
@@ -169,59 +291,89 @@ async fn run_inference(
             model_name,
             model_version: Some(modelsource::version(&model.from)),
             prediction: None,
+            series: None,
+            served_by: None,
             duration_ms: start_time.elapsed().as_millis(),
         };
     };
 
-    match run(runnable, Arc::clone(&df)).await {
-        Ok(inference_result) => {
-            if let Some(column_data) = inference_result.column_by_name("y") {
-                if let Some(array) = column_data.as_any().downcast_ref::<Float32Array>() {
-                    let result = array.values().iter().copied().collect_vec();
-                    return PredictResponse {
-                        status: PredictStatus::Success,
-                        error_message: None,
-                        model_name,
-                        model_version: Some(modelsource::version(&model.from)),
-                        prediction: Some(result),
-                        duration_ms: start_time.elapsed().as_millis(),
-                    };
-                }
-                tracing::error!(
-                    "Failed to cast inference result for model {model_name} to Float32Array"
-                );
-                tracing::debug!("Failed to cast inference result for model {model_name} to Float32Array: {column_data:?}");
-                return PredictResponse {
-                    status: PredictStatus::InternalError,
-                    error_message: Some(
-                        "Unable to cast inference result to Float32Array".to_string(),
-                    ),
-                    model_name,
-                    model_version: Some(modelsource::version(&model.from)),
-                    prediction: None,
-                    duration_ms: start_time.elapsed().as_millis(),
-                };
-            }
-            tracing::error!("Unable to find column 'y' in inference result for model {model_name}");
-            PredictResponse {
-                status: PredictStatus::InternalError,
-                error_message: Some("Unable to find column 'y' in inference result".to_string()),
-                model_name,
-                model_version: Some(modelsource::version(&model.from)),
-                prediction: None,
-                duration_ms: start_time.elapsed().as_millis(),
-            }
-        }
+    let model_version = Some(modelsource::version(&model.from));
+    let mut record = InferenceRecord::new(InferenceKind::Predict, &model_name, Duration::ZERO);
+    record.params = series
+        .as_ref()
+        .and_then(|series| serde_json::to_string(&serde_json::json!({ "series": series })).ok());
+    let span = tracing::info_span!("model_inference", model = %model_name);
+    let result = if model
+        .forecast
+        .as_ref()
+        .is_some_and(|forecast| forecast.series_key.is_some())
+    {
+        run_series(runnable, Arc::clone(&df), series)
+            .instrument(span)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|forecasts| {
+                forecasts
+                    .into_iter()
+                    .map(|(key, batch)| {
+                        predictions(&batch).map(|prediction| SeriesPrediction { key, prediction })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map(|series| (None, Some(series)))
+    } else {
+        run(runnable, Arc::clone(&df))
+            .instrument(span)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|batch| predictions(&batch))
+            .map(|prediction| (Some(prediction), None))
+    };
+
+    record.duration = start_time.elapsed();
+    match &result {
+        Ok((Some(prediction), _)) => record.output = serde_json::to_string(prediction).ok(),
+        Ok((None, series)) => record.output = serde_json::to_string(series).ok(),
+        Err(e) => record.error = Some(e.clone()),
+    }
+    df.log_inference(record);
+
+    match result {
+        Ok((prediction, series)) => PredictResponse {
+            status: PredictStatus::Success,
+            error_message: None,
+            model_name,
+            model_version,
+            prediction,
+            series,
+            served_by: None,
+            duration_ms: start_time.elapsed().as_millis(),
+        },
         Err(e) => {
-            tracing::error!("Unable to run inference: {e}");
+            tracing::error!("Unable to run inference for model {model_name}: {e}");
             PredictResponse {
                 status: PredictStatus::InternalError,
-                error_message: Some(e.to_string()),
+                error_message: Some(e),
                 model_name,
-                model_version: Some(modelsource::version(&model.from)),
+                model_version,
                 prediction: None,
+                series: None,
+                served_by: None,
                 duration_ms: start_time.elapsed().as_millis(),
             }
         }
     }
 }
+
+/// Returns the predictions of the `y` column of the result of a model.
+fn predictions(result: &RecordBatch) -> Result<Vec<f32>, String> {
+    let Some(column_data) = result.column_by_name("y") else {
+        return Err("Unable to find column 'y' in inference result".to_string());
+    };
+    let Some(array) = column_data.as_any().downcast_ref::<Float32Array>() else {
+        tracing::debug!("Failed to cast inference result to Float32Array: {column_data:?}");
+        return Err("Unable to cast inference result to Float32Array".to_string());
+    };
+
+    Ok(array.values().iter().copied().collect_vec())
+}
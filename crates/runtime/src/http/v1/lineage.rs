@@ -0,0 +1,54 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use app::App;
+use axum::{
+    extract::Query,
+    http::status,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use crate::{datafusion::DataFusion, lineage::Lineage};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LineageParams {
+    /// Only return what is downstream of the node with this id, i.e. `source:postgres:orders`.
+    downstream_of: Option<String>,
+}
+
+pub(crate) async fn get(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    Query(params): Query<LineageParams>,
+) -> Response {
+    let app_lock = app.read().await;
+    let Some(readable_app) = &*app_lock else {
+        return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+    };
+
+    let lineage = Lineage::of(readable_app, &df.ctx.state()).await;
+    let lineage = match params.downstream_of {
+        Some(id) => lineage.downstream_of(&id),
+        None => lineage,
+    };
+
+    (status::StatusCode::OK, Json(lineage)).into_response()
+}
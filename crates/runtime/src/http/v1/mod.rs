@@ -15,32 +15,52 @@ limitations under the License.
 */
 pub mod assist;
 pub mod chat;
+pub mod cluster;
 pub mod datasets;
 pub mod embeddings;
 pub mod inference;
+pub mod lineage;
 pub mod models;
 pub mod nsql;
+pub mod queries;
 pub mod query;
 pub mod ready;
+pub mod refresh;
 pub mod spicepods;
 pub mod status;
+pub mod tests;
+pub mod watch;
 
 use std::sync::Arc;
 
 use crate::{
+    audit::{AuditAction, AuditRecord},
+    auth::Principal,
     component::dataset::Dataset,
-    datafusion::query::{Protocol, QueryBuilder},
+    datafusion::query::{
+        labels::{parse_labels, QueryLabels, LABELS_HEADER},
+        Protocol, QueryBuilder,
+    },
+    error_info::ToErrorInfo,
+    quotas,
 };
 use arrow::array::RecordBatch;
 use axum::{
-    http::{HeaderMap, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
+use chrono::{TimeDelta, Utc};
 use csv::Writer;
 use datafusion::execution::context::SQLOptions;
 use serde::{Deserialize, Serialize};
 
-use crate::{datafusion::DataFusion, status::ComponentStatus};
+use crate::{
+    datafusion::DataFusion,
+    error_info::{ErrorInfo, ErrorKind},
+    status::ComponentStatus,
+    Runtime,
+};
 
 use futures::TryStreamExt;
 
@@ -61,6 +81,102 @@ fn convert_entry_to_csv<T: Serialize>(entries: &[T]) -> Result<String, Box<dyn s
     Ok(String::from_utf8(w.into_inner()?)?)
 }
 
+/// Records an audit event for an action on a dataset or model made over HTTP.
+fn audit(
+    df: &DataFusion,
+    principal: Option<&Principal>,
+    action: AuditAction,
+    resource: &str,
+    error: Option<String>,
+) {
+    df.audit(AuditRecord {
+        principal: principal.map(|principal| principal.subject.clone()),
+        protocol: Some(Protocol::Http.to_string()),
+        resources: vec![resource.to_string()],
+        error,
+        ..AuditRecord::new(action)
+    });
+}
+
+/// Counts a request of `principal` for `model` against the rate limits of the runtime, returning the
+/// `429 Too Many Requests` response of a request over a limit.
+async fn acquire_rate_limit(
+    rt: &Runtime,
+    principal: Option<&Principal>,
+    model: &str,
+) -> Result<(), Response> {
+    let app = rt.app.read().await;
+    let limits = app
+        .as_ref()
+        .map_or(&[][..], |app| app.runtime.rate_limits.as_slice());
+
+    rt.rate_limiter
+        .acquire(
+            limits,
+            principal.map(|principal| principal.subject.as_str()),
+            model,
+            Utc::now(),
+        )
+        .map_err(|exceeded| {
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(
+                    header::RETRY_AFTER,
+                    exceeded.retry_after().as_secs().max(1).to_string(),
+                )],
+                Json(serde_json::json!({
+                    "error": {
+                        "message": exceeded.to_string(),
+                        "type": "rate_limit_exceeded",
+                    }
+                })),
+            )
+                .into_response()
+        })
+}
+
+/// Checks that the namespaces of `principal` have LLM tokens left for the day, returning the
+/// `429 Too Many Requests` response of a request over their quota.
+fn check_llm_token_quota(principal: Option<&Principal>) -> Result<(), Response> {
+    let now = Utc::now();
+    quotas::check_llm_tokens(principal.map(|principal| principal.subject.as_str()), now).map_err(
+        |e| {
+            let retry_after = (now + TimeDelta::days(1))
+                .date_naive()
+                .and_hms_opt(0, 0, 0)
+                .map_or(1, |midnight| {
+                    (midnight.and_utc() - now).num_seconds().max(1)
+                });
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(serde_json::json!({
+                    "error": {
+                        "message": e.to_string(),
+                        "type": "quota_exceeded",
+                    }
+                })),
+            )
+                .into_response()
+        },
+    )
+}
+
+/// Counts the tokens of a completed request of `principal` for `model` against its daily budget.
+fn record_rate_limited_tokens(
+    rt: &Runtime,
+    principal: Option<&Principal>,
+    model: &str,
+    tokens: u32,
+) {
+    rt.rate_limiter.record_tokens(
+        principal.map(|principal| principal.subject.as_str()),
+        model,
+        u64::from(tokens),
+        Utc::now(),
+    );
+}
+
 fn dataset_status(df: &DataFusion, ds: &Dataset) -> ComponentStatus {
     if df.table_exists(ds.name.clone()) {
         ComponentStatus::Ready
@@ -75,28 +191,28 @@ pub async fn sql_to_http_response(
     sql: &str,
     restricted_sql_options: Option<SQLOptions>,
     nsql: Option<String>,
+    principal: Option<Principal>,
+    labels: QueryLabels,
 ) -> Response {
     let query = QueryBuilder::new(sql.to_string(), Arc::clone(&df), Protocol::Http)
         .restricted_sql_options(restricted_sql_options)
         .nsql(nsql)
+        .principal(principal)
+        .labels(labels)
         .protocol(Protocol::Http)
         .build();
 
-    let (data, is_data_from_cache) = match query.run().await {
+    let (data, is_data_from_cache, warnings) = match query.run().await {
         Ok(query_result) => match query_result.data.try_collect::<Vec<RecordBatch>>().await {
-            Ok(batches) => (batches, query_result.from_cache),
+            Ok(batches) => (batches, query_result.from_cache, query_result.warnings),
             Err(e) => {
                 tracing::debug!("Error executing query: {e}");
-                return (
-                    StatusCode::BAD_REQUEST,
-                    format!("Error processing batch: {e}"),
-                )
-                    .into_response();
+                return error_response(format!("Error processing batch: {e}"), &e.error_info());
             }
         },
         Err(e) => {
             tracing::debug!("Error executing query: {e}");
-            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+            return error_response(e.to_string(), &e.error_info());
         }
     };
     let buf = Vec::new();
@@ -120,6 +236,53 @@ pub async fn sql_to_http_response(
         }
     };
 
+    let mut headers = cache_headers(is_data_from_cache);
+    add_warning_headers(&mut headers, &warnings);
+    (StatusCode::OK, headers, res).into_response()
+}
+
+/// Adds a `Warning: 110` (response is stale) header for each warning of the query, i.e. for stale accelerations.
+/// The labels the client attributes its query to with the `X-Spice-Query-Labels` header.
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    message: String,
+    #[serde(flatten)]
+    info: &'a ErrorInfo,
+}
+
+/// A JSON error response with the message, code, component, retryability and hint of an error, and the status of
+/// its kind.
+pub(crate) fn error_response(message: String, info: &ErrorInfo) -> Response {
+    let status = match info.kind {
+        ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        ErrorKind::AccessDenied => StatusCode::FORBIDDEN,
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(ErrorBody { message, info })).into_response()
+}
+
+fn query_labels(headers: &HeaderMap) -> QueryLabels {
+    headers
+        .get(LABELS_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_labels)
+        .unwrap_or_default()
+}
+
+fn add_warning_headers(headers: &mut HeaderMap, warnings: &[String]) {
+    for warning in warnings {
+        let value = format!("110 spiceai \"{}\"", warning.replace('"', "'"));
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.append(header::WARNING, value);
+        }
+    }
+}
+
+/// Returns the `X-Cache` header indicating whether the results were served from the results cache.
+fn cache_headers(is_data_from_cache: Option<bool>) -> HeaderMap {
     let mut headers = HeaderMap::new();
 
     match is_data_from_cache {
@@ -135,5 +298,6 @@ pub async fn sql_to_http_response(
         }
         None => {}
     };
-    (StatusCode::OK, headers, res).into_response()
+
+    headers
 }
@@ -23,7 +23,7 @@ use async_openai::{
     },
 };
 use axum::{
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Extension, Json,
 };
@@ -36,8 +36,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tracing::Instrument;
 
-use crate::{datafusion::DataFusion, http::v1::sql_to_http_response, model::LLMModelStore};
+use crate::{
+    auth::Principal,
+    datafusion::DataFusion,
+    http::v1::{query_labels, sql_to_http_response},
+    model::{record_token_usage, LLMModelStore},
+};
 
 fn clean_model_based_sql(input: &str) -> String {
     let no_dashes = match input.strip_prefix("--") {
@@ -66,6 +72,8 @@ fn default_model() -> String {
 pub(crate) async fn post(
     Extension(df): Extension<Arc<DataFusion>>,
     Extension(llms): Extension<Arc<RwLock<LLMModelStore>>>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
     Json(payload): Json<Request>,
 ) -> Response {
     // Get all public table CREATE TABLE statements to add to prompt.
@@ -112,8 +120,17 @@ pub(crate) async fn post(
                 )
                     .into_response();
             };
-            match nql_model.write().await.chat_request(req).await {
-                Ok(r) => r,
+            match nql_model
+                .write()
+                .await
+                .chat_request(req)
+                .instrument(tracing::info_span!("llm_inference", model = %payload.model))
+                .await
+            {
+                Ok(r) => {
+                    record_token_usage(&payload.model, r.usage.as_ref());
+                    r
+                }
                 Err(e) => {
                     tracing::error!("Error running NQL model: {e}");
                     return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
@@ -145,6 +162,8 @@ pub(crate) async fn post(
                 &cleaned_query,
                 Some(restricted_sql_options),
                 Some(nsql_query_copy),
+                principal.map(|Extension(principal)| principal),
+                query_labels(&headers),
             )
             .await
         }
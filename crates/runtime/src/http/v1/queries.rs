@@ -0,0 +1,171 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::sync::Arc;
+
+use arrow::array::RecordBatch;
+use axum::{
+    extract::{Path, Query},
+    http::status,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    auth::Principal,
+    datafusion::query::async_query::{AsyncQueryStatus, AsyncQueryStore},
+};
+
+use super::datasets::MessageResponse;
+
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PaginationParams {
+    #[serde(default)]
+    offset: usize,
+
+    #[serde(default = "default_page_size")]
+    limit: usize,
+}
+
+fn default_page_size() -> usize {
+    DEFAULT_PAGE_SIZE
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct QueryStatusResponse {
+    query_id: String,
+
+    #[serde(flatten)]
+    status: AsyncQueryStatus,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct QueryResultsResponse {
+    query_id: String,
+
+    #[serde(flatten)]
+    status: AsyncQueryStatus,
+
+    offset: usize,
+
+    total_rows: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_offset: Option<usize>,
+
+    rows: Vec<Value>,
+}
+
+fn parse_query_id(query_id: &str) -> Result<Uuid, Response> {
+    Uuid::parse_str(query_id).map_err(|_| query_not_found(query_id))
+}
+
+fn query_not_found(query_id: &str) -> Response {
+    (
+        status::StatusCode::NOT_FOUND,
+        Json(MessageResponse {
+            message: format!("Query {query_id} not found. Results of finished queries expire after runtime.async_queries.results_ttl."),
+        }),
+    )
+        .into_response()
+}
+
+pub(crate) async fn get(
+    Extension(async_queries): Extension<Arc<AsyncQueryStore>>,
+    principal: Option<Extension<Principal>>,
+    Path(query_id): Path<String>,
+) -> Response {
+    let id = match parse_query_id(&query_id) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    match async_queries.status(&id, principal.as_deref()) {
+        Some(status) => (
+            status::StatusCode::OK,
+            Json(QueryStatusResponse { query_id, status }),
+        )
+            .into_response(),
+        None => query_not_found(&query_id),
+    }
+}
+
+pub(crate) async fn results(
+    Extension(async_queries): Extension<Arc<AsyncQueryStore>>,
+    principal: Option<Extension<Principal>>,
+    Path(query_id): Path<String>,
+    Query(params): Query<PaginationParams>,
+) -> Response {
+    let id = match parse_query_id(&query_id) {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let Some(results) =
+        async_queries.results(&id, principal.as_deref(), params.offset, params.limit)
+    else {
+        return query_not_found(&query_id);
+    };
+
+    let status_code = match &results.status {
+        AsyncQueryStatus::Running => status::StatusCode::ACCEPTED,
+        AsyncQueryStatus::Completed => status::StatusCode::OK,
+        AsyncQueryStatus::Failed(_) => status::StatusCode::BAD_REQUEST,
+    };
+
+    let rows = match batches_to_json_rows(&results.batches) {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::debug!("Error converting results to JSON: {e}");
+            return (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+
+    let end = params.offset + rows.len();
+    let next_offset = (end < results.total_rows).then_some(end);
+
+    (
+        status_code,
+        Json(QueryResultsResponse {
+            query_id,
+            status: results.status,
+            offset: params.offset,
+            total_rows: results.total_rows,
+            next_offset,
+            rows,
+        }),
+    )
+        .into_response()
+}
+
+pub(crate) fn batches_to_json_rows(
+    batches: &[RecordBatch],
+) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let mut writer = arrow_json::ArrayWriter::new(Vec::new());
+    writer.write_batches(&batches.iter().collect::<Vec<&RecordBatch>>())?;
+    writer.finish()?;
+
+    let buf = writer.into_inner();
+    if buf.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(serde_json::from_slice(&buf)?)
+}
@@ -15,19 +15,157 @@ limitations under the License.
 */
 use std::sync::Arc;
 
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use arrow_ipc::{writer::StreamWriter, CompressionType};
+use async_stream::stream;
 use axum::{
-    body::Bytes,
-    http::StatusCode,
+    body::{Body, Bytes},
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Extension,
 };
-use datafusion::execution::context::SQLOptions;
+use datafusion::{
+    execution::{context::SQLOptions, SendableRecordBatchStream},
+    physical_plan::memory::MemoryStream,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    auth::Principal,
+    datafusion::{
+        query::{
+            async_query::{slice_batches, AsyncQueryStatus, AsyncQueryStore},
+            Protocol, Query as SqlQuery, QueryBuilder,
+        },
+        DataFusion,
+    },
+    error_info::ToErrorInfo,
+    ipc_compression::{self, COMPRESSION_HEADER},
+};
 
-use crate::datafusion::DataFusion;
+use super::{
+    add_warning_headers, cache_headers, error_response,
+    queries::{batches_to_json_rows, DEFAULT_PAGE_SIZE},
+    query_labels, sql_to_http_response,
+};
 
-use super::sql_to_http_response;
+const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const CSV_CONTENT_TYPE: &str = "text/csv";
+
+/// Holds the token to pass as `cursor` for the next page of a paginated query, if it has more rows.
+const CONTINUATION_TOKEN_HEADER: &str = "X-Continuation-Token";
+
+/// The format of the results returned by the SQL endpoint.
+///
+/// `Json` buffers the whole result into a single JSON array, all other formats are streamed as they are produced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultFormat {
+    #[default]
+    Json,
+    #[serde(alias = "jsonl")]
+    NdJson,
+    Csv,
+    #[serde(alias = "arrow_stream")]
+    Arrow,
+}
+
+impl ResultFormat {
+    /// Picks the result format from the first supported media type in an `Accept` header.
+    fn from_accept_header(headers: &HeaderMap) -> Option<Self> {
+        let accept = headers.get(header::ACCEPT)?.to_str().ok()?;
+        accept.split(',').find_map(|media_type| {
+            let media_type = media_type.split(';').next().unwrap_or_default().trim();
+            match media_type {
+                ARROW_STREAM_CONTENT_TYPE => Some(ResultFormat::Arrow),
+                NDJSON_CONTENT_TYPE | "application/jsonl" => Some(ResultFormat::NdJson),
+                CSV_CONTENT_TYPE => Some(ResultFormat::Csv),
+                "application/json" => Some(ResultFormat::Json),
+                _ => None,
+            }
+        })
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ResultFormat::Json => "application/json",
+            ResultFormat::NdJson => NDJSON_CONTENT_TYPE,
+            ResultFormat::Csv => CSV_CONTENT_TYPE,
+            ResultFormat::Arrow => ARROW_STREAM_CONTENT_TYPE,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryMode {
+    #[default]
+    Sync,
+    /// Run the query in the background and return its id, see `GET /v1/queries/:id/results`.
+    Async,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct QueryParams {
+    /// Overrides the format negotiated from the `Accept` header.
+    format: Option<ResultFormat>,
+
+    #[serde(default)]
+    mode: QueryMode,
+
+    /// Returns at most this many rows, keeping the rest of the results to page through with `cursor`.
+    max_rows: Option<usize>,
+
+    /// Returns the next page of a paginated query instead of running the request body.
+    cursor: Option<String>,
+}
+
+/// Where a paginated query continues from, given to clients as an opaque continuation token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    query_id: Uuid,
+    offset: usize,
+}
+
+impl Cursor {
+    fn encode(self) -> String {
+        format!("{}{:016x}", self.query_id.simple(), self.offset)
+    }
+
+    fn decode(token: &str) -> Option<Self> {
+        if token.len() != 48 || !token.is_ascii() {
+            return None;
+        }
+        let (query_id, offset) = token.split_at(32);
+
+        Some(Self {
+            query_id: Uuid::parse_str(query_id).ok()?,
+            offset: usize::from_str_radix(offset, 16).ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AsyncQueryResponse {
+    query_id: String,
+    status: &'static str,
+}
+
+pub(crate) async fn post(
+    Extension(df): Extension<Arc<DataFusion>>,
+    Extension(async_queries): Extension<Arc<AsyncQueryStore>>,
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<QueryParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let principal = principal.map(|Extension(principal)| principal);
+    let labels = query_labels(&headers);
 
-pub(crate) async fn post(Extension(df): Extension<Arc<DataFusion>>, body: Bytes) -> Response {
     let query = match String::from_utf8(body.to_vec()) {
         Ok(query) => query,
         Err(e) => {
@@ -36,10 +174,427 @@ pub(crate) async fn post(Extension(df): Extension<Arc<DataFusion>>, body: Bytes)
         }
     };
 
+    let format = params
+        .format
+        .or_else(|| ResultFormat::from_accept_header(&headers))
+        .unwrap_or_default();
+    let compression = ipc_compression::negotiate(
+        headers
+            .get(COMPRESSION_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    );
+
+    if params.max_rows == Some(0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "max_rows must be greater than zero",
+        )
+            .into_response();
+    }
+
+    if let Some(cursor) = &params.cursor {
+        return next_page(
+            &async_queries,
+            principal.as_ref(),
+            cursor,
+            params.max_rows.unwrap_or(DEFAULT_PAGE_SIZE),
+            format,
+            compression,
+        );
+    }
+
     let restricted_sql_options = SQLOptions::new()
         .with_allow_ddl(false)
         .with_allow_dml(false)
         .with_allow_statements(false);
 
-    sql_to_http_response(df, &query, Some(restricted_sql_options), None).await
+    if params.mode == QueryMode::Async {
+        let query_id = match async_queries.submit(
+            df,
+            query,
+            Some(restricted_sql_options),
+            principal,
+            labels,
+        ) {
+            Ok(query_id) => query_id,
+            Err(e) => return (StatusCode::TOO_MANY_REQUESTS, e.to_string()).into_response(),
+        };
+        let mut headers = HeaderMap::new();
+        if let Ok(location) = HeaderValue::from_str(&format!("/v1/queries/{query_id}")) {
+            headers.insert(header::LOCATION, location);
+        }
+        return (
+            StatusCode::ACCEPTED,
+            headers,
+            axum::Json(AsyncQueryResponse {
+                query_id: query_id.to_string(),
+                status: "running",
+            }),
+        )
+            .into_response();
+    }
+
+    if let Some(max_rows) = params.max_rows {
+        let owner = principal.clone();
+        let query = QueryBuilder::new(query, Arc::clone(&df), Protocol::Http)
+            .restricted_sql_options(Some(restricted_sql_options))
+            .principal(principal)
+            .labels(labels)
+            .build();
+        return first_page(
+            &async_queries,
+            owner.as_ref(),
+            query,
+            max_rows,
+            format,
+            compression,
+        )
+        .await;
+    }
+
+    if format == ResultFormat::Json {
+        return sql_to_http_response(
+            df,
+            &query,
+            Some(restricted_sql_options),
+            None,
+            principal,
+            labels,
+        )
+        .await;
+    }
+
+    let query = QueryBuilder::new(query, Arc::clone(&df), Protocol::Http)
+        .restricted_sql_options(Some(restricted_sql_options))
+        .principal(principal)
+        .labels(labels)
+        .build();
+
+    let query_result = match query.run().await {
+        Ok(query_result) => query_result,
+        Err(e) => {
+            tracing::debug!("Error executing query: {e}");
+            return error_response(e.to_string(), &e.error_info());
+        }
+    };
+
+    let mut headers = cache_headers(query_result.from_cache);
+    add_warning_headers(&mut headers, &query_result.warnings);
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    add_compression_header(&mut headers, format, compression);
+
+    // The body pulls batches from the query stream as the client consumes them, so a slow client applies backpressure to the query.
+    let body = match format {
+        ResultFormat::Arrow => Body::from_stream(arrow_stream(query_result.data, compression)),
+        ResultFormat::NdJson => Body::from_stream(ndjson_stream(query_result.data)),
+        ResultFormat::Csv => Body::from_stream(csv_stream(query_result.data)),
+        ResultFormat::Json => unreachable!("JSON results are buffered above"),
+    };
+
+    (StatusCode::OK, headers, body).into_response()
+}
+
+/// Runs `query` and returns up to `max_rows` of its rows. The results are kept for the following pages, so every
+/// page comes from the same snapshot no matter how the data changes in between. Only `principal` can read them.
+async fn first_page(
+    async_queries: &AsyncQueryStore,
+    principal: Option<&Principal>,
+    query: SqlQuery,
+    max_rows: usize,
+    format: ResultFormat,
+    compression: Option<CompressionType>,
+) -> Response {
+    let query_result = match query.run().await {
+        Ok(query_result) => query_result,
+        Err(e) => {
+            tracing::debug!("Error executing query: {e}");
+            return error_response(e.to_string(), &e.error_info());
+        }
+    };
+
+    let schema = query_result.data.schema();
+    let batches = match async_queries.collect(query_result.data).await {
+        Ok(batches) => batches,
+        Err(e) => {
+            tracing::debug!("Error executing query: {e}");
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+
+    let total_rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+    let page = slice_batches(&batches, 0, max_rows);
+    let next = if total_rows > max_rows {
+        match async_queries.insert_completed(principal, Arc::clone(&schema), batches) {
+            Ok(query_id) => Some(Cursor {
+                query_id,
+                offset: max_rows,
+            }),
+            Err(e) => return (StatusCode::TOO_MANY_REQUESTS, e.to_string()).into_response(),
+        }
+    } else {
+        None
+    };
+
+    let mut headers = cache_headers(query_result.from_cache);
+    add_warning_headers(&mut headers, &query_result.warnings);
+    page_response(format, compression, schema, page, headers, next)
+}
+
+fn next_page(
+    async_queries: &AsyncQueryStore,
+    principal: Option<&Principal>,
+    token: &str,
+    max_rows: usize,
+    format: ResultFormat,
+    compression: Option<CompressionType>,
+) -> Response {
+    let Some(cursor) = Cursor::decode(token) else {
+        return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response();
+    };
+
+    let results = async_queries.results(&cursor.query_id, principal, cursor.offset, max_rows);
+    let Some((schema, results)) = results
+        .filter(|results| results.status == AsyncQueryStatus::Completed)
+        .and_then(|results| Some((results.schema.clone()?, results)))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            "Cursor not found. Paginated results expire after runtime.async_queries.results_ttl.",
+        )
+            .into_response();
+    };
+
+    let page_rows: usize = results.batches.iter().map(RecordBatch::num_rows).sum();
+    let end = cursor.offset + page_rows;
+    let next = (end < results.total_rows).then_some(Cursor {
+        query_id: cursor.query_id,
+        offset: end,
+    });
+
+    page_response(
+        format,
+        compression,
+        schema,
+        results.batches,
+        HeaderMap::new(),
+        next,
+    )
+}
+
+/// Encodes a page of results in `format`, with the continuation token of the next page if there is one.
+fn page_response(
+    format: ResultFormat,
+    compression: Option<CompressionType>,
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+    mut headers: HeaderMap,
+    next: Option<Cursor>,
+) -> Response {
+    if let Some(next) = next {
+        if let Ok(token) = HeaderValue::from_str(&next.encode()) {
+            headers.insert(CONTINUATION_TOKEN_HEADER, token);
+        }
+    }
+
+    if format == ResultFormat::Json {
+        return match batches_to_json_rows(&batches) {
+            Ok(rows) => (StatusCode::OK, headers, axum::Json(rows)).into_response(),
+            Err(e) => {
+                tracing::debug!("Error converting results to JSON: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        };
+    }
+
+    let data: SendableRecordBatchStream = match MemoryStream::try_new(batches, schema, None) {
+        Ok(stream) => Box::pin(stream),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    add_compression_header(&mut headers, format, compression);
+    let body = match format {
+        ResultFormat::Arrow => Body::from_stream(arrow_stream(data, compression)),
+        ResultFormat::NdJson => Body::from_stream(ndjson_stream(data)),
+        ResultFormat::Csv => Body::from_stream(csv_stream(data)),
+        ResultFormat::Json => unreachable!("JSON pages are encoded above"),
+    };
+
+    (StatusCode::OK, headers, body).into_response()
+}
+
+/// Confirms the codec Arrow results are compressed with to the client.
+fn add_compression_header(
+    headers: &mut HeaderMap,
+    format: ResultFormat,
+    compression: Option<CompressionType>,
+) {
+    if let (ResultFormat::Arrow, Some(compression)) = (format, compression) {
+        headers.insert(
+            COMPRESSION_HEADER,
+            HeaderValue::from_static(ipc_compression::name(compression)),
+        );
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Encodes the record batches as an Arrow IPC stream, flushing the encoded bytes after each batch. The bodies of the
+/// batches are compressed with `compression`, if any.
+fn arrow_stream(
+    mut data: SendableRecordBatchStream,
+    compression: Option<CompressionType>,
+) -> impl futures::Stream<Item = Result<Bytes, BoxError>> {
+    stream! {
+        let schema = data.schema();
+        let options = ipc_compression::write_options(compression);
+        let mut writer = match StreamWriter::try_new_with_options(Vec::new(), &schema, options) {
+            Ok(writer) => writer,
+            Err(e) => {
+                yield Err(e.into());
+                return;
+            }
+        };
+        yield Ok(Bytes::from(std::mem::take(writer.get_mut())));
+
+        while let Some(batch) = data.next().await {
+            let batch = match batch {
+                Ok(batch) => batch,
+                Err(e) => {
+                    tracing::debug!("Error streaming query results: {e}");
+                    yield Err(e.into());
+                    return;
+                }
+            };
+            if let Err(e) = writer.write(&batch) {
+                yield Err(e.into());
+                return;
+            }
+            yield Ok(Bytes::from(std::mem::take(writer.get_mut())));
+        }
+
+        if let Err(e) = writer.finish() {
+            yield Err(e.into());
+            return;
+        }
+        yield Ok(Bytes::from(std::mem::take(writer.get_mut())));
+    }
+}
+
+/// Encodes the record batches as newline-delimited JSON, one object per row.
+fn ndjson_stream(
+    data: SendableRecordBatchStream,
+) -> impl futures::Stream<Item = Result<Bytes, BoxError>> {
+    data.map(|batch| {
+        let batch = batch?;
+        let mut writer = arrow_json::LineDelimitedWriter::new(Vec::new());
+        writer.write(&batch)?;
+        writer.finish()?;
+        Ok(Bytes::from(writer.into_inner()))
+    })
+}
+
+/// Encodes the record batches as CSV, writing the header row from the schema first, so empty results have one too.
+fn csv_stream(
+    data: SendableRecordBatchStream,
+) -> impl futures::Stream<Item = Result<Bytes, BoxError>> {
+    fn encode(batch: &RecordBatch, with_header: bool) -> Result<Bytes, BoxError> {
+        let mut buf = Vec::new();
+        arrow::csv::WriterBuilder::new()
+            .with_header(with_header)
+            .build(&mut buf)
+            .write(batch)?;
+        Ok(Bytes::from(buf))
+    }
+
+    let header = encode(&RecordBatch::new_empty(data.schema()), true);
+    futures::stream::once(async move { header }).chain(data.map(|batch| encode(&batch?, false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    fn results(rows: i64) -> SendableRecordBatchStream {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batches = if rows == 0 {
+            vec![]
+        } else {
+            let ids = (0..rows).collect::<Vec<_>>();
+            let names = ids.iter().map(|id| format!("n{id}")).collect::<Vec<_>>();
+            vec![RecordBatch::try_new(
+                Arc::clone(&schema),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .expect("valid batch")]
+        };
+        Box::pin(MemoryStream::try_new(batches, schema, None).expect("valid stream"))
+    }
+
+    async fn body(stream: impl futures::Stream<Item = Result<Bytes, BoxError>>) -> Vec<u8> {
+        stream
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("encoded results")
+            .concat()
+    }
+
+    #[tokio::test]
+    async fn test_result_formats() {
+        let csv = body(csv_stream(results(2))).await;
+        assert_eq!(String::from_utf8_lossy(&csv), "id,name\n0,n0\n1,n1\n");
+        let csv = body(csv_stream(results(0))).await;
+        assert_eq!(String::from_utf8_lossy(&csv), "id,name\n");
+
+        let ndjson = body(ndjson_stream(results(2))).await;
+        assert_eq!(
+            String::from_utf8_lossy(&ndjson),
+            "{\"id\":0,\"name\":\"n0\"}\n{\"id\":1,\"name\":\"n1\"}\n"
+        );
+
+        let arrow = body(arrow_stream(results(2), None)).await;
+        let reader =
+            arrow_ipc::reader::StreamReader::try_new(arrow.as_slice(), None).expect("ipc stream");
+        assert_eq!(reader.schema().fields().len(), 2);
+        let rows = reader
+            .map(|batch| batch.expect("batch").num_rows())
+            .sum::<usize>();
+        assert_eq!(rows, 2);
+        let arrow = body(arrow_stream(results(0), None)).await;
+        let reader =
+            arrow_ipc::reader::StreamReader::try_new(arrow.as_slice(), None).expect("ipc stream");
+        assert_eq!(reader.count(), 0);
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor {
+            query_id: Uuid::new_v4(),
+            offset: 12_345,
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+
+        assert_eq!(Cursor::decode(""), None);
+        assert_eq!(Cursor::decode(&format!("{}zz", cursor.encode())), None);
+        assert_eq!(Cursor::decode(&"g".repeat(48)), None);
+    }
 }
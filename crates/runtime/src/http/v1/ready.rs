@@ -16,16 +16,137 @@ limitations under the License.
 
 use std::sync::Arc;
 
-use crate::datafusion::DataFusion;
+use crate::{
+    datafusion::DataFusion,
+    shutdown,
+    status::{self, ComponentState, ComponentStates, ComponentStatus},
+};
+use app::App;
 use axum::{
-    http::status,
+    http::StatusCode,
     response::{IntoResponse, Response},
     Extension,
 };
+use tokio::sync::RwLock;
+
+/// Reports whether the runtime can serve queries, gated on the datasets listed in `runtime.readiness`. A runtime
+/// that is shutting down is never ready.
+pub(crate) async fn get(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+) -> Response {
+    if shutdown::is_draining() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Shutting Down").into_response();
+    }
+
+    let required_datasets = app.read().await.as_ref().and_then(required_datasets);
+
+    if is_ready(
+        &status::component_states(),
+        required_datasets.as_deref(),
+        df.is_initial_load_complete(),
+    ) {
+        return (StatusCode::OK, "Ready").into_response();
+    }
+    (StatusCode::SERVICE_UNAVAILABLE, "Not Ready").into_response()
+}
+
+/// The datasets listed in `runtime.readiness` and the datasets marked as `critical`, or `None` to wait for every
+/// dataset when neither is set.
+fn required_datasets(app: &App) -> Option<Vec<String>> {
+    let critical = app
+        .datasets
+        .iter()
+        .filter(|dataset| dataset.critical)
+        .map(|dataset| dataset.name.clone());
+
+    match &app.runtime.readiness.datasets {
+        Some(datasets) => {
+            let mut required = datasets.clone();
+            required.extend(critical.filter(|name| !datasets.contains(name)));
+            Some(required)
+        }
+        None => {
+            let critical: Vec<_> = critical.collect();
+            (!critical.is_empty()).then_some(critical)
+        }
+    }
+}
+
+/// Without a list of datasets, the runtime is ready once every dataset has finished loading, successfully
+/// or not. Listed datasets must all have loaded successfully.
+fn is_ready(
+    states: &ComponentStates,
+    required_datasets: Option<&[String]>,
+    initial_load_complete: bool,
+) -> bool {
+    match required_datasets {
+        None => {
+            initial_load_complete
+                && states
+                    .datasets
+                    .values()
+                    .all(|state| state.is_loaded() || state.status == ComponentStatus::Error)
+        }
+        Some(datasets) => datasets.iter().all(|dataset| {
+            states
+                .datasets
+                .get(dataset)
+                .is_some_and(ComponentState::is_loaded)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::is_ready;
+    use crate::status::{ComponentState, ComponentStates, ComponentStatus};
+
+    fn states(datasets: &[(&str, ComponentStatus, bool)]) -> ComponentStates {
+        let mut states = ComponentStates::default();
+        for (name, status, refreshed) in datasets {
+            states.datasets.insert(
+                (*name).to_string(),
+                ComponentState {
+                    status: *status,
+                    last_refresh: refreshed.then(Utc::now),
+                    error: None,
+                    violations: vec![],
+                    pii_columns: std::collections::BTreeMap::new(),
+                },
+            );
+        }
+        states
+    }
+
+    #[test]
+    fn test_ready_waits_for_initial_load() {
+        let loaded = states(&[
+            ("orders", ComponentStatus::Ready, false),
+            ("customers", ComponentStatus::Error, false),
+        ]);
+        assert!(!is_ready(&loaded, None, false));
+        assert!(is_ready(&loaded, None, true));
+
+        let loading = states(&[("orders", ComponentStatus::Initializing, false)]);
+        assert!(!is_ready(&loading, None, true));
+    }
+
+    #[test]
+    fn test_ready_required_datasets() {
+        let states = states(&[
+            ("orders", ComponentStatus::Refreshing, true),
+            ("customers", ComponentStatus::Error, false),
+        ]);
+        let orders = ["orders".to_string()];
+        let both = ["orders".to_string(), "customers".to_string()];
+        let unknown = ["events".to_string()];
 
-pub(crate) async fn get(Extension(df): Extension<Arc<DataFusion>>) -> Response {
-    if df.is_initial_load_complete() {
-        return (status::StatusCode::OK, "Ready").into_response();
+        assert!(is_ready(&states, Some(&orders), false));
+        assert!(!is_ready(&states, Some(&both), true));
+        assert!(!is_ready(&states, Some(&unknown), true));
+        assert!(is_ready(&states, Some(&[]), false));
     }
-    (status::StatusCode::SERVICE_UNAVAILABLE, "Not Ready").into_response()
 }
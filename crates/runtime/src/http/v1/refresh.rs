@@ -0,0 +1,142 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Controls the refreshes of every accelerated dataset at once, i.e. to pause them during a maintenance window of
+//! the sources, or to reload everything after an incident.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use app::App;
+use axum::{
+    http::status,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::{
+    accelerated_table::refresh, audit::AuditAction, auth::Principal, datafusion::DataFusion,
+};
+
+use super::{audit, datasets::MessageResponse};
+
+/// The resource the audit events of runtime-wide refresh controls are recorded against.
+const ALL_DATASETS: &str = "*";
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RefreshStatus {
+    paused: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RefreshAllResponse {
+    triggered: Vec<String>,
+    failed: BTreeMap<String, String>,
+}
+
+pub(crate) async fn get() -> Response {
+    (
+        status::StatusCode::OK,
+        Json(RefreshStatus {
+            paused: refresh::refreshes_paused(),
+        }),
+    )
+        .into_response()
+}
+
+/// Pauses the refreshes of every accelerated dataset until they are resumed.
+pub(crate) async fn pause(
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+) -> Response {
+    refresh::pause_refreshes();
+    tracing::info!("[refresh] Refreshes paused");
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::RefreshPause,
+        ALL_DATASETS,
+        None,
+    );
+    get().await
+}
+
+pub(crate) async fn resume(
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+) -> Response {
+    refresh::resume_refreshes();
+    tracing::info!("[refresh] Refreshes resumed");
+    audit(
+        &df,
+        principal.as_deref(),
+        AuditAction::RefreshResume,
+        ALL_DATASETS,
+        None,
+    );
+    get().await
+}
+
+/// Triggers a refresh of every accelerated dataset that supports manual refreshes.
+pub(crate) async fn post(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+) -> Response {
+    if refresh::refreshes_paused() {
+        return (
+            status::StatusCode::CONFLICT,
+            Json(MessageResponse {
+                message: "Refreshes are paused, resume them to refresh the datasets".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let app_lock = app.read().await;
+    let Some(readable_app) = &*app_lock else {
+        return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+    };
+
+    let mut response = RefreshAllResponse {
+        triggered: Vec::new(),
+        failed: BTreeMap::new(),
+    };
+    for dataset in readable_app
+        .datasets
+        .iter()
+        .filter(|d| d.acceleration.as_ref().is_some_and(|a| a.enabled))
+    {
+        let result = df.refresh_table(&dataset.name).await;
+        audit(
+            &df,
+            principal.as_deref(),
+            AuditAction::DatasetRefresh,
+            &dataset.name,
+            result.as_ref().err().map(ToString::to_string),
+        );
+        match result {
+            Ok(_) => response.triggered.push(dataset.name.clone()),
+            Err(e) => {
+                response.failed.insert(dataset.name.clone(), e.to_string());
+            }
+        }
+    }
+
+    (status::StatusCode::CREATED, Json(response)).into_response()
+}
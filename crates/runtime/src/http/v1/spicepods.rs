@@ -27,6 +27,8 @@ use serde::{Deserialize, Serialize};
 use spicepod::Spicepod;
 use tokio::sync::RwLock;
 
+use crate::{validation::Diagnostic, Runtime};
+
 use super::{convert_entry_to_csv, Format};
 
 #[derive(Debug, Deserialize)]
@@ -94,3 +96,8 @@ pub(crate) async fn get(
         }
     }
 }
+
+/// Reports every problem with the spicepod and its components, without reloading anything.
+pub(crate) async fn validate(Extension(rt): Extension<Arc<Runtime>>) -> Json<Vec<Diagnostic>> {
+    Json(rt.validate().await)
+}
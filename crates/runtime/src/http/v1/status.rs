@@ -16,18 +16,23 @@ limitations under the License.
 use csv::Writer;
 use flight_client::FlightClient;
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, sync::Arc};
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
 use tonic_0_9_0::transport::Channel;
 use tonic_health::{pb::health_client::HealthClient, ServingStatus};
 
 use axum::{
     extract::Query,
-    http::status,
+    http::StatusCode,
     response::{IntoResponse, Response},
     Extension, Json,
 };
 
-use crate::{config, status::ComponentStatus};
+use crate::{
+    config,
+    connector_health::{self, ConnectorState},
+    quotas::{self, NamespaceUsage},
+    status::{self, ComponentStates, ComponentStatus},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -50,6 +55,19 @@ pub struct ConnectionDetails {
     status: ComponentStatus,
 }
 
+/// The endpoints of the runtime, and the state of each dataset, model and extension it loaded.
+#[derive(Serialize)]
+pub struct RuntimeStatus {
+    connections: Vec<ConnectionDetails>,
+    #[serde(flatten)]
+    components: ComponentStates,
+    /// The health of the data connector of each dataset.
+    connectors: BTreeMap<String, ConnectorState>,
+    /// The usage and quotas of each namespace.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    namespaces: BTreeMap<String, NamespaceUsage>,
+}
+
 fn default_format() -> Format {
     Format::Json
 }
@@ -109,12 +127,21 @@ pub(crate) async fn get(
     ];
 
     match params.format {
-        Format::Json => (status::StatusCode::OK, Json(details)).into_response(),
+        Format::Json => (
+            StatusCode::OK,
+            Json(RuntimeStatus {
+                connections: details,
+                components: status::component_states(),
+                connectors: connector_health::connector_states(),
+                namespaces: quotas::usage(),
+            }),
+        )
+            .into_response(),
         Format::Csv => match convert_details_to_csv(&details) {
-            Ok(csv) => (status::StatusCode::OK, csv).into_response(),
+            Ok(csv) => (StatusCode::OK, csv).into_response(),
             Err(e) => {
                 tracing::error!("Error converting to CSV: {e}");
-                (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
             }
         },
     }
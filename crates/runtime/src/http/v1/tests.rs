@@ -0,0 +1,76 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use app::App;
+use axum::{
+    extract::Query,
+    http::status,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Deserialize;
+use spicepod::component::test::Test;
+use tokio::sync::RwLock;
+
+use crate::{data_tests, datafusion::DataFusion};
+
+use super::datasets::MessageResponse;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RunParams {
+    /// Only run the test with this name.
+    name: Option<String>,
+}
+
+/// Runs the tests of the spicepod, responding with the result of each test. The response is `200 OK` even when tests
+/// fail, with `success` set to whether every test passed.
+pub(crate) async fn run(
+    Extension(app): Extension<Arc<RwLock<Option<App>>>>,
+    Extension(df): Extension<Arc<DataFusion>>,
+    Query(params): Query<RunParams>,
+) -> Response {
+    let tests: Vec<Test> = {
+        let app_lock = app.read().await;
+        let Some(readable_app) = &*app_lock else {
+            return (status::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+        };
+
+        readable_app
+            .tests
+            .iter()
+            .filter(|t| params.name.as_ref().map_or(true, |name| &t.name == name))
+            .cloned()
+            .collect()
+    };
+
+    if let (Some(name), true) = (&params.name, tests.is_empty()) {
+        return (
+            status::StatusCode::NOT_FOUND,
+            Json(MessageResponse {
+                message: format!("Test {name} not found"),
+            }),
+        )
+            .into_response();
+    }
+
+    (
+        status::StatusCode::OK,
+        Json(data_tests::run(&df, &tests).await),
+    )
+        .into_response()
+}
@@ -0,0 +1,324 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use arrow::array::RecordBatch;
+use arrow_ipc::writer::StreamWriter;
+use axum::{
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    response::Response,
+    Extension,
+};
+use datafusion::execution::{context::SQLOptions, SendableRecordBatchStream};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Instant, MissedTickBehavior};
+
+use crate::{
+    auth::Principal,
+    datafusion::{
+        query::{Protocol, QueryBuilder},
+        DataFusion,
+    },
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+/// The maximum in-memory size of the results of a watched query, which are encoded to compare with the last frame.
+const MAX_RESULT_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FrameFormat {
+    /// Text frames with the rows as a JSON array.
+    #[default]
+    Json,
+    /// Binary frames with the result encoded as an Arrow IPC stream.
+    Arrow,
+}
+
+/// Sent by the client to start (or replace) the query watched on the socket.
+#[derive(Debug, Deserialize)]
+struct WatchRequest {
+    sql: String,
+
+    /// How often the query is re-evaluated, e.g. `5s`. Defaults to `1s`.
+    interval: Option<String>,
+
+    #[serde(default)]
+    format: FrameFormat,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+enum ServerMessage {
+    /// All the rows of the query, replacing those of the previous frame.
+    Results {
+        rows: serde_json::Value,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Upgrades the connection to a WebSocket that re-runs a watched query on an interval, and pushes its full results
+/// whenever they differ from the last ones pushed. Results aren't incremental: each frame replaces the previous one.
+pub(crate) async fn get(
+    Extension(df): Extension<Arc<DataFusion>>,
+    principal: Option<Extension<Principal>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let principal = principal.map(|Extension(principal)| principal);
+    ws.on_upgrade(move |socket| handle_socket(socket, df, principal))
+}
+
+async fn handle_socket(mut socket: WebSocket, df: Arc<DataFusion>, principal: Option<Principal>) {
+    let mut watched: Option<WatchRequest> = None;
+    let mut last_frame: Option<Vec<u8>> = None;
+    let mut ticker = tokio::time::interval(DEFAULT_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // The socket is closed when the token it was opened with expires, as it isn't checked again.
+    let expiry = principal.as_ref().and_then(expires_at);
+
+    loop {
+        tokio::select! {
+            () = sleep_until(expiry) => {
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "token expired".into(),
+                    })))
+                    .await;
+                return;
+            }
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                    Some(Ok(_)) => continue,
+                };
+
+                let request = match serde_json::from_str::<WatchRequest>(&text) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        let message = format!("Invalid watch request: {e}");
+                        if send_error(&mut socket, message).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+
+                let interval = match request.interval.as_deref().map(fundu::parse_duration) {
+                    Some(Ok(interval)) => interval.max(MIN_INTERVAL),
+                    Some(Err(e)) => {
+                        let message = format!("Invalid interval: {e}");
+                        if send_error(&mut socket, message).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                    None => DEFAULT_INTERVAL,
+                };
+
+                ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                last_frame = None;
+                watched = Some(request);
+            }
+            _ = ticker.tick(), if watched.is_some() => {
+                let Some(request) = &watched else {
+                    continue;
+                };
+
+                let frame = match evaluate(&df, request, principal.clone()).await {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        tracing::debug!("Error evaluating watched query: {e}");
+                        if send_error(&mut socket, e.to_string()).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+
+                // Only push results that differ from what the client already has.
+                if last_frame.as_ref() == Some(&frame) {
+                    continue;
+                }
+
+                let message = match request.format {
+                    FrameFormat::Json => match String::from_utf8(frame.clone()) {
+                        Ok(text) => Message::Text(text),
+                        Err(e) => {
+                            tracing::debug!("Error encoding watched query results: {e}");
+                            continue;
+                        }
+                    },
+                    FrameFormat::Arrow => Message::Binary(frame.clone()),
+                };
+
+                if socket.send(message).await.is_err() {
+                    return;
+                }
+                last_frame = Some(frame);
+            }
+        }
+    }
+}
+
+/// When the token of `principal` expires, from its `exp` claim.
+fn expires_at(principal: &Principal) -> Option<Instant> {
+    let exp = principal.claim("exp")?.parse::<f64>().ok()?;
+    let remaining = Duration::try_from_secs_f64(exp)
+        .ok()?
+        .saturating_sub(SystemTime::now().duration_since(UNIX_EPOCH).ok()?);
+    Some(Instant::now() + remaining)
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Collects the results of the watched query, failing once they exceed `max_size`.
+async fn collect(
+    mut data: SendableRecordBatchStream,
+    max_size: usize,
+) -> Result<Vec<RecordBatch>, BoxError> {
+    let mut batches = vec![];
+    let mut size = 0;
+    while let Some(batch) = data.next().await {
+        let batch = batch?;
+        size += batch.get_array_memory_size();
+        if size > max_size {
+            return Err(format!(
+                "The results of the watched query exceed {} MiB. Aggregate or limit the query.",
+                max_size / (1024 * 1024)
+            )
+            .into());
+        }
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+/// Runs the watched query and encodes the full result as a single frame.
+async fn evaluate(
+    df: &Arc<DataFusion>,
+    request: &WatchRequest,
+    principal: Option<Principal>,
+) -> Result<Vec<u8>, BoxError> {
+    let restricted_sql_options = SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false)
+        .with_allow_statements(false);
+
+    let query = QueryBuilder::new(request.sql.clone(), Arc::clone(df), Protocol::Http)
+        .restricted_sql_options(Some(restricted_sql_options))
+        .principal(principal)
+        .build();
+
+    let data = query.run().await?.data;
+    let schema = data.schema();
+    let batches = collect(data, MAX_RESULT_SIZE).await?;
+
+    match request.format {
+        FrameFormat::Json => {
+            let mut writer = arrow_json::ArrayWriter::new(Vec::new());
+            writer.write_batches(&batches.iter().collect::<Vec<&RecordBatch>>())?;
+            writer.finish()?;
+            let buf = writer.into_inner();
+            let rows = if buf.is_empty() {
+                serde_json::Value::Array(vec![])
+            } else {
+                serde_json::from_slice(&buf)?
+            };
+            Ok(serde_json::to_vec(&ServerMessage::Results { rows })?)
+        }
+        FrameFormat::Arrow => {
+            let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+            for batch in &batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+            Ok(writer.into_inner()?)
+        }
+    }
+}
+
+async fn send_error(socket: &mut WebSocket, message: String) -> Result<(), axum::Error> {
+    let payload = serde_json::to_string(&ServerMessage::Error { message })
+        .unwrap_or_else(|_| r#"{"type":"error"}"#.to_string());
+    socket.send(Message::Text(payload)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::Int64Array,
+        datatypes::{DataType, Field, Schema},
+    };
+    use datafusion::physical_plan::memory::MemoryStream;
+    use serde_json::{Map, Value};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bounded_results_and_expiry() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![Arc::new(Int64Array::from((0..1024).collect::<Vec<i64>>()))],
+        )
+        .expect("valid batch");
+        let size = batch.get_array_memory_size();
+        let data = || -> SendableRecordBatchStream {
+            Box::pin(
+                MemoryStream::try_new(
+                    vec![batch.clone(), batch.clone()],
+                    Arc::clone(&schema),
+                    None,
+                )
+                .expect("valid stream"),
+            )
+        };
+        assert_eq!(collect(data(), 2 * size).await.expect("results").len(), 2);
+        assert!(collect(data(), 2 * size - 1).await.is_err());
+
+        let principal = |exp: Option<u64>| Principal {
+            subject: "alice".to_string(),
+            claims: exp
+                .map(|exp| Map::from_iter([("exp".to_string(), Value::from(exp))]))
+                .unwrap_or_default(),
+            admin: false,
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time after epoch")
+            .as_secs();
+        assert!(expires_at(&principal(None)).is_none());
+        assert!(expires_at(&principal(Some(now - 60))).is_some_and(|at| at <= Instant::now()));
+        assert!(expires_at(&principal(Some(now + 60))).is_some_and(|at| at > Instant::now()));
+    }
+}
@@ -0,0 +1,344 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Records the calls of models, LLMs and embedding models in `runtime.inference_log`, i.e.
+//! `SELECT model, sum(prompt_tokens), avg(duration_ms) FROM runtime.inference_log GROUP BY model`.
+//!
+//! Prompts and responses are recorded as their SHA-256 hashes, unless `runtime.inference_log.full_text` is set, so
+//! identical prompts can still be grouped without storing their text.
+
+use std::{
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+
+use arrow::{
+    array::{RecordBatch, StringArray, TimestampNanosecondArray, UInt32Array, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use datafusion::sql::TableReference;
+use sha2::{Digest, Sha256};
+use snafu::prelude::*;
+use spicepod::component::runtime::InferenceLog as InferenceLogConfig;
+use tokio::sync::mpsc;
+
+use crate::{
+    accelerated_table::{refresh::Refresh, Retention},
+    component::dataset::{acceleration::Acceleration, TimeFormat},
+    datafusion::{DataFusion, SPICE_RUNTIME_SCHEMA},
+    dataupdate::{DataUpdate, UpdateType},
+    internal_table::create_internal_accelerated_table,
+};
+
+pub const DEFAULT_INFERENCE_LOG_TABLE: &str = "inference_log";
+
+const DEFAULT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const MAX_BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid runtime.inference_log.retention {retention}: {source}"))]
+    InvalidRetention {
+        retention: String,
+        source: fundu::ParseError,
+    },
+
+    #[snafu(display("Unable to create the inference_log table: {source}"))]
+    UnableToCreateTable {
+        source: crate::internal_table::Error,
+    },
+
+    #[snafu(display("Unable to register the inference_log table: {source}"))]
+    UnableToRegisterTable { source: crate::datafusion::Error },
+
+    #[snafu(display("Unable to write to the inference_log table: {source}"))]
+    UnableToWriteTable { source: crate::datafusion::Error },
+
+    #[snafu(display("Unable to create inference_log row: {source}"))]
+    UnableToCreateRow { source: arrow::error::ArrowError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferenceKind {
+    Predict,
+    Chat,
+    Embedding,
+    AiFunction,
+}
+
+impl std::fmt::Display for InferenceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InferenceKind::Predict => write!(f, "predict"),
+            InferenceKind::Chat => write!(f, "chat"),
+            InferenceKind::Embedding => write!(f, "embedding"),
+            InferenceKind::AiFunction => write!(f, "ai_function"),
+        }
+    }
+}
+
+/// A call of a model.
+#[derive(Debug, Clone)]
+pub struct InferenceRecord {
+    pub kind: InferenceKind,
+    pub model: String,
+    /// The prompt, messages or other input of the call.
+    pub input: Option<String>,
+    pub output: Option<String>,
+    /// The other parameters of the call, as JSON.
+    pub params: Option<String>,
+    pub duration: Duration,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub error: Option<String>,
+}
+
+impl InferenceRecord {
+    #[must_use]
+    pub fn new(kind: InferenceKind, model: impl Into<String>, duration: Duration) -> Self {
+        Self {
+            kind,
+            model: model.into(),
+            input: None,
+            output: None,
+            params: None,
+            duration,
+            prompt_tokens: None,
+            completion_tokens: None,
+            error: None,
+        }
+    }
+}
+
+struct InferenceEvent {
+    time: SystemTime,
+    record: InferenceRecord,
+}
+
+/// Hands off the calls of models to a background writer for the `inference_log` table.
+pub struct InferenceLog {
+    full_text: bool,
+    sender: mpsc::UnboundedSender<InferenceEvent>,
+}
+
+impl InferenceLog {
+    pub async fn try_new(config: &InferenceLogConfig, df: &Arc<DataFusion>) -> Result<Arc<Self>> {
+        let retention = match &config.retention {
+            Some(retention) => fundu::parse_duration(retention).context(InvalidRetentionSnafu {
+                retention: retention.clone(),
+            })?,
+            None => DEFAULT_RETENTION,
+        };
+        register_table(df, retention).await?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(write_events(receiver, Arc::downgrade(df)));
+
+        Ok(Arc::new(Self {
+            full_text: config.full_text,
+            sender,
+        }))
+    }
+
+    pub fn record(&self, mut record: InferenceRecord) {
+        if !self.full_text {
+            record.input = record.input.as_deref().map(hash);
+            record.output = record.output.as_deref().map(hash);
+        }
+
+        let event = InferenceEvent {
+            time: SystemTime::now(),
+            record,
+        };
+        if self.sender.send(event).is_err() {
+            tracing::error!("Inference log writer stopped, dropping inference record");
+        }
+    }
+}
+
+/// The SHA-256 of `text`, as lowercase hex.
+fn hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+async fn write_events(mut receiver: mpsc::UnboundedReceiver<InferenceEvent>, df: Weak<DataFusion>) {
+    while let Some(event) = receiver.recv().await {
+        let mut events = vec![event];
+        while events.len() < MAX_BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(_) => break,
+            }
+        }
+
+        let Some(df) = df.upgrade() else {
+            return;
+        };
+        if let Err(e) = write(&df, &events).await {
+            tracing::error!("Unable to write {} inference records: {e}", events.len());
+        }
+    }
+}
+
+async fn write(df: &DataFusion, events: &[InferenceEvent]) -> Result<()> {
+    let data_update = DataUpdate {
+        schema: table_schema(),
+        data: vec![to_record_batch(events)?],
+        update_type: UpdateType::Append,
+    };
+    df.write_data(table_reference(), data_update)
+        .await
+        .context(UnableToWriteTableSnafu)
+}
+
+#[must_use]
+pub fn table_reference() -> TableReference {
+    TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_INFERENCE_LOG_TABLE)
+}
+
+fn table_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new("kind", DataType::Utf8, false),
+        Field::new("model", DataType::Utf8, false),
+        Field::new("input", DataType::Utf8, true),
+        Field::new("output", DataType::Utf8, true),
+        Field::new("params", DataType::Utf8, true),
+        Field::new("duration_ms", DataType::UInt64, false),
+        Field::new("prompt_tokens", DataType::UInt32, true),
+        Field::new("completion_tokens", DataType::UInt32, true),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("error", DataType::Utf8, true),
+    ]))
+}
+
+async fn register_table(df: &Arc<DataFusion>, retention: Duration) -> Result<()> {
+    let retention = Retention::new(
+        Some("timestamp".to_string()),
+        Some(TimeFormat::UnixSeconds),
+        Some(retention),
+        Some(Duration::from_secs(300)),
+        true,
+    );
+    let table = create_internal_accelerated_table(
+        table_reference(),
+        table_schema(),
+        Acceleration::default(),
+        Refresh::default(),
+        retention,
+    )
+    .await
+    .context(UnableToCreateTableSnafu)?;
+
+    df.register_runtime_table(table_reference(), table)
+        .context(UnableToRegisterTableSnafu)
+}
+
+fn to_record_batch(events: &[InferenceEvent]) -> Result<RecordBatch> {
+    let timestamp = |time: SystemTime| {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .and_then(|duration| i64::try_from(duration.as_nanos()).ok())
+            .unwrap_or_default()
+    };
+
+    RecordBatch::try_new(
+        table_schema(),
+        vec![
+            Arc::new(TimestampNanosecondArray::from_iter_values(
+                events.iter().map(|e| timestamp(e.time)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                events.iter().map(|e| e.record.kind.to_string()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                events.iter().map(|e| e.record.model.clone()),
+            )),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.record.input.clone()),
+            )),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.record.output.clone()),
+            )),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.record.params.clone()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(events.iter().map(|e| {
+                u64::try_from(e.record.duration.as_millis()).unwrap_or(u64::MAX)
+            }))),
+            Arc::new(UInt32Array::from_iter(
+                events.iter().map(|e| e.record.prompt_tokens),
+            )),
+            Arc::new(UInt32Array::from_iter(
+                events.iter().map(|e| e.record.completion_tokens),
+            )),
+            Arc::new(StringArray::from_iter_values(events.iter().map(|e| {
+                if e.record.error.is_some() {
+                    "error"
+                } else {
+                    "success"
+                }
+            }))),
+            Arc::new(StringArray::from_iter(
+                events.iter().map(|e| e.record.error.clone()),
+            )),
+        ],
+    )
+    .context(UnableToCreateRowSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_prompts_and_responses_unless_full_text() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut record = InferenceRecord::new(InferenceKind::Chat, "gpt", Duration::ZERO);
+        record.input = Some("hello".to_string());
+        record.params = Some("{}".to_string());
+
+        InferenceLog {
+            full_text: false,
+            sender: sender.clone(),
+        }
+        .record(record.clone());
+        let hashed = receiver.try_recv().expect("recorded").record;
+        assert_eq!(
+            hashed.input.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(hashed.params.as_deref(), Some("{}"));
+
+        InferenceLog {
+            full_text: true,
+            sender,
+        }
+        .record(record);
+        let full = receiver.try_recv().expect("recorded").record;
+        assert_eq!(full.input.as_deref(), Some("hello"));
+    }
+}
@@ -0,0 +1,74 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Negotiates the compression of the Arrow IPC payloads of query results, which cuts the transfer time of large
+//! results for remote clients at the cost of some CPU on both ends.
+//!
+//! Clients opt in with the `X-Spice-Arrow-Compression` HTTP header or Flight metadata, listing the codecs they accept
+//! in order of preference, i.e. `zstd, lz4`. The record batch bodies are then compressed with the first supported
+//! codec, which Arrow IPC readers detect from the messages themselves.
+
+use arrow_ipc::{writer::IpcWriteOptions, CompressionType};
+
+/// The HTTP header and Flight metadata key clients list the codecs they accept with.
+pub const COMPRESSION_HEADER: &str = "x-spice-arrow-compression";
+
+/// The first codec of `accepted` the runtime supports, if any.
+#[must_use]
+pub fn negotiate(accepted: Option<&str>) -> Option<CompressionType> {
+    accepted?
+        .split(',')
+        .find_map(|codec| match codec.trim().to_ascii_lowercase().as_str() {
+            "zstd" => Some(CompressionType::ZSTD),
+            "lz4" | "lz4_frame" => Some(CompressionType::LZ4_FRAME),
+            _ => None,
+        })
+}
+
+/// The name of `compression` to confirm the negotiated codec to the client with.
+#[must_use]
+pub fn name(compression: CompressionType) -> &'static str {
+    if compression == CompressionType::LZ4_FRAME {
+        "lz4"
+    } else {
+        "zstd"
+    }
+}
+
+/// The options to write Arrow IPC messages compressed with `compression` with.
+#[must_use]
+pub fn write_options(compression: Option<CompressionType>) -> IpcWriteOptions {
+    // Compression only fails for IPC metadata versions before V5, which the default options don't use.
+    IpcWriteOptions::default()
+        .try_with_compression(compression)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_first_supported_codec() {
+        assert_eq!(
+            negotiate(Some("gzip, LZ4, zstd")),
+            Some(CompressionType::LZ4_FRAME)
+        );
+        assert_eq!(negotiate(Some("zstd")), Some(CompressionType::ZSTD));
+        assert_eq!(negotiate(Some("gzip")), None);
+        assert_eq!(negotiate(None), None);
+    }
+}
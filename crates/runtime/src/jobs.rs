@@ -0,0 +1,452 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runs the SQL jobs of the spicepod on their cron schedules, and records each run in `spice.runtime.job_history`.
+//!
+//! The schedules are checked at the start of every minute against the jobs of the current app, so jobs added or
+//! changed by a reload of the spicepod take effect from the next minute. A job that is still running when it is next
+//! due isn't started again, and the skipped run is recorded in the history.
+
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use arrow::{
+    array::{RecordBatch, StringArray, TimestampNanosecondArray, UInt32Array, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use chrono::{DateTime, Datelike, DurationRound, TimeDelta, TimeZone, Timelike, Utc};
+use datafusion::sql::TableReference;
+use futures::StreamExt;
+use snafu::{ResultExt, Snafu};
+use spicepod::component::job::Job;
+
+use crate::{
+    accelerated_table::{refresh::Refresh, AcceleratedTable, Retention},
+    component::dataset::{acceleration::Acceleration, TimeFormat},
+    datafusion::{
+        query::{Protocol, QueryBuilder},
+        DataFusion, SPICE_RUNTIME_SCHEMA,
+    },
+    dataupdate::{DataUpdate, UpdateType},
+    internal_table::create_internal_accelerated_table,
+    Runtime,
+};
+
+pub const DEFAULT_JOB_HISTORY_TABLE: &str = "job_history";
+
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Error registering table: {source}"))]
+    UnableToRegisterTable {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Error writing to job_history table: {source}"))]
+    UnableToWriteToTable {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Error creating job_history row: {source}"))]
+    UnableToCreateRow { source: arrow::error::ArrowError },
+
+    #[snafu(display("Invalid schedule {schedule}: {reason}"))]
+    InvalidSchedule { schedule: String, reason: String },
+}
+
+pub async fn instantiate_job_history_table() -> Result<Arc<AcceleratedTable>, Error> {
+    let retention = Retention::new(
+        Some("start_time".to_string()),
+        Some(TimeFormat::UnixSeconds),
+        Some(Duration::from_secs(7 * 24 * 60 * 60)), // 7 days
+        Some(Duration::from_secs(300)),
+        true,
+    );
+    create_internal_accelerated_table(
+        TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_JOB_HISTORY_TABLE),
+        Arc::new(table_schema()),
+        Acceleration::default(),
+        Refresh::default(),
+        retention,
+    )
+    .await
+    .boxed()
+    .context(UnableToRegisterTableSnafu)
+}
+
+fn table_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("job_name", DataType::Utf8, false),
+        Field::new(
+            "start_time",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new(
+            "end_time",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("attempts", DataType::UInt32, false),
+        Field::new("rows_produced", DataType::UInt64, true),
+        Field::new("error_message", DataType::Utf8, true),
+    ])
+}
+
+/// When a job runs: the minutes, hours, days of the month, months and days of the week of a cron expression, as bit
+/// sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| Error::InvalidSchedule {
+            schedule: schedule.to_string(),
+            reason,
+        };
+
+        let fields: Vec<&str> = schedule.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid(
+                "expected the 5 fields minute, hour, day of month, month and day of week"
+                    .to_string(),
+            ));
+        };
+
+        // Sunday is both 0 and 7.
+        let mut weekday_set = parse_field(weekdays, 0, 7).map_err(invalid)?;
+        if weekday_set & (1 << 7) != 0 {
+            weekday_set = (weekday_set | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59).map_err(invalid)?,
+            hours: parse_field(hours, 0, 23).map_err(invalid)?,
+            days: parse_field(days, 1, 31).map_err(invalid)?,
+            months: parse_field(months, 1, 12).map_err(invalid)?,
+            weekdays: weekday_set,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl Schedule {
+    /// Returns whether the job is due in the minute of `time`, in the timezone of `time`.
+    #[must_use]
+    pub fn matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let is_set = |set: u64, value: u32| set & (1 << value) != 0;
+
+        // As in cron, a restricted day of the month or day of the week matches if either does.
+        let day = is_set(self.days, time.day());
+        let weekday = is_set(self.weekdays, time.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        };
+
+        is_set(self.minutes, time.minute())
+            && is_set(self.hours, time.hour())
+            && is_set(self.months, time.month())
+            && day_matches
+    }
+}
+
+/// Parses a comma separated list of `*`, values or ranges, each with an optional `/step`, into a bit set.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let parse_value = |value: &str| match value.parse::<u32>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!("{value} is not between {min} and {max}")),
+    };
+
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step {step}")),
+            },
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+                // `5/15` runs from 5 to the end of the range.
+                None if step > 1 => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("invalid range {range}"));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+/// Returns the start of the minute after `time`, when schedules are next checked.
+pub(crate) fn next_minute(time: DateTime<Utc>) -> DateTime<Utc> {
+    let minute = time.duration_trunc(TimeDelta::minutes(1)).unwrap_or(time);
+    minute + TimeDelta::minutes(1)
+}
+
+pub(crate) async fn sleep_until(time: DateTime<Utc>) {
+    tokio::time::sleep((time - Utc::now()).to_std().unwrap_or_default()).await;
+}
+
+/// Starts the jobs of the app when they are due, until the runtime stops.
+pub(crate) async fn schedule(rt: &Runtime) {
+    let running: Arc<Mutex<HashSet<String>>> = Arc::default();
+
+    loop {
+        let next_minute = next_minute(Utc::now());
+        sleep_until(next_minute).await;
+
+        let jobs = match rt.app.read().await.as_ref() {
+            Some(app) => app.jobs.clone(),
+            None => continue,
+        };
+
+        for job in jobs {
+            match job.schedule.parse::<Schedule>() {
+                Ok(schedule) if schedule.matches(&next_minute) => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::debug!("Not running job {}: {e}", job.name);
+                    continue;
+                }
+            }
+
+            let df = Arc::clone(&rt.df);
+            let start_time = SystemTime::now();
+            let newly_running = running
+                .lock()
+                .map(|mut running| running.insert(job.name.clone()))
+                .unwrap_or(false);
+            if !newly_running {
+                tracing::warn!(
+                    "Skipping the run of job {} due at {next_minute}, as its previous run hasn't finished",
+                    job.name
+                );
+                let run = JobRun {
+                    job_name: job.name,
+                    start_time,
+                    end_time: start_time,
+                    status: "skipped",
+                    attempts: 0,
+                    rows_produced: None,
+                    error_message: None,
+                };
+                tokio::spawn(async move { run.write(&df).await });
+                continue;
+            }
+
+            let running = Arc::clone(&running);
+            tokio::spawn(async move {
+                let run = run_job(&df, &job).await;
+                if let Ok(mut running) = running.lock() {
+                    running.remove(&job.name);
+                }
+                run.write(&df).await;
+            });
+        }
+    }
+}
+
+/// Runs `job`, retrying it when it fails.
+async fn run_job(df: &Arc<DataFusion>, job: &Job) -> JobRun {
+    let retries = job.retries.unwrap_or(0);
+    let retry_delay = match job.retry_delay.as_deref().map(fundu::parse_duration) {
+        None => DEFAULT_RETRY_DELAY,
+        Some(Ok(retry_delay)) => retry_delay,
+        Some(Err(e)) => {
+            tracing::warn!(
+                "Invalid retry_delay for job {}, retrying after {DEFAULT_RETRY_DELAY:?}: {e}",
+                job.name
+            );
+            DEFAULT_RETRY_DELAY
+        }
+    };
+
+    tracing::info!("Running job {}", job.name);
+    let start_time = SystemTime::now();
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        match execute(df, &job.sql).await {
+            Ok(rows) => break Ok(rows),
+            Err(e) if attempts <= retries => {
+                tracing::warn!(
+                    "Job {} failed, retrying in {retry_delay:?} (attempt {attempts} of {}): {e}",
+                    job.name,
+                    retries + 1
+                );
+                tokio::time::sleep(retry_delay).await;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    let (status, rows_produced, error_message) = match result {
+        Ok(rows) => {
+            tracing::info!("Job {} completed, producing {rows} rows", job.name);
+            ("succeeded", Some(rows), None)
+        }
+        Err(e) => {
+            tracing::error!("Job {} failed after {attempts} attempts: {e}", job.name);
+            ("failed", None, Some(e))
+        }
+    };
+
+    JobRun {
+        job_name: job.name.clone(),
+        start_time,
+        end_time: SystemTime::now(),
+        status,
+        attempts,
+        rows_produced,
+        error_message,
+    }
+}
+
+/// Runs `sql` to completion, returning the number of rows it produced.
+async fn execute(df: &Arc<DataFusion>, sql: &str) -> Result<u64, String> {
+    let query = QueryBuilder::new(sql.to_string(), Arc::clone(df), Protocol::Job).build();
+    let mut data = query.run().await.map_err(|e| e.to_string())?.data;
+
+    let mut rows = 0;
+    while let Some(batch) = data.next().await {
+        rows += batch.map_err(|e| e.to_string())?.num_rows() as u64;
+    }
+    Ok(rows)
+}
+
+/// A row of the job history.
+struct JobRun {
+    job_name: String,
+    start_time: SystemTime,
+    end_time: SystemTime,
+    status: &'static str,
+    attempts: u32,
+    rows_produced: Option<u64>,
+    error_message: Option<String>,
+}
+
+impl JobRun {
+    async fn write(self, df: &DataFusion) {
+        let job_name = self.job_name.clone();
+        if let Err(e) = self.write_history(df).await {
+            tracing::warn!("Unable to record the run of job {job_name}: {e}");
+        }
+    }
+
+    async fn write_history(self, df: &DataFusion) -> Result<(), Error> {
+        let timestamp = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .ok()
+                .and_then(|duration| i64::try_from(duration.as_nanos()).ok())
+        };
+
+        let schema = Arc::new(table_schema());
+        let data = RecordBatch::try_new(
+            Arc::clone(&schema),
+            vec![
+                Arc::new(StringArray::from(vec![self.job_name])),
+                Arc::new(TimestampNanosecondArray::from(vec![timestamp(
+                    self.start_time,
+                )])),
+                Arc::new(TimestampNanosecondArray::from(vec![timestamp(
+                    self.end_time,
+                )])),
+                Arc::new(StringArray::from(vec![self.status])),
+                Arc::new(UInt32Array::from(vec![self.attempts])),
+                Arc::new(UInt64Array::from(vec![self.rows_produced])),
+                Arc::new(StringArray::from(vec![self.error_message])),
+            ],
+        )
+        .context(UnableToCreateRowSnafu)?;
+
+        df.write_data(
+            TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_JOB_HISTORY_TABLE),
+            DataUpdate {
+                schema,
+                data: vec![data],
+                update_type: UpdateType::Append,
+            },
+        )
+        .await
+        .boxed()
+        .context(UnableToWriteToTableSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_cron_schedules() {
+        let schedule: Schedule = "*/15 9-17 * * 1-5".parse().expect("valid schedule");
+        // Monday 2024-07-01.
+        let at = |d, h, m| {
+            Utc.with_ymd_and_hms(2024, 7, d, h, m, 0)
+                .single()
+                .expect("valid time")
+        };
+        assert!(schedule.matches(&at(1, 9, 0)));
+        assert!(schedule.matches(&at(1, 17, 45)));
+        assert!(!schedule.matches(&at(1, 9, 5)));
+        assert!(!schedule.matches(&at(1, 18, 0)));
+        assert!(!schedule.matches(&at(6, 9, 0)));
+
+        // Either the day of the month or the day of the week, and Sunday as 7.
+        let schedule: Schedule = "0 0 1 * 7".parse().expect("valid schedule");
+        assert!(schedule.matches(&at(1, 0, 0)));
+        assert!(schedule.matches(&at(7, 0, 0)));
+        assert!(!schedule.matches(&at(2, 0, 0)));
+
+        assert!("* * *".parse::<Schedule>().is_err());
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+    }
+}
@@ -0,0 +1,255 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Kerberos logins for connectors whose clients authenticate with GSSAPI from the process credential cache: ODBC
+//! drivers such as Hive's, PostgreSQL through its ODBC driver, and HDFS.
+//!
+//! A connector with `kerberos_principal` logs in with `kinit` from a keytab, given as a file with
+//! `kerberos_keytab_path` or as base64-encoded contents in the secret named by `kerberos_keytab_key`
+//! (default `kerberos_keytab`). The ticket is renewed from the keytab every `kerberos_renew_interval` (default `1h`).
+//!
+//! The credential cache is shared by the process through `KRB5CCNAME`, so a runtime logs in as a single principal.
+//! Changing the environment isn't safe once threads are running, so [`init`] points it to a cache in a private
+//! directory before the runtime starts, unless it is already set.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Output,
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
+use secrets::{get_secret_or_param, Secret};
+use snafu::prelude::*;
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+pub const PRINCIPAL_PARAM: &str = "kerberos_principal";
+
+const DEFAULT_RENEW_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Missing Kerberos keytab for {principal}. Specify kerberos_keytab_path, or the kerberos_keytab secret."))]
+    MissingKeytab { principal: String },
+
+    #[snafu(display("The Kerberos keytab secret isn't valid base64: {source}"))]
+    InvalidKeytab { source: base64::DecodeError },
+
+    #[snafu(display("Invalid kerberos_renew_interval {interval}: {source}"))]
+    InvalidRenewInterval {
+        interval: String,
+        source: fundu::ParseError,
+    },
+
+    #[snafu(display("Unable to write the Kerberos keytab: {source}"))]
+    UnableToWriteKeytab { source: std::io::Error },
+
+    #[snafu(display("KRB5CCNAME isn't set. The runtime sets it when it starts, unless Kerberos is initialized by the application embedding it."))]
+    MissingCredentialCache,
+
+    #[snafu(display(
+        "Unable to run kinit. Ensure the Kerberos client tools are installed: {source}"
+    ))]
+    UnableToRunKinit { source: std::io::Error },
+
+    #[snafu(display("kinit failed for {principal}: {stderr}"))]
+    KinitFailed { principal: String, stderr: String },
+
+    #[snafu(display("Already logged in to Kerberos as {current}. A runtime can only log in as one principal, not also as {principal}."))]
+    ConflictingPrincipal { current: String, principal: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The keytab of a login, kept in memory when it comes from a secret so it is only on disk while `kinit` reads it.
+enum Keytab {
+    Path(PathBuf),
+    Contents(Vec<u8>),
+}
+
+struct Login {
+    principal: String,
+    keytab: Keytab,
+    cache: String,
+}
+
+lazy_static! {
+    static ref LOGIN: Mutex<Option<Arc<Login>>> = Mutex::new(None);
+}
+
+/// The directory of the credential cache and of keytabs being read, only accessible by the runtime.
+static KERBEROS_DIR: OnceCell<TempDir> = OnceCell::new();
+
+fn kerberos_dir() -> std::io::Result<&'static Path> {
+    KERBEROS_DIR
+        .get_or_try_init(|| tempfile::Builder::new().prefix("spice_kerberos").tempdir())
+        .map(TempDir::path)
+}
+
+/// Points `KRB5CCNAME` to a credential cache in a private directory, unless it is already set. It changes the
+/// environment of the process, so it must be called before any other thread starts.
+pub fn init() -> std::io::Result<()> {
+    if std::env::var_os("KRB5CCNAME").is_some() {
+        return Ok(());
+    }
+    let cache = kerberos_dir()?.join("krb5cc");
+    std::env::set_var("KRB5CCNAME", format!("FILE:{}", cache.display()));
+    Ok(())
+}
+
+fn keytab(
+    principal: &str,
+    params: &HashMap<String, String>,
+    secret: &Option<Secret>,
+) -> Result<Keytab> {
+    if let Some(path) = params.get("kerberos_keytab_path") {
+        return Ok(Keytab::Path(PathBuf::from(path)));
+    }
+
+    let contents = get_secret_or_param(params, secret, "kerberos_keytab_key", "kerberos_keytab")
+        .context(MissingKeytabSnafu { principal })?;
+    STANDARD
+        .decode(contents.trim())
+        .map(Keytab::Contents)
+        .context(InvalidKeytabSnafu)
+}
+
+fn run_kinit(login: &Login) -> Result<Output> {
+    // The file is removed when it is dropped, once kinit has read it.
+    let keytab_file;
+    let keytab = match &login.keytab {
+        Keytab::Path(path) => path.as_path(),
+        Keytab::Contents(contents) => {
+            let mut file = kerberos_dir()
+                .and_then(|dir| tempfile::Builder::new().suffix(".keytab").tempfile_in(dir))
+                .context(UnableToWriteKeytabSnafu)?;
+            file.write_all(contents)
+                .and_then(|()| file.flush())
+                .context(UnableToWriteKeytabSnafu)?;
+            keytab_file = file;
+            keytab_file.path()
+        }
+    };
+
+    std::process::Command::new("kinit")
+        .arg("-k")
+        .arg("-t")
+        .arg(keytab)
+        .arg("-c")
+        .arg(&login.cache)
+        .arg(&login.principal)
+        .output()
+        .context(UnableToRunKinitSnafu)
+}
+
+async fn kinit(login: &Arc<Login>) -> Result<()> {
+    let running = Arc::clone(login);
+    let output = tokio::task::spawn_blocking(move || run_kinit(&running))
+        .await
+        .map_err(std::io::Error::other)
+        .context(UnableToRunKinitSnafu)??;
+
+    ensure!(
+        output.status.success(),
+        KinitFailedSnafu {
+            principal: login.principal.clone(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+    );
+    Ok(())
+}
+
+/// Logs in as the `kerberos_principal` in `params`, if there is one, and keeps its ticket renewed.
+///
+/// Returns whether the connector uses Kerberos.
+pub async fn login(params: &HashMap<String, String>, secret: &Option<Secret>) -> Result<bool> {
+    let Some(principal) = params.get(PRINCIPAL_PARAM) else {
+        return Ok(false);
+    };
+
+    let mut current = LOGIN.lock().await;
+    if let Some(login) = current.as_ref() {
+        ensure!(
+            login.principal == *principal,
+            ConflictingPrincipalSnafu {
+                current: login.principal.clone(),
+                principal: principal.clone(),
+            }
+        );
+        return Ok(true);
+    }
+
+    let renew_interval = match params.get("kerberos_renew_interval") {
+        Some(interval) => {
+            fundu::parse_duration(interval).context(InvalidRenewIntervalSnafu { interval })?
+        }
+        None => DEFAULT_RENEW_INTERVAL,
+    };
+
+    let login = Arc::new(Login {
+        principal: principal.clone(),
+        keytab: keytab(principal, params, secret)?,
+        cache: std::env::var("KRB5CCNAME").map_err(|_| Error::MissingCredentialCache)?,
+    });
+    kinit(&login).await?;
+    tracing::info!("Logged in to Kerberos as {principal}");
+
+    let renewed = Arc::clone(&login);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(renew_interval).await;
+            match kinit(&renewed).await {
+                Ok(()) => tracing::debug!("Renewed Kerberos ticket for {}", renewed.principal),
+                Err(e) => tracing::warn!(
+                    "Unable to renew Kerberos ticket for {}: {e}",
+                    renewed.principal
+                ),
+            }
+        }
+    });
+
+    *current = Some(login);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_login_requires_keytab() {
+        assert!(!login(&HashMap::new(), &None).await.expect("no login"));
+
+        let params =
+            HashMap::from([(PRINCIPAL_PARAM.to_string(), "spice@EXAMPLE.COM".to_string())]);
+        assert!(matches!(
+            login(&params, &None).await,
+            Err(Error::MissingKeytab { .. })
+        ));
+
+        let params = HashMap::from([("kerberos_keytab".to_string(), "a2V5dGFi".to_string())]);
+        assert!(matches!(
+            keytab("spice@EXAMPLE.COM", &params, &None),
+            Ok(Keytab::Contents(contents)) if contents == b"keytab"
+        ));
+    }
+}
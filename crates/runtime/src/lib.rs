@@ -16,9 +16,10 @@ limitations under the License.
 
 #![allow(clippy::missing_errors_doc)]
 
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 
@@ -32,59 +33,106 @@ use ::datafusion::sql::sqlparser::{self, ast};
 use ::datafusion::sql::TableReference;
 use accelerated_table::AcceleratedTable;
 use app::App;
+use app_diff::AppDiff;
 use cache::QueryResultsCacheProvider;
 use component::dataset::{self, Dataset};
+use component::function::{Function, FunctionKind};
 use component::view::View;
 use config::Config;
 use datafusion::query::query_history;
 use datafusion::SPICE_RUNTIME_SCHEMA;
 use datasets_health_monitor::DatasetsHealthMonitor;
 use embeddings::connector::EmbeddingConnector;
+use error_info::{classify, ErrorComponent, ToErrorInfo};
 use futures::future::join_all;
 use futures::StreamExt;
 use llms::embeddings::Embed;
 use metrics::SetRecorderError;
+use metrics_exporter_prometheus::PrometheusHandle;
 use model::{try_to_chat_model, try_to_embedding, LLMModelStore};
 use model_components::{model::Model, modelsource::source as model_source};
 pub use notify::Error as NotifyError;
-use secrets::{spicepod_secret_store_type, Secret};
+use projection::ProjectedConnector;
+use secrets::{spicepod_secret_store_type, ExposeSecret, Secret};
 use snafu::prelude::*;
 use spice_metrics::get_metrics_table_reference;
-use spicepod::component::model::Model as SpicepodModel;
+use spicepod::component::{
+    dataset::Dataset as SpicepodDataset, embeddings::Embeddings, llms::Llm,
+    model::Model as SpicepodModel,
+};
+use tokio::sync::broadcast;
 use tokio::sync::oneshot::error::RecvError;
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 use tracing_util::dataset_registered_trace;
+use type_mapping::TypeMappedConnector;
 pub use util::shutdown_signal;
 use uuid::Uuid;
 
 use crate::extension::{Extension, ExtensionFactory};
 pub mod accelerated_table;
+mod alerts;
+mod anomalies;
+pub mod app_diff;
+pub mod audit;
+pub mod auth;
+pub mod cached_table;
+pub mod cluster;
+pub mod column_types;
 pub mod component;
 pub mod config;
+pub mod connector_health;
+mod data_tests;
 pub mod dataaccelerator;
 pub mod dataconnector;
 pub mod datafusion;
 pub mod dataupdate;
+pub mod deployments;
 pub mod embeddings;
+pub mod error_info;
+pub mod events;
 pub mod execution_plan;
+mod expectations;
 pub mod extension;
 mod flight;
 mod http;
+pub mod inference_log;
 pub mod internal_table;
+pub mod ipc_compression;
+pub mod jobs;
+pub mod kerberos;
+pub mod lineage;
 pub mod model;
+mod model_versions;
 pub mod object_store_registry;
 pub mod objectstore;
 mod opentelemetry;
+pub mod otlp_metrics;
+mod pii;
 pub mod podswatcher;
+pub mod projection;
+pub mod quotas;
+pub mod rate_limits;
+pub mod record_format;
+mod secret_rotation;
+pub mod shutdown;
 pub mod spice_metrics;
 pub mod status;
 pub mod timing;
+pub mod tls;
 pub(crate) mod tracers;
 mod tracing_util;
+pub mod type_mapping;
+pub mod validation;
 
 pub mod datasets_health_monitor;
 
+/// How long the audit log can take to write its buffered events on shutdown.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the accelerators of encrypted files can take to close their connections on shutdown.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Unable to start HTTP server: {source}"))]
@@ -96,6 +144,21 @@ pub enum Error {
     #[snafu(display("Unable to start OpenTelemetry server: {source}"))]
     UnableToStartOpenTelemetryServer { source: opentelemetry::Error },
 
+    #[snafu(display("Unable to initialize authentication: {source}"))]
+    UnableToInitializeAuth { source: auth::Error },
+
+    #[snafu(display("Unable to initialize audit log: {source}"))]
+    UnableToInitializeAuditLog { source: audit::Error },
+
+    #[snafu(display("Unable to initialize inference log: {source}"))]
+    UnableToInitializeInferenceLog { source: inference_log::Error },
+
+    #[snafu(display("Unable to load TLS configuration: {source}"))]
+    UnableToLoadTls { source: tls::Error },
+
+    #[snafu(display("Unable to start metrics server: {source}"))]
+    UnableToStartMetricsServer { source: http::Error },
+
     #[snafu(display("Unknown data source: {data_source}"))]
     UnknownDataSource { data_source: String },
 
@@ -148,6 +211,23 @@ pub enum Error {
     #[snafu(display("Specify the SQL string for view {name} using either `sql: SELECT * FROM...` inline or as a file reference with `sql_ref: my_view.sql`"))]
     NeedToSpecifySQLView { name: String },
 
+    #[snafu(display("Specify the SQL string for function {name} using either `sql: SELECT * FROM...` inline or as a file reference with `sql_ref: my_function.sql`, or the WASM module exporting it with `wasm: my_function.wasm`"))]
+    NeedToSpecifySQLFunction { name: String },
+
+    #[snafu(display("Specify the SQL type WASM function {name} returns with `returns: DOUBLE`"))]
+    NeedToSpecifyFunctionReturnType { name: String },
+
+    #[snafu(display(
+        "The SQL of function {name} refers to {{{{ {param} }}}}, which is not one of its params"
+    ))]
+    UnknownFunctionParam { name: String, param: String },
+
+    #[snafu(display("Unable to load function {name}: {source}"))]
+    UnableToLoadFunction {
+        name: String,
+        source: datafusion::Error,
+    },
+
     #[snafu(display(
         "A federated table was configured as read_write without setting replication.enabled = true"
     ))]
@@ -162,14 +242,6 @@ pub enum Error {
     #[snafu(display("The accelerator engine {name} is not available. Valid engines are arrow, duckdb, sqlite, and postgres."))]
     AcceleratorEngineNotAvailable { name: String },
 
-    #[snafu(display(
-        "Dataset names should not include a catalog. Unexpected '{}' in '{}'. Remove '{}' from the dataset name and try again.",
-        catalog,
-        name,
-        catalog,
-    ))]
-    DatasetNameIncludesCatalog { catalog: Arc<str>, name: Arc<str> },
-
     #[snafu(display("Unable to load dataset connector: {dataset}"))]
     UnableToLoadDatasetConnector { dataset: TableReference },
 
@@ -179,6 +251,40 @@ pub enum Error {
         source: datafusion::Error,
     },
 
+    #[snafu(display("Unable to decrypt the acceleration file of {dataset}: {source}"))]
+    UnableToDecryptAccelerationFile {
+        dataset: TableReference,
+        source: dataaccelerator::encryption::Error,
+    },
+
+    #[snafu(display("Unable to get the mask key from secret {secret}: {source}"))]
+    UnableToGetMaskKey {
+        source: Box<dyn std::error::Error + Send + Sync>,
+        secret: String,
+    },
+
+    #[snafu(display(
+        "Unable to load the mask key: secret {secret} of runtime.masks.hash_secret has no key value"
+    ))]
+    MissingMaskKey { secret: String },
+
+    #[snafu(display("Unable to get the cluster token from secret {secret}: {source}"))]
+    UnableToGetClusterToken {
+        source: Box<dyn std::error::Error + Send + Sync>,
+        secret: String,
+    },
+
+    #[snafu(display(
+        "Unable to load the cluster token: secret {secret} of runtime.cluster.secret has no token value"
+    ))]
+    MissingClusterToken { secret: String },
+
+    #[snafu(display("Unable to load dataset {dataset} in the cluster: {source}"))]
+    UnableToRouteDataset {
+        dataset: TableReference,
+        source: cluster::Error,
+    },
+
     #[snafu(display("Unable to receive accelerated table status: {source}"))]
     UnableToReceiveAcceleratedTableStatus { source: RecvError },
 
@@ -193,6 +299,12 @@ pub enum Error {
     #[snafu(display("Unable to track query history: {source}"))]
     UnableToTrackQueryHistory { source: query_history::Error },
 
+    #[snafu(display("Unable to track job history: {source}"))]
+    UnableToTrackJobHistory { source: jobs::Error },
+
+    #[snafu(display("Unable to track deployment results: {source}"))]
+    UnableToTrackDeploymentResults { source: deployments::Error },
+
     #[snafu(display("Unable to create metrics table: {source}"))]
     UnableToCreateMetricsTable { source: DataFusionError },
 
@@ -203,6 +315,15 @@ pub enum Error {
     InvalidSpicepodDataset {
         source: crate::component::dataset::Error,
     },
+
+    #[snafu(display("Unable to persist dataset {dataset} to the spicepod: {source}"))]
+    UnableToPersistDataset {
+        source: spicepod::Error,
+        dataset: String,
+    },
+
+    #[snafu(display("Unable to persist dataset {dataset}: the runtime was not started from a spicepod directory"))]
+    NoSpicepodToPersistDataset { dataset: String },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -220,6 +341,10 @@ pub struct Runtime {
     pub pods_watcher: Arc<RwLock<Option<podswatcher::PodsWatcher>>>,
     pub secrets_provider: Arc<RwLock<secrets::SecretsProvider>>,
     pub datasets_health_monitor: Option<Arc<DatasetsHealthMonitor>>,
+    pub metrics_handle: Option<PrometheusHandle>,
+    pub spicepod_path: Option<PathBuf>,
+    pub rate_limiter: Arc<rate_limits::RateLimiter>,
+    pub cluster: Option<Arc<cluster::Cluster>>,
 
     extensions: Arc<RwLock<Vec<Box<dyn Extension>>>>,
     spaced_tracer: Arc<tracers::SpacedTracer>,
@@ -239,6 +364,24 @@ impl Runtime {
             Some(app) => app.name.clone(),
             None => "spice".to_string(),
         };
+        let ai_functions = app
+            .as_ref()
+            .map(|app| app.runtime.ai_functions.clone())
+            .unwrap_or_default();
+        let copy_targets = app
+            .as_ref()
+            .map(|app| app.runtime.copy.allowed_targets.clone())
+            .unwrap_or_default();
+        let cluster = app
+            .as_ref()
+            .filter(|app| app.runtime.cluster.enabled)
+            .and_then(|app| match cluster::Cluster::new(&app.runtime.cluster) {
+                Ok(cluster) => Some(Arc::new(cluster)),
+                Err(e) => {
+                    tracing::error!("Cluster mode is disabled: {e}");
+                    None
+                }
+            });
 
         let mut rt = Runtime {
             instance_name: format!("{name}-{hash}").to_string(),
@@ -252,7 +395,40 @@ impl Runtime {
             spaced_tracer: Arc::new(tracers::SpacedTracer::new(Duration::from_secs(15))),
             extensions: Arc::new(RwLock::new(vec![])),
             datasets_health_monitor: None,
+            metrics_handle: None,
+            spicepod_path: None,
+            rate_limiter: Arc::new(rate_limits::RateLimiter::new()),
+            cluster,
         };
+        rt.df.set_copy_targets(copy_targets);
+        datafusion::ai::register_ai_udf(
+            &rt.df.ctx,
+            Arc::downgrade(&rt.df),
+            Arc::clone(&rt.llms),
+            Arc::clone(&rt.app),
+            Arc::clone(&rt.rate_limiter),
+            &ai_functions,
+        );
+        rt.df
+            .ctx
+            .register_udf(embeddings::embed::Embed::new(Arc::clone(&rt.embeds)).into());
+        rt.df
+            .ctx
+            .register_udf(datafusion::predict::Predict::new(Arc::clone(&rt.models)).into());
+        if let Err(err) = rt.df.register_runtime_table(
+            TableReference::partial(SPICE_RUNTIME_SCHEMA, rate_limits::RATE_LIMIT_USAGE_TABLE),
+            Arc::new(rate_limits::RateLimitUsageTable::new(Arc::clone(
+                &rt.rate_limiter,
+            ))),
+        ) {
+            tracing::warn!("Unable to register the rate limit usage table: {err}");
+        }
+        if let Err(err) = rt.df.register_runtime_table(
+            TableReference::partial(SPICE_RUNTIME_SCHEMA, lineage::LINEAGE_TABLE),
+            Arc::new(lineage::LineageTable::new(Arc::clone(&rt.app))),
+        ) {
+            tracing::warn!("Unable to register the lineage table: {err}");
+        }
 
         let mut extensions: Vec<Box<dyn Extension>> = vec![];
         for factory in extension_factories.iter() {
@@ -276,15 +452,47 @@ impl Runtime {
     }
 
     pub async fn start_extensions(&self) {
+        // Subscribe before the extensions start, so they see every event from the datasets loaded after them.
+        let mut events = events::subscribe();
+        let dispatched_extensions = Arc::clone(&self.extensions);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        for extension in dispatched_extensions.read().await.iter() {
+                            extension::dispatch(extension.as_ref(), &event).await;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "Extensions fell behind, {skipped} runtime events were skipped"
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
         let mut extensions = self.extensions.write().await;
         for i in 0..extensions.len() {
-            if let Err(err) = extensions[i].on_start(self).await {
-                tracing::warn!("Failed to start extension: {err}");
+            let name = extensions[i].name();
+            status::update_extension(name, status::ComponentStatus::Initializing, None);
+            match extensions[i].on_start(self).await {
+                Ok(()) => status::update_extension(name, status::ComponentStatus::Ready, None),
+                Err(err) => {
+                    tracing::warn!("Failed to start extension: {} {err}", err.error_info());
+                    status::update_extension(
+                        name,
+                        status::ComponentStatus::Error,
+                        Some(err.to_string()),
+                    );
+                }
             }
         }
     }
 
     pub fn with_pods_watcher(&mut self, pods_watcher: podswatcher::PodsWatcher) {
+        self.spicepod_path = Some(pods_watcher.root_path().to_path_buf());
         self.pods_watcher = Arc::new(RwLock::new(Some(pods_watcher)));
     }
 
@@ -292,17 +500,22 @@ impl Runtime {
         self.datasets_health_monitor = Some(Arc::new(datasets_health_monitor));
     }
 
+    pub fn with_metrics_handle(&mut self, metrics_handle: PrometheusHandle) {
+        self.metrics_handle = Some(metrics_handle);
+    }
+
     pub async fn load_secrets(&self) {
         measure_scope_ms!("load_secrets");
         let mut secret_store = self.secrets_provider.write().await;
 
         let app_lock = self.app.read().await;
         if let Some(app) = app_lock.as_ref() {
-            let Some(secret_store_type) = spicepod_secret_store_type(&app.secrets.store) else {
+            let Some(secret_store_type) = spicepod_secret_store_type(&app.secrets) else {
                 return;
             };
 
             secret_store.store = secret_store_type;
+            secret_store.aws = app.secrets.aws.clone().unwrap_or_default();
         }
 
         if let Err(e) = secret_store.load_secrets().await {
@@ -314,6 +527,22 @@ impl Runtime {
         app.datasets.iter().cloned().map(Dataset::try_from)
     }
 
+    /// Converts a dataset of an app diff, reporting it like [`Self::get_valid_datasets`] does if it is invalid.
+    fn diff_dataset(ds: &SpicepodDataset) -> Option<Dataset> {
+        match Dataset::try_from(ds.clone()) {
+            Ok(dataset) => Some(dataset),
+            Err(e) => {
+                status::update_dataset(
+                    &TableReference::parse_str(&ds.name),
+                    status::ComponentStatus::Error,
+                );
+                metrics::counter!("datasets_load_error").increment(1);
+                tracing::error!(dataset = &ds.name, "{e}");
+                None
+            }
+        }
+    }
+
     /// Returns a list of valid datasets from the given App, skipping any that fail to parse and logging an error for them.
     fn get_valid_datasets(app: &App, log_failures: bool) -> Vec<Dataset> {
         Self::datasets_iter(app)
@@ -355,12 +584,46 @@ impl Runtime {
             .collect()
     }
 
+    /// Returns the valid functions from the given App, skipping any that fail to parse and logging an error for them.
+    fn get_valid_functions(app: &App) -> Vec<Function> {
+        app.functions
+            .iter()
+            .cloned()
+            .map(Function::try_from)
+            .zip(&app.functions)
+            .filter_map(|(function, spicepod_function)| match function {
+                Ok(function) => Some(function),
+                Err(e) => {
+                    metrics::counter!("functions_load_error").increment(1);
+                    tracing::error!(function = &spicepod_function.name, "{e}");
+                    None
+                }
+            })
+            .collect()
+    }
+
     pub async fn load_datasets(&self) {
         let app_lock = self.app.read().await;
         let Some(app) = app_lock.as_ref() else {
             return;
         };
 
+        if let Err(e) = quotas::configure(&app.runtime.namespaces) {
+            tracing::error!("{e}");
+        }
+
+        if let Some(cluster) = &self.cluster {
+            cluster.check_health().await;
+            let token = match self.cluster_token(cluster).await {
+                Ok(token) => Some(token),
+                Err(e) => {
+                    tracing::error!("{e}");
+                    None
+                }
+            };
+            cluster::exchange::configure(Arc::clone(cluster), token);
+        }
+
         let valid_datasets = Self::get_valid_datasets(app, true);
         let mut futures = vec![];
         for ds in &valid_datasets {
@@ -371,13 +634,13 @@ impl Runtime {
         if let Some(parallel_num) = app.runtime.num_of_parallel_loading_at_start_up {
             let stream = futures::stream::iter(futures).buffer_unordered(parallel_num);
             let _ = stream.collect::<Vec<_>>().await;
-            return;
+        } else {
+            let _ = join_all(futures).await;
         }
 
-        let _ = join_all(futures).await;
-
         // After all datasets have loaded, load the views.
         self.load_views(app, &valid_datasets);
+        self.load_functions(app).await;
 
         self.df.mark_initial_load_complete();
     }
@@ -392,8 +655,52 @@ impl Runtime {
         }
     }
 
+    async fn load_functions(&self, app: &App) {
+        for function in Self::get_valid_functions(app) {
+            if let Err(e) = self.load_function(&function).await {
+                tracing::error!("{e}");
+            }
+        }
+    }
+
+    /// Registers `function`, replacing the function of the same name if there is one.
+    pub async fn load_function(&self, function: &Function) -> Result<()> {
+        match &function.kind {
+            FunctionKind::Sql(sql) => {
+                self.df
+                    .register_table_function(&function.name, &function.param_types, sql)
+                    .await
+            }
+            FunctionKind::Wasm {
+                module,
+                export,
+                returns,
+            } => self.df.register_wasm_function(
+                &function.name,
+                module,
+                export,
+                &function.param_types,
+                returns,
+            ),
+        }
+        .context(UnableToLoadFunctionSnafu {
+            name: &function.name,
+        })
+    }
+
     // Caller must set `status::update_dataset(...` before calling `load_dataset`. This function will set error/ready statuses appropriately.`
     pub async fn load_dataset(&self, ds: &Dataset) {
+        let ds = match self.cluster_dataset(ds) {
+            Ok(ds) => ds,
+            Err(err) => {
+                // Loading the dataset without its place in the cluster would duplicate the rows of other members.
+                status::update_dataset(&ds.name, status::ComponentStatus::Error);
+                metrics::counter!("datasets_load_error").increment(1);
+                tracing::error!("{err}");
+                return;
+            }
+        };
+        let ds = ds.as_ref();
         let spaced_tracer = Arc::clone(&self.spaced_tracer);
 
         loop {
@@ -403,7 +710,8 @@ impl Runtime {
                     let ds_name = &ds.name;
                     status::update_dataset(ds_name, status::ComponentStatus::Error);
                     metrics::counter!("datasets_load_error").increment(1);
-                    warn_spaced!(spaced_tracer, "{} {err}", ds_name.table());
+                    let info = classify(&err, ErrorComponent::Runtime);
+                    warn_spaced!(spaced_tracer, "{} {info} {err}", ds_name.table());
                     sleep(Duration::from_secs(1)).await;
                     continue;
                 }
@@ -420,6 +728,17 @@ impl Runtime {
         }
     }
 
+    /// The dataset as this runtime loads it. In a cluster, the accelerated datasets owned by another member are read
+    /// from that member.
+    fn cluster_dataset<'a>(&self, ds: &'a Dataset) -> Result<Cow<'a, Dataset>> {
+        match &self.cluster {
+            Some(cluster) => cluster.route(ds).context(UnableToRouteDatasetSnafu {
+                dataset: ds.name.clone(),
+            }),
+            None => Ok(Cow::Borrowed(ds)),
+        }
+    }
+
     pub fn load_view(&self, view: &View, all_datasets: &[Dataset]) -> Result<()> {
         let existing_tables = all_datasets
             .iter()
@@ -434,7 +753,7 @@ impl Runtime {
         }
 
         let df = Arc::clone(&self.df);
-        df.register_view(view.name.clone(), view.sql.clone())
+        df.register_view(view.name.clone(), view.sql.clone(), view.namespace.clone())
             .context(UnableToAttachViewSnafu)?;
 
         Ok(())
@@ -453,6 +772,7 @@ impl Runtime {
         let params = Arc::new(ds.params.clone());
         let data_connector: Arc<dyn DataConnector> = match Runtime::get_dataconnector_from_source(
             &source,
+            &ds.secret_name(),
             &secrets_provider,
             Arc::clone(&params),
         )
@@ -463,7 +783,8 @@ impl Runtime {
                 let ds_name = &ds.name;
                 status::update_dataset(ds_name, status::ComponentStatus::Error);
                 metrics::counter!("datasets_load_error").increment(1);
-                warn_spaced!(spaced_tracer, "{} {err}", ds_name.table());
+                let info = classify(&err, ErrorComponent::Runtime);
+                warn_spaced!(spaced_tracer, "{} {info} {err}", ds_name.table());
                 return UnableToLoadDatasetConnectorSnafu {
                     dataset: ds.name.clone(),
                 }
@@ -471,7 +792,17 @@ impl Runtime {
             }
         };
 
-        Ok(data_connector)
+        // Only wrap data connector when necessary.
+        let data_connector: Arc<dyn DataConnector> = if ds.columns.is_empty() {
+            data_connector
+        } else {
+            Arc::new(ProjectedConnector::new(data_connector))
+        };
+        if ds.type_mappings.is_empty() {
+            Ok(data_connector)
+        } else {
+            Ok(Arc::new(TypeMappedConnector::new(data_connector)))
+        }
     }
 
     pub async fn register_loaded_dataset(
@@ -491,7 +822,8 @@ impl Runtime {
         if let Err(err) = data_connector.read_provider(&ds).await {
             status::update_dataset(&ds.name, status::ComponentStatus::Error);
             metrics::counter!("datasets_load_error").increment(1);
-            warn_spaced!(spaced_tracer, "{}{err}", "");
+            let info = err.error_info();
+            warn_spaced!(spaced_tracer, "{}{info} {err}", "");
             return UnableToLoadDatasetConnectorSnafu {
                 dataset: ds.name.clone(),
             }
@@ -535,6 +867,9 @@ impl Runtime {
                 );
                 metrics::gauge!("datasets_count", "engine" => engine).increment(1.0);
                 status::update_dataset(&ds.name, status::ComponentStatus::Ready);
+                events::publish(events::RuntimeEvent::DatasetRegistered {
+                    dataset: ds.name.clone(),
+                });
 
                 Ok(())
             }
@@ -548,7 +883,8 @@ impl Runtime {
                 {
                     tracing::error!("{source}");
                 }
-                warn_spaced!(spaced_tracer, "{}{err}", "");
+                let info = classify(&err, ErrorComponent::Runtime);
+                warn_spaced!(spaced_tracer, "{}{info} {err}", "");
 
                 Err(err)
             }
@@ -569,6 +905,7 @@ impl Runtime {
             }
         }
 
+        status::remove_dataset(&ds.name);
         tracing::info!("Unloaded dataset {}", &ds.name);
         let engine = ds.acceleration.as_ref().map_or_else(
             || "None".to_string(),
@@ -581,38 +918,146 @@ impl Runtime {
             },
         );
         metrics::gauge!("datasets_count", "engine" => engine).decrement(1.0);
+        events::publish(events::RuntimeEvent::DatasetRemoved {
+            dataset: ds.name.clone(),
+        });
     }
 
-    pub async fn update_dataset(&self, ds: &Dataset) {
+    pub async fn update_dataset(&self, ds: &Dataset) -> Result<()> {
+        let ds = match self.cluster_dataset(ds) {
+            Ok(ds) => ds,
+            Err(err) => {
+                status::update_dataset(&ds.name, status::ComponentStatus::Error);
+                return Err(err);
+            }
+        };
+        let ds = ds.as_ref();
         status::update_dataset(&ds.name, status::ComponentStatus::Refreshing);
-        if let Ok(connector) = self.load_dataset_connector(ds).await {
-            tracing::info!("Updating accelerated dataset {}...", &ds.name);
-
-            // File accelerated datasets don't support hot reload.
-            if ds.is_accelerated() {
-                if let Ok(()) = &self
-                    .reload_accelerated_dataset(ds, Arc::clone(&connector))
-                    .await
-                {
-                    status::update_dataset(&ds.name, status::ComponentStatus::Ready);
-                    return;
-                }
-                tracing::debug!("Failed to create accelerated table for dataset {}, falling back to full dataset reload", ds.name);
+        let connector = match self.load_dataset_connector(ds).await {
+            Ok(connector) => connector,
+            Err(err) => {
+                status::update_dataset(&ds.name, status::ComponentStatus::Error);
+                return Err(err);
             }
+        };
+        tracing::info!("Updating accelerated dataset {}...", &ds.name);
 
-            self.remove_dataset(ds).await;
-
-            if let Ok(()) = self
-                .register_loaded_dataset(ds, Arc::clone(&connector), None)
+        // File accelerated datasets don't support hot reload.
+        if ds.is_accelerated() {
+            if let Ok(()) = &self
+                .reload_accelerated_dataset(ds, Arc::clone(&connector))
                 .await
             {
                 status::update_dataset(&ds.name, status::ComponentStatus::Ready);
-            } else {
+                return Ok(());
+            }
+            tracing::debug!("Failed to create accelerated table for dataset {}, falling back to full dataset reload", ds.name);
+        }
+
+        self.remove_dataset(ds).await;
+
+        match self
+            .register_loaded_dataset(ds, Arc::clone(&connector), None)
+            .await
+        {
+            Ok(()) => {
+                status::update_dataset(&ds.name, status::ComponentStatus::Ready);
+                Ok(())
+            }
+            Err(err) => {
                 status::update_dataset(&ds.name, status::ComponentStatus::Error);
+                Err(err)
             }
+        }
+    }
+
+    /// Loads a dataset submitted at runtime, or reloads it if a dataset of the same name is already loaded.
+    ///
+    /// Unlike datasets loaded from the spicepod, the dataset is loaded once and an error is returned if it fails.
+    /// When `persist` is set, the dataset is also written to the root spicepod so it survives a restart;
+    /// otherwise it is dropped the next time the spicepod changes on disk.
+    pub async fn upsert_dataset(&self, spicepod_ds: SpicepodDataset, persist: bool) -> Result<()> {
+        let ds = Dataset::try_from(spicepod_ds.clone()).context(InvalidSpicepodDatasetSnafu)?;
+
+        if self.df.table_exists(ds.name.clone()) {
+            self.update_dataset(&ds).await?;
         } else {
-            status::update_dataset(&ds.name, status::ComponentStatus::Error);
+            let ds = self.cluster_dataset(&ds)?;
+            status::update_dataset(&ds.name, status::ComponentStatus::Initializing);
+            let connector = match self.load_dataset_connector(&ds).await {
+                Ok(connector) => connector,
+                Err(err) => {
+                    status::remove_dataset(&ds.name);
+                    return Err(err);
+                }
+            };
+            if let Err(err) = self.register_loaded_dataset(&ds, connector, None).await {
+                status::remove_dataset(&ds.name);
+                return Err(err);
+            }
         }
+
+        // Update the loaded app before persisting, so the pods watcher sees no change to apply.
+        if let Some(app) = self.app.write().await.as_mut() {
+            match app.datasets.iter_mut().find(|d| d.name == spicepod_ds.name) {
+                Some(existing) => *existing = spicepod_ds.clone(),
+                None => app.datasets.push(spicepod_ds.clone()),
+            }
+        }
+
+        if persist {
+            let path = self.spicepod_path_for(&spicepod_ds.name)?;
+            spicepod::Spicepod::save_dataset(path, spicepod_ds.clone()).context(
+                UnableToPersistDatasetSnafu {
+                    dataset: spicepod_ds.name,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Unloads the dataset named `name`, returning `false` if there is no such dataset.
+    ///
+    /// When `persist` is set, the dataset is also removed from the root spicepod.
+    pub async fn delete_dataset(&self, name: &str, persist: bool) -> Result<bool> {
+        let spicepod_ds = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .and_then(|app| app.datasets.iter().find(|d| d.name == name).cloned());
+        let Some(spicepod_ds) = spicepod_ds else {
+            return Ok(false);
+        };
+        let ds = Dataset::try_from(spicepod_ds).context(InvalidSpicepodDatasetSnafu)?;
+        let path = if persist {
+            Some(self.spicepod_path_for(name)?)
+        } else {
+            None
+        };
+
+        if let Some(app) = self.app.write().await.as_mut() {
+            app.datasets.retain(|d| d.name != name);
+        }
+        status::update_dataset(&ds.name, status::ComponentStatus::Disabled);
+        self.remove_dataset(&ds).await;
+
+        if let Some(path) = path {
+            spicepod::Spicepod::remove_dataset(path, name).context(
+                UnableToPersistDatasetSnafu {
+                    dataset: name.to_string(),
+                },
+            )?;
+        }
+
+        Ok(true)
+    }
+
+    fn spicepod_path_for(&self, dataset: &str) -> Result<&PathBuf> {
+        self.spicepod_path
+            .as_ref()
+            .context(NoSpicepodToPersistDatasetSnafu { dataset })
     }
 
     async fn reload_accelerated_dataset(
@@ -620,6 +1065,7 @@ impl Runtime {
         ds: &Dataset,
         connector: Arc<dyn DataConnector>,
     ) -> Result<()> {
+        Runtime::decrypt_acceleration_file(ds, Arc::clone(&self.secrets_provider)).await?;
         let acceleration_secret =
             Runtime::get_acceleration_secret(ds, Arc::clone(&self.secrets_provider)).await?;
 
@@ -647,10 +1093,11 @@ impl Runtime {
 
     async fn get_dataconnector_from_source(
         source: &str,
+        secret_name: &str,
         secrets_provider: &secrets::SecretsProvider,
         params: Arc<HashMap<String, String>>,
     ) -> Result<Arc<dyn DataConnector>> {
-        let secret = secrets_provider.get_secret(source).await.context(
+        let secret = secrets_provider.get_secret(secret_name).await.context(
             UnableToGetSecretForDataConnectorSnafu {
                 data_connector: source,
             },
@@ -683,11 +1130,7 @@ impl Runtime {
             AcceleratedReadWriteTableWithoutReplicationSnafu.fail()?;
         }
 
-        let accelerator_engine = acceleration_settings.engine;
-        let secret_key = acceleration_settings
-            .engine_secret
-            .clone()
-            .unwrap_or(format!("{accelerator_engine}_engine").to_lowercase());
+        let secret_key = ds.scoped_secret_name(&acceleration_settings.engine_secret_name());
 
         let secrets_provider_read_guard = secrets_provider.read().await;
         let acceleration_secret = secrets_provider_read_guard
@@ -702,6 +1145,53 @@ impl Runtime {
         Ok(acceleration_secret)
     }
 
+    /// Decrypts the acceleration file of a dataset with an `encryption_secret` before its accelerator opens it.
+    async fn decrypt_acceleration_file(
+        ds: &Dataset,
+        secrets_provider: Arc<RwLock<secrets::SecretsProvider>>,
+    ) -> Result<()> {
+        let Some(acceleration_settings) = ds.acceleration.as_ref() else {
+            return Ok(());
+        };
+        let (Some(encryption_secret), Some(path)) = (
+            &acceleration_settings.encryption_secret,
+            dataaccelerator::accelerated_file_path(&ds.name, acceleration_settings),
+        ) else {
+            return Ok(());
+        };
+
+        let secret_name = ds.scoped_secret_name(encryption_secret);
+        let secret = secrets_provider
+            .read()
+            .await
+            .get_secret(&secret_name)
+            .await
+            .context(UnableToGetSecretForDataConnectorSnafu {
+                data_connector: ds.source(),
+            })?;
+
+        let key = dataaccelerator::encryption::EncryptionKey::try_from_secret(
+            &secret_name,
+            secret.as_ref(),
+        )
+        .context(UnableToDecryptAccelerationFileSnafu {
+            dataset: ds.name.clone(),
+        })?;
+        let path = PathBuf::from(path);
+        let file = path.clone();
+        // Decrypting reads and writes the whole file, so it runs outside of the async runtime.
+        tokio::task::spawn_blocking(move || {
+            dataaccelerator::encryption::decrypt_acceleration_file(&file, &key)
+        })
+        .await
+        .unwrap_or(Err(
+            dataaccelerator::encryption::Error::DecryptionInterrupted { path },
+        ))
+        .context(UnableToDecryptAccelerationFileSnafu {
+            dataset: ds.name.clone(),
+        })
+    }
+
     async fn register_dataset(
         ds: impl Borrow<Dataset>,
         data_connector: Arc<dyn DataConnector>,
@@ -749,6 +1239,7 @@ impl Runtime {
                     name: ds.name.to_string(),
                 })?;
         let accelerator_engine = acceleration_settings.engine;
+        Runtime::decrypt_acceleration_file(ds, Arc::clone(&secrets_provider)).await?;
         let acceleration_secret = Runtime::get_acceleration_secret(ds, secrets_provider).await?;
 
         dataaccelerator::get_accelerator_engine(accelerator_engine)
@@ -789,56 +1280,121 @@ impl Runtime {
         let app_lock = self.app.read().await;
         if let Some(app) = app_lock.as_ref() {
             for in_llm in &app.llms {
-                status::update_llm(&in_llm.name, status::ComponentStatus::Initializing);
-                match try_to_chat_model(in_llm) {
-                    Ok(l) => {
-                        let mut llm_map = self.llms.write().await;
-                        llm_map.insert(in_llm.name.clone(), l.into());
-                        tracing::info!("Llm [{}] deployed, ready for inferencing", in_llm.name);
-                        metrics::gauge!("llms_count", "llm" => in_llm.name.clone(), "source" => in_llm.get_prefix().map(|x| x.to_string()).unwrap_or_default()).increment(1.0);
-                        status::update_llm(&in_llm.name, status::ComponentStatus::Ready);
-                    }
-                    Err(e) => {
-                        metrics::counter!("llms_load_error").increment(1);
-                        status::update_llm(&in_llm.name, status::ComponentStatus::Error);
-                        tracing::warn!(
-                            "Unable to load LLM from spicepod {}, error: {}",
-                            in_llm.name,
-                            e,
-                        );
-                    }
-                }
+                self.load_llm(in_llm).await;
+            }
+        }
+    }
+
+    pub async fn load_llm(&self, in_llm: &Llm) {
+        status::update_llm(&in_llm.name, status::ComponentStatus::Initializing);
+        let mut llm = in_llm.clone();
+        self.fill_params_from_secret(model::llm_secret_name(in_llm), &mut llm.params)
+            .await;
+        match try_to_chat_model(&llm) {
+            Ok(l) => {
+                let mut llm_map = self.llms.write().await;
+                llm_map.insert(in_llm.name.clone(), l.into());
+                tracing::info!("Llm [{}] deployed, ready for inferencing", in_llm.name);
+                metrics::gauge!("llms_count", "llm" => in_llm.name.clone(), "source" => in_llm.get_prefix().map(|x| x.to_string()).unwrap_or_default()).increment(1.0);
+                status::update_llm(&in_llm.name, status::ComponentStatus::Ready);
+            }
+            Err(e) => {
+                metrics::counter!("llms_load_error").increment(1);
+                status::update_llm(&in_llm.name, status::ComponentStatus::Error);
+                tracing::warn!(
+                    "Unable to load LLM from spicepod {}, error: {}",
+                    in_llm.name,
+                    e,
+                );
             }
         }
     }
 
+    pub async fn remove_llm(&self, in_llm: &Llm) {
+        if self.llms.write().await.remove(&in_llm.name).is_none() {
+            return;
+        }
+        status::remove_llm(&in_llm.name);
+        tracing::info!("Llm [{}] has been unloaded", in_llm.name);
+        metrics::gauge!("llms_count", "llm" => in_llm.name.clone(), "source" => in_llm.get_prefix().map(|x| x.to_string()).unwrap_or_default()).decrement(1.0);
+    }
+
     pub async fn load_embeddings(&self) {
         let app_lock = self.app.read().await;
         if let Some(app) = app_lock.as_ref() {
             for in_embed in &app.embeddings {
-                status::update_embedding(&in_embed.name, status::ComponentStatus::Initializing);
-                match try_to_embedding(in_embed) {
-                    Ok(e) => {
-                        let mut embeds_map = self.embeds.write().await;
-                        embeds_map.insert(in_embed.name.clone(), e.into());
-                        tracing::info!("Embedding [{}] ready to embed", in_embed.name);
-                        metrics::gauge!("embeddings_count", "embeddings" => in_embed.name.clone(), "source" => in_embed.get_prefix().map(|x| x.to_string()).unwrap_or_default()).increment(1.0);
-                        status::update_embedding(&in_embed.name, status::ComponentStatus::Ready);
-                    }
-                    Err(e) => {
-                        metrics::counter!("embeddings_load_error").increment(1);
-                        status::update_embedding(&in_embed.name, status::ComponentStatus::Error);
-                        tracing::warn!(
-                            "Unable to load embedding from spicepod {}, error: {}",
-                            in_embed.name,
-                            e,
-                        );
-                    }
-                }
+                self.load_embedding(in_embed).await;
+            }
+        }
+    }
+
+    pub async fn load_embedding(&self, in_embed: &Embeddings) {
+        status::update_embedding(&in_embed.name, status::ComponentStatus::Initializing);
+        let mut embed = in_embed.clone();
+        self.fill_params_from_secret(model::embedding_secret_name(in_embed), &mut embed.params)
+            .await;
+        match try_to_embedding(&embed) {
+            Ok(e) => {
+                let mut embeds_map = self.embeds.write().await;
+                embeds_map.insert(in_embed.name.clone(), e.into());
+                tracing::info!("Embedding [{}] ready to embed", in_embed.name);
+                metrics::gauge!("embeddings_count", "embeddings" => in_embed.name.clone(), "source" => in_embed.get_prefix().map(|x| x.to_string()).unwrap_or_default()).increment(1.0);
+                status::update_embedding(&in_embed.name, status::ComponentStatus::Ready);
+            }
+            Err(e) => {
+                metrics::counter!("embeddings_load_error").increment(1);
+                status::update_embedding(&in_embed.name, status::ComponentStatus::Error);
+                tracing::warn!(
+                    "Unable to load embedding from spicepod {}, error: {}",
+                    in_embed.name,
+                    e,
+                );
+            }
+        }
+    }
+
+    /// Sets the params that the spicepod leaves unset from the secret `secret_name`, so API keys can be kept in the
+    /// secret store and rotated there.
+    async fn fill_params_from_secret(
+        &self,
+        secret_name: Option<String>,
+        params: &mut Option<HashMap<String, String>>,
+    ) {
+        let Some(secret_name) = secret_name else {
+            return;
+        };
+        let secret = match self
+            .secrets_provider
+            .read()
+            .await
+            .get_secret(&secret_name)
+            .await
+        {
+            Ok(Some(secret)) => secret,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("Unable to get secret {secret_name}: {e}");
+                return;
             }
+        };
+
+        let params = params.get_or_insert_with(HashMap::new);
+        for (key, value) in secret.iter() {
+            params
+                .entry(key.clone())
+                .or_insert_with(|| value.expose_secret().clone());
         }
     }
 
+    pub async fn remove_embedding(&self, in_embed: &Embeddings) {
+        if self.embeds.write().await.remove(&in_embed.name).is_none() {
+            return;
+        }
+        status::remove_embedding(&in_embed.name);
+        tracing::info!("Embedding [{}] has been unloaded", in_embed.name);
+        metrics::gauge!("embeddings_count", "embeddings" => in_embed.name.clone(), "source" => in_embed.get_prefix().map(|x| x.to_string()).unwrap_or_default()).decrement(1.0);
+    }
+
     pub async fn load_models(&self) {
         let app_lock = self.app.read().await;
         if let Some(app) = app_lock.as_ref() {
@@ -860,10 +1416,8 @@ impl Runtime {
         let shared_secrets_provider = Arc::clone(&self.secrets_provider);
         let secrets_provider = shared_secrets_provider.read().await;
 
-        let secret = match secrets_provider
-            .get_secret(source.to_string().as_str())
-            .await
-        {
+        let secret_name = model.secret.clone().unwrap_or_else(|| source.to_string());
+        let secret = match secrets_provider.get_secret(&secret_name).await {
             Ok(s) => s,
             Err(e) => {
                 metrics::counter!("models_load_error").increment(1);
@@ -907,6 +1461,7 @@ impl Runtime {
             return;
         }
         model_map.remove(&m.name);
+        status::remove_model(&m.name);
         tracing::info!("Model [{}] has been unloaded", m.name);
         metrics::gauge!("models_count", "model" => m.name.clone(), "source" => model_source(&m.from).to_string()).decrement(1.0);
     }
@@ -918,8 +1473,8 @@ impl Runtime {
     }
 
     pub async fn start_metrics(&mut self, with_metrics: Option<SocketAddr>) -> Result<()> {
-        if let Some(metrics_socket) = with_metrics {
-            let mut recorder = MetricsRecorder::new(metrics_socket);
+        if let (Some(_), Some(metrics_handle)) = (with_metrics, &self.metrics_handle) {
+            let mut recorder = MetricsRecorder::new(metrics_handle.clone());
 
             let table_reference = get_metrics_table_reference();
             let metrics_table = self.df.get_table(table_reference).await;
@@ -939,6 +1494,164 @@ impl Runtime {
         Ok(())
     }
 
+    /// Pushes the runtime metrics to the OpenTelemetry collector of `runtime.otlp_metrics`, until the runtime stops.
+    /// Returns immediately if it isn't enabled.
+    pub async fn start_otlp_metrics(&self) {
+        let config = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.runtime.otlp_metrics.clone())
+            .unwrap_or_default();
+        if !config.enabled {
+            return;
+        }
+        let Some(metrics_handle) = self.metrics_handle.clone() else {
+            tracing::warn!("runtime.otlp_metrics is enabled, but no metrics are recorded");
+            return;
+        };
+
+        match otlp_metrics::OtlpMetricsExporter::try_new(
+            &config,
+            self.instance_name.clone(),
+            metrics_handle,
+        ) {
+            Ok(exporter) => exporter.run().await,
+            Err(e) => tracing::error!("Unable to start pushing metrics: {e}"),
+        }
+    }
+
+    /// Reloads components whose secrets are rotated, checking every `secrets.refresh_interval` until the runtime stops.
+    /// Returns immediately if no refresh interval is set.
+    pub async fn start_secret_rotation(&self) {
+        let refresh_interval = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .and_then(|app| app.secrets.refresh_interval.clone());
+        let Some(refresh_interval) = refresh_interval else {
+            return;
+        };
+
+        match fundu::parse_duration(&refresh_interval) {
+            Ok(interval) => secret_rotation::watch(self, interval).await,
+            Err(e) => tracing::warn!(
+                "Secret rotation is disabled, invalid secrets.refresh_interval {refresh_interval}: {e}"
+            ),
+        }
+    }
+
+    /// Runs the jobs of the app on their schedules, until the runtime stops.
+    pub async fn start_jobs(&self) {
+        if let Err(err) = self.init_job_history().await {
+            tracing::warn!("Creating internal job history table: {err}");
+        }
+
+        jobs::schedule(self).await;
+    }
+
+    /// Checks the members of the cluster for availability every `health_check_interval`, and reloads the datasets
+    /// whose owner or shards changed, until the runtime stops.
+    pub async fn start_cluster(&self) {
+        let Some(cluster) = &self.cluster else {
+            return;
+        };
+
+        // The datasets as routed when they were loaded, by `load_datasets` or since.
+        let mut assigned: HashMap<TableReference, Dataset> = HashMap::new();
+        let mut interval = tokio::time::interval(cluster.health_check_interval());
+        loop {
+            interval.tick().await;
+            let datasets = match self.app.read().await.as_ref() {
+                Some(app) => Self::get_valid_datasets(app, false),
+                None => continue,
+            };
+            let datasets = datasets
+                .into_iter()
+                .filter(|ds| cluster::is_distributed(ds) || cluster::is_partitioned(ds))
+                .collect::<Vec<_>>();
+            assigned.retain(|name, _| datasets.iter().any(|ds| &ds.name == name));
+            for ds in &datasets {
+                if assigned.contains_key(&ds.name) {
+                    continue;
+                }
+                if let Ok(routed) = cluster.route(ds) {
+                    assigned.insert(ds.name.clone(), routed.into_owned());
+                }
+            }
+
+            cluster.check_health().await;
+            for ds in &datasets {
+                // Datasets that can't be routed failed to load, and are reported by `load_dataset`.
+                let Ok(routed) = cluster.route(ds).map(Cow::into_owned) else {
+                    continue;
+                };
+                if assigned.get(&ds.name) == Some(&routed) {
+                    continue;
+                }
+                if cluster::is_partitioned(ds) {
+                    tracing::info!("Reloading the shards of dataset {}", ds.name);
+                } else {
+                    tracing::info!(
+                        "Moving dataset {} to cluster member {}",
+                        ds.name,
+                        cluster.owner(&ds.name).name
+                    );
+                }
+                match self.update_dataset(ds).await {
+                    Ok(()) => {
+                        assigned.insert(ds.name.clone(), routed);
+                    }
+                    // Retried on the next health check.
+                    Err(e) => tracing::warn!("Unable to reload dataset {}: {e}", ds.name),
+                }
+            }
+        }
+    }
+
+    /// Checks the alerts of the app on their schedules and after the refreshes of their datasets, until the runtime
+    /// stops.
+    pub async fn start_alerts(&self) {
+        alerts::watch(self).await;
+    }
+
+    /// Checks the expectations of the datasets after their refreshes and every minute, until the runtime stops.
+    pub async fn start_expectation_checks(&self) {
+        expectations::watch(self).await;
+    }
+
+    /// Flags the columns of the datasets with `detect_pii` that look like personal data, until the runtime stops.
+    pub async fn start_pii_detection(&self) {
+        pii::watch(self).await;
+    }
+
+    /// Scores the datasets with `anomaly_detection` for anomalies after their refreshes, until the runtime stops.
+    pub async fn start_anomaly_detection(&self) {
+        anomalies::watch(self).await;
+    }
+
+    pub async fn start_model_version_checks(&self) {
+        model_versions::watch(self).await;
+    }
+
+    async fn init_job_history(&self) -> Result<()> {
+        let job_history_table_reference =
+            TableReference::partial(SPICE_RUNTIME_SCHEMA, jobs::DEFAULT_JOB_HISTORY_TABLE);
+        if self.df.table_exists(job_history_table_reference.clone()) {
+            return Ok(());
+        }
+
+        match jobs::instantiate_job_history_table().await {
+            Ok(table) => self
+                .df
+                .register_runtime_table(job_history_table_reference, table)
+                .context(UnableToCreateBackendSnafu),
+            Err(err) => Err(Error::UnableToTrackJobHistory { source: err }),
+        }
+    }
+
     pub fn start_datasets_health_monitor(&self) {
         if let Some(datasets_health_monitor) = &self.datasets_health_monitor {
             datasets_health_monitor.start();
@@ -950,6 +1663,35 @@ impl Runtime {
         config: Config,
         with_metrics: Option<SocketAddr>,
     ) -> Result<()> {
+        let auth_config = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.runtime.auth.clone())
+            .unwrap_or_default();
+
+        let auth = match &auth_config.oidc {
+            Some(oidc) => Some(
+                auth::OidcValidator::try_new(oidc)
+                    .await
+                    .context(UnableToInitializeAuthSnafu)?,
+            ),
+            None => None,
+        };
+
+        let tls_config = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.runtime.tls.clone())
+            .unwrap_or_default();
+
+        let tls = tls::TlsConfig::try_load(&tls_config, &*self.secrets_provider.read().await)
+            .await
+            .context(UnableToLoadTlsSnafu)?;
+
         let http_server_future = http::start(
             config.http_bind_address,
             Arc::clone(&self.app),
@@ -959,25 +1701,136 @@ impl Runtime {
             Arc::clone(&self.embeds),
             config.clone().into(),
             with_metrics,
+            auth.clone(),
+            tls.clone(),
+            Arc::new(self.clone()),
         );
 
-        let flight_server_future = flight::start(config.flight_bind_address, Arc::clone(&self.df));
-        let open_telemetry_server_future =
-            opentelemetry::start(config.open_telemetry_bind_address, Arc::clone(&self.df));
+        let flight_config = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.runtime.flight.clone())
+            .unwrap_or_default();
+
+        let flight_server_future = flight::start(
+            config.flight_bind_address,
+            Arc::clone(&self.df),
+            flight_config,
+            auth,
+            tls.clone(),
+        );
+        let open_telemetry_server_future = opentelemetry::start(
+            config.open_telemetry_bind_address,
+            Arc::clone(&self.df),
+            tls.clone(),
+        );
+        let metrics_server_future = async {
+            match (with_metrics, self.metrics_handle.clone()) {
+                (Some(metrics_socket), Some(metrics_handle)) => {
+                    http::start_metrics(metrics_socket, metrics_handle, tls).await
+                }
+                _ => std::future::pending().await,
+            }
+        };
         let pods_watcher_future = self.start_pods_watcher();
 
         tokio::select! {
             http_res = http_server_future => http_res.context(UnableToStartHttpServerSnafu),
             flight_res = flight_server_future => flight_res.context(UnableToStartFlightServerSnafu),
             open_telemetry_res = open_telemetry_server_future => open_telemetry_res.context(UnableToStartOpenTelemetryServerSnafu),
+            metrics_res = metrics_server_future => metrics_res.context(UnableToStartMetricsServerSnafu),
             pods_watcher_res = pods_watcher_future => pods_watcher_res.context(UnableToInitializePodsWatcherSnafu),
-            () = shutdown_signal() => {
+            () = self.drain_on_shutdown() => {
                 tracing::info!("Goodbye!");
                 Ok(())
             },
         }
     }
 
+    /// Waits for the shutdown signal, then drains the runtime for `runtime.shutdown.drain_period`, encrypts the
+    /// acceleration files with an `encryption_secret` and flushes the audit log. The servers keep serving the requests
+    /// in flight until it returns.
+    async fn drain_on_shutdown(&self) {
+        shutdown_signal().await;
+
+        let drain_period = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .and_then(|app| app.runtime.shutdown.drain_period.clone())
+            // Invalid durations are reported by the validation of the spicepod.
+            .and_then(|drain_period| fundu::parse_duration(&drain_period).ok())
+            .unwrap_or(shutdown::DEFAULT_DRAIN_PERIOD);
+        tracing::info!("Shutting down, no longer accepting queries");
+        shutdown::drain(drain_period).await;
+        self.close_encrypted_accelerations().await;
+        if tokio::task::spawn_blocking(dataaccelerator::encryption::encrypt_acceleration_files)
+            .await
+            .is_err()
+        {
+            tracing::error!("Unable to encrypt the acceleration files");
+        }
+
+        if let Some(audit_log) = self.df.audit_log() {
+            if tokio::time::timeout(FLUSH_TIMEOUT, audit_log.flush())
+                .await
+                .is_err()
+            {
+                tracing::error!(
+                    "Unable to flush the audit log within {}s, {} audit events were not written",
+                    FLUSH_TIMEOUT.as_secs(),
+                    audit_log.pending()
+                );
+            }
+        }
+    }
+
+    /// Unloads the datasets whose acceleration files are encrypted, and waits for their accelerators to close their
+    /// connections, so the files are complete and no longer written to when they are encrypted.
+    async fn close_encrypted_accelerations(&self) {
+        let datasets = match self.app.read().await.as_ref() {
+            Some(app) => Self::get_valid_datasets(app, false),
+            None => return,
+        };
+
+        let mut accelerators = vec![];
+        for ds in datasets.iter().filter(|ds| {
+            ds.acceleration
+                .as_ref()
+                .is_some_and(|acceleration| acceleration.encryption_secret.is_some())
+        }) {
+            let Ok(table) = self.df.ctx.table_provider(ds.name.clone()).await else {
+                continue;
+            };
+            if let Some(accelerated) = table.as_any().downcast_ref::<AcceleratedTable>() {
+                accelerators.push(accelerated.get_accelerator());
+            }
+            drop(table);
+            if let Err(e) = self.df.remove_table(&ds.name) {
+                tracing::warn!("Unable to unload dataset {}: {e}", ds.name);
+            }
+        }
+
+        // The refresh tasks of the tables are aborted as they are dropped, releasing the accelerators shortly after.
+        let deadline = tokio::time::Instant::now() + CLOSE_TIMEOUT;
+        while accelerators
+            .iter()
+            .any(|accelerator| Arc::strong_count(accelerator) > 1)
+        {
+            if tokio::time::Instant::now() >= deadline {
+                tracing::warn!(
+                    "Accelerators are still in use after {}s, encrypting their files anyway",
+                    CLOSE_TIMEOUT.as_secs()
+                );
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    }
+
     pub async fn start_pods_watcher(&self) -> notify::Result<()> {
         let mut pods_watcher = self.pods_watcher.write().await;
         let Some(mut pods_watcher) = pods_watcher.take() else {
@@ -987,77 +1840,208 @@ impl Runtime {
 
         while let Some(new_app) = rx.recv().await {
             let mut app_lock = self.app.write().await;
-            if let Some(current_app) = app_lock.as_mut() {
-                if *current_app == new_app {
-                    continue;
-                }
-
-                tracing::debug!("Updated pods information: {:?}", new_app);
-                tracing::debug!("Previous pods information: {:?}", current_app);
-
-                // check for new and updated datasets
-                let valid_datasets = Self::get_valid_datasets(&new_app, true);
-                for ds in &valid_datasets {
-                    if let Some(current_ds) = current_app
-                        .datasets
-                        .iter()
-                        .find(|d| TableReference::parse_str(&d.name) == ds.name)
-                    {
-                        if TableReference::parse_str(&current_ds.name) != ds.name {
-                            self.update_dataset(ds).await;
-                        }
-                    } else {
-                        status::update_dataset(&ds.name, status::ComponentStatus::Initializing);
-                        self.load_dataset(ds).await;
-                    }
-                }
+            let Some(current_app) = app_lock.as_mut() else {
+                *app_lock = Some(new_app);
+                continue;
+            };
+            if *current_app == new_app {
+                continue;
+            }
 
-                // check for new and updated models
-                for model in &new_app.models {
-                    if let Some(current_model) =
-                        current_app.models.iter().find(|m| m.name == model.name)
-                    {
-                        if current_model != model {
-                            self.update_model(model).await;
-                        }
-                    } else {
-                        status::update_model(&model.name, status::ComponentStatus::Initializing);
-                        self.load_model(model).await;
-                    }
-                }
+            let hot_reload = new_app.runtime.hot_reload.clone();
+            if !hot_reload.enabled {
+                tracing::info!("Spicepod changed, restart the runtime to apply the changes");
+                continue;
+            }
 
-                // Remove models that are no longer in the app
-                for model in &current_app.models {
-                    if !new_app.models.iter().any(|m| m.name == model.name) {
-                        status::update_model(&model.name, status::ComponentStatus::Disabled);
-                        self.remove_model(model).await;
-                    }
+            {
+                let diff = AppDiff::new(current_app, &new_app);
+                if diff.is_empty() {
+                    tracing::debug!("Spicepod changed without affecting any components");
                 }
 
-                // Remove datasets that are no longer in the app
-                for ds in &current_app.datasets {
-                    if !new_app.datasets.iter().any(|d| d.name == ds.name) {
-                        let ds = match Dataset::try_from(ds.clone()) {
-                            Ok(ds) => ds,
-                            Err(e) => {
-                                tracing::error!("Could not remove dataset {}: {e}", ds.name);
-                                continue;
-                            }
-                        };
-                        status::update_dataset(&ds.name, status::ComponentStatus::Disabled);
-                        self.remove_dataset(&ds).await;
-                    }
+                let mut diagnostics = self
+                    .spicepod_path
+                    .as_ref()
+                    .map(spicepod::validation::validate)
+                    .unwrap_or_default();
+                diagnostics.extend(
+                    validation::validate_app(&new_app, &*self.secrets_provider.read().await).await,
+                );
+                if hot_reload.dry_run {
+                    tracing::info!("Spicepod changes (dry run, not applied):\n{diff}");
+                    validation::log_diagnostics(&diagnostics);
+                    continue;
                 }
 
-                *current_app = new_app;
-            } else {
-                *app_lock = Some(new_app);
+                tracing::info!("Applying spicepod changes:\n{diff}");
+                validation::log_diagnostics(&diagnostics);
+                self.apply_app_diff(&diff, &new_app).await;
             }
+
+            *current_app = new_app;
         }
 
         Ok(())
     }
 
+    /// Loads, reloads and unloads the components in `diff`, which leads to `app`. Unchanged components are left
+    /// untouched.
+    async fn apply_app_diff(&self, diff: &AppDiff<'_>, app: &App) {
+        if let Err(e) = quotas::configure(&app.runtime.namespaces) {
+            tracing::error!("{e}");
+        }
+
+        for ds in diff
+            .datasets
+            .removed
+            .iter()
+            .filter_map(|ds| Self::diff_dataset(ds))
+        {
+            status::update_dataset(&ds.name, status::ComponentStatus::Disabled);
+            self.remove_dataset(&ds).await;
+        }
+        for ds in diff
+            .datasets
+            .updated
+            .iter()
+            .filter_map(|ds| Self::diff_dataset(ds))
+        {
+            let _ = self.update_dataset(&ds).await;
+        }
+        for ds in diff
+            .datasets
+            .added
+            .iter()
+            .filter_map(|ds| Self::diff_dataset(ds))
+        {
+            status::update_dataset(&ds.name, status::ComponentStatus::Initializing);
+            self.load_dataset(&ds).await;
+        }
+
+        self.apply_views_diff(diff, app);
+        self.apply_functions_diff(diff, app).await;
+
+        for model in &diff.models.removed {
+            status::update_model(&model.name, status::ComponentStatus::Disabled);
+            self.remove_model(model).await;
+        }
+        for model in &diff.models.updated {
+            self.update_model(model).await;
+        }
+        for model in &diff.models.added {
+            status::update_model(&model.name, status::ComponentStatus::Initializing);
+            self.load_model(model).await;
+        }
+
+        for llm in &diff.llms.removed {
+            self.remove_llm(llm).await;
+        }
+        for llm in diff.llms.updated.iter().chain(&diff.llms.added) {
+            self.remove_llm(llm).await;
+            self.load_llm(llm).await;
+        }
+
+        for embedding in &diff.embeddings.removed {
+            self.remove_embedding(embedding).await;
+        }
+        for embedding in diff.embeddings.updated.iter().chain(&diff.embeddings.added) {
+            self.remove_embedding(embedding).await;
+            self.load_embedding(embedding).await;
+        }
+    }
+
+    /// Unloads the removed views, and reloads the added and updated views along with the views that depend on a
+    /// changed dataset, since a view keeps the table it was planned against.
+    fn apply_views_diff(&self, diff: &AppDiff<'_>, app: &App) {
+        for view in &diff.views.removed {
+            self.remove_view(&Dataset::parse_table_reference(&view.name));
+        }
+
+        let changed_views: HashSet<&str> = diff
+            .views
+            .added
+            .iter()
+            .chain(&diff.views.updated)
+            .map(|v| v.name.as_str())
+            .collect();
+        let changed_datasets: Vec<TableReference> = diff
+            .datasets
+            .added
+            .iter()
+            .chain(&diff.datasets.updated)
+            .chain(&diff.datasets.removed)
+            .map(|ds| Dataset::parse_table_reference(&ds.name))
+            .collect();
+        let valid_datasets = Self::get_valid_datasets(app, false);
+
+        for view in Self::get_valid_views(app, true) {
+            let depends_on_changed_dataset = get_view_dependent_tables(&view)
+                .is_ok_and(|tables| tables.iter().any(|t| changed_datasets.contains(t)));
+            if !changed_views.contains(view.name.to_string().as_str())
+                && !depends_on_changed_dataset
+            {
+                continue;
+            }
+
+            self.remove_view(&view.name);
+            match self.load_view(&view, &valid_datasets) {
+                Ok(()) => tracing::info!("Loaded view {}", view.name),
+                Err(e) => tracing::error!("Unable to load view: {e}"),
+            }
+        }
+    }
+
+    /// Removes the removed functions, and reloads the added and updated functions. All functions are reloaded when a
+    /// dataset or view changes, since a function keeps the tables it was planned against.
+    async fn apply_functions_diff(&self, diff: &AppDiff<'_>, app: &App) {
+        for function in &diff.functions.removed {
+            self.df.remove_function(&function.name);
+            tracing::info!("Unloaded function {}", function.name);
+        }
+
+        let reload_all = !diff.datasets.is_empty() || !diff.views.is_empty();
+        for function in Self::get_valid_functions(app) {
+            let changed = diff
+                .functions
+                .added
+                .iter()
+                .chain(&diff.functions.updated)
+                .any(|f| f.name == function.name);
+            if !changed && !reload_all {
+                continue;
+            }
+
+            match self.load_function(&function).await {
+                Ok(()) => tracing::info!("Loaded function {}", function.name),
+                Err(e) => tracing::error!("{e}"),
+            }
+        }
+    }
+
+    fn remove_view(&self, name: &TableReference) {
+        match self.df.remove_table(name) {
+            Ok(()) => tracing::info!("Unloaded view {name}"),
+            Err(e) => tracing::warn!("Unable to unload view {name}: {e}"),
+        }
+    }
+
+    /// Validates the spicepod the runtime was started from and the app loaded from it, without loading any components.
+    pub async fn validate(&self) -> Vec<validation::Diagnostic> {
+        let mut diagnostics = self
+            .spicepod_path
+            .as_ref()
+            .map(spicepod::validation::validate)
+            .unwrap_or_default();
+
+        if let Some(app) = self.app.read().await.as_ref() {
+            diagnostics
+                .extend(validation::validate_app(app, &*self.secrets_provider.read().await).await);
+        }
+
+        diagnostics
+    }
+
     pub async fn init_results_cache(&self) {
         let app = self.app.read().await;
         let Some(app) = app.as_ref() else { return };
@@ -1079,11 +2063,138 @@ impl Runtime {
         };
     }
 
+    pub async fn init_audit_log(&self) -> Result<()> {
+        let audit_config = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.runtime.audit.clone())
+            .unwrap_or_default();
+
+        if !audit_config.enabled {
+            return Ok(());
+        }
+
+        let audit_log = audit::AuditLog::try_new(&audit_config, &self.df)
+            .await
+            .context(UnableToInitializeAuditLogSnafu)?;
+        self.df.set_audit_log(audit_log);
+
+        tracing::info!("Audit log enabled; sink: {:?}", audit_config.sink);
+
+        Ok(())
+    }
+
+    pub async fn init_inference_log(&self) -> Result<()> {
+        let config = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .map(|app| app.runtime.inference_log.clone())
+            .unwrap_or_default();
+
+        if !config.enabled {
+            return Ok(());
+        }
+
+        let inference_log = inference_log::InferenceLog::try_new(&config, &self.df)
+            .await
+            .context(UnableToInitializeInferenceLogSnafu)?;
+        self.df.set_inference_log(inference_log);
+
+        tracing::info!("Inference log enabled");
+
+        Ok(())
+    }
+
+    /// Keys the HMAC of `hash` masks with the secret of `runtime.masks.hash_secret`, if set.
+    /// The `token` of `runtime.cluster.secret`, that the members of the cluster authenticate to each other with.
+    async fn cluster_token(&self, cluster: &cluster::Cluster) -> Result<String> {
+        let secret = self
+            .secrets_provider
+            .read()
+            .await
+            .get_secret(cluster.secret())
+            .await
+            .context(UnableToGetClusterTokenSnafu {
+                secret: cluster.secret(),
+            })?;
+        secret
+            .as_ref()
+            .and_then(|secret| secret.get("token"))
+            .filter(|token| !token.is_empty())
+            .map(ToString::to_string)
+            .context(MissingClusterTokenSnafu {
+                secret: cluster.secret(),
+            })
+    }
+
+    pub async fn init_mask_key(&self) -> Result<()> {
+        let Some(secret_name) = self
+            .app
+            .read()
+            .await
+            .as_ref()
+            .and_then(|app| app.runtime.masks.hash_secret.clone())
+        else {
+            return Ok(());
+        };
+
+        let secret = self
+            .secrets_provider
+            .read()
+            .await
+            .get_secret(&secret_name)
+            .await
+            .context(UnableToGetMaskKeySnafu {
+                secret: secret_name.clone(),
+            })?;
+        let key = secret
+            .as_ref()
+            .and_then(|secret| secret.get("key"))
+            .filter(|key| !key.is_empty())
+            .context(MissingMaskKeySnafu {
+                secret: secret_name,
+            })?;
+        self.df.set_mask_key(key.as_bytes());
+
+        Ok(())
+    }
+
+    pub async fn init_deployment_results(&self) -> Result<()> {
+        let deployment_results_table_reference = TableReference::partial(
+            SPICE_RUNTIME_SCHEMA,
+            deployments::DEFAULT_DEPLOYMENT_RESULTS_TABLE,
+        );
+        if self
+            .df
+            .table_exists(deployment_results_table_reference.clone())
+        {
+            return Ok(());
+        }
+
+        match deployments::instantiate_deployment_results_table().await {
+            Ok(table) => self
+                .df
+                .register_runtime_table(deployment_results_table_reference, table)
+                .context(UnableToCreateBackendSnafu),
+            Err(err) => Err(Error::UnableToTrackDeploymentResults { source: err }),
+        }
+    }
+
     pub async fn init_query_history(&self) -> Result<()> {
         let query_history_table_reference = TableReference::partial(
             SPICE_RUNTIME_SCHEMA,
             query_history::DEFAULT_QUERY_HISTORY_TABLE,
         );
+        // An extension may have registered a query history table that is synced elsewhere.
+        if self.df.table_exists(query_history_table_reference.clone()) {
+            tracing::debug!("Query history table is already registered");
+            return Ok(());
+        }
+
         match query_history::instantiate_query_history_table().await {
             Ok(table) => self
                 .df
@@ -1107,8 +2218,17 @@ fn verify_dependent_tables(view: &View, existing_tables: &[TableReference]) -> b
         }
     };
 
+    // The tables of the views of a namespace are resolved in its catalog.
+    let catalog = view
+        .namespace
+        .as_deref()
+        .unwrap_or(datafusion::SPICE_DEFAULT_CATALOG);
+    let existing_tables = existing_tables
+        .iter()
+        .map(|table| resolve_table(table, datafusion::SPICE_DEFAULT_CATALOG))
+        .collect::<Vec<_>>();
     for tbl in &dependent_tables {
-        if !existing_tables.contains(tbl) {
+        if !existing_tables.contains(&resolve_table(tbl, catalog)) {
             tracing::error!(
                 "Failed to load view {}. Dependent table {} not found",
                 &view.name,
@@ -1141,6 +2261,14 @@ fn get_view_dependent_tables(view: impl Borrow<View>) -> Result<Vec<TableReferen
     Ok(get_dependent_table_names(&statements[0]))
 }
 
+/// `table` with its catalog and schema, defaulting to `catalog` and the `public` schema.
+pub(crate) fn resolve_table(table: &TableReference, catalog: &str) -> TableReference {
+    let resolved = table
+        .clone()
+        .resolve(catalog, datafusion::SPICE_DEFAULT_SCHEMA);
+    TableReference::full(resolved.catalog, resolved.schema, resolved.table)
+}
+
 fn get_dependent_table_names(statement: &parser::Statement) -> Vec<TableReference> {
     let mut table_names = Vec::new();
     let mut cte_names = HashSet::new();
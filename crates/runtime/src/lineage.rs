@@ -0,0 +1,409 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The lineage of the app: the sources datasets load from, the datasets and views that views read, and the tables jobs
+//! read and write, i.e. a job materializing `daily_summary` with `INSERT INTO daily_summary SELECT ... FROM orders`.
+//!
+//! It is served as a graph by `GET /v1/lineage`, and as edges in `runtime.lineage`, to see what a change to a source
+//! affects downstream.
+
+use std::{
+    any::Any,
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::Arc,
+};
+
+use app::App;
+use arrow::{
+    array::{RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use async_trait::async_trait;
+use datafusion::{
+    datasource::{TableProvider, TableType},
+    error::Result as DataFusionResult,
+    execution::context::SessionState,
+    logical_expr::{Expr, LogicalPlan},
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+    sql::TableReference,
+};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::{
+    component::{dataset::Dataset, view::View},
+    datafusion::{query::copy, SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA},
+    get_view_dependent_tables,
+};
+
+pub const LINEAGE_TABLE: &str = "lineage";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Source,
+    Dataset,
+    AcceleratedDataset,
+    View,
+    Job,
+    /// A table that isn't a component of the app, such as a runtime table.
+    Table,
+    /// A location jobs export to with `COPY`.
+    Location,
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeKind::Source => write!(f, "source"),
+            NodeKind::Dataset => write!(f, "dataset"),
+            NodeKind::AcceleratedDataset => write!(f, "accelerated_dataset"),
+            NodeKind::View => write!(f, "view"),
+            NodeKind::Job => write!(f, "job"),
+            NodeKind::Table => write!(f, "table"),
+            NodeKind::Location => write!(f, "location"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Node {
+    /// The kind and name of the node, i.e. `view:top_customers`.
+    pub id: String,
+    pub kind: NodeKind,
+    pub name: String,
+}
+
+/// `from` is read by, or loaded into, `to`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Lineage {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Lineage {
+    /// The lineage of the components of `app`, planning the SQL of jobs in `state` to find the tables they use.
+    pub async fn of(app: &App, state: &SessionState) -> Self {
+        let mut builder = LineageBuilder::default();
+
+        for dataset in &app.datasets {
+            let kind = if dataset.acceleration.as_ref().is_some_and(|a| a.enabled) {
+                NodeKind::AcceleratedDataset
+            } else {
+                NodeKind::Dataset
+            };
+            let id = builder.add_table(kind, &Dataset::parse_table_reference(&dataset.name));
+            let source = builder.add_node(NodeKind::Source, &dataset.from);
+            builder.add_edge(source, id);
+        }
+
+        // Views are added before their edges, as views can read views defined after them.
+        let views = app
+            .views
+            .iter()
+            .filter_map(|view| View::try_from(view.clone()).ok())
+            .collect::<Vec<_>>();
+        for view in &views {
+            builder.add_table(NodeKind::View, &view.name);
+        }
+        for view in &views {
+            let id = builder.table(&view.name);
+            for table in get_view_dependent_tables(view).unwrap_or_default() {
+                let from = builder.table(&table);
+                builder.add_edge(from, id.clone());
+            }
+        }
+
+        for job in &app.jobs {
+            let id = builder.add_node(NodeKind::Job, &job.name);
+            let sql = copy::rewrite_copy_statement(&job.sql);
+            let plan = match state
+                .create_logical_plan(sql.as_deref().unwrap_or(&job.sql))
+                .await
+            {
+                Ok(plan) => plan,
+                Err(e) => {
+                    tracing::debug!("Unable to plan job {} for its lineage: {e}", job.name);
+                    continue;
+                }
+            };
+
+            for table in scanned_tables(&plan) {
+                let from = builder.table(&table);
+                builder.add_edge(from, id.clone());
+            }
+            let to = match &plan {
+                LogicalPlan::Dml(dml) => builder.table(&dml.table_name),
+                LogicalPlan::Copy(copy) => builder.add_node(NodeKind::Location, &copy.output_url),
+                _ => continue,
+            };
+            builder.add_edge(id, to);
+        }
+
+        builder.build()
+    }
+
+    /// The part of the lineage downstream of the node `id`, including it: what a change to it affects.
+    #[must_use]
+    pub fn downstream_of(&self, id: &str) -> Self {
+        let mut reached = BTreeSet::from([id]);
+        let mut pending = vec![id];
+        while let Some(node) = pending.pop() {
+            for edge in self.edges.iter().filter(|edge| edge.from == node) {
+                if reached.insert(edge.to.as_str()) {
+                    pending.push(edge.to.as_str());
+                }
+            }
+        }
+
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|node| reached.contains(node.id.as_str()))
+                .cloned()
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|edge| reached.contains(edge.from.as_str()))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LineageBuilder {
+    nodes: BTreeSet<Node>,
+    edges: BTreeSet<Edge>,
+    /// The nodes of datasets and views by their fully qualified name, as SQL can name them with or without the
+    /// default catalog and schema.
+    tables: HashMap<String, String>,
+}
+
+impl LineageBuilder {
+    fn add_node(&mut self, kind: NodeKind, name: &str) -> String {
+        let id = format!("{kind}:{name}");
+        self.nodes.insert(Node {
+            id: id.clone(),
+            kind,
+            name: name.to_string(),
+        });
+        id
+    }
+
+    fn add_table(&mut self, kind: NodeKind, table: &TableReference) -> String {
+        let id = self.add_node(kind, &table.to_string());
+        self.tables.insert(qualified_name(table), id.clone());
+        id
+    }
+
+    /// The node of the dataset or view `table`, or of a table that isn't a component of the app.
+    fn table(&mut self, table: &TableReference) -> String {
+        match self.tables.get(&qualified_name(table)) {
+            Some(id) => id.clone(),
+            None => self.add_node(NodeKind::Table, &table.to_string()),
+        }
+    }
+
+    fn add_edge(&mut self, from: String, to: String) {
+        self.edges.insert(Edge { from, to });
+    }
+
+    fn build(self) -> Lineage {
+        Lineage {
+            nodes: self.nodes.into_iter().collect(),
+            edges: self.edges.into_iter().collect(),
+        }
+    }
+}
+
+fn qualified_name(table: &TableReference) -> String {
+    table
+        .clone()
+        .resolve(SPICE_DEFAULT_CATALOG, SPICE_DEFAULT_SCHEMA)
+        .to_string()
+}
+
+/// The tables the plan scans, not looking into views.
+fn scanned_tables(plan: &LogicalPlan) -> Vec<TableReference> {
+    fn collect(plan: &LogicalPlan, tables: &mut Vec<TableReference>) {
+        if let LogicalPlan::TableScan(scan) = plan {
+            if !tables.contains(&scan.table_name) {
+                tables.push(scan.table_name.clone());
+            }
+        }
+        for input in plan.inputs() {
+            collect(input, tables);
+        }
+    }
+
+    let mut tables = vec![];
+    collect(plan, &mut tables);
+    tables
+}
+
+/// Lists the edges of the lineage of the current app when it is scanned.
+pub struct LineageTable {
+    app: Arc<RwLock<Option<App>>>,
+    schema: SchemaRef,
+}
+
+impl LineageTable {
+    #[must_use]
+    pub fn new(app: Arc<RwLock<Option<App>>>) -> Self {
+        Self {
+            app,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("upstream_kind", DataType::Utf8, false),
+                Field::new("upstream", DataType::Utf8, false),
+                Field::new("downstream_kind", DataType::Utf8, false),
+                Field::new("downstream", DataType::Utf8, false),
+            ])),
+        }
+    }
+
+    fn record_batch(&self, lineage: &Lineage) -> DataFusionResult<RecordBatch> {
+        let nodes = lineage
+            .nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node))
+            .collect::<HashMap<_, _>>();
+        let edges = lineage
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                Some((
+                    *nodes.get(edge.from.as_str())?,
+                    *nodes.get(edge.to.as_str())?,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(RecordBatch::try_new(
+            self.schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    edges.iter().map(|(from, _)| from.kind.to_string()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    edges.iter().map(|(from, _)| from.name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    edges.iter().map(|(_, to)| to.kind.to_string()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    edges.iter().map(|(_, to)| to.name.as_str()),
+                )),
+            ],
+        )?)
+    }
+}
+
+#[async_trait]
+impl TableProvider for LineageTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let lineage = match self.app.read().await.as_ref() {
+            Some(app) => Lineage::of(app, state).await,
+            None => Lineage::default(),
+        };
+        let batch = self.record_batch(&lineage)?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_downstream_of_source() {
+        let mut builder = LineageBuilder::default();
+        let orders = builder.add_table(
+            NodeKind::AcceleratedDataset,
+            &TableReference::bare("orders"),
+        );
+        let source = builder.add_node(NodeKind::Source, "postgres:orders");
+        builder.add_edge(source.clone(), orders.clone());
+        let users = builder.add_table(NodeKind::Dataset, &TableReference::bare("users"));
+        let users_source = builder.add_node(NodeKind::Source, "mysql:users");
+        builder.add_edge(users_source, users.clone());
+        let view = builder.add_table(NodeKind::View, &TableReference::bare("top_customers"));
+        // Named as views and jobs name them, with or without the default schema.
+        let orders_in_view = builder.table(&TableReference::partial("public", "orders"));
+        builder.add_edge(orders_in_view, view.clone());
+        builder.add_edge(users, view.clone());
+        let lineage = builder.build();
+
+        let downstream = lineage.downstream_of(&source);
+        assert_eq!(
+            downstream
+                .nodes
+                .iter()
+                .map(|node| node.id.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "accelerated_dataset:orders",
+                "source:postgres:orders",
+                "view:top_customers",
+            ]
+        );
+        assert_eq!(
+            downstream.edges,
+            vec![
+                Edge {
+                    from: orders.clone(),
+                    to: view,
+                },
+                Edge {
+                    from: source,
+                    to: orders,
+                },
+            ]
+        );
+    }
+}
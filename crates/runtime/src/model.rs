@@ -14,13 +14,17 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 #![allow(clippy::module_name_repetitions)]
+use arrow::array::AsArray;
 use arrow::record_batch::RecordBatch;
+use async_openai::types::CompletionUsage;
+use datafusion::sql::TableReference;
 use llms::chat::{Chat, Error as LlmError};
-use llms::embeddings::Embed;
+use llms::embeddings::{Embed, Pooling};
 use llms::openai::{DEFAULT_EMBEDDING_MODEL, DEFAULT_LLM_MODEL};
 use model_components::model::{Error as ModelError, Model};
 use spicepod::component::embeddings::{EmbeddingParams, EmbeddingPrefix};
 use spicepod::component::llms::{Architecture, LlmParams, LlmPrefix};
+use spicepod::component::model::Forecast;
 use std::collections::HashMap;
 use std::result::Result;
 use std::sync::Arc;
@@ -29,7 +33,21 @@ use tokio::sync::RwLock;
 use crate::DataFusion;
 pub type LLMModelStore = HashMap<String, RwLock<Box<dyn Chat>>>;
 
+/// Counts the prompt and completion tokens of an LLM request, when the model reports them.
+pub(crate) fn record_token_usage(model: &str, usage: Option<&CompletionUsage>) {
+    if let Some(usage) = usage {
+        let labels = [("model", model.to_string())];
+        metrics::counter!("llm_prompt_tokens", &labels).increment(u64::from(usage.prompt_tokens));
+        metrics::counter!("llm_completion_tokens", &labels)
+            .increment(u64::from(usage.completion_tokens));
+    }
+}
+
 pub async fn run(m: &Model, df: Arc<DataFusion>) -> Result<RecordBatch, ModelError> {
+    if let Some((dataset, forecast)) = forecast_binding(m) {
+        return run_forecast(m, &df, &dataset, forecast).await;
+    }
+
     match df
         .ctx
         .sql(
@@ -52,6 +70,149 @@ pub async fn run(m: &Model, df: Arc<DataFusion>) -> Result<RecordBatch, ModelErr
     }
 }
 
+/// Forecasts each series of the dataset of `m`, or only those of `series` if given, for a model bound with
+/// `forecast.series_key`. Returns the key of each series with its forecast.
+pub async fn run_series(
+    m: &Model,
+    df: Arc<DataFusion>,
+    series: Option<Vec<String>>,
+) -> Result<Vec<(String, RecordBatch)>, ModelError> {
+    let Some((dataset, forecast)) = forecast_binding(m) else {
+        return Err(run_error("The model doesn't forecast series"));
+    };
+    let Some(series_key) = &forecast.series_key else {
+        return Err(run_error("The model doesn't forecast series"));
+    };
+
+    let keys = match series {
+        Some(keys) => keys,
+        None => {
+            let sql = format!(
+                "SELECT DISTINCT CAST({} AS VARCHAR) AS key FROM {dataset} WHERE {} IS NOT NULL ORDER BY key",
+                quote_identifier(series_key),
+                quote_identifier(series_key)
+            );
+            let batches = collect(&df, &sql).await?;
+            let mut keys = vec![];
+            for batch in &batches {
+                let Some(column) = batch.column(0).as_string_opt::<i32>() else {
+                    return Err(run_error("Unable to read the keys of the series"));
+                };
+                keys.extend(column.iter().flatten().map(ToString::to_string));
+            }
+            keys
+        }
+    };
+
+    let lookback = lookback(m, forecast)?;
+    let mut forecasts = Vec::with_capacity(keys.len());
+    for key in keys {
+        let sql = forecast_sql(&dataset, forecast, lookback, Some(&key));
+        let input = collect(&df, &sql).await?;
+        forecasts.push((key, m.run(input)?));
+    }
+
+    Ok(forecasts)
+}
+
+/// Forecasts the time series of the dataset of `m`, a model bound with `forecast`.
+async fn run_forecast(
+    m: &Model,
+    df: &DataFusion,
+    dataset: &str,
+    forecast: &Forecast,
+) -> Result<RecordBatch, ModelError> {
+    if forecast.series_key.is_some() {
+        return Err(run_error(
+            "The model forecasts each series of its dataset, predict the series instead",
+        ));
+    }
+
+    let sql = forecast_sql(dataset, forecast, lookback(m, forecast)?, None);
+    let input = collect(df, &sql).await?;
+    m.run(input)
+}
+
+fn forecast_binding(m: &Model) -> Option<(String, &Forecast)> {
+    let forecast = m.model.forecast.as_ref()?;
+    let dataset = m.model.datasets.first()?;
+    Some((
+        TableReference::from(dataset.as_str()).to_quoted_string(),
+        forecast,
+    ))
+}
+
+fn lookback(m: &Model, forecast: &Forecast) -> Result<usize, ModelError> {
+    forecast
+        .lookback
+        .or_else(|| m.lookback_size())
+        .ok_or_else(|| {
+            run_error("Unable to infer the lookback of the model, set forecast.lookback")
+        })
+}
+
+/// Returns the SQL of the most recent `lookback` rows of the series, in time order, with the time as `ts`, the value
+/// as `y` and the covariates as `x0`, `x1` and so on.
+fn forecast_sql(
+    dataset: &str,
+    forecast: &Forecast,
+    lookback: usize,
+    series: Option<&str>,
+) -> String {
+    let mut columns = vec![
+        format!("{} AS ts", quote_identifier(&forecast.time_column)),
+        format!(
+            "CAST({} AS DOUBLE) AS y",
+            quote_identifier(&forecast.value_column)
+        ),
+    ];
+    for (i, covariate) in forecast.covariates.iter().enumerate() {
+        columns.push(format!(
+            "CAST({} AS DOUBLE) AS x{i}",
+            quote_identifier(covariate)
+        ));
+    }
+
+    let filter = match (&forecast.series_key, series) {
+        (Some(series_key), Some(series)) => format!(
+            " WHERE CAST({} AS VARCHAR) = '{}'",
+            quote_identifier(series_key),
+            series.replace('\'', "''")
+        ),
+        _ => String::new(),
+    };
+
+    format!(
+        "SELECT * FROM (SELECT {} FROM {dataset}{filter} ORDER BY {} DESC LIMIT {lookback}) ORDER BY ts ASC",
+        columns.join(", "),
+        quote_identifier(&forecast.time_column)
+    )
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+async fn collect(df: &DataFusion, sql: &str) -> Result<Vec<RecordBatch>, ModelError> {
+    df.ctx
+        .sql(sql)
+        .await
+        .map_err(|e| ModelError::UnableToRunModel {
+            source: Box::new(e),
+        })?
+        .collect()
+        .await
+        .map_err(|e| ModelError::UnableToRunModel {
+            source: Box::new(e),
+        })
+}
+
+fn run_error(message: &str) -> ModelError {
+    ModelError::UnableToRunModel {
+        source: message.into(),
+    }
+}
+
 pub fn try_to_embedding(
     component: &spicepod::component::embeddings::Embeddings,
 ) -> Result<Box<dyn Embed>, LlmError> {
@@ -65,7 +226,11 @@ pub fn try_to_embedding(
 
     let model_id = component.get_model_id();
 
-    match construct_embedding_params(&prefix, &(component.params).clone().unwrap_or_default()) {
+    match construct_embedding_params(
+        &prefix,
+        &model_id,
+        &(component.params).clone().unwrap_or_default(),
+    )? {
         EmbeddingParams::OpenAiParams {
             api_base,
             api_key,
@@ -78,6 +243,38 @@ pub fn try_to_embedding(
             org_id,
             project_id,
         ))),
+        EmbeddingParams::HuggingfaceParams {
+            model_id,
+            revision,
+            hf_token,
+            pooling,
+            normalize,
+        } => llms::embeddings::create_hf_embedding(
+            &model_id,
+            revision.as_deref(),
+            hf_token,
+            parse_pooling(pooling.as_deref())?,
+            parse_normalize(normalize.as_deref())?,
+        )
+        .map_err(|e| LlmError::FailedToLoadModel {
+            source: Box::new(e),
+        }),
+        EmbeddingParams::LocalModelParams {
+            weights_path,
+            config_path,
+            tokenizer_path,
+            pooling,
+            normalize,
+        } => llms::embeddings::create_local_embedding(
+            &weights_path,
+            &config_path,
+            &tokenizer_path,
+            parse_pooling(pooling.as_deref())?,
+            parse_normalize(normalize.as_deref())?,
+        )
+        .map_err(|e| LlmError::FailedToLoadModel {
+            source: Box::new(e),
+        }),
         EmbeddingParams::None => Err(LlmError::UnsupportedTaskForModel {
             from: component.from.clone(),
             task: "embedding".into(),
@@ -85,6 +282,45 @@ pub fn try_to_embedding(
     }
 }
 
+/// The secret that the API key of a hosted LLM can be read from, named after its source, i.e. `openai`.
+#[must_use]
+pub fn llm_secret_name(component: &spicepod::component::llms::Llm) -> Option<String> {
+    match component.get_prefix()? {
+        prefix @ LlmPrefix::OpenAi => Some(prefix.to_string()),
+        _ => None,
+    }
+}
+
+/// The secret that the API key of a hosted embedding model can be read from, named after its source.
+#[must_use]
+pub fn embedding_secret_name(
+    component: &spicepod::component::embeddings::Embeddings,
+) -> Option<String> {
+    match component.get_prefix()? {
+        prefix @ EmbeddingPrefix::OpenAi => Some(prefix.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_pooling(pooling: Option<&str>) -> Result<Pooling, LlmError> {
+    pooling
+        .map(str::parse)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(|e| LlmError::FailedToLoadModel {
+            source: Box::new(e),
+        })
+}
+
+fn parse_normalize(normalize: Option<&str>) -> Result<bool, LlmError> {
+    match normalize {
+        None => Ok(true),
+        Some(normalize) => normalize.parse().map_err(|_| LlmError::FailedToLoadModel {
+            source: format!("normalize must be true or false, received {normalize}").into(),
+        }),
+    }
+}
+
 /// Attempt to derive a runnable Chat model from a given component from the Spicepod definition.
 pub fn try_to_chat_model(
     component: &spicepod::component::llms::Llm,
@@ -224,14 +460,65 @@ fn construct_llm_params(
 /// If a `model_id` is provided (in the `from: `), it is provided.
 fn construct_embedding_params(
     from: &EmbeddingPrefix,
+    model_id: &Option<String>,
     params: &HashMap<String, String>,
-) -> EmbeddingParams {
+) -> Result<EmbeddingParams, LlmError> {
+    let required = |key: &str| {
+        params
+            .get(key)
+            .cloned()
+            .ok_or_else(|| LlmError::FailedToLoadModel {
+                source: format!("No '{key}' parameter provided").into(),
+            })
+    };
+
     match from {
-        EmbeddingPrefix::OpenAi => EmbeddingParams::OpenAiParams {
+        EmbeddingPrefix::OpenAi => Ok(EmbeddingParams::OpenAiParams {
             api_base: params.get("endpoint").cloned(),
             api_key: params.get("openai_api_key").cloned(),
             org_id: params.get("openai_org_id").cloned(),
             project_id: params.get("openai_project_id").cloned(),
-        },
+        }),
+        EmbeddingPrefix::HuggingFace => Ok(EmbeddingParams::HuggingfaceParams {
+            model_id: model_id
+                .clone()
+                .ok_or_else(|| LlmError::FailedToLoadModel {
+                    source:
+                        "No model provided, i.e. huggingface:huggingface.co/BAAI/bge-small-en-v1.5"
+                            .into(),
+                })?,
+            revision: params.get("revision").cloned(),
+            hf_token: params.get("hf_token").cloned(),
+            pooling: params.get("pooling").cloned(),
+            normalize: params.get("normalize").cloned(),
+        }),
+        EmbeddingPrefix::File => Ok(EmbeddingParams::LocalModelParams {
+            weights_path: required("weights_path")?,
+            config_path: required("config_path")?,
+            tokenizer_path: required("tokenizer_path")?,
+            pooling: params.get("pooling").cloned(),
+            normalize: params.get("normalize").cloned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_the_most_recent_rows_of_a_series() {
+        let forecast = Forecast {
+            time_column: "sale_date".to_string(),
+            value_column: "units".to_string(),
+            covariates: vec!["price".to_string()],
+            lookback: None,
+            series_key: Some("store_id".to_string()),
+        };
+
+        assert_eq!(
+            forecast_sql("\"sales\"", &forecast, 30, Some("o'hare")),
+            "SELECT * FROM (SELECT \"sale_date\" AS ts, CAST(\"units\" AS DOUBLE) AS y, CAST(\"price\" AS DOUBLE) AS x0 FROM \"sales\" WHERE CAST(\"store_id\" AS VARCHAR) = 'o''hare' ORDER BY \"sale_date\" DESC LIMIT 30) ORDER BY ts ASC"
+        );
     }
 }
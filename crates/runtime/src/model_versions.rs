@@ -0,0 +1,134 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks the registries of models with a `version_check_interval`, and loads the new version of a model when the
+//! stage, alias or revision it is loaded from points to a different version.
+//!
+//! The new version replaces the loaded one once it is loaded, so the model keeps serving inferences meanwhile. A
+//! version that fails to load is tried again with the next check.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use model_components::{model::Model, modelsource::source as model_source};
+use spicepod::component::model::Model as SpicepodModel;
+
+use crate::{status, Runtime};
+
+/// How often the intervals of the models are checked.
+const TICK: Duration = Duration::from_secs(10);
+
+/// Checks the versions of the models that are due, until the runtime stops.
+pub(crate) async fn watch(rt: &Runtime) {
+    // When each model was last checked, starting when it is first seen.
+    let mut checked: HashMap<String, Instant> = HashMap::new();
+
+    loop {
+        tokio::time::sleep(TICK).await;
+
+        let models: Vec<(SpicepodModel, Duration)> = match rt.app.read().await.as_ref() {
+            Some(app) => app
+                .models
+                .iter()
+                .filter_map(|m| {
+                    // Invalid intervals are reported by the validation of the spicepod.
+                    let interval = fundu::parse_duration(m.version_check_interval.as_deref()?);
+                    Some((m.clone(), interval.ok()?))
+                })
+                .collect(),
+            None => vec![],
+        };
+        checked.retain(|name, _| models.iter().any(|(m, _)| &m.name == name));
+
+        for (m, interval) in models {
+            let now = Instant::now();
+            let last = *checked.entry(m.name.clone()).or_insert(now);
+            if now.duration_since(last) < interval {
+                continue;
+            }
+            checked.insert(m.name.clone(), now);
+
+            check(rt, &m).await;
+        }
+    }
+}
+
+/// Loads the version of `m` its registry points to, if it isn't the loaded version.
+async fn check(rt: &Runtime, m: &SpicepodModel) {
+    let loaded = match rt.models.read().await.get(&m.name) {
+        // The model may have been updated in the spicepod since it was loaded.
+        Some(loaded) if loaded.model == *m => loaded.version.clone(),
+        _ => return,
+    };
+
+    let secret_name = m
+        .secret
+        .clone()
+        .unwrap_or_else(|| model_source(&m.from).to_string());
+    let secret = match rt
+        .secrets_provider
+        .read()
+        .await
+        .get_secret(&secret_name)
+        .await
+    {
+        Ok(Some(secret)) => secret,
+        Ok(None) => {
+            tracing::warn!(
+                "Unable to check the version of model {}: secret {secret_name} not found",
+                m.name
+            );
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Unable to check the version of model {}: {e}", m.name);
+            return;
+        }
+    };
+
+    let version = match Model::registry_version(m, secret.clone()).await {
+        Ok(Some(version)) => version,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Unable to check the version of model {}: {e}", m.name);
+            return;
+        }
+    };
+    if loaded.as_ref() == Some(&version) {
+        return;
+    }
+
+    tracing::info!(
+        "Model [{}] changed from version {} to {version} in its registry, loading the new version...",
+        m.name,
+        loaded.as_deref().unwrap_or("unknown")
+    );
+    match Model::load(m.clone(), Some(secret)).await {
+        Ok(model) => {
+            let version = model.version.clone().unwrap_or(version);
+            rt.models.write().await.insert(m.name.clone(), model);
+            status::update_model(&m.name, status::ComponentStatus::Ready);
+            tracing::info!("Model [{}] version {version} deployed", m.name);
+        }
+        Err(e) => {
+            metrics::counter!("models_load_error").increment(1);
+            tracing::warn!(
+                "Unable to load version {version} of model {}, keeping the loaded version: {e}",
+                m.name
+            );
+        }
+    }
+}
@@ -14,7 +14,7 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use datafusion::{
     error::DataFusionError,
@@ -23,13 +23,17 @@ use datafusion::{
         runtime_env::{RuntimeConfig, RuntimeEnv},
     },
 };
-use object_store::{aws::AmazonS3Builder, ClientOptions, ObjectStore};
+use object_store::{
+    aws::AmazonS3Builder, limit::LimitStore, ClientOptions, ObjectStore, RetryConfig,
+};
 use url::{form_urlencoded::parse, Url};
 
 #[cfg(feature = "ftp")]
 use crate::objectstore::ftp::FTPObjectStore;
 #[cfg(feature = "ftp")]
 use crate::objectstore::sftp::SFTPObjectStore;
+#[cfg(feature = "hdfs")]
+use hdfs_native_object_store::HdfsObjectStore;
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, Default)]
 pub struct SpiceObjectStoreRegistry {
@@ -46,40 +50,12 @@ impl SpiceObjectStoreRegistry {
         {
             if url.as_str().starts_with("s3://") {
                 if let Some(bucket_name) = url.host_str() {
-                    let mut s3_builder = AmazonS3Builder::from_env()
-                        .with_bucket_name(bucket_name)
-                        .with_allow_http(true);
-                    let mut client_options = ClientOptions::default();
-
                     let params: HashMap<String, String> =
                         parse(url.fragment().unwrap_or_default().as_bytes())
                             .into_owned()
                             .collect();
 
-                    if let Some(region) = params.get("region") {
-                        s3_builder = s3_builder.with_region(region);
-                    }
-                    if let Some(endpoint) = params.get("endpoint") {
-                        s3_builder = s3_builder.with_endpoint(endpoint);
-                    }
-                    if let Some(timeout) = params.get("timeout") {
-                        client_options = client_options.with_timeout(
-                            fundu::parse_duration(timeout).map_err(|_| {
-                                DataFusionError::Configuration(format!(
-                                    "Unable to parse timeout: {timeout}",
-                                ))
-                            })?,
-                        );
-                    }
-                    if let (Some(key), Some(secret)) = (params.get("key"), params.get("secret")) {
-                        s3_builder = s3_builder.with_access_key_id(key);
-                        s3_builder = s3_builder.with_secret_access_key(secret);
-                    } else {
-                        s3_builder = s3_builder.with_skip_signature(true);
-                    };
-                    s3_builder = s3_builder.with_client_options(client_options);
-
-                    return Ok(Arc::new(s3_builder.build()?));
+                    return s3_object_store(bucket_name, &params);
                 }
             }
             #[cfg(feature = "ftp")]
@@ -140,6 +116,18 @@ impl SpiceObjectStoreRegistry {
                     return Ok(Arc::new(sftp_object_store) as Arc<dyn ObjectStore>);
                 }
             }
+            #[cfg(feature = "hdfs")]
+            if url.scheme() == "hdfs" {
+                if let Some(host) = url.host_str() {
+                    let name_node = match url.port() {
+                        Some(port) => format!("hdfs://{host}:{port}"),
+                        None => format!("hdfs://{host}"),
+                    };
+                    let hdfs_object_store = HdfsObjectStore::with_url(&name_node)
+                        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                    return Ok(Arc::new(hdfs_object_store) as Arc<dyn ObjectStore>);
+                }
+            }
         }
 
         Err(DataFusionError::Execution(format!(
@@ -150,6 +138,115 @@ impl SpiceObjectStoreRegistry {
     }
 }
 
+fn s3_object_store(
+    bucket_name: &str,
+    params: &HashMap<String, String>,
+) -> datafusion::error::Result<Arc<dyn ObjectStore>> {
+    let mut s3_builder = AmazonS3Builder::from_env()
+        .with_bucket_name(bucket_name)
+        .with_allow_http(true);
+    let mut client_options = ClientOptions::default();
+
+    if let Some(region) = params.get("region") {
+        s3_builder = s3_builder.with_region(region);
+    }
+    // Custom endpoints, i.e. of MinIO or Ceph, generally only support path-style requests, which are the default.
+    if let Some(endpoint) = params.get("endpoint") {
+        s3_builder = s3_builder.with_endpoint(endpoint);
+    }
+    if let Some(force_path_style) = params.get("force_path_style") {
+        s3_builder = s3_builder
+            .with_virtual_hosted_style_request(!parse_bool("force_path_style", force_path_style)?);
+    }
+    if let Some(s3_express) = params.get("s3_express") {
+        s3_builder = s3_builder.with_s3_express(parse_bool("s3_express", s3_express)?);
+    }
+    if let Some(requester_pays) = params.get("requester_pays") {
+        s3_builder = s3_builder.with_request_payer(parse_bool("requester_pays", requester_pays)?);
+    }
+    if let Some(timeout) = params.get("timeout") {
+        client_options = client_options.with_timeout(parse_duration("timeout", timeout)?);
+    }
+    if let Some(connect_timeout) = params.get("connect_timeout") {
+        client_options = client_options
+            .with_connect_timeout(parse_duration("connect_timeout", connect_timeout)?);
+    }
+    s3_builder = s3_builder.with_retry(retry_config(params)?);
+
+    if let (Some(key), Some(secret)) = (params.get("key"), params.get("secret")) {
+        s3_builder = s3_builder.with_access_key_id(key);
+        s3_builder = s3_builder.with_secret_access_key(secret);
+        if let Some(session_token) = params.get("session_token") {
+            s3_builder = s3_builder.with_token(session_token);
+        }
+    } else {
+        s3_builder = s3_builder.with_skip_signature(true);
+    };
+    s3_builder = s3_builder.with_client_options(client_options);
+
+    let store: Arc<dyn ObjectStore> = Arc::new(s3_builder.build()?);
+    // Limits the requests made to the bucket at once, i.e. to stay within the request rate of a bucket or gateway.
+    match params.get("max_concurrent_requests") {
+        Some(max_requests) => {
+            let max_requests = max_requests.parse::<usize>().ok().filter(|max| *max > 0).ok_or_else(|| {
+                DataFusionError::Configuration(format!(
+                    "Unable to parse max_concurrent_requests: {max_requests}, expected a positive number",
+                ))
+            })?;
+            Ok(Arc::new(LimitStore::new(store, max_requests)))
+        }
+        None => Ok(store),
+    }
+}
+
+/// The store `COPY ... TO` exports to in an S3 bucket. Unlike the stores of datasets, its requests are signed with the
+/// credentials of the environment, as unsigned requests can't write to a bucket.
+pub(crate) fn copy_target_store(
+    bucket_name: &str,
+) -> datafusion::error::Result<Arc<dyn ObjectStore>> {
+    let store = AmazonS3Builder::from_env()
+        .with_bucket_name(bucket_name)
+        .with_allow_http(true)
+        .build()?;
+    Ok(Arc::new(store))
+}
+
+/// The retries of failed requests, from the `max_retries`, `retry_timeout`, `retry_initial_backoff` and
+/// `retry_max_backoff` parameters.
+fn retry_config(params: &HashMap<String, String>) -> datafusion::error::Result<RetryConfig> {
+    let mut retry = RetryConfig::default();
+    if let Some(max_retries) = params.get("max_retries") {
+        retry.max_retries = max_retries.parse().map_err(|_| {
+            DataFusionError::Configuration(format!("Unable to parse max_retries: {max_retries}"))
+        })?;
+    }
+    if let Some(retry_timeout) = params.get("retry_timeout") {
+        retry.retry_timeout = parse_duration("retry_timeout", retry_timeout)?;
+    }
+    if let Some(initial_backoff) = params.get("retry_initial_backoff") {
+        retry.backoff.init_backoff = parse_duration("retry_initial_backoff", initial_backoff)?;
+    }
+    if let Some(max_backoff) = params.get("retry_max_backoff") {
+        retry.backoff.max_backoff = parse_duration("retry_max_backoff", max_backoff)?;
+    }
+    Ok(retry)
+}
+
+fn parse_duration(name: &str, value: &str) -> datafusion::error::Result<Duration> {
+    fundu::parse_duration(value)
+        .map_err(|_| DataFusionError::Configuration(format!("Unable to parse {name}: {value}")))
+}
+
+fn parse_bool(name: &str, value: &str) -> datafusion::error::Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(DataFusionError::Configuration(format!(
+            "Unable to parse {name}: {value}, expected true or false"
+        ))),
+    }
+}
+
 impl ObjectStoreRegistry for SpiceObjectStoreRegistry {
     fn register_store(
         &self,
@@ -180,3 +277,28 @@ pub(crate) fn default_runtime_env() -> Arc<RuntimeEnv> {
         .unwrap_or_default(),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_retry_config() {
+        let params = HashMap::from([
+            ("max_retries".to_string(), "3".to_string()),
+            ("retry_timeout".to_string(), "30s".to_string()),
+            ("retry_max_backoff".to_string(), "5s".to_string()),
+        ]);
+        let retry = retry_config(&params).expect("valid retry config");
+        assert_eq!(retry.max_retries, 3);
+        assert_eq!(retry.retry_timeout, Duration::from_secs(30));
+        assert_eq!(retry.backoff.max_backoff, Duration::from_secs(5));
+        assert_eq!(
+            retry.backoff.init_backoff,
+            RetryConfig::default().backoff.init_backoff
+        );
+
+        let params = HashMap::from([("max_retries".to_string(), "many".to_string())]);
+        assert!(retry_config(&params).is_err());
+    }
+}
@@ -55,6 +55,7 @@ use tonic_health::pb::health_server::HealthServer;
 use crate::datafusion::DataFusion;
 use crate::dataupdate::DataUpdate;
 use crate::dataupdate::UpdateType;
+use crate::tls::TlsConfig;
 use crate::{tracers::OnceTracer, warn_once};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -66,6 +67,11 @@ pub enum Error {
         source: tonic_0_9_0::transport::Error,
     },
 
+    #[snafu(display("Unable to configure TLS: {source}"))]
+    UnableToConfigureTls {
+        source: tonic_0_9_0::transport::Error,
+    },
+
     #[snafu(display("Failed to build record batch from OpenTelemetry metrics: {source}"))]
     FailedToBuildRecordBatch { source: arrow::error::ArrowError },
 
@@ -583,7 +589,11 @@ fn append_null(
     }
 }
 
-pub async fn start(bind_address: SocketAddr, data_fusion: Arc<DataFusion>) -> Result<()> {
+pub async fn start(
+    bind_address: SocketAddr,
+    data_fusion: Arc<DataFusion>,
+    tls: Option<TlsConfig>,
+) -> Result<()> {
     let service = Service {
         data_fusion,
         once_tracer: OnceTracer::new(),
@@ -592,7 +602,14 @@ pub async fn start(bind_address: SocketAddr, data_fusion: Arc<DataFusion>) -> Re
 
     tracing::info!("Spice Runtime OpenTelemetry listening on {bind_address}");
 
-    Server::builder()
+    let mut server = Server::builder();
+    if let Some(tls) = tls {
+        server = server
+            .tls_config(tls.tonic_0_9_config())
+            .context(UnableToConfigureTlsSnafu)?;
+    }
+
+    server
         .add_service(create_health_service().await)
         .add_service(svc)
         .serve(bind_address)
@@ -0,0 +1,345 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Pushes the runtime metrics to an OpenTelemetry collector with `runtime.otlp_metrics`.
+//!
+//! The metrics are read from the Prometheus recorder, like the `runtime.metrics` table: counters are exported as
+//! cumulative monotonic sums, gauges as gauges, and histograms with the buckets of the recorder.
+
+use std::time::{Duration, SystemTime};
+
+use metrics_exporter_prometheus::PrometheusHandle;
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::{
+        metrics_service_client::MetricsServiceClient, ExportMetricsServiceRequest,
+    },
+    common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue},
+    metrics::v1::{
+        metric::Data, number_data_point, AggregationTemporality, Gauge, Histogram,
+        HistogramDataPoint, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics, Sum,
+    },
+    resource::v1::Resource,
+};
+use snafu::prelude::*;
+use spicepod::component::runtime::OtlpMetrics;
+use tonic_0_9_0::{
+    metadata::{AsciiMetadataKey, AsciiMetadataValue, MetadataMap},
+    transport::{Channel, Endpoint},
+};
+
+use crate::shutdown;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("runtime.otlp_metrics requires an endpoint"))]
+    MissingEndpoint,
+
+    #[snafu(display("Invalid OTLP metrics endpoint {endpoint}: {source}"))]
+    InvalidEndpoint {
+        endpoint: String,
+        source: tonic_0_9_0::transport::Error,
+    },
+
+    #[snafu(display("Invalid OTLP metrics header {name}"))]
+    InvalidHeader { name: String },
+
+    #[snafu(display("Invalid OTLP metrics interval {interval}: {source}"))]
+    InvalidInterval {
+        interval: String,
+        source: fundu::ParseError,
+    },
+
+    #[snafu(display("Error parsing prometheus metrics: {source}"))]
+    UnableToParsePrometheusMetrics { source: std::io::Error },
+
+    #[snafu(display("Unable to export metrics: {source}"))]
+    UnableToExportMetrics { source: tonic_0_9_0::Status },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub struct OtlpMetricsExporter {
+    client: MetricsServiceClient<Channel>,
+    headers: MetadataMap,
+    interval: Duration,
+    instance_name: String,
+    handle: PrometheusHandle,
+    /// The start of the cumulative counters and histograms, when the exporter was created.
+    start_time_unix_nano: u64,
+}
+
+impl OtlpMetricsExporter {
+    pub fn try_new(
+        config: &OtlpMetrics,
+        instance_name: String,
+        handle: PrometheusHandle,
+    ) -> Result<Self> {
+        let endpoint = config.endpoint.clone().context(MissingEndpointSnafu)?;
+        let channel = Endpoint::from_shared(endpoint.clone())
+            .context(InvalidEndpointSnafu { endpoint })?
+            .connect_lazy();
+
+        let mut headers = MetadataMap::new();
+        for (name, value) in &config.headers {
+            let key = AsciiMetadataKey::from_bytes(name.to_ascii_lowercase().as_bytes())
+                .ok()
+                .context(InvalidHeaderSnafu { name: name.clone() })?;
+            let value = AsciiMetadataValue::try_from(value.as_str())
+                .ok()
+                .context(InvalidHeaderSnafu { name: name.clone() })?;
+            headers.insert(key, value);
+        }
+
+        let interval = match &config.interval {
+            Some(interval) => fundu::parse_duration(interval).context(InvalidIntervalSnafu {
+                interval: interval.clone(),
+            })?,
+            None => DEFAULT_INTERVAL,
+        };
+
+        Ok(Self {
+            client: MetricsServiceClient::new(channel),
+            headers,
+            interval,
+            instance_name,
+            handle,
+            start_time_unix_nano: unix_nanos(SystemTime::now()),
+        })
+    }
+
+    /// Pushes the metrics every interval, until the runtime has drained on shutdown and the last metrics are pushed.
+    pub async fn run(mut self) {
+        let mut interval_timer = tokio::time::interval(self.interval);
+        loop {
+            let drained = tokio::select! {
+                _ = interval_timer.tick() => false,
+                () = shutdown::drained() => true,
+            };
+            if let Err(e) = self.export().await {
+                tracing::error!("{e}");
+            }
+            if drained {
+                return;
+            }
+        }
+    }
+
+    async fn export(&mut self) -> Result<()> {
+        let body = self.handle.render();
+        let lines = body.lines().map(|s| Ok(s.to_owned()));
+        let scrape =
+            prometheus_parse::Scrape::parse(lines).context(UnableToParsePrometheusMetricsSnafu)?;
+
+        let mut request = tonic_0_9_0::Request::new(to_export_request(
+            scrape,
+            &self.instance_name,
+            self.start_time_unix_nano,
+        ));
+        *request.metadata_mut() = self.headers.clone();
+
+        self.client
+            .export(request)
+            .await
+            .context(UnableToExportMetricsSnafu)?;
+
+        Ok(())
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| u64::try_from(duration.as_nanos()).unwrap_or_default())
+        .unwrap_or_default()
+}
+
+fn string_attribute(key: &str, value: impl Into<String>) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.into())),
+        }),
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn to_metric(sample: prometheus_parse::Sample, start_time_unix_nano: u64) -> Metric {
+    let time_unix_nano = sample
+        .timestamp
+        .timestamp_nanos_opt()
+        .and_then(|nanos| u64::try_from(nanos).ok())
+        .unwrap_or_default();
+    let mut attributes = sample
+        .labels
+        .iter()
+        .map(|(key, value)| string_attribute(key, value.clone()))
+        .collect::<Vec<_>>();
+    attributes.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let number_data_point = |value: f64| NumberDataPoint {
+        attributes: attributes.clone(),
+        start_time_unix_nano,
+        time_unix_nano,
+        value: Some(number_data_point::Value::AsDouble(value)),
+        ..NumberDataPoint::default()
+    };
+
+    let data = match sample.value {
+        prometheus_parse::Value::Counter(value) => Data::Sum(Sum {
+            data_points: vec![number_data_point(value)],
+            aggregation_temporality: AggregationTemporality::Cumulative as i32,
+            is_monotonic: true,
+        }),
+        prometheus_parse::Value::Gauge(value) | prometheus_parse::Value::Untyped(value) => {
+            Data::Gauge(Gauge {
+                data_points: vec![number_data_point(value)],
+            })
+        }
+        prometheus_parse::Value::Histogram(mut buckets) => {
+            buckets.sort_by(|a, b| a.less_than.total_cmp(&b.less_than));
+            // The buckets of Prometheus are cumulative, the buckets of OpenTelemetry aren't.
+            let mut bucket_counts = vec![];
+            let mut previous = 0.0;
+            for bucket in &buckets {
+                bucket_counts.push((bucket.count - previous).max(0.0) as u64);
+                previous = bucket.count;
+            }
+            let mut explicit_bounds = buckets
+                .iter()
+                .map(|bucket| bucket.less_than)
+                .filter(|bound| bound.is_finite())
+                .collect::<Vec<_>>();
+            if explicit_bounds.len() == bucket_counts.len() {
+                // Without a +Inf bucket, every value is within the last bound.
+                explicit_bounds.pop();
+            }
+
+            Data::Histogram(Histogram {
+                data_points: vec![HistogramDataPoint {
+                    attributes: attributes.clone(),
+                    start_time_unix_nano,
+                    time_unix_nano,
+                    count: previous as u64,
+                    bucket_counts,
+                    explicit_bounds,
+                    ..HistogramDataPoint::default()
+                }],
+                aggregation_temporality: AggregationTemporality::Cumulative as i32,
+            })
+        }
+        // The quantiles of summaries can't be aggregated, so only their counts are exported.
+        prometheus_parse::Value::Summary(quantiles) => Data::Gauge(Gauge {
+            data_points: vec![number_data_point(
+                quantiles.into_iter().map(|quantile| quantile.count).sum(),
+            )],
+        }),
+    };
+
+    Metric {
+        name: sample.metric,
+        data: Some(data),
+        ..Metric::default()
+    }
+}
+
+fn to_export_request(
+    scrape: prometheus_parse::Scrape,
+    instance_name: &str,
+    start_time_unix_nano: u64,
+) -> ExportMetricsServiceRequest {
+    let metrics = scrape
+        .samples
+        .into_iter()
+        .map(|sample| to_metric(sample, start_time_unix_nano))
+        .collect();
+
+    ExportMetricsServiceRequest {
+        resource_metrics: vec![ResourceMetrics {
+            resource: Some(Resource {
+                attributes: vec![
+                    string_attribute("service.name", "spiced"),
+                    string_attribute("service.instance.id", instance_name),
+                ],
+                dropped_attributes_count: 0,
+            }),
+            scope_metrics: vec![ScopeMetrics {
+                scope: Some(InstrumentationScope {
+                    name: "spice.runtime".to_string(),
+                    ..InstrumentationScope::default()
+                }),
+                metrics,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_prometheus_metrics() {
+        let body = r#"# TYPE query_executions counter
+query_executions{protocol="http"} 3
+# TYPE datasets_count gauge
+datasets_count{engine="arrow"} 2
+# TYPE query_duration_seconds histogram
+query_duration_seconds_bucket{le="0.1"} 1
+query_duration_seconds_bucket{le="1"} 3
+query_duration_seconds_bucket{le="+Inf"} 4
+"#;
+        let scrape = prometheus_parse::Scrape::parse(body.lines().map(|s| Ok(s.to_owned())))
+            .expect("valid metrics");
+
+        let request = to_export_request(scrape, "spiced-1", 1);
+        let metrics = &request.resource_metrics[0].scope_metrics[0].metrics;
+        let metric = |name: &str| {
+            metrics
+                .iter()
+                .find(|metric| metric.name == name)
+                .and_then(|metric| metric.data.clone())
+                .expect("metric is exported")
+        };
+
+        let Data::Sum(sum) = metric("query_executions") else {
+            panic!("counters are exported as sums");
+        };
+        assert!(sum.is_monotonic);
+        assert_eq!(
+            sum.data_points[0].attributes,
+            vec![string_attribute("protocol", "http")]
+        );
+
+        assert!(matches!(metric("datasets_count"), Data::Gauge(_)));
+
+        let Some(Data::Histogram(histogram)) =
+            metrics.iter().find_map(|metric| match &metric.data {
+                Some(Data::Histogram(histogram)) => Some(Data::Histogram(histogram.clone())),
+                _ => None,
+            })
+        else {
+            panic!("histograms are exported as histograms");
+        };
+        let data_point = &histogram.data_points[0];
+        assert_eq!(data_point.count, 4);
+        assert_eq!(data_point.explicit_bounds, vec![0.1, 1.0]);
+        assert_eq!(data_point.bucket_counts, vec![1, 2, 1]);
+    }
+}
@@ -0,0 +1,389 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Flags the columns of datasets with `detect_pii` that look like they hold personal data in `/v1/status`.
+//!
+//! A sample of the dataset is read when it is registered, and after the refreshes of an accelerated dataset until
+//! the sample has rows. A column is flagged when most of its sampled values look like email addresses, phone
+//! numbers, US social security numbers, credit card numbers or IP addresses, or when its name suggests it holds
+//! these, dates of birth, addresses or names. Detection is a heuristic to review policies against, not a guarantee.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::IpAddr,
+};
+
+use arrow::{array::AsArray, compute::cast, datatypes::DataType};
+use datafusion::sql::TableReference;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::sync::broadcast;
+
+use crate::{
+    datafusion::DataFusion,
+    events::{self, RuntimeEvent},
+    status, Runtime,
+};
+
+const SAMPLE_ROWS: usize = 1000;
+
+/// The share of the non-empty sampled values of a column that must look like personal data to flag it.
+const MATCH_RATIO: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PiiKind {
+    Email,
+    Phone,
+    Ssn,
+    CreditCard,
+    IpAddress,
+    DateOfBirth,
+    Address,
+    Name,
+}
+
+impl PiiKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            PiiKind::Email => "email",
+            PiiKind::Phone => "phone",
+            PiiKind::Ssn => "ssn",
+            PiiKind::CreditCard => "credit_card",
+            PiiKind::IpAddress => "ip_address",
+            PiiKind::DateOfBirth => "date_of_birth",
+            PiiKind::Address => "address",
+            PiiKind::Name => "name",
+        }
+    }
+}
+
+static VALUE_PATTERNS: Lazy<Vec<(PiiKind, Regex)>> = Lazy::new(|| {
+    [
+        (
+            PiiKind::Email,
+            r"^[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}$",
+        ),
+        (PiiKind::Ssn, r"^\d{3}-\d{2}-\d{4}$"),
+        (PiiKind::CreditCard, r"^(?:\d[ \-]?){12,18}\d$"),
+        (
+            PiiKind::Phone,
+            r"^(?:\+\d{1,3}[\s.\-]?)?\(?\d{3}\)?[\s.\-]?\d{3}[\s.\-]?\d{4}$",
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, pattern)| {
+        let regex =
+            Regex::new(pattern).unwrap_or_else(|_| panic!("Invalid {} pattern", kind.as_str()));
+        (kind, regex)
+    })
+    .collect()
+});
+
+/// The words of column names that suggest the kind of personal data they hold, checked in order. A word only matches
+/// whole words of a name, so `dob` doesn't match `adobe_id`.
+const NAME_WORDS: [(PiiKind, &[&str]); 8] = [
+    (PiiKind::Email, &["email", "e_mail"]),
+    (PiiKind::IpAddress, &["ip", "ipaddress", "remote_addr"]),
+    (PiiKind::Ssn, &["ssn", "social_security"]),
+    (
+        PiiKind::CreditCard,
+        &["credit_card", "card_number", "cc_number", "creditcard"],
+    ),
+    (PiiKind::Phone, &["phone", "mobile", "msisdn"]),
+    (
+        PiiKind::DateOfBirth,
+        &[
+            "dob",
+            "birth_date",
+            "date_of_birth",
+            "birthdate",
+            "birthday",
+        ],
+    ),
+    (
+        PiiKind::Address,
+        &["address", "street", "postal_code", "zip_code"],
+    ),
+    (
+        PiiKind::Name,
+        &[
+            "first_name",
+            "last_name",
+            "full_name",
+            "surname",
+            "firstname",
+            "lastname",
+        ],
+    ),
+];
+
+/// Detects personal data in the datasets with `detect_pii` as they are registered and refreshed, until the runtime
+/// stops.
+pub(crate) async fn watch(rt: &Runtime) {
+    let mut events = events::subscribe();
+    // The datasets sampled with rows, which aren't sampled again until they are registered again.
+    let mut detected: HashSet<TableReference> = HashSet::new();
+
+    loop {
+        let dataset = match events.recv().await {
+            Ok(RuntimeEvent::DatasetRegistered { dataset }) => {
+                detected.remove(&dataset);
+                dataset
+            }
+            Ok(RuntimeEvent::RefreshComplete {
+                dataset,
+                error: None,
+            }) if !detected.contains(&dataset) => dataset,
+            Ok(RuntimeEvent::DatasetRemoved { dataset }) => {
+                detected.remove(&dataset);
+                continue;
+            }
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!("PII detection fell behind, {skipped} runtime events were skipped");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        let masked: Option<HashSet<String>> = rt.app.read().await.as_ref().and_then(|app| {
+            let ds = app
+                .datasets
+                .iter()
+                .find(|ds| ds.detect_pii && TableReference::from(ds.name.as_str()) == dataset)?;
+            Some(
+                ds.policies
+                    .iter()
+                    .flat_map(|policy| policy.masks.keys().cloned())
+                    .collect(),
+            )
+        });
+        let Some(masked) = masked else {
+            continue;
+        };
+
+        match detect_dataset(&rt.df, &dataset).await {
+            // The acceleration hasn't loaded yet.
+            Ok(None) => {}
+            Ok(Some(pii_columns)) => {
+                detected.insert(dataset.clone());
+                let unmasked = pii_columns
+                    .iter()
+                    .filter(|(column, _)| !masked.contains(*column))
+                    .map(|(column, kind)| format!("{column} ({kind})"))
+                    .collect::<Vec<_>>();
+                if !unmasked.is_empty() {
+                    tracing::warn!(
+                        "Dataset {dataset} has columns that look like personal data without masks: {}",
+                        unmasked.join(", ")
+                    );
+                }
+                status::update_dataset_pii_columns(&dataset, pii_columns);
+            }
+            Err(e) => tracing::warn!("Unable to detect personal data in {dataset}: {e}"),
+        }
+    }
+}
+
+/// The columns of a sample of the dataset that look like personal data, or `None` if the sample has no rows.
+async fn detect_dataset(
+    df: &DataFusion,
+    dataset: &TableReference,
+) -> Result<Option<BTreeMap<String, String>>, String> {
+    let sql = format!(
+        "SELECT * FROM {} LIMIT {SAMPLE_ROWS}",
+        dataset.to_quoted_string()
+    );
+    let batches = df
+        .ctx
+        .sql(&sql)
+        .await
+        .map_err(|e| e.to_string())?
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    if batches.iter().all(|batch| batch.num_rows() == 0) {
+        return Ok(None);
+    }
+
+    let schema = batches[0].schema();
+    let mut pii_columns = BTreeMap::new();
+    for (index, field) in schema.fields().iter().enumerate() {
+        let sampled = matches!(
+            field.data_type(),
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Int64 | DataType::UInt64
+        );
+        let mut values = vec![];
+        if sampled {
+            for batch in &batches {
+                let strings =
+                    cast(batch.column(index), &DataType::Utf8).map_err(|e| e.to_string())?;
+                values.extend(
+                    strings
+                        .as_string::<i32>()
+                        .iter()
+                        .flatten()
+                        .map(ToString::to_string),
+                );
+            }
+        }
+
+        if let Some(kind) = detect(field.name(), values.iter().map(String::as_str)) {
+            pii_columns.insert(field.name().clone(), kind.as_str().to_string());
+        }
+    }
+    Ok(Some(pii_columns))
+}
+
+/// The kind of personal data a column looks like it holds, from its sampled values or else its name.
+#[allow(clippy::cast_precision_loss)]
+fn detect<'a>(name: &str, values: impl Iterator<Item = &'a str>) -> Option<PiiKind> {
+    let values = values
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>();
+    if !values.is_empty() {
+        let kind = [
+            PiiKind::Email,
+            PiiKind::Ssn,
+            PiiKind::CreditCard,
+            PiiKind::Phone,
+            PiiKind::IpAddress,
+        ]
+        .into_iter()
+        .find(|kind| {
+            let matching = values
+                .iter()
+                .filter(|value| looks_like(*kind, value))
+                .count();
+            matching as f64 / values.len() as f64 >= MATCH_RATIO
+        });
+        if kind.is_some() {
+            return kind;
+        }
+    }
+
+    let name = format!("_{}_", snake_case(name));
+    NAME_WORDS
+        .iter()
+        .find(|(_, words)| words.iter().any(|word| name.contains(&format!("_{word}_"))))
+        .map(|(kind, _)| *kind)
+}
+
+/// `customerEmail` and `Customer-Email` as `customer_email`, to match the words of column names.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    let mut previous_lowercase = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lowercase {
+            snake.push('_');
+        }
+        previous_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_lowercase()
+        } else {
+            '_'
+        });
+    }
+    snake
+}
+
+fn looks_like(kind: PiiKind, value: &str) -> bool {
+    match kind {
+        PiiKind::IpAddress => value.parse::<IpAddr>().is_ok(),
+        PiiKind::CreditCard => {
+            pattern_matches(kind, value) && luhn_valid(value.chars().filter(char::is_ascii_digit))
+        }
+        kind => pattern_matches(kind, value),
+    }
+}
+
+fn pattern_matches(kind: PiiKind, value: &str) -> bool {
+    VALUE_PATTERNS
+        .iter()
+        .any(|(pattern_kind, regex)| *pattern_kind == kind && regex.is_match(value))
+}
+
+/// Whether the digits pass the Luhn checksum of card numbers.
+fn luhn_valid(digits: impl DoubleEndedIterator<Item = char>) -> bool {
+    let sum: u32 = digits
+        .rev()
+        .filter_map(|digit| digit.to_digit(10))
+        .enumerate()
+        .map(|(index, digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_personal_data() {
+        assert_eq!(
+            detect(
+                "contact",
+                ["a@example.com", "b@example.org", ""].into_iter()
+            ),
+            Some(PiiKind::Email)
+        );
+        assert_eq!(
+            detect("tax_id", ["123-45-6789", "987-65-4321"].into_iter()),
+            Some(PiiKind::Ssn)
+        );
+        assert_eq!(
+            detect("payment", ["4111 1111 1111 1111"].into_iter()),
+            Some(PiiKind::CreditCard)
+        );
+        // Numbers failing the Luhn checksum aren't card numbers.
+        assert_eq!(detect("order_id", ["4111111111111112"].into_iter()), None);
+        assert_eq!(
+            detect("contact", ["+1 (555) 010-0100", "555.010.0199"].into_iter()),
+            Some(PiiKind::Phone)
+        );
+        assert_eq!(
+            detect("client", ["10.0.0.1", "::1"].into_iter()),
+            Some(PiiKind::IpAddress)
+        );
+        assert_eq!(detect("created_at", ["2024-01-01"].into_iter()), None);
+
+        assert_eq!(
+            detect("customerEmail", std::iter::empty()),
+            Some(PiiKind::Email)
+        );
+        assert_eq!(
+            detect("user_dob", std::iter::empty()),
+            Some(PiiKind::DateOfBirth)
+        );
+        assert_eq!(
+            detect("ship_address", std::iter::empty()),
+            Some(PiiKind::Address)
+        );
+        assert_eq!(detect("adobe_id", std::iter::empty()), None);
+    }
+}
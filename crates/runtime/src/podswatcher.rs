@@ -19,7 +19,7 @@ use notify::{
     EventKind, RecursiveMode, Watcher,
 };
 use spicepod::component::ComponentOrReference;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::sync::mpsc::{channel, Receiver};
 
 use app::{App, AppBuilder};
@@ -38,6 +38,11 @@ impl PodsWatcher {
         }
     }
 
+    #[must_use]
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+
     pub fn watch(&mut self) -> notify::Result<Receiver<App>> {
         let root_path = self.root_path.clone();
 
@@ -98,6 +103,10 @@ fn get_watch_paths(app_path: impl Into<PathBuf>) -> Vec<PathBuf> {
         root_dir.join("spicepod.yaml"),
         root_dir.join("spicepod.yml"),
     ];
+    if let Some(profile) = spicepod::environment::active_profile() {
+        dirs.push(root_dir.join(format!("spicepod.{profile}.yaml")));
+        dirs.push(root_dir.join(format!("spicepod.{profile}.yml")));
+    }
 
     if let Ok(spicepod) = spicepod::Spicepod::load_definition(&root_dir) {
         for dep in spicepod.dependencies {
@@ -0,0 +1,217 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Restricts a dataset to the `columns` of its source it declares, so only those columns are fetched from the source
+//! and stored in its accelerator.
+
+use std::{any::Any, sync::Arc};
+
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use datafusion::{
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionState,
+    logical_expr::{Expr, TableProviderFilterPushDown},
+    physical_plan::ExecutionPlan,
+};
+
+use crate::{
+    component::dataset::Dataset,
+    dataconnector::{AnyErrorResult, DataConnector, DataConnectorError, DataConnectorResult},
+};
+
+/// Wraps the providers of a [`DataConnector`] in a [`ProjectedTable`] of the columns of the dataset.
+pub struct ProjectedConnector {
+    inner_connector: Arc<dyn DataConnector>,
+}
+
+impl ProjectedConnector {
+    pub fn new(inner_connector: Arc<dyn DataConnector>) -> Self {
+        Self { inner_connector }
+    }
+
+    fn wrap(
+        inner_table_provider: Arc<dyn TableProvider>,
+        dataset: &Dataset,
+    ) -> DataConnectorResult<Arc<dyn TableProvider>> {
+        if dataset.columns.is_empty() {
+            return Ok(inner_table_provider);
+        }
+
+        ProjectedTable::try_new(inner_table_provider, &dataset.columns)
+            .map(|table| Arc::new(table) as Arc<dyn TableProvider>)
+            .map_err(|e| DataConnectorError::InvalidConfiguration {
+                dataconnector: dataset.source(),
+                message: format!("Unable to select the columns of {}.", dataset.name),
+                source: Box::new(e),
+            })
+    }
+}
+
+#[async_trait]
+impl DataConnector for ProjectedConnector {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> DataConnectorResult<Arc<dyn TableProvider>> {
+        Self::wrap(self.inner_connector.read_provider(dataset).await?, dataset)
+    }
+
+    // Writes need every column of the source, so the columns only apply to reads.
+    async fn read_write_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<DataConnectorResult<Arc<dyn TableProvider>>> {
+        self.inner_connector.read_write_provider(dataset).await
+    }
+
+    async fn stream_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<AnyErrorResult<Arc<dyn TableProvider>>> {
+        match self.inner_connector.stream_provider(dataset).await {
+            Some(Ok(inner)) => Some(Self::wrap(inner, dataset).map_err(Into::into)),
+            other => other,
+        }
+    }
+
+    async fn metadata_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<DataConnectorResult<Arc<dyn TableProvider>>> {
+        self.inner_connector.metadata_provider(dataset).await
+    }
+}
+
+/// A table of some of the columns of another table, which only scans those columns.
+pub struct ProjectedTable {
+    inner: Arc<dyn TableProvider>,
+    /// The indices of the columns in the schema of `inner`.
+    indices: Vec<usize>,
+    schema: SchemaRef,
+}
+
+impl ProjectedTable {
+    pub fn try_new(inner: Arc<dyn TableProvider>, columns: &[String]) -> DataFusionResult<Self> {
+        let inner_schema = inner.schema();
+        let indices = columns
+            .iter()
+            .map(|column| {
+                inner_schema.index_of(column).map_err(|_| {
+                    DataFusionError::Plan(format!(
+                        "The column {column} was not found in the source, which has the columns {}",
+                        inner_schema
+                            .fields()
+                            .iter()
+                            .map(|f| f.name().as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                })
+            })
+            .collect::<DataFusionResult<Vec<_>>>()?;
+        let schema = Arc::new(inner_schema.project(&indices)?);
+
+        Ok(Self {
+            inner,
+            indices,
+            schema,
+        })
+    }
+
+    /// Maps a projection of this table to the projection of the inner table.
+    fn inner_projection(&self, projection: Option<&Vec<usize>>) -> Vec<usize> {
+        match projection {
+            Some(projection) => projection.iter().map(|i| self.indices[*i]).collect(),
+            None => self.indices.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for ProjectedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    // The filters only reference the selected columns, which the inner table has.
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        self.inner.supports_filters_pushdown(filters)
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let projection = self.inner_projection(projection);
+        self.inner
+            .scan(state, Some(&projection), filters, limit)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::empty::EmptyTable;
+
+    use super::*;
+
+    #[test]
+    fn projects_the_selected_columns() {
+        let inner: Arc<dyn TableProvider> = Arc::new(EmptyTable::new(Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, false),
+            Field::new("b", DataType::Utf8, true),
+            Field::new("c", DataType::Float64, true),
+        ]))));
+
+        let table =
+            ProjectedTable::try_new(Arc::clone(&inner), &["c".to_string(), "a".to_string()])
+                .expect("columns exist");
+        assert_eq!(
+            table
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["c", "a"]
+        );
+        assert_eq!(table.inner_projection(None), vec![2, 0]);
+        assert_eq!(table.inner_projection(Some(&vec![1])), vec![0]);
+
+        assert!(ProjectedTable::try_new(inner, &["d".to_string()]).is_err());
+    }
+}
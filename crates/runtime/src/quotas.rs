@@ -0,0 +1,468 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Enforces the `quotas` of `runtime.namespaces`, and reports the usage of each namespace in `/v1/status`.
+//!
+//! The accelerated storage of a namespace is the sum of the acceleration sizes of its datasets, as recorded after
+//! each refresh: the file size of file accelerators, the in-memory size of the others. A refresh that would take it
+//! over the quota is skipped before it is written, estimating the storage it adds from its in-memory size and the
+//! ratio of stored to in-memory size of the previous refreshes of the dataset. Queries that read the datasets of a
+//! namespace fail once their scans have read more rows than its quota. The LLM tokens of a namespace are those of the
+//! calls of its principals, counted per UTC day once a model responds, so calls are allowed while the day's tokens are
+//! under the quota.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{PoisonError, RwLock},
+};
+
+use byte_unit::Byte;
+use chrono::{DateTime, NaiveDate, Utc};
+use datafusion::sql::TableReference;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use snafu::prelude::*;
+use spicepod::component::runtime::Namespace;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid max_accelerated_size {value} of namespace {namespace}: {source}"))]
+    InvalidMaxAcceleratedSize {
+        namespace: String,
+        value: String,
+        source: byte_unit::ParseError,
+    },
+
+    #[snafu(display(
+        "Refreshing {dataset} would take the accelerated storage of namespace {namespace} to {size} bytes, over its quota of {limit} bytes"
+    ))]
+    AcceleratedSizeExceeded {
+        namespace: String,
+        dataset: String,
+        size: u64,
+        limit: u64,
+    },
+
+    #[snafu(display(
+        "Namespace {namespace} used its quota of {limit} LLM tokens for today, retry after midnight UTC"
+    ))]
+    LlmTokensExceeded { namespace: String, limit: u64 },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Limits {
+    max_accelerated_size: Option<u64>,
+    max_rows_scanned: Option<u64>,
+    max_llm_tokens_per_day: Option<u64>,
+}
+
+/// The size of the acceleration of a dataset.
+#[derive(Debug, Clone, Copy, Default)]
+struct AcceleratedSize {
+    /// The storage used, the usage counted towards the quota.
+    stored: u64,
+    /// The in-memory size of the data written to the acceleration since it was last replaced.
+    written: u64,
+}
+
+impl AcceleratedSize {
+    /// The storage that writing data of `written` in-memory bytes uses, at the ratio of the previous writes.
+    fn estimate(self, written: u64) -> u64 {
+        if self.written == 0 {
+            return written;
+        }
+        let estimate = u128::from(written) * u128::from(self.stored) / u128::from(self.written);
+        u64::try_from(estimate).unwrap_or(u64::MAX)
+    }
+}
+
+/// The LLM tokens used in a UTC day.
+#[derive(Debug, Clone, Copy, Default)]
+struct DailyTokens {
+    day: Option<NaiveDate>,
+    tokens: u64,
+}
+
+impl DailyTokens {
+    fn used(self, day: NaiveDate) -> u64 {
+        if self.day == Some(day) {
+            self.tokens
+        } else {
+            0
+        }
+    }
+
+    fn add(&mut self, day: NaiveDate, tokens: u64) {
+        self.tokens = self.used(day).saturating_add(tokens);
+        self.day = Some(day);
+    }
+}
+
+#[derive(Debug, Default)]
+struct NamespaceQuota {
+    limits: Limits,
+    /// The subjects of the principals of the namespace.
+    principals: Vec<String>,
+    /// The acceleration size of each dataset of the namespace.
+    accelerated_sizes: HashMap<String, AcceleratedSize>,
+    llm_tokens: DailyTokens,
+}
+
+impl NamespaceQuota {
+    fn accelerated_size(&self) -> u64 {
+        self.accelerated_sizes
+            .values()
+            .map(|size| size.stored)
+            .sum()
+    }
+
+    fn has_principal(&self, subject: &str) -> bool {
+        self.principals.iter().any(|principal| principal == subject)
+    }
+}
+
+/// The usage and quotas of a namespace, as reported by `GET /v1/status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NamespaceUsage {
+    pub accelerated_size_bytes: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_accelerated_size_bytes: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_rows_scanned: Option<u64>,
+
+    /// The LLM tokens used by the principals of the namespace in the current UTC day.
+    pub llm_tokens_today: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_llm_tokens_per_day: Option<u64>,
+}
+
+static NAMESPACES: Lazy<RwLock<HashMap<String, NamespaceQuota>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Applies the quotas of `namespaces`, keeping the usage recorded for namespaces that were already configured.
+pub fn configure(namespaces: &[Namespace]) -> Result<()> {
+    let mut limits = HashMap::new();
+    for namespace in namespaces {
+        let max_accelerated_size = namespace
+            .quotas
+            .max_accelerated_size
+            .as_ref()
+            .map(|value| {
+                Byte::parse_str(value, true)
+                    .map(|size| size.as_u64())
+                    .context(InvalidMaxAcceleratedSizeSnafu {
+                        namespace: namespace.name.clone(),
+                        value: value.clone(),
+                    })
+            })
+            .transpose()?;
+        limits.insert(
+            namespace.name.clone(),
+            (
+                Limits {
+                    max_accelerated_size,
+                    max_rows_scanned: namespace.quotas.max_rows_scanned,
+                    max_llm_tokens_per_day: namespace.quotas.max_llm_tokens_per_day,
+                },
+                namespace.principals.clone(),
+            ),
+        );
+    }
+
+    let mut quotas = NAMESPACES.write().unwrap_or_else(PoisonError::into_inner);
+    quotas.retain(|namespace, _| limits.contains_key(namespace));
+    for (namespace, (limits, principals)) in limits {
+        let quota = quotas.entry(namespace).or_default();
+        quota.limits = limits;
+        quota.principals = principals;
+    }
+    Ok(())
+}
+
+/// The namespace of a dataset is the catalog it is registered in.
+fn namespace_of(dataset: &TableReference) -> Option<&str> {
+    dataset.catalog()
+}
+
+/// Checks that writing data of `written` in-memory bytes to the acceleration of `dataset`, replacing its data if
+/// `overwrite`, keeps its namespace within its accelerated storage quota.
+pub fn check_accelerated_size(
+    dataset: &TableReference,
+    written: u64,
+    overwrite: bool,
+) -> Result<()> {
+    let Some(namespace) = namespace_of(dataset) else {
+        return Ok(());
+    };
+    let quotas = NAMESPACES.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(quota) = quotas.get(namespace) else {
+        return Ok(());
+    };
+    let Some(limit) = quota.limits.max_accelerated_size else {
+        return Ok(());
+    };
+
+    let current = quota
+        .accelerated_sizes
+        .get(&dataset.to_string())
+        .copied()
+        .unwrap_or_default();
+    let others = quota.accelerated_size() - current.stored;
+    let size = if overwrite {
+        others + current.estimate(written)
+    } else {
+        others + current.stored + current.estimate(written)
+    };
+    if size > limit {
+        metrics::counter!("namespace_quota_rejections", "namespace" => namespace.to_string(), "quota" => "max_accelerated_size")
+            .increment(1);
+        return AcceleratedSizeExceededSnafu {
+            namespace,
+            dataset: dataset.to_string(),
+            size,
+            limit,
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+/// Records the acceleration size of `dataset` after a refresh that wrote data of `written` in-memory bytes, replacing
+/// its data if `overwrite`. `stored` is the size of the acceleration file, if the accelerator has one; otherwise the
+/// written size is stored.
+pub fn record_accelerated_size(
+    dataset: &TableReference,
+    written: u64,
+    stored: Option<u64>,
+    overwrite: bool,
+) {
+    let Some(namespace) = namespace_of(dataset) else {
+        return;
+    };
+    let mut quotas = NAMESPACES.write().unwrap_or_else(PoisonError::into_inner);
+    let Some(quota) = quotas.get_mut(namespace) else {
+        return;
+    };
+
+    let accelerated_size = quota
+        .accelerated_sizes
+        .entry(dataset.to_string())
+        .or_default();
+    if overwrite {
+        *accelerated_size = AcceleratedSize::default();
+    }
+    accelerated_size.written += written;
+    accelerated_size.stored = stored.unwrap_or(accelerated_size.stored + written);
+    record_usage(namespace, quota);
+}
+
+/// Stops counting the acceleration of a removed dataset towards the storage of its namespace.
+pub fn remove(dataset: &TableReference) {
+    let Some(namespace) = namespace_of(dataset) else {
+        return;
+    };
+    let mut quotas = NAMESPACES.write().unwrap_or_else(PoisonError::into_inner);
+    if let Some(quota) = quotas.get_mut(namespace) {
+        quota.accelerated_sizes.remove(&dataset.to_string());
+        record_usage(namespace, quota);
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn record_usage(namespace: &str, quota: &NamespaceQuota) {
+    metrics::gauge!("namespace_accelerated_size_bytes", "namespace" => namespace.to_string())
+        .set(quota.accelerated_size() as f64);
+}
+
+/// The smallest quota of rows scanned of the namespaces of `datasets`, with the namespace it belongs to.
+#[must_use]
+pub fn max_rows_scanned<'a>(
+    datasets: impl IntoIterator<Item = &'a TableReference>,
+) -> Option<(String, u64)> {
+    let quotas = NAMESPACES.read().unwrap_or_else(PoisonError::into_inner);
+    datasets
+        .into_iter()
+        .filter_map(|dataset| {
+            let namespace = namespace_of(dataset)?;
+            let limit = quotas.get(namespace)?.limits.max_rows_scanned?;
+            Some((namespace.to_string(), limit))
+        })
+        .min_by_key(|(_, limit)| *limit)
+}
+
+/// Checks that the namespaces of the principal `subject` have LLM tokens left for the day of `now`.
+pub fn check_llm_tokens(subject: Option<&str>, now: DateTime<Utc>) -> Result<()> {
+    let Some(subject) = subject else {
+        return Ok(());
+    };
+    let today = now.date_naive();
+    let quotas = NAMESPACES.read().unwrap_or_else(PoisonError::into_inner);
+    for (namespace, quota) in quotas.iter() {
+        let Some(limit) = quota.limits.max_llm_tokens_per_day else {
+            continue;
+        };
+        if quota.has_principal(subject) && quota.llm_tokens.used(today) >= limit {
+            metrics::counter!("namespace_quota_rejections", "namespace" => namespace.clone(), "quota" => "max_llm_tokens_per_day")
+                .increment(1);
+            return LlmTokensExceededSnafu { namespace, limit }.fail();
+        }
+    }
+    Ok(())
+}
+
+/// Counts the `tokens` of an LLM call of the principal `subject` towards the day of `now` of its namespaces.
+#[allow(clippy::cast_precision_loss)]
+pub fn record_llm_tokens(subject: Option<&str>, tokens: u64, now: DateTime<Utc>) {
+    let Some(subject) = subject else {
+        return;
+    };
+    let today = now.date_naive();
+    let mut quotas = NAMESPACES.write().unwrap_or_else(PoisonError::into_inner);
+    for (namespace, quota) in quotas.iter_mut() {
+        if quota.has_principal(subject) {
+            quota.llm_tokens.add(today, tokens);
+            metrics::gauge!("namespace_llm_tokens_today", "namespace" => namespace.clone())
+                .set(quota.llm_tokens.tokens as f64);
+        }
+    }
+}
+
+/// A snapshot of the usage and quotas of all namespaces.
+#[must_use]
+pub fn usage() -> BTreeMap<String, NamespaceUsage> {
+    let today = Utc::now().date_naive();
+    NAMESPACES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|(namespace, quota)| {
+            let usage = NamespaceUsage {
+                accelerated_size_bytes: quota.accelerated_size(),
+                max_accelerated_size_bytes: quota.limits.max_accelerated_size,
+                max_rows_scanned: quota.limits.max_rows_scanned,
+                llm_tokens_today: quota.llm_tokens.used(today),
+                max_llm_tokens_per_day: quota.limits.max_llm_tokens_per_day,
+            };
+            (namespace.clone(), usage)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use spicepod::component::runtime::Quotas;
+
+    use super::*;
+
+    #[test]
+    fn enforces_accelerated_size() {
+        configure(&[Namespace {
+            name: "quotas_test".to_string(),
+            path: "quotas_test".to_string(),
+            principals: vec![],
+            quotas: Quotas {
+                max_accelerated_size: Some("1KiB".to_string()),
+                max_rows_scanned: Some(10),
+                max_llm_tokens_per_day: None,
+            },
+        }])
+        .expect("valid quotas");
+
+        let orders = TableReference::full("quotas_test", "public", "orders");
+        let users = TableReference::full("quotas_test", "public", "users");
+        record_accelerated_size(&orders, 600, None, true);
+        assert!(check_accelerated_size(&users, 500, true).is_err());
+        // Replacing the data of a dataset frees its previous size.
+        assert!(check_accelerated_size(&orders, 1000, true).is_ok());
+        assert!(check_accelerated_size(&orders, 500, false).is_err());
+
+        assert_eq!(usage()["quotas_test"].accelerated_size_bytes, 600);
+        assert_eq!(
+            max_rows_scanned([&TableReference::bare("orders"), &orders]),
+            Some(("quotas_test".to_string(), 10))
+        );
+
+        remove(&orders);
+        assert!(check_accelerated_size(&users, 500, true).is_ok());
+        configure(&[]).expect("valid quotas");
+    }
+
+    #[test]
+    fn estimates_stored_size() {
+        configure(&[Namespace {
+            name: "quotas_file_test".to_string(),
+            path: "quotas_file_test".to_string(),
+            principals: vec![],
+            quotas: Quotas {
+                max_accelerated_size: Some("1KiB".to_string()),
+                max_rows_scanned: None,
+                max_llm_tokens_per_day: None,
+            },
+        }])
+        .expect("valid quotas");
+
+        // The file of the acceleration is a quarter of the in-memory size of the data written to it.
+        let orders = TableReference::full("quotas_file_test", "public", "orders");
+        record_accelerated_size(&orders, 2000, Some(500), true);
+        assert_eq!(usage()["quotas_file_test"].accelerated_size_bytes, 500);
+
+        // Appending 1600 in-memory bytes adds about 400 bytes to the file.
+        assert!(check_accelerated_size(&orders, 1600, false).is_ok());
+        assert!(check_accelerated_size(&orders, 2400, false).is_err());
+        assert!(check_accelerated_size(&orders, 4000, true).is_ok());
+        configure(&[]).expect("valid quotas");
+    }
+
+    #[test]
+    fn enforces_llm_tokens_per_day() {
+        configure(&[Namespace {
+            name: "quotas_llm_test".to_string(),
+            path: "quotas_llm_test".to_string(),
+            principals: vec!["quotas_llm_alice".to_string()],
+            quotas: Quotas {
+                max_llm_tokens_per_day: Some(100),
+                ..Quotas::default()
+            },
+        }])
+        .expect("valid quotas");
+
+        let day = |d| {
+            NaiveDate::from_ymd_opt(2024, 6, d)
+                .and_then(|date| date.and_hms_opt(12, 0, 0))
+                .expect("valid date")
+                .and_utc()
+        };
+        assert!(check_llm_tokens(Some("quotas_llm_alice"), day(1)).is_ok());
+        record_llm_tokens(Some("quotas_llm_alice"), 60, day(1));
+        assert!(check_llm_tokens(Some("quotas_llm_alice"), day(1)).is_ok());
+        record_llm_tokens(Some("quotas_llm_alice"), 60, day(1));
+        assert!(matches!(
+            check_llm_tokens(Some("quotas_llm_alice"), day(1)),
+            Err(Error::LlmTokensExceeded { limit: 100, .. })
+        ));
+
+        // Other principals, and the next day, aren't limited by the tokens used.
+        assert!(check_llm_tokens(Some("quotas_llm_bob"), day(1)).is_ok());
+        assert!(check_llm_tokens(None, day(1)).is_ok());
+        assert!(check_llm_tokens(Some("quotas_llm_alice"), day(2)).is_ok());
+        configure(&[]).expect("valid quotas");
+    }
+}
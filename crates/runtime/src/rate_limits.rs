@@ -0,0 +1,349 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Enforces the `runtime.rate_limits` of the LLM and embedding endpoints, and counts the requests and tokens of each
+//! principal and model, queryable in `runtime.rate_limit_usage`.
+//!
+//! Requests per minute are counted in a window starting with the first request of the window. Tokens are only known
+//! once a model responds, so a request is allowed while the day's tokens are under the budget, and its tokens are
+//! counted after it completes.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use arrow::{
+    array::{RecordBatch, StringArray, UInt32Array, UInt64Array},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use datafusion::{
+    datasource::{TableProvider, TableType},
+    error::Result as DataFusionResult,
+    execution::context::SessionState,
+    logical_expr::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+};
+use spicepod::component::runtime::RateLimit;
+
+pub const RATE_LIMIT_USAGE_TABLE: &str = "rate_limit_usage";
+
+/// The principal of unauthenticated requests.
+const ANONYMOUS: &str = "anonymous";
+
+/// A limit a request was rejected for.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Exceeded {
+    Requests { limit: u32, retry_after: Duration },
+    Tokens { limit: u64, retry_after: Duration },
+}
+
+impl Exceeded {
+    pub(crate) fn retry_after(&self) -> Duration {
+        match self {
+            Exceeded::Requests { retry_after, .. } | Exceeded::Tokens { retry_after, .. } => {
+                *retry_after
+            }
+        }
+    }
+}
+
+impl fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exceeded::Requests { limit, retry_after } => write!(
+                f,
+                "Rate limit of {limit} requests per minute exceeded, retry in {}s",
+                retry_after.as_secs().max(1)
+            ),
+            Exceeded::Tokens { limit, retry_after } => write!(
+                f,
+                "Budget of {limit} tokens per day exceeded, retry in {}s",
+                retry_after.as_secs().max(1)
+            ),
+        }
+    }
+}
+
+/// The requests and tokens of a principal for a model.
+#[derive(Debug, Default)]
+struct Usage {
+    minute_start: Option<DateTime<Utc>>,
+    minute_requests: u32,
+    day: Option<NaiveDate>,
+    day_tokens: u64,
+    requests: u64,
+    tokens: u64,
+    rejected: u64,
+}
+
+impl Usage {
+    fn roll(&mut self, now: DateTime<Utc>) {
+        if self
+            .minute_start
+            .map_or(true, |start| now - start >= TimeDelta::minutes(1))
+        {
+            self.minute_start = Some(now);
+            self.minute_requests = 0;
+        }
+        if self.day != Some(now.date_naive()) {
+            self.day = Some(now.date_naive());
+            self.day_tokens = 0;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    usage: Mutex<HashMap<(String, String), Usage>>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request of `principal` for `model`, unless it exceeds one of `limits`.
+    pub(crate) fn acquire(
+        &self,
+        limits: &[RateLimit],
+        principal: Option<&str>,
+        model: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), Exceeded> {
+        let principal = principal.unwrap_or(ANONYMOUS);
+        let mut usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let usage = usage
+            .entry((principal.to_string(), model.to_string()))
+            .or_default();
+        usage.roll(now);
+
+        for limit in limits.iter().filter(|limit| {
+            limit.model.as_deref().map_or(true, |m| m == model)
+                && limit.principal.as_deref().map_or(true, |p| p == principal)
+        }) {
+            let exceeded = match (limit.requests_per_minute, limit.tokens_per_day) {
+                (Some(rpm), _) if usage.minute_requests >= rpm => Some(Exceeded::Requests {
+                    limit: rpm,
+                    retry_after: usage
+                        .minute_start
+                        .map(|start| start + TimeDelta::minutes(1) - now)
+                        .and_then(|delta| delta.to_std().ok())
+                        .unwrap_or_default(),
+                }),
+                (_, Some(tpd)) if usage.day_tokens >= tpd => Some(Exceeded::Tokens {
+                    limit: tpd,
+                    retry_after: now
+                        .date_naive()
+                        .succ_opt()
+                        .and_then(|tomorrow| tomorrow.and_hms_opt(0, 0, 0))
+                        .and_then(|midnight| (midnight.and_utc() - now).to_std().ok())
+                        .unwrap_or_default(),
+                }),
+                _ => None,
+            };
+            if let Some(exceeded) = exceeded {
+                usage.rejected += 1;
+                return Err(exceeded);
+            }
+        }
+
+        usage.minute_requests += 1;
+        usage.requests += 1;
+        Ok(())
+    }
+
+    /// Counts the tokens of a completed request of `principal` for `model`.
+    pub(crate) fn record_tokens(
+        &self,
+        principal: Option<&str>,
+        model: &str,
+        tokens: u64,
+        now: DateTime<Utc>,
+    ) {
+        let principal = principal.unwrap_or(ANONYMOUS);
+        let mut usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let usage = usage
+            .entry((principal.to_string(), model.to_string()))
+            .or_default();
+        usage.roll(now);
+        usage.day_tokens += tokens;
+        usage.tokens += tokens;
+    }
+
+    fn record_batch(&self, schema: SchemaRef) -> DataFusionResult<RecordBatch> {
+        let usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut rows: Vec<_> = usage.iter().collect();
+        rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|((principal, _), _)| principal),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|((_, model), _)| model),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    rows.iter().map(|(_, u)| u.requests),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    rows.iter().map(|(_, u)| u.tokens),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    rows.iter().map(|(_, u)| u.rejected),
+                )),
+                Arc::new(UInt32Array::from_iter_values(
+                    rows.iter().map(|(_, u)| u.minute_requests),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    rows.iter().map(|(_, u)| u.day_tokens),
+                )),
+            ],
+        )?)
+    }
+}
+
+/// Lists the usage counted by a [`RateLimiter`] when it is scanned.
+pub struct RateLimitUsageTable {
+    limiter: Arc<RateLimiter>,
+    schema: SchemaRef,
+}
+
+impl RateLimitUsageTable {
+    #[must_use]
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limiter,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("principal", DataType::Utf8, false),
+                Field::new("model", DataType::Utf8, false),
+                Field::new("requests", DataType::UInt64, false),
+                Field::new("tokens", DataType::UInt64, false),
+                Field::new("rejected_requests", DataType::UInt64, false),
+                Field::new("requests_this_minute", DataType::UInt32, false),
+                Field::new("tokens_today", DataType::UInt64, false),
+            ])),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for RateLimitUsageTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let batch = self.limiter.record_batch(self.schema())?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn limits_requests_and_tokens() {
+        let limits = vec![
+            RateLimit {
+                model: Some("gpt".to_string()),
+                requests_per_minute: Some(2),
+                ..RateLimit::default()
+            },
+            RateLimit {
+                principal: Some("alice".to_string()),
+                tokens_per_day: Some(100),
+                ..RateLimit::default()
+            },
+        ];
+        let limiter = RateLimiter::new();
+        let at = |h, m, s| {
+            Utc.with_ymd_and_hms(2024, 7, 1, h, m, s)
+                .single()
+                .expect("valid time")
+        };
+
+        assert!(limiter.acquire(&limits, None, "gpt", at(12, 0, 0)).is_ok());
+        assert!(limiter.acquire(&limits, None, "gpt", at(12, 0, 10)).is_ok());
+        assert_eq!(
+            limiter.acquire(&limits, None, "gpt", at(12, 0, 20)),
+            Err(Exceeded::Requests {
+                limit: 2,
+                retry_after: Duration::from_secs(40)
+            })
+        );
+        assert!(limiter.acquire(&limits, None, "gpt", at(12, 1, 0)).is_ok());
+        // Limits are counted per model and per principal.
+        assert!(limiter
+            .acquire(&limits, None, "embed", at(12, 1, 0))
+            .is_ok());
+
+        let alice = Some("alice");
+        assert!(limiter
+            .acquire(&limits, alice, "embed", at(12, 0, 0))
+            .is_ok());
+        limiter.record_tokens(alice, "embed", 100, at(12, 0, 0));
+        assert_eq!(
+            limiter.acquire(&limits, alice, "embed", at(12, 0, 0)),
+            Err(Exceeded::Tokens {
+                limit: 100,
+                retry_after: Duration::from_secs(12 * 60 * 60)
+            })
+        );
+        assert!(limiter
+            .acquire(&limits, alice, "embed", at(12, 0, 0) + TimeDelta::days(1))
+            .is_ok());
+    }
+}
@@ -0,0 +1,769 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Decodes Avro and Protobuf records into Arrow, for the records sent to `POST /v1/datasets/:name/ingest`.
+//!
+//! The format is set in the params of the dataset:
+//! - `ingest_format`: `avro` or `protobuf`.
+//! - `ingest_schema_file`: a local `.avsc` or `.proto` schema file, with `ingest_message` the full name of the
+//!   Protobuf message.
+//! - `ingest_schema_registry_url`: a Confluent schema registry, with `ingest_schema_registry_username` and
+//!   `ingest_schema_registry_password` for basic authentication. Payloads start with the header of the registry: a
+//!   zero byte, the big-endian id of the schema and, for Protobuf, the indexes of the message in the schema.
+//!
+//! An Avro payload is one or more records written one after another, or an object container file, which carries
+//! its own schema. A Protobuf payload is one message. Records, arrays and maps become Arrow structs, lists and maps.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        ArrayRef, ArrowPrimitiveType, BinaryArray, BooleanArray, FixedSizeBinaryArray, ListArray,
+        MapArray, NullArray, PrimitiveArray, RecordBatch, StringArray, StructArray,
+    },
+    buffer::{NullBuffer, OffsetBuffer},
+    datatypes::{
+        DataType, Date32Type, Decimal128Type, Fields, Float32Type, Float64Type, Int32Type,
+        Int64Type, Time32MillisecondType, Time64MicrosecondType, TimeUnit,
+        TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType, UInt32Type,
+        UInt64Type,
+    },
+    error::ArrowError,
+};
+use snafu::prelude::*;
+
+mod avro;
+mod protobuf;
+mod schema_registry;
+
+use schema_registry::{SchemaRegistry, SchemaType};
+
+/// The first bytes of an Avro object container file.
+const AVRO_CONTAINER_MAGIC: &[u8] = b"Obj\x01";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unsupported ingest_format {format}, expected avro or protobuf"))]
+    UnsupportedFormat { format: String },
+
+    #[snafu(display("ingest_format requires ingest_schema_file or ingest_schema_registry_url"))]
+    MissingSchemaSource,
+
+    #[snafu(display("Set either ingest_schema_file or ingest_schema_registry_url, not both"))]
+    AmbiguousSchemaSource,
+
+    #[snafu(display(
+        "A Protobuf ingest_schema_file requires ingest_message, the full name of the message"
+    ))]
+    MissingMessage,
+
+    #[snafu(display("Invalid ingest_schema_registry_url {url}: {source}"))]
+    InvalidRegistryUrl {
+        url: String,
+        source: url::ParseError,
+    },
+
+    #[snafu(display("Unable to read the schema file {path}: {source}"))]
+    UnableToReadSchemaFile {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Invalid Avro schema: {source}"))]
+    InvalidAvroSchema { source: apache_avro::Error },
+
+    #[snafu(display("The Avro schema is not a record"))]
+    NotARecord,
+
+    #[snafu(display("Unable to decode the Avro records: {source}"))]
+    UnableToDecodeAvro { source: apache_avro::Error },
+
+    #[snafu(display("Invalid Protobuf schema: {source}"))]
+    InvalidProtobufSchema { source: protox::Error },
+
+    #[snafu(display("The Protobuf message {message} is not in the schema"))]
+    MessageNotFound { message: String },
+
+    #[snafu(display("Unable to decode the Protobuf message: {source}"))]
+    UnableToDecodeProtobuf { source: prost::DecodeError },
+
+    #[snafu(display(
+        "The payload doesn't start with the schema registry header, a zero byte and the id of the schema"
+    ))]
+    MissingRegistryHeader,
+
+    #[snafu(display("Unable to fetch schema {id} from the schema registry: {source}"))]
+    UnableToFetchSchema { id: u32, source: reqwest::Error },
+
+    #[snafu(display(
+        "Unable to fetch the schema of subject {subject} version {version} from the schema registry: {source}"
+    ))]
+    UnableToFetchReference {
+        subject: String,
+        version: i32,
+        source: reqwest::Error,
+    },
+
+    #[snafu(display(
+        "Schema {id} of the schema registry is a {actual} schema, expected {expected}"
+    ))]
+    UnexpectedSchemaType {
+        id: u32,
+        actual: SchemaType,
+        expected: SchemaType,
+    },
+
+    #[snafu(display("{kind} values can't be converted to Arrow"))]
+    UnsupportedType { kind: String },
+
+    #[snafu(display("A value doesn't match its Arrow type {data_type}"))]
+    UnexpectedValue { data_type: DataType },
+
+    #[snafu(display("Unable to convert the records to Arrow: {source}"))]
+    UnableToBuildBatch { source: ArrowError },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Avro,
+    Protobuf,
+}
+
+#[derive(Debug)]
+enum SchemaSource {
+    File(PathBuf),
+    Registry(SchemaRegistry),
+}
+
+/// Decodes the payloads sent to a dataset in its `ingest_format`.
+#[derive(Debug)]
+pub struct RecordDecoder {
+    format: RecordFormat,
+    source: SchemaSource,
+    message: Option<String>,
+}
+
+impl RecordDecoder {
+    /// The decoder of the `ingest_*` params of a dataset, or `None` if it doesn't set `ingest_format`.
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Option<Self>> {
+        let Some(format) = params.get("ingest_format") else {
+            return Ok(None);
+        };
+        let format = match format.to_ascii_lowercase().as_str() {
+            "avro" => RecordFormat::Avro,
+            "protobuf" => RecordFormat::Protobuf,
+            _ => {
+                return UnsupportedFormatSnafu {
+                    format: format.clone(),
+                }
+                .fail()
+            }
+        };
+
+        let source = match (
+            params.get("ingest_schema_file"),
+            params.get("ingest_schema_registry_url"),
+        ) {
+            (Some(path), None) => SchemaSource::File(PathBuf::from(path)),
+            (None, Some(url)) => SchemaSource::Registry(SchemaRegistry::try_new(
+                url,
+                params.get("ingest_schema_registry_username").cloned(),
+                params.get("ingest_schema_registry_password").cloned(),
+            )?),
+            (Some(_), Some(_)) => return AmbiguousSchemaSourceSnafu.fail(),
+            (None, None) => return MissingSchemaSourceSnafu.fail(),
+        };
+
+        let message = params.get("ingest_message").cloned();
+        if format == RecordFormat::Protobuf
+            && matches!(source, SchemaSource::File(_))
+            && message.is_none()
+        {
+            return MissingMessageSnafu.fail();
+        }
+
+        Ok(Some(Self {
+            format,
+            source,
+            message,
+        }))
+    }
+
+    pub async fn decode(&self, payload: &[u8]) -> Result<RecordBatch> {
+        match (self.format, &self.source) {
+            (RecordFormat::Avro, _) if payload.starts_with(AVRO_CONTAINER_MAGIC) => {
+                avro::decode_container(payload)
+            }
+            (RecordFormat::Avro, SchemaSource::File(path)) => {
+                let schema = avro::parse_schema(&read_schema_file(path).await?)?;
+                avro::decode_datums(&schema, payload)
+            }
+            (RecordFormat::Avro, SchemaSource::Registry(registry)) => {
+                let (id, payload) = schema_registry::split_header(payload)?;
+                let schema = registry.schema(id, SchemaType::Avro).await?;
+                avro::decode_datums(&avro::parse_schema(&schema.schema)?, payload)
+            }
+            (RecordFormat::Protobuf, SchemaSource::File(path)) => {
+                let pool = protobuf::compile_file(path)?;
+                let name = self.message.clone().unwrap_or_default();
+                let message = pool
+                    .get_message_by_name(&name)
+                    .context(MessageNotFoundSnafu { message: name })?;
+                protobuf::decode(&message, payload)
+            }
+            (RecordFormat::Protobuf, SchemaSource::Registry(registry)) => {
+                let (id, mut payload) = schema_registry::split_header(payload)?;
+                let indexes = protobuf::message_indexes(&mut payload)?;
+                let schema = registry.schema(id, SchemaType::Protobuf).await?;
+                let root = format!("{id}.proto");
+                let mut sources = schema.references.clone();
+                sources.insert(root.clone(), schema.schema.clone());
+                let pool = protobuf::compile_sources(&root, sources)?;
+                let message = protobuf::indexed_message(&pool, &root, &indexes)?;
+                protobuf::decode(&message, payload)
+            }
+        }
+    }
+}
+
+async fn read_schema_file(path: &Path) -> Result<String> {
+    tokio::fs::read_to_string(path)
+        .await
+        .context(UnableToReadSchemaFileSnafu {
+            path: path.display().to_string(),
+        })
+}
+
+/// A decoded value, before it is built into an Arrow array of the type of its field.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Datum {
+    Null,
+    Boolean(bool),
+    Int32(i32),
+    Int64(i64),
+    UInt32(u32),
+    UInt64(u64),
+    Float32(f32),
+    Float64(f64),
+    Decimal(i128),
+    Utf8(String),
+    Binary(Vec<u8>),
+    List(Vec<Datum>),
+    /// The values of the fields of a struct, in the order of the fields.
+    Struct(Vec<Datum>),
+    Map(Vec<(Datum, Datum)>),
+}
+
+/// Builds the rows, each a [`Datum::Struct`] of the values of `fields`, into a batch.
+fn to_record_batch(fields: &Fields, rows: Vec<Datum>) -> Result<RecordBatch> {
+    Ok(RecordBatch::from(build_struct(fields, rows)?))
+}
+
+fn build_array(data_type: &DataType, values: Vec<Datum>) -> Result<ArrayRef> {
+    let array: ArrayRef = match data_type {
+        DataType::Null => Arc::new(NullArray::new(values.len())),
+        DataType::Boolean => Arc::new(
+            values
+                .iter()
+                .map(|datum| match datum {
+                    Datum::Null => Ok(None),
+                    Datum::Boolean(value) => Ok(Some(*value)),
+                    _ => unexpected(data_type),
+                })
+                .collect::<Result<BooleanArray>>()?,
+        ),
+        DataType::Int32 => primitive::<Int32Type>(data_type, &values, int32)?,
+        DataType::Date32 => primitive::<Date32Type>(data_type, &values, int32)?,
+        DataType::Time32(TimeUnit::Millisecond) => {
+            primitive::<Time32MillisecondType>(data_type, &values, int32)?
+        }
+        DataType::Int64 => primitive::<Int64Type>(data_type, &values, int64)?,
+        DataType::Time64(TimeUnit::Microsecond) => {
+            primitive::<Time64MicrosecondType>(data_type, &values, int64)?
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            primitive::<TimestampMillisecondType>(data_type, &values, int64)?
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            primitive::<TimestampMicrosecondType>(data_type, &values, int64)?
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            primitive::<TimestampNanosecondType>(data_type, &values, int64)?
+        }
+        DataType::UInt32 => primitive::<UInt32Type>(data_type, &values, |datum| match datum {
+            Datum::UInt32(value) => Some(*value),
+            _ => None,
+        })?,
+        DataType::UInt64 => primitive::<UInt64Type>(data_type, &values, |datum| match datum {
+            Datum::UInt64(value) => Some(*value),
+            _ => None,
+        })?,
+        DataType::Float32 => primitive::<Float32Type>(data_type, &values, |datum| match datum {
+            Datum::Float32(value) => Some(*value),
+            _ => None,
+        })?,
+        DataType::Float64 => primitive::<Float64Type>(data_type, &values, |datum| match datum {
+            Datum::Float64(value) => Some(*value),
+            _ => None,
+        })?,
+        DataType::Decimal128(_, _) => {
+            primitive::<Decimal128Type>(data_type, &values, |datum| match datum {
+                Datum::Decimal(value) => Some(*value),
+                _ => None,
+            })?
+        }
+        DataType::Utf8 => Arc::new(
+            values
+                .iter()
+                .map(|datum| match datum {
+                    Datum::Null => Ok(None),
+                    Datum::Utf8(value) => Ok(Some(value.as_str())),
+                    _ => unexpected(data_type),
+                })
+                .collect::<Result<StringArray>>()?,
+        ),
+        DataType::Binary => Arc::new(
+            values
+                .iter()
+                .map(|datum| match datum {
+                    Datum::Null => Ok(None),
+                    Datum::Binary(value) => Ok(Some(value.as_slice())),
+                    _ => unexpected(data_type),
+                })
+                .collect::<Result<BinaryArray>>()?,
+        ),
+        DataType::FixedSizeBinary(size) => {
+            let values = values
+                .iter()
+                .map(|datum| match datum {
+                    Datum::Null => Ok(None),
+                    Datum::Binary(value) => Ok(Some(value.as_slice())),
+                    _ => unexpected(data_type),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(
+                FixedSizeBinaryArray::try_from_sparse_iter_with_size(values.into_iter(), *size)
+                    .context(UnableToBuildBatchSnafu)?,
+            )
+        }
+        DataType::List(field) => {
+            let mut lengths = Vec::with_capacity(values.len());
+            let mut valid = Vec::with_capacity(values.len());
+            let mut items = vec![];
+            for datum in values {
+                match datum {
+                    Datum::List(list) => {
+                        lengths.push(list.len());
+                        valid.push(true);
+                        items.extend(list);
+                    }
+                    Datum::Null => {
+                        lengths.push(0);
+                        valid.push(false);
+                    }
+                    _ => return unexpected(data_type),
+                }
+            }
+            Arc::new(
+                ListArray::try_new(
+                    Arc::clone(field),
+                    OffsetBuffer::from_lengths(lengths),
+                    build_array(field.data_type(), items)?,
+                    nulls(valid),
+                )
+                .context(UnableToBuildBatchSnafu)?,
+            )
+        }
+        DataType::Struct(fields) => Arc::new(build_struct(fields, values)?),
+        DataType::Map(entries, sorted) => {
+            let DataType::Struct(entry_fields) = entries.data_type() else {
+                return unexpected(data_type);
+            };
+            let mut lengths = Vec::with_capacity(values.len());
+            let mut valid = Vec::with_capacity(values.len());
+            let mut pairs = vec![];
+            for datum in values {
+                match datum {
+                    Datum::Map(map) => {
+                        lengths.push(map.len());
+                        valid.push(true);
+                        pairs.extend(
+                            map.into_iter()
+                                .map(|(key, value)| Datum::Struct(vec![key, value])),
+                        );
+                    }
+                    Datum::Null => {
+                        lengths.push(0);
+                        valid.push(false);
+                    }
+                    _ => return unexpected(data_type),
+                }
+            }
+            Arc::new(
+                MapArray::try_new(
+                    Arc::clone(entries),
+                    OffsetBuffer::from_lengths(lengths),
+                    build_struct(entry_fields, pairs)?,
+                    nulls(valid),
+                    *sorted,
+                )
+                .context(UnableToBuildBatchSnafu)?,
+            )
+        }
+        data_type => {
+            return UnsupportedTypeSnafu {
+                kind: data_type.to_string(),
+            }
+            .fail()
+        }
+    };
+    Ok(array)
+}
+
+fn build_struct(fields: &Fields, values: Vec<Datum>) -> Result<StructArray> {
+    let mut columns = vec![Vec::with_capacity(values.len()); fields.len()];
+    let mut valid = Vec::with_capacity(values.len());
+    for datum in values {
+        match datum {
+            Datum::Struct(items) if items.len() == fields.len() => {
+                for (column, item) in columns.iter_mut().zip(items) {
+                    column.push(item);
+                }
+                valid.push(true);
+            }
+            Datum::Null => {
+                for column in &mut columns {
+                    column.push(Datum::Null);
+                }
+                valid.push(false);
+            }
+            _ => return unexpected(&DataType::Struct(fields.clone())),
+        }
+    }
+
+    if fields.is_empty() {
+        let len = valid.len();
+        return Ok(StructArray::new_empty_fields(len, nulls(valid)));
+    }
+    let arrays = fields
+        .iter()
+        .zip(columns)
+        .map(|(field, column)| build_array(field.data_type(), column))
+        .collect::<Result<Vec<_>>>()?;
+    StructArray::try_new(fields.clone(), arrays, nulls(valid)).context(UnableToBuildBatchSnafu)
+}
+
+fn primitive<T: ArrowPrimitiveType>(
+    data_type: &DataType,
+    values: &[Datum],
+    value: impl Fn(&Datum) -> Option<T::Native>,
+) -> Result<ArrayRef> {
+    let array = values
+        .iter()
+        .map(|datum| match datum {
+            Datum::Null => Ok(None),
+            datum => value(datum).map(Some).context(UnexpectedValueSnafu {
+                data_type: data_type.clone(),
+            }),
+        })
+        .collect::<Result<PrimitiveArray<T>>>()?;
+    Ok(Arc::new(array.with_data_type(data_type.clone())))
+}
+
+fn int32(datum: &Datum) -> Option<i32> {
+    match datum {
+        Datum::Int32(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn int64(datum: &Datum) -> Option<i64> {
+    match datum {
+        Datum::Int64(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn nulls(valid: Vec<bool>) -> Option<NullBuffer> {
+    valid.contains(&false).then(|| NullBuffer::from(valid))
+}
+
+fn unexpected<T>(data_type: &DataType) -> Result<T> {
+    UnexpectedValueSnafu {
+        data_type: data_type.clone(),
+    }
+    .fail()
+}
+
+#[cfg(test)]
+mod tests {
+    use apache_avro::{to_avro_datum, types::Value as AvroValue, Schema, Writer};
+    use arrow::array::{Array, AsArray};
+    use prost::Message;
+    use prost_reflect::{DynamicMessage, MapKey, Value};
+
+    use super::*;
+
+    const ORDER_AVSC: &str = r#"{
+        "type": "record",
+        "name": "Order",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "note", "type": ["null", "string"]},
+            {"name": "items", "type": {"type": "array", "items": {
+                "type": "record",
+                "name": "Item",
+                "fields": [{"name": "sku", "type": "string"}, {"name": "quantity", "type": "int"}]
+            }}},
+            {"name": "tags", "type": {"type": "map", "values": "int"}},
+            {"name": "placed_at", "type": {"type": "long", "logicalType": "timestamp-millis"}}
+        ]
+    }"#;
+
+    fn order(id: i64, note: Option<&str>) -> AvroValue {
+        AvroValue::Record(vec![
+            ("id".to_string(), AvroValue::Long(id)),
+            (
+                "note".to_string(),
+                match note {
+                    Some(note) => {
+                        AvroValue::Union(1, Box::new(AvroValue::String(note.to_string())))
+                    }
+                    None => AvroValue::Union(0, Box::new(AvroValue::Null)),
+                },
+            ),
+            (
+                "items".to_string(),
+                AvroValue::Array(vec![AvroValue::Record(vec![
+                    ("sku".to_string(), AvroValue::String("a-1".to_string())),
+                    ("quantity".to_string(), AvroValue::Int(2)),
+                ])]),
+            ),
+            (
+                "tags".to_string(),
+                AvroValue::Map(HashMap::from([("priority".to_string(), AvroValue::Int(1))])),
+            ),
+            (
+                "placed_at".to_string(),
+                AvroValue::TimestampMillis(1_717_200_000_000),
+            ),
+        ])
+    }
+
+    fn check_orders(batch: &RecordBatch) {
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            batch.column(0).as_primitive::<Int64Type>().values(),
+            &[1, 2]
+        );
+
+        let notes = batch.column(1).as_string::<i32>();
+        assert_eq!(notes.value(0), "rush");
+        assert!(notes.is_null(1));
+
+        let items = batch.column(2).as_list::<i32>();
+        let item = items.value(0);
+        let item = item.as_struct();
+        assert_eq!(item.column(0).as_string::<i32>().value(0), "a-1");
+        assert_eq!(item.column(1).as_primitive::<Int32Type>().value(0), 2);
+
+        let tags = batch.column(3).as_map();
+        assert_eq!(tags.keys().as_string::<i32>().value(0), "priority");
+        assert_eq!(tags.values().as_primitive::<Int32Type>().value(0), 1);
+
+        assert_eq!(
+            batch.schema().field(4).data_type(),
+            &DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_avro_records_of_a_schema_file() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("order.avsc");
+        std::fs::write(&path, ORDER_AVSC).expect("schema file");
+        let decoder = RecordDecoder::from_params(&HashMap::from([
+            ("ingest_format".to_string(), "avro".to_string()),
+            ("ingest_schema_file".to_string(), path.display().to_string()),
+        ]))
+        .expect("valid params")
+        .expect("decoder");
+
+        let schema = Schema::parse_str(ORDER_AVSC).expect("valid schema");
+        let mut payload = to_avro_datum(&schema, order(1, Some("rush"))).expect("encoded");
+        payload.extend(to_avro_datum(&schema, order(2, None)).expect("encoded"));
+
+        check_orders(&decoder.decode(&payload).await.expect("decoded"));
+    }
+
+    #[test]
+    fn decodes_avro_container_files() {
+        let schema = Schema::parse_str(ORDER_AVSC).expect("valid schema");
+        let mut writer = Writer::new(&schema, vec![]);
+        writer.append(order(1, Some("rush"))).expect("appended");
+        writer.append(order(2, None)).expect("appended");
+        let payload = writer.into_inner().expect("container file");
+
+        check_orders(&avro::decode_container(&payload).expect("decoded"));
+    }
+
+    #[test]
+    fn rejects_invalid_params() {
+        let params = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        assert!(matches!(RecordDecoder::from_params(&params(&[])), Ok(None)));
+        assert!(matches!(
+            RecordDecoder::from_params(&params(&[("ingest_format", "json")])),
+            Err(Error::UnsupportedFormat { .. })
+        ));
+        assert!(matches!(
+            RecordDecoder::from_params(&params(&[("ingest_format", "avro")])),
+            Err(Error::MissingSchemaSource)
+        ));
+        assert!(matches!(
+            RecordDecoder::from_params(&params(&[
+                ("ingest_format", "protobuf"),
+                ("ingest_schema_file", "order.proto")
+            ])),
+            Err(Error::MissingMessage)
+        ));
+    }
+
+    const ORDER_PROTO: &str = r#"
+        syntax = "proto3";
+        package shop;
+
+        import "google/protobuf/timestamp.proto";
+
+        message Order {
+            message Item {
+                string sku = 1;
+                int32 quantity = 2;
+            }
+
+            int64 id = 1;
+            optional string note = 2;
+            repeated Item items = 3;
+            map<string, int32> tags = 4;
+            google.protobuf.Timestamp placed_at = 5;
+            Status status = 6;
+        }
+
+        enum Status {
+            PENDING = 0;
+            SHIPPED = 1;
+        }
+    "#;
+
+    #[test]
+    fn decodes_protobuf_messages_of_the_schema_registry() {
+        let pool = protobuf::compile_sources(
+            "1.proto",
+            HashMap::from([("1.proto".to_string(), ORDER_PROTO.to_string())]),
+        )
+        .expect("valid schema");
+        let descriptor = protobuf::indexed_message(&pool, "1.proto", &[0]).expect("message");
+        assert_eq!(descriptor.full_name(), "shop.Order");
+        let item = protobuf::indexed_message(&pool, "1.proto", &[0, 0]).expect("message");
+        assert_eq!(item.full_name(), "shop.Order.Item");
+
+        let mut order = DynamicMessage::new(descriptor.clone());
+        order.set_field_by_name("id", Value::I64(7));
+        let mut line = DynamicMessage::new(item);
+        line.set_field_by_name("sku", Value::String("a-1".to_string()));
+        line.set_field_by_name("quantity", Value::I32(2));
+        order.set_field_by_name("items", Value::List(vec![Value::Message(line)]));
+        order.set_field_by_name(
+            "tags",
+            Value::Map(HashMap::from([(
+                MapKey::String("priority".to_string()),
+                Value::I32(1),
+            )])),
+        );
+        let timestamp = pool
+            .get_message_by_name("google.protobuf.Timestamp")
+            .expect("well-known type");
+        let mut placed_at = DynamicMessage::new(timestamp);
+        placed_at.set_field_by_name("seconds", Value::I64(1_717_200_000));
+        placed_at.set_field_by_name("nanos", Value::I32(5));
+        order.set_field_by_name("placed_at", Value::Message(placed_at));
+        order.set_field_by_name("status", Value::EnumNumber(1));
+
+        // The header of schema 1, then the indexes of the first message, encoded as a single zero.
+        let mut payload = vec![0, 0, 0, 0, 1, 0];
+        payload.extend(order.encode_to_vec());
+        let (id, mut rest) = schema_registry::split_header(&payload).expect("header");
+        assert_eq!(id, 1);
+        assert_eq!(
+            protobuf::message_indexes(&mut rest).expect("indexes"),
+            vec![0]
+        );
+
+        let batch = protobuf::decode(&descriptor, rest).expect("decoded");
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), 7);
+        // Unset optional fields are null, rather than their default value.
+        assert!(batch.column(1).is_null(0));
+        let items = batch.column(2).as_list::<i32>().value(0);
+        assert_eq!(
+            items
+                .as_struct()
+                .column(1)
+                .as_primitive::<Int32Type>()
+                .value(0),
+            2
+        );
+        assert_eq!(
+            batch.column(3).as_map().keys().as_string::<i32>().value(0),
+            "priority"
+        );
+        assert_eq!(
+            batch
+                .column(4)
+                .as_primitive::<TimestampNanosecondType>()
+                .value(0),
+            1_717_200_000_000_000_005
+        );
+        assert_eq!(batch.column(5).as_string::<i32>().value(0), "SHIPPED");
+    }
+
+    #[test]
+    fn reads_message_indexes() {
+        // Zigzag varints: two indexes, 1 and 2.
+        let mut payload: &[u8] = &[4, 2, 4, 0xff];
+        assert_eq!(
+            protobuf::message_indexes(&mut payload).expect("indexes"),
+            vec![1, 2]
+        );
+        assert_eq!(payload, &[0xff]);
+
+        assert!(matches!(
+            schema_registry::split_header(&[1, 0, 0, 0, 1]),
+            Err(Error::MissingRegistryHeader)
+        ));
+    }
+}
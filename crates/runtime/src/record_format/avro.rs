@@ -0,0 +1,243 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use apache_avro::{
+    from_avro_datum,
+    schema::{Name, ResolvedSchema, SchemaKind, UnionSchema},
+    types::Value,
+    Decimal, Reader, Schema,
+};
+use arrow::{
+    array::RecordBatch,
+    datatypes::{DataType, Field, Fields, TimeUnit},
+};
+use snafu::prelude::*;
+
+use super::{
+    to_record_batch, Datum, InvalidAvroSchemaSnafu, NotARecordSnafu, Result,
+    UnableToDecodeAvroSnafu, UnsupportedTypeSnafu,
+};
+
+pub(super) fn parse_schema(schema: &str) -> Result<Schema> {
+    Schema::parse_str(schema).context(InvalidAvroSchemaSnafu)
+}
+
+/// Decodes an object container file, with the schema it embeds.
+pub(super) fn decode_container(payload: &[u8]) -> Result<RecordBatch> {
+    let reader = Reader::new(payload).context(UnableToDecodeAvroSnafu)?;
+    let fields = record_fields(reader.writer_schema())?;
+    let rows = reader
+        .map(|value| datum(&value.context(UnableToDecodeAvroSnafu)?))
+        .collect::<Result<Vec<_>>>()?;
+    to_record_batch(&fields, rows)
+}
+
+/// Decodes the records of `schema` written one after another.
+pub(super) fn decode_datums(schema: &Schema, mut payload: &[u8]) -> Result<RecordBatch> {
+    let fields = record_fields(schema)?;
+    let mut rows = vec![];
+    while !payload.is_empty() {
+        let value = from_avro_datum(schema, &mut payload, None).context(UnableToDecodeAvroSnafu)?;
+        rows.push(datum(&value)?);
+    }
+    to_record_batch(&fields, rows)
+}
+
+/// The Arrow fields of the records of `schema`.
+fn record_fields(schema: &Schema) -> Result<Fields> {
+    let resolved = ResolvedSchema::try_from(schema).context(InvalidAvroSchemaSnafu)?;
+    match data_type(schema, resolved.get_names(), &mut vec![])?.0 {
+        DataType::Struct(fields) => Ok(fields),
+        _ => NotARecordSnafu.fail(),
+    }
+}
+
+/// The Arrow type of `schema`, and whether it is nullable. `records` are the records being converted, as a record
+/// can't contain itself in Arrow.
+fn data_type(
+    schema: &Schema,
+    names: &HashMap<Name, &Schema>,
+    records: &mut Vec<Name>,
+) -> Result<(DataType, bool)> {
+    let data_type = match schema {
+        Schema::Null => return Ok((DataType::Null, true)),
+        Schema::Boolean => DataType::Boolean,
+        Schema::Int => DataType::Int32,
+        Schema::Long => DataType::Int64,
+        Schema::Float => DataType::Float32,
+        Schema::Double => DataType::Float64,
+        Schema::Bytes => DataType::Binary,
+        Schema::String | Schema::Uuid | Schema::Enum(_) => DataType::Utf8,
+        Schema::Fixed(fixed) => {
+            DataType::FixedSizeBinary(i32::try_from(fixed.size).map_err(|_| unsupported(schema))?)
+        }
+        Schema::Decimal(decimal) => DataType::Decimal128(
+            u8::try_from(decimal.precision)
+                .ok()
+                .filter(|precision| *precision <= 38)
+                .ok_or_else(|| unsupported(schema))?,
+            i8::try_from(decimal.scale).map_err(|_| unsupported(schema))?,
+        ),
+        Schema::Date => DataType::Date32,
+        Schema::TimeMillis => DataType::Time32(TimeUnit::Millisecond),
+        Schema::TimeMicros => DataType::Time64(TimeUnit::Microsecond),
+        Schema::TimestampMillis => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        Schema::TimestampMicros => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        Schema::LocalTimestampMillis => DataType::Timestamp(TimeUnit::Millisecond, None),
+        Schema::LocalTimestampMicros => DataType::Timestamp(TimeUnit::Microsecond, None),
+        Schema::Array(items) => {
+            let (item_type, nullable) = data_type(items, names, records)?;
+            DataType::List(Arc::new(Field::new("item", item_type, nullable)))
+        }
+        Schema::Map(values) => {
+            let (value_type, nullable) = data_type(values, names, records)?;
+            DataType::Map(
+                Arc::new(Field::new(
+                    "entries",
+                    DataType::Struct(Fields::from(vec![
+                        Field::new("keys", DataType::Utf8, false),
+                        Field::new("values", value_type, nullable),
+                    ])),
+                    false,
+                )),
+                false,
+            )
+        }
+        Schema::Union(union) => return union_type(schema, union, names, records),
+        Schema::Record(record) => {
+            records.push(record.name.clone());
+            let fields = record
+                .fields
+                .iter()
+                .map(|field| {
+                    let (data_type, nullable) = data_type(&field.schema, names, records)?;
+                    Ok(Field::new(&field.name, data_type, nullable))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            records.pop();
+            DataType::Struct(Fields::from(fields))
+        }
+        Schema::Ref { name } => {
+            if records.contains(name) {
+                return UnsupportedTypeSnafu {
+                    kind: format!("Recursive Avro record {name}"),
+                }
+                .fail();
+            }
+            let named = names.get(name).ok_or_else(|| unsupported(schema))?;
+            return data_type(named, names, records);
+        }
+        _ => return Err(unsupported(schema)),
+    };
+    Ok((data_type, false))
+}
+
+/// Unions of `null` and another type are that type, nullable. Other unions have no Arrow equivalent.
+fn union_type(
+    schema: &Schema,
+    union: &UnionSchema,
+    names: &HashMap<Name, &Schema>,
+    records: &mut Vec<Name>,
+) -> Result<(DataType, bool)> {
+    let variants = union.variants();
+    let nullable = variants
+        .iter()
+        .any(|variant| matches!(variant, Schema::Null));
+    match variants
+        .iter()
+        .filter(|variant| !matches!(variant, Schema::Null))
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => Ok((DataType::Null, true)),
+        [variant] => {
+            let (data_type, variant_nullable) = data_type(variant, names, records)?;
+            Ok((data_type, nullable || variant_nullable))
+        }
+        _ => Err(unsupported(schema)),
+    }
+}
+
+fn datum(value: &Value) -> Result<Datum> {
+    Ok(match value {
+        Value::Null => Datum::Null,
+        Value::Boolean(value) => Datum::Boolean(*value),
+        Value::Int(value) | Value::Date(value) | Value::TimeMillis(value) => Datum::Int32(*value),
+        Value::Long(value)
+        | Value::TimeMicros(value)
+        | Value::TimestampMillis(value)
+        | Value::TimestampMicros(value)
+        | Value::LocalTimestampMillis(value)
+        | Value::LocalTimestampMicros(value) => Datum::Int64(*value),
+        Value::Float(value) => Datum::Float32(*value),
+        Value::Double(value) => Datum::Float64(*value),
+        Value::Bytes(value) | Value::Fixed(_, value) => Datum::Binary(value.clone()),
+        Value::String(value) | Value::Enum(_, value) => Datum::Utf8(value.clone()),
+        Value::Uuid(value) => Datum::Utf8(value.to_string()),
+        Value::Decimal(value) => Datum::Decimal(decimal(value)?),
+        Value::Union(_, value) => datum(value)?,
+        Value::Array(items) => Datum::List(items.iter().map(datum).collect::<Result<_>>()?),
+        Value::Map(entries) => {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Datum::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((Datum::Utf8(key.clone()), datum(value)?)))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Value::Record(fields) => Datum::Struct(
+            fields
+                .iter()
+                .map(|(_, value)| datum(value))
+                .collect::<Result<_>>()?,
+        ),
+        value => {
+            return UnsupportedTypeSnafu {
+                kind: format!("Avro {:?}", SchemaKind::from(value)),
+            }
+            .fail()
+        }
+    })
+}
+
+/// The unscaled value of a decimal, from its big-endian two's complement bytes.
+fn decimal(value: &Decimal) -> Result<i128> {
+    let bytes = Vec::<u8>::try_from(value).context(UnableToDecodeAvroSnafu)?;
+    if bytes.len() > 16 {
+        return UnsupportedTypeSnafu {
+            kind: "Avro decimals wider than 16 bytes",
+        }
+        .fail();
+    }
+    let fill = if bytes.first().is_some_and(|byte| byte & 0x80 != 0) {
+        0xff
+    } else {
+        0
+    };
+    let mut be_bytes = [fill; 16];
+    be_bytes[16 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(i128::from_be_bytes(be_bytes))
+}
+
+fn unsupported(schema: &Schema) -> super::Error {
+    super::Error::UnsupportedType {
+        kind: format!("Avro {:?}", SchemaKind::from(schema)),
+    }
+}
@@ -0,0 +1,269 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use arrow::{
+    array::RecordBatch,
+    datatypes::{DataType, Field, Fields, TimeUnit},
+};
+use prost::encoding::decode_varint;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor, Value,
+};
+use protox::{
+    file::{ChainFileResolver, File, FileResolver, GoogleFileResolver},
+    Compiler,
+};
+use snafu::prelude::*;
+
+use super::{
+    to_record_batch, Datum, InvalidProtobufSchemaSnafu, MessageNotFoundSnafu, Result,
+    UnableToDecodeProtobufSnafu, UnsupportedTypeSnafu,
+};
+
+/// Converted to an Arrow timestamp rather than a struct of its seconds and nanos.
+const TIMESTAMP: &str = "google.protobuf.Timestamp";
+
+/// Compiles a `.proto` file, with the files it imports from its directory and the well-known types.
+pub(super) fn compile_file(path: &Path) -> Result<DescriptorPool> {
+    let include = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut compiler = Compiler::new([include]).context(InvalidProtobufSchemaSnafu)?;
+    compiler.include_imports(true);
+    compiler
+        .open_file(path)
+        .context(InvalidProtobufSchemaSnafu)?;
+    Ok(compiler.descriptor_pool())
+}
+
+/// Compiles the file `root` of `sources`, the `.proto` files by name, with the well-known types.
+pub(super) fn compile_sources(
+    root: &str,
+    sources: HashMap<String, String>,
+) -> Result<DescriptorPool> {
+    let mut resolver = ChainFileResolver::new();
+    resolver.add(SourceResolver(sources));
+    resolver.add(GoogleFileResolver::new());
+
+    let mut compiler = Compiler::with_file_resolver(resolver);
+    compiler.include_imports(true);
+    compiler
+        .open_file(root)
+        .context(InvalidProtobufSchemaSnafu)?;
+    Ok(compiler.descriptor_pool())
+}
+
+struct SourceResolver(HashMap<String, String>);
+
+impl FileResolver for SourceResolver {
+    fn open_file(&self, name: &str) -> Result<File, protox::Error> {
+        match self.0.get(name) {
+            Some(source) => File::from_source(name, source),
+            None => Err(protox::Error::file_not_found(name)),
+        }
+    }
+}
+
+/// Reads the message indexes of the schema registry header: the index of a message in the file, then of each
+/// nested message, as zigzag varints preceded by their count. A count of zero is the first message of the file.
+pub(super) fn message_indexes(payload: &mut &[u8]) -> Result<Vec<usize>> {
+    let count = zigzag_varint(payload)?;
+    if count == 0 {
+        return Ok(vec![0]);
+    }
+    (0..count).map(|_| zigzag_varint(payload)).collect()
+}
+
+fn zigzag_varint(payload: &mut &[u8]) -> Result<usize> {
+    let value = decode_varint(payload).context(UnableToDecodeProtobufSnafu)?;
+    let value = (value >> 1) ^ (value & 1).wrapping_neg();
+    usize::try_from(value).map_err(|_| super::Error::MissingRegistryHeader)
+}
+
+/// The message of `file` at `indexes`, as in the schema registry header.
+pub(super) fn indexed_message(
+    pool: &DescriptorPool,
+    file: &str,
+    indexes: &[usize],
+) -> Result<MessageDescriptor> {
+    let not_found = || MessageNotFoundSnafu {
+        message: format!("{file} {indexes:?}"),
+    };
+    let file_descriptor = pool.get_file_by_name(file).with_context(not_found)?;
+    let (first, nested) = indexes.split_first().with_context(not_found)?;
+    let mut message = file_descriptor.messages().nth(*first);
+    for index in nested {
+        message = message.and_then(|message| message.child_messages().nth(*index));
+    }
+    message.with_context(not_found)
+}
+
+pub(super) fn decode(message: &MessageDescriptor, payload: &[u8]) -> Result<RecordBatch> {
+    let fields = message_fields(message, &mut vec![])?;
+    let decoded =
+        DynamicMessage::decode(message.clone(), payload).context(UnableToDecodeProtobufSnafu)?;
+    to_record_batch(&fields, vec![message_datum(&decoded)])
+}
+
+/// The Arrow fields of `message`. `messages` are the messages being converted, as a struct can't contain itself in
+/// Arrow.
+fn message_fields(message: &MessageDescriptor, messages: &mut Vec<String>) -> Result<Fields> {
+    if messages.iter().any(|name| name == message.full_name()) {
+        return UnsupportedTypeSnafu {
+            kind: format!("Recursive Protobuf message {}", message.full_name()),
+        }
+        .fail();
+    }
+    messages.push(message.full_name().to_string());
+    let fields = message
+        .fields()
+        .map(|field| field_type(&field, messages))
+        .collect::<Result<Vec<_>>>()?;
+    messages.pop();
+    Ok(Fields::from(fields))
+}
+
+/// Fields without presence, like the scalars of proto3, always have a value, their default if unset.
+fn field_type(field: &FieldDescriptor, messages: &mut Vec<String>) -> Result<Field> {
+    let data_type = match field.kind() {
+        Kind::Message(entry) if field.is_map() => {
+            let key = entry.map_entry_key_field();
+            let value = entry.map_entry_value_field();
+            DataType::Map(
+                Arc::new(Field::new(
+                    "entries",
+                    DataType::Struct(Fields::from(vec![
+                        Field::new("keys", kind_type(&key.kind(), messages)?, false),
+                        Field::new("values", kind_type(&value.kind(), messages)?, false),
+                    ])),
+                    false,
+                )),
+                false,
+            )
+        }
+        kind if field.is_list() => DataType::List(Arc::new(Field::new(
+            "item",
+            kind_type(&kind, messages)?,
+            false,
+        ))),
+        kind => kind_type(&kind, messages)?,
+    };
+    let nullable = !field.is_list() && !field.is_map() && field.supports_presence();
+    Ok(Field::new(field.name(), data_type, nullable))
+}
+
+fn kind_type(kind: &Kind, messages: &mut Vec<String>) -> Result<DataType> {
+    Ok(match kind {
+        Kind::Double => DataType::Float64,
+        Kind::Float => DataType::Float32,
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => DataType::Int32,
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => DataType::Int64,
+        Kind::Uint32 | Kind::Fixed32 => DataType::UInt32,
+        Kind::Uint64 | Kind::Fixed64 => DataType::UInt64,
+        Kind::Bool => DataType::Boolean,
+        Kind::String | Kind::Enum(_) => DataType::Utf8,
+        Kind::Bytes => DataType::Binary,
+        Kind::Message(message) if message.full_name() == TIMESTAMP => {
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into()))
+        }
+        Kind::Message(message) => DataType::Struct(message_fields(message, messages)?),
+    })
+}
+
+fn message_datum(message: &DynamicMessage) -> Datum {
+    Datum::Struct(
+        message
+            .descriptor()
+            .fields()
+            .map(|field| {
+                if !field.is_list()
+                    && !field.is_map()
+                    && field.supports_presence()
+                    && !message.has_field(&field)
+                {
+                    return Datum::Null;
+                }
+                field_datum(&field, &message.get_field(&field))
+            })
+            .collect(),
+    )
+}
+
+fn field_datum(field: &FieldDescriptor, value: &Value) -> Datum {
+    match (value, field.kind()) {
+        (Value::List(items), kind) => {
+            Datum::List(items.iter().map(|item| value_datum(item, &kind)).collect())
+        }
+        (Value::Map(entries), Kind::Message(entry)) => {
+            let value_kind = entry.map_entry_value_field().kind();
+            Datum::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key_datum(key), value_datum(value, &value_kind)))
+                    .collect(),
+            )
+        }
+        (value, kind) => value_datum(value, &kind),
+    }
+}
+
+fn value_datum(value: &Value, kind: &Kind) -> Datum {
+    match (value, kind) {
+        (Value::Bool(value), _) => Datum::Boolean(*value),
+        (Value::I32(value), _) => Datum::Int32(*value),
+        (Value::I64(value), _) => Datum::Int64(*value),
+        (Value::U32(value), _) => Datum::UInt32(*value),
+        (Value::U64(value), _) => Datum::UInt64(*value),
+        (Value::F32(value), _) => Datum::Float32(*value),
+        (Value::F64(value), _) => Datum::Float64(*value),
+        (Value::String(value), _) => Datum::Utf8(value.clone()),
+        (Value::Bytes(value), _) => Datum::Binary(value.to_vec()),
+        (Value::EnumNumber(number), Kind::Enum(descriptor)) => Datum::Utf8(
+            descriptor
+                .get_value(*number)
+                .map_or_else(|| number.to_string(), |value| value.name().to_string()),
+        ),
+        (Value::EnumNumber(number), _) => Datum::Utf8(number.to_string()),
+        (Value::Message(message), _) if message.descriptor().full_name() == TIMESTAMP => {
+            let seconds = message
+                .get_field_by_name("seconds")
+                .and_then(|seconds| seconds.as_i64())
+                .unwrap_or_default();
+            let nanos = message
+                .get_field_by_name("nanos")
+                .and_then(|nanos| nanos.as_i32())
+                .unwrap_or_default();
+            Datum::Int64(
+                seconds
+                    .saturating_mul(1_000_000_000)
+                    .saturating_add(i64::from(nanos)),
+            )
+        }
+        (Value::Message(message), _) => message_datum(message),
+        (Value::List(_) | Value::Map(_), _) => Datum::Null,
+    }
+}
+
+fn key_datum(key: &MapKey) -> Datum {
+    match key {
+        MapKey::Bool(value) => Datum::Boolean(*value),
+        MapKey::I32(value) => Datum::Int32(*value),
+        MapKey::I64(value) => Datum::Int64(*value),
+        MapKey::U32(value) => Datum::UInt32(*value),
+        MapKey::U64(value) => Datum::UInt64(*value),
+        MapKey::String(value) => Datum::Utf8(value.clone()),
+    }
+}
@@ -0,0 +1,188 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize};
+use snafu::prelude::*;
+use url::Url;
+
+use super::{
+    Error, InvalidRegistryUrlSnafu, MissingRegistryHeaderSnafu, Result,
+    UnableToFetchReferenceSnafu, UnableToFetchSchemaSnafu, UnexpectedSchemaTypeSnafu,
+};
+
+/// The schemas fetched by registry and id. The schema of an id never changes, so they are kept for the lifetime of
+/// the runtime.
+static SCHEMAS: Lazy<Mutex<HashMap<(String, u32), Arc<RegisteredSchema>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    Avro,
+    Protobuf,
+    Json,
+}
+
+impl fmt::Display for SchemaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaType::Avro => write!(f, "Avro"),
+            SchemaType::Protobuf => write!(f, "Protobuf"),
+            SchemaType::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// A schema of the registry, with the schemas it references by the name they are imported with.
+#[derive(Debug)]
+pub(super) struct RegisteredSchema {
+    pub(super) schema: String,
+    pub(super) references: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaResponse {
+    schema: String,
+    /// Unset for Avro schemas.
+    schema_type: Option<SchemaType>,
+    #[serde(default)]
+    references: Vec<SchemaReference>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaReference {
+    name: String,
+    subject: String,
+    version: i32,
+}
+
+/// A Confluent schema registry.
+#[derive(Debug)]
+pub(super) struct SchemaRegistry {
+    url: Url,
+    username: Option<String>,
+    password: Option<String>,
+    client: reqwest::Client,
+}
+
+impl SchemaRegistry {
+    pub(super) fn try_new(
+        url: &str,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self> {
+        let parsed = Url::parse(url).context(InvalidRegistryUrlSnafu { url })?;
+        Ok(Self {
+            url: parsed,
+            username,
+            password,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// The schema `id`, which must be a schema of type `expected`.
+    pub(super) async fn schema(
+        &self,
+        id: u32,
+        expected: SchemaType,
+    ) -> Result<Arc<RegisteredSchema>> {
+        let key = (self.url.to_string(), id);
+        let cached = match SCHEMAS.lock() {
+            Ok(schemas) => schemas.get(&key).cloned(),
+            Err(poisoned) => poisoned.into_inner().get(&key).cloned(),
+        };
+        if let Some(schema) = cached {
+            return Ok(schema);
+        }
+
+        let response: SchemaResponse = self
+            .get(&["schemas", "ids", &id.to_string()])
+            .await
+            .context(UnableToFetchSchemaSnafu { id })?;
+        let actual = response.schema_type.unwrap_or(SchemaType::Avro);
+        ensure!(
+            actual == expected,
+            UnexpectedSchemaTypeSnafu {
+                id,
+                actual,
+                expected
+            }
+        );
+
+        // References are fetched by subject and version, and can reference other schemas in turn.
+        let mut references = HashMap::new();
+        let mut pending = response.references;
+        while let Some(reference) = pending.pop() {
+            if references.contains_key(&reference.name) {
+                continue;
+            }
+            let referenced: SchemaResponse = self
+                .get(&[
+                    "subjects",
+                    &reference.subject,
+                    "versions",
+                    &reference.version.to_string(),
+                ])
+                .await
+                .context(UnableToFetchReferenceSnafu {
+                    subject: reference.subject.clone(),
+                    version: reference.version,
+                })?;
+            pending.extend(referenced.references);
+            references.insert(reference.name, referenced.schema);
+        }
+
+        let schema = Arc::new(RegisteredSchema {
+            schema: response.schema,
+            references,
+        });
+        match SCHEMAS.lock() {
+            Ok(mut schemas) => schemas.insert(key, Arc::clone(&schema)),
+            Err(poisoned) => poisoned.into_inner().insert(key, Arc::clone(&schema)),
+        };
+        Ok(schema)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> reqwest::Result<T> {
+        let mut url = self.url.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+
+        let mut request = self.client.get(url);
+        if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        request.send().await?.error_for_status()?.json().await
+    }
+}
+
+/// Splits a payload into the id of its schema and the rest of the payload, after the magic byte and the big-endian
+/// id of the header.
+pub(super) fn split_header(payload: &[u8]) -> Result<(u32, &[u8]), Error> {
+    match payload {
+        [0, a, b, c, d, rest @ ..] => Ok((u32::from_be_bytes([*a, *b, *c, *d]), rest)),
+        _ => MissingRegistryHeaderSnafu.fail(),
+    }
+}
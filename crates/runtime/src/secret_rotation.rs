@@ -0,0 +1,217 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Re-reads the secrets used by loaded components and reloads the components whose secrets were rotated.
+//!
+//! A dataset is only reloaded once its data connector can connect with the new secret, and an accelerated dataset
+//! keeps serving queries from its current table until the new one is ready, so a rotation in progress doesn't take
+//! datasets offline. A secret that can't be read, or whose components fail to reload, is checked again next time.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use model_components::modelsource::source as model_source;
+use spicepod::component::{embeddings::Embeddings, llms::Llm, model::Model as SpicepodModel};
+
+use crate::component::dataset::Dataset;
+use crate::{model, Runtime};
+
+/// A loaded component that reads a secret.
+enum Component {
+    Dataset(Dataset),
+    Model(SpicepodModel),
+    Llm(Llm),
+    Embedding(Embeddings),
+}
+
+impl Component {
+    fn kind(&self) -> &'static str {
+        match self {
+            Component::Dataset(_) => "dataset",
+            Component::Model(_) => "model",
+            Component::Llm(_) => "llm",
+            Component::Embedding(_) => "embedding",
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Component::Dataset(ds) => ds.name.to_string(),
+            Component::Model(m) => m.name.clone(),
+            Component::Llm(llm) => llm.name.clone(),
+            Component::Embedding(embed) => embed.name.clone(),
+        }
+    }
+}
+
+/// Checks the secrets of the loaded components every `interval`, until the runtime stops.
+pub(crate) async fn watch(rt: &Runtime, interval: Duration) {
+    // The fingerprint of each secret when its components were last loaded.
+    let mut fingerprints: HashMap<String, u64> = HashMap::new();
+
+    loop {
+        if let Err(e) = rt.secrets_provider.write().await.reload() {
+            tracing::warn!("Unable to reload secrets: {e}");
+        }
+
+        let usages = secret_usages(rt).await;
+        let names: HashSet<&str> = usages.iter().map(|(name, _)| name.as_str()).collect();
+
+        let mut rotated = HashMap::new();
+        {
+            let secrets_provider = rt.secrets_provider.read().await;
+            for name in &names {
+                let fingerprint = match secrets_provider.get_secret(name).await {
+                    Ok(Some(secret)) => secret.fingerprint(),
+                    // Components keep their current secret while it is missing or can't be read.
+                    Ok(None) => continue,
+                    Err(e) => {
+                        tracing::warn!("Unable to check secret {name} for rotation: {e}");
+                        continue;
+                    }
+                };
+                match fingerprints.insert((*name).to_string(), fingerprint) {
+                    Some(previous) if previous != fingerprint => {
+                        rotated.insert(*name, previous);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        fingerprints.retain(|name, _| names.contains(name.as_str()));
+
+        let mut reloaded = HashSet::new();
+        for (name, component) in &usages {
+            let Some(previous) = rotated.get(name.as_str()) else {
+                continue;
+            };
+            if !reloaded.insert((component.kind(), component.name())) {
+                continue;
+            }
+
+            tracing::info!(
+                "Secret {name} was rotated, reloading {} {}",
+                component.kind(),
+                component.name()
+            );
+            if !reload(rt, component).await {
+                // Try again with the next check.
+                fingerprints.insert(name.clone(), *previous);
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Returns the loaded components of the app, with the name of each secret they read.
+async fn secret_usages(rt: &Runtime) -> Vec<(String, Component)> {
+    let app = rt.app.read().await;
+    let Some(app) = app.as_ref() else {
+        return vec![];
+    };
+    let mut usages = vec![];
+
+    for ds in app
+        .datasets
+        .iter()
+        .filter_map(|ds| Dataset::try_from(ds.clone()).ok())
+        .filter(|ds| rt.df.table_exists(ds.name.clone()))
+    {
+        if let Some(acceleration) = ds.acceleration.as_ref().filter(|a| a.enabled) {
+            usages.push((
+                ds.scoped_secret_name(&acceleration.engine_secret_name()),
+                Component::Dataset(ds.clone()),
+            ));
+        }
+        usages.push((ds.secret_name(), Component::Dataset(ds)));
+    }
+
+    let models = rt.models.read().await;
+    for m in app.models.iter().filter(|m| models.contains_key(&m.name)) {
+        let secret_name = m
+            .secret
+            .clone()
+            .unwrap_or_else(|| model_source(&m.from).to_string());
+        usages.push((secret_name, Component::Model(m.clone())));
+    }
+    drop(models);
+
+    let llms = rt.llms.read().await;
+    for llm in app.llms.iter().filter(|llm| llms.contains_key(&llm.name)) {
+        if let Some(secret_name) = model::llm_secret_name(llm) {
+            usages.push((secret_name, Component::Llm(llm.clone())));
+        }
+    }
+    drop(llms);
+
+    let embeds = rt.embeds.read().await;
+    for embed in app
+        .embeddings
+        .iter()
+        .filter(|e| embeds.contains_key(&e.name))
+    {
+        if let Some(secret_name) = model::embedding_secret_name(embed) {
+            usages.push((secret_name, Component::Embedding(embed.clone())));
+        }
+    }
+
+    usages
+}
+
+/// Reloads `component` with its current secrets, returning `false` if it couldn't be reloaded.
+async fn reload(rt: &Runtime, component: &Component) -> bool {
+    match component {
+        Component::Dataset(ds) => {
+            // Check the new secret before replacing a dataset that can still serve queries.
+            let connector = Runtime::get_dataconnector_from_source(
+                &ds.source(),
+                &ds.secret_name(),
+                &*rt.secrets_provider.read().await,
+                Arc::new(ds.params.clone()),
+            )
+            .await;
+            let can_connect = match connector {
+                Ok(connector) => connector.read_provider(ds).await.is_ok(),
+                Err(_) => false,
+            };
+            if !can_connect {
+                tracing::warn!(
+                    "Unable to connect dataset {} with the rotated secret, keeping the current connection",
+                    ds.name
+                );
+                return false;
+            }
+
+            rt.update_dataset(ds).await.is_ok()
+        }
+        Component::Model(m) => {
+            rt.update_model(m).await;
+            true
+        }
+        Component::Llm(llm) => {
+            rt.remove_llm(llm).await;
+            rt.load_llm(llm).await;
+            true
+        }
+        Component::Embedding(embed) => {
+            rt.remove_embedding(embed).await;
+            rt.load_embedding(embed).await;
+            true
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Drains the runtime before it exits, for `runtime.shutdown`.
+//!
+//! Once draining, new queries and refreshes are rejected and `/health/ready` reports the runtime as not ready, so
+//! load balancers stop routing to it, while the queries and refreshes in flight run until they finish or the drain
+//! period ends.
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use tokio::sync::watch;
+
+pub(crate) const DEFAULT_DRAIN_PERIOD: Duration = Duration::from_secs(25);
+
+/// How often the work in flight is checked while draining.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Running,
+    Draining,
+    /// The work in flight has finished or was abandoned, and the buffered data is being flushed.
+    Drained,
+}
+
+static PHASE: Lazy<watch::Sender<Phase>> = Lazy::new(|| watch::channel(Phase::Running).0);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the runtime is shutting down, and rejects new queries and refreshes.
+#[must_use]
+pub fn is_draining() -> bool {
+    *PHASE.borrow() != Phase::Running
+}
+
+/// Waits until the runtime has drained, to flush what was buffered.
+pub async fn drained() {
+    let mut phase = PHASE.subscribe();
+    // The sender lives in a static, so it is never dropped.
+    let _ = phase.wait_for(|phase| *phase == Phase::Drained).await;
+}
+
+/// A query or refresh in flight, which the runtime waits for while draining until it is dropped.
+#[must_use]
+pub struct InFlight(());
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+pub fn track() -> InFlight {
+    IN_FLIGHT.fetch_add(1, Ordering::AcqRel);
+    InFlight(())
+}
+
+/// Stops accepting new work, and waits up to `drain_period` for the work in flight to finish.
+pub(crate) async fn drain(drain_period: Duration) {
+    PHASE.send_replace(Phase::Draining);
+
+    let in_flight = IN_FLIGHT.load(Ordering::Acquire);
+    if in_flight > 0 {
+        tracing::info!(
+            "Waiting up to {}s for {in_flight} queries and refreshes in flight",
+            drain_period.as_secs()
+        );
+    }
+
+    let finished = tokio::time::timeout(drain_period, async {
+        while IN_FLIGHT.load(Ordering::Acquire) > 0 {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+    .await;
+    if finished.is_err() {
+        tracing::warn!(
+            "Shutting down with {} queries and refreshes still in flight after {}s",
+            IN_FLIGHT.load(Ordering::Acquire),
+            drain_period.as_secs()
+        );
+    }
+
+    PHASE.send_replace(Phase::Drained);
+}
@@ -14,7 +14,6 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,6 +23,7 @@ use arrow::record_batch::RecordBatch;
 use arrow_tools::record_batch::{self, try_cast_to};
 use chrono::Utc;
 use datafusion::sql::TableReference;
+use metrics_exporter_prometheus::PrometheusHandle;
 use snafu::prelude::*;
 use tokio::spawn;
 
@@ -44,9 +44,6 @@ pub enum Error {
     #[snafu(display("Error casting record batch: {source}",))]
     UnableToCastRecordBatch { source: record_batch::Error },
 
-    #[snafu(display("Error querying prometheus metrics: {source}"))]
-    FailedToQueryPrometheusMetrics { source: reqwest::Error },
-
     #[snafu(display("Error parsing prometheus metrics: {source}"))]
     UnableToParsePrometheusMetrics { source: std::io::Error },
 
@@ -61,15 +58,15 @@ pub enum Error {
 }
 
 pub struct MetricsRecorder {
-    socket_addr: Arc<SocketAddr>,
+    handle: PrometheusHandle,
     remote_schema: Arc<Option<Arc<Schema>>>,
 }
 
 impl MetricsRecorder {
     #[must_use]
-    pub fn new(socket_addr: SocketAddr) -> Self {
+    pub fn new(handle: PrometheusHandle) -> Self {
         Self {
-            socket_addr: Arc::new(socket_addr),
+            handle,
             remote_schema: Arc::new(None),
         }
     }
@@ -107,17 +104,12 @@ impl MetricsRecorder {
     }
 
     async fn tick(
-        socket_addr: &SocketAddr,
+        handle: &PrometheusHandle,
         instance_name: String,
         datafusion: &Arc<DataFusion>,
         remote_schema: &Arc<Option<Arc<Schema>>>,
     ) -> Result<(), Error> {
-        let body = reqwest::get(format!("http://{socket_addr}/metrics"))
-            .await
-            .context(FailedToQueryPrometheusMetricsSnafu)?
-            .text()
-            .await
-            .context(FailedToQueryPrometheusMetricsSnafu)?;
+        let body = handle.render();
 
         let lines = body.lines().map(|s| Ok(s.to_owned()));
         let scrape =
@@ -194,14 +186,14 @@ impl MetricsRecorder {
     }
 
     pub fn start(&self, instance_name: String, datafusion: &Arc<DataFusion>) {
-        let addr = Arc::clone(&self.socket_addr);
+        let handle = self.handle.clone();
         let df = Arc::clone(datafusion);
         let schema = Arc::clone(&self.remote_schema);
 
         spawn(async move {
             loop {
                 if let Err(err) =
-                    MetricsRecorder::tick(&addr, instance_name.clone(), &df, &schema).await
+                    MetricsRecorder::tick(&handle, instance_name.clone(), &df, &schema).await
                 {
                     tracing::error!("{err}");
                 }
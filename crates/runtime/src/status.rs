@@ -14,10 +14,16 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::fmt::Display;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{PoisonError, RwLock},
+};
 
+use chrono::{DateTime, Utc};
 use datafusion::sql::TableReference;
 use metrics::gauge;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
 #[allow(clippy::module_name_repetitions)]
@@ -28,6 +34,8 @@ pub enum ComponentStatus {
     Disabled = 3,
     Error = 4,
     Refreshing = 5,
+    /// Loaded, but violating the expectations of the dataset, such as its minimum rows or maximum staleness.
+    Degraded = 6,
 }
 
 impl Display for ComponentStatus {
@@ -38,26 +46,220 @@ impl Display for ComponentStatus {
             ComponentStatus::Disabled => write!(f, "Disabled"),
             ComponentStatus::Error => write!(f, "Error"),
             ComponentStatus::Refreshing => write!(f, "Refreshing"),
+            ComponentStatus::Degraded => write!(f, "Degraded"),
         }
     }
 }
 
+/// The last known state of a component, as reported by `GET /v1/status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComponentState {
+    pub status: ComponentStatus,
+
+    /// When the data of an accelerated dataset was last refreshed successfully.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_refresh: Option<DateTime<Utc>>,
+
+    /// The error of the last failed load or refresh, cleared once the component is ready again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// The expectations of a dataset it violated when last checked.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<String>,
+
+    /// The columns of a dataset with `detect_pii` that look like they hold personal data, with the kind of data.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pii_columns: BTreeMap<String, String>,
+}
+
+impl ComponentState {
+    fn new(status: ComponentStatus) -> Self {
+        Self {
+            status,
+            last_refresh: None,
+            error: None,
+            violations: vec![],
+            pii_columns: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the component has been loaded at least once, even if a later refresh failed.
+    #[must_use]
+    pub fn is_loaded(&self) -> bool {
+        matches!(
+            self.status,
+            ComponentStatus::Ready | ComponentStatus::Degraded
+        ) || self.last_refresh.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ComponentStates {
+    pub datasets: BTreeMap<String, ComponentState>,
+    pub models: BTreeMap<String, ComponentState>,
+    pub llms: BTreeMap<String, ComponentState>,
+    pub embeddings: BTreeMap<String, ComponentState>,
+    pub extensions: BTreeMap<String, ComponentState>,
+}
+
+static COMPONENT_STATES: Lazy<RwLock<ComponentStates>> =
+    Lazy::new(|| RwLock::new(ComponentStates::default()));
+
+fn set_state(
+    components: fn(&mut ComponentStates) -> &mut BTreeMap<String, ComponentState>,
+    name: String,
+    status: ComponentStatus,
+    error: Option<String>,
+) -> ComponentStatus {
+    let mut states = COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    let state = components(&mut states)
+        .entry(name)
+        .or_insert_with(|| ComponentState::new(status));
+    if error.is_some() || status == ComponentStatus::Ready {
+        state.error = error;
+    }
+    // A dataset stays degraded until its expectations are met again.
+    state.status = if status == ComponentStatus::Ready && !state.violations.is_empty() {
+        ComponentStatus::Degraded
+    } else {
+        status
+    };
+    state.status
+}
+
+/// A snapshot of the state of all the components of the runtime.
+#[must_use]
+pub fn component_states() -> ComponentStates {
+    COMPONENT_STATES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// The state of a dataset, if the runtime has loaded it.
+#[must_use]
+pub fn dataset_state(dataset: &TableReference) -> Option<ComponentState> {
+    COMPONENT_STATES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .datasets
+        .get(&dataset.to_string())
+        .cloned()
+}
+
 pub fn update_dataset(dataset: &TableReference, status: ComponentStatus) {
     let ds_name = dataset.to_string();
+    let status = set_state(|s| &mut s.datasets, ds_name.clone(), status, None);
     gauge!("dataset/status", "dataset" => ds_name).set(f64::from(status as u32));
 }
 
+/// Records the expectations the dataset violates, marking a loaded dataset as degraded while it violates any.
+#[allow(clippy::cast_precision_loss)]
+pub fn update_dataset_violations(dataset: &TableReference, violations: Vec<String>) {
+    let ds_name = dataset.to_string();
+    let mut states = COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    let Some(state) = states.datasets.get_mut(&ds_name) else {
+        return;
+    };
+
+    gauge!("dataset/expectation_violations", "dataset" => ds_name.clone())
+        .set(violations.len() as f64);
+    state.status = match state.status {
+        ComponentStatus::Ready if !violations.is_empty() => ComponentStatus::Degraded,
+        ComponentStatus::Degraded if violations.is_empty() => ComponentStatus::Ready,
+        status => status,
+    };
+    state.violations = violations;
+    gauge!("dataset/status", "dataset" => ds_name).set(f64::from(state.status as u32));
+}
+
+/// Records the columns of the dataset suspected of holding personal data.
+pub fn update_dataset_pii_columns(dataset: &TableReference, pii_columns: BTreeMap<String, String>) {
+    let mut states = COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(state) = states.datasets.get_mut(&dataset.to_string()) {
+        state.pii_columns = pii_columns;
+    }
+}
+
+/// Marks the dataset as failed, keeping `error` as its last error.
+pub fn update_dataset_error(dataset: &TableReference, error: String) {
+    let ds_name = dataset.to_string();
+    set_state(
+        |s| &mut s.datasets,
+        ds_name.clone(),
+        ComponentStatus::Error,
+        Some(error),
+    );
+    gauge!("dataset/status", "dataset" => ds_name).set(f64::from(ComponentStatus::Error as u32));
+}
+
+/// Records that the acceleration of the dataset was refreshed successfully.
+pub fn mark_dataset_refreshed(dataset: &TableReference) {
+    let mut states = COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(state) = states.datasets.get_mut(&dataset.to_string()) {
+        state.last_refresh = Some(Utc::now());
+    }
+}
+
+pub fn remove_dataset(dataset: &TableReference) {
+    COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .datasets
+        .remove(&dataset.to_string());
+}
+
 pub fn update_model(model_name: &str, status: ComponentStatus) {
     let model_name = model_name.to_string();
+    set_state(|s| &mut s.models, model_name.clone(), status, None);
     gauge!("model/status", "model" => model_name).set(f64::from(status as u32));
 }
 
+pub fn remove_model(model_name: &str) {
+    COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .models
+        .remove(model_name);
+}
+
 pub fn update_llm(model_name: &str, status: ComponentStatus) {
     let model_name = model_name.to_string();
+    set_state(|s| &mut s.llms, model_name.clone(), status, None);
     gauge!("llm/status", "model" => model_name).set(f64::from(status as u32));
 }
 
+pub fn remove_llm(model_name: &str) {
+    COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .llms
+        .remove(model_name);
+}
+
 pub fn update_embedding(model_name: &str, status: ComponentStatus) {
     let model_name = model_name.to_string();
+    set_state(|s| &mut s.embeddings, model_name.clone(), status, None);
     gauge!("embedding/status", "model" => model_name).set(f64::from(status as u32));
 }
+
+pub fn remove_embedding(model_name: &str) {
+    COMPONENT_STATES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .embeddings
+        .remove(model_name);
+}
+
+pub fn update_extension(name: &str, status: ComponentStatus, error: Option<String>) {
+    set_state(|s| &mut s.extensions, name.to_string(), status, error);
+}
@@ -14,11 +14,35 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::HashMap;
+
+use opentelemetry::propagation::TextMapPropagator;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
 use crate::component::dataset::{
-    acceleration::{Acceleration, Mode, RefreshMode, ZeroResultsAction},
+    acceleration::{Acceleration, Mode, RefreshMode, UnavailableAction, ZeroResultsAction},
     Dataset,
 };
 
+const TRACE_CONTEXT_HEADERS: [&str; 2] = ["traceparent", "tracestate"];
+
+/// Parents `span` to the trace a caller propagated with the W3C `traceparent` and `tracestate` headers.
+pub(crate) fn set_remote_parent<'a>(span: &Span, header: impl Fn(&str) -> Option<&'a str>) {
+    let carrier: HashMap<String, String> = TRACE_CONTEXT_HEADERS
+        .iter()
+        .filter_map(|name| Some(((*name).to_string(), header(name)?.to_string())))
+        .collect();
+
+    if carrier.is_empty() {
+        return;
+    }
+
+    let parent =
+        opentelemetry::global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+    span.set_parent(parent);
+}
+
 // Format: Dataset taxi_trips registered (s3://spiceai-demo-datasets/taxi_trips/2024/), acceleration (duckdb), results cache enabled.
 pub fn dataset_registered_trace(ds: &Dataset, results_cache_enabled: bool) -> String {
     let mut info = format!("Dataset {} registered ({})", &ds.name, &ds.from);
@@ -31,6 +55,13 @@ pub fn dataset_registered_trace(ds: &Dataset, results_cache_enabled: bool) -> St
         }
     }
 
+    if let Some(cache) = ds.cache.as_ref().filter(|cache| cache.enabled) {
+        info.push_str(&format!(
+            ", source scans cached for {}",
+            cache.ttl.as_deref().unwrap_or("1m")
+        ));
+    }
+
     if results_cache_enabled {
         info.push_str(", results cache enabled");
     }
@@ -51,7 +82,12 @@ fn dataset_acceleration_info(acceleration: &Acceleration) -> String {
         info.push_str(", append");
     }
 
-    if let Some(refresh_interval) = &acceleration.refresh_check_interval {
+    if let Some(refresh_cron) = &acceleration.refresh_cron {
+        info.push_str(&format!(", refresh at {refresh_cron}"));
+        if let Some(timezone) = &acceleration.refresh_cron_timezone {
+            info.push_str(&format!(" {timezone}"));
+        }
+    } else if let Some(refresh_interval) = &acceleration.refresh_check_interval {
         info.push_str(&format!(", {refresh_interval} refresh"));
     }
     if let Some(retention_check_interval) = &acceleration.retention_check_interval {
@@ -59,9 +95,17 @@ fn dataset_acceleration_info(acceleration: &Acceleration) -> String {
             info.push_str(&format!(", {retention_check_interval} retention"));
         }
     }
+    if let Some(compaction_interval) = &acceleration.compaction_interval {
+        info.push_str(&format!(", {compaction_interval} compaction"));
+    }
     if acceleration.on_zero_results == ZeroResultsAction::UseSource {
         info.push_str(", fallback on source on empty result");
     }
+    match acceleration.on_unavailable {
+        UnavailableAction::ServeStale => {}
+        UnavailableAction::UseSource => info.push_str(", fallback on source when unavailable"),
+        UnavailableAction::Error => info.push_str(", error when unavailable"),
+    }
     info
 }
 
@@ -0,0 +1,392 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Reads the columns of a dataset as the types of its `type_mappings` instead of the types its data connector maps
+//! them to, i.e. Postgres `NUMERIC` as `double` or a MySQL `TINYINT(1)` as `boolean`, by casting the batches the
+//! connector scans.
+
+use std::{any::Any, collections::HashSet, sync::Arc};
+
+use arrow::{
+    compute::can_cast_types,
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use datafusion::{
+    datasource::{TableProvider, TableType},
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionState,
+    logical_expr::{utils::expr_to_columns, Expr, TableProviderFilterPushDown},
+    physical_plan::ExecutionPlan,
+};
+use spicepod::component::dataset::type_mapping::TypeMapping;
+
+use crate::{
+    column_types::parse_column_type,
+    component::dataset::Dataset,
+    dataconnector::{AnyErrorResult, DataConnector, DataConnectorError, DataConnectorResult},
+    execution_plan::schema_cast::SchemaCastScanExec,
+};
+
+/// Wraps the providers of a [`DataConnector`] in a [`TypeMappedTable`] of the type mappings of the dataset.
+pub struct TypeMappedConnector {
+    inner_connector: Arc<dyn DataConnector>,
+}
+
+impl TypeMappedConnector {
+    pub fn new(inner_connector: Arc<dyn DataConnector>) -> Self {
+        Self { inner_connector }
+    }
+
+    fn wrap(
+        inner_table_provider: Arc<dyn TableProvider>,
+        dataset: &Dataset,
+    ) -> DataConnectorResult<Arc<dyn TableProvider>> {
+        if dataset.type_mappings.is_empty() {
+            return Ok(inner_table_provider);
+        }
+
+        TypeMappedTable::try_new(inner_table_provider, &dataset.type_mappings)
+            .map(|table| Arc::new(table) as Arc<dyn TableProvider>)
+            .map_err(|e| DataConnectorError::InvalidConfiguration {
+                dataconnector: dataset.source(),
+                message: format!("Unable to map the column types of {}.", dataset.name),
+                source: Box::new(e),
+            })
+    }
+}
+
+#[async_trait]
+impl DataConnector for TypeMappedConnector {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn read_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> DataConnectorResult<Arc<dyn TableProvider>> {
+        Self::wrap(self.inner_connector.read_provider(dataset).await?, dataset)
+    }
+
+    // Writes are in the types of the source, so the mappings only apply to reads.
+    async fn read_write_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<DataConnectorResult<Arc<dyn TableProvider>>> {
+        self.inner_connector.read_write_provider(dataset).await
+    }
+
+    async fn stream_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<AnyErrorResult<Arc<dyn TableProvider>>> {
+        match self.inner_connector.stream_provider(dataset).await {
+            Some(Ok(inner)) => Some(Self::wrap(inner, dataset).map_err(Into::into)),
+            other => other,
+        }
+    }
+
+    async fn metadata_provider(
+        &self,
+        dataset: &Dataset,
+    ) -> Option<DataConnectorResult<Arc<dyn TableProvider>>> {
+        self.inner_connector.metadata_provider(dataset).await
+    }
+}
+
+/// Why a type mapping can't apply to any column, if it can't.
+pub(crate) fn invalid_mapping(mapping: &TypeMapping) -> Option<String> {
+    match (&mapping.from, &mapping.column) {
+        (None, None) => return Some("either from or column is required".to_string()),
+        (Some(_), Some(_)) => return Some("only one of from and column can be set".to_string()),
+        (Some(from), None) if !is_type_class(from) && parse_column_type(from).is_none() => {
+            return Some(format!("unsupported type {from}"));
+        }
+        _ => {}
+    }
+
+    let Some(to) = parse_column_type(&mapping.to) else {
+        return Some(format!("unsupported type {}", mapping.to));
+    };
+    match (&mapping.timezone, to) {
+        (Some(timezone), DataType::Timestamp(..)) => timezone
+            .parse::<Tz>()
+            .err()
+            .map(|_| format!("unknown timezone {timezone}")),
+        (Some(_), _) => Some("a timezone only applies to timestamp types".to_string()),
+        (None, _) => None,
+    }
+}
+
+/// The type classes `from` can name, which match every precision, scale, unit or timezone of their types.
+fn is_type_class(from: &str) -> bool {
+    matches!(
+        from.trim().to_ascii_lowercase().as_str(),
+        "decimal" | "numeric" | "timestamp" | "timestamptz"
+    )
+}
+
+fn matches_type(from: &str, data_type: &DataType) -> bool {
+    match from.trim().to_ascii_lowercase().as_str() {
+        "decimal" | "numeric" => {
+            matches!(
+                data_type,
+                DataType::Decimal128(..) | DataType::Decimal256(..)
+            )
+        }
+        "timestamp" => matches!(data_type, DataType::Timestamp(..)),
+        "timestamptz" => matches!(data_type, DataType::Timestamp(_, Some(_))),
+        "string" | "text" | "varchar" | "utf8" => {
+            matches!(data_type, DataType::Utf8 | DataType::LargeUtf8)
+        }
+        from => parse_column_type(from).is_some_and(|from| &from == data_type),
+    }
+}
+
+/// The type `field` is read as with `mapping`. Timestamps keep the unit of the source.
+fn mapped_type(field: &Field, mapping: &TypeMapping) -> DataFusionResult<DataType> {
+    if let Some(message) = invalid_mapping(mapping) {
+        return Err(DataFusionError::Plan(message));
+    }
+    let to = parse_column_type(&mapping.to)
+        .ok_or_else(|| DataFusionError::Plan(format!("unsupported type {}", mapping.to)))?;
+    let to = match to {
+        DataType::Timestamp(unit, timezone) => {
+            let unit = match field.data_type() {
+                DataType::Timestamp(unit, _) => *unit,
+                _ => unit,
+            };
+            DataType::Timestamp(
+                unit,
+                mapping.timezone.as_deref().map(Into::into).or(timezone),
+            )
+        }
+        to => to,
+    };
+
+    if !can_cast_types(field.data_type(), &to) {
+        return Err(DataFusionError::Plan(format!(
+            "The column {} of type {} can't be read as {to}",
+            field.name(),
+            field.data_type()
+        )));
+    }
+    Ok(to)
+}
+
+/// The schema of `schema` with the types of `mappings`, and the names of the columns they changed. A column is
+/// mapped by the first mapping of it, or else by the first mapping of its type.
+pub fn map_schema(
+    schema: &Schema,
+    mappings: &[TypeMapping],
+) -> DataFusionResult<(Schema, HashSet<String>)> {
+    for column in mappings
+        .iter()
+        .filter_map(|mapping| mapping.column.as_ref())
+    {
+        if schema.field_with_name(column).is_err() {
+            return Err(DataFusionError::Plan(format!(
+                "The column {column} was not found in the source"
+            )));
+        }
+    }
+
+    let mut mapped = HashSet::new();
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let mapping = mappings
+                .iter()
+                .find(|mapping| mapping.column.as_ref() == Some(field.name()))
+                .or_else(|| {
+                    mappings.iter().find(|mapping| {
+                        mapping
+                            .from
+                            .as_ref()
+                            .is_some_and(|from| matches_type(from, field.data_type()))
+                    })
+                });
+            let Some(mapping) = mapping else {
+                return Ok(Arc::clone(field));
+            };
+
+            let to = mapped_type(field, mapping)?;
+            if &to == field.data_type() {
+                return Ok(Arc::clone(field));
+            }
+            mapped.insert(field.name().clone());
+            Ok(Arc::new(field.as_ref().clone().with_data_type(to)))
+        })
+        .collect::<DataFusionResult<Vec<_>>>()?;
+
+    Ok((
+        Schema::new_with_metadata(fields, schema.metadata().clone()),
+        mapped,
+    ))
+}
+
+/// A table that reads the columns of another table as other types.
+pub struct TypeMappedTable {
+    inner: Arc<dyn TableProvider>,
+    schema: SchemaRef,
+    /// The columns whose types differ from the types of `inner`.
+    mapped: HashSet<String>,
+}
+
+impl TypeMappedTable {
+    pub fn try_new(
+        inner: Arc<dyn TableProvider>,
+        mappings: &[TypeMapping],
+    ) -> DataFusionResult<Self> {
+        let (schema, mapped) = map_schema(&inner.schema(), mappings)?;
+        Ok(Self {
+            inner,
+            schema: Arc::new(schema),
+            mapped,
+        })
+    }
+
+    /// Whether `filter` compares a mapped column, whose values in the source are of another type.
+    fn references_mapped(&self, filter: &Expr) -> bool {
+        let mut columns = HashSet::new();
+        expr_to_columns(filter, &mut columns).is_err()
+            || columns
+                .iter()
+                .any(|column| self.mapped.contains(&column.name))
+    }
+}
+
+#[async_trait]
+impl TableProvider for TypeMappedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        let pushable = filters
+            .iter()
+            .copied()
+            .filter(|filter| !self.references_mapped(filter))
+            .collect::<Vec<_>>();
+        let mut inner = self.inner.supports_filters_pushdown(&pushable)?.into_iter();
+
+        Ok(filters
+            .iter()
+            .map(|filter| {
+                if self.references_mapped(filter) {
+                    TableProviderFilterPushDown::Unsupported
+                } else {
+                    inner
+                        .next()
+                        .unwrap_or(TableProviderFilterPushDown::Unsupported)
+                }
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let filters = filters
+            .iter()
+            .filter(|filter| !self.references_mapped(filter))
+            .cloned()
+            .collect::<Vec<_>>();
+        let schema = match projection {
+            Some(projection) => Arc::new(self.schema.project(projection)?),
+            None => Arc::clone(&self.schema),
+        };
+
+        let plan = self.inner.scan(state, projection, &filters, limit).await?;
+        Ok(Arc::new(SchemaCastScanExec::new(plan, schema)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::TimeUnit;
+
+    use super::*;
+
+    fn mapping(from: Option<&str>, column: Option<&str>, to: &str) -> TypeMapping {
+        TypeMapping {
+            from: from.map(ToString::to_string),
+            column: column.map(ToString::to_string),
+            to: to.to_string(),
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn maps_column_types() {
+        let schema = Schema::new(vec![
+            Field::new("price", DataType::Decimal128(38, 9), true),
+            Field::new("active", DataType::Int8, true),
+            Field::new("rank", DataType::Int8, true),
+            Field::new(
+                "created_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+        ]);
+        let mut timestamps = mapping(Some("timestamp"), None, "timestamptz");
+        timestamps.timezone = Some("America/New_York".to_string());
+        let mappings = vec![
+            mapping(None, Some("active"), "boolean"),
+            mapping(Some("decimal"), None, "double"),
+            mapping(Some("int8"), None, "int8"),
+            timestamps,
+        ];
+
+        let (mapped_schema, mapped) = map_schema(&schema, &mappings).expect("mapped schema");
+        assert_eq!(
+            mapped_schema
+                .fields()
+                .iter()
+                .map(|f| f.data_type().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                DataType::Float64,
+                DataType::Boolean,
+                DataType::Int8,
+                DataType::Timestamp(TimeUnit::Microsecond, Some("America/New_York".into())),
+            ]
+        );
+        assert_eq!(mapped.len(), 3);
+        assert!(!mapped.contains("rank"));
+
+        assert!(map_schema(&schema, &[mapping(None, Some("missing"), "boolean")]).is_err());
+        assert!(invalid_mapping(&mapping(Some("decimal"), Some("price"), "double")).is_some());
+    }
+}
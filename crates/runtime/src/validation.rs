@@ -0,0 +1,811 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks the components of an app that the spicepod schema can't, such as durations, acceleration
+//! settings and secrets, without loading them.
+
+use std::collections::HashSet;
+
+use app::App;
+use byte_unit::Byte;
+use datafusion::sql::TableReference;
+use secrets::SecretsProvider;
+use spicepod::component::alert::{AlertNotifier, AlertNotifierKind};
+use spicepod::component::dataset::cache::Cache;
+use spicepod::component::dataset::quality::QualityCheck;
+use spicepod::component::dataset::{
+    anomaly_detection::{AnomalyDetection, AnomalyMethod},
+    expectations::Expectations,
+};
+use spicepod::component::deployment::DeploymentMode;
+pub use spicepod::validation::Diagnostic;
+
+use crate::accelerated_table::schedule::CronSchedule;
+use crate::component::dataset::acceleration::{Acceleration, Engine, Mode, RefreshMode};
+use crate::component::dataset::{self, Dataset};
+use crate::component::view::View;
+use crate::jobs::Schedule;
+use crate::{
+    column_types, dataaccelerator, dataconnector, get_view_dependent_tables, type_mapping,
+};
+
+/// Validates the datasets, views, jobs, alerts and tests of `app`, using `secrets_provider` to check the secrets they reference.
+pub async fn validate_app(app: &App, secrets_provider: &SecretsProvider) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut dataset_names = vec![];
+
+    if let Some(refresh_interval) = &app.secrets.refresh_interval {
+        if let Err(e) = fundu::parse_duration(refresh_interval) {
+            diagnostics.push(Diagnostic::new(
+                "secrets.refresh_interval",
+                format!("invalid duration {refresh_interval}: {e}"),
+            ));
+        }
+    }
+
+    if let Some(retention) = &app.runtime.inference_log.retention {
+        if let Err(e) = fundu::parse_duration(retention) {
+            diagnostics.push(Diagnostic::new(
+                "runtime.inference_log.retention",
+                format!("invalid duration {retention}: {e}"),
+            ));
+        }
+    }
+
+    let otlp_metrics = &app.runtime.otlp_metrics;
+    if otlp_metrics.enabled && otlp_metrics.endpoint.is_none() {
+        diagnostics.push(Diagnostic::new(
+            "runtime.otlp_metrics.endpoint",
+            "pushing metrics requires the endpoint of an OTLP/gRPC collector",
+        ));
+    }
+    if let Some(interval) = &otlp_metrics.interval {
+        if let Err(e) = fundu::parse_duration(interval) {
+            diagnostics.push(Diagnostic::new(
+                "runtime.otlp_metrics.interval",
+                format!("invalid duration {interval}: {e}"),
+            ));
+        }
+    }
+
+    if let Some(drain_period) = &app.runtime.shutdown.drain_period {
+        if let Err(e) = fundu::parse_duration(drain_period) {
+            diagnostics.push(Diagnostic::new(
+                "runtime.shutdown.drain_period",
+                format!("invalid duration {drain_period}: {e}"),
+            ));
+        }
+    }
+
+    let cluster = &app.runtime.cluster;
+    if cluster.enabled {
+        if let Err(e) = crate::cluster::Cluster::new(cluster) {
+            diagnostics.push(Diagnostic::new("runtime.cluster", e.to_string()));
+        }
+        if let Some(interval) = &cluster.health_check_interval {
+            if let Err(e) = fundu::parse_duration(interval) {
+                diagnostics.push(Diagnostic::new(
+                    "runtime.cluster.health_check_interval",
+                    format!("invalid duration {interval}: {e}"),
+                ));
+            }
+        }
+        let mut names = HashSet::new();
+        for (index, member) in cluster.members.iter().enumerate() {
+            if !names.insert(&member.name) {
+                diagnostics.push(Diagnostic::new(
+                    format!("runtime.cluster.members[{index}].name"),
+                    format!(
+                        "{} is the name of another member of the cluster",
+                        member.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    for dataset in app.runtime.readiness.datasets.iter().flatten() {
+        if !app.datasets.iter().any(|ds| &ds.name == dataset) {
+            diagnostics.push(Diagnostic::new(
+                "runtime.readiness.datasets",
+                format!("dataset {dataset} is not a dataset of the spicepod, so the runtime is never ready"),
+            ));
+        }
+    }
+
+    for (index, namespace) in app.runtime.namespaces.iter().enumerate() {
+        if let Some(max_accelerated_size) = &namespace.quotas.max_accelerated_size {
+            if let Err(e) = Byte::parse_str(max_accelerated_size, true) {
+                diagnostics.push(Diagnostic::new(
+                    format!("runtime.namespaces[{index}].quotas.max_accelerated_size"),
+                    format!("invalid size {max_accelerated_size}: {e}"),
+                ));
+            }
+        }
+    }
+
+    for (index, limit) in app.runtime.rate_limits.iter().enumerate() {
+        let path = format!("runtime.rate_limits[{index}]");
+        if limit.requests_per_minute.is_none() && limit.tokens_per_day.is_none() {
+            diagnostics.push(Diagnostic::new(
+                path.clone(),
+                "a rate limit needs requests_per_minute or tokens_per_day",
+            ));
+        }
+        if let Some(model) = &limit.model {
+            if !app.llms.iter().any(|llm| &llm.name == model)
+                && !app
+                    .embeddings
+                    .iter()
+                    .any(|embedding| &embedding.name == model)
+            {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.model"),
+                    format!("model {model} is not an LLM or embedding model of the spicepod"),
+                ));
+            }
+        }
+    }
+
+    for (index, spicepod_ds) in app.datasets.iter().enumerate() {
+        let path = format!("datasets[{index}]");
+        let ds = match Dataset::try_from(spicepod_ds.clone()) {
+            Ok(ds) => ds,
+            Err(e) => {
+                diagnostics.push(Diagnostic::new(path, e.to_string()));
+                continue;
+            }
+        };
+        dataset_names.push(ds.name.clone());
+
+        let source = ds.source();
+        if !dataconnector::is_registered(&source).await {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.from"),
+                format!("unknown data connector {source}"),
+            ));
+        }
+
+        if let Some(secret) = &ds.secret {
+            check_secret(
+                secrets_provider,
+                &ds.scoped_secret_name(secret),
+                format!("{path}.secret"),
+                &mut diagnostics,
+            )
+            .await;
+        }
+
+        let replicated = ds.replication.as_ref().is_some_and(|r| r.enabled);
+        if ds.mode() == dataset::Mode::ReadWrite && !replicated {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.mode"),
+                "read_write requires replication.enabled: true",
+            ));
+        }
+
+        if ds.partition_by().is_some() && !app.runtime.cluster.enabled {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.acceleration.partition_by"),
+                "partition_by requires runtime.cluster.enabled: true",
+            ));
+        }
+
+        if !ds.columns.is_empty() {
+            validate_columns(&ds, &format!("{path}.columns"), &mut diagnostics);
+        }
+
+        if !ds.column_types.is_empty() {
+            validate_column_types(&ds, &format!("{path}.column_types"), &mut diagnostics);
+        }
+
+        for (index, mapping) in ds.type_mappings.iter().enumerate() {
+            if let Some(message) = type_mapping::invalid_mapping(mapping) {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.type_mappings[{index}]"),
+                    message,
+                ));
+            }
+        }
+
+        if let Some(expectations) = &spicepod_ds.expectations {
+            validate_expectations(
+                &ds,
+                expectations,
+                &format!("{path}.expectations"),
+                &mut diagnostics,
+            );
+        }
+
+        if let Some(anomaly_detection) = &spicepod_ds.anomaly_detection {
+            validate_anomaly_detection(
+                &ds,
+                anomaly_detection,
+                &format!("{path}.anomaly_detection"),
+                &mut diagnostics,
+            );
+        }
+
+        if let Some(cache) = ds.cache.as_ref().filter(|cache| cache.enabled) {
+            validate_cache(&ds, cache, &format!("{path}.cache"), &mut diagnostics);
+        }
+
+        if !ds.quality_checks.is_empty() {
+            validate_quality_checks(
+                &ds,
+                &ds.quality_checks,
+                &format!("{path}.quality_checks"),
+                &mut diagnostics,
+            );
+        }
+
+        if let Some(acceleration) = ds.acceleration.as_ref().filter(|a| a.enabled) {
+            let path = format!("{path}.acceleration");
+            validate_acceleration(&ds, acceleration, &path, &mut diagnostics);
+
+            if let Some(engine_secret) = &acceleration.engine_secret {
+                check_secret(
+                    secrets_provider,
+                    engine_secret,
+                    format!("{path}.engine_secret"),
+                    &mut diagnostics,
+                )
+                .await;
+            }
+
+            if let Some(encryption_secret) = &acceleration.encryption_secret {
+                check_secret(
+                    secrets_provider,
+                    &ds.scoped_secret_name(encryption_secret),
+                    format!("{path}.encryption_secret"),
+                    &mut diagnostics,
+                )
+                .await;
+            }
+        }
+    }
+
+    for (index, model) in app.models.iter().enumerate() {
+        if model.forecast.is_some() && model.datasets.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                format!("models[{index}].forecast"),
+                "a forecasting model requires the dataset it forecasts in datasets",
+            ));
+        }
+        if let Some(interval) = &model.version_check_interval {
+            if let Err(e) = fundu::parse_duration(interval) {
+                diagnostics.push(Diagnostic::new(
+                    format!("models[{index}].version_check_interval"),
+                    format!("invalid duration {interval}: {e}"),
+                ));
+            }
+        }
+        if let Some(secret) = &model.secret {
+            check_secret(
+                secrets_provider,
+                secret,
+                format!("models[{index}].secret"),
+                &mut diagnostics,
+            )
+            .await;
+        }
+    }
+
+    for (index, spicepod_view) in app.views.iter().enumerate() {
+        let path = format!("views[{index}]");
+        let view = match View::try_from(spicepod_view.clone()) {
+            Ok(view) => view,
+            Err(e) => {
+                diagnostics.push(Diagnostic::new(path, e.to_string()));
+                continue;
+            }
+        };
+
+        match get_view_dependent_tables(&view) {
+            Ok(tables) => {
+                for table in tables.iter().filter(|t| !dataset_names.contains(t)) {
+                    diagnostics.push(Diagnostic::new(
+                        format!("{path}.sql"),
+                        format!("dataset {table} is not defined"),
+                    ));
+                }
+            }
+            Err(e) => diagnostics.push(Diagnostic::new(format!("{path}.sql"), e.to_string())),
+        }
+    }
+
+    for (index, job) in app.jobs.iter().enumerate() {
+        let path = format!("jobs[{index}]");
+        if let Err(e) = job.schedule.parse::<Schedule>() {
+            diagnostics.push(Diagnostic::new(format!("{path}.schedule"), e.to_string()));
+        }
+        if let Some(retry_delay) = &job.retry_delay {
+            if let Err(e) = fundu::parse_duration(retry_delay) {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.retry_delay"),
+                    format!("invalid duration {retry_delay}: {e}"),
+                ));
+            }
+        }
+    }
+
+    for (index, alert) in app.alerts.iter().enumerate() {
+        let path = format!("alerts[{index}]");
+        match &alert.schedule {
+            Some(schedule) => {
+                if let Err(e) = schedule.parse::<Schedule>() {
+                    diagnostics.push(Diagnostic::new(format!("{path}.schedule"), e.to_string()));
+                }
+            }
+            None if alert.on_refresh.is_empty() => diagnostics.push(Diagnostic::new(
+                path.clone(),
+                "the alert is never checked, set schedule or on_refresh",
+            )),
+            None => {}
+        }
+
+        for dataset in alert
+            .on_refresh
+            .iter()
+            .filter(|dataset| !dataset_names.contains(&TableReference::from(dataset.as_str())))
+        {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.on_refresh"),
+                format!("dataset {dataset} is not defined"),
+            ));
+        }
+
+        if alert.notify.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.notify"),
+                "the alert has no notifiers",
+            ));
+        }
+        validate_notifiers(&alert.notify, &format!("{path}.notify"), &mut diagnostics);
+    }
+
+    let mut test_names = HashSet::new();
+    for (index, test) in app.tests.iter().enumerate() {
+        if !test_names.insert(test.name.as_str()) {
+            diagnostics.push(Diagnostic::new(
+                format!("tests[{index}].name"),
+                format!("test {} is defined more than once", test.name),
+            ));
+        }
+    }
+
+    let is_model = |name: &str| app.models.iter().any(|m| m.name == name);
+    let is_llm = |name: &str| app.llms.iter().any(|llm| llm.name == name);
+    for (index, deployment) in app.deployments.iter().enumerate() {
+        let path = format!("deployments[{index}]");
+        if is_model(&deployment.name) || is_llm(&deployment.name) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.name"),
+                format!(
+                    "{} is also the name of a model, which the deployment hides",
+                    deployment.name
+                ),
+            ));
+        }
+
+        let (primary, candidate) = (&deployment.primary, &deployment.candidate);
+        let models = (is_model(primary), is_model(candidate));
+        let llms = (is_llm(primary), is_llm(candidate));
+        for (field, name, defined) in [
+            ("primary", primary, models.0 || llms.0),
+            ("candidate", candidate, models.1 || llms.1),
+        ] {
+            if !defined {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.{field}"),
+                    format!("model {name} is not defined"),
+                ));
+            }
+        }
+        if (models.0 && llms.1) || (llms.0 && models.1) {
+            diagnostics.push(Diagnostic::new(
+                path.clone(),
+                "the primary and the candidate must both be models or both be LLMs",
+            ));
+        }
+
+        if deployment.traffic > 100 {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.traffic"),
+                format!("expected a percentage, received {}", deployment.traffic),
+            ));
+        } else if deployment.mode == DeploymentMode::Shadow && deployment.traffic > 0 {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.traffic"),
+                "traffic only applies in split mode, the primary serves every request in shadow mode",
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+/// Reports a secret that a component names explicitly but the secret store doesn't have.
+async fn check_secret(
+    secrets_provider: &SecretsProvider,
+    secret: &str,
+    path: String,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match secrets_provider.get_secret(secret).await {
+        Ok(Some(_)) => {}
+        Ok(None) => diagnostics.push(Diagnostic::new(path, format!("secret {secret} not found"))),
+        Err(e) => diagnostics.push(Diagnostic::new(
+            path,
+            format!("unable to get secret {secret}: {e}"),
+        )),
+    }
+}
+
+fn validate_expectations(
+    ds: &Dataset,
+    expectations: &Expectations,
+    path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if let Some(max_staleness) = &expectations.max_staleness {
+        if let Err(e) = fundu::parse_duration(max_staleness) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.max_staleness"),
+                format!("invalid duration {max_staleness}: {e}"),
+            ));
+        }
+        if !ds.is_accelerated() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.max_staleness"),
+                "max_staleness requires an accelerated dataset, which refreshes",
+            ));
+        }
+    }
+    validate_notifiers(&expectations.notify, &format!("{path}.notify"), diagnostics);
+}
+
+/// Checks that anomaly detection has an accelerated dataset to score after its refreshes, ordered by its time column.
+fn validate_anomaly_detection(
+    ds: &Dataset,
+    anomaly_detection: &AnomalyDetection,
+    path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if !ds.is_accelerated() {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "anomaly_detection requires an accelerated dataset, which is scored after its refreshes",
+        ));
+    }
+    if ds.time_column.is_none() {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "anomaly_detection requires a time_column to order the values by",
+        ));
+    }
+    if anomaly_detection.window == Some(0) {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.window"),
+            "window must be greater than 0",
+        ));
+    }
+    match anomaly_detection.period {
+        Some(0 | 1) => diagnostics.push(Diagnostic::new(
+            format!("{path}.period"),
+            "period must be greater than 1",
+        )),
+        None if anomaly_detection.method == AnomalyMethod::Seasonal => {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.period"),
+                "the seasonal method requires a period",
+            ));
+        }
+        _ => {}
+    }
+    if anomaly_detection.max_anomalies == Some(0) {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.max_anomalies"),
+            "max_anomalies must be greater than 0",
+        ));
+    }
+}
+
+/// Checks that a cached dataset is a read-only federated dataset, with a valid TTL and size.
+fn validate_cache(ds: &Dataset, cache: &Cache, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if ds.is_accelerated() {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "cache can't be enabled with acceleration, which already stores the dataset",
+        ));
+    }
+    if ds.mode() == dataset::Mode::ReadWrite {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "cache can't be enabled for read_write datasets",
+        ));
+    }
+    if let Some(ttl) = &cache.ttl {
+        if let Err(e) = fundu::parse_duration(ttl) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.ttl"),
+                format!("invalid duration {ttl}: {e}"),
+            ));
+        }
+    }
+    if let Some(max_size) = &cache.max_size {
+        if let Err(e) = Byte::parse_str(max_size, true) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.max_size"),
+                format!("invalid size {max_size}: {e}"),
+            ));
+        }
+    }
+}
+
+/// Checks that each quality check checks one thing, of data that is refreshed into an accelerator.
+fn validate_quality_checks(
+    ds: &Dataset,
+    checks: &[QualityCheck],
+    path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if !ds.is_accelerated() {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "quality_checks require an accelerated dataset, which refreshes",
+        ));
+    }
+
+    for (index, check) in checks.iter().enumerate() {
+        let path = format!("{path}[{index}]");
+        let kinds = [
+            !check.not_null.is_empty(),
+            !check.unique.is_empty(),
+            check.range.is_some(),
+            check.sql.is_some(),
+        ];
+        if kinds.iter().filter(|set| **set).count() != 1 {
+            diagnostics.push(Diagnostic::new(
+                &path,
+                "a quality check requires exactly one of not_null, unique, range or sql",
+            ));
+        }
+        if let Some(range) = &check.range {
+            if range.min.is_none() && range.max.is_none() {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.range"),
+                    "min or max is required",
+                ));
+            }
+        }
+    }
+}
+
+fn validate_notifiers(notifiers: &[AlertNotifier], path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for (index, notifier) in notifiers.iter().enumerate() {
+        let (setting, value) = match notifier.kind {
+            AlertNotifierKind::Webhook | AlertNotifierKind::Slack => ("url", &notifier.url),
+            AlertNotifierKind::PagerDuty => ("routing_key", &notifier.routing_key),
+        };
+        if value.is_none() && notifier.secret.is_none() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}[{index}]"),
+                format!("{setting} or a secret with a {setting} key is required"),
+            ));
+        }
+    }
+}
+
+/// Checks that the columns a dataset selects include the columns the rest of its configuration uses.
+fn validate_columns(ds: &Dataset, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if ds.mode() == dataset::Mode::ReadWrite {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "columns can't be selected for a read_write dataset, which writes every column",
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for column in &ds.columns {
+        if !seen.insert(column) {
+            diagnostics.push(Diagnostic::new(
+                path,
+                format!("column {column} is selected more than once"),
+            ));
+        }
+    }
+
+    let used = ds
+        .time_column
+        .iter()
+        .map(|column| ("time_column", column))
+        .chain(
+            ds.embeddings
+                .iter()
+                .map(|embedding| ("embeddings", &embedding.column)),
+        );
+    for (field, column) in used {
+        if !ds.columns.contains(column) {
+            diagnostics.push(Diagnostic::new(
+                path,
+                format!("{field} uses the column {column}, which isn't selected"),
+            ));
+        }
+    }
+}
+
+/// Checks that the overridden types of the columns of a dataset are supported.
+fn validate_column_types(ds: &Dataset, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for (column, column_type) in &ds.column_types {
+        if column_types::parse_column_type(column_type).is_none() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.{column}"),
+                format!("unsupported column type {column_type}"),
+            ));
+        }
+    }
+}
+
+fn validate_acceleration(
+    ds: &Dataset,
+    acceleration: &Acceleration,
+    path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let durations = [
+        (
+            "refresh_check_interval",
+            &acceleration.refresh_check_interval,
+        ),
+        ("refresh_data_window", &acceleration.refresh_data_window),
+        ("retention_period", &acceleration.retention_period),
+        ("stale_after", &acceleration.stale_after),
+        (
+            "retention_check_interval",
+            &acceleration.retention_check_interval,
+        ),
+        ("compaction_interval", &acceleration.compaction_interval),
+    ];
+    for (key, value) in durations {
+        if let Some(value) = value {
+            if let Err(e) = fundu::parse_duration(value) {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.{key}"),
+                    format!("invalid duration {value}: {e}"),
+                ));
+            }
+        }
+    }
+
+    match (
+        &acceleration.refresh_cron,
+        &acceleration.refresh_cron_timezone,
+    ) {
+        (Some(refresh_cron), timezone) => {
+            if let Err(e) = CronSchedule::parse(refresh_cron, timezone.as_deref()) {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.refresh_cron"),
+                    e.to_string(),
+                ));
+            }
+            if acceleration.refresh_check_interval.is_some() {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}.refresh_check_interval"),
+                    "is ignored, as refresh_cron schedules the refreshes",
+                ));
+            }
+        }
+        (None, Some(_)) => diagnostics.push(Diagnostic::new(
+            format!("{path}.refresh_cron_timezone"),
+            "requires refresh_cron",
+        )),
+        (None, None) => {}
+    }
+
+    if acceleration.engine == Engine::PostgreSQL && acceleration.compaction_interval.is_some() {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.compaction_interval"),
+            "the postgres engine doesn't support compaction, which its autovacuum already does",
+        ));
+    }
+
+    if acceleration.engine == Engine::Arrow && acceleration.mode == Mode::File {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.mode"),
+            "the arrow engine doesn't support mode: file",
+        ));
+    }
+
+    for (param, engine) in [
+        ("duckdb_file", Engine::DuckDB),
+        ("sqlite_file", Engine::Sqlite),
+    ] {
+        if acceleration.params.contains_key(param)
+            && (acceleration.engine != engine || acceleration.mode != Mode::File)
+        {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.params.{param}"),
+                format!("only applies to the {engine} engine with mode: file"),
+            ));
+        }
+    }
+
+    if acceleration.encryption_secret.is_some()
+        && dataaccelerator::accelerated_file_path(&ds.name, acceleration).is_none()
+    {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.encryption_secret"),
+            "only applies to the duckdb and sqlite engines with mode: file",
+        ));
+    }
+
+    if acceleration.retained_versions > 0 {
+        if dataaccelerator::accelerated_file_path(&ds.name, acceleration).is_none() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.retained_versions"),
+                "only applies to the duckdb and sqlite engines with mode: file",
+            ));
+        } else if acceleration.encryption_secret.is_some() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.retained_versions"),
+                "versions are not kept for encrypted accelerations, as they would be stored unencrypted",
+            ));
+        }
+        if acceleration.refresh_mode == RefreshMode::Append {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.retained_versions"),
+                "only full refreshes are kept as versions, not appends",
+            ));
+        }
+    }
+
+    if acceleration.refresh_data_window.is_some() && ds.time_column.is_none() {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.refresh_data_window"),
+            "requires the dataset's time_column",
+        ));
+    }
+
+    if acceleration.retention_check_enabled {
+        if acceleration.retention_period.is_none() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.retention_check_enabled"),
+                "requires retention_period",
+            ));
+        }
+        if ds.time_column.is_none() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.retention_check_enabled"),
+                "requires the dataset's time_column",
+            ));
+        }
+    }
+}
+
+/// Logs `diagnostics` as a single warning, so a misconfigured spicepod is reported up front.
+pub fn log_diagnostics(diagnostics: &[Diagnostic]) {
+    if diagnostics.is_empty() {
+        return;
+    }
+
+    let problems = diagnostics
+        .iter()
+        .map(|diagnostic| format!("  {diagnostic}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    tracing::warn!("Spicepod has {} problem(s):\n{problems}", diagnostics.len());
+}
@@ -0,0 +1,75 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use aws_sdk_ssm::{error::SdkError, operation::get_parameter::GetParameterError};
+use snafu::Snafu;
+
+use super::{parse_secret_value, split_field, Secret, SecretStore};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to get AWS parameter: {source}"))]
+    UnableToGetParameter { source: SdkError<GetParameterError> },
+}
+
+/// Reads secrets from AWS Systems Manager Parameter Store, decrypting `SecureString` parameters.
+#[allow(clippy::module_name_repetitions)]
+pub struct AwsParameterStore {
+    client: aws_sdk_ssm::Client,
+    prefix: String,
+}
+
+impl AwsParameterStore {
+    /// Reads secrets from the parameters named `{prefix}{secret name}`.
+    #[must_use]
+    pub fn new(config: &SdkConfig, prefix: impl Into<String>) -> Self {
+        Self {
+            client: aws_sdk_ssm::Client::new(config),
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretStore for AwsParameterStore {
+    #[must_use]
+    async fn get_secret(&self, secret_name: &str) -> super::AnyErrorResult<Option<Secret>> {
+        let (secret_name, field) = split_field(secret_name);
+        let parameter_name = format!("{}{secret_name}", self.prefix);
+
+        tracing::trace!("Getting parameter {parameter_name} from AWS Parameter Store");
+
+        let parameter = match self
+            .client
+            .get_parameter()
+            .name(&parameter_name)
+            .with_decryption(true)
+            .send()
+            .await
+        {
+            Ok(output) => output.parameter,
+            Err(SdkError::ServiceError(e)) if e.err().is_parameter_not_found() => return Ok(None),
+            Err(err) => return Err(Box::new(Error::UnableToGetParameter { source: err })),
+        };
+
+        Ok(parameter
+            .and_then(|parameter| parameter.value)
+            .and_then(|value| parse_secret_value(&value, field))
+            .map(Secret::new))
+    }
+}
@@ -14,21 +14,16 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::collections::HashMap;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use aws_sdk_sts::operation::get_caller_identity::GetCallerIdentityError;
-
-use super::{Secret, SecretStore};
-
+use aws_config::{BehaviorVersion, Region, SdkConfig};
 use aws_sdk_secretsmanager::{error::SdkError, operation::get_secret_value::GetSecretValueError};
+use aws_sdk_sts::operation::get_caller_identity::GetCallerIdentityError;
+use snafu::{ResultExt, Snafu};
+use spicepod::component::secrets::Aws;
 
-use aws_config::{self, BehaviorVersion};
-use aws_sdk_secretsmanager::{self};
-
-use snafu::{OptionExt, ResultExt, Snafu};
-
-const SPICE_SECRET_PREFIX: &str = "spice_secret_";
+use super::{parse_secret_value, split_field, Secret, SecretStore};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -37,12 +32,6 @@ pub enum Error {
         source: SdkError<GetCallerIdentityError>,
     },
 
-    #[snafu(display("Unable to parse AWS secret as JSON: {source}"))]
-    UnableToParseJson { source: serde_json::Error },
-
-    #[snafu(display("Invalid AWS secret value: JSON object is expected"))]
-    InvalidJsonFormat {},
-
     #[snafu(display("Unable to get AWS secret: {source}"))]
     UnableToGetSecret {
         source: SdkError<GetSecretValueError>,
@@ -51,43 +40,50 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-#[allow(clippy::module_name_repetitions)]
-#[derive(Copy, Clone)]
-pub struct AwsSecretsManager {}
-
-impl Default for AwsSecretsManager {
-    fn default() -> Self {
-        Self::new()
+/// Loads the AWS configuration from the environment, and verifies that its credentials are valid.
+///
+/// # Errors
+///
+/// Returns an error if the call to STS `get_caller_identity` fails, which might be due to invalid or expired AWS credentials.
+pub async fn load_config(aws: &Aws) -> Result<SdkConfig> {
+    let mut loader = aws_config::defaults(BehaviorVersion::v2023_11_09());
+    if let Some(region) = &aws.region {
+        loader = loader.region(Region::new(region.clone()));
     }
+    let config = loader.load().await;
+
+    aws_sdk_sts::Client::new(&config)
+        .get_caller_identity()
+        .send()
+        .await
+        .context(UnableToVerifyAwsIdentitySnafu)?;
+
+    Ok(config)
 }
 
-impl AwsSecretsManager {
-    #[must_use]
-    pub fn new() -> Self {
-        Self {}
-    }
+/// Parses the secrets cache TTL, falling back to 5 minutes if it is invalid.
+#[must_use]
+pub fn cache_ttl(aws: &Aws) -> Duration {
+    fundu::parse_duration(&aws.cache_ttl).unwrap_or_else(|_| {
+        tracing::warn!("Invalid secrets.aws.cache_ttl {}, using 5m", aws.cache_ttl);
+        Duration::from_secs(300)
+    })
+}
 
-    /// Initializes AWS configuration and verifies AWS credentials.
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error:
-    /// - If the AWS configuration cannot be loaded.
-    /// - If the call to STS `get_caller_identity` fails, which might be due to invalid or expired AWS credentials.
-    pub async fn init(self) -> Result<()> {
-        let config = aws_config::defaults(BehaviorVersion::v2023_11_09())
-            .load()
-            .await;
-
-        let sts_client = aws_sdk_sts::Client::new(&config);
-
-        sts_client
-            .get_caller_identity()
-            .send()
-            .await
-            .context(UnableToVerifyAwsIdentitySnafu)?;
+#[allow(clippy::module_name_repetitions)]
+pub struct AwsSecretsManager {
+    client: aws_sdk_secretsmanager::Client,
+    prefix: String,
+}
 
-        Ok(())
+impl AwsSecretsManager {
+    /// Reads secrets named `{prefix}{secret name}`.
+    #[must_use]
+    pub fn new(config: &SdkConfig, prefix: impl Into<String>) -> Self {
+        Self {
+            client: aws_sdk_secretsmanager::Client::new(config),
+            prefix: prefix.into(),
+        }
     }
 }
 
@@ -95,17 +91,18 @@ impl AwsSecretsManager {
 impl SecretStore for AwsSecretsManager {
     #[must_use]
     async fn get_secret(&self, secret_name: &str) -> super::AnyErrorResult<Option<Secret>> {
-        let secret_name = format!("{SPICE_SECRET_PREFIX}{secret_name}");
+        let (secret_name, field) = split_field(secret_name);
+        let secret_name = format!("{}{secret_name}", self.prefix);
 
         tracing::trace!("Getting secret {} from AWS Secrets Manager", secret_name);
 
-        let config = aws_config::defaults(BehaviorVersion::v2023_11_09())
-            .load()
-            .await;
-
-        let asm = aws_sdk_secretsmanager::Client::new(&config);
-
-        let secret_value = match asm.get_secret_value().secret_id(&secret_name).send().await {
+        let secret_value = match self
+            .client
+            .get_secret_value()
+            .secret_id(&secret_name)
+            .send()
+            .await
+        {
             Ok(secret) => secret,
             Err(SdkError::ServiceError(e)) => {
                 // It is expected that not all parameters are present in secrets.
@@ -122,31 +119,9 @@ impl SecretStore for AwsSecretsManager {
             }
         };
 
-        if let Some(secret_str) = secret_value.secret_string() {
-            let data = parse_json_to_hashmap(secret_str)?;
-            return Ok(Some(Secret::new(data)));
-        }
-
-        Ok(None)
+        Ok(secret_value
+            .secret_string()
+            .and_then(|value| parse_secret_value(value, field))
+            .map(Secret::new))
     }
 }
-/// Parses a JSON string into a `HashMap<String, String>`.
-///
-/// # Errors
-///
-/// This function will return an error if:
-/// - The input string cannot be parsed as a valid JSON object.
-pub fn parse_json_to_hashmap(json_str: &str) -> Result<HashMap<String, String>> {
-    let parsed: serde_json::Value =
-        serde_json::from_str(json_str).context(UnableToParseJsonSnafu)?;
-    let root = parsed.as_object().context(InvalidJsonFormatSnafu)?;
-
-    let mut data = HashMap::new();
-    for (key, value) in root {
-        if let Some(value_str) = value.as_str() {
-            data.insert(key.clone(), value_str.to_string());
-        }
-    }
-
-    Ok(data)
-}
@@ -0,0 +1,252 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt, Snafu};
+use spicepod::component::secrets::{AzureAuthMethod, AzureKeyVault};
+use tokio::sync::RwLock;
+
+use super::{parse_secret_value, split_field, Secret, SecretStore};
+
+const KEY_VAULT_SCOPE: &str = "https://vault.azure.net";
+const KEY_VAULT_API_VERSION: &str = "7.4";
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// Access tokens are replaced this long before they expire.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The Key Vault URL is not set, set `secrets.azure.vault_url` or AZURE_KEYVAULT_URL"
+    ))]
+    MissingVaultUrl {},
+
+    #[snafu(display("{variable} is not set, it is required to authenticate with Azure"))]
+    MissingCredentials { variable: &'static str },
+
+    #[snafu(display("Unable to read the Azure federated token: {source}"))]
+    UnableToReadFederatedToken { source: std::io::Error },
+
+    #[snafu(display("Unable to get an Azure access token: {source}"))]
+    UnableToGetAccessToken { source: reqwest::Error },
+
+    #[snafu(display("Unable to get secret {secret_name} from Azure Key Vault: {source}"))]
+    UnableToGetSecret {
+        source: reqwest::Error,
+        secret_name: String,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// The managed identity endpoint returns the lifetime as a string, Microsoft Entra ID as a number.
+    expires_in: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct SecretResponse {
+    value: String,
+}
+
+struct AccessToken {
+    token: String,
+    expires_at: Instant,
+}
+
+#[allow(clippy::module_name_repetitions)]
+pub struct AzureKeyVaultSecretStore {
+    client: reqwest::Client,
+    vault_url: String,
+    config: AzureKeyVault,
+    token: RwLock<Option<AccessToken>>,
+}
+
+impl AzureKeyVaultSecretStore {
+    pub fn new(config: AzureKeyVault) -> Result<Self> {
+        let vault_url = config
+            .vault_url
+            .clone()
+            .or_else(|| std::env::var("AZURE_KEYVAULT_URL").ok())
+            .context(MissingVaultUrlSnafu)?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            vault_url: vault_url.trim_end_matches('/').to_string(),
+            config,
+            token: RwLock::new(None),
+        })
+    }
+
+    /// Gets the first access token, so invalid credentials are reported when the store is loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an access token can't be obtained with the configured auth method.
+    pub async fn init(&self) -> Result<()> {
+        self.access_token().await.map(|_| ())
+    }
+
+    /// Returns the cached access token, or a new one if it is about to expire.
+    async fn access_token(&self) -> Result<String> {
+        if let Some(token) = self.token.read().await.as_ref() {
+            if Instant::now() + TOKEN_EXPIRY_MARGIN < token.expires_at {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let response = self.request_token().await?;
+        let expires_in = match &response.expires_in {
+            serde_json::Value::Number(n) => n.as_u64(),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+        .unwrap_or_default();
+
+        let token = response.access_token;
+        *self.token.write().await = Some(AccessToken {
+            token: token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(expires_in),
+        });
+
+        Ok(token)
+    }
+
+    async fn request_token(&self) -> Result<TokenResponse> {
+        let client_id = self
+            .config
+            .client_id
+            .clone()
+            .or_else(|| std::env::var("AZURE_CLIENT_ID").ok());
+        let scope = format!("{KEY_VAULT_SCOPE}/.default");
+
+        let request = match self.config.auth {
+            AzureAuthMethod::ServicePrincipal => {
+                let tenant_id = env_credential("AZURE_TENANT_ID")?;
+                let client_id = client_id.context(MissingCredentialsSnafu {
+                    variable: "AZURE_CLIENT_ID",
+                })?;
+                let client_secret = env_credential("AZURE_CLIENT_SECRET")?;
+                self.client.post(token_url(&tenant_id)).form(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", &client_id),
+                    ("client_secret", &client_secret),
+                    ("scope", &scope),
+                ])
+            }
+            // AKS workload identity exchanges the pod's federated token for an access token.
+            AzureAuthMethod::ManagedIdentity
+                if std::env::var("AZURE_FEDERATED_TOKEN_FILE").is_ok() =>
+            {
+                let token_file = env_credential("AZURE_FEDERATED_TOKEN_FILE")?;
+                let assertion =
+                    std::fs::read_to_string(token_file).context(UnableToReadFederatedTokenSnafu)?;
+                let tenant_id = env_credential("AZURE_TENANT_ID")?;
+                let client_id = client_id.context(MissingCredentialsSnafu {
+                    variable: "AZURE_CLIENT_ID",
+                })?;
+                self.client.post(token_url(&tenant_id)).form(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", &client_id),
+                    (
+                        "client_assertion_type",
+                        "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+                    ),
+                    ("client_assertion", assertion.trim()),
+                    ("scope", &scope),
+                ])
+            }
+            AzureAuthMethod::ManagedIdentity => {
+                let mut query = vec![
+                    ("api-version", "2018-02-01".to_string()),
+                    ("resource", KEY_VAULT_SCOPE.to_string()),
+                ];
+                if let Some(client_id) = client_id {
+                    query.push(("client_id", client_id));
+                }
+                self.client
+                    .get(IMDS_TOKEN_URL)
+                    .header("Metadata", "true")
+                    .query(&query)
+            }
+        };
+
+        request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(UnableToGetAccessTokenSnafu)?
+            .json()
+            .await
+            .context(UnableToGetAccessTokenSnafu)
+    }
+}
+
+fn env_credential(variable: &'static str) -> Result<String> {
+    std::env::var(variable)
+        .ok()
+        .context(MissingCredentialsSnafu { variable })
+}
+
+fn token_url(tenant_id: &str) -> String {
+    format!("https://login.microsoftonline.com/{tenant_id}/oauth2/v2.0/token")
+}
+
+#[async_trait]
+impl SecretStore for AzureKeyVaultSecretStore {
+    #[must_use]
+    async fn get_secret(&self, secret_name: &str) -> super::AnyErrorResult<Option<Secret>> {
+        let (secret_name, field) = split_field(secret_name);
+        let secret_name = format!("{}{secret_name}", self.config.prefix).replace('_', "-");
+
+        let token = self.access_token().await?;
+        let response = self
+            .client
+            .get(format!("{}/secrets/{secret_name}", self.vault_url))
+            .query(&[("api-version", KEY_VAULT_API_VERSION)])
+            .bearer_auth(token)
+            .send()
+            .await
+            .context(UnableToGetSecretSnafu {
+                secret_name: &secret_name,
+            })?;
+
+        // Not every data connector has a secret, so a missing secret isn't an error.
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let secret: SecretResponse = response
+            .error_for_status()
+            .context(UnableToGetSecretSnafu {
+                secret_name: &secret_name,
+            })?
+            .json()
+            .await
+            .context(UnableToGetSecretSnafu {
+                secret_name: &secret_name,
+            })?;
+
+        Ok(parse_secret_value(&secret.value, field).map(Secret::new))
+    }
+}
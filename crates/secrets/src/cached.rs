@@ -0,0 +1,63 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use super::{AnyErrorResult, Secret, SecretStore};
+
+/// Caches the secrets of a remote secret store, including the ones it doesn't have, for `ttl`.
+#[allow(clippy::module_name_repetitions)]
+pub struct CachedSecretStore {
+    store: Box<dyn SecretStore + Send + Sync>,
+    ttl: Duration,
+    cache: RwLock<HashMap<String, (Instant, Option<Secret>)>>,
+}
+
+impl CachedSecretStore {
+    #[must_use]
+    pub fn new(store: Box<dyn SecretStore + Send + Sync>, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretStore for CachedSecretStore {
+    async fn get_secret(&self, secret_name: &str) -> AnyErrorResult<Option<Secret>> {
+        if let Some((fetched_at, secret)) = self.cache.read().await.get(secret_name) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(secret.clone());
+            }
+        }
+
+        let secret = self.store.get_secret(secret_name).await?;
+        self.cache
+            .write()
+            .await
+            .insert(secret_name.to_string(), (Instant::now(), secret.clone()));
+
+        Ok(secret)
+    }
+}
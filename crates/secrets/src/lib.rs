@@ -14,22 +14,30 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+#[cfg(feature = "aws-secrets-manager")]
+pub mod aws_parameter_store;
 #[cfg(feature = "aws-secrets-manager")]
 pub mod aws_secrets_manager;
+pub mod azure_key_vault;
+pub mod cached;
 pub mod env;
 pub mod file;
 #[cfg(feature = "keyring-secret-store")]
 pub mod keyring;
 pub mod kubernetes;
+pub mod vault;
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 use async_trait::async_trait;
 use secrecy::SecretString;
 use snafu::prelude::*;
 
+use crate::cached::CachedSecretStore;
 use crate::file::FileSecretStore;
-use spicepod::component::secrets::SpiceSecretStore;
+use spicepod::component::secrets::{Aws, AzureKeyVault, Secrets, SpiceSecretStore, Vault};
 
 pub use secrecy::ExposeSecret;
 
@@ -42,6 +50,14 @@ pub enum Error {
     UnableToInitializeAwsSecretsManager {
         source: crate::aws_secrets_manager::Error,
     },
+    #[snafu(display("Unable to initialize Vault: {source}"))]
+    UnableToInitializeVault { source: crate::vault::Error },
+
+    #[snafu(display("Unable to initialize Azure Key Vault: {source}"))]
+    UnableToInitializeAzureKeyVault {
+        source: crate::azure_key_vault::Error,
+    },
+
     #[snafu(display("Unable to parse secret value"))]
     UnableToParseSecretValue {},
 }
@@ -84,6 +100,21 @@ impl Secret {
     pub fn add(&mut self, key: String, value: String) {
         self.data.insert(key, SecretString::from(value));
     }
+
+    /// Hashes the keys and values of the secret, so a rotated secret can be detected without keeping its values.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut entries = self
+            .data
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.expose_secret().as_str()))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        entries.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 pub enum SecretStoreType {
@@ -94,11 +125,15 @@ pub enum SecretStoreType {
     Kubernetes,
     #[cfg(feature = "aws-secrets-manager")]
     AwsSecretsManager,
+    #[cfg(feature = "aws-secrets-manager")]
+    AwsParameterStore,
+    Vault(Vault),
+    AzureKeyVault(AzureKeyVault),
 }
 
 #[must_use]
-pub fn spicepod_secret_store_type(store: &SpiceSecretStore) -> Option<SecretStoreType> {
-    match store {
+pub fn spicepod_secret_store_type(secrets: &Secrets) -> Option<SecretStoreType> {
+    match secrets.store {
         SpiceSecretStore::File => Some(SecretStoreType::File),
         SpiceSecretStore::Env => Some(SecretStoreType::Env),
         #[cfg(feature = "keyring-secret-store")]
@@ -106,6 +141,14 @@ pub fn spicepod_secret_store_type(store: &SpiceSecretStore) -> Option<SecretStor
         SpiceSecretStore::Kubernetes => Some(SecretStoreType::Kubernetes),
         #[cfg(feature = "aws-secrets-manager")]
         SpiceSecretStore::AwsSecretsManager => Some(SecretStoreType::AwsSecretsManager),
+        #[cfg(feature = "aws-secrets-manager")]
+        SpiceSecretStore::AwsParameterStore => Some(SecretStoreType::AwsParameterStore),
+        SpiceSecretStore::Vault => Some(SecretStoreType::Vault(
+            secrets.vault.clone().unwrap_or_default(),
+        )),
+        SpiceSecretStore::AzureKeyVault => Some(SecretStoreType::AzureKeyVault(
+            secrets.azure.clone().unwrap_or_default(),
+        )),
         #[cfg(not(all(feature = "keyring-secret-store", feature = "aws-secrets-manager")))]
         _ => None,
     }
@@ -115,14 +158,30 @@ pub fn spicepod_secret_store_type(store: &SpiceSecretStore) -> Option<SecretStor
 pub struct SecretsProvider {
     pub store: SecretStoreType,
 
+    /// Configures the AWS stores, both as the `store` and when selected by a secret's `aws_sm:` or `aws_ssm:` prefix.
+    pub aws: Aws,
+
     secret_store: Option<Box<dyn SecretStore + Send + Sync>>,
+
+    #[cfg(feature = "aws-secrets-manager")]
+    aws_scheme_stores: tokio::sync::OnceCell<AwsSchemeStores>,
+}
+
+/// The AWS stores that secrets prefixed with `aws_sm:` and `aws_ssm:` are read from.
+#[cfg(feature = "aws-secrets-manager")]
+struct AwsSchemeStores {
+    secrets_manager: CachedSecretStore,
+    parameter_store: CachedSecretStore,
 }
 
 impl Default for SecretsProvider {
     fn default() -> Self {
         Self {
             store: SecretStoreType::File,
+            aws: Aws::default(),
             secret_store: None,
+            #[cfg(feature = "aws-secrets-manager")]
+            aws_scheme_stores: tokio::sync::OnceCell::new(),
         }
     }
 }
@@ -139,7 +198,7 @@ impl SecretsProvider {
     ///
     /// Returns an error if the secrets cannot be loaded.
     pub async fn load_secrets(&mut self) -> Result<()> {
-        match self.store {
+        match &self.store {
             SecretStoreType::File => {
                 let mut file_secret_store = FileSecretStore::new();
 
@@ -171,15 +230,82 @@ impl SecretsProvider {
             }
             #[cfg(feature = "aws-secrets-manager")]
             SecretStoreType::AwsSecretsManager => {
-                let secret_store = aws_secrets_manager::AwsSecretsManager::new();
+                let config = aws_secrets_manager::load_config(&self.aws)
+                    .await
+                    .context(UnableToInitializeAwsSecretsManagerSnafu)?;
+                let secret_store = aws_secrets_manager::AwsSecretsManager::new(
+                    &config,
+                    self.aws.secrets_manager_prefix.clone(),
+                );
+
+                self.secret_store = Some(Box::new(CachedSecretStore::new(
+                    Box::new(secret_store),
+                    aws_secrets_manager::cache_ttl(&self.aws),
+                )));
+            }
+            #[cfg(feature = "aws-secrets-manager")]
+            SecretStoreType::AwsParameterStore => {
+                let config = aws_secrets_manager::load_config(&self.aws)
+                    .await
+                    .context(UnableToInitializeAwsSecretsManagerSnafu)?;
+                let secret_store = aws_parameter_store::AwsParameterStore::new(
+                    &config,
+                    self.aws.parameter_store_prefix.clone(),
+                );
+
+                self.secret_store = Some(Box::new(CachedSecretStore::new(
+                    Box::new(secret_store),
+                    aws_secrets_manager::cache_ttl(&self.aws),
+                )));
+            }
+            SecretStoreType::Vault(config) => {
+                let secret_store = vault::VaultSecretStore::new(config.clone())
+                    .context(UnableToInitializeVaultSnafu)?;
 
                 secret_store
                     .init()
                     .await
-                    .context(UnableToInitializeAwsSecretsManagerSnafu)?;
+                    .context(UnableToInitializeVaultSnafu)?;
 
                 self.secret_store = Some(Box::new(secret_store));
             }
+            SecretStoreType::AzureKeyVault(config) => {
+                let secret_store = azure_key_vault::AzureKeyVaultSecretStore::new(config.clone())
+                    .context(UnableToInitializeAzureKeyVaultSnafu)?;
+
+                secret_store
+                    .init()
+                    .await
+                    .context(UnableToInitializeAzureKeyVaultSnafu)?;
+
+                self.secret_store = Some(Box::new(secret_store));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-reads the `file` and `env` stores, which read every secret when they are loaded, so rotated values are
+    /// seen. The other stores read secrets when they are requested and are left as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the secrets cannot be loaded.
+    pub fn reload(&mut self) -> Result<()> {
+        match self.store {
+            SecretStoreType::File => {
+                let mut file_secret_store = FileSecretStore::new();
+                file_secret_store
+                    .load_secrets()
+                    .context(UnableToLoadSecretsSnafu)?;
+                self.secret_store = Some(Box::new(file_secret_store));
+            }
+            SecretStoreType::Env => {
+                let mut env_secret_store = env::EnvSecretStore::new();
+                env_secret_store.load_secrets();
+                self.secret_store = Some(Box::new(env_secret_store));
+            }
+            _ => {}
         }
 
         Ok(())
@@ -189,12 +315,90 @@ impl SecretsProvider {
     ///
     /// Will return `None` if the secret store is not initialized or pass error from the secret store.
     pub async fn get_secret(&self, secret_name: &str) -> AnyErrorResult<Option<Secret>> {
+        #[cfg(feature = "aws-secrets-manager")]
+        if let Some((scheme @ ("aws_sm" | "aws_ssm"), secret_name)) = secret_name.split_once(':') {
+            let stores = self.aws_scheme_stores().await?;
+            let store = if scheme == "aws_sm" {
+                &stores.secrets_manager
+            } else {
+                &stores.parameter_store
+            };
+            return store.get_secret(secret_name).await;
+        }
+
         if let Some(ref secret_store) = self.secret_store {
             secret_store.get_secret(secret_name).await
         } else {
             Ok(None)
         }
     }
+
+    /// Connects to AWS the first time a secret with an AWS scheme is read.
+    #[cfg(feature = "aws-secrets-manager")]
+    async fn aws_scheme_stores(&self) -> Result<&AwsSchemeStores, aws_secrets_manager::Error> {
+        self.aws_scheme_stores
+            .get_or_try_init(|| async {
+                let config = aws_secrets_manager::load_config(&self.aws).await?;
+                let ttl = aws_secrets_manager::cache_ttl(&self.aws);
+                Ok(AwsSchemeStores {
+                    secrets_manager: CachedSecretStore::new(
+                        Box::new(aws_secrets_manager::AwsSecretsManager::new(&config, "")),
+                        ttl,
+                    ),
+                    parameter_store: CachedSecretStore::new(
+                        Box::new(aws_parameter_store::AwsParameterStore::new(&config, "")),
+                        ttl,
+                    ),
+                })
+            })
+            .await
+    }
+}
+
+/// Splits the field selected with a `#<field>` suffix from a secret name.
+pub(crate) fn split_field(secret_name: &str) -> (&str, Option<&str>) {
+    match secret_name.split_once('#') {
+        Some((name, field)) => (name, Some(field)),
+        None => (secret_name, None),
+    }
+}
+
+/// Parses the value of a secret stored in a remote store.
+///
+/// A JSON object is parsed into its fields, or into the fields of its `field` when one is selected.
+/// A selected field that isn't an object, or a value that isn't JSON, is returned as a single key:
+/// the field's name, or `value`. Returns `None` if the selected field doesn't exist.
+pub(crate) fn parse_secret_value(
+    value: &str,
+    field: Option<&str>,
+) -> Option<HashMap<String, String>> {
+    let to_string = |value: serde_json::Value| match value {
+        serde_json::Value::String(value) => value,
+        value => value.to_string(),
+    };
+
+    let json = match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(json @ serde_json::Value::Object(_)) => json,
+        _ if field.is_none() => {
+            return Some(HashMap::from([("value".to_string(), value.to_string())]))
+        }
+        _ => return None,
+    };
+
+    let (key, json) = match field {
+        Some(field) => (field, json.get(field)?.clone()),
+        None => ("value", json),
+    };
+
+    match json {
+        serde_json::Value::Object(fields) => Some(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, to_string(value)))
+                .collect(),
+        ),
+        value => Some(HashMap::from([(key.to_string(), to_string(value))])),
+    }
 }
 
 #[must_use]
@@ -222,3 +426,47 @@ pub fn get_secret_or_param(
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_json_secret_fields() {
+        let value = r#"{"postgres": {"password": "pg", "port": 5432}, "token": "abc"}"#;
+
+        let secret = parse_secret_value(value, None).expect("secret");
+        assert_eq!(secret.get("token").map(String::as_str), Some("abc"));
+
+        let secret = parse_secret_value(value, Some("postgres")).expect("secret");
+        assert_eq!(secret.get("password").map(String::as_str), Some("pg"));
+        assert_eq!(secret.get("port").map(String::as_str), Some("5432"));
+
+        let secret = parse_secret_value(value, Some("token")).expect("secret");
+        assert_eq!(secret.get("token").map(String::as_str), Some("abc"));
+
+        assert!(parse_secret_value(value, Some("mysql")).is_none());
+    }
+
+    #[test]
+    fn fingerprint_changes_with_values() {
+        let secret = |password: &str| {
+            Secret::new(HashMap::from([
+                ("username".to_string(), "spice".to_string()),
+                ("password".to_string(), password.to_string()),
+            ]))
+        };
+
+        assert_eq!(secret("old").fingerprint(), secret("old").fingerprint());
+        assert_ne!(secret("old").fingerprint(), secret("new").fingerprint());
+    }
+
+    #[test]
+    fn parses_plain_secret_as_value() {
+        let secret = parse_secret_value("hunter2", None).expect("secret");
+        assert_eq!(secret.get("value").map(String::as_str), Some("hunter2"));
+
+        assert_eq!(split_field("aws/db#password"), ("aws/db", Some("password")));
+        assert_eq!(split_field("aws/db"), ("aws/db", None));
+    }
+}
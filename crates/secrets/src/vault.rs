@@ -0,0 +1,356 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt, Snafu};
+use spicepod::component::secrets::{Vault, VaultAuthMethod};
+use tokio::sync::RwLock;
+
+use super::{Secret, SecretStore};
+
+const KUBERNETES_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+/// How long to wait before logging in again after a failed attempt.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The Vault address is not set, set `secrets.vault.address` or VAULT_ADDR"))]
+    MissingAddress {},
+
+    #[snafu(display("{variable} is not set, it is required by the Vault {method} auth method"))]
+    MissingCredentials {
+        variable: &'static str,
+        method: &'static str,
+    },
+
+    #[snafu(display("`secrets.vault.auth.role` is required by the Vault kubernetes auth method"))]
+    MissingKubernetesRole {},
+
+    #[snafu(display("Unable to read the Kubernetes service account token: {source}"))]
+    UnableToReadServiceAccountToken { source: std::io::Error },
+
+    #[snafu(display("Unable to log in to Vault: {source}"))]
+    UnableToLogin { source: reqwest::Error },
+
+    #[snafu(display("Unable to renew the Vault token: {source}"))]
+    UnableToRenewToken { source: reqwest::Error },
+
+    #[snafu(display("Unable to get secret {secret_name} from Vault: {source}"))]
+    UnableToGetSecret {
+        source: reqwest::Error,
+        secret_name: String,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The lease of a Vault token.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Lease {
+    #[serde(alias = "ttl")]
+    lease_duration: u64,
+    renewable: bool,
+}
+
+impl Lease {
+    /// Renews the token once two thirds of its lease have passed, or never for tokens without a TTL.
+    fn renew_after(self) -> Option<Duration> {
+        (self.lease_duration > 0).then(|| Duration::from_secs((self.lease_duration * 2 / 3).max(1)))
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthResponse {
+    auth: AuthData,
+}
+
+#[derive(Deserialize)]
+struct AuthData {
+    client_token: String,
+    #[serde(flatten)]
+    lease: Lease,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    data: Lease,
+}
+
+#[derive(Deserialize)]
+struct KvResponse {
+    data: KvData,
+}
+
+#[derive(Deserialize)]
+struct KvData {
+    data: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Clone)]
+struct VaultClient {
+    client: reqwest::Client,
+    address: String,
+    namespace: Option<String>,
+    config: Vault,
+}
+
+impl VaultClient {
+    fn request(&self, method: reqwest::Method, path: &str, token: Option<&str>) -> RequestBuilder {
+        let mut request = self
+            .client
+            .request(method, format!("{}/v1/{path}", self.address));
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        if let Some(token) = token {
+            request = request.header("X-Vault-Token", token);
+        }
+        request
+    }
+
+    fn auth_mount(&self, method: &'static str) -> String {
+        self.config
+            .auth
+            .mount
+            .clone()
+            .unwrap_or_else(|| method.to_string())
+    }
+
+    /// Logs in with the configured auth method, returning the client token and its lease.
+    async fn login(&self) -> Result<(String, Lease)> {
+        let (mount, body) = match self.config.auth.method {
+            VaultAuthMethod::Token => {
+                let token = std::env::var("VAULT_TOKEN")
+                    .ok()
+                    .context(MissingCredentialsSnafu {
+                        variable: "VAULT_TOKEN",
+                        method: "token",
+                    })?;
+                let lookup: LookupResponse = self
+                    .request(reqwest::Method::GET, "auth/token/lookup-self", Some(&token))
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .context(UnableToLoginSnafu)?
+                    .json()
+                    .await
+                    .context(UnableToLoginSnafu)?;
+                return Ok((token, lookup.data));
+            }
+            VaultAuthMethod::AppRole => {
+                let role_id =
+                    std::env::var("VAULT_ROLE_ID")
+                        .ok()
+                        .context(MissingCredentialsSnafu {
+                            variable: "VAULT_ROLE_ID",
+                            method: "approle",
+                        })?;
+                let secret_id =
+                    std::env::var("VAULT_SECRET_ID")
+                        .ok()
+                        .context(MissingCredentialsSnafu {
+                            variable: "VAULT_SECRET_ID",
+                            method: "approle",
+                        })?;
+                (
+                    self.auth_mount("approle"),
+                    serde_json::json!({ "role_id": role_id, "secret_id": secret_id }),
+                )
+            }
+            VaultAuthMethod::Kubernetes => {
+                let role = self
+                    .config
+                    .auth
+                    .role
+                    .clone()
+                    .context(MissingKubernetesRoleSnafu)?;
+                let jwt = std::fs::read_to_string(KUBERNETES_TOKEN_PATH)
+                    .context(UnableToReadServiceAccountTokenSnafu)?;
+                (
+                    self.auth_mount("kubernetes"),
+                    serde_json::json!({ "role": role, "jwt": jwt.trim() }),
+                )
+            }
+        };
+
+        let response: AuthResponse = self
+            .request(reqwest::Method::POST, &format!("auth/{mount}/login"), None)
+            .json(&body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(UnableToLoginSnafu)?
+            .json()
+            .await
+            .context(UnableToLoginSnafu)?;
+
+        Ok((response.auth.client_token, response.auth.lease))
+    }
+
+    async fn renew(&self, token: &str) -> Result<Lease> {
+        let response: AuthResponse = self
+            .request(reqwest::Method::POST, "auth/token/renew-self", Some(token))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context(UnableToRenewTokenSnafu)?
+            .json()
+            .await
+            .context(UnableToRenewTokenSnafu)?;
+
+        Ok(response.auth.lease)
+    }
+
+    /// Renews the token in `token`, logging in again if it can't be renewed, and returns its new lease.
+    async fn refresh(&self, token: &RwLock<String>, lease: Lease) -> Lease {
+        if lease.renewable {
+            let current = token.read().await.clone();
+            match self.renew(&current).await {
+                Ok(lease) => return lease,
+                Err(e) => tracing::debug!("{e}, logging in again"),
+            }
+        }
+
+        loop {
+            match self.login().await {
+                Ok((new_token, lease)) => {
+                    *token.write().await = new_token;
+                    return lease;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Unable to refresh the Vault token, retrying in {}s: {e}",
+                        RETRY_INTERVAL.as_secs()
+                    );
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn read(
+        &self,
+        token: &str,
+        secret_name: &str,
+    ) -> Result<Option<HashMap<String, String>>> {
+        let secret_path = match &self.config.path {
+            Some(path) => format!("{}/{secret_name}", path.trim_matches('/')),
+            None => secret_name.to_string(),
+        };
+        let response = self
+            .request(
+                reqwest::Method::GET,
+                &format!("{}/data/{secret_path}", self.config.mount.trim_matches('/')),
+                Some(token),
+            )
+            .send()
+            .await
+            .context(UnableToGetSecretSnafu { secret_name })?;
+
+        // Not every data connector has a secret, so a missing secret isn't an error.
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let kv: KvResponse = response
+            .error_for_status()
+            .context(UnableToGetSecretSnafu { secret_name })?
+            .json()
+            .await
+            .context(UnableToGetSecretSnafu { secret_name })?;
+
+        Ok(Some(
+            kv.data
+                .data
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key, value),
+                    value => (key, value.to_string()),
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Reads secrets from the KV v2 secrets engine of a HashiCorp Vault server, renewing its token in the background.
+#[allow(clippy::module_name_repetitions)]
+pub struct VaultSecretStore {
+    client: VaultClient,
+    token: Arc<RwLock<String>>,
+}
+
+impl VaultSecretStore {
+    pub fn new(config: Vault) -> Result<Self> {
+        let address = config
+            .address
+            .clone()
+            .or_else(|| std::env::var("VAULT_ADDR").ok())
+            .context(MissingAddressSnafu)?;
+        let namespace = config
+            .namespace
+            .clone()
+            .or_else(|| std::env::var("VAULT_NAMESPACE").ok());
+
+        Ok(Self {
+            client: VaultClient {
+                client: reqwest::Client::new(),
+                address: address.trim_end_matches('/').to_string(),
+                namespace,
+                config,
+            },
+            token: Arc::new(RwLock::new(String::new())),
+        })
+    }
+
+    /// Logs in to Vault and starts renewing the token before its lease expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credentials for the auth method are missing or rejected.
+    pub async fn init(&self) -> Result<()> {
+        let (token, lease) = self.client.login().await?;
+        *self.token.write().await = token;
+
+        let client = self.client.clone();
+        let token = Arc::clone(&self.token);
+        tokio::spawn(async move {
+            let mut lease = lease;
+            while let Some(renew_after) = lease.renew_after() {
+                tokio::time::sleep(renew_after).await;
+                lease = client.refresh(&token, lease).await;
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SecretStore for VaultSecretStore {
+    #[must_use]
+    async fn get_secret(&self, secret_name: &str) -> super::AnyErrorResult<Option<Secret>> {
+        let token = self.token.read().await.clone();
+        match self.client.read(&token, secret_name).await {
+            Ok(data) => Ok(data.map(Secret::new)),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
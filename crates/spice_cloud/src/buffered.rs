@@ -0,0 +1,362 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Buffers writes to a Spice Cloud dataset, so rows written while the cloud is unreachable are sent once it is
+//! reachable again instead of failing the local write.
+//!
+//! The buffer is bounded: when it is full the oldest rows are dropped, and counted in
+//! `spice_cloud_sync_dropped_rows`. How far the cloud is behind is reported as `spice_cloud_sync_lag_seconds`.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use async_trait::async_trait;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::execution::context::{SessionContext, SessionState};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::insert::{DataSink, DataSinkExec};
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::{collect, DisplayAs, DisplayFormatType, ExecutionPlan};
+use futures::StreamExt;
+use tokio::sync::Notify;
+
+/// How often the buffer is checked for new rows when nothing notifies it.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Wraps the table provider of a Spice Cloud dataset, buffering appends and sending them in the background.
+pub(crate) struct BufferedTableProvider {
+    inner: Arc<dyn TableProvider>,
+    buffer: Arc<SyncBuffer>,
+}
+
+impl BufferedTableProvider {
+    /// Wraps `inner`, keeping at most `max_rows` rows that haven't been sent yet.
+    ///
+    /// Must be called from a Tokio runtime, which runs the task sending the buffered rows.
+    pub(crate) fn new(dataset: &str, inner: Arc<dyn TableProvider>, max_rows: usize) -> Self {
+        let buffer = Arc::new(SyncBuffer::new(dataset, max_rows));
+        tokio::spawn(flush(Arc::clone(&inner), Arc::downgrade(&buffer)));
+
+        Self { inner, buffer }
+    }
+}
+
+#[async_trait]
+impl TableProvider for BufferedTableProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        self.inner.scan(state, projection, filters, limit).await
+    }
+
+    async fn insert_into(
+        &self,
+        state: &SessionState,
+        input: Arc<dyn ExecutionPlan>,
+        overwrite: bool,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        // Buffered appends sent after an overwrite would be lost, so overwrites go straight to the cloud.
+        if overwrite {
+            return self.inner.insert_into(state, input, overwrite).await;
+        }
+
+        Ok(Arc::new(DataSinkExec::new(
+            input,
+            Arc::new(BufferSink {
+                buffer: Arc::clone(&self.buffer),
+            }),
+            self.schema(),
+            None,
+        )))
+    }
+}
+
+struct BufferedBatch {
+    /// Increases with each buffered batch, to tell which batches were sent while others were dropped.
+    sequence: u64,
+    buffered_at: Instant,
+    batch: RecordBatch,
+}
+
+#[derive(Default)]
+struct Pending {
+    batches: VecDeque<BufferedBatch>,
+    rows: usize,
+    next_sequence: u64,
+}
+
+struct SyncBuffer {
+    dataset: String,
+    max_rows: usize,
+    pending: Mutex<Pending>,
+    notify: Notify,
+}
+
+impl SyncBuffer {
+    fn new(dataset: &str, max_rows: usize) -> Self {
+        Self {
+            dataset: dataset.to_string(),
+            max_rows,
+            pending: Mutex::new(Pending::default()),
+            notify: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Pending> {
+        self.pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Buffers `batches`, dropping the oldest buffered rows if there are more than `max_rows`.
+    fn push(&self, batches: Vec<RecordBatch>) {
+        let mut pending = self.lock();
+        let now = Instant::now();
+        for batch in batches {
+            pending.rows += batch.num_rows();
+            let sequence = pending.next_sequence;
+            pending.next_sequence += 1;
+            pending.batches.push_back(BufferedBatch {
+                sequence,
+                buffered_at: now,
+                batch,
+            });
+        }
+
+        let mut dropped = 0;
+        while pending.rows > self.max_rows {
+            let Some(oldest) = pending.batches.pop_front() else {
+                break;
+            };
+            pending.rows -= oldest.batch.num_rows();
+            dropped += oldest.batch.num_rows();
+        }
+        if dropped > 0 {
+            tracing::warn!(
+                "Spice Cloud sync buffer for {} is full, dropped the {dropped} oldest rows",
+                self.dataset
+            );
+            metrics::counter!("spice_cloud_sync_dropped_rows", "dataset" => self.dataset.clone())
+                .increment(dropped as u64);
+        }
+
+        self.record_metrics(&pending);
+        drop(pending);
+        self.notify.notify_one();
+    }
+
+    /// Returns the buffered batches, with the sequence of the newest one.
+    fn snapshot(&self) -> Option<(u64, Vec<RecordBatch>)> {
+        let pending = self.lock();
+        let last = pending.batches.back()?.sequence;
+        let batches = pending.batches.iter().map(|b| b.batch.clone()).collect();
+        Some((last, batches))
+    }
+
+    /// Removes the batches up to `sequence`, once they were sent.
+    fn remove_through(&self, sequence: u64) {
+        let mut pending = self.lock();
+        while pending
+            .batches
+            .front()
+            .is_some_and(|b| b.sequence <= sequence)
+        {
+            if let Some(sent) = pending.batches.pop_front() {
+                pending.rows -= sent.batch.num_rows();
+            }
+        }
+        self.record_metrics(&pending);
+    }
+
+    fn record_metrics(&self, pending: &Pending) {
+        let lag = pending
+            .batches
+            .front()
+            .map_or(Duration::ZERO, |oldest| oldest.buffered_at.elapsed());
+        metrics::gauge!("spice_cloud_sync_lag_seconds", "dataset" => self.dataset.clone())
+            .set(lag.as_secs_f64());
+        #[allow(clippy::cast_precision_loss)]
+        metrics::gauge!("spice_cloud_sync_buffered_rows", "dataset" => self.dataset.clone())
+            .set(pending.rows as f64);
+    }
+}
+
+/// Sends the buffered rows to `inner`, retrying with exponential backoff while it fails, until the buffer is dropped.
+async fn flush(inner: Arc<dyn TableProvider>, buffer: Weak<SyncBuffer>) {
+    let ctx = SessionContext::new();
+    let mut backoff = INITIAL_BACKOFF;
+    let mut failing = false;
+
+    loop {
+        let Some(buffer) = buffer.upgrade() else {
+            return;
+        };
+
+        let Some((last_sequence, batches)) = buffer.snapshot() else {
+            // Wake up now and then to notice when the table is dropped.
+            let _ = tokio::time::timeout(FLUSH_INTERVAL, buffer.notify.notified()).await;
+            continue;
+        };
+
+        match send(&ctx, &inner, batches).await {
+            Ok(()) => {
+                buffer.remove_through(last_sequence);
+                if failing {
+                    tracing::info!("Spice Cloud sync for {} has caught up", buffer.dataset);
+                }
+                failing = false;
+                backoff = INITIAL_BACKOFF;
+            }
+            Err(e) => {
+                if failing {
+                    tracing::debug!("Unable to sync {} to Spice Cloud: {e}", buffer.dataset);
+                } else {
+                    tracing::warn!(
+                        "Unable to sync {} to Spice Cloud, buffering rows until it is reachable: {e}",
+                        buffer.dataset
+                    );
+                }
+                failing = true;
+                // Keep the lag current while the cloud is unreachable.
+                buffer.record_metrics(&buffer.lock());
+                drop(buffer);
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+async fn send(
+    ctx: &SessionContext,
+    inner: &Arc<dyn TableProvider>,
+    batches: Vec<RecordBatch>,
+) -> Result<()> {
+    let input = Arc::new(MemoryExec::try_new(&[batches], inner.schema(), None)?);
+    let plan = inner.insert_into(&ctx.state(), input, false).await?;
+    collect(plan, ctx.task_ctx()).await?;
+
+    Ok(())
+}
+
+/// Buffers the rows written to a [`BufferedTableProvider`].
+struct BufferSink {
+    buffer: Arc<SyncBuffer>,
+}
+
+impl fmt::Debug for BufferSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferSink")
+            .field("dataset", &self.buffer.dataset)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DisplayAs for BufferSink {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "SpiceCloudSyncBuffer (dataset={})", self.buffer.dataset)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataSink for BufferSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        None
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        _context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let mut batches = vec![];
+        let mut row_count = 0;
+        while let Some(batch) = data.next().await.transpose()? {
+            row_count += batch.num_rows();
+            batches.push(batch);
+        }
+
+        self.buffer.push(batches);
+
+        Ok(row_count as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    fn batch(rows: i64) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from_iter_values(0..rows))],
+        )
+        .expect("valid batch")
+    }
+
+    #[test]
+    fn drops_oldest_rows_when_full() {
+        let buffer = SyncBuffer::new("test", 10);
+        buffer.push(vec![batch(4), batch(4)]);
+        let (first_sent, _) = buffer.snapshot().expect("rows are buffered");
+
+        buffer.push(vec![batch(4)]);
+        assert_eq!(buffer.lock().rows, 8);
+
+        // Only the batches sent in the snapshot are removed, even though one of them was already dropped.
+        buffer.remove_through(first_sent);
+        let pending = buffer.lock();
+        assert_eq!(pending.rows, 4);
+        assert_eq!(pending.batches.len(), 1);
+    }
+}
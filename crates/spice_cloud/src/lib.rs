@@ -1,13 +1,16 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use datafusion::{datasource::TableProvider, sql::TableReference};
-use serde::Deserialize;
-use serde_json::json;
 use snafu::prelude::*;
 
 use runtime::{
     accelerated_table::{refresh::Refresh, AcceleratedTable, Retention},
+    audit::audit_table_reference,
     component::dataset::{
         acceleration::{Acceleration, RefreshMode},
         replication::Replication,
@@ -15,143 +18,525 @@ use runtime::{
     },
     dataaccelerator::{self, create_accelerator_table},
     dataconnector::{create_new_connector, DataConnectorError},
+    datafusion::{query::query_history::DEFAULT_QUERY_HISTORY_TABLE, SPICE_RUNTIME_SCHEMA},
+    error_info::{classify, ErrorComponent, ErrorInfo, ErrorKind, ToErrorInfo},
     extension::{Extension, ExtensionFactory, ExtensionManifest, Result},
     spice_metrics::get_metrics_table_reference,
     Runtime,
 };
 use secrets::Secret;
 
+use crate::buffered::BufferedTableProvider;
+use crate::publish::{parse_publish_datasets, PublishTarget, Publisher};
+use crate::scope::{Connections, Scope};
+
+mod buffered;
+mod publish;
+mod scope;
+
+/// How many rows written to a synced table are kept while Spice Cloud is unreachable, unless set with
+/// `sync_buffer_max_rows`.
+const DEFAULT_SYNC_BUFFER_MAX_ROWS: usize = 100_000;
+
+/// How often the scopes' secrets are checked for refreshed API keys, unless set with `token_refresh_interval`.
+const DEFAULT_TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Unable to get read-write table provider"))]
     NoReadWriteProvider {},
 
-    #[snafu(display("Unable to create data connector"))]
+    #[snafu(display("Unable to create data connector: {source}"))]
     UnableToCreateDataConnector {
         source: Box<dyn std::error::Error + Sync + Send>,
     },
 
-    #[snafu(display("Unable to create source table provider"))]
+    #[snafu(display("Unable to create source table provider: {source}"))]
     UnableToCreateSourceTableProvider { source: DataConnectorError },
 
     #[snafu(display("Unable to create accelerated table provider: {source}"))]
     UnableToCreateAcceleratedTableProvider { source: dataaccelerator::Error },
 
-    #[snafu(display("Unable to get Spice Cloud secret: {source}"))]
+    #[snafu(display("Unable to get Spice Cloud secret {secret_name}: {source}"))]
     UnableToGetSpiceSecret {
+        secret_name: String,
         source: Box<dyn std::error::Error + Sync + Send>,
     },
 
-    #[snafu(display("Spice Cloud secret not found"))]
-    SpiceSecretNotFound {},
+    #[snafu(display("Spice Cloud secret {secret_name} not found"))]
+    SpiceSecretNotFound { secret_name: String },
+
+    #[snafu(display("Spice Cloud api_key not provided in secret {secret_name}"))]
+    SpiceApiKeyNotFound { secret_name: String },
 
-    #[snafu(display("Spice Cloud api_key not provided"))]
-    SpiceApiKeyNotFound {},
+    #[snafu(display(
+        "Invalid scopes value {value}, expected a comma-separated list of unique scope names without '/'"
+    ))]
+    InvalidScopes { value: String },
 
     #[snafu(display("Unable to connect to Spice Cloud: {source}"))]
     UnableToConnectToSpiceCloud { source: reqwest::Error },
+
+    #[snafu(display("Invalid {param} value {value}: {source}"))]
+    InvalidDuration {
+        param: String,
+        value: String,
+        source: fundu::ParseError,
+    },
+
+    #[snafu(display("Invalid sync_buffer_max_rows value {value}, expected a number of rows"))]
+    InvalidSyncBufferMaxRows { value: String },
+
+    #[snafu(display(
+        "Invalid publish_datasets value {value}, expected a comma-separated list of <local dataset>[:<cloud dataset>]"
+    ))]
+    InvalidPublishDatasets { value: String },
+
+    #[snafu(display(
+        "Unable to publish {dataset} to Spice Cloud dataset {cloud_dataset}: {source}"
+    ))]
+    UnableToPublishDataset {
+        dataset: String,
+        cloud_dataset: String,
+        source: datafusion::error::DataFusionError,
+    },
+
+    #[snafu(display("{param} must be greater than zero"))]
+    ZeroDuration { param: String },
+
+    #[snafu(display(
+        "{table}_sync_window ({sync_window:?}) can't be longer than {table}_retention ({retention:?}), synced rows would be deleted right away"
+    ))]
+    SyncWindowExceedsRetention {
+        table: &'static str,
+        sync_window: Duration,
+        retention: Duration,
+    },
 }
 
-pub struct SpiceExtension {
-    manifest: ExtensionManifest,
+impl ToErrorInfo for Error {
+    fn error_info(&self) -> ErrorInfo {
+        match self {
+            Error::UnableToCreateDataConnector { source } => {
+                classify(source.as_ref(), ErrorComponent::Extension)
+            }
+            Error::UnableToCreateSourceTableProvider { source } => source.error_info(),
+            Error::UnableToCreateAcceleratedTableProvider { source } => source.error_info(),
+            Error::UnableToPublishDataset { source, .. } => source.error_info(),
+            Error::UnableToConnectToSpiceCloud { .. } => ErrorInfo::new(
+                "SPICE_CLOUD_UNREACHABLE",
+                ErrorComponent::Extension,
+                ErrorKind::Unavailable,
+            )
+            .with_hint("Check that the runtime can reach Spice Cloud"),
+            Error::UnableToGetSpiceSecret { .. }
+            | Error::SpiceSecretNotFound { .. }
+            | Error::SpiceApiKeyNotFound { .. } => ErrorInfo::new(
+                "SPICE_CLOUD_MISSING_API_KEY",
+                ErrorComponent::Extension,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Run `spice login`, or set the api_key of the Spice Cloud secret"),
+            Error::InvalidScopes { .. }
+            | Error::InvalidDuration { .. }
+            | Error::InvalidSyncBufferMaxRows { .. }
+            | Error::InvalidPublishDatasets { .. }
+            | Error::ZeroDuration { .. }
+            | Error::SyncWindowExceedsRetention { .. } => ErrorInfo::new(
+                "EXTENSION_INVALID_CONFIGURATION",
+                ErrorComponent::Extension,
+                ErrorKind::InvalidInput,
+            )
+            .with_hint("Check the params of the spice_cloud extension in the spicepod"),
+            Error::NoReadWriteProvider {} => ErrorInfo::internal(ErrorComponent::Extension),
+        }
+    }
 }
 
-impl SpiceExtension {
-    #[must_use]
-    pub fn new(manifest: ExtensionManifest) -> Self {
-        SpiceExtension { manifest }
+impl From<Error> for runtime::extension::Error {
+    fn from(error: Error) -> Self {
+        runtime::extension::Error::Coded {
+            info: error.error_info(),
+            source: Box::new(error),
+        }
     }
+}
+
+/// How a runtime table is synced with Spice Cloud, set with the extension's `<table>_*` params.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SyncConfig {
+    /// `<table>_retention`: how long rows are kept locally, or forever if unset.
+    retention: Option<Duration>,
+    /// `<table>_refresh_interval`: how often the table is synced.
+    refresh_interval: Duration,
+    /// `<table>_sync_window`: how far back rows are synced from the cloud, or all rows if unset.
+    sync_window: Option<Duration>,
+}
+
+const METRICS_SYNC: SyncConfig = SyncConfig {
+    retention: Some(Duration::from_secs(30 * 60)),
+    refresh_interval: Duration::from_secs(10),
+    sync_window: Some(Duration::from_secs(30 * 60)),
+};
+
+/// Keeps query history for as long as the runtime does when it isn't synced.
+const QUERY_HISTORY_SYNC: SyncConfig = SyncConfig {
+    retention: Some(Duration::from_secs(24 * 60 * 60)),
+    refresh_interval: Duration::from_secs(10),
+    sync_window: Some(Duration::from_secs(24 * 60 * 60)),
+};
+
+/// Audit events are never expired locally, but only the last day is synced back from the cloud.
+const AUDIT_LOG_SYNC: SyncConfig = SyncConfig {
+    retention: None,
+    refresh_interval: Duration::from_secs(10),
+    sync_window: Some(Duration::from_secs(24 * 60 * 60)),
+};
 
-    fn spice_http_url(&self) -> String {
-        self.manifest
-            .params
-            .get("endpoint")
-            .unwrap_or(&"https://data.spiceai.io".to_string())
-            .to_string()
+impl SyncConfig {
+    fn try_from_params(
+        params: &HashMap<String, String>,
+        table: &'static str,
+        defaults: Self,
+    ) -> Result<Self, Error> {
+        let config = Self {
+            retention: parse_duration_param(params, &format!("{table}_retention"))?
+                .or(defaults.retention),
+            refresh_interval: parse_duration_param(params, &format!("{table}_refresh_interval"))?
+                .unwrap_or(defaults.refresh_interval),
+            sync_window: parse_duration_param(params, &format!("{table}_sync_window"))?
+                .or(defaults.sync_window),
+        };
+
+        if let (Some(sync_window), Some(retention)) = (config.sync_window, config.retention) {
+            ensure!(
+                sync_window <= retention,
+                SyncWindowExceedsRetentionSnafu {
+                    table,
+                    sync_window,
+                    retention,
+                }
+            );
+        }
+
+        Ok(config)
     }
+}
+
+fn parse_duration_param(
+    params: &HashMap<String, String>,
+    param: &str,
+) -> Result<Option<Duration>, Error> {
+    let Some(value) = params.get(param) else {
+        return Ok(None);
+    };
 
-    async fn get_spice_secret(&self, runtime: &Runtime) -> Result<Secret, Error> {
-        let secrets = runtime.secrets_provider.read().await;
-        let secret = secrets
-            .get_secret("spiceai")
-            .await
-            .context(UnableToGetSpiceSecretSnafu)?;
+    let duration = fundu::parse_duration(value).context(InvalidDurationSnafu { param, value })?;
+    ensure!(!duration.is_zero(), ZeroDurationSnafu { param });
+
+    Ok(Some(duration))
+}
+
+/// A runtime table synced with a dataset of the connected Spice Cloud app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TableSync {
+    dataset: String,
+    config: SyncConfig,
+}
 
-        secret.ok_or(Error::SpiceSecretNotFound {})
+impl TableSync {
+    /// Reads the sync of `table`, enabled by setting the `<table>_dataset` param to the name of the cloud dataset.
+    fn try_from_params(
+        params: &HashMap<String, String>,
+        table: &'static str,
+        defaults: SyncConfig,
+    ) -> Result<Option<Self>, Error> {
+        let Some(dataset) = params.get(&format!("{table}_dataset")) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            dataset: dataset.clone(),
+            config: SyncConfig::try_from_params(params, table, defaults)?,
+        }))
     }
+}
 
-    async fn get_spice_api_key(&self, runtime: &Runtime) -> Result<String, Error> {
-        let secret = self.get_spice_secret(runtime).await?;
-        let api_key = secret.get("key").ok_or(Error::SpiceApiKeyNotFound {})?;
+/// What the extension syncs with Spice Cloud, read from its params.
+#[derive(Debug, Clone)]
+struct CloudSync {
+    scopes: Vec<Scope>,
+    metrics_sync: SyncConfig,
+    query_history_sync: Option<TableSync>,
+    audit_log_sync: Option<TableSync>,
+    sync_buffer_max_rows: usize,
+    publish_targets: Vec<PublishTarget>,
+    /// Publishes on a schedule when set, or after each refresh of a published dataset otherwise.
+    publish_interval: Option<Duration>,
+    /// How often the scopes' secrets are checked for refreshed API keys, or never if unset.
+    token_refresh_interval: Option<Duration>,
+}
 
-        Ok(api_key.to_string())
+impl Default for CloudSync {
+    fn default() -> Self {
+        Self {
+            scopes: vec![],
+            metrics_sync: METRICS_SYNC,
+            query_history_sync: None,
+            audit_log_sync: None,
+            sync_buffer_max_rows: DEFAULT_SYNC_BUFFER_MAX_ROWS,
+            publish_targets: vec![],
+            publish_interval: None,
+            token_refresh_interval: Some(DEFAULT_TOKEN_REFRESH_INTERVAL),
+        }
     }
+}
+
+impl CloudSync {
+    fn try_from_params(params: &HashMap<String, String>) -> Result<Self, Error> {
+        let mut sync = Self {
+            scopes: Scope::all_from_params(params)?,
+            metrics_sync: SyncConfig::try_from_params(params, "metrics", METRICS_SYNC)?,
+            query_history_sync: TableSync::try_from_params(
+                params,
+                "query_history",
+                QUERY_HISTORY_SYNC,
+            )?,
+            audit_log_sync: TableSync::try_from_params(params, "audit_log", AUDIT_LOG_SYNC)?,
+            publish_interval: parse_duration_param(params, "publish_interval")?,
+            ..Self::default()
+        };
+
+        if let Some(value) = params.get("sync_buffer_max_rows") {
+            sync.sync_buffer_max_rows = value
+                .parse()
+                .ok()
+                .filter(|rows| *rows > 0)
+                .context(InvalidSyncBufferMaxRowsSnafu { value })?;
+        }
+        if let Some(value) = params.get("publish_datasets") {
+            sync.publish_targets = parse_publish_datasets(value)?;
+        }
+        match params.get("token_refresh_interval").map(String::as_str) {
+            Some("none") => sync.token_refresh_interval = None,
+            Some(_) => {
+                sync.token_refresh_interval =
+                    parse_duration_param(params, "token_refresh_interval")?;
+            }
+            None => {}
+        }
+
+        Ok(sync)
+    }
+
+    /// Reads the secret of each scope, in the order of `scopes`.
+    async fn get_secrets(&self, runtime: &Runtime) -> Result<Vec<Secret>, Error> {
+        let mut secrets = Vec::with_capacity(self.scopes.len());
+        for scope in &self.scopes {
+            secrets.push(scope.get_secret(runtime).await?);
+        }
+        Ok(secrets)
+    }
+
+    /// Connects every scope with `secrets` and registers the synced tables, replacing the ones registered by a
+    /// previous start. Returns the publisher of the published datasets, if any.
+    async fn start(
+        &self,
+        runtime: &Runtime,
+        secrets: Vec<Secret>,
+    ) -> Result<Option<Arc<Publisher>>> {
+        let mut connections = Vec::with_capacity(self.scopes.len());
+        for (scope, secret) in self.scopes.iter().zip(secrets) {
+            connections.push(scope.connect(secret).await?);
+        }
+        let connections = Connections(connections);
+
+        let default_scope = connections.default_scope();
+        let from = default_scope.dataset_path(&default_scope.metrics_dataset_name);
+        self.register_synced_table(
+            runtime,
+            get_metrics_table_reference(),
+            "timestamp",
+            &from,
+            default_scope.secret.clone(),
+            self.metrics_sync,
+        )
+        .await?;
+        tracing::info!("Enabled metrics sync from runtime.metrics to {from}",);
+
+        if let Some(sync) = &self.query_history_sync {
+            let (connection, from) = connections.resolve(&sync.dataset);
+            self.register_synced_table(
+                runtime,
+                TableReference::partial(SPICE_RUNTIME_SCHEMA, DEFAULT_QUERY_HISTORY_TABLE),
+                "start_time",
+                &from,
+                connection.secret.clone(),
+                sync.config,
+            )
+            .await?;
+            tracing::info!("Enabled query history sync from runtime.query_history to {from}");
+        }
+
+        if let Some(sync) = &self.audit_log_sync {
+            // The audit log only writes to its table with the `table` sink, which registers it before extensions start.
+            if runtime.datafusion().table_exists(audit_table_reference()) {
+                let (connection, from) = connections.resolve(&sync.dataset);
+                self.register_synced_table(
+                    runtime,
+                    audit_table_reference(),
+                    "timestamp",
+                    &from,
+                    connection.secret.clone(),
+                    sync.config,
+                )
+                .await?;
+                tracing::info!("Enabled audit log sync from runtime.audit_log to {from}");
+            } else {
+                tracing::warn!(
+                    "audit_log_dataset is set, but the audit log isn't written to a table. Set runtime.audit.sink to table to sync it."
+                );
+            }
+        }
+
+        if self.publish_targets.is_empty() {
+            return Ok(None);
+        }
+
+        let mut targets = Vec::with_capacity(self.publish_targets.len());
+        for target in &self.publish_targets {
+            let (connection, to) = connections.resolve(&target.cloud_dataset);
+            let cloud_table = get_spiceai_table_provider(
+                target.local.table(),
+                &to,
+                Some(connection.secret.clone()),
+            )
+            .await?;
+            tracing::info!("Publishing {} to {to}", target.local);
+            targets.push((target.clone(), cloud_table));
+        }
 
-    async fn connect(&self, runtime: &Runtime) -> Result<SpiceCloudConnectResponse, Error> {
-        let api_key = self.get_spice_api_key(runtime).await?;
-        let client = reqwest::Client::new();
-        let response = client
-            .post(format!("{}/v1/connect", self.spice_http_url()))
-            .json(&json!({}))
-            .header("Content-Type", "application/json")
-            .header("X-API-Key", api_key)
-            .send()
-            .await
-            .context(UnableToConnectToSpiceCloudSnafu)?;
-
-        let response: SpiceCloudConnectResponse = response
-            .json()
-            .await
-            .context(UnableToConnectToSpiceCloudSnafu)?;
-
-        Ok(response)
+        let publisher = Arc::new(Publisher::new(runtime.datafusion(), targets));
+        if let Some(interval) = self.publish_interval {
+            publisher.schedule(interval);
+        }
+
+        Ok(Some(publisher))
     }
 
-    async fn register_runtime_metrics_table(
-        &mut self,
+    /// Registers `table_reference` as a table synced with the cloud dataset `from`, replacing the local table if
+    /// the runtime has already registered one.
+    async fn register_synced_table(
+        &self,
         runtime: &Runtime,
-        from: String,
+        table_reference: TableReference,
+        time_column: &str,
+        from: &str,
         secret: Secret,
+        config: SyncConfig,
     ) -> Result<()> {
         let retention = Retention::new(
-            Some("timestamp".to_string()),
+            Some(time_column.to_string()),
             Some(TimeFormat::UnixSeconds),
-            Some(Duration::from_secs(1800)), // delete metrics older then 30 minutes
-            Some(Duration::from_secs(300)),  // run retention every 5 minutes
-            true,
+            config.retention,
+            // Check retention at most every 5 minutes, and more often for short retention periods.
+            config
+                .retention
+                .map(|retention| retention.min(Duration::from_secs(300))),
+            config.retention.is_some(),
         );
 
         let refresh = Refresh::new(
-            Some("timestamp".to_string()),
+            Some(time_column.to_string()),
             Some(TimeFormat::UnixSeconds),
-            Some(Duration::from_secs(10)),
+            Some(config.refresh_interval),
             None,
             RefreshMode::Full,
-            Some(Duration::from_secs(1800)), // sync only last 30 minutes from cloud
+            config.sync_window,
         );
 
-        let metrics_table_reference = get_metrics_table_reference();
-
         let table = create_synced_internal_accelerated_table(
-            metrics_table_reference.clone(),
-            from.as_str(),
+            table_reference.clone(),
+            from,
             Some(secret),
             Acceleration::default(),
             refresh,
             retention,
+            self.sync_buffer_max_rows,
         )
-        .await
-        .boxed()
-        .map_err(|e| runtime::extension::Error::UnableToStartExtension { source: e })?;
+        .await?;
 
-        runtime
-            .datafusion()
-            .register_runtime_table(metrics_table_reference, table)
+        let df = runtime.datafusion();
+        if df.table_exists(table_reference.clone()) {
+            df.remove_table(&table_reference)
+                .boxed()
+                .map_err(|e| runtime::extension::Error::UnableToStartExtension { source: e })?;
+        }
+        df.register_runtime_table(table_reference, table)
             .boxed()
             .map_err(|e| runtime::extension::Error::UnableToStartExtension { source: e })?;
 
         Ok(())
     }
+
+    /// Restarts the sync whenever the secret of a scope changes, so short-lived API keys written to the secret
+    /// store are picked up before they expire.
+    async fn refresh_tokens(
+        self,
+        runtime: Runtime,
+        interval: Duration,
+        mut fingerprints: Vec<u64>,
+        publisher: Arc<Mutex<Option<Arc<Publisher>>>>,
+    ) {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            if let Err(e) = runtime.secrets_provider.write().await.reload() {
+                tracing::warn!("Unable to reload secrets: {e}");
+            }
+            let secrets = match self.get_secrets(&runtime).await {
+                Ok(secrets) => secrets,
+                Err(e) => {
+                    tracing::warn!("Unable to refresh Spice Cloud API keys: {e}");
+                    continue;
+                }
+            };
+            let current: Vec<u64> = secrets.iter().map(Secret::fingerprint).collect();
+            if current == fingerprints {
+                continue;
+            }
+
+            tracing::info!("Spice Cloud API keys were refreshed, reconnecting");
+            match self.start(&runtime, secrets).await {
+                Ok(new_publisher) => {
+                    *publisher
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner) = new_publisher;
+                    fingerprints = current;
+                }
+                // Keep the previous fingerprints to try again with the next check.
+                Err(e) => tracing::warn!("Unable to reconnect to Spice Cloud: {e}"),
+            }
+        }
+    }
+}
+
+pub struct SpiceExtension {
+    manifest: ExtensionManifest,
+    sync: CloudSync,
+    /// Replaced each time the extension reconnects with refreshed API keys.
+    publisher: Arc<Mutex<Option<Arc<Publisher>>>>,
+}
+
+impl SpiceExtension {
+    #[must_use]
+    pub fn new(manifest: ExtensionManifest) -> Self {
+        SpiceExtension {
+            manifest,
+            sync: CloudSync::default(),
+            publisher: Arc::new(Mutex::new(None)),
+        }
+    }
 }
 
 impl Default for SpiceExtension {
@@ -171,6 +556,8 @@ impl Extension for SpiceExtension {
             return Ok(());
         }
 
+        self.sync = CloudSync::try_from_params(&self.manifest.params)?;
+
         Ok(())
     }
 
@@ -179,30 +566,40 @@ impl Extension for SpiceExtension {
             return Ok(());
         }
 
-        let secret = self
-            .get_spice_secret(runtime)
-            .await
-            .boxed()
-            .map_err(|e| runtime::extension::Error::UnableToStartExtension { source: e })?;
-
-        let connection = self
-            .connect(runtime)
-            .await
-            .boxed()
-            .map_err(|e| runtime::extension::Error::UnableToStartExtension { source: e })?;
-
-        let spiceai_metrics_dataset_path = format!(
-            "spice.ai/{}/{}/{}",
-            connection.org_name, connection.app_name, connection.metrics_dataset_name
-        );
-
-        let from = spiceai_metrics_dataset_path.to_string();
-        self.register_runtime_metrics_table(runtime, from.clone(), secret)
-            .await?;
-        tracing::info!("Enabled metrics sync from runtime.metrics to {from}",);
+        let secrets = self.sync.get_secrets(runtime).await?;
+        let fingerprints = secrets.iter().map(Secret::fingerprint).collect();
+
+        let publisher = self.sync.start(runtime, secrets).await?;
+        *self
+            .publisher
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = publisher;
+
+        if let Some(interval) = self.sync.token_refresh_interval {
+            tokio::spawn(self.sync.clone().refresh_tokens(
+                runtime.clone(),
+                interval,
+                fingerprints,
+                Arc::clone(&self.publisher),
+            ));
+        }
 
         Ok(())
     }
+
+    async fn on_refresh_complete(&self, dataset: &TableReference, error: Option<&str>) {
+        if self.sync.publish_interval.is_some() || error.is_some() {
+            return;
+        }
+        let publisher = self
+            .publisher
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone();
+        if let Some(publisher) = publisher {
+            publisher.spawn_publish(dataset);
+        }
+    }
 }
 
 pub struct SpiceExtensionFactory {
@@ -218,9 +615,7 @@ impl SpiceExtensionFactory {
 
 impl ExtensionFactory for SpiceExtensionFactory {
     fn create(&self) -> Box<dyn Extension> {
-        Box::new(SpiceExtension {
-            manifest: self.manifest.clone(),
-        })
+        Box::new(SpiceExtension::new(self.manifest.clone()))
     }
 }
 
@@ -252,6 +647,9 @@ async fn get_spiceai_table_provider(
 
 /// Create a new accelerated table that is synced with the cloud dataset
 ///
+/// Rows written to the table are sent to the cloud in the background, keeping up to `buffer_max_rows` rows while
+/// the cloud is unreachable.
+///
 /// # Errors
 ///
 /// This function will return an error if the accelerated table provider cannot be created
@@ -262,9 +660,15 @@ pub async fn create_synced_internal_accelerated_table(
     acceleration: Acceleration,
     refresh: Refresh,
     retention: Option<Retention>,
+    buffer_max_rows: usize,
 ) -> Result<Arc<AcceleratedTable>, Error> {
-    let source_table_provider =
+    let cloud_table_provider =
         get_spiceai_table_provider(table_reference.table(), from, secret).await?;
+    let source_table_provider: Arc<dyn TableProvider> = Arc::new(BufferedTableProvider::new(
+        from,
+        cloud_table_provider,
+        buffer_max_rows,
+    ));
 
     let accelerated_table_provider = create_accelerator_table(
         table_reference.clone(),
@@ -289,10 +693,73 @@ pub async fn create_synced_internal_accelerated_table(
     Ok(Arc::new(accelerated_table))
 }
 
-#[derive(Deserialize, Debug)]
-#[allow(clippy::struct_field_names)]
-struct SpiceCloudConnectResponse {
-    org_name: String,
-    app_name: String,
-    metrics_dataset_name: String,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn sync_config_from_params() {
+        assert_eq!(
+            SyncConfig::try_from_params(&HashMap::new(), "metrics", METRICS_SYNC)
+                .expect("defaults are valid"),
+            METRICS_SYNC
+        );
+
+        let config = SyncConfig::try_from_params(
+            &params(&[
+                ("metrics_retention", "2h"),
+                ("metrics_refresh_interval", "1m"),
+                ("metrics_sync_window", "1h"),
+            ]),
+            "metrics",
+            METRICS_SYNC,
+        )
+        .expect("valid params");
+        assert_eq!(config.retention, Some(Duration::from_secs(7200)));
+        assert_eq!(config.refresh_interval, Duration::from_secs(60));
+        assert_eq!(config.sync_window, Some(Duration::from_secs(3600)));
+
+        for invalid in [
+            ("metrics_refresh_interval", "soon"),
+            ("metrics_refresh_interval", "0s"),
+            ("metrics_sync_window", "1h"),
+        ] {
+            assert!(
+                SyncConfig::try_from_params(&params(&[invalid]), "metrics", METRICS_SYNC).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn table_sync_is_enabled_by_dataset() {
+        assert_eq!(
+            TableSync::try_from_params(&HashMap::new(), "audit_log", AUDIT_LOG_SYNC)
+                .expect("valid params"),
+            None
+        );
+
+        let sync = TableSync::try_from_params(
+            &params(&[
+                ("audit_log_dataset", "audit"),
+                ("audit_log_sync_window", "7d"),
+            ]),
+            "audit_log",
+            AUDIT_LOG_SYNC,
+        )
+        .expect("valid params")
+        .expect("sync is enabled");
+        assert_eq!(sync.dataset, "audit");
+        assert_eq!(sync.config.retention, None);
+        assert_eq!(
+            sync.config.sync_window,
+            Some(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+    }
 }
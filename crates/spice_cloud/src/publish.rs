@@ -0,0 +1,222 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Publishes local datasets to datasets of the connected Spice Cloud app, so edge runtimes can send the data they
+//! accelerate or derive to a central app.
+//!
+//! Each publish writes all the current rows of the local dataset to the cloud dataset as an overwrite, either every
+//! `publish_interval` or, without one, after each successful refresh of the local dataset.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use datafusion::{datasource::TableProvider, physical_plan::collect, sql::TableReference};
+use runtime::datafusion::DataFusion;
+use snafu::prelude::*;
+
+use crate::{Error, InvalidPublishDatasetsSnafu, UnableToPublishDatasetSnafu};
+
+/// A local dataset published to a dataset of the connected Spice Cloud app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PublishTarget {
+    pub(crate) local: TableReference,
+    pub(crate) cloud_dataset: String,
+}
+
+/// Parses the `publish_datasets` param: a comma-separated list of `<local dataset>[:<cloud dataset>]`, where the
+/// cloud dataset defaults to the name of the local one.
+pub(crate) fn parse_publish_datasets(value: &str) -> Result<Vec<PublishTarget>, Error> {
+    value
+        .split(',')
+        .map(str::trim)
+        .map(|entry| {
+            let (local, cloud_dataset) = match entry.split_once(':') {
+                Some((local, cloud_dataset)) => (local.trim(), cloud_dataset.trim()),
+                None => (entry, entry),
+            };
+            ensure!(
+                !local.is_empty() && !cloud_dataset.is_empty(),
+                InvalidPublishDatasetsSnafu { value }
+            );
+
+            Ok(PublishTarget {
+                local: TableReference::from(local),
+                cloud_dataset: cloud_dataset.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub(crate) struct Publisher {
+    df: Arc<DataFusion>,
+    /// Each target, with the table provider writing to its cloud dataset.
+    targets: Vec<(PublishTarget, Arc<dyn TableProvider>)>,
+    /// The local datasets being published, so a slow publish isn't overlapped by the next one.
+    in_progress: Mutex<HashSet<TableReference>>,
+}
+
+impl Publisher {
+    pub(crate) fn new(
+        df: Arc<DataFusion>,
+        targets: Vec<(PublishTarget, Arc<dyn TableProvider>)>,
+    ) -> Self {
+        Self {
+            df,
+            targets,
+            in_progress: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Publishes every target each `interval`, until the publisher is dropped.
+    pub(crate) fn schedule(self: &Arc<Self>, interval: Duration) {
+        let publisher = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(publisher) = publisher.upgrade() else {
+                    return;
+                };
+                for (target, _) in &publisher.targets {
+                    publisher.publish_logged(&target.local).await;
+                }
+            }
+        });
+    }
+
+    /// Publishes `dataset` in the background if it is a target.
+    pub(crate) fn spawn_publish(self: &Arc<Self>, dataset: &TableReference) {
+        if !self
+            .targets
+            .iter()
+            .any(|(target, _)| &target.local == dataset)
+        {
+            return;
+        }
+
+        let publisher = Arc::clone(self);
+        let dataset = dataset.clone();
+        tokio::spawn(async move { publisher.publish_logged(&dataset).await });
+    }
+
+    async fn publish_logged(&self, dataset: &TableReference) {
+        if !self.start(dataset) {
+            tracing::debug!("{dataset} is still being published to Spice Cloud, skipping");
+            return;
+        }
+
+        for (target, cloud_table) in self.targets.iter().filter(|(t, _)| &t.local == dataset) {
+            match self.publish(target, cloud_table).await {
+                Ok(rows) => tracing::info!(
+                    "Published {rows} rows from {} to Spice Cloud dataset {}",
+                    target.local,
+                    target.cloud_dataset
+                ),
+                Err(e) => tracing::warn!("{e}"),
+            }
+        }
+
+        self.finish(dataset);
+    }
+
+    async fn publish(
+        &self,
+        target: &PublishTarget,
+        cloud_table: &Arc<dyn TableProvider>,
+    ) -> Result<u64, Error> {
+        let publish_failed = || UnableToPublishDatasetSnafu {
+            dataset: target.local.to_string(),
+            cloud_dataset: target.cloud_dataset.clone(),
+        };
+
+        let local = self
+            .df
+            .ctx
+            .table(target.local.clone())
+            .await
+            .context(publish_failed())?;
+        let input = local
+            .create_physical_plan()
+            .await
+            .context(publish_failed())?;
+        let plan = cloud_table
+            .insert_into(&self.df.ctx.state(), input, true)
+            .await
+            .context(publish_failed())?;
+
+        let batches = collect(plan, self.df.ctx.task_ctx())
+            .await
+            .context(publish_failed())?;
+
+        Ok(rows_written(&batches))
+    }
+
+    fn start(&self, dataset: &TableReference) -> bool {
+        self.in_progress
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(dataset.clone())
+    }
+
+    fn finish(&self, dataset: &TableReference) {
+        self.in_progress
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(dataset);
+    }
+}
+
+/// Sums the `count` column returned by an insert plan.
+fn rows_written(batches: &[arrow::record_batch::RecordBatch]) -> u64 {
+    batches
+        .iter()
+        .filter_map(|batch| {
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<arrow::array::UInt64Array>()
+        })
+        .flat_map(|counts| counts.iter().flatten())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_datasets_from_param() {
+        assert_eq!(
+            parse_publish_datasets("sales_by_hour, local_stats:edge_stats")
+                .expect("valid publish_datasets"),
+            vec![
+                PublishTarget {
+                    local: TableReference::from("sales_by_hour"),
+                    cloud_dataset: "sales_by_hour".to_string(),
+                },
+                PublishTarget {
+                    local: TableReference::from("local_stats"),
+                    cloud_dataset: "edge_stats".to_string(),
+                },
+            ]
+        );
+
+        assert!(parse_publish_datasets("sales,").is_err());
+        assert!(parse_publish_datasets("sales:").is_err());
+    }
+}
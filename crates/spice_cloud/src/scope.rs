@@ -0,0 +1,224 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The Spice Cloud apps the extension connects to.
+//!
+//! The default scope connects with the `spiceai` secret. Additional scopes are listed in the `scopes` param, and
+//! each connects with its own secret (`<scope>_secret`, defaulting to `spiceai_<scope>`) and optionally its own
+//! endpoint (`<scope>_endpoint`). Dataset params select a scope by prefixing the dataset with `<scope>/`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use serde_json::json;
+use snafu::prelude::*;
+
+use runtime::Runtime;
+use secrets::Secret;
+
+use crate::{
+    Error, InvalidScopesSnafu, SpiceApiKeyNotFoundSnafu, SpiceSecretNotFoundSnafu,
+    UnableToConnectToSpiceCloudSnafu, UnableToGetSpiceSecretSnafu,
+};
+
+const DEFAULT_ENDPOINT: &str = "https://data.spiceai.io";
+const DEFAULT_SECRET: &str = "spiceai";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Scope {
+    /// The name datasets are prefixed with to use this scope, or `None` for the default scope.
+    pub(crate) name: Option<String>,
+    pub(crate) secret_name: String,
+    pub(crate) endpoint: String,
+}
+
+impl Scope {
+    /// Reads the default scope and the scopes listed in the `scopes` param.
+    pub(crate) fn all_from_params(params: &HashMap<String, String>) -> Result<Vec<Self>, Error> {
+        let endpoint = params
+            .get("endpoint")
+            .map_or(DEFAULT_ENDPOINT, String::as_str);
+        let mut scopes = vec![Self {
+            name: None,
+            secret_name: DEFAULT_SECRET.to_string(),
+            endpoint: endpoint.to_string(),
+        }];
+
+        let Some(value) = params.get("scopes") else {
+            return Ok(scopes);
+        };
+        let mut names = HashSet::new();
+        for name in value.split(',').map(str::trim) {
+            ensure!(
+                !name.is_empty() && !name.contains('/') && names.insert(name),
+                InvalidScopesSnafu { value }
+            );
+
+            scopes.push(Self {
+                name: Some(name.to_string()),
+                secret_name: params
+                    .get(&format!("{name}_secret"))
+                    .cloned()
+                    .unwrap_or_else(|| format!("{DEFAULT_SECRET}_{name}")),
+                endpoint: params
+                    .get(&format!("{name}_endpoint"))
+                    .map_or(endpoint, String::as_str)
+                    .to_string(),
+            });
+        }
+
+        Ok(scopes)
+    }
+
+    pub(crate) async fn get_secret(&self, runtime: &Runtime) -> Result<Secret, Error> {
+        let secrets = runtime.secrets_provider.read().await;
+        let secret =
+            secrets
+                .get_secret(&self.secret_name)
+                .await
+                .context(UnableToGetSpiceSecretSnafu {
+                    secret_name: &self.secret_name,
+                })?;
+
+        secret.context(SpiceSecretNotFoundSnafu {
+            secret_name: &self.secret_name,
+        })
+    }
+
+    /// Connects to the Spice Cloud app whose API key is in `secret`.
+    pub(crate) async fn connect(&self, secret: Secret) -> Result<Connection, Error> {
+        let api_key = secret.get("key").context(SpiceApiKeyNotFoundSnafu {
+            secret_name: &self.secret_name,
+        })?;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/v1/connect", self.endpoint))
+            .json(&json!({}))
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", api_key)
+            .send()
+            .await
+            .context(UnableToConnectToSpiceCloudSnafu)?;
+
+        let response: SpiceCloudConnectResponse = response
+            .json()
+            .await
+            .context(UnableToConnectToSpiceCloudSnafu)?;
+
+        Ok(Connection {
+            scope_name: self.name.clone(),
+            org_name: response.org_name,
+            app_name: response.app_name,
+            metrics_dataset_name: response.metrics_dataset_name,
+            secret,
+        })
+    }
+}
+
+/// A connected Spice Cloud app.
+pub(crate) struct Connection {
+    scope_name: Option<String>,
+    pub(crate) org_name: String,
+    pub(crate) app_name: String,
+    pub(crate) metrics_dataset_name: String,
+    pub(crate) secret: Secret,
+}
+
+impl Connection {
+    pub(crate) fn dataset_path(&self, dataset: &str) -> String {
+        format!("spice.ai/{}/{}/{dataset}", self.org_name, self.app_name)
+    }
+}
+
+/// The connections of every scope, the default scope's first.
+pub(crate) struct Connections(pub(crate) Vec<Connection>);
+
+impl Connections {
+    pub(crate) fn default_scope(&self) -> &Connection {
+        &self.0[0]
+    }
+
+    /// Returns the connection for `dataset` with the dataset path in its app. A dataset is in a named scope when
+    /// prefixed with `<scope>/`, and in the default scope otherwise.
+    pub(crate) fn resolve(&self, dataset: &str) -> (&Connection, String) {
+        if let Some((scope, name)) = dataset.split_once('/') {
+            if let Some(connection) = self
+                .0
+                .iter()
+                .find(|c| c.scope_name.as_deref() == Some(scope))
+            {
+                return (connection, connection.dataset_path(name));
+            }
+        }
+
+        let connection = self.default_scope();
+        (connection, connection.dataset_path(dataset))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(clippy::struct_field_names)]
+struct SpiceCloudConnectResponse {
+    org_name: String,
+    app_name: String,
+    metrics_dataset_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_from_params() {
+        let params: HashMap<String, String> = [
+            ("endpoint", "https://cloud.example.com"),
+            ("scopes", "analytics, edge"),
+            ("edge_secret", "edge_key"),
+            ("edge_endpoint", "https://edge.example.com"),
+        ]
+        .iter()
+        .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+        .collect();
+
+        let scopes = Scope::all_from_params(&params).expect("valid scopes");
+        assert_eq!(
+            scopes,
+            vec![
+                Scope {
+                    name: None,
+                    secret_name: "spiceai".to_string(),
+                    endpoint: "https://cloud.example.com".to_string(),
+                },
+                Scope {
+                    name: Some("analytics".to_string()),
+                    secret_name: "spiceai_analytics".to_string(),
+                    endpoint: "https://cloud.example.com".to_string(),
+                },
+                Scope {
+                    name: Some("edge".to_string()),
+                    secret_name: "edge_key".to_string(),
+                    endpoint: "https://edge.example.com".to_string(),
+                },
+            ]
+        );
+
+        for invalid in ["edge,edge", "edge,", "a/b"] {
+            let params = HashMap::from([("scopes".to_string(), invalid.to_string())]);
+            assert!(Scope::all_from_params(&params).is_err());
+        }
+    }
+}
@@ -20,15 +20,20 @@ use std::path::PathBuf;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use snafu::prelude::*;
 
-use crate::reader;
+use crate::{environment, reader};
+pub mod alert;
 pub mod dataset;
+pub mod deployment;
 pub mod embeddings;
 pub mod extension;
+pub mod function;
+pub mod job;
 pub mod llms;
 pub mod model;
 pub mod params;
 pub mod runtime;
 pub mod secrets;
+pub mod test;
 pub mod view;
 
 pub trait WithDependsOn<T> {
@@ -60,6 +65,11 @@ pub enum Error {
         source: serde_yaml::Error,
         path: PathBuf,
     },
+    #[snafu(display("Unable to interpolate spicepod component {}: {source}", path.display()))]
+    UnableToInterpolateSpicepodComponent {
+        source: environment::Error,
+        path: PathBuf,
+    },
     #[snafu(display("The component referenced by {} does not exist", path.display()))]
     InvalidComponentReference { path: PathBuf },
 }
@@ -92,10 +102,20 @@ where
                         path: component_base_path.clone(),
                     })?;
 
-                let component_definition: ComponentType = serde_yaml::from_reader(component_rdr)
+                let mut component_value: serde_yaml::Value = serde_yaml::from_reader(component_rdr)
                     .context(UnableToParseSpicepodComponentSnafu {
-                        path: component_base_path,
+                        path: component_base_path.clone(),
                     })?;
+                environment::interpolate(&mut component_value, &|name| std::env::var(name).ok())
+                    .context(UnableToInterpolateSpicepodComponentSnafu {
+                        path: component_base_path.clone(),
+                    })?;
+                let component_definition: ComponentType = serde_yaml::from_value(component_value)
+                    .context(
+                    UnableToParseSpicepodComponentSnafu {
+                        path: component_base_path,
+                    },
+                )?;
 
                 let component = component_definition.depends_on(&reference.depends_on);
 
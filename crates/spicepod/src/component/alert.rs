@@ -0,0 +1,72 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+/// A SQL condition the runtime checks on a schedule or when datasets refresh, notifying when it starts or stops
+/// returning rows, i.e. `SELECT * FROM orders_summary WHERE last_order < now() - INTERVAL '1 hour'` for stale data.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Alert {
+    pub name: String,
+
+    /// The alert fires while the SQL returns rows.
+    pub sql: String,
+
+    /// A cron expression of when to check the alert, in UTC, as for jobs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+
+    /// The datasets the alert is checked after each refresh of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_refresh: Vec<String>,
+
+    pub notify: Vec<AlertNotifier>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertNotifier {
+    #[serde(rename = "type")]
+    pub kind: AlertNotifierKind,
+
+    /// The URL the `webhook` and `slack` notifiers post to. Read from the `url` key of `secret` if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// The integration key of the `pagerduty` notifier. Read from the `routing_key` key of `secret` if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing_key: Option<String>,
+
+    /// The secret the URL or routing key is read from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
+    /// The severity of PagerDuty incidents: `critical`, `error` (the default), `warning` or `info`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+
+    /// The message sent, with `{{ alert }}`, `{{ status }}`, `{{ rows }}`, `{{ results }}` and `{{ time }}` replaced.
+    /// The `webhook` notifier sends it as the body of the request, instead of the default JSON payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertNotifierKind {
+    Webhook,
+    Slack,
+    PagerDuty,
+}
@@ -14,6 +14,8 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 use super::{embeddings::ColumnEmbeddingConfig, params::Params, WithDependsOn};
@@ -55,6 +57,11 @@ pub struct Dataset {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub params: Option<Params>,
 
+    /// The secret with the data connector's credentials. Defaults to the secret named after the data connector,
+    /// i.e. `postgres`, so datasets that connect to different accounts with the same connector name their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
     #[serde(rename = "metadata", default, skip_serializing_if = "Option::is_none")]
     pub has_metadata_table: Option<bool>,
 
@@ -70,13 +77,55 @@ pub struct Dataset {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub acceleration: Option<acceleration::Acceleration>,
 
+    /// The columns of the source to fetch, and to store in the accelerator. Defaults to every column.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+
+    /// The types of columns of CSV or JSON files, overriding the types inferred from a sample of their records.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub column_types: HashMap<String, String>,
+
+    /// Overrides the types the data connector maps the columns of the source to, i.e. `decimal` columns to
+    /// `double` for tools that don't read decimals. The first mapping that matches a column applies.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_mappings: Vec<type_mapping::TypeMapping>,
+
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(rename = "embeddings", default)]
     pub embeddings: Vec<ColumnEmbeddingConfig>,
 
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policies: Vec<policy::Policy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expectations: Option<expectations::Expectations>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quality_checks: Vec<quality::QualityCheck>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anomaly_detection: Option<anomaly_detection::AnomalyDetection>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<cache::Cache>,
+
+    /// Gates `/health/ready` on the first successful load of the dataset, while datasets that aren't critical load
+    /// in the background.
+    #[serde(default)]
+    pub critical: bool,
+
+    /// Samples the dataset after it loads to flag the columns that look like they hold personal data in
+    /// `/v1/status`, so they can be given `masks` in its policies.
+    #[serde(default)]
+    pub detect_pii: bool,
+
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(rename = "dependsOn", default)]
     pub depends_on: Vec<String>,
+
+    /// The namespace the dataset was loaded into from `runtime.namespaces`, which scopes its secrets.
+    #[serde(skip)]
+    pub namespace: Option<String>,
 }
 
 impl Dataset {
@@ -87,13 +136,25 @@ impl Dataset {
             name,
             mode: Mode::default(),
             params: None,
+            secret: None,
             has_metadata_table: None,
             replication: None,
             time_column: None,
             time_format: None,
             acceleration: None,
+            columns: Vec::default(),
+            column_types: HashMap::default(),
+            type_mappings: Vec::default(),
             embeddings: Vec::default(),
+            policies: Vec::default(),
+            expectations: None,
+            quality_checks: Vec::default(),
+            anomaly_detection: None,
+            cache: None,
+            critical: false,
+            detect_pii: false,
             depends_on: Vec::default(),
+            namespace: None,
         }
     }
 }
@@ -105,17 +166,52 @@ impl WithDependsOn<Dataset> for Dataset {
             name: self.name.clone(),
             mode: self.mode.clone(),
             params: self.params.clone(),
+            secret: self.secret.clone(),
             has_metadata_table: self.has_metadata_table,
             replication: self.replication.clone(),
             time_column: self.time_column.clone(),
             time_format: self.time_format.clone(),
             acceleration: self.acceleration.clone(),
+            columns: self.columns.clone(),
+            column_types: self.column_types.clone(),
+            type_mappings: self.type_mappings.clone(),
             embeddings: self.embeddings.clone(),
+            policies: self.policies.clone(),
+            expectations: self.expectations.clone(),
+            quality_checks: self.quality_checks.clone(),
+            anomaly_detection: self.anomaly_detection.clone(),
+            cache: self.cache.clone(),
+            critical: self.critical,
+            detect_pii: self.detect_pii,
             depends_on: depends_on.to_vec(),
+            namespace: self.namespace.clone(),
         }
     }
 }
 
+pub mod type_mapping {
+    use serde::{Deserialize, Serialize};
+
+    /// Reads the columns of a type, or a single column, as another type.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct TypeMapping {
+        /// The type the data connector maps the columns to, i.e. `decimal`, `timestamp` or `int8`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub from: Option<String>,
+
+        /// The column to map, instead of the columns of a type, i.e. a MySQL `TINYINT(1)` flag.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub column: Option<String>,
+
+        /// The type the columns are read as, i.e. `double`, `boolean` or `decimal(18, 4)`.
+        pub to: String,
+
+        /// The timezone timestamps are read in. Timestamps without a timezone are taken as local times of it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub timezone: Option<String>,
+    }
+}
+
 pub mod acceleration {
     use serde::{Deserialize, Serialize};
     use std::{collections::HashMap, fmt::Display};
@@ -167,6 +263,54 @@ pub mod acceleration {
         }
     }
 
+    /// Behavior when a query reads an accelerated table that hasn't loaded, failed its last refresh, or is older
+    /// than `stale_after`.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum UnavailableAction {
+        /// Serve the accelerated data, with a `Warning` header in HTTP responses. This is the default.
+        #[default]
+        ServeStale,
+        /// Query the source table instead.
+        UseSource,
+        /// Fail the query.
+        Error,
+    }
+
+    impl Display for UnavailableAction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                UnavailableAction::ServeStale => write!(f, "serve_stale"),
+                UnavailableAction::UseSource => write!(f, "use_source"),
+                UnavailableAction::Error => write!(f, "error"),
+            }
+        }
+    }
+
+    /// How the struct, list and map columns of a source are accelerated by an engine that can't store them.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum NestedTypes {
+        /// Fail to accelerate the dataset, naming the columns. This is the default.
+        #[default]
+        Error,
+        /// Store the columns as JSON strings.
+        Json,
+        /// Store the fields of struct columns as columns with dotted names, i.e. `address.city`, and lists and maps
+        /// as JSON strings.
+        Flatten,
+    }
+
+    impl Display for NestedTypes {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                NestedTypes::Error => write!(f, "error"),
+                NestedTypes::Json => write!(f, "json"),
+                NestedTypes::Flatten => write!(f, "flatten"),
+            }
+        }
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
     #[serde(rename_all = "lowercase")]
     pub enum IndexType {
@@ -201,6 +345,14 @@ pub mod acceleration {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub refresh_check_interval: Option<String>,
 
+        /// A cron expression, i.e. `0 2 * * *`, to refresh at instead of every `refresh_check_interval`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub refresh_cron: Option<String>,
+
+        /// The IANA timezone `refresh_cron` is evaluated in, UTC by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub refresh_cron_timezone: Option<String>,
+
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub refresh_sql: Option<String>,
 
@@ -213,6 +365,16 @@ pub mod acceleration {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub engine_secret: Option<String>,
 
+        /// The secret whose `key` is the base64 encoded 256-bit key the file of a `mode: file` acceleration is
+        /// encrypted with at rest.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub encryption_secret: Option<String>,
+
+        /// The number of previous loads of a `mode: file` acceleration kept as versions that can be queried with
+        /// `FOR SYSTEM_TIME AS OF` or the `acceleration_version` table function.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub retained_versions: Option<usize>,
+
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub retention_period: Option<String>,
 
@@ -222,14 +384,31 @@ pub mod acceleration {
         #[serde(default, skip_serializing_if = "is_false")]
         pub retention_check_enabled: bool,
 
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub compaction_interval: Option<String>,
+
         #[serde(default)]
         pub on_zero_results: ZeroResultsAction,
 
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub stale_after: Option<String>,
+
+        #[serde(default)]
+        pub on_unavailable: UnavailableAction,
+
+        #[serde(default)]
+        pub nested_types: NestedTypes,
+
         #[serde(default, skip_serializing_if = "HashMap::is_empty")]
         pub indexes: HashMap<String, IndexType>,
 
         #[serde(default, skip_serializing_if = "Option::is_none")]
         pub primary_key: Option<String>,
+
+        /// In a cluster, splits the acceleration across the members by the hash of this column. Each member
+        /// accelerates its shards, and queries scan the shards of every member and merge the results.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub partition_by: Option<String>,
     }
 
     #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -249,16 +428,25 @@ pub mod acceleration {
                 engine: None,
                 refresh_mode: RefreshMode::Full,
                 refresh_check_interval: None,
+                refresh_cron: None,
+                refresh_cron_timezone: None,
                 refresh_sql: None,
                 refresh_data_window: None,
                 params: None,
                 engine_secret: None,
+                encryption_secret: None,
+                retained_versions: None,
                 retention_period: None,
                 retention_check_interval: None,
                 retention_check_enabled: false,
+                compaction_interval: None,
                 on_zero_results: ZeroResultsAction::ReturnEmpty,
+                stale_after: None,
+                on_unavailable: UnavailableAction::ServeStale,
+                nested_types: NestedTypes::Error,
                 indexes: HashMap::default(),
                 primary_key: None,
+                partition_by: None,
             }
         }
     }
@@ -273,3 +461,190 @@ pub mod replication {
         pub enabled: bool,
     }
 }
+
+pub mod policy {
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    /// Restricts the rows and columns of a dataset visible to the principals a query runs on behalf of.
+    ///
+    /// The first policy whose `when` conditions match the principal applies. If a dataset has policies
+    /// and none match, the dataset can't be queried.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+    pub struct Policy {
+        /// Claims the principal must have, i.e. `role: analyst`. Empty matches every principal.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        pub when: HashMap<String, String>,
+
+        /// The columns the principal can query. Unset allows all columns.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub columns: Option<Vec<String>>,
+
+        /// A SQL predicate rows must satisfy, i.e. `region = ${principal.region}`.
+        /// `${principal.<claim>}` is replaced with the value of the claim as a string literal.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub row_filter: Option<String>,
+
+        /// How the values of columns are masked for the principal, i.e. `email: hash`. Row filters see the values
+        /// before they are masked.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        pub masks: HashMap<String, Mask>,
+    }
+
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Mask {
+        /// The hex encoded HMAC-SHA256 of the value, keyed by `runtime.masks.hash_secret`, so masked values can
+        /// still be joined and grouped on.
+        Hash,
+        /// A fixed placeholder.
+        Redact,
+        /// Only the last 4 characters of the value, i.e. `*******1234`.
+        Partial,
+        /// Null.
+        Nullify,
+    }
+}
+
+pub mod expectations {
+    use serde::{Deserialize, Serialize};
+
+    use crate::component::alert::AlertNotifier;
+
+    /// The row count and freshness a dataset is expected to have, checked after each refresh. The dataset is
+    /// degraded while it violates them.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+    pub struct Expectations {
+        /// The fewest rows the dataset should have.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub min_rows: Option<u64>,
+
+        /// How long ago the acceleration of the dataset can have last refreshed, i.e. `1h`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_staleness: Option<String>,
+
+        /// Notified when the dataset becomes degraded, and when it recovers.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub notify: Vec<AlertNotifier>,
+    }
+}
+
+pub mod quality {
+    use serde::{Deserialize, Serialize};
+
+    /// A check of the data of each refresh of an accelerated dataset, run before the data replaces the accelerated
+    /// data. Each check sets one of `not_null`, `unique`, `range` or `sql`.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+    pub struct QualityCheck {
+        /// Columns that can't have nulls.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub not_null: Vec<String>,
+
+        /// Columns whose values, together, can't repeat.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub unique: Vec<String>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub range: Option<RangeCheck>,
+
+        /// A query that fails the check if it returns rows, with the refreshed data as the table of the dataset,
+        /// i.e. `SELECT * FROM orders WHERE shipped_at < created_at`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub sql: Option<String>,
+
+        #[serde(default)]
+        pub on_failure: OnFailure,
+    }
+
+    /// The values of `column` must be between `min` and `max`, inclusive.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct RangeCheck {
+        pub column: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub min: Option<f64>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max: Option<f64>,
+    }
+
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+    #[serde(rename_all = "lowercase")]
+    pub enum OnFailure {
+        /// Discards the refreshed data, keeping the accelerated data, and marks the dataset as failed.
+        #[default]
+        Fail,
+        /// Logs a warning and accelerates the refreshed data.
+        Warn,
+    }
+}
+
+pub mod anomaly_detection {
+    use serde::{Deserialize, Serialize};
+
+    /// Scores the values of a column of an accelerated dataset for anomalies after each refresh, ordered by its
+    /// `time_column`, and writes the scores to the `runtime.<dataset>_anomalies` table.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct AnomalyDetection {
+        pub column: String,
+
+        #[serde(default)]
+        pub method: AnomalyMethod,
+
+        /// The number of previous values `zscore` compares each value to, 24 by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub window: Option<u64>,
+
+        /// The number of values in a season for `seasonal`, i.e. `24` for hourly values with a daily cycle.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub period: Option<u64>,
+
+        /// The most outliers `esd` finds, 10 by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_anomalies: Option<u64>,
+
+        /// The absolute score above which `zscore` and `seasonal` flag a value as anomalous, 3 by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub threshold: Option<f64>,
+
+        /// The columns that identify separate series, i.e. one series per sensor.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub partition_by: Vec<String>,
+    }
+
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+    #[serde(rename_all = "lowercase")]
+    pub enum AnomalyMethod {
+        /// The z-score over a rolling window of previous values.
+        #[default]
+        Zscore,
+        /// The generalized extreme studentized deviate test.
+        Esd,
+        /// The residual of a seasonal decomposition.
+        Seasonal,
+    }
+}
+
+pub mod cache {
+    use serde::{Deserialize, Serialize};
+
+    /// Caches the results of each distinct scan pushed down to the source of a dataset, i.e. its projection, filters
+    /// and limit, for `ttl`, instead of accelerating the whole dataset.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    pub struct Cache {
+        #[serde(default = "default_true")]
+        pub enabled: bool,
+
+        /// How long the results of a scan are cached, `1m` by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub ttl: Option<String>,
+
+        /// The size of the cached results of the dataset, `128MiB` by default.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_size: Option<String>,
+    }
+
+    const fn default_true() -> bool {
+        true
+    }
+}
@@ -0,0 +1,63 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+/// Serves two models or LLMs of the spicepod under one name, to validate a candidate before it replaces the primary.
+///
+/// Requests for the deployment's name are served by the primary, except for the `traffic` percentage of requests the
+/// candidate serves in `split` mode. In `shadow` mode the candidate runs on every request after the primary has
+/// answered, and only its result is recorded. Both results are recorded in `spice.runtime.deployment_results`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Deployment {
+    pub name: String,
+
+    /// The name of the model or LLM serving requests.
+    pub primary: String,
+
+    /// The name of the model or LLM being validated.
+    pub candidate: String,
+
+    #[serde(default)]
+    pub mode: DeploymentMode,
+
+    /// The percentage of requests served by the candidate, in `split` mode.
+    #[serde(default)]
+    pub traffic: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeploymentMode {
+    /// The primary and the candidate each serve part of the requests.
+    Split,
+    /// The primary serves every request, and the candidate runs on a copy of each request.
+    #[default]
+    Shadow,
+}
+
+impl Deployment {
+    #[must_use]
+    pub fn new(name: String, primary: String, candidate: String) -> Self {
+        Self {
+            name,
+            primary,
+            candidate,
+            mode: DeploymentMode::default(),
+            traffic: 0,
+        }
+    }
+}
@@ -63,6 +63,8 @@ impl Embeddings {
 
 pub enum EmbeddingPrefix {
     OpenAi,
+    HuggingFace,
+    File,
 }
 
 impl TryFrom<&str> for EmbeddingPrefix {
@@ -71,6 +73,10 @@ impl TryFrom<&str> for EmbeddingPrefix {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         if value.starts_with("openai") {
             Ok(EmbeddingPrefix::OpenAi)
+        } else if value.starts_with("huggingface:huggingface.co") {
+            Ok(EmbeddingPrefix::HuggingFace)
+        } else if value.starts_with("file:") {
+            Ok(EmbeddingPrefix::File)
         } else {
             Err("Unknown prefix")
         }
@@ -81,6 +87,8 @@ impl Display for EmbeddingPrefix {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             EmbeddingPrefix::OpenAi => write!(f, "openai"),
+            EmbeddingPrefix::HuggingFace => write!(f, "huggingface:huggingface.co"),
+            EmbeddingPrefix::File => write!(f, "file:"),
         }
     }
 }
@@ -94,6 +102,22 @@ pub enum EmbeddingParams {
         org_id: Option<String>,
         project_id: Option<String>,
     },
+    /// A sentence-transformers style model run locally, i.e. BGE or E5, downloaded from Hugging Face.
+    HuggingfaceParams {
+        model_id: String,
+        revision: Option<String>,
+        hf_token: Option<String>,
+        pooling: Option<String>,
+        normalize: Option<String>,
+    },
+    /// A sentence-transformers style model run locally from files on disk.
+    LocalModelParams {
+        weights_path: String,
+        config_path: String,
+        tokenizer_path: String,
+        pooling: Option<String>,
+        normalize: Option<String>,
+    },
     None,
 }
 
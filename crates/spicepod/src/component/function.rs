@@ -0,0 +1,91 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use super::WithDependsOn;
+
+/// A function callable from SQL.
+///
+/// A function with `sql` is a table function, called as `SELECT * FROM name(arg1, arg2)`, that returns the results of
+/// the SQL with `{{ param }}` replaced by the argument for each parameter. A function with `wasm` is a scalar function
+/// exported by a WASM module.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Function {
+    pub name: String,
+
+    /// The parameters, in the order the arguments are passed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<FunctionParam>,
+
+    /// Inline SQL the function returns the results of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+
+    /// Reference to a SQL file the function returns the results of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql_ref: Option<String>,
+
+    /// Path to the WASM module exporting the scalar function.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm: Option<String>,
+
+    /// The name the WASM module exports the scalar function as, defaulting to `name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export: Option<String>,
+
+    /// The SQL type the scalar function returns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "dependsOn", default)]
+    pub depends_on: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionParam {
+    pub name: String,
+
+    /// The SQL type the argument is cast to, i.e. `VARCHAR`, `BIGINT` or `DOUBLE`.
+    #[serde(rename = "type")]
+    pub data_type: String,
+}
+
+impl Function {
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            params: Vec::default(),
+            sql: None,
+            sql_ref: None,
+            wasm: None,
+            export: None,
+            returns: None,
+            depends_on: Vec::default(),
+        }
+    }
+}
+
+impl WithDependsOn<Function> for Function {
+    fn depends_on(&self, depends_on: &[String]) -> Function {
+        Self {
+            depends_on: depends_on.to_vec(),
+            ..self.clone()
+        }
+    }
+}
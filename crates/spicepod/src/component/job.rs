@@ -0,0 +1,53 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+/// A SQL statement the runtime runs on a schedule, i.e. `INSERT INTO daily_summary SELECT ...` or
+/// `COPY (SELECT ...) TO 's3://bucket/export/' (FORMAT PARQUET)`.
+///
+/// A run that is still going when the job is next due is left to finish, and that run is skipped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Job {
+    pub name: String,
+
+    /// A cron expression of the minute, hour, day of month, month and day of week to run at, in UTC, i.e.
+    /// `0 * * * *` for every hour.
+    pub schedule: String,
+
+    pub sql: String,
+
+    /// The number of times a failed run is retried, defaulting to none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    /// How long to wait before retrying a failed run, i.e. `30s`. Defaults to `10s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<String>,
+}
+
+impl Job {
+    #[must_use]
+    pub fn new(name: String, schedule: String, sql: String) -> Self {
+        Self {
+            name,
+            schedule,
+            sql,
+            retries: None,
+            retry_delay: None,
+        }
+    }
+}
@@ -29,6 +29,46 @@ pub struct Model {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(rename = "datasets", default)]
     pub datasets: Vec<String>,
+
+    /// The secret the model is loaded with. Defaults to the secret named after the model's source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
+    /// Binds a forecasting model to the time series of its dataset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast: Option<Forecast>,
+
+    /// How often to check a model registry for a new version of the model, i.e. `5m`, for a model loaded from an
+    /// MLflow stage or alias, or a Hugging Face branch or tag. The new version is loaded when it changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_check_interval: Option<String>,
+}
+
+/// The columns of the dataset a forecasting model reads, the most recent `lookback` rows of each series in time order.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Forecast {
+    /// The column of the time of each row. Defaults to `ts`.
+    #[serde(default = "default_time_column")]
+    pub time_column: String,
+
+    /// The column of the values forecast.
+    pub value_column: String,
+
+    /// Other columns the model reads at each time, after the value, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub covariates: Vec<String>,
+
+    /// The number of rows read. Defaults to the lookback of the input of the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookback: Option<usize>,
+
+    /// The column identifying each series, to forecast each series separately, i.e. `store_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_key: Option<String>,
+}
+
+fn default_time_column() -> String {
+    "ts".to_string()
 }
 
 impl WithDependsOn<Model> for Model {
@@ -38,6 +78,9 @@ impl WithDependsOn<Model> for Model {
             name: self.name.clone(),
             files: depends_on.to_vec(),
             datasets: depends_on.to_vec(),
+            secret: self.secret.clone(),
+            forecast: self.forecast.clone(),
+            version_check_interval: self.version_check_interval.clone(),
         }
     }
 }
@@ -14,6 +14,8 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -21,6 +23,429 @@ pub struct Runtime {
     #[serde(default)]
     pub results_cache: ResultsCache,
     pub num_of_parallel_loading_at_start_up: Option<usize>,
+
+    #[serde(default)]
+    pub flight: Flight,
+
+    #[serde(default)]
+    pub async_queries: AsyncQueries,
+
+    #[serde(default)]
+    pub auth: Auth,
+
+    #[serde(default)]
+    pub tls: Tls,
+
+    #[serde(default)]
+    pub audit: Audit,
+
+    #[serde(default)]
+    pub readiness: Readiness,
+
+    #[serde(default)]
+    pub hot_reload: HotReload,
+
+    #[serde(default)]
+    pub ai_functions: AiFunctions,
+
+    #[serde(default)]
+    pub inference_log: InferenceLog,
+
+    #[serde(default)]
+    pub otlp_metrics: OtlpMetrics,
+
+    #[serde(default)]
+    pub shutdown: Shutdown,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rate_limits: Vec<RateLimit>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub namespaces: Vec<Namespace>,
+
+    #[serde(default)]
+    pub cluster: Cluster,
+
+    #[serde(default)]
+    pub copy: Copy,
+
+    #[serde(default)]
+    pub masks: Masks,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Flight {
+    /// The maximum number of endpoints returned by `GetFlightInfo` for a query, allowing clients
+    /// to fetch the partitions of a large result in parallel. Unset or `1` returns a single endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_endpoints: Option<usize>,
+}
+
+/// Limits for queries submitted asynchronously with `POST /v1/sql?mode=async`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AsyncQueries {
+    /// The maximum size of the stored results of a single query, i.e. `100MiB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_result_size: Option<String>,
+
+    /// How long the results of a finished query are kept, i.e. `1h`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub results_ttl: Option<String>,
+
+    /// The maximum number of queries whose results are kept at once. Defaults to `1000`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_stored_queries: Option<usize>,
+
+    /// The maximum size of the stored results of all queries together, i.e. `1GiB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_size: Option<String>,
+}
+
+/// Where `COPY ... TO` statements can export query results.
+///
+/// ```yaml
+/// copy:
+///   allowed_targets:
+///     - s3://exports/spice/
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Copy {
+    /// The URL prefixes of the export targets, i.e. `s3://bucket/path/`. Without any, `COPY` is disabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Masks {
+    /// The secret whose `key` keys the HMAC of `hash` masks, so masked values are stable across restarts and
+    /// runtimes. Unset, a random key is generated when the runtime starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Auth {
+    /// Requires requests to the HTTP and Flight endpoints to carry a JWT issued by this OIDC provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oidc: Option<Oidc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Oidc {
+    /// The issuer URL, used to validate the `iss` claim and to discover the JWKS endpoint.
+    pub issuer_url: String,
+
+    /// The expected `aud` claim. Unset skips the audience check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+
+    /// Overrides the JWKS URL advertised by the issuer's discovery document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_url: Option<String>,
+
+    /// How often the signing keys are re-fetched, i.e. `1h`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks_refresh_interval: Option<String>,
+
+    /// The claim that identifies the principal. Defaults to `sub`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal_claim: Option<String>,
+
+    /// The claim that grants a principal admin access: changing datasets, pausing refreshes, running tests and
+    /// exporting or importing accelerations. Without it, no principal is an admin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin: Option<AdminClaim>,
+}
+
+/// Principals are admins when their `claim` is `value`, or is a list that contains `value`.
+///
+/// ```yaml
+/// admin:
+///   claim: groups
+///   value: spice-admins
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdminClaim {
+    pub claim: String,
+    pub value: String,
+}
+
+/// Serves the HTTP, Flight, OpenTelemetry and metrics endpoints over TLS.
+///
+/// The certificate and key are read from PEM files, or from the `certificate` and `key` keys of a secret.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Tls {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_file: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+
+    /// The name of a secret holding the PEM encoded `certificate` and `key`, used instead of the files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
+    /// PEM encoded CA certificates used to verify client certificates. Setting it requires clients to
+    /// present a certificate signed by one of these CAs (mutual TLS).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_file: Option<String>,
+}
+
+/// Controls when `/health/ready` reports the runtime as ready to serve queries.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Readiness {
+    /// The datasets that must have loaded, in addition to the datasets marked as `critical`. Unset waits for every
+    /// dataset unless some are marked as `critical`, an empty list for none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub datasets: Option<Vec<String>>,
+}
+
+/// Limits the calls of the `ai()` SQL function to the LLMs of the spicepod.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AiFunctions {
+    /// The maximum number of calls in flight across all queries. Defaults to `4`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+
+    /// The maximum number of calls of a query, above which the query fails instead of calling the models. A prompt
+    /// repeated in a batch of rows is called once. Defaults to `100`.
+    #[serde(
+        default,
+        alias = "max_calls_per_batch",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_calls_per_query: Option<usize>,
+}
+
+/// Applies changes to the spicepod while the runtime is running.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HotReload {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Validates and logs the changes that would be applied, without applying them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dry_run: false,
+        }
+    }
+}
+
+/// Records who ran which queries, accessed which models and triggered which admin actions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Audit {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub sink: AuditSink,
+
+    /// The file audit events are appended to with the `file` sink. Defaults to `audit.log`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// The OTLP/gRPC endpoint audit events are exported to with the `otlp` sink, i.e. `http://localhost:4317`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Records every call of the models, LLMs and embedding models of the spicepod in the `runtime.inference_log` table,
+/// for evaluation and cost tracking with SQL.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct InferenceLog {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Records the full text of prompts and responses, rather than their SHA-256 hashes.
+    #[serde(default)]
+    pub full_text: bool,
+
+    /// How long calls are kept, i.e. `30d`. Defaults to `7d`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<String>,
+}
+
+/// Pushes the runtime metrics to an OpenTelemetry collector over OTLP/gRPC, so they don't have to be scraped from the
+/// `--metrics` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct OtlpMetrics {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// The OTLP/gRPC endpoint of the collector, i.e. `http://localhost:4317`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    /// Headers sent with each export, i.e. the API key of a hosted collector.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+
+    /// How often the metrics are pushed, i.e. `30s`. Defaults to `60s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
+
+/// How the runtime shuts down on `SIGTERM` or `Ctrl-C`: it stops accepting queries and reports itself as not ready,
+/// then waits for the queries and refreshes in flight before flushing the audit log and metrics.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Shutdown {
+    /// How long queries and refreshes in flight can run before the runtime exits anyway, i.e. `60s`. Defaults to
+    /// `25s`, within the 30 second termination grace period of Kubernetes pods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drain_period: Option<String>,
+}
+
+/// Limits the requests to `/v1/chat/completions` and `/v1/embeddings`, counted separately for each principal and
+/// model. Requests over a limit are rejected with `429 Too Many Requests`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct RateLimit {
+    /// The model limited. Unset limits each model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// The principal limited, the subject of its token. Unset limits each principal, and unauthenticated requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+
+    /// The prompt and completion tokens allowed per UTC day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_day: Option<u64>,
+}
+
+/// Loads another spicepod into its own catalog, so the datasets of several teams can be hosted by one runtime.
+///
+/// The datasets and views of the spicepod are registered in the catalog named after the namespace, i.e. `orders` as
+/// `team_a.public.orders`, and their secrets are read with the namespace as prefix, i.e. `team_a_postgres`. The
+/// other components of the spicepod aren't loaded.
+///
+/// Example:
+/// ```yaml
+/// runtime:
+///   namespaces:
+///     - name: team_a
+///       path: tenants/team_a
+///       principals:
+///         - team-a-service
+///       quotas:
+///         max_accelerated_size: 10GiB
+///         max_rows_scanned: 100000000
+///         max_llm_tokens_per_day: 1000000
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Namespace {
+    pub name: String,
+
+    /// The directory of the spicepod, relative to the root spicepod.
+    pub path: String,
+
+    /// The principals of the namespace, by the subject of their token. Their LLM calls count towards its
+    /// `max_llm_tokens_per_day`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub principals: Vec<String>,
+
+    #[serde(default)]
+    pub quotas: Quotas,
+}
+
+/// Limits the resources the datasets and principals of a namespace use, reported with their usage in `/v1/status`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Quotas {
+    /// The total size of the accelerations of the datasets, i.e. `10GiB`. Refreshes that would exceed it are skipped,
+    /// keeping the accelerated data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_accelerated_size: Option<String>,
+
+    /// The rows a query can read from the datasets' sources and accelerations before it fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows_scanned: Option<u64>,
+
+    /// The prompt and completion tokens the principals of the namespace can use per UTC day, across the chat
+    /// completions endpoint and `ai()`. Calls are rejected once the day's tokens reach it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_llm_tokens_per_day: Option<u64>,
+}
+
+/// Runs the runtime as a member of a cluster of runtimes with the same spicepod, sharing the work of the accelerated
+/// datasets.
+///
+/// Each accelerated dataset is accelerated and refreshed by one member, chosen by consistent hashing of its name, and
+/// the other members query it from that member over Flight. When a member stops responding, its datasets move to the
+/// next healthy member of the hash ring, and move back when it recovers.
+///
+/// Example:
+/// ```yaml
+/// runtime:
+///   cluster:
+///     enabled: true
+///     secret: cluster
+///     tls: true
+///     members:
+///       - name: spice-0
+///         flight_endpoint: spice-0.spice:50051
+///       - name: spice-1
+///         flight_endpoint: spice-1.spice:50051
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Cluster {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// The member this runtime is. Defaults to the `SPICE_CLUSTER_NODE` environment variable, then the hostname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<ClusterMember>,
+
+    /// How often the members are checked for availability, i.e. `5s`. Defaults to `10s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check_interval: Option<String>,
+
+    /// The points of each member on the hash ring. More points spread the datasets more evenly. Defaults to `64`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtual_nodes: Option<usize>,
+
+    /// Reaches the Flight endpoints of the members over TLS. Required, so the token of `secret` isn't sent in
+    /// plaintext.
+    #[serde(default)]
+    pub tls: bool,
+
+    /// The secret whose `token` the members authenticate to each other with. The token is shared by the members only,
+    /// and only allows the scans of the accelerations of the datasets they read from each other.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClusterMember {
+    pub name: String,
+
+    /// The host and port of the Flight endpoint of the member, i.e. `spice-0.spice:50051`.
+    pub flight_endpoint: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSink {
+    /// JSON lines appended to a file.
+    #[default]
+    File,
+    /// The `runtime.audit_log` table.
+    Table,
+    /// OpenTelemetry log records.
+    Otlp,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
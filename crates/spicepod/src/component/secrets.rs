@@ -26,12 +26,34 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Secrets {
     pub store: SpiceSecretStore,
+
+    /// Configures the `vault` store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<Vault>,
+
+    /// Configures the `aws_secrets_manager` and `aws_parameter_store` stores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aws: Option<Aws>,
+
+    /// Configures the `azure_key_vault` store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azure: Option<AzureKeyVault>,
+
+    /// How often the secrets used by loaded components are re-read, i.e. `5m`. Components whose secret changed are
+    /// reloaded with the new value. Unset disables rotation; remote stores are still read no more often than their
+    /// cache TTL allows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_interval: Option<String>,
 }
 
 impl Default for Secrets {
     fn default() -> Self {
         Self {
             store: SpiceSecretStore::File,
+            vault: None,
+            aws: None,
+            azure: None,
+            refresh_interval: None,
         }
     }
 }
@@ -45,4 +67,184 @@ pub enum SpiceSecretStore {
     Keyring,
     #[serde(rename = "aws_secrets_manager")]
     AwsSecretsManager,
+    #[serde(rename = "aws_parameter_store")]
+    AwsParameterStore,
+    Vault,
+    #[serde(rename = "azure_key_vault")]
+    AzureKeyVault,
+}
+
+/// The AWS secret stores, authenticated with the default AWS credential chain, including IAM roles for
+/// EC2 instances and EKS service accounts.
+///
+/// Secrets can also be read from AWS regardless of the configured store, by prefixing their name with
+/// `aws_sm:` for Secrets Manager or `aws_ssm:` for Parameter Store, i.e. `engine_secret: aws_ssm:/prod/duckdb`.
+/// Append `#<field>` to read a single field of a JSON secret, i.e. `aws_sm:prod/databases#postgres`.
+///
+/// Example:
+/// ```yaml
+/// secrets:
+///   store: aws_parameter_store
+///   aws:
+///     region: us-east-1
+///     cache_ttl: 10m
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Aws {
+    /// Defaults to the region of the AWS environment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// How long secrets are cached for before they are read again.
+    #[serde(default = "default_aws_cache_ttl")]
+    pub cache_ttl: String,
+
+    /// Prepended to secret names when `aws_secrets_manager` is the store.
+    #[serde(default = "default_secrets_manager_prefix")]
+    pub secrets_manager_prefix: String,
+
+    /// Prepended to secret names when `aws_parameter_store` is the store.
+    #[serde(default = "default_parameter_store_prefix")]
+    pub parameter_store_prefix: String,
+}
+
+fn default_aws_cache_ttl() -> String {
+    "5m".to_string()
+}
+
+fn default_secrets_manager_prefix() -> String {
+    "spice_secret_".to_string()
+}
+
+fn default_parameter_store_prefix() -> String {
+    "/spice/".to_string()
+}
+
+impl Default for Aws {
+    fn default() -> Self {
+        Self {
+            region: None,
+            cache_ttl: default_aws_cache_ttl(),
+            secrets_manager_prefix: default_secrets_manager_prefix(),
+            parameter_store_prefix: default_parameter_store_prefix(),
+        }
+    }
+}
+
+/// The HashiCorp Vault server that secrets are read from, as `<mount>/data/<path>/<secret name>` in a KV v2 engine.
+///
+/// Credentials are never read from the spicepod: the token is taken from `VAULT_TOKEN`, the `AppRole` credentials
+/// from `VAULT_ROLE_ID` and `VAULT_SECRET_ID`, and the Kubernetes service account token from the pod.
+///
+/// Example:
+/// ```yaml
+/// secrets:
+///   store: vault
+///   vault:
+///     address: https://vault.example.com:8200
+///     path: spice
+///     auth:
+///       method: kubernetes
+///       role: spiced
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Vault {
+    /// Defaults to `VAULT_ADDR`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// The Vault Enterprise namespace. Defaults to `VAULT_NAMESPACE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// The mount of the KV v2 secrets engine.
+    #[serde(default = "default_vault_mount")]
+    pub mount: String,
+
+    /// The path within the mount that secrets are stored under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    #[serde(default)]
+    pub auth: VaultAuth,
+}
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self {
+            address: None,
+            namespace: None,
+            mount: default_vault_mount(),
+            path: None,
+            auth: VaultAuth::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct VaultAuth {
+    #[serde(default)]
+    pub method: VaultAuthMethod,
+
+    /// The mount of the auth method. Defaults to the method's name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount: Option<String>,
+
+    /// The role to log in as, required by the `kubernetes` method.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultAuthMethod {
+    #[default]
+    Token,
+    AppRole,
+    Kubernetes,
+}
+
+/// The Azure Key Vault that secrets are read from.
+///
+/// Key Vault secret names can't contain underscores, so they are replaced with dashes: the `duckdb_engine` secret is
+/// read from `<prefix>duckdb-engine`. Service principal credentials are read from `AZURE_TENANT_ID`, `AZURE_CLIENT_ID`
+/// and `AZURE_CLIENT_SECRET`, never from the spicepod.
+///
+/// Example:
+/// ```yaml
+/// secrets:
+///   store: azure_key_vault
+///   azure:
+///     vault_url: https://my-vault.vault.azure.net
+///     auth: managed_identity
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AzureKeyVault {
+    /// Defaults to `AZURE_KEYVAULT_URL`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault_url: Option<String>,
+
+    #[serde(default)]
+    pub auth: AzureAuthMethod,
+
+    /// The client ID of a user-assigned managed identity. Defaults to `AZURE_CLIENT_ID`, or the system-assigned identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+
+    /// Prepended to secret names.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AzureAuthMethod {
+    /// The managed identity of the VM or AKS node, or the AKS workload identity when `AZURE_FEDERATED_TOKEN_FILE` is set.
+    #[default]
+    ManagedIdentity,
+    ServicePrincipal,
 }
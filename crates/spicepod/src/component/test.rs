@@ -0,0 +1,34 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use serde::{Deserialize, Serialize};
+
+/// A SQL assertion about the data of the spicepod, run against the runtime with `POST /v1/tests/run`, i.e.
+/// `SELECT * FROM orders WHERE amount < 0` to check that no order has a negative amount.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Test {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The test passes when the SQL returns `expected_rows` rows.
+    pub sql: String,
+
+    /// The number of rows the SQL must return, no rows by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_rows: Option<u64>,
+}
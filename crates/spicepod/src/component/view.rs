@@ -33,6 +33,10 @@ pub struct View {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(rename = "dependsOn", default)]
     pub depends_on: Vec<String>,
+
+    /// The namespace the view was loaded into from `runtime.namespaces`, whose catalog its SQL is resolved in.
+    #[serde(skip)]
+    pub namespace: Option<String>,
 }
 
 impl View {
@@ -43,6 +47,7 @@ impl View {
             sql: None,
             sql_ref: None,
             depends_on: Vec::default(),
+            namespace: None,
         }
     }
 }
@@ -54,6 +59,7 @@ impl WithDependsOn<View> for View {
             sql: self.sql.clone(),
             sql_ref: self.sql_ref.clone(),
             depends_on: depends_on.to_vec(),
+            namespace: self.namespace.clone(),
         }
     }
 }
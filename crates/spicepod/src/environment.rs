@@ -0,0 +1,239 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Adapts a spicepod to the environment it is loaded in, so the same definition works across dev, staging and prod.
+//!
+//! The definition is resolved in this order, later steps taking precedence:
+//! 1. `spicepod.yaml` is the base.
+//! 2. With `SPICE_PROFILE` set, i.e. to `prod`, `spicepod.prod.yaml` is merged over it when it exists. Mappings are
+//!    merged key by key, lists of components are merged by `name` (appending the components the base doesn't
+//!    have), and other values are replaced.
+//! 3. `${VAR}` and `${VAR:-default}` in string values are replaced with environment variables. Loading fails if a
+//!    variable without a default isn't set. `$${` is a literal `${`, and placeholders that aren't variable names,
+//!    such as `${principal.region}`, are left as is.
+
+use serde_yaml::{Mapping, Value};
+use snafu::prelude::*;
+
+/// The environment variable that selects the profile overlay.
+pub const PROFILE_ENV_VAR: &str = "SPICE_PROFILE";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Environment variable {name} is not set, set it or give a default with ${{{name}:-default}}"))]
+    MissingVariable { name: String },
+
+    #[snafu(display("Unterminated placeholder in {value}"))]
+    UnterminatedPlaceholder { value: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The profile selected with `SPICE_PROFILE`, if any.
+#[must_use]
+pub fn active_profile() -> Option<String> {
+    std::env::var(PROFILE_ENV_VAR)
+        .ok()
+        .map(|profile| profile.trim().to_string())
+        .filter(|profile| !profile.is_empty())
+}
+
+/// Merges `overlay` over `base`.
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => merge_mappings(base, overlay),
+        (Value::Sequence(base), Value::Sequence(overlay))
+            if base
+                .iter()
+                .chain(&overlay)
+                .all(|item| name_of(item).is_some()) =>
+        {
+            for item in overlay {
+                match base
+                    .iter_mut()
+                    .find(|existing| name_of(existing) == name_of(&item))
+                {
+                    Some(existing) => merge(existing, item),
+                    None => base.push(item),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn merge_mappings(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match base.get_mut(&key) {
+            Some(existing) => merge(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The `name` of a component in a list of components.
+fn name_of(item: &Value) -> Option<&str> {
+    item.as_mapping()?.get("name")?.as_str()
+}
+
+/// Replaces the placeholders in the string values of `value`, reading the variables with `var`.
+pub fn interpolate(value: &mut Value, var: &impl Fn(&str) -> Option<String>) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            if s.contains('$') {
+                *s = interpolate_str(s, var)?;
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate(item, var)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (_, item) in mapping.iter_mut() {
+                interpolate(item, var)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate(&mut tagged.value, var)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(s: &str, var: &impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start..];
+
+        if let Some(escaped) = after.strip_prefix("$${") {
+            result.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(placeholder) = after.strip_prefix("${") else {
+            result.push('$');
+            rest = &after[1..];
+            continue;
+        };
+        let end = placeholder
+            .find('}')
+            .context(UnterminatedPlaceholderSnafu { value: s })?;
+        let expression = &placeholder[..end];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+
+        if is_variable_name(name) {
+            let value = match default {
+                Some(default) => var(name)
+                    .filter(|value| !value.is_empty())
+                    .unwrap_or_else(|| default.to_string()),
+                None => var(name).context(MissingVariableSnafu { name })?,
+            };
+            result.push_str(&value);
+        } else {
+            result.push_str(&after[..end + 3]);
+        }
+        rest = &placeholder[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_profiles_and_interpolates_variables() {
+        let mut base: Value = serde_yaml::from_str(
+            r"
+datasets:
+  - name: orders
+    from: postgres:orders
+    params:
+      pg_host: ${PG_HOST}
+      pg_port: ${PG_PORT:-5432}
+    acceleration:
+      enabled: true
+  - name: users
+    from: postgres:users
+policies: ['region = ${principal.region}', '$${HOME}']
+",
+        )
+        .expect("valid yaml");
+        let overlay: Value = serde_yaml::from_str(
+            r"
+datasets:
+  - name: orders
+    params:
+      pg_host: prod.internal
+  - name: events
+    from: s3://events
+policies: []
+",
+        )
+        .expect("valid yaml");
+
+        merge(&mut base, overlay);
+        interpolate(&mut base, &|name| {
+            (name == "PG_HOST").then(|| "dev.internal".to_string())
+        })
+        .expect("variables are set");
+
+        let expected: Value = serde_yaml::from_str(
+            r"
+datasets:
+  - name: orders
+    from: postgres:orders
+    params:
+      pg_host: prod.internal
+      pg_port: '5432'
+    acceleration:
+      enabled: true
+  - name: users
+    from: postgres:users
+  - name: events
+    from: s3://events
+policies: []
+",
+        )
+        .expect("valid yaml");
+        assert_eq!(base, expected);
+
+        let mut missing = Value::String("${MISSING}".to_string());
+        assert!(interpolate(&mut missing, &|_| None).is_err());
+        let mut policy = Value::String("region = ${principal.region} AND $${HOME}".to_string());
+        interpolate(&mut policy, &|_| None).expect("not variables");
+        assert_eq!(
+            policy,
+            Value::String("region = ${principal.region} AND ${HOME}".to_string())
+        );
+    }
+}
@@ -21,25 +21,45 @@ use component::view::View;
 use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 use std::collections::HashMap;
-use std::{fmt::Debug, path::PathBuf};
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
 
+use component::alert::Alert;
+use component::deployment::Deployment;
 use component::embeddings::Embeddings;
+use component::function::Function;
+use component::job::Job;
 use component::llms::Llm;
 use component::model::Model;
 use component::runtime::Runtime;
 use component::secrets::Secrets;
-use component::{dataset::Dataset, extension::Extension};
+use component::test::Test;
+use component::{dataset::Dataset, extension::Extension, ComponentOrReference};
 
 use spec::{SpicepodDefinition, SpicepodVersion};
 
 pub mod component;
+pub mod environment;
 pub mod reader;
-mod spec;
+pub mod spec;
+pub mod validation;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Unable to parse spicepod.yaml: {source}"))]
     UnableToParseSpicepod { source: serde_yaml::Error },
+    #[snafu(display("Unable to parse spicepod.{profile}.yaml: {source}"))]
+    UnableToParseProfile {
+        source: serde_yaml::Error,
+        profile: String,
+    },
+    #[snafu(display("Unable to interpolate {}: {source}", path.display()))]
+    UnableToInterpolateSpicepod {
+        source: environment::Error,
+        path: PathBuf,
+    },
     #[snafu(display("Unable to resolve spicepod components {}: {source}", path.display()))]
     UnableToResolveSpicepodComponents {
         source: component::Error,
@@ -47,6 +67,13 @@ pub enum Error {
     },
     #[snafu(display("spicepod.yaml not found in {}, run `spice init <name>` to initialize spicepod.yaml", path.display()))]
     SpicepodNotFound { path: PathBuf },
+    #[snafu(display("Unable to serialize spicepod.yaml: {source}"))]
+    UnableToSerializeSpicepod { source: serde_yaml::Error },
+    #[snafu(display("Unable to write {}: {source}", path.display()))]
+    UnableToWriteSpicepod {
+        source: std::io::Error,
+        path: PathBuf,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -65,6 +92,8 @@ pub struct Spicepod {
 
     pub views: Vec<View>,
 
+    pub functions: Vec<Function>,
+
     pub models: Vec<Model>,
 
     pub dependencies: Vec<String>,
@@ -73,6 +102,14 @@ pub struct Spicepod {
 
     pub embeddings: Vec<Embeddings>,
 
+    pub jobs: Vec<Job>,
+
+    pub alerts: Vec<Alert>,
+
+    pub tests: Vec<Test>,
+
+    pub deployments: Vec<Deployment>,
+
     pub runtime: Runtime,
 }
 
@@ -92,8 +129,20 @@ impl Spicepod {
             .open_yaml(&path_str, "spicepod")
             .ok_or_else(|| Error::SpicepodNotFound { path: path.clone() })?;
 
-        let spicepod_definition: SpicepodDefinition =
+        let mut spicepod_value: serde_yaml::Value =
             serde_yaml::from_reader(spicepod_rdr).context(UnableToParseSpicepodSnafu)?;
+        if let Some(profile) = environment::active_profile() {
+            if let Some(overlay_rdr) = fs.open_yaml(&path_str, &format!("spicepod.{profile}")) {
+                let overlay: serde_yaml::Value = serde_yaml::from_reader(overlay_rdr)
+                    .context(UnableToParseProfileSnafu { profile })?;
+                environment::merge(&mut spicepod_value, overlay);
+            }
+        }
+        environment::interpolate(&mut spicepod_value, &|name| std::env::var(name).ok())
+            .context(UnableToInterpolateSpicepodSnafu { path: path.clone() })?;
+
+        let spicepod_definition: SpicepodDefinition =
+            serde_yaml::from_value(spicepod_value).context(UnableToParseSpicepodSnafu)?;
         let resolved_datasets = component::resolve_component_references(
             fs,
             &path,
@@ -106,6 +155,14 @@ impl Spicepod {
             component::resolve_component_references(fs, &path, &spicepod_definition.views, "view")
                 .context(UnableToResolveSpicepodComponentsSnafu { path: path.clone() })?;
 
+        let resolved_functions = component::resolve_component_references(
+            fs,
+            &path,
+            &spicepod_definition.functions,
+            "function",
+        )
+        .context(UnableToResolveSpicepodComponentsSnafu { path: path.clone() })?;
+
         let resolved_models = component::resolve_component_references(
             fs,
             &path,
@@ -130,6 +187,7 @@ impl Spicepod {
             spicepod_definition,
             resolved_datasets,
             resolved_views,
+            resolved_functions,
             resolved_embeddings,
             resolved_models,
             resolved_llms,
@@ -156,6 +214,56 @@ impl Spicepod {
 
         Ok(spicepod_definition)
     }
+
+    /// Adds `dataset` to the spicepod definition in `path`, replacing the dataset of the same name if it is defined inline.
+    pub fn save_dataset(path: impl Into<PathBuf>, dataset: Dataset) -> Result<()> {
+        let path = path.into();
+        let mut definition = Self::load_definition(&path)?;
+
+        let existing = definition.datasets.iter_mut().find(|component| {
+            matches!(component, ComponentOrReference::Component(ds) if ds.name == dataset.name)
+        });
+        match existing {
+            Some(component) => *component = ComponentOrReference::Component(dataset),
+            None => definition
+                .datasets
+                .push(ComponentOrReference::Component(dataset)),
+        }
+
+        write_definition(&path, &definition)
+    }
+
+    /// Removes the inline dataset named `name` from the spicepod definition in `path`.
+    ///
+    /// Returns `false` if the definition has no such dataset.
+    pub fn remove_dataset(path: impl Into<PathBuf>, name: &str) -> Result<bool> {
+        let path = path.into();
+        let mut definition = Self::load_definition(&path)?;
+
+        let len = definition.datasets.len();
+        definition.datasets.retain(
+            |component| !matches!(component, ComponentOrReference::Component(ds) if ds.name == name),
+        );
+        if definition.datasets.len() == len {
+            return Ok(false);
+        }
+
+        write_definition(&path, &definition)?;
+        Ok(true)
+    }
+}
+
+/// Writes `definition` over the spicepod.yaml (or spicepod.yml) in `path`.
+fn write_definition(path: &Path, definition: &SpicepodDefinition) -> Result<()> {
+    let yaml = serde_yaml::to_string(definition).context(UnableToSerializeSpicepodSnafu)?;
+
+    let file_path = ["spicepod.yaml", "spicepod.yml"]
+        .iter()
+        .map(|file_name| path.join(file_name))
+        .find(|file_path| file_path.exists())
+        .unwrap_or_else(|| path.join("spicepod.yaml"));
+
+    std::fs::write(&file_path, yaml).context(UnableToWriteSpicepodSnafu { path: file_path })
 }
 
 #[must_use]
@@ -163,6 +271,7 @@ fn from_definition(
     spicepod_definition: SpicepodDefinition,
     datasets: Vec<Dataset>,
     views: Vec<View>,
+    functions: Vec<Function>,
     embeddings: Vec<Embeddings>,
     models: Vec<Model>,
     llms: Vec<Llm>,
@@ -174,9 +283,14 @@ fn from_definition(
         secrets: spicepod_definition.secrets,
         datasets,
         views,
+        functions,
         models,
         llms,
         embeddings,
+        jobs: spicepod_definition.jobs,
+        alerts: spicepod_definition.alerts,
+        tests: spicepod_definition.tests,
+        deployments: spicepod_definition.deployments,
         dependencies: spicepod_definition.dependencies,
         runtime: spicepod_definition.runtime,
     }
@@ -23,7 +23,8 @@ use crate::component::embeddings::Embeddings;
 use crate::component::runtime::Runtime;
 use crate::component::secrets::Secrets;
 use crate::component::{
-    dataset::Dataset, extension::Extension, llms::Llm, model::Model, view::View,
+    alert::Alert, dataset::Dataset, deployment::Deployment, extension::Extension,
+    function::Function, job::Job, llms::Llm, model::Model, test::Test, view::View,
     ComponentOrReference,
 };
 
@@ -73,6 +74,10 @@ pub struct SpicepodDefinition {
     #[serde(default)]
     pub views: Vec<ComponentOrReference<View>>,
 
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub functions: Vec<ComponentOrReference<Function>>,
+
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
     pub models: Vec<ComponentOrReference<Model>>,
@@ -88,6 +93,26 @@ pub struct SpicepodDefinition {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
     pub llms: Vec<ComponentOrReference<Llm>>,
+
+    /// SQL statements the runtime runs on a schedule.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+
+    /// SQL conditions the runtime notifies about when they start or stop returning rows.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+
+    /// SQL assertions about the data of the spicepod, run with `POST /v1/tests/run`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub tests: Vec<Test>,
+
+    /// Models or LLMs served alongside a candidate under one name, in a traffic split or in shadow mode.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub deployments: Vec<Deployment>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,239 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks a spicepod against its schema without loading any of its components, reporting every
+//! problem found rather than stopping at the first.
+
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
+
+use crate::component::{
+    dataset::Dataset, embeddings::Embeddings, llms::Llm, model::Model, view::View,
+};
+use crate::reader::{self, ReadableYaml};
+use crate::spec::SpicepodDefinition;
+
+/// The component lists of a spicepod, with the basename of their referenced component files.
+const COMPONENT_KINDS: [(&str, &str); 5] = [
+    ("datasets", "dataset"),
+    ("views", "view"),
+    ("models", "model"),
+    ("llms", "llms"),
+    ("embeddings", "embeddings"),
+];
+
+/// A problem with a spicepod, addressed by its path in the spicepod, i.e. `datasets[0].acceleration.engine`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+#[must_use]
+pub fn validate(path: impl Into<PathBuf>) -> Vec<Diagnostic> {
+    validate_from(&reader::StdFileSystem, path)
+}
+
+/// Validates the spicepod in `path` and the component files it references.
+#[must_use]
+pub fn validate_from<T>(fs: &impl ReadableYaml<T>, path: impl Into<PathBuf>) -> Vec<Diagnostic> {
+    let path = path.into();
+    let mut diagnostics = vec![];
+
+    let root = match read_yaml(fs, &path, "spicepod") {
+        Some(Ok(root)) => root,
+        Some(Err(e)) => return vec![Diagnostic::new("", e.to_string())],
+        None => {
+            return vec![Diagnostic::new(
+                "",
+                format!("spicepod.yaml not found in {}", path.display()),
+            )]
+        }
+    };
+
+    // Components are checked one by one below, so their errors aren't reported once per list.
+    let mut definition = root.clone();
+    if let Value::Mapping(mapping) = &mut definition {
+        for (list, _) in COMPONENT_KINDS {
+            mapping.remove(list);
+        }
+    }
+    check::<SpicepodDefinition>(definition, "", &mut diagnostics);
+
+    for (list, basename) in COMPONENT_KINDS {
+        let Some(items) = root.get(list) else {
+            continue;
+        };
+        let Value::Sequence(items) = items else {
+            diagnostics.push(Diagnostic::new(list, "expected a list"));
+            continue;
+        };
+
+        let mut names = HashSet::new();
+        for (index, item) in items.iter().enumerate() {
+            let item_path = format!("{list}[{index}]");
+            let component = match item.get("ref").and_then(Value::as_str) {
+                Some(reference) => match read_yaml(fs, &path.join(reference), basename) {
+                    Some(Ok(component)) => component,
+                    Some(Err(e)) => {
+                        diagnostics
+                            .push(Diagnostic::new(format!("{item_path}.ref"), e.to_string()));
+                        continue;
+                    }
+                    None => {
+                        diagnostics.push(Diagnostic::new(
+                            format!("{item_path}.ref"),
+                            format!("the referenced {basename} {reference} does not exist"),
+                        ));
+                        continue;
+                    }
+                },
+                None => item.clone(),
+            };
+
+            if let Some(name) = component.get("name").and_then(Value::as_str) {
+                if !names.insert(name.to_string()) {
+                    diagnostics.push(Diagnostic::new(
+                        format!("{item_path}.name"),
+                        format!("{name} is defined more than once in {list}"),
+                    ));
+                }
+            }
+
+            match list {
+                "datasets" => check::<Dataset>(component, &item_path, &mut diagnostics),
+                "views" => check::<View>(component, &item_path, &mut diagnostics),
+                "models" => check::<Model>(component, &item_path, &mut diagnostics),
+                "llms" => check::<Llm>(component, &item_path, &mut diagnostics),
+                _ => check::<Embeddings>(component, &item_path, &mut diagnostics),
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Reads `{basename}.yaml` from `path`, returning `None` if it doesn't exist.
+fn read_yaml<T>(
+    fs: &impl ReadableYaml<T>,
+    path: &Path,
+    basename: &str,
+) -> Option<Result<Value, serde_yaml::Error>> {
+    let rdr = fs.open_yaml(path.to_str()?, basename)?;
+    Some(serde_yaml::from_reader(rdr))
+}
+
+/// Deserializes `value` as a `C`, reporting any error and every key that `C` doesn't define.
+fn check<C: DeserializeOwned>(value: Value, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut unknown_keys = vec![];
+    let result: Result<C, _> = serde_ignored::deserialize(value, |ignored| {
+        unknown_keys.push(join_path(path, &format_path(&ignored)));
+    });
+
+    if let Err(e) = result {
+        diagnostics.push(Diagnostic::new(path, e.to_string()));
+    }
+    diagnostics.extend(
+        unknown_keys
+            .into_iter()
+            .map(|key| Diagnostic::new(key, "unknown key")),
+    );
+}
+
+fn format_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}[{index}]", format_path(parent)),
+        serde_ignored::Path::Map { parent, key } => join_path(&format_path(parent), key),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => format_path(parent),
+    }
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else if key.starts_with('[') {
+        format!("{parent}{key}")
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics<C: DeserializeOwned>(yaml: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        check::<C>(
+            serde_yaml::from_str(yaml).expect("valid yaml"),
+            "datasets[0]",
+            &mut diagnostics,
+        );
+        diagnostics
+    }
+
+    #[test]
+    fn reports_unknown_keys_by_path() {
+        let diagnostics = diagnostics::<Dataset>(
+            "from: s3://bucket/path\nname: taxi_trips\nacceleration:\n  enabled: true\n  refresh_interval: 10s\n",
+        );
+
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                "datasets[0].acceleration.refresh_interval",
+                "unknown key"
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        let diagnostics = diagnostics::<Dataset>(
+            "from: s3://bucket/path\nname: taxi_trips\nacceleration:\n  mode: disk\n",
+        );
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "datasets[0]");
+    }
+}
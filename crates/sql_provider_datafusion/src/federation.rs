@@ -1,7 +1,9 @@
 use async_trait::async_trait;
 use datafusion_federation::{FederatedTableProviderAdaptor, FederatedTableSource};
 use datafusion_federation_sql::{SQLExecutor, SQLFederationProvider, SQLTableSource};
-use db_connection_pool::{dbconnection::get_schema, JoinPushDown};
+use db_connection_pool::{
+    dbconnection::get_schema, query_comment::apply_query_comment, JoinPushDown,
+};
 use futures::TryStreamExt;
 use snafu::prelude::*;
 use std::sync::Arc;
@@ -69,7 +71,10 @@ impl<T, P> SQLExecutor for SqlTable<T, P> {
         query: &str,
         schema: SchemaRef,
     ) -> DataFusionResult<SendableRecordBatchStream> {
-        let fut = get_stream(Arc::clone(&self.pool), query.to_string());
+        let fut = get_stream(
+            Arc::clone(&self.pool),
+            apply_query_comment(query.to_string()),
+        );
 
         let stream = futures::stream::once(fut).try_flatten();
         Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
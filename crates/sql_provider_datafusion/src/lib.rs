@@ -19,6 +19,7 @@ limitations under the License.
 use async_trait::async_trait;
 use datafusion::sql::unparser::dialect::Dialect;
 use db_connection_pool::dbconnection::{get_schema, query_arrow};
+use db_connection_pool::query_comment::apply_query_comment;
 use db_connection_pool::DbConnectionPool;
 use expr::Engine;
 use futures::TryStreamExt;
@@ -138,6 +139,30 @@ impl<T, P> SqlTable<T, P> {
         )?))
     }
 
+    /// Scans a sample of the table, where `table_sample` is the sampling clause of the engine that follows the table
+    /// in `FROM`, i.e. `TABLESAMPLE 10 PERCENT (bernoulli)`.
+    pub fn scan_sample(
+        &self,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+        table_sample: String,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let exec = SqlExec::new(
+            projection,
+            &self.schema(),
+            &self.table_reference,
+            Arc::clone(&self.pool),
+            filters,
+            limit,
+            self.engine,
+        )?;
+        Ok(Arc::new(SqlExec {
+            table_sample: Some(table_sample),
+            ..exec
+        }))
+    }
+
     // Return the current memory location of the object as a unique identifier
     fn unique_id(&self) -> usize {
         std::ptr::from_ref(self) as usize
@@ -199,6 +224,7 @@ struct SqlExec<T, P> {
     limit: Option<usize>,
     properties: PlanProperties,
     engine: Option<Engine>,
+    table_sample: Option<String>,
 }
 
 pub fn project_schema_safe(
@@ -242,6 +268,7 @@ impl<T, P> SqlExec<T, P> {
                 ExecutionMode::Bounded,
             ),
             engine,
+            table_sample: None,
         })
     }
 
@@ -277,8 +304,10 @@ impl<T, P> SqlExec<T, P> {
             format!("WHERE {}", filter_expr.join(" AND "))
         };
 
+        let table_sample = self.table_sample.as_deref().unwrap_or_default();
+
         Ok(format!(
-            "SELECT {columns} FROM {table_reference} {where_expr} {limit_expr}",
+            "SELECT {columns} FROM {table_reference} {table_sample} {where_expr} {limit_expr}",
             table_reference = self.table_reference.to_quoted_string(),
         ))
     }
@@ -331,7 +360,7 @@ impl<T: 'static, P: 'static> ExecutionPlan for SqlExec<T, P> {
         _partition: usize,
         _context: Arc<TaskContext>,
     ) -> DataFusionResult<SendableRecordBatchStream> {
-        let sql = self.sql().map_err(to_execution_error)?;
+        let sql = apply_query_comment(self.sql().map_err(to_execution_error)?);
         tracing::debug!("SqlExec sql: {sql}");
 
         let fut = get_stream(Arc::clone(&self.pool), sql);
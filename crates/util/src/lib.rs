@@ -61,8 +61,25 @@ pub async fn shutdown_signal() {
         }
     };
 
+    // Kubernetes stops pods with `SIGTERM`.
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("Failed to listen to SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
     tokio::select! {
         () = ctrl_c => {},
+        () = terminate => {},
     }
 }
 